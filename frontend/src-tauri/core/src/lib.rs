@@ -0,0 +1,134 @@
+//! The platform code behind ShareCode's window stealth: keeping windows out of screen captures,
+//! letting clicks through them, fading them, and hiding them from the taskbar, the Dock and the
+//! window switchers. None of it depends on Tauri, so it can be used with any window that exposes
+//! its native handle.
+//!
+//! Each platform module works on raw handles. [`WindowStealth`] covers what every desktop platform
+//! with per-window capture protection can do, implemented by [`windows::Win32Window`] and
+//! [`macos::CocoaWindow`]; the rest (the taskbar, Spaces, session notifications) stays
+//! platform-specific. Linux can't protect windows at all, and [`linux`] explains why. With the
+//! `mock` feature, [`mock::MockWindow`] stands in for a native window in tests.
+
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "macos")]
+pub mod macos;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+#[cfg(target_os = "windows")]
+pub mod windows;
+
+/// How the window was kept out of screen captures, as reported to the frontend.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureProtection {
+    /// Protection is off.
+    Off,
+    /// `WDA_EXCLUDEFROMCAPTURE`: the window is left out of captures.
+    ExcludeFromCapture,
+    /// `WDA_MONITOR`, on Windows before 10 2004: the window is captured as a black rectangle.
+    Monitor,
+    /// `NSWindowSharingNone` on macOS.
+    SharingNone,
+}
+
+/// A native window that can be hidden from captures, made click-through and faded.
+///
+/// On macOS the calls must be made on the main thread.
+pub trait WindowStealth {
+    /// Keep the window out of screen captures, returning how.
+    fn hide_from_capture(&self) -> Result<CaptureProtection, String>;
+
+    fn show_in_capture(&self) -> Result<(), String>;
+
+    fn capture_protection(&self) -> Result<CaptureProtection, String>;
+
+    /// Let mouse input pass through the window to whatever is under it.
+    fn set_click_through(&self, enabled: bool) -> Result<(), String>;
+
+    fn is_click_through(&self) -> Result<bool, String>;
+
+    /// Opacity of the whole window, from 0.0 to 1.0.
+    fn set_opacity(&self, opacity: f64) -> Result<(), String>;
+
+    fn opacity(&self) -> Result<f64, String>;
+}
+
+/// Put back protection the OS dropped from a window that should have it. Returns how it was
+/// re-applied, or `None` if it was still in place.
+pub fn reapply_protection<W: WindowStealth + ?Sized>(window: &W) -> Result<Option<CaptureProtection>, String> {
+    if window.capture_protection().is_ok_and(|p| p != CaptureProtection::Off) {
+        return Ok(None);
+    }
+    window.hide_from_capture().map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWindow;
+
+    #[test]
+    fn protection_turns_on_and_off() {
+        let window = MockWindow::new();
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::Off));
+        assert_eq!(window.hide_from_capture(), Ok(CaptureProtection::ExcludeFromCapture));
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::ExcludeFromCapture));
+        // Hiding twice changes nothing
+        assert_eq!(window.hide_from_capture(), Ok(CaptureProtection::ExcludeFromCapture));
+        window.show_in_capture().unwrap();
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::Off));
+    }
+
+    #[test]
+    fn protection_falls_back_without_exclusion() {
+        let window = MockWindow::without_exclude();
+        assert_eq!(window.hide_from_capture(), Ok(CaptureProtection::Monitor));
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::Monitor));
+        window.show_in_capture().unwrap();
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::Off));
+    }
+
+    #[test]
+    fn dropped_protection_is_reapplied() {
+        let window = MockWindow::new();
+        window.hide_from_capture().unwrap();
+        assert_eq!(reapply_protection(&window), Ok(None));
+
+        window.drop_protection();
+        assert_eq!(reapply_protection(&window), Ok(Some(CaptureProtection::ExcludeFromCapture)));
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::ExcludeFromCapture));
+    }
+
+    #[test]
+    fn failures_are_reported_and_leave_state_alone() {
+        let window = MockWindow::new();
+        window.hide_from_capture().unwrap();
+        window.drop_protection();
+        window.fail_with(Some("window is gone"));
+        assert_eq!(reapply_protection(&window), Err("window is gone".to_string()));
+        assert!(window.set_click_through(true).is_err());
+
+        window.fail_with(None);
+        assert_eq!(window.capture_protection(), Ok(CaptureProtection::Off));
+        assert_eq!(window.is_click_through(), Ok(false));
+    }
+
+    #[test]
+    fn click_through_and_opacity() {
+        let window = MockWindow::new();
+        assert_eq!(window.is_click_through(), Ok(false));
+        window.set_click_through(true).unwrap();
+        assert_eq!(window.is_click_through(), Ok(true));
+        window.set_click_through(false).unwrap();
+        assert_eq!(window.is_click_through(), Ok(false));
+
+        assert_eq!(window.opacity(), Ok(1.0));
+        window.set_opacity(0.5).unwrap();
+        assert_eq!(window.opacity(), Ok(0.5));
+        window.set_opacity(2.0).unwrap();
+        assert_eq!(window.opacity(), Ok(1.0));
+        window.set_opacity(-1.0).unwrap();
+        assert_eq!(window.opacity(), Ok(0.0));
+    }
+}
@@ -0,0 +1,90 @@
+//! Linux has no per-window capture protection on either display server. This explains why, per
+//! session type, for callers to report; click-through and opacity go through the toolkit.
+//!
+//! A capture-exclusion backend was asked for, using XShape, XComposite redirection or
+//! override-redirect on X11 and portal hints on Wayland. It is declined, not deferred. On X11
+//! those only change how a window is composited and managed; a capturing client still reads its
+//! pixels with `XGetImage` or from the composite pixmap. The Wayland screencast portal has no
+//! hint through which an application can withhold a window, and compositors expose none either.
+//! Shipping any of them would report protection that a screen share then ignores.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionType {
+    X11,
+    Wayland,
+    Unknown,
+}
+
+pub fn session_type() -> SessionType {
+    detect(
+        std::env::var("XDG_SESSION_TYPE").ok().as_deref(),
+        std::env::var_os("WAYLAND_DISPLAY").is_some(),
+        std::env::var_os("DISPLAY").is_some(),
+    )
+}
+
+/// The session type from `XDG_SESSION_TYPE` and whether `WAYLAND_DISPLAY` and `DISPLAY` are set.
+/// A Wayland socket wins over `DISPLAY`, which XWayland sets too.
+fn detect(declared: Option<&str>, wayland_display: bool, display: bool) -> SessionType {
+    if declared == Some("wayland") || wayland_display {
+        SessionType::Wayland
+    } else if declared == Some("x11") || display {
+        SessionType::X11
+    } else {
+        SessionType::Unknown
+    }
+}
+
+/// Neither display server lets a client keep one of its windows out of captures, so this
+/// refuses rather than report a protection that screen sharing would not honour.
+pub fn hide_from_capture() -> Result<(), String> {
+    Err(refusal(session_type()).to_string())
+}
+
+/// Why capture protection is refused in a session of this type.
+pub fn refusal(session: SessionType) -> &'static str {
+    match session {
+        // Any X11 client can read the whole screen with XGetImage or XComposite; shaping
+        // or override-redirect only change how the window is managed, not whether it is read.
+        SessionType::X11 => "Screen capture protection is not possible on X11: any application can read the screen",
+        // The compositor and the screencast portal decide what is captured, and neither
+        // takes a per-window opt-out from the application.
+        SessionType::Wayland => {
+            "Screen capture protection is not available on Wayland: the compositor decides what is captured"
+        }
+        SessionType::Unknown => "Screen capture protection is not supported without a display server",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_display_server() {
+        assert_eq!(detect(Some("wayland"), false, false), SessionType::Wayland);
+        assert_eq!(detect(None, true, false), SessionType::Wayland);
+        // XWayland sets DISPLAY inside a Wayland session
+        assert_eq!(detect(Some("wayland"), true, true), SessionType::Wayland);
+        assert_eq!(detect(None, true, true), SessionType::Wayland);
+        assert_eq!(detect(Some("x11"), false, false), SessionType::X11);
+        assert_eq!(detect(None, false, true), SessionType::X11);
+        assert_eq!(detect(Some("tty"), false, true), SessionType::X11);
+        assert_eq!(detect(Some("tty"), false, false), SessionType::Unknown);
+        assert_eq!(detect(None, false, false), SessionType::Unknown);
+    }
+
+    #[test]
+    fn refusal_names_the_display_server() {
+        assert!(refusal(SessionType::X11).contains("X11"));
+        assert!(refusal(SessionType::X11).contains("any application can read the screen"));
+        assert!(refusal(SessionType::Wayland).contains("Wayland"));
+        assert!(refusal(SessionType::Wayland).contains("compositor"));
+        assert!(refusal(SessionType::Unknown).contains("without a display server"));
+    }
+
+    #[test]
+    fn hide_from_capture_always_refuses() {
+        assert_eq!(hide_from_capture(), Err(refusal(session_type()).to_string()));
+    }
+}
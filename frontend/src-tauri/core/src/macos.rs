@@ -0,0 +1,153 @@
+//! AppKit: window sharing type for capture protection, collection behaviour for Spaces and Mission
+//! Control, and the activation policy for the Dock. The functions take any `NSWindow` (or
+//! `NSApplication`) and are unsafe for that reason; [`CocoaWindow`] wraps one for the
+//! [`WindowStealth`] calls.
+
+use cocoa::appkit::{NSWindow, NSWindowSharingType};
+use cocoa::base::{id, BOOL, NO, YES};
+use cocoa::foundation::NSUInteger;
+use objc::*;
+
+use crate::{CaptureProtection, WindowStealth};
+
+pub unsafe fn hide_from_capture(ns_window: id) {
+    // Prevent window from being captured in screen recordings
+    let _: () = msg_send![ns_window, setSharingType: NSWindowSharingType::NSWindowSharingNone];
+}
+
+pub unsafe fn show_in_capture(ns_window: id) {
+    // Allow window to be captured in screen recordings
+    let _: () = msg_send![ns_window, setSharingType: NSWindowSharingType::NSWindowSharingReadOnly];
+}
+
+pub unsafe fn is_hidden_from_capture(ns_window: id) -> bool {
+    // Read as an integer: an enum receiving a value it has no variant for would be undefined.
+    let sharing: NSUInteger = msg_send![ns_window, sharingType];
+    sharing == NSWindowSharingType::NSWindowSharingNone as NSUInteger
+}
+
+pub unsafe fn set_click_through(ns_window: id, enabled: bool) {
+    // Mouse events go to whatever is underneath the window
+    let _: () = msg_send![ns_window, setIgnoresMouseEvents: if enabled { YES } else { NO }];
+}
+
+pub unsafe fn is_click_through(ns_window: id) -> bool {
+    let ignores: BOOL = msg_send![ns_window, ignoresMouseEvents];
+    ignores == YES
+}
+
+pub unsafe fn set_opacity(ns_window: id, opacity: f64) {
+    let _: () = msg_send![ns_window, setAlphaValue: opacity.clamp(0.0, 1.0)];
+}
+
+pub unsafe fn opacity(ns_window: id) -> f64 {
+    msg_send![ns_window, alphaValue]
+}
+
+// NSWindowCollectionBehavior flags
+const CAN_JOIN_ALL_SPACES: NSUInteger = 1 << 0;
+const MANAGED: NSUInteger = 1 << 2;
+const TRANSIENT: NSUInteger = 1 << 3;
+const STATIONARY: NSUInteger = 1 << 4;
+const IGNORES_CYCLE: NSUInteger = 1 << 6;
+const FULL_SCREEN_AUXILIARY: NSUInteger = 1 << 8;
+const OVERLAY_BEHAVIOR: NSUInteger = CAN_JOIN_ALL_SPACES | STATIONARY | FULL_SCREEN_AUXILIARY;
+
+pub unsafe fn set_all_spaces(ns_window: id, enabled: bool) {
+    // On every Space, left in place by Mission Control and shown over full-screen apps
+    let behavior: NSUInteger = msg_send![ns_window, collectionBehavior];
+    let behavior = if enabled {
+        (behavior & !TRANSIENT) | OVERLAY_BEHAVIOR
+    } else {
+        behavior & !OVERLAY_BEHAVIOR
+    };
+    let _: () = msg_send![ns_window, setCollectionBehavior: behavior];
+}
+
+pub unsafe fn hide_from_expose(ns_window: id) {
+    // Transient windows are left out of Mission Control; at most one of managed, transient and
+    // stationary may be set, so this wins over overlay mode's stationary
+    let behavior: NSUInteger = msg_send![ns_window, collectionBehavior];
+    let behavior = (behavior & !(MANAGED | STATIONARY)) | TRANSIENT | IGNORES_CYCLE;
+    let _: () = msg_send![ns_window, setCollectionBehavior: behavior];
+}
+
+pub unsafe fn show_in_expose(ns_window: id) {
+    let behavior: NSUInteger = msg_send![ns_window, collectionBehavior];
+    let mut behavior = behavior & !(TRANSIENT | IGNORES_CYCLE);
+    // Back in overlay mode, if the window was in it
+    if (behavior & CAN_JOIN_ALL_SPACES) != 0 {
+        behavior |= STATIONARY;
+    }
+    let _: () = msg_send![ns_window, setCollectionBehavior: behavior];
+}
+
+pub unsafe fn is_on_all_spaces(ns_window: id) -> bool {
+    let behavior: NSUInteger = msg_send![ns_window, collectionBehavior];
+    (behavior & CAN_JOIN_ALL_SPACES) != 0
+}
+
+pub unsafe fn hide_from_dock(ns_app: id) {
+    // Hide from dock by setting activation policy to accessory
+    let _: BOOL = msg_send![ns_app, setActivationPolicy: 1]; // NSApplicationActivationPolicyAccessory = 1
+}
+
+pub unsafe fn show_in_dock(ns_app: id) {
+    // Show in dock by setting activation policy to regular
+    let _: BOOL = msg_send![ns_app, setActivationPolicy: 0]; // NSApplicationActivationPolicyRegular = 0
+}
+
+/// A window, by its `NSWindow`.
+#[derive(Clone, Copy)]
+pub struct CocoaWindow(id);
+
+impl CocoaWindow {
+    /// # Safety
+    ///
+    /// `ns_window` must be an `NSWindow` that outlives the returned value, used on the main thread.
+    pub unsafe fn from_raw(ns_window: id) -> Self {
+        Self(ns_window)
+    }
+
+    pub fn ns_window(self) -> id {
+        self.0
+    }
+}
+
+impl WindowStealth for CocoaWindow {
+    fn hide_from_capture(&self) -> Result<CaptureProtection, String> {
+        unsafe { hide_from_capture(self.0) };
+        Ok(CaptureProtection::SharingNone)
+    }
+
+    fn show_in_capture(&self) -> Result<(), String> {
+        unsafe { show_in_capture(self.0) };
+        Ok(())
+    }
+
+    fn capture_protection(&self) -> Result<CaptureProtection, String> {
+        Ok(if unsafe { is_hidden_from_capture(self.0) } {
+            CaptureProtection::SharingNone
+        } else {
+            CaptureProtection::Off
+        })
+    }
+
+    fn set_click_through(&self, enabled: bool) -> Result<(), String> {
+        unsafe { set_click_through(self.0, enabled) };
+        Ok(())
+    }
+
+    fn is_click_through(&self) -> Result<bool, String> {
+        Ok(unsafe { is_click_through(self.0) })
+    }
+
+    fn set_opacity(&self, opacity: f64) -> Result<(), String> {
+        unsafe { set_opacity(self.0, opacity) };
+        Ok(())
+    }
+
+    fn opacity(&self) -> Result<f64, String> {
+        Ok(unsafe { opacity(self.0) })
+    }
+}
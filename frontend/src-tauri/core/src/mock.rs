@@ -0,0 +1,105 @@
+//! A [`WindowStealth`] that keeps its state in memory and behaves as a Win32 window does, for
+//! testing code that drives window stealth without a display.
+
+use std::cell::Cell;
+
+use crate::{CaptureProtection, WindowStealth};
+
+pub struct MockWindow {
+    /// `WDA_EXCLUDEFROMCAPTURE` is available, as on Windows 10 2004 and later.
+    exclude_supported: bool,
+    protection: Cell<CaptureProtection>,
+    click_through: Cell<bool>,
+    opacity: Cell<f64>,
+    /// Every call fails with this while it is set, as when the native window is gone.
+    error: Cell<Option<&'static str>>,
+}
+
+impl Default for MockWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockWindow {
+    pub fn new() -> Self {
+        Self {
+            exclude_supported: true,
+            protection: Cell::new(CaptureProtection::Off),
+            click_through: Cell::new(false),
+            opacity: Cell::new(1.0),
+            error: Cell::new(None),
+        }
+    }
+
+    /// A window on a system that can only capture it as black.
+    pub fn without_exclude() -> Self {
+        Self {
+            exclude_supported: false,
+            ..Self::new()
+        }
+    }
+
+    /// Lose capture protection behind the app's back, as Windows does on some display changes.
+    pub fn drop_protection(&self) {
+        self.protection.set(CaptureProtection::Off);
+    }
+
+    /// Make every call fail with `error`, or succeed again with `None`.
+    pub fn fail_with(&self, error: Option<&'static str>) {
+        self.error.set(error);
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match self.error.get() {
+            Some(error) => Err(error.to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl WindowStealth for MockWindow {
+    fn hide_from_capture(&self) -> Result<CaptureProtection, String> {
+        self.check()?;
+        let protection = if self.exclude_supported {
+            CaptureProtection::ExcludeFromCapture
+        } else {
+            CaptureProtection::Monitor
+        };
+        self.protection.set(protection);
+        Ok(protection)
+    }
+
+    fn show_in_capture(&self) -> Result<(), String> {
+        self.check()?;
+        self.protection.set(CaptureProtection::Off);
+        Ok(())
+    }
+
+    fn capture_protection(&self) -> Result<CaptureProtection, String> {
+        self.check()?;
+        Ok(self.protection.get())
+    }
+
+    fn set_click_through(&self, enabled: bool) -> Result<(), String> {
+        self.check()?;
+        self.click_through.set(enabled);
+        Ok(())
+    }
+
+    fn is_click_through(&self) -> Result<bool, String> {
+        self.check()?;
+        Ok(self.click_through.get())
+    }
+
+    fn set_opacity(&self, opacity: f64) -> Result<(), String> {
+        self.check()?;
+        self.opacity.set(opacity.clamp(0.0, 1.0));
+        Ok(())
+    }
+
+    fn opacity(&self) -> Result<f64, String> {
+        self.check()?;
+        Ok(self.opacity.get())
+    }
+}
@@ -0,0 +1,265 @@
+//! Win32: display affinity for capture protection, extended window styles for click-through,
+//! opacity and the taskbar, and session notifications. The functions take any `HWND` and are
+//! unsafe for that reason; [`Win32Window`] wraps one for the [`WindowStealth`] calls.
+
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use windows::core::w;
+use windows::Win32::Foundation::{COLORREF, HWND, LPARAM, LRESULT, WPARAM};
+use windows::Win32::System::RemoteDesktop::{WTSRegisterSessionNotification, NOTIFY_FOR_THIS_SESSION};
+use windows::Win32::UI::Shell::{DefSubclassProc, SetWindowSubclass};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateWindowExW, GetLayeredWindowAttributes, GetWindowDisplayAffinity, GetWindowLongPtrW, IsWindowVisible,
+    SetLayeredWindowAttributes, SetWindowDisplayAffinity, SetWindowLongPtrW, SetWindowPos, ShowWindow,
+    GWLP_HWNDPARENT, GWL_EXSTYLE, HWND_TOPMOST, LAYERED_WINDOW_ATTRIBUTES_FLAGS, LWA_ALPHA, SWP_NOACTIVATE,
+    SWP_NOMOVE, SWP_NOSIZE, SW_HIDE, SW_SHOWNA, WDA_EXCLUDEFROMCAPTURE, WDA_MONITOR, WDA_NONE, WINDOW_EX_STYLE,
+    WINDOW_STYLE, WM_ENDSESSION, WM_WTSSESSION_CHANGE, WS_EX_APPWINDOW, WS_EX_LAYERED, WS_EX_TOOLWINDOW,
+    WS_EX_TOPMOST, WS_EX_TRANSPARENT, WTS_SESSION_LOCK, WTS_SESSION_UNLOCK,
+};
+
+use crate::{CaptureProtection, WindowStealth};
+
+pub unsafe fn hide_from_capture(hwnd: HWND) -> Result<CaptureProtection, String> {
+    // Leaves the window out of captures entirely (Windows 10 2004+)
+    if SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE).is_ok() {
+        return Ok(CaptureProtection::ExcludeFromCapture);
+    }
+    // Older builds reject that flag; there the window can only be captured as black
+    SetWindowDisplayAffinity(hwnd, WDA_MONITOR).map_err(|e| format!("Failed to exclude from capture: {}", e))?;
+    Ok(CaptureProtection::Monitor)
+}
+
+pub unsafe fn show_in_capture(hwnd: HWND) -> Result<(), String> {
+    SetWindowDisplayAffinity(hwnd, WDA_NONE).map_err(|e| format!("Failed to include in capture: {}", e))
+}
+
+pub unsafe fn capture_protection(hwnd: HWND) -> Result<CaptureProtection, String> {
+    let mut affinity = 0u32;
+    GetWindowDisplayAffinity(hwnd, &mut affinity)
+        .map_err(|e| format!("Failed to read display affinity: {}", e))?;
+    Ok(match affinity {
+        a if a == WDA_EXCLUDEFROMCAPTURE.0 => CaptureProtection::ExcludeFromCapture,
+        a if a == WDA_MONITOR.0 => CaptureProtection::Monitor,
+        _ => CaptureProtection::Off,
+    })
+}
+
+pub unsafe fn hide_from_taskbar(hwnd: HWND) -> Result<(), String> {
+    let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+
+    // Remove WS_EX_APPWINDOW and add WS_EX_TOOLWINDOW to hide from taskbar
+    ex_style &= !(WS_EX_APPWINDOW.0 as isize);
+    ex_style |= WS_EX_TOOLWINDOW.0 as isize;
+
+    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+    Ok(())
+}
+
+pub unsafe fn show_in_taskbar(hwnd: HWND) -> Result<(), String> {
+    let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+
+    // Add WS_EX_APPWINDOW and remove WS_EX_TOOLWINDOW to show in taskbar
+    ex_style |= WS_EX_APPWINDOW.0 as isize;
+    ex_style &= !(WS_EX_TOOLWINDOW.0 as isize);
+
+    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+    Ok(())
+}
+
+/// A window that is never shown, to own windows kept out of the switcher.
+static SWITCHER_OWNER: AtomicIsize = AtomicIsize::new(0);
+
+unsafe fn switcher_owner() -> Result<HWND, String> {
+    let owner = SWITCHER_OWNER.load(Ordering::Relaxed);
+    if owner != 0 {
+        return Ok(HWND(owner as _));
+    }
+    let owner = CreateWindowExW(
+        WINDOW_EX_STYLE(0),
+        w!("STATIC"),
+        w!(""),
+        WINDOW_STYLE(0),
+        0,
+        0,
+        0,
+        0,
+        None,
+        None,
+        None,
+        None,
+    )
+    .map_err(|e| format!("Failed to create the owner window: {}", e))?;
+    SWITCHER_OWNER.store(owner.0 as isize, Ordering::Relaxed);
+    Ok(owner)
+}
+
+/// The shell only reads a window's styles and owner when it is shown.
+unsafe fn reshow(hwnd: HWND) {
+    if IsWindowVisible(hwnd).as_bool() {
+        let _ = ShowWindow(hwnd, SW_HIDE);
+        let _ = ShowWindow(hwnd, SW_SHOWNA);
+    }
+}
+
+pub unsafe fn hide_from_switcher(hwnd: HWND) -> Result<(), String> {
+    hide_from_taskbar(hwnd)?;
+    // The window frame rewrites the extended style on some state changes and can drop
+    // WS_EX_TOOLWINDOW; a window owned by a hidden one stays out of Alt+Tab and Task View anyway
+    SetWindowLongPtrW(hwnd, GWLP_HWNDPARENT, switcher_owner()?.0 as isize);
+    reshow(hwnd);
+    Ok(())
+}
+
+pub unsafe fn show_in_switcher(hwnd: HWND, taskbar: bool) -> Result<(), String> {
+    SetWindowLongPtrW(hwnd, GWLP_HWNDPARENT, 0);
+    if taskbar {
+        show_in_taskbar(hwnd)?;
+    }
+    reshow(hwnd);
+    Ok(())
+}
+
+pub unsafe fn set_opacity(hwnd: HWND, opacity: f64) -> Result<(), String> {
+    // Per-window alpha only applies to layered windows
+    let ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style | WS_EX_LAYERED.0 as isize);
+
+    let alpha = (opacity.clamp(0.0, 1.0) * 255.0).round() as u8;
+    SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha, LWA_ALPHA)
+        .map_err(|e| format!("Failed to set window opacity: {}", e))
+}
+
+pub unsafe fn opacity(hwnd: HWND) -> Result<f64, String> {
+    if (GetWindowLongPtrW(hwnd, GWL_EXSTYLE) & WS_EX_LAYERED.0 as isize) == 0 {
+        return Ok(1.0);
+    }
+    let mut alpha = 255u8;
+    let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS(0);
+    GetLayeredWindowAttributes(hwnd, None, Some(&mut alpha), Some(&mut flags))
+        .map_err(|e| format!("Failed to read window opacity: {}", e))?;
+    // Layered by a color key only, the window is drawn opaque
+    Ok(if flags.contains(LWA_ALPHA) { alpha as f64 / 255.0 } else { 1.0 })
+}
+
+pub unsafe fn set_click_through(hwnd: HWND, enabled: bool) -> Result<(), String> {
+    let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+
+    if enabled {
+        // Clicks only pass through layered windows, and those are not drawn until given an alpha
+        if (ex_style & WS_EX_LAYERED.0 as isize) == 0 {
+            ex_style |= WS_EX_LAYERED.0 as isize;
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+            SetLayeredWindowAttributes(hwnd, COLORREF(0), 255, LWA_ALPHA)
+                .map_err(|e| format!("Failed to make the window layered: {}", e))?;
+        }
+        ex_style |= WS_EX_TRANSPARENT.0 as isize;
+    } else {
+        // Stays layered, so any opacity that was set is kept
+        ex_style &= !(WS_EX_TRANSPARENT.0 as isize);
+    }
+
+    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+    Ok(())
+}
+
+pub unsafe fn is_click_through(hwnd: HWND) -> bool {
+    (GetWindowLongPtrW(hwnd, GWL_EXSTYLE) & WS_EX_TRANSPARENT.0 as isize) != 0
+}
+
+pub unsafe fn is_topmost(hwnd: HWND) -> bool {
+    (GetWindowLongPtrW(hwnd, GWL_EXSTYLE) & WS_EX_TOPMOST.0 as isize) != 0
+}
+
+pub unsafe fn set_topmost(hwnd: HWND) -> Result<(), String> {
+    // Brings the window back above others that went topmost after it, without focusing it
+    SetWindowPos(hwnd, HWND_TOPMOST, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE)
+        .map_err(|e| format!("Failed to keep the window on top: {}", e))
+}
+
+/// What `watch_session` reports about the user's session.
+pub enum SessionEvent {
+    /// The user is logging off or Windows is shutting down. The process ends once the handler
+    /// returns, without the event loop ever exiting.
+    Ending,
+    Locked,
+    Unlocked,
+}
+
+type SessionHandler = Box<dyn Fn(SessionEvent)>;
+
+/// Call `handler` on the window's thread whenever the session ends, locks or unlocks.
+pub unsafe fn watch_session(hwnd: HWND, handler: impl Fn(SessionEvent) + 'static) -> Result<(), String> {
+    // Freed with the process; the subclass lives as long as the window
+    let handler = Box::into_raw(Box::new(Box::new(handler) as SessionHandler));
+    if !SetWindowSubclass(hwnd, Some(session_proc), 1, handler as usize).as_bool() {
+        drop(Box::from_raw(handler));
+        return Err("Failed to watch for the session ending".to_string());
+    }
+    WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION)
+        .map_err(|e| format!("Failed to watch for the screen locking: {}", e))
+}
+
+unsafe extern "system" fn session_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+    _id: usize,
+    handler: usize,
+) -> LRESULT {
+    let handler = &*(handler as *const SessionHandler);
+    match msg {
+        WM_ENDSESSION if wparam.0 != 0 => handler(SessionEvent::Ending),
+        WM_WTSSESSION_CHANGE if wparam.0 == WTS_SESSION_LOCK as usize => handler(SessionEvent::Locked),
+        WM_WTSSESSION_CHANGE if wparam.0 == WTS_SESSION_UNLOCK as usize => handler(SessionEvent::Unlocked),
+        _ => {}
+    }
+    DefSubclassProc(hwnd, msg, wparam, lparam)
+}
+
+/// A top-level window, by its `HWND`.
+#[derive(Clone, Copy)]
+pub struct Win32Window(HWND);
+
+impl Win32Window {
+    /// # Safety
+    ///
+    /// `hwnd` must be a window of this process that outlives the returned value.
+    pub unsafe fn from_raw(hwnd: HWND) -> Self {
+        Self(hwnd)
+    }
+
+    pub fn hwnd(self) -> HWND {
+        self.0
+    }
+}
+
+impl WindowStealth for Win32Window {
+    fn hide_from_capture(&self) -> Result<CaptureProtection, String> {
+        unsafe { hide_from_capture(self.0) }
+    }
+
+    fn show_in_capture(&self) -> Result<(), String> {
+        unsafe { show_in_capture(self.0) }
+    }
+
+    fn capture_protection(&self) -> Result<CaptureProtection, String> {
+        unsafe { capture_protection(self.0) }
+    }
+
+    fn set_click_through(&self, enabled: bool) -> Result<(), String> {
+        unsafe { set_click_through(self.0, enabled) }
+    }
+
+    fn is_click_through(&self) -> Result<bool, String> {
+        Ok(unsafe { is_click_through(self.0) })
+    }
+
+    fn set_opacity(&self, opacity: f64) -> Result<(), String> {
+        unsafe { set_opacity(self.0, opacity) }
+    }
+
+    fn opacity(&self) -> Result<f64, String> {
+        unsafe { opacity(self.0) }
+    }
+}
@@ -0,0 +1,131 @@
+//! The system's high-contrast and reduce-motion settings, mirrored so the backend renderers can
+//! follow them and the UI hears when they change (`system-preferences-changed`).
+//!
+//! Platforms notify about these in different ways, some of them not at all, so the settings are
+//! polled: NSWorkspace on macOS, SystemParametersInfo on Windows and GNOME's gsettings elsewhere.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+static HIGH_CONTRAST: AtomicBool = AtomicBool::new(false);
+static REDUCE_MOTION: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemPreferences {
+    pub high_contrast: bool,
+    pub reduce_motion: bool,
+}
+
+/// The preferences as of the last poll.
+pub fn current() -> SystemPreferences {
+    SystemPreferences {
+        high_contrast: HIGH_CONTRAST.load(Ordering::Relaxed),
+        reduce_motion: REDUCE_MOTION.load(Ordering::Relaxed),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect(app: &AppHandle) -> Option<SystemPreferences> {
+    use objc::runtime::{Object, BOOL, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    // AppKit refreshes these from the main run loop, so read them there.
+    let (tx, rx) = std::sync::mpsc::channel();
+    app.run_on_main_thread(move || unsafe {
+        let workspace: *mut Object = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let contrast: BOOL = msg_send![workspace, accessibilityDisplayShouldIncreaseContrast];
+        let motion: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+        tx.send(SystemPreferences {
+            high_contrast: contrast == YES,
+            reduce_motion: motion == YES,
+        })
+        .ok();
+    })
+    .ok()?;
+    rx.recv_timeout(POLL_INTERVAL).ok()
+}
+
+#[cfg(target_os = "windows")]
+fn detect(_app: &AppHandle) -> Option<SystemPreferences> {
+    use std::ffi::c_void;
+
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::UI::Accessibility::{HCF_HIGHCONTRASTON, HIGHCONTRASTW};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    let mut contrast = HIGHCONTRASTW {
+        cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+        ..Default::default()
+    };
+    let mut animation = BOOL(1);
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            contrast.cbSize,
+            Some(&mut contrast as *mut _ as *mut c_void),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .ok()?;
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            Some(&mut animation as *mut _ as *mut c_void),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+        .ok()?;
+    }
+    Some(SystemPreferences {
+        high_contrast: contrast.dwFlags.0 & HCF_HIGHCONTRASTON.0 != 0,
+        reduce_motion: !animation.as_bool(),
+    })
+}
+
+#[cfg(all(desktop, not(any(target_os = "macos", target_os = "windows"))))]
+fn detect(_app: &AppHandle) -> Option<SystemPreferences> {
+    let get = |schema: &str, key: &str| -> Option<bool> {
+        let output = std::process::Command::new("gsettings").args(["get", schema, key]).output().ok()?;
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    };
+    Some(SystemPreferences {
+        high_contrast: get("org.gnome.desktop.a11y.interface", "high-contrast").unwrap_or(false),
+        reduce_motion: !get("org.gnome.desktop.interface", "enable-animations").unwrap_or(true),
+    })
+}
+
+/// Mobile webviews apply these preferences themselves.
+#[cfg(mobile)]
+fn detect(_app: &AppHandle) -> Option<SystemPreferences> {
+    None
+}
+
+/// Poll the system settings for the life of the app. Called once from setup.
+pub fn spawn_watcher(app: AppHandle) {
+    thread::spawn(move || loop {
+        if let Some(preferences) = detect(&app).filter(|&p| p != current()) {
+            HIGH_CONTRAST.store(preferences.high_contrast, Ordering::Relaxed);
+            REDUCE_MOTION.store(preferences.reduce_motion, Ordering::Relaxed);
+            if let Err(e) = app.emit("system-preferences-changed", preferences) {
+                log::warn!("Failed to emit system preferences: {}", e);
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
+
+#[tauri::command]
+pub fn get_system_preferences() -> SystemPreferences {
+    current()
+}
@@ -0,0 +1,137 @@
+//! ASCII armor for snippets: deflated, checksummed and base64-wrapped between BEGIN/END lines,
+//! so a snippet survives mail and chat systems that mangle whitespace, long lines or non-ASCII text.
+//!
+//! ```text
+//! -----BEGIN SHARECODE SNIPPET-----
+//! Version: 1
+//!
+//! <base64, 64 columns>
+//! =<base64 checksum>
+//! -----END SHARECODE SNIPPET-----
+//! ```
+
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::snippets::{PortableSnippet, SnippetLibrary};
+
+const BEGIN: &str = "-----BEGIN SHARECODE SNIPPET-----";
+const END: &str = "-----END SHARECODE SNIPPET-----";
+const VERSION: &str = "1";
+const LINE_WIDTH: usize = 64;
+const CHECKSUM_LEN: usize = 4;
+/// Limit on the inflated payload, so a tiny encoded snippet cannot expand without bound.
+const MAX_PAYLOAD: u64 = 1024 * 1024;
+
+/// Serialize and deflate a snippet; also used by the image encoder.
+pub fn compress(snippet: &PortableSnippet) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(snippet).map_err(|e| e.to_string())?;
+    if json.len() as u64 > MAX_PAYLOAD {
+        return Err(format!("Snippets over {} KiB are too large to encode", MAX_PAYLOAD / 1024));
+    }
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&json).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// Inflate and parse what [`compress`] produced, refusing to inflate past the size limit.
+pub fn decompress(compressed: &[u8]) -> Result<PortableSnippet, String> {
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(MAX_PAYLOAD + 1)
+        .read_to_end(&mut json)
+        .map_err(|_| "Encoded snippet is corrupted".to_string())?;
+    if json.len() as u64 > MAX_PAYLOAD {
+        return Err("Encoded snippet is too large".to_string());
+    }
+    serde_json::from_slice(&json).map_err(|e| format!("Encoded snippet is invalid: {}", e))
+}
+
+/// Wrap a snippet in armor.
+pub fn armor(snippet: &PortableSnippet) -> Result<String, String> {
+    let compressed = compress(snippet)?;
+
+    let body = STANDARD.encode(&compressed);
+    let mut out = format!("{}\nVersion: {}\n\n", BEGIN, VERSION);
+    for line in body.as_bytes().chunks(LINE_WIDTH) {
+        // Base64 is ASCII, so every chunk is valid UTF-8.
+        out.push_str(std::str::from_utf8(line).unwrap_or_default());
+        out.push('\n');
+    }
+    out.push('=');
+    out.push_str(&STANDARD.encode(&Sha256::digest(&compressed)[..CHECKSUM_LEN]));
+    out.push('\n');
+    out.push_str(END);
+    out.push('\n');
+    Ok(out)
+}
+
+/// Find the first armored block in `text` and decode it. `Ok(None)` means there is none.
+///
+/// Tolerates what mail clients do to pasted text: quote prefixes (`> `), CRLF line breaks,
+/// indentation and trailing spaces.
+pub fn dearmor(text: &str) -> Result<Option<PortableSnippet>, String> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim().trim_start_matches(|c: char| c == '>' || c.is_whitespace()))
+        .collect();
+    let Some(begin) = lines.iter().position(|line| *line == BEGIN) else {
+        return Ok(None);
+    };
+    let end = lines[begin..]
+        .iter()
+        .position(|line| *line == END)
+        .map(|i| begin + i)
+        .ok_or("Armored snippet is missing its END line")?;
+    let block = &lines[begin + 1..end];
+
+    // Headers are the leading `Key: value` lines; base64 never contains a colon, so this still
+    // works if the blank separator line was lost on the way.
+    let headers_end = block.iter().take_while(|line| line.contains(':')).count();
+    for header in &block[..headers_end] {
+        if let Some(("Version", version)) = header.split_once(':').map(|(k, v)| (k.trim(), v.trim())) {
+            if version != VERSION {
+                return Err(format!("Unsupported armor version {}", version));
+            }
+        }
+    }
+    let mut body = String::new();
+    let mut checksum = None;
+    for line in &block[headers_end..] {
+        match line.strip_prefix('=') {
+            Some(sum) => checksum = Some(sum),
+            None => body.push_str(line),
+        }
+    }
+    let checksum = checksum.ok_or("Armored snippet has no checksum")?;
+
+    let compressed = STANDARD
+        .decode(body.as_bytes())
+        .map_err(|_| "Armored snippet is corrupted (invalid base64)".to_string())?;
+    let expected = STANDARD
+        .decode(checksum)
+        .map_err(|_| "Armored snippet has an invalid checksum".to_string())?;
+    if Sha256::digest(&compressed)[..CHECKSUM_LEN] != expected[..] {
+        return Err("Armored snippet failed its checksum (was it edited in transit?)".to_string());
+    }
+    decompress(&compressed).map(Some)
+}
+
+#[tauri::command]
+pub fn armor_snippet(app: AppHandle, library: State<'_, SnippetLibrary>, id: String) -> Result<String, String> {
+    armor(&PortableSnippet::from(library.get(&app, &id)?))
+}
+
+/// Decode armor found anywhere in pasted text. Returns `None` when the text holds no armor, so
+/// the webview can call this on every paste and fall through to a normal paste.
+#[tauri::command]
+pub fn dearmor_snippet(text: String) -> Result<Option<PortableSnippet>, String> {
+    dearmor(&text)
+}
@@ -0,0 +1,308 @@
+//! The versioned container ShareCode writes its own files in, and `validate_file`, which
+//! identifies and checks any file ShareCode produces.
+//!
+//! Session snapshots and offline bundles are containers. Kind 2 is set aside for timeline
+//! recordings; rendered clips and replays stay GIF, WebM, SVG or HTML so that any player opens
+//! them. All integers are little-endian:
+//!
+//! | Offset  | Size | Field                                                          |
+//! |---------|------|----------------------------------------------------------------|
+//! | 0       | 4    | Magic `SCAF`                                                   |
+//! | 4       | 1    | Major version, currently 1                                     |
+//! | 5       | 1    | Minor version, currently 0                                     |
+//! | 6       | 2    | Kind: 1 session snapshot, 2 recording, 3 offline bundle        |
+//! | 8       | 4    | Header length, which is where the body starts                  |
+//! | 12      | 8    | Body length                                                    |
+//! | 20      | 32   | BLAKE3 hash of the body                                        |
+//! | 52      | 4    | Metadata length `n`                                            |
+//! | 56      | n    | Metadata: a JSON object of strings, keys sorted                |
+//! | 56 + n  | 32   | BLAKE3 hash of bytes `0..56 + n`                               |
+//! | 88 + n  |      | Fields added by later minor versions                           |
+//! | header  |      | Body                                                           |
+//!
+//! Compatibility rules:
+//!
+//! - A reader refuses a major version it does not know. Majors change only when existing
+//!   fields change meaning.
+//! - Minor versions only add: new fields between the header hash and the body (reached by the
+//!   header length, so older readers skip them), new metadata keys, and new kinds. Readers
+//!   ignore metadata keys and skip fields they do not know. They report unknown kinds instead
+//!   of failing on them.
+//! - The container adds nothing time- or machine-dependent, so the same kind, metadata and
+//!   body always give the same bytes.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use serde::Serialize;
+
+const MAGIC: &[u8; 4] = b"SCAF";
+const MAJOR: u8 = 1;
+const MINOR: u8 = 0;
+const HASH_LEN: usize = 32;
+const FIXED_LEN: usize = 56;
+/// Containers are read whole; nothing ShareCode writes comes near this.
+const MAX_ARTIFACT_BYTES: u64 = 512 * 1024 * 1024;
+/// Enough of a non-container file to tell what it is.
+const SNIFF_LEN: usize = 64;
+
+// Other files ShareCode writes, recognized by their signatures.
+const BACKUP_MAGIC: &[u8; 4] = b"SCBK";
+const LEGACY_BUNDLE_MAGIC: &[u8; 4] = b"SCOB";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    SessionSnapshot,
+    /// Reserved for timeline recordings; nothing writes them yet.
+    #[allow(dead_code)]
+    Recording,
+    OfflineBundle,
+}
+
+impl Kind {
+    fn code(self) -> u16 {
+        match self {
+            Kind::SessionSnapshot => 1,
+            Kind::Recording => 2,
+            Kind::OfflineBundle => 3,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::SessionSnapshot => "sessionSnapshot",
+            Kind::Recording => "recording",
+            Kind::OfflineBundle => "offlineBundle",
+        }
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        match code {
+            1 => Some(Kind::SessionSnapshot),
+            2 => Some(Kind::Recording),
+            3 => Some(Kind::OfflineBundle),
+            _ => None,
+        }
+    }
+}
+
+pub type Metadata = BTreeMap<String, String>;
+
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(kind: Kind, metadata: &Metadata, body: &[u8]) -> Result<Vec<u8>, String> {
+    let metadata = serde_json::to_vec(metadata).map_err(|e| e.to_string())?;
+    let metadata_len = u32::try_from(metadata.len()).map_err(|_| "Artifact metadata is too large".to_string())?;
+    let header_len = FIXED_LEN + metadata.len() + HASH_LEN;
+    let mut out = Vec::with_capacity(header_len + body.len());
+    out.extend_from_slice(MAGIC);
+    out.push(MAJOR);
+    out.push(MINOR);
+    out.extend_from_slice(&kind.code().to_le_bytes());
+    out.extend_from_slice(&(header_len as u32).to_le_bytes());
+    out.extend_from_slice(&(body.len() as u64).to_le_bytes());
+    out.extend_from_slice(blake3::hash(body).as_bytes());
+    out.extend_from_slice(&metadata_len.to_le_bytes());
+    out.extend_from_slice(&metadata);
+    let header_hash = blake3::hash(&out);
+    out.extend_from_slice(header_hash.as_bytes());
+    out.extend_from_slice(body);
+    Ok(out)
+}
+
+/// The container's fields, before the kind is known to this version.
+struct Parsed<'a> {
+    major: u8,
+    minor: u8,
+    kind: u16,
+    metadata: Metadata,
+    body: &'a [u8],
+    body_hash: [u8; HASH_LEN],
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn parse(bytes: &[u8]) -> Result<Parsed<'_>, String> {
+    if bytes.len() < FIXED_LEN || !is_container(bytes) {
+        return Err("Not a ShareCode artifact".to_string());
+    }
+    let (major, minor) = (bytes[4], bytes[5]);
+    if major != MAJOR {
+        return Err(format!("Unsupported artifact format version {}.{}", major, minor));
+    }
+    let kind = u16::from_le_bytes([bytes[6], bytes[7]]);
+    let header_len = u32_at(bytes, 8) as usize;
+    let body_len = u64::from_le_bytes(bytes[12..20].try_into().unwrap());
+    let body_hash: [u8; HASH_LEN] = bytes[20..52].try_into().unwrap();
+    let metadata_end = FIXED_LEN + u32_at(bytes, 52) as usize;
+    let hashed = metadata_end + HASH_LEN;
+    if hashed > bytes.len() || header_len < hashed || header_len > bytes.len() {
+        return Err("Artifact header is truncated".to_string());
+    }
+    if blake3::hash(&bytes[..metadata_end]).as_bytes() != &bytes[metadata_end..hashed] {
+        return Err("Artifact header failed its integrity check".to_string());
+    }
+    let metadata: Metadata = serde_json::from_slice(&bytes[FIXED_LEN..metadata_end])
+        .map_err(|e| format!("Artifact metadata is invalid: {}", e))?;
+    let body = &bytes[header_len..];
+    if body.len() as u64 != body_len {
+        return Err(format!("Artifact body is {} bytes, expected {}", body.len(), body_len));
+    }
+    Ok(Parsed {
+        major,
+        minor,
+        kind,
+        metadata,
+        body,
+        body_hash,
+    })
+}
+
+/// The body of a container of `kind`, after checking both hashes.
+pub fn decode(bytes: &[u8], kind: Kind) -> Result<&[u8], String> {
+    let parsed = parse(bytes)?;
+    if parsed.kind != kind.code() {
+        return Err("The file is a different kind of ShareCode artifact".to_string());
+    }
+    if blake3::hash(parsed.body).as_bytes() != &parsed.body_hash {
+        return Err("Artifact contents failed their integrity check".to_string());
+    }
+    Ok(parsed.body)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactReport {
+    /// `sessionSnapshot`, `recording`, `offlineBundle`, `backup`, `gifRecording`,
+    /// `webmRecording`, `replay`, `pdf`, or `unknown`.
+    pub kind: String,
+    /// Container version as `major.minor`, or the format version of a legacy file.
+    pub version: Option<String>,
+    pub size: u64,
+    /// Hex BLAKE3 of the body, for containers.
+    pub content_hash: Option<String>,
+    pub metadata: Metadata,
+    /// Whether everything that can be checked without a key or passphrase checked out.
+    pub valid: bool,
+    pub problems: Vec<String>,
+}
+
+impl ArtifactReport {
+    fn new(kind: &str, size: u64) -> Self {
+        Self {
+            kind: kind.to_string(),
+            version: None,
+            size,
+            content_hash: None,
+            metadata: Metadata::new(),
+            valid: true,
+            problems: Vec::new(),
+        }
+    }
+
+    fn problem(mut self, problem: impl Into<String>) -> Self {
+        self.valid = false;
+        self.problems.push(problem.into());
+        self
+    }
+}
+
+pub(crate) fn validate_container(bytes: &[u8]) -> ArtifactReport {
+    let size = bytes.len() as u64;
+    let parsed = match parse(bytes) {
+        Ok(parsed) => parsed,
+        Err(e) => return ArtifactReport::new("unknown", size).problem(e),
+    };
+    let kind = Kind::from_code(parsed.kind).map_or("unknown", Kind::name);
+    let hash = blake3::hash(parsed.body);
+    let mut report = ArtifactReport {
+        version: Some(format!("{}.{}", parsed.major, parsed.minor)),
+        content_hash: Some(hash.to_hex().to_string()),
+        metadata: parsed.metadata,
+        ..ArtifactReport::new(kind, size)
+    };
+    if kind == "unknown" {
+        report = report.problem(format!("Kind {} is newer than this version of ShareCode", parsed.kind));
+    }
+    if hash.as_bytes() != &parsed.body_hash {
+        report = report.problem("Contents failed their integrity check");
+    }
+    report
+}
+
+/// Identify a file that is not a container from its first and last bytes.
+pub(crate) fn validate_other(head: &[u8], tail: &[u8], size: u64) -> ArtifactReport {
+    if head.starts_with(BACKUP_MAGIC) || head.starts_with(LEGACY_BUNDLE_MAGIC) {
+        let kind = if head.starts_with(BACKUP_MAGIC) { "backup" } else { "offlineBundle" };
+        let report = ArtifactReport {
+            version: head.get(4).map(|v| v.to_string()),
+            ..ArtifactReport::new(kind, size)
+        };
+        // Sealed with a key or passphrase; the contents are authenticated when opened.
+        return match head.get(4) {
+            Some(1) => report,
+            Some(version) => report.problem(format!("Unsupported format version {}", version)),
+            None => report.problem("File is truncated"),
+        };
+    }
+    if head.starts_with(b"GIF89a") {
+        let report = ArtifactReport::new("gifRecording", size);
+        return if tail.last() == Some(&0x3b) {
+            report
+        } else {
+            report.problem("GIF is truncated")
+        };
+    }
+    if head.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+        return ArtifactReport::new("webmRecording", size);
+    }
+    if head.starts_with(b"%PDF-") {
+        let report = ArtifactReport::new("pdf", size);
+        return if tail.windows(5).any(|w| w == b"%%EOF") {
+            report
+        } else {
+            report.problem("PDF is truncated")
+        };
+    }
+    if head.starts_with(b"<svg") || head.starts_with(b"<!DOCTYPE html>") {
+        let report = ArtifactReport::new("replay", size);
+        let trimmed = String::from_utf8_lossy(tail);
+        let trimmed = trimmed.trim_end();
+        return if trimmed.ends_with("</svg>") || trimmed.ends_with("</html>") {
+            report
+        } else {
+            report.problem("Replay is truncated")
+        };
+    }
+    ArtifactReport::new("unknown", size).problem("Not a file ShareCode produces")
+}
+
+/// Identify a file ShareCode wrote and check whatever can be checked without opening it.
+#[tauri::command]
+pub fn validate_file(path: String) -> Result<ArtifactReport, String> {
+    let mut file = File::open(&path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut head = vec![0u8; SNIFF_LEN.min(size as usize)];
+    file.read_exact(&mut head).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if is_container(&head) {
+        if size > MAX_ARTIFACT_BYTES {
+            return Ok(ArtifactReport::new("unknown", size).problem("File is too large to check"));
+        }
+        let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        return Ok(validate_container(&bytes));
+    }
+    let mut tail = Vec::new();
+    if size > SNIFF_LEN as u64 {
+        file.seek(SeekFrom::Start(size.saturating_sub(1024).max(SNIFF_LEN as u64)))
+            .map_err(|e| e.to_string())?;
+        file.read_to_end(&mut tail).map_err(|e| e.to_string())?;
+    } else {
+        tail.clone_from(&head);
+    }
+    Ok(validate_other(&head, &tail, size))
+}
@@ -0,0 +1,458 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::storage;
+
+const BACKUP_DIR: &str = "backups";
+const CONFIG_FILE: &str = "backup.json";
+const KEY_FILE: &str = "backup.key";
+/// Machine-local secrets that never go into an archive nor come out of one. On macOS and Windows
+/// the config directory is the data directory, so they sit among the files being backed up.
+const SECRET_FILES: [&str; 2] = [KEY_FILE, "transparency.key"];
+/// Suffixes for the siblings of the data directory a restore swaps through.
+const STAGING_SUFFIX: &str = "restore-staging";
+const REPLACED_SUFFIX: &str = "before-restore";
+const FILE_PREFIX: &str = "sharecode-backup-";
+const FILE_EXTENSION: &str = "scbk";
+
+const MAGIC: &[u8; 4] = b"SCBK";
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 24;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    pub interval_minutes: u64,
+    pub on_exit: bool,
+    /// Number of most recent archives to keep; 0 disables count-based pruning.
+    pub keep_last: usize,
+    /// Archives older than this are removed, regardless of `keep_last`.
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: 60,
+            on_exit: true,
+            keep_last: 10,
+            max_age_days: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub path: String,
+    pub created_at: u64,
+    pub size: u64,
+}
+
+pub struct BackupState {
+    config: Mutex<BackupConfig>,
+    last_run: Mutex<u64>,
+    // Serializes backup and restore so they never observe each other's partial writes.
+    busy: Mutex<()>,
+}
+
+impl BackupState {
+    pub fn load(app: &AppHandle) -> Self {
+        let config = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+            log::warn!("Using default backup config: {}", e);
+            BackupConfig::default()
+        });
+        Self {
+            config: Mutex::new(config),
+            last_run: Mutex::new(storage::now_secs()),
+            busy: Mutex::new(()),
+        }
+    }
+
+    fn config(&self) -> BackupConfig {
+        self.config.lock().unwrap().clone()
+    }
+}
+
+struct Entry {
+    path: String,
+    data: Vec<u8>,
+}
+
+fn backup_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = storage::data_dir(app)?.join(BACKUP_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+fn load_key(app: &AppHandle) -> Result<[u8; 32], String> {
+    let path = storage::config_dir(app)?.join(KEY_FILE);
+    let mut key = [0u8; 32];
+    if path.exists() {
+        let raw = fs::read(&path).map_err(|e| format!("Failed to read backup key: {}", e))?;
+        if raw.len() != key.len() {
+            return Err("Backup key file is corrupted".to_string());
+        }
+        key.copy_from_slice(&raw);
+    } else {
+        rand::rngs::OsRng.fill_bytes(&mut key);
+        fs::write(&path, key).map_err(|e| format!("Failed to write backup key: {}", e))?;
+    }
+    Ok(key)
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<Entry>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if dir == root && (name == BACKUP_DIR || SECRET_FILES.iter().any(|secret| name == *secret)) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, out)?;
+        } else if !name.to_string_lossy().ends_with(".tmp") {
+            let rel = path
+                .strip_prefix(root)
+                .map_err(|e| e.to_string())?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            out.push(Entry { path: rel, data });
+        }
+    }
+    Ok(())
+}
+
+// Payload layout: u32 entry count, then per entry
+// u32 path length, path bytes, u64 data length, data bytes, sha256(data).
+fn encode_entries(entries: &[Entry]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for entry in entries {
+        buf.extend_from_slice(&(entry.path.len() as u32).to_le_bytes());
+        buf.extend_from_slice(entry.path.as_bytes());
+        buf.extend_from_slice(&(entry.data.len() as u64).to_le_bytes());
+        buf.extend_from_slice(&entry.data);
+        buf.extend_from_slice(&Sha256::digest(&entry.data));
+    }
+    buf
+}
+
+fn take<'a>(buf: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8], String> {
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= buf.len())
+        .ok_or_else(|| "Backup archive is truncated".to_string())?;
+    let slice = &buf[*pos..end];
+    *pos = end;
+    Ok(slice)
+}
+
+fn decode_entries(buf: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut pos = 0;
+    let count = u32::from_le_bytes(take(buf, &mut pos, 4)?.try_into().unwrap()) as usize;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let path_len = u32::from_le_bytes(take(buf, &mut pos, 4)?.try_into().unwrap()) as usize;
+        let path = String::from_utf8(take(buf, &mut pos, path_len)?.to_vec())
+            .map_err(|_| "Backup archive contains an invalid path".to_string())?;
+        let data_len = u64::from_le_bytes(take(buf, &mut pos, 8)?.try_into().unwrap()) as usize;
+        let data = take(buf, &mut pos, data_len)?.to_vec();
+        let digest = take(buf, &mut pos, 32)?;
+        if Sha256::digest(&data).as_slice() != digest {
+            return Err(format!("Checksum mismatch for {}", path));
+        }
+        let safe = Path::new(&path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if path.is_empty() || !safe {
+            return Err(format!("Backup archive contains an unsafe path: {}", path));
+        }
+        entries.push(Entry { path, data });
+    }
+    if pos != buf.len() {
+        return Err("Backup archive has trailing data".to_string());
+    }
+    Ok(entries)
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Failed to encrypt backup".to_string())?;
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 1 + NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn decrypt(key: &[u8; 32], archive: &[u8]) -> Result<Vec<u8>, String> {
+    let header = MAGIC.len() + 1 + NONCE_LEN;
+    if archive.len() < header || &archive[..MAGIC.len()] != MAGIC {
+        return Err("Not a ShareCode backup archive".to_string());
+    }
+    if archive[MAGIC.len()] != FORMAT_VERSION {
+        return Err(format!("Unsupported backup format version {}", archive[MAGIC.len()]));
+    }
+    let nonce = &archive[MAGIC.len() + 1..header];
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(XNonce::from_slice(nonce), &archive[header..])
+        .map_err(|_| "Backup archive failed integrity check (wrong key or corrupted file)".to_string())
+}
+
+fn parse_timestamp(path: &Path) -> Option<u64> {
+    if path.extension()? != FILE_EXTENSION {
+        return None;
+    }
+    path.file_stem()?.to_str()?.strip_prefix(FILE_PREFIX)?.parse().ok()
+}
+
+fn list_archives(app: &AppHandle) -> Result<Vec<BackupInfo>, String> {
+    let dir = backup_dir(app)?;
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if let Some(created_at) = parse_timestamp(&path) {
+            let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            backups.push(BackupInfo {
+                path: path.to_string_lossy().into_owned(),
+                created_at,
+                size,
+            });
+        }
+    }
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    Ok(backups)
+}
+
+fn apply_retention(app: &AppHandle, config: &BackupConfig) -> Result<(), String> {
+    let now = storage::now_secs();
+    for (index, backup) in list_archives(app)?.iter().enumerate() {
+        let too_many = config.keep_last > 0 && index >= config.keep_last;
+        let too_old = config
+            .max_age_days
+            .map(|days| now.saturating_sub(backup.created_at) > days * 24 * 60 * 60)
+            .unwrap_or(false);
+        // Never prune the newest archive, whatever the rules say.
+        if index > 0 && (too_many || too_old) {
+            fs::remove_file(&backup.path).map_err(|e| format!("Failed to prune {}: {}", backup.path, e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run_backup(app: &AppHandle) -> Result<BackupInfo, String> {
+    let state = app.state::<BackupState>();
+    let _guard = state.busy.lock().unwrap();
+    write_backup(app, &state)
+}
+
+/// Archive the data directory. The caller holds `busy`.
+fn write_backup(app: &AppHandle, state: &BackupState) -> Result<BackupInfo, String> {
+    let root = storage::data_dir(app)?;
+    let mut entries = Vec::new();
+    collect_files(&root, &root, &mut entries)?;
+    let archive = encrypt(&load_key(app)?, &encode_entries(&entries))?;
+
+    // Bump the timestamp if two backups land in the same second.
+    let mut created_at = storage::now_secs();
+    let dir = backup_dir(app)?;
+    let mut path = dir.join(format!("{}{}.{}", FILE_PREFIX, created_at, FILE_EXTENSION));
+    while path.exists() {
+        created_at += 1;
+        path = dir.join(format!("{}{}.{}", FILE_PREFIX, created_at, FILE_EXTENSION));
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, &archive).map_err(|e| format!("Failed to write backup: {}", e))?;
+    fs::rename(&tmp, &path).map_err(|e| format!("Failed to finalize backup: {}", e))?;
+
+    *state.last_run.lock().unwrap() = storage::now_secs();
+    apply_retention(app, &state.config())?;
+
+    Ok(BackupInfo {
+        path: path.to_string_lossy().into_owned(),
+        created_at,
+        size: archive.len() as u64,
+    })
+}
+
+/// Start the background thread that takes periodic backups while `enabled` is set.
+pub fn spawn_scheduler(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(30));
+        let state = app.state::<BackupState>();
+        let config = state.config();
+        if !config.enabled || config.interval_minutes == 0 {
+            continue;
+        }
+        let last_run = *state.last_run.lock().unwrap();
+        if storage::now_secs().saturating_sub(last_run) >= config.interval_minutes * 60 {
+            if let Err(e) = run_backup(&app) {
+                log::error!("Scheduled backup failed: {}", e);
+                *state.last_run.lock().unwrap() = storage::now_secs();
+            }
+        }
+    });
+}
+
+/// Called from the run loop when the app is about to exit.
+pub fn on_exit(app: &AppHandle) {
+    let config = app.state::<BackupState>().config();
+    if config.enabled && config.on_exit {
+        if let Err(e) = run_backup(app) {
+            log::error!("Exit backup failed: {}", e);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_backup_config(state: State<'_, BackupState>) -> BackupConfig {
+    state.config()
+}
+
+#[tauri::command]
+pub fn set_backup_config(app: AppHandle, state: State<'_, BackupState>, config: BackupConfig) -> Result<(), String> {
+    storage::save_json(&app, CONFIG_FILE, &config)?;
+    *state.config.lock().unwrap() = config;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_backup(app: AppHandle) -> Result<BackupInfo, String> {
+    run_backup(&app)
+}
+
+#[tauri::command]
+pub fn list_backups(app: AppHandle) -> Result<Vec<BackupInfo>, String> {
+    list_archives(&app)
+}
+
+/// Files in the data directory that a restore keeps rather than takes from the archive.
+fn is_kept(name: &str) -> bool {
+    name == BACKUP_DIR || SECRET_FILES.contains(&name)
+}
+
+fn sibling(root: &Path, suffix: &str) -> Result<PathBuf, String> {
+    let name = root
+        .file_name()
+        .ok_or_else(|| format!("{} has no parent directory", root.display()))?;
+    Ok(root.with_file_name(format!("{}.{}", name.to_string_lossy(), suffix)))
+}
+
+/// Move the kept files named in `names` from `from` to `to`, stopping at the first failure.
+fn carry(from: &Path, to: &Path, names: &[String]) -> Result<(), String> {
+    for name in names {
+        fs::rename(from.join(name), to.join(name)).map_err(|e| format!("Failed to carry over {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn discard_staging(root: &Path, staging: &Path, kept: &[String]) {
+    if kept.iter().all(|name| root.join(name).exists()) {
+        fs::remove_dir_all(staging).ok();
+    } else {
+        log::error!("Restore failed midway; backups and keys were left in {}", staging.display());
+    }
+}
+
+/// Replace the data directory with `staging` in one rename, keeping what `is_kept` names. If the
+/// swap fails, the data directory is left as it was and `staging` is removed, unless something
+/// kept could not be moved back out of it.
+fn swap_in(root: &Path, staging: &Path) -> Result<(), String> {
+    let replaced = sibling(root, REPLACED_SUFFIX)?;
+    if replaced.exists() {
+        fs::remove_dir_all(&replaced).map_err(|e| format!("Failed to clear {}: {}", replaced.display(), e))?;
+    }
+    let mut kept = Vec::new();
+    for entry in fs::read_dir(root).map_err(|e| format!("Failed to read {}: {}", root.display(), e))? {
+        let name = entry.map_err(|e| e.to_string())?.file_name().to_string_lossy().into_owned();
+        if is_kept(&name) {
+            kept.push(name);
+        }
+    }
+    if let Err(e) = carry(root, staging, &kept) {
+        // Put back whatever made it across before the failure.
+        for name in &kept {
+            if staging.join(name).exists() {
+                fs::rename(staging.join(name), root.join(name)).ok();
+            }
+        }
+        discard_staging(root, staging, &kept);
+        return Err(e);
+    }
+
+    let swapped = fs::rename(root, &replaced).and_then(|_| {
+        fs::rename(staging, root).inspect_err(|_| {
+            fs::rename(&replaced, root).ok();
+        })
+    });
+    if let Err(e) = swapped {
+        carry(staging, root, &kept).ok();
+        discard_staging(root, staging, &kept);
+        return Err(format!("Failed to swap in the restored data: {}", e));
+    }
+    if let Err(e) = fs::remove_dir_all(&replaced) {
+        log::warn!("Failed to remove {}: {}", replaced.display(), e);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn restore_backup(app: AppHandle, state: State<'_, BackupState>, path: String) -> Result<(), String> {
+    // Fully decrypt, parse and checksum the archive before touching the data directory.
+    let archive = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let entries = decode_entries(&decrypt(&load_key(&app)?, &archive)?)?;
+
+    let _guard = state.busy.lock().unwrap();
+    // Keep a copy of the current state so a bad restore can itself be undone.
+    write_backup(&app, &state)?;
+
+    let root = storage::data_dir(&app)?;
+    let staging = sibling(&root, STAGING_SUFFIX)?;
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|e| format!("Failed to clear staging directory: {}", e))?;
+    }
+    fs::create_dir_all(&staging).map_err(|e| format!("Failed to create staging directory: {}", e))?;
+    // Archives from before keys were left out may still carry them; the ones here stay.
+    for entry in entries.iter().filter(|entry| !is_kept(entry.path.split('/').next().unwrap_or(""))) {
+        let target = staging.join(&entry.path);
+        let written = match target.parent() {
+            Some(parent) => fs::create_dir_all(parent).and_then(|_| fs::write(&target, &entry.data)),
+            None => fs::write(&target, &entry.data),
+        };
+        if let Err(e) = written {
+            fs::remove_dir_all(&staging).ok();
+            return Err(format!("Failed to stage {}: {}", entry.path, e));
+        }
+    }
+    swap_in(&root, &staging)?;
+
+    // Settings, recents, indexes and the rest were loaded from the replaced files; start over
+    // rather than let any of them save on top of the restored ones.
+    storage::seal_data_dir();
+    app.request_restart();
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Upload metering and an optional per-session cap, for hosts on metered or tethered connections.
+//!
+//! Every byte queued for a viewer is counted, once per viewer, from the moment a session starts.
+//! With a cap set, the sharing hub sends less as the count approaches it: from `COALESCE_AT` of
+//! the cap, document changes are held and sent every few seconds as a batch of patches or one
+//! snapshot, whichever is smaller; from `SNAPSHOT_AT`, only a snapshot per changed document is sent
+//! now and then; at the cap, nothing but lifecycle notices goes out and viewers cannot join, until
+//! the cap is raised or the next session starts. Level changes are emitted as
+//! `bandwidth-level-changed`; `get_bandwidth_usage` is the live meter.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::sharing::SharingHub;
+use crate::storage;
+
+const CONFIG_FILE: &str = "bandwidth.json";
+/// Fractions of the cap at which sharing degrades.
+const COALESCE_AT: f64 = 0.75;
+const SNAPSHOT_AT: f64 = 0.9;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Level {
+    Normal,
+    /// Document changes are batched.
+    Coalescing,
+    /// Document changes go out as occasional snapshots.
+    SnapshotOnly,
+    /// Only lifecycle notices go out.
+    Capped,
+}
+
+impl Level {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Coalescing,
+            2 => Self::SnapshotOnly,
+            3 => Self::Capped,
+            _ => Self::Normal,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct BandwidthConfig {
+    /// Bytes a session may upload; `None` for no cap.
+    upload_cap: Option<u64>,
+}
+
+/// The hub's byte counter. Counts are kept in atomics, as every fan-out worker adds to them.
+#[derive(Default)]
+pub struct Meter {
+    sent: AtomicU64,
+    /// 0 for no cap.
+    cap: AtomicU64,
+    /// Bytes sent in the last second, as last sampled by `sample`.
+    rate: AtomicU64,
+    last_sample: AtomicU64,
+    /// The level last reported, as `Level as u8`.
+    reported: AtomicU8,
+}
+
+impl Meter {
+    pub fn add(&self, bytes: usize) {
+        self.sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn cap(&self) -> Option<u64> {
+        Some(self.cap.load(Ordering::Relaxed)).filter(|&cap| cap > 0)
+    }
+
+    fn set_cap(&self, cap: Option<u64>) {
+        self.cap.store(cap.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn level(&self) -> Level {
+        let Some(cap) = self.cap() else {
+            return Level::Normal;
+        };
+        let used = self.sent() as f64 / cap as f64;
+        if used >= 1.0 {
+            Level::Capped
+        } else if used >= SNAPSHOT_AT {
+            Level::SnapshotOnly
+        } else if used >= COALESCE_AT {
+            Level::Coalescing
+        } else {
+            Level::Normal
+        }
+    }
+
+    /// Start counting afresh, for a new session.
+    fn reset(&self) {
+        self.sent.store(0, Ordering::Relaxed);
+        self.rate.store(0, Ordering::Relaxed);
+        self.last_sample.store(0, Ordering::Relaxed);
+    }
+
+    /// Update the rate; called once a second. Returns the level if it changed since last reported.
+    pub fn sample(&self) -> Option<Level> {
+        let sent = self.sent();
+        let before = self.last_sample.swap(sent, Ordering::Relaxed);
+        self.rate.store(sent.saturating_sub(before), Ordering::Relaxed);
+        let level = self.level();
+        let reported = Level::from_u8(self.reported.swap(level as u8, Ordering::Relaxed));
+        (level != reported).then_some(level)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthUsage {
+    /// Bytes queued for viewers since the session started.
+    pub sent_bytes: u64,
+    pub cap_bytes: Option<u64>,
+    pub remaining_bytes: Option<u64>,
+    pub bytes_per_second: u64,
+    pub level: Level,
+}
+
+fn usage(meter: &Meter) -> BandwidthUsage {
+    let sent_bytes = meter.sent();
+    let cap_bytes = meter.cap();
+    BandwidthUsage {
+        sent_bytes,
+        cap_bytes,
+        remaining_bytes: cap_bytes.map(|cap| cap.saturating_sub(sent_bytes)),
+        bytes_per_second: meter.rate.load(Ordering::Relaxed),
+        level: meter.level(),
+    }
+}
+
+/// Tell the webview the level changed, with the usage that changed it.
+pub fn level_changed(app: &AppHandle, meter: &Meter) {
+    if let Err(e) = app.emit("bandwidth-level-changed", usage(meter)) {
+        log::warn!("Failed to emit bandwidth-level-changed: {}", e);
+    }
+}
+
+/// Apply the saved cap at startup.
+pub fn restore(app: &AppHandle) {
+    match storage::load_json::<BandwidthConfig>(app, CONFIG_FILE) {
+        Ok(config) => app.state::<Arc<SharingHub>>().meter().set_cap(config.upload_cap),
+        Err(e) => log::warn!("Starting without an upload cap: {}", e),
+    }
+}
+
+/// Zero the meter, when a session starts.
+pub fn reset(app: &AppHandle) {
+    app.state::<Arc<SharingHub>>().meter().reset();
+}
+
+#[tauri::command]
+pub fn get_bandwidth_usage(hub: State<'_, Arc<SharingHub>>) -> BandwidthUsage {
+    usage(hub.meter())
+}
+
+/// Cap what a session may upload, in bytes, or lift the cap with `None`. Applies to the current
+/// session at once, counting what it has already sent.
+#[tauri::command]
+pub fn set_upload_cap(app: AppHandle, hub: State<'_, Arc<SharingHub>>, cap_bytes: Option<u64>) -> Result<(), String> {
+    if cap_bytes == Some(0) {
+        return Err("The upload cap must be more than 0 bytes".to_string());
+    }
+    storage::save_json(&app, CONFIG_FILE, &BandwidthConfig { upload_cap: cap_bytes })?;
+    hub.meter().set_cap(cap_bytes);
+    Ok(())
+}
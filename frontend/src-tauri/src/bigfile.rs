@@ -0,0 +1,277 @@
+//! Read-only, memory-mapped access to files too large to load into the editor.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use memmap2::Mmap;
+use serde::Serialize;
+use tauri::ipc::Response;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::binary;
+use crate::documents::{Document, DocumentInfo, DocumentStore};
+use crate::language;
+use crate::settings::SettingsStore;
+use crate::storage::new_id;
+
+/// Upper bound on a single windowed read so one request cannot pull the whole file through IPC.
+const MAX_WINDOW_BYTES: usize = 4 * 1024 * 1024;
+const MAX_WINDOW_LINES: usize = 10_000;
+/// Regions larger than this are refused when turned into a shareable document.
+const MAX_REGION_BYTES: usize = 16 * 1024 * 1024;
+/// How far the on-demand indexer scans past the requested line in one go.
+const INDEX_CHUNK_BYTES: usize = 1024 * 1024;
+
+struct LineIndex {
+    /// Byte offset of the start of each line discovered so far; always starts with 0.
+    starts: Vec<usize>,
+    /// Bytes already scanned for line breaks.
+    scanned: usize,
+}
+
+pub struct LargeFile {
+    path: PathBuf,
+    map: Mmap,
+    index: Mutex<LineIndex>,
+}
+
+#[derive(Default)]
+pub struct LargeFileStore {
+    files: Mutex<HashMap<String, Arc<LargeFile>>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LargeFileInfo {
+    pub id: String,
+    pub path: String,
+    pub size: usize,
+    /// Lines indexed so far; equals the total once `complete` is set.
+    pub indexed_lines: usize,
+    pub complete: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineWindow {
+    pub start_line: usize,
+    pub lines: Vec<String>,
+    /// True when the window ends at the end of the file.
+    pub at_end: bool,
+}
+
+impl LargeFile {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Scan forward until the line after `line` has a known start offset, or the file ends.
+    fn ensure_line(&self, line: usize) {
+        loop {
+            let from = {
+                let index = self.index.lock().unwrap();
+                if index.starts.len() > line.saturating_add(1) || index.scanned >= self.len() {
+                    return;
+                }
+                index.scanned
+            };
+            // Scan without holding the lock so windowed reads stay responsive during a full index.
+            let to = (from + INDEX_CHUNK_BYTES).min(self.len());
+            let found: Vec<usize> = memchr::memchr_iter(b'\n', &self.map[from..to])
+                .map(|pos| from + pos + 1)
+                .collect();
+            let mut index = self.index.lock().unwrap();
+            if index.scanned == from {
+                index.starts.extend(found);
+                index.scanned = to;
+            }
+        }
+    }
+
+    /// Byte range of `line` without its trailing line break, if the line exists.
+    fn line_range(&self, line: usize) -> Option<(usize, usize)> {
+        self.ensure_line(line);
+        let index = self.index.lock().unwrap();
+        let start = *index.starts.get(line)?;
+        if start >= self.len() && line > 0 {
+            return None;
+        }
+        let end = index.starts.get(line + 1).map(|next| next - 1).unwrap_or(self.len());
+        let end = if end > start && self.map[end - 1] == b'\r' { end - 1 } else { end };
+        Some((start, end))
+    }
+
+    fn info(&self, id: &str) -> LargeFileInfo {
+        let index = self.index.lock().unwrap();
+        let complete = index.scanned >= self.len();
+        LargeFileInfo {
+            id: id.to_string(),
+            path: self.path.to_string_lossy().into_owned(),
+            size: self.len(),
+            indexed_lines: line_total(&index.starts, self.len(), complete),
+            complete,
+        }
+    }
+}
+
+// A trailing newline does not start another line.
+fn line_total(starts: &[usize], len: usize, complete: bool) -> usize {
+    if complete && starts.last() == Some(&len) && len > 0 {
+        starts.len() - 1
+    } else {
+        starts.len()
+    }
+}
+
+impl LargeFileStore {
+    fn get(&self, id: &str) -> Result<Arc<LargeFile>, String> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Large file {} is not open", id))
+    }
+}
+
+#[tauri::command]
+pub fn open_large_file(store: State<'_, LargeFileStore>, path: String) -> Result<LargeFileInfo, String> {
+    let path = PathBuf::from(path);
+    let file = File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    // Safety: the mapping is read-only; if another process truncates the file the OS may
+    // fault on access, which is the accepted trade-off for not copying gigabytes into memory.
+    let map = unsafe { Mmap::map(&file) }.map_err(|e| format!("Failed to map {}: {}", path.display(), e))?;
+    let file = Arc::new(LargeFile {
+        path,
+        map,
+        index: Mutex::new(LineIndex { starts: vec![0], scanned: 0 }),
+    });
+    let id = new_id();
+    let info = file.info(&id);
+    store.files.lock().unwrap().insert(id, file);
+    Ok(info)
+}
+
+/// Index the whole file on a background thread, emitting `large-file-indexed` when done.
+#[tauri::command]
+pub fn index_large_file(app: AppHandle, store: State<'_, LargeFileStore>, id: String) -> Result<(), String> {
+    let file = store.get(&id)?;
+    thread::spawn(move || {
+        file.ensure_line(usize::MAX);
+        if let Err(e) = app.emit("large-file-indexed", file.info(&id)) {
+            log::warn!("Failed to emit large-file-indexed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_large_file_info(store: State<'_, LargeFileStore>, id: String) -> Result<LargeFileInfo, String> {
+    Ok(store.get(&id)?.info(&id))
+}
+
+/// Read up to `count` lines starting at `start_line`, decoding lossily as UTF-8.
+#[tauri::command]
+pub fn read_large_file_lines(
+    store: State<'_, LargeFileStore>,
+    id: String,
+    start_line: usize,
+    count: usize,
+) -> Result<LineWindow, String> {
+    let file = store.get(&id)?;
+    let mut lines = Vec::new();
+    let mut bytes = 0;
+    let mut line = start_line;
+    while lines.len() < count.min(MAX_WINDOW_LINES) && bytes < MAX_WINDOW_BYTES {
+        let Some((start, end)) = file.line_range(line) else {
+            break;
+        };
+        let end = end.min(start + MAX_WINDOW_BYTES - bytes);
+        lines.push(String::from_utf8_lossy(&file.map[start..end]).into_owned());
+        bytes += end - start;
+        line += 1;
+    }
+    let at_end = file.line_range(line).is_none();
+    Ok(LineWindow { start_line, lines, at_end })
+}
+
+/// Read raw bytes (lossily decoded) from an arbitrary offset, for hex-style or byte-offset navigation.
+#[tauri::command]
+pub fn read_large_file_range(store: State<'_, LargeFileStore>, id: String, offset: usize, length: usize) -> Result<String, String> {
+    let file = store.get(&id)?;
+    let start = offset.min(file.len());
+    let end = start + length.min(MAX_WINDOW_BYTES).min(file.len() - start);
+    Ok(String::from_utf8_lossy(&file.map[start..end]).into_owned())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RangeHeader {
+    offset: usize,
+    length: usize,
+}
+
+/// Like `read_large_file_range`, but the exact bytes, as a binary frame with the range read.
+#[tauri::command]
+pub fn read_large_file_range_binary(
+    store: State<'_, LargeFileStore>,
+    id: String,
+    offset: usize,
+    length: usize,
+) -> Result<Response, String> {
+    let file = store.get(&id)?;
+    let start = offset.min(file.len());
+    let end = start + length.min(MAX_WINDOW_BYTES).min(file.len() - start);
+    let header = RangeHeader {
+        offset: start,
+        length: end - start,
+    };
+    binary::frame(&header, &file.map[start..end])
+}
+
+/// Copy lines `start_line..=end_line` into a regular document so the region can be shared.
+#[tauri::command]
+pub fn extract_large_file_region(
+    app: AppHandle,
+    store: State<'_, LargeFileStore>,
+    documents: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    id: String,
+    start_line: usize,
+    end_line: usize,
+) -> Result<DocumentInfo, String> {
+    let file = store.get(&id)?;
+    if end_line < start_line {
+        return Err("Region end must not be before its start".to_string());
+    }
+    let (start, _) = file.line_range(start_line).ok_or("Region starts past the end of the file")?;
+    let end = file
+        .line_range(end_line)
+        .map(|(_, end)| end)
+        .unwrap_or(file.len());
+    if end - start > MAX_REGION_BYTES {
+        return Err(format!("Region is too large to share ({} bytes, limit {})", end - start, MAX_REGION_BYTES));
+    }
+    let text = String::from_utf8_lossy(&file.map[start..end]).into_owned();
+    let info = documents.insert(Document::new(None, text));
+    let language = file
+        .path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(language::language_for_extension);
+    match language {
+        Some(language) => {
+            crate::documents::apply_language(&app, &documents, &settings, &info.id, language)?;
+            documents.with(&info.id, |doc| doc.info(&info.id))
+        }
+        None => Ok(info),
+    }
+}
+
+#[tauri::command]
+pub fn close_large_file(store: State<'_, LargeFileStore>, id: String) -> bool {
+    store.files.lock().unwrap().remove(&id).is_some()
+}
@@ -0,0 +1,51 @@
+//! Binary IPC responses for large payloads (minimaps, prepared screenshots, raw file ranges),
+//! which reach the webview as an `ArrayBuffer` instead of base64 inside JSON.
+//!
+//! A frame is the magic `SCB1`, the length of the header as a little-endian `u32`, the header as
+//! UTF-8 JSON, and then the body bytes to the end of the buffer. `decodeFrame` in `tauri.ts`
+//! reads it back.
+
+#[cfg(feature = "bench")]
+use base64::engine::general_purpose::STANDARD;
+#[cfg(feature = "bench")]
+use base64::Engine;
+use serde::Serialize;
+use tauri::ipc::Response;
+
+const MAGIC: &[u8; 4] = b"SCB1";
+/// Largest payload `ipc_benchmark_payload` will produce; the base64 copy is a third larger again.
+#[cfg(feature = "bench")]
+const MAX_BENCHMARK_BYTES: usize = 16 * 1024 * 1024;
+
+pub fn frame<H: Serialize>(header: &H, body: &[u8]) -> Result<Response, String> {
+    let header = serde_json::to_vec(header).map_err(|e| e.to_string())?;
+    let header_len = u32::try_from(header.len()).map_err(|_| "Frame header is too large".to_string())?;
+    let mut out = Vec::with_capacity(MAGIC.len() + 4 + header.len() + body.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&header_len.to_le_bytes());
+    out.extend_from_slice(&header);
+    out.extend_from_slice(body);
+    Ok(Response::new(out))
+}
+
+#[cfg(feature = "bench")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BenchmarkHeader {
+    bytes: usize,
+}
+
+/// `bytes` of filler, up to `MAX_BENCHMARK_BYTES`, as a binary frame or as base64 in JSON, so the
+/// webview can time both. Only in builds made with the `bench` feature.
+#[cfg(feature = "bench")]
+#[tauri::command]
+pub fn ipc_benchmark_payload(bytes: usize, binary: bool) -> Result<Response, String> {
+    let bytes = bytes.min(MAX_BENCHMARK_BYTES);
+    // Not all zeroes, so nothing along the way can shortcut it.
+    let body: Vec<u8> = (0..bytes).map(|i| (i % 251) as u8).collect();
+    if binary {
+        return frame(&BenchmarkHeader { bytes }, &body);
+    }
+    let json = serde_json::to_string(&STANDARD.encode(&body)).map_err(|e| e.to_string())?;
+    Ok(Response::new(json))
+}
@@ -0,0 +1,404 @@
+//! Offline snippet transfer over Bluetooth LE between two nearby instances, for rooms with no
+//! network.
+//!
+//! The receiver exposes the sharecode GATT service; the sender connects to it as a central. Before
+//! any content moves, both screens show a six-digit code derived from nonces exchanged by the two
+//! devices, and both users confirm it matches. The snippet is then written in numbered chunks and
+//! checked against its SHA-256 on arrival.
+//!
+//! btleplug only implements the central role, so the receiving side's GATT service is provided by
+//! the platform layer: it hands every write to the RX characteristic to `receive_ble_frame` and
+//! sends whatever bytes come back as a notification on TX.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter, ValueNotification, WriteType};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures::stream::{Stream, StreamExt};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::events::{self, Channel};
+use crate::snippets::{PortableSnippet, Snippet, SnippetLibrary};
+use crate::storage::new_id;
+
+pub const SERVICE_UUID: Uuid = Uuid::from_u128(0x5c0d_e000_7a1e_4b1e_9a5e_5ba2_ec0d_e001);
+/// Written by the sender.
+pub const RX_UUID: Uuid = Uuid::from_u128(0x5c0d_e000_7a1e_4b1e_9a5e_5ba2_ec0d_e002);
+/// Notified by the receiver.
+pub const TX_UUID: Uuid = Uuid::from_u128(0x5c0d_e000_7a1e_4b1e_9a5e_5ba2_ec0d_e003);
+
+/// Snippets only; anything larger takes minutes over BLE.
+const MAX_TRANSFER: usize = 64 * 1024;
+/// Chunk payload size, within the 512-byte attribute limit with room for the header.
+const CHUNK_SIZE: usize = 180;
+const NONCE_LEN: usize = 16;
+const DEFAULT_SCAN: Duration = Duration::from_secs(5);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long either user has to compare and confirm the pairing code.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(90);
+
+// Frame kinds, sender to receiver...
+const HELLO: u8 = 0x01;
+const CHUNK: u8 = 0x02;
+const DONE: u8 = 0x03;
+const CANCEL: u8 = 0x04;
+// ...and receiver to sender.
+const NONCE: u8 = 0x81;
+const DECISION: u8 = 0x82;
+const ACK: u8 = 0x83;
+
+type Notifications = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlePeer {
+    pub id: String,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PairingCode {
+    transfer_id: String,
+    code: String,
+    /// `send` or `receive`.
+    direction: &'static str,
+}
+
+/// One event per chunk; only the latest for each transfer reaches the webview.
+const BLE_PROGRESS: Channel = Channel::latest_wins("ble-progress");
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    transfer_id: String,
+    direction: &'static str,
+    bytes: usize,
+    total: usize,
+}
+
+/// A transfer being received, between the sender's hello and its final chunk.
+struct Incoming {
+    transfer_id: String,
+    sender_nonce: [u8; NONCE_LEN],
+    nonce: [u8; NONCE_LEN],
+    total: usize,
+    sha256: [u8; 32],
+    chunks: Vec<Option<Vec<u8>>>,
+    confirmed: bool,
+}
+
+#[derive(Default)]
+pub struct BleState {
+    /// Kept between scanning and sending so discovered peers stay known.
+    adapter: tokio::sync::Mutex<Option<Adapter>>,
+    /// Sending transfers waiting for this user to confirm the code.
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    incoming: Mutex<Option<Incoming>>,
+}
+
+fn bt(error: btleplug::Error) -> String {
+    format!("Bluetooth error: {}", error)
+}
+
+fn nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// Six digits both devices derive from the two nonces. Matching codes show that the two users
+/// picked each other's devices, not some other instance in range.
+fn pairing_code(sender_nonce: &[u8], receiver_nonce: &[u8]) -> String {
+    let digest = Sha256::new()
+        .chain_update(b"sharecode-ble-pairing")
+        .chain_update(sender_nonce)
+        .chain_update(receiver_nonce)
+        .finalize();
+    let value = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]);
+    format!("{:06}", value % 1_000_000)
+}
+
+fn chunk_count(total: usize) -> usize {
+    total.div_ceil(CHUNK_SIZE)
+}
+
+async fn adapter(state: &BleState) -> Result<Adapter, String> {
+    let mut adapter = state.adapter.lock().await;
+    if let Some(adapter) = adapter.as_ref() {
+        return Ok(adapter.clone());
+    }
+    let manager = Manager::new().await.map_err(bt)?;
+    let found = manager
+        .adapters()
+        .await
+        .map_err(bt)?
+        .into_iter()
+        .next()
+        .ok_or("No Bluetooth adapter found")?;
+    *adapter = Some(found.clone());
+    Ok(found)
+}
+
+/// Wait for the receiver's next frame of `kind`.
+async fn reply(notifications: &mut Notifications, kind: u8, timeout: Duration) -> Result<Vec<u8>, String> {
+    let wait = async {
+        while let Some(notification) = notifications.next().await {
+            if notification.uuid == TX_UUID && notification.value.first() == Some(&kind) {
+                return Ok(notification.value);
+            }
+        }
+        Err("The receiver disconnected".to_string())
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .map_err(|_| "The receiver did not respond".to_string())?
+}
+
+/// Look for nearby instances ready to receive.
+#[tauri::command]
+pub async fn scan_ble_peers(state: State<'_, BleState>, seconds: Option<u64>) -> Result<Vec<BlePeer>, String> {
+    let adapter = adapter(&state).await?;
+    let filter = ScanFilter {
+        services: vec![SERVICE_UUID],
+    };
+    adapter.start_scan(filter).await.map_err(bt)?;
+    tokio::time::sleep(seconds.map_or(DEFAULT_SCAN, Duration::from_secs)).await;
+    adapter.stop_scan().await.map_err(bt)?;
+
+    let mut peers = Vec::new();
+    for peripheral in adapter.peripherals().await.map_err(bt)? {
+        let Some(properties) = peripheral.properties().await.map_err(bt)? else {
+            continue;
+        };
+        if properties.services.contains(&SERVICE_UUID) {
+            peers.push(BlePeer {
+                id: peripheral.id().to_string(),
+                name: properties.local_name,
+                rssi: properties.rssi,
+            });
+        }
+    }
+    peers.sort_by_key(|p| std::cmp::Reverse(p.rssi));
+    Ok(peers)
+}
+
+async fn send(app: &AppHandle, state: &BleState, peripheral: &Peripheral, payload: &[u8]) -> Result<(), String> {
+    peripheral.discover_services().await.map_err(bt)?;
+    let characteristics = peripheral.characteristics();
+    let rx = characteristics
+        .iter()
+        .find(|c| c.uuid == RX_UUID)
+        .ok_or("The peer does not offer snippet transfer")?;
+    let tx = characteristics
+        .iter()
+        .find(|c| c.uuid == TX_UUID)
+        .ok_or("The peer does not offer snippet transfer")?;
+    peripheral.subscribe(tx).await.map_err(bt)?;
+    let mut notifications = peripheral.notifications().await.map_err(bt)?;
+
+    let own_nonce = nonce();
+    let mut hello = vec![HELLO];
+    hello.extend_from_slice(&own_nonce);
+    hello.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    hello.extend_from_slice(&Sha256::digest(payload));
+    peripheral.write(rx, &hello, WriteType::WithResponse).await.map_err(bt)?;
+
+    let reply_nonce = reply(&mut notifications, NONCE, REPLY_TIMEOUT).await?;
+    let receiver_nonce = reply_nonce.get(1..1 + NONCE_LEN).ok_or("Malformed reply from the receiver")?;
+    let transfer_id = new_id();
+    let (confirm, confirmed) = oneshot::channel();
+    state.pending.lock().unwrap().insert(transfer_id.clone(), confirm);
+    let code = PairingCode {
+        transfer_id: transfer_id.clone(),
+        code: pairing_code(&own_nonce, receiver_nonce),
+        direction: "send",
+    };
+    app.emit("ble-pairing-code", code).map_err(|e| e.to_string())?;
+
+    let accepted = tokio::time::timeout(CONFIRM_TIMEOUT, confirmed).await;
+    state.pending.lock().unwrap().remove(&transfer_id);
+    if !matches!(accepted, Ok(Ok(true))) {
+        peripheral.write(rx, &[CANCEL], WriteType::WithResponse).await.ok();
+        return Err("Pairing was not confirmed".to_string());
+    }
+    let decision = reply(&mut notifications, DECISION, CONFIRM_TIMEOUT).await?;
+    if decision.get(1) != Some(&1) {
+        return Err("The receiver declined the snippet".to_string());
+    }
+
+    for (index, chunk) in payload.chunks(CHUNK_SIZE).enumerate() {
+        let mut frame = vec![CHUNK];
+        frame.extend_from_slice(&(index as u16).to_be_bytes());
+        frame.extend_from_slice(chunk);
+        // Acknowledged writes double as flow control.
+        peripheral.write(rx, &frame, WriteType::WithResponse).await.map_err(bt)?;
+        let progress = Progress {
+            transfer_id: transfer_id.clone(),
+            direction: "send",
+            bytes: (index * CHUNK_SIZE + chunk.len()).min(payload.len()),
+            total: payload.len(),
+        };
+        events::emit_keyed(app, &BLE_PROGRESS, &transfer_id, progress).ok();
+    }
+    peripheral.write(rx, &[DONE], WriteType::WithResponse).await.map_err(bt)?;
+    let ack = reply(&mut notifications, ACK, REPLY_TIMEOUT).await?;
+    if ack.get(1) != Some(&1) {
+        return Err("The snippet arrived damaged".to_string());
+    }
+    Ok(())
+}
+
+/// Send a snippet from the library to a nearby peer found by `scan_ble_peers`.
+#[tauri::command]
+pub async fn send_snippet_ble(
+    app: AppHandle,
+    state: State<'_, BleState>,
+    library: State<'_, SnippetLibrary>,
+    peer_id: String,
+    snippet_id: String,
+) -> Result<(), String> {
+    let snippet = PortableSnippet::from(library.get(&app, &snippet_id)?);
+    let payload = serde_json::to_vec(&snippet).map_err(|e| e.to_string())?;
+    if payload.len() > MAX_TRANSFER {
+        return Err(format!("Snippets over {} KiB are too large for Bluetooth", MAX_TRANSFER / 1024));
+    }
+    if chunk_count(payload.len()) > u16::MAX as usize {
+        return Err("Snippet is too large".to_string());
+    }
+
+    let adapter = adapter(&state).await?;
+    let peripheral = adapter
+        .peripherals()
+        .await
+        .map_err(bt)?
+        .into_iter()
+        .find(|p| p.id().to_string() == peer_id)
+        .ok_or("Peer is no longer in range; scan again")?;
+    peripheral.connect().await.map_err(bt)?;
+    let result = send(&app, &state, &peripheral, &payload).await;
+    peripheral.disconnect().await.ok();
+    result
+}
+
+/// Handle a frame the platform's GATT service received on RX. Returns bytes to notify on TX.
+#[tauri::command]
+pub fn receive_ble_frame(
+    app: AppHandle,
+    state: State<'_, BleState>,
+    library: State<'_, SnippetLibrary>,
+    frame: Vec<u8>,
+) -> Result<Option<Vec<u8>>, String> {
+    let mut incoming = state.incoming.lock().unwrap();
+    match frame.first().copied() {
+        Some(HELLO) => {
+            if frame.len() != 1 + NONCE_LEN + 4 + 32 {
+                return Err("Malformed hello".to_string());
+            }
+            let sender_nonce: [u8; NONCE_LEN] = frame[1..1 + NONCE_LEN].try_into().unwrap();
+            let total = u32::from_be_bytes(frame[1 + NONCE_LEN..5 + NONCE_LEN].try_into().unwrap()) as usize;
+            if total > MAX_TRANSFER {
+                return Err("Incoming snippet is too large".to_string());
+            }
+            let transfer = Incoming {
+                transfer_id: new_id(),
+                sender_nonce,
+                nonce: nonce(),
+                total,
+                sha256: frame[5 + NONCE_LEN..].try_into().unwrap(),
+                chunks: vec![None; chunk_count(total)],
+                confirmed: false,
+            };
+            let code = PairingCode {
+                transfer_id: transfer.transfer_id.clone(),
+                code: pairing_code(&transfer.sender_nonce, &transfer.nonce),
+                direction: "receive",
+            };
+            app.emit("ble-pairing-code", code).map_err(|e| e.to_string())?;
+            let mut reply = vec![NONCE];
+            reply.extend_from_slice(&transfer.nonce);
+            // A new hello replaces any transfer that was abandoned half-way.
+            *incoming = Some(transfer);
+            Ok(Some(reply))
+        }
+        Some(CHUNK) => {
+            let transfer = incoming
+                .as_mut()
+                .filter(|t| t.confirmed)
+                .ok_or("No confirmed transfer in progress")?;
+            let index = u16::from_be_bytes(frame.get(1..3).ok_or("Malformed chunk")?.try_into().unwrap()) as usize;
+            let slot = transfer.chunks.get_mut(index).ok_or("Chunk out of range")?;
+            *slot = Some(frame[3..].to_vec());
+            let received: usize = transfer.chunks.iter().flatten().map(Vec::len).sum();
+            let progress = Progress {
+                transfer_id: transfer.transfer_id.clone(),
+                direction: "receive",
+                bytes: received,
+                total: transfer.total,
+            };
+            events::emit_keyed(&app, &BLE_PROGRESS, &transfer.transfer_id, progress).ok();
+            Ok(None)
+        }
+        Some(DONE) => {
+            let transfer = incoming.take().filter(|t| t.confirmed).ok_or("No confirmed transfer in progress")?;
+            let payload: Option<Vec<u8>> = transfer
+                .chunks
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(|chunks| chunks.concat());
+            let snippet = payload
+                .filter(|p| p.len() == transfer.total && Sha256::digest(p)[..] == transfer.sha256)
+                .and_then(|p| serde_json::from_slice::<PortableSnippet>(&p).ok())
+                .map(|p| save(&app, &library, p))
+                .transpose()?;
+            let ok = snippet.is_some();
+            if let Some(snippet) = snippet {
+                app.emit("ble-received", snippet).map_err(|e| e.to_string())?;
+            }
+            Ok(Some(vec![ACK, ok as u8]))
+        }
+        Some(CANCEL) => {
+            if let Some(transfer) = incoming.take() {
+                app.emit("ble-cancelled", transfer.transfer_id).ok();
+            }
+            Ok(None)
+        }
+        _ => Err("Unknown frame".to_string()),
+    }
+}
+
+fn save(app: &AppHandle, library: &SnippetLibrary, payload: PortableSnippet) -> Result<Snippet, String> {
+    library
+        .upsert_many(app, vec![payload.into_input("bluetooth")])?
+        .pop()
+        .ok_or_else(|| "Failed to save snippet".to_string())
+}
+
+/// Confirm (or reject) that the pairing code matches the other device. When this device is
+/// receiving, returns the bytes to notify to the sender.
+#[tauri::command]
+pub fn confirm_ble_pairing(state: State<'_, BleState>, transfer_id: String, accept: bool) -> Result<Option<Vec<u8>>, String> {
+    if let Some(confirm) = state.pending.lock().unwrap().remove(&transfer_id) {
+        confirm.send(accept).ok();
+        return Ok(None);
+    }
+    let mut incoming = state.incoming.lock().unwrap();
+    let transfer = incoming
+        .as_mut()
+        .filter(|t| t.transfer_id == transfer_id)
+        .ok_or_else(|| format!("Unknown transfer {}", transfer_id))?;
+    transfer.confirmed = accept;
+    if !accept {
+        *incoming = None;
+    }
+    Ok(Some(vec![DECISION, accept as u8]))
+}
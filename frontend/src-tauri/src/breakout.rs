@@ -0,0 +1,199 @@
+//! Breakout scratchpads: a private document per participant, held by the host.
+//!
+//! Only the owning participant and the host see a scratchpad. The host can look at it at any
+//! time and pull its contents into the main shared document.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::documents::{self, Document, DocumentInfo, DocumentStore};
+use crate::protocol::Message;
+use crate::session::{self, Session};
+use crate::settings::SettingsStore;
+use crate::syntax::OffsetMap;
+
+#[derive(Default)]
+pub struct Breakouts {
+    /// Participant id -> scratchpad document id.
+    scratchpads: Mutex<HashMap<String, String>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scratchpad {
+    pub participant_id: String,
+    pub participant_name: String,
+    pub document: DocumentInfo,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScratchpadUpdated {
+    participant_id: String,
+    document_id: String,
+    length: usize,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullMode {
+    Append,
+    Replace,
+    Insert,
+}
+
+impl Breakouts {
+    fn document_id(&self, participant_id: &str) -> Result<String, String> {
+        self.scratchpads
+            .lock()
+            .unwrap()
+            .get(participant_id)
+            .cloned()
+            .ok_or_else(|| format!("No scratchpad for participant {}", participant_id))
+    }
+}
+
+fn describe(store: &DocumentStore, session: &Session, participant_id: &str, doc_id: &str) -> Result<Scratchpad, String> {
+    Ok(Scratchpad {
+        participant_id: participant_id.to_string(),
+        participant_name: session.participant(participant_id)?.name,
+        document: store.with(doc_id, |doc| doc.info(doc_id))?,
+    })
+}
+
+/// Apply a participant's scratchpad edit received over the session.
+pub fn handle_scratchpad(
+    app: &AppHandle,
+    session: &Session,
+    participant_id: &str,
+    text: String,
+) -> Result<bool, String> {
+    session.participant(participant_id)?;
+    let breakouts = app.state::<Breakouts>();
+    let store = app.state::<DocumentStore>();
+    let doc_id = breakouts.document_id(participant_id)?;
+    let length = text.len();
+    store.with(&doc_id, |doc| doc.set_text(text, Some(participant_id)))??;
+    let event = ScratchpadUpdated {
+        participant_id: participant_id.to_string(),
+        document_id: doc_id,
+        length,
+    };
+    app.emit("scratchpad-updated", event).map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+/// Give a participant a private scratchpad, optionally seeded with starter text.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn open_scratchpad(
+    app: AppHandle,
+    breakouts: State<'_, Breakouts>,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    session: State<'_, Session>,
+    participant_id: String,
+    text: Option<String>,
+    language: Option<String>,
+) -> Result<Scratchpad, String> {
+    session.participant(&participant_id)?;
+    if let Ok(existing) = breakouts.document_id(&participant_id) {
+        return describe(&store, &session, &participant_id, &existing);
+    }
+    let text = text.unwrap_or_default();
+    let info = store.insert(Document::new(None, text.clone()));
+    if let Some(language) = &language {
+        documents::apply_language(&app, &store, &settings, &info.id, language)?;
+    }
+    breakouts
+        .scratchpads
+        .lock()
+        .unwrap()
+        .insert(participant_id.clone(), info.id.clone());
+    let message = Message::Scratchpad {
+        participant_id: participant_id.clone(),
+        text,
+        language,
+    };
+    session::send_to(&app, &participant_id, &message)?;
+    describe(&store, &session, &participant_id, &info.id)
+}
+
+#[tauri::command]
+pub fn list_scratchpads(
+    breakouts: State<'_, Breakouts>,
+    store: State<'_, DocumentStore>,
+    session: State<'_, Session>,
+) -> Vec<Scratchpad> {
+    let scratchpads = breakouts.scratchpads.lock().unwrap().clone();
+    let mut list: Vec<Scratchpad> = scratchpads
+        .iter()
+        .filter_map(|(participant, doc_id)| describe(&store, &session, participant, doc_id).ok())
+        .collect();
+    list.sort_by(|a, b| a.participant_name.cmp(&b.participant_name));
+    list
+}
+
+/// Current text of a participant's scratchpad, for the host to look at.
+#[tauri::command]
+pub fn view_scratchpad(
+    breakouts: State<'_, Breakouts>,
+    store: State<'_, DocumentStore>,
+    participant_id: String,
+) -> Result<String, String> {
+    let doc_id = breakouts.document_id(&participant_id)?;
+    store.with(&doc_id, |doc| doc.text.clone())
+}
+
+/// Copy a scratchpad into the main document: appended, replacing it, or inserted at
+/// `offset` (UTF-16). The change is attributed to the scratchpad's owner.
+#[tauri::command]
+pub fn pull_scratchpad(
+    breakouts: State<'_, Breakouts>,
+    store: State<'_, DocumentStore>,
+    participant_id: String,
+    target_id: String,
+    mode: PullMode,
+    offset: Option<usize>,
+) -> Result<DocumentInfo, String> {
+    let doc_id = breakouts.document_id(&participant_id)?;
+    let scratch = store.with(&doc_id, |doc| doc.text.clone())?;
+    store.with(&target_id, |doc| {
+        let text = match mode {
+            PullMode::Replace => scratch,
+            PullMode::Append => {
+                let mut text = doc.text.clone();
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                text + &scratch
+            }
+            PullMode::Insert => {
+                let at = OffsetMap::new(&doc.text).to_byte(offset.unwrap_or(0));
+                let mut text = doc.text.clone();
+                text.insert_str(at, &scratch);
+                text
+            }
+        };
+        doc.set_text(text, Some(&participant_id))?;
+        Ok(doc.info(&target_id))
+    })?
+}
+
+#[tauri::command]
+pub fn close_scratchpad(
+    breakouts: State<'_, Breakouts>,
+    store: State<'_, DocumentStore>,
+    participant_id: String,
+) -> bool {
+    match breakouts.scratchpads.lock().unwrap().remove(&participant_id) {
+        Some(doc_id) => {
+            store.docs.lock().unwrap().remove(&doc_id);
+            true
+        }
+        None => false,
+    }
+}
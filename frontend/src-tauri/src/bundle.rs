@@ -0,0 +1,306 @@
+//! Offline session bundles: the open documents and their review comments, encrypted with a
+//! passphrase into a single file that can be carried across an air gap and imported elsewhere.
+//!
+//! Unlike backups, which use a per-install key, a bundle must open on another machine, so the key
+//! is derived from the passphrase with Argon2id and a random salt stored in the header.
+//!
+//! The sealed bundle is written inside an `artifact` container so `validate_file` can check it
+//! without the passphrase. Bundles written before that, which start with the sealed header
+//! itself, still import.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::artifact::{self, Kind, Metadata};
+use crate::documents::{self, Document, DocumentInfo, DocumentStore};
+use crate::recents::{self, RecentKind};
+use crate::review::ReviewStore;
+use crate::settings::SettingsStore;
+use crate::storage;
+use crate::webhooks;
+
+const MAGIC: &[u8; 4] = b"SCOB";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+const MIN_PASSPHRASE_CHARS: usize = 8;
+/// Refuse to read anything larger; no session of plain-text documents comes close.
+const MAX_BUNDLE_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundledDocument {
+    /// Id on the exporting machine; only used to tie annotations to their document.
+    id: String,
+    /// File name without its directory, so local paths do not travel with the bundle.
+    name: Option<String>,
+    language: Option<String>,
+    text: String,
+    /// Hex sha256 of `text`.
+    sha256: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleAnnotation {
+    pub document_id: String,
+    /// File within the document, for review documents that combine several files.
+    pub path: Option<String>,
+    pub line: Option<u64>,
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    created_at: u64,
+    documents: Vec<BundledDocument>,
+    annotations: Vec<BundleAnnotation>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleInfo {
+    pub path: String,
+    pub created_at: u64,
+    pub size: u64,
+    pub documents: usize,
+    pub annotations: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedDocument {
+    /// File name on the exporting machine, to suggest when the document is first saved.
+    pub name: Option<String>,
+    pub document: DocumentInfo,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedBundle {
+    pub created_at: u64,
+    pub documents: Vec<ImportedDocument>,
+    /// Annotations with `document_id` pointing at the newly opened documents.
+    pub annotations: Vec<BundleAnnotation>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive bundle key: {}", e))?;
+    Ok(key)
+}
+
+// Layout: magic, version, salt, nonce, then the sealed JSON manifest. The header is bound to the
+// ciphertext as associated data, so a tampered version or salt fails the integrity check too.
+fn seal(passphrase: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(FORMAT_VERSION);
+    let mut random = [0u8; SALT_LEN + NONCE_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut random);
+    header.extend_from_slice(&random);
+
+    let key = derive_key(passphrase, &random[..SALT_LEN])?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let payload = chacha20poly1305::aead::Payload { msg: plaintext, aad: &header };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&random[SALT_LEN..]), payload)
+        .map_err(|_| "Failed to encrypt bundle".to_string())?;
+
+    let mut out = header;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+fn open(passphrase: &str, bundle: &[u8]) -> Result<Vec<u8>, String> {
+    if bundle.len() < HEADER_LEN || &bundle[..MAGIC.len()] != MAGIC {
+        return Err("Not a ShareCode session bundle".to_string());
+    }
+    if bundle[MAGIC.len()] != FORMAT_VERSION {
+        return Err(format!("Unsupported bundle format version {}", bundle[MAGIC.len()]));
+    }
+    let (header, ciphertext) = bundle.split_at(HEADER_LEN);
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_LEN];
+    let nonce = &header[MAGIC.len() + 1 + SALT_LEN..];
+    let key = derive_key(passphrase, salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let payload = chacha20poly1305::aead::Payload { msg: ciphertext, aad: header };
+    cipher
+        .decrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| "Bundle failed integrity check (wrong passphrase or corrupted file)".to_string())
+}
+
+fn collect(store: &DocumentStore, reviews: &ReviewStore) -> Manifest {
+    let documents: Vec<BundledDocument> = store
+        .docs
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, doc)| BundledDocument {
+            id: id.clone(),
+            name: doc
+                .path
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
+            language: doc.language.clone(),
+            text: doc.text.clone(),
+            sha256: hex(&Sha256::digest(doc.text.as_bytes())),
+        })
+        .collect();
+    // Looked up after the document lock is released; closing a review takes the locks the other way round.
+    let annotations = documents
+        .iter()
+        .flat_map(|doc| {
+            reviews.comments_for(&doc.id).into_iter().map(|c| BundleAnnotation {
+                document_id: doc.id.clone(),
+                path: c.path,
+                line: c.line,
+                author: c.author,
+                body: c.body,
+                created_at: c.created_at,
+            })
+        })
+        .collect();
+    Manifest {
+        created_at: storage::now_secs(),
+        documents,
+        annotations,
+    }
+}
+
+/// Reject anything the importer should not trust, before a single document is opened.
+fn verify(manifest: &Manifest) -> Result<(), String> {
+    for doc in &manifest.documents {
+        if hex(&Sha256::digest(doc.text.as_bytes())) != doc.sha256 {
+            return Err(format!(
+                "Checksum mismatch for {}",
+                doc.name.as_deref().unwrap_or("an untitled document")
+            ));
+        }
+        if doc.name.as_ref().is_some_and(|n| Path::new(n).file_name() != Some(OsStr::new(n))) {
+            return Err(format!("Bundle contains an unsafe file name: {}", doc.name.as_deref().unwrap_or_default()));
+        }
+    }
+    let orphan = manifest
+        .annotations
+        .iter()
+        .find(|a| !manifest.documents.iter().any(|d| d.id == a.document_id));
+    if let Some(annotation) = orphan {
+        return Err(format!("Annotation refers to unknown document {}", annotation.document_id));
+    }
+    Ok(())
+}
+
+/// Write every open document and its annotations to `path`, encrypted with `passphrase`.
+#[tauri::command]
+pub fn export_offline_bundle(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    reviews: State<'_, ReviewStore>,
+    path: String,
+    passphrase: String,
+) -> Result<BundleInfo, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!("Passphrase must be at least {} characters", MIN_PASSPHRASE_CHARS));
+    }
+    let manifest = collect(&store, &reviews);
+    if manifest.documents.is_empty() {
+        return Err("No open documents to export".to_string());
+    }
+    let plaintext = serde_json::to_vec(&manifest).map_err(|e| e.to_string())?;
+    let metadata = Metadata::from([("encryption".to_string(), "argon2id-xchacha20poly1305".to_string())]);
+    let bundle = artifact::encode(Kind::OfflineBundle, &metadata, &seal(&passphrase, &plaintext)?)?;
+
+    // Removable drives are often pulled mid-write; never leave a truncated bundle under the real name.
+    let target = Path::new(&path);
+    let tmp = target.with_extension("tmp");
+    fs::write(&tmp, &bundle).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, target).map_err(|e| format!("Failed to finalize {}: {}", path, e))?;
+    webhooks::export_created(&app, "offline-bundle", target);
+
+    Ok(BundleInfo {
+        path,
+        created_at: manifest.created_at,
+        size: bundle.len() as u64,
+        documents: manifest.documents.len(),
+        annotations: manifest.annotations.len(),
+    })
+}
+
+/// Decrypt and verify a bundle, then open its documents as new untitled documents.
+#[tauri::command]
+pub fn import_offline_bundle(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    passphrase: String,
+) -> Result<ImportedBundle, String> {
+    let size = fs::metadata(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?.len();
+    if size > MAX_BUNDLE_BYTES {
+        return Err("Bundle is too large".to_string());
+    }
+    let bundle = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let sealed = if artifact::is_container(&bundle) {
+        artifact::decode(&bundle, Kind::OfflineBundle)?
+    } else {
+        &bundle[..]
+    };
+    let manifest: Manifest = serde_json::from_slice(&open(&passphrase, sealed)?)
+        .map_err(|e| format!("Bundle manifest is invalid: {}", e))?;
+    verify(&manifest)?;
+    recents::note(&app, Path::new(&path), RecentKind::Bundle);
+
+    let mut documents = Vec::new();
+    let mut ids = Vec::new();
+    for bundled in manifest.documents {
+        let info = store.insert(Document::new(None, bundled.text));
+        let info = match bundled.language {
+            Some(language) => {
+                documents::apply_language(&app, &store, &settings, &info.id, &language)?;
+                store.with(&info.id, |doc| doc.info(&info.id))?
+            }
+            None => info,
+        };
+        ids.push((bundled.id, info.id.clone()));
+        documents.push(ImportedDocument {
+            name: bundled.name,
+            document: info,
+        });
+    }
+    let annotations = manifest
+        .annotations
+        .into_iter()
+        .filter_map(|mut annotation| {
+            let (_, new_id) = ids.iter().find(|(old, _)| *old == annotation.document_id)?;
+            annotation.document_id = new_id.clone();
+            Some(annotation)
+        })
+        .collect();
+
+    Ok(ImportedBundle {
+        created_at: manifest.created_at,
+        documents,
+        annotations,
+    })
+}
@@ -0,0 +1,110 @@
+//! Intra-project call graph built from the indexer's call sites.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::indexer::{split_qualified, ProjectIndex, SymbolLocation};
+use crate::syntax::TextRange;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallEdge {
+    /// The other end of the edge: the caller for `get_callers`, the callee for `get_callees`.
+    pub name: String,
+    pub container: Option<String>,
+    /// Where the call happens.
+    pub file: String,
+    pub call_range: TextRange,
+    /// Best-ranked definitions of the other end within the imported project; empty for
+    /// library or builtin functions.
+    pub definitions: Vec<SymbolLocation>,
+}
+
+fn matches_caller(qualifier: Option<&str>, name: &str, caller: Option<&str>, container: Option<&str>) -> bool {
+    caller == Some(name) && (qualifier.is_none() || container == qualifier)
+}
+
+/// Call sites that invoke `symbol`, grouped by the calling function.
+#[tauri::command]
+pub fn get_callers(index: State<'_, Arc<ProjectIndex>>, symbol: String) -> Vec<CallEdge> {
+    let (_, name) = split_qualified(&symbol);
+    let sites: Vec<(PathBuf, _)> = {
+        let files = index.files.read().unwrap();
+        files
+            .iter()
+            .flat_map(|(path, indexed)| {
+                indexed
+                    .calls
+                    .iter()
+                    .filter(|c| c.callee == name)
+                    .map(move |c| (path.clone(), c.clone()))
+            })
+            .collect()
+    };
+    let mut edges: Vec<CallEdge> = sites
+        .into_iter()
+        .map(|(path, site)| {
+            let caller = site.caller.clone().unwrap_or_else(|| "<top level>".to_string());
+            let qualified = match &site.caller_container {
+                Some(container) => format!("{}::{}", container, caller),
+                None => caller.clone(),
+            };
+            let definitions = match site.caller {
+                Some(_) => index
+                    .find_definitions(&qualified, Some(&path))
+                    .into_iter()
+                    .filter(|d| d.file == path.to_string_lossy())
+                    .take(1)
+                    .collect(),
+                None => Vec::new(),
+            };
+            CallEdge {
+                name: caller,
+                container: site.caller_container,
+                file: path.to_string_lossy().into_owned(),
+                call_range: site.range,
+                definitions,
+            }
+        })
+        .collect();
+    edges.sort_by(|a, b| a.file.cmp(&b.file).then(a.call_range.start_byte.cmp(&b.call_range.start_byte)));
+    edges
+}
+
+/// Calls made from inside `symbol` (`name` or `Container::name`), in source order.
+#[tauri::command]
+pub fn get_callees(index: State<'_, Arc<ProjectIndex>>, symbol: String) -> Vec<CallEdge> {
+    let (qualifier, name) = split_qualified(&symbol);
+    let sites: Vec<(PathBuf, _)> = {
+        let files = index.files.read().unwrap();
+        files
+            .iter()
+            .flat_map(|(path, indexed)| {
+                indexed
+                    .calls
+                    .iter()
+                    .filter(|c| matches_caller(qualifier, name, c.caller.as_deref(), c.caller_container.as_deref()))
+                    .map(move |c| (path.clone(), c.clone()))
+            })
+            .collect()
+    };
+    let mut edges: Vec<CallEdge> = sites
+        .into_iter()
+        .map(|(path, site)| {
+            // Same-file definitions rank first, which is how most languages resolve unqualified calls.
+            let definitions = index.find_definitions(&site.callee, Some(&path)).into_iter().take(3).collect();
+            CallEdge {
+                name: site.callee,
+                container: None,
+                file: path.to_string_lossy().into_owned(),
+                call_range: site.range,
+                definitions,
+            }
+        })
+        .collect();
+    edges.sort_by(|a, b| a.file.cmp(&b.file).then(a.call_range.start_byte.cmp(&b.call_range.start_byte)));
+    edges
+}
@@ -0,0 +1,116 @@
+//! Opt-in clipboard watching: noticing that code was copied anywhere on the system, so the
+//! frontend can offer to import it as a snippet.
+//!
+//! Nothing is read until `start_clipboard_watch`, and watching stops with
+//! `stop_clipboard_watch` or on quit; it is never turned on by itself. The clipboard is polled,
+//! as no platform arboard supports reports changes. A copy is only looked at once the clipboard
+//! has held it for `SETTLE`, so a burst of copies is reported once, for the last of them. Text
+//! `language::detect` recognizes is emitted as `clipboard-code`; anything else is ignored, as is
+//! whatever was on the clipboard when watching started.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::language::{self, LanguageGuess};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a copy must stay on the clipboard to be looked at.
+const SETTLE: Duration = Duration::from_millis(750);
+/// Shorter text is too little to tell code from prose.
+const MIN_LENGTH: usize = 16;
+/// Longer text is more than anyone means as a snippet.
+const MAX_LENGTH: usize = 256 * 1024;
+
+#[derive(Default)]
+pub struct ClipboardWatch {
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ClipboardCode {
+    text: String,
+    guess: LanguageGuess,
+}
+
+fn read(clipboard: &mut arboard::Clipboard) -> Option<String> {
+    // Fails while the clipboard holds no text, e.g. an image
+    clipboard.get_text().ok()
+}
+
+fn check(app: &AppHandle, text: &str) {
+    let trimmed = text.trim();
+    if trimmed.len() < MIN_LENGTH || trimmed.len() > MAX_LENGTH {
+        return;
+    }
+    let guess = language::detect(trimmed);
+    if guess.language.is_none() {
+        return;
+    }
+    let code = ClipboardCode {
+        text: trimmed.to_string(),
+        guess,
+    };
+    if let Err(e) = app.emit("clipboard-code", code) {
+        log::warn!("Failed to emit clipboard-code: {}", e);
+    }
+}
+
+fn watch(app: AppHandle, stop: Arc<AtomicBool>) {
+    // Opened on this thread, as it cannot be sent between threads on every platform
+    let mut clipboard = match arboard::Clipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(e) => {
+            log::warn!("Stopped watching the clipboard: {}", e);
+            return;
+        }
+    };
+    let mut seen = read(&mut clipboard);
+    // The copy waiting to settle, and when it was first seen
+    let mut waiting: Option<(String, Instant)> = None;
+    while !stop.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        let current = read(&mut clipboard);
+        if current != seen {
+            seen = current.clone();
+            waiting = current.map(|text| (text, Instant::now()));
+            continue;
+        }
+        if waiting.as_ref().is_some_and(|(_, since)| since.elapsed() >= SETTLE) {
+            let (text, _) = waiting.take().unwrap();
+            check(&app, &text);
+        }
+    }
+}
+
+/// Start watching the clipboard for code. Returns false if it already was.
+#[tauri::command]
+pub fn start_clipboard_watch(app: AppHandle, state: State<'_, ClipboardWatch>) -> Result<bool, String> {
+    let mut running = state.stop.lock().unwrap();
+    if running.is_some() {
+        return Ok(false);
+    }
+    // Fails here rather than silently on the watching thread
+    arboard::Clipboard::new().map_err(|e| format!("Failed to open the clipboard: {}", e))?;
+    let stop = Arc::new(AtomicBool::new(false));
+    *running = Some(stop.clone());
+    thread::spawn(move || watch(app, stop));
+    Ok(true)
+}
+
+/// Stop watching the clipboard. Returns false if it wasn't being watched.
+#[tauri::command]
+pub fn stop_clipboard_watch(state: State<'_, ClipboardWatch>) -> bool {
+    match state.stop.lock().unwrap().take() {
+        Some(stop) => {
+            stop.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
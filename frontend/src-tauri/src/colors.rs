@@ -0,0 +1,240 @@
+//! Participant colours that stay distinguishable for colour-blind users.
+//!
+//! Palettes are chosen from the Okabe–Ito and Paul Tol sets: starting from the first candidate,
+//! each next colour is the one farthest (CIELAB ΔE) from those already picked as seen through the
+//! selected colour-vision profile, and picking stops once the closest pair would fall under
+//! `MIN_DELTA_E`. Each participant's slot comes from a hash of their id, so the same person
+//! keeps the same colour across sessions unless someone present already has it.
+
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+
+use crate::export::Rgb;
+use crate::session::{self, Session};
+use crate::storage;
+
+const CONFIG_FILE: &str = "colors.json";
+/// Closest two palette colours may be, as the profile sees them.
+const MIN_DELTA_E: f64 = 18.0;
+/// Cursor colours must show on both light and dark editors.
+const LIGHTNESS: std::ops::RangeInclusive<f64> = 35.0..=80.0;
+
+const CANDIDATES: [Rgb; 23] = [
+    // Okabe–Ito, without black.
+    [0x00, 0x72, 0xb2],
+    [0xe6, 0x9f, 0x00],
+    [0x00, 0x9e, 0x73],
+    [0xcc, 0x79, 0xa7],
+    [0x56, 0xb4, 0xe9],
+    [0xd5, 0x5e, 0x00],
+    [0xf0, 0xe4, 0x42],
+    // Tol bright.
+    [0x44, 0x77, 0xaa],
+    [0xee, 0x66, 0x77],
+    [0x22, 0x88, 0x33],
+    [0xcc, 0xbb, 0x44],
+    [0x66, 0xcc, 0xee],
+    [0xaa, 0x33, 0x77],
+    [0xbb, 0xbb, 0xbb],
+    // Tol muted.
+    [0x33, 0x22, 0x88],
+    [0x88, 0xcc, 0xee],
+    [0x44, 0xaa, 0x99],
+    [0x11, 0x77, 0x33],
+    [0x99, 0x99, 0x33],
+    [0xdd, 0xcc, 0x77],
+    [0xcc, 0x66, 0x77],
+    [0x88, 0x22, 0x55],
+    [0xaa, 0x44, 0x99],
+];
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfile {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ColorConfig {
+    profile: ColorProfile,
+}
+
+#[derive(Default)]
+pub struct ColorSettings {
+    profile: Mutex<ColorProfile>,
+}
+
+impl ColorSettings {
+    pub fn load(app: &AppHandle) -> Self {
+        let config: ColorConfig = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+            log::warn!("Using default colour palette: {}", e);
+            ColorConfig::default()
+        });
+        Self {
+            profile: Mutex::new(config.profile),
+        }
+    }
+
+    pub fn profile(&self) -> ColorProfile {
+        *self.profile.lock().unwrap()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantColor {
+    /// Cursor and name tag.
+    pub color: String,
+    /// Translucent, for selections.
+    pub color_light: String,
+}
+
+fn linear(channel: u8) -> f64 {
+    let c = channel as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Full-severity dichromacy simulation (Machado, Oliveira and Fernandes, 2009) in linear RGB.
+fn simulate(rgb: [f64; 3], profile: ColorProfile) -> [f64; 3] {
+    let m = match profile {
+        ColorProfile::Standard => return rgb,
+        ColorProfile::Deuteranopia => [
+            [0.367322, 0.860646, -0.227968],
+            [0.280085, 0.672501, 0.047413],
+            [-0.011820, 0.042940, 0.968881],
+        ],
+        ColorProfile::Protanopia => [
+            [0.152286, 1.052583, -0.204868],
+            [0.114503, 0.786281, 0.099216],
+            [-0.003882, -0.048116, 1.051998],
+        ],
+    };
+    m.map(|row| (row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]).clamp(0.0, 1.0))
+}
+
+/// CIELAB (D65) of a linear RGB colour.
+fn lab([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+fn perceived(color: Rgb, profile: ColorProfile) -> [f64; 3] {
+    lab(simulate(color.map(linear), profile))
+}
+
+fn delta_e(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn build_palette(profile: ColorProfile) -> Vec<Rgb> {
+    let mut candidates: Vec<(Rgb, [f64; 3])> = CANDIDATES
+        .iter()
+        .filter(|&&c| LIGHTNESS.contains(&lab(c.map(linear))[0]))
+        .map(|&c| (c, perceived(c, profile)))
+        .collect();
+    let mut palette = vec![candidates.remove(0)];
+    while !candidates.is_empty() {
+        let (best, distance) = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (_, seen))| (i, palette.iter().map(|(_, p)| delta_e(*seen, *p)).fold(f64::MAX, f64::min)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        if distance < MIN_DELTA_E {
+            break;
+        }
+        palette.push(candidates.remove(best));
+    }
+    palette.into_iter().map(|(c, _)| c).collect()
+}
+
+/// The palette for `profile`, most distinct colours first.
+pub fn palette(profile: ColorProfile) -> &'static [Rgb] {
+    static PALETTES: [OnceLock<Vec<Rgb>>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+    PALETTES[profile as usize].get_or_init(|| build_palette(profile))
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// The colour for participant `key`: its own slot, or the next one nobody in `taken` has.
+/// When every colour is in use, colours repeat.
+fn pick_rgb(profile: ColorProfile, key: &str, taken: &[String]) -> Rgb {
+    let palette = palette(profile);
+    let hash = Sha256::digest(key.as_bytes());
+    let slot = u64::from_be_bytes(hash[..8].try_into().unwrap()) as usize % palette.len();
+    (0..palette.len())
+        .map(|i| palette[(slot + i) % palette.len()])
+        .find(|&color| !taken.contains(&hex(color)))
+        .unwrap_or(palette[slot])
+}
+
+pub fn pick(profile: ColorProfile, key: &str, taken: &[String]) -> String {
+    hex(pick_rgb(profile, key, taken))
+}
+
+/// The colour for a participant joining now, avoiding those already present.
+pub fn assign(app: &AppHandle, key: &str, taken: &[String]) -> String {
+    pick(app.state::<ColorSettings>().profile(), key, taken)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorPalette {
+    pub profile: ColorProfile,
+    pub colors: Vec<String>,
+}
+
+impl ColorPalette {
+    fn new(profile: ColorProfile) -> Self {
+        Self {
+            profile,
+            colors: palette(profile).iter().map(|&c| hex(c)).collect(),
+        }
+    }
+}
+
+/// Colours for someone outside the session list, e.g. a collaborator in the web editor.
+#[tauri::command]
+pub fn get_participant_color(settings: State<'_, ColorSettings>, key: String) -> ParticipantColor {
+    let [r, g, b] = pick_rgb(settings.profile(), &key, &[]);
+    ParticipantColor {
+        color: hex([r, g, b]),
+        color_light: format!("rgba({}, {}, {}, 0.35)", r, g, b),
+    }
+}
+
+#[tauri::command]
+pub fn get_color_palette(settings: State<'_, ColorSettings>) -> ColorPalette {
+    ColorPalette::new(settings.profile())
+}
+
+/// Switch palettes and recolour everyone in the session.
+#[tauri::command]
+pub fn set_color_palette(
+    app: AppHandle,
+    settings: State<'_, ColorSettings>,
+    session: State<'_, Session>,
+    profile: ColorProfile,
+) -> Result<ColorPalette, String> {
+    storage::save_json(&app, CONFIG_FILE, &ColorConfig { profile })?;
+    *settings.profile.lock().unwrap() = profile;
+    session::recolor(&app, &session, profile)?;
+    Ok(ColorPalette::new(profile))
+}
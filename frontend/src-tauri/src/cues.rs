@@ -0,0 +1,336 @@
+//! Short audio cues for session events (a viewer joining, a raised hand, the connection to a host
+//! dropping), played natively because webview audio is throttled or silenced while the window is
+//! hidden, which is when a presenter needs them.
+//!
+//! Each cue is a couple of synthesised tones played by a worker thread through rodio, at its own
+//! volume. While a meeting app is using the microphone they are skipped, so they never end up in
+//! the call: Windows reports which apps hold the microphone, PulseAudio and PipeWire list their
+//! recording streams, and macOS only says whether the microphone is in use at all, so there it
+//! counts as a meeting when one of the apps is also running.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+#[cfg(desktop)]
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::storage;
+
+const CONFIG_FILE: &str = "audio-cues.json";
+
+/// Lower-case fragments of the names of apps that put the microphone into a call. Browsers are in
+/// for Meet and other web meetings.
+#[cfg(desktop)]
+const MEETING_APPS: [&str; 12] = [
+    "zoom", "teams", "webex", "slack", "discord", "skype", "facetime", "chrome", "firefox", "msedge", "safari",
+    "meet",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioCue {
+    ViewerJoined,
+    HandRaised,
+    ConnectionLost,
+}
+
+impl AudioCue {
+    /// Tones in Hz and how long each lasts in ms: rising for arrivals, falling for losses.
+    #[cfg(desktop)]
+    fn tones(self) -> &'static [(f32, u64)] {
+        match self {
+            Self::ViewerJoined => &[(660.0, 90), (880.0, 120)],
+            Self::HandRaised => &[(1175.0, 140)],
+            Self::ConnectionLost => &[(523.0, 120), (392.0, 180)],
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AudioCueConfig {
+    pub enabled: bool,
+    /// 0 to 1 per cue; cues not listed play at `DEFAULT_VOLUME`, and 0 turns a cue off.
+    pub volumes: HashMap<AudioCue, f32>,
+    pub mute_during_meetings: bool,
+}
+
+const DEFAULT_VOLUME: f32 = 0.4;
+
+impl Default for AudioCueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volumes: HashMap::new(),
+            mute_during_meetings: true,
+        }
+    }
+}
+
+impl AudioCueConfig {
+    fn volume(&self, cue: AudioCue) -> f32 {
+        self.volumes.get(&cue).copied().unwrap_or(DEFAULT_VOLUME)
+    }
+}
+
+struct Request {
+    cue: AudioCue,
+    volume: f32,
+    /// Previews from the settings page play even during a meeting.
+    check_meeting: bool,
+}
+
+#[derive(Default)]
+pub struct AudioCues {
+    config: Mutex<AudioCueConfig>,
+    muted: AtomicBool,
+    worker: Mutex<Option<Sender<Request>>>,
+}
+
+impl AudioCues {
+    pub fn load(app: &AppHandle) -> Self {
+        let config = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+            log::warn!("Using default audio cue config: {}", e);
+            AudioCueConfig::default()
+        });
+        Self {
+            config: Mutex::new(config),
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> AudioCueConfig {
+        self.config.lock().unwrap().clone()
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn microphone_users() -> Vec<String> {
+    use windows::core::{w, PCWSTR, PWSTR};
+    use windows::Win32::System::Registry::{
+        RegCloseKey, RegEnumKeyExW, RegGetValueW, RegOpenKeyExW, HKEY, HKEY_CURRENT_USER, KEY_READ, RRF_RT_REG_QWORD,
+    };
+
+    // Every app that asked for the microphone has a key here; `LastUsedTimeStop` is 0 while it
+    // still holds it. Desktop apps are under `NonPackaged`, Store apps directly.
+    let stores = [
+        w!("Software\\Microsoft\\Windows\\CurrentVersion\\CapabilityAccessManager\\ConsentStore\\microphone"),
+        w!("Software\\Microsoft\\Windows\\CurrentVersion\\CapabilityAccessManager\\ConsentStore\\microphone\\NonPackaged"),
+    ];
+    let mut users = Vec::new();
+    for store in stores {
+        let mut key = HKEY::default();
+        unsafe {
+            if RegOpenKeyExW(HKEY_CURRENT_USER, store, 0, KEY_READ, &mut key).is_err() {
+                continue;
+            }
+            let mut index = 0;
+            loop {
+                let mut name = [0u16; 512];
+                let mut len = name.len() as u32;
+                let listed = RegEnumKeyExW(key, index, PWSTR(name.as_mut_ptr()), &mut len, None, PWSTR::null(), None, None);
+                if listed.is_err() {
+                    break;
+                }
+                index += 1;
+                let mut stopped = 0u64;
+                let mut size = std::mem::size_of::<u64>() as u32;
+                let read = RegGetValueW(
+                    key,
+                    PCWSTR(name.as_ptr()),
+                    w!("LastUsedTimeStop"),
+                    RRF_RT_REG_QWORD,
+                    None,
+                    Some(&mut stopped as *mut u64 as *mut _),
+                    Some(&mut size),
+                );
+                if read.is_ok() && stopped == 0 {
+                    users.push(String::from_utf16_lossy(&name[..len as usize]));
+                }
+            }
+            let _ = RegCloseKey(key);
+        }
+    }
+    users
+}
+
+#[cfg(target_os = "macos")]
+fn microphone_users() -> Vec<String> {
+    #[repr(C)]
+    struct PropertyAddress {
+        selector: u32,
+        scope: u32,
+        element: u32,
+    }
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const std::ffi::c_void,
+            size: *mut u32,
+            data: *mut std::ffi::c_void,
+        ) -> i32;
+    }
+    const SYSTEM_OBJECT: u32 = 1;
+    const GLOBAL: u32 = u32::from_be_bytes(*b"glob");
+    let property = |object: u32, selector: &[u8; 4]| -> Option<u32> {
+        let address = PropertyAddress {
+            selector: u32::from_be_bytes(*selector),
+            scope: GLOBAL,
+            element: 0,
+        };
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            AudioObjectGetPropertyData(object, &address, 0, std::ptr::null(), &mut size, &mut value as *mut u32 as *mut _)
+        };
+        (status == 0).then_some(value)
+    };
+
+    // The default input device, and whether any process is recording from it
+    let in_use = property(SYSTEM_OBJECT, b"dIn ").and_then(|device| property(device, b"gone")) == Some(1);
+    if !in_use {
+        return Vec::new();
+    }
+    crate::share_detector::process_names()
+}
+
+#[cfg(all(desktop, not(any(target_os = "windows", target_os = "macos"))))]
+fn microphone_users() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("pactl").args(["list", "source-outputs"]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("application.process.binary") || line.starts_with("application.name"))
+        .filter_map(|line| line.split_once('=').map(|(_, value)| value.trim().trim_matches('"').to_string()))
+        .collect()
+}
+
+#[cfg(desktop)]
+fn in_meeting() -> bool {
+    microphone_users().iter().any(|user| {
+        let user = user.to_lowercase();
+        MEETING_APPS.iter().any(|app| user.contains(app))
+    })
+}
+
+#[cfg(desktop)]
+fn play(cue: AudioCue, volume: f32) -> Result<(), String> {
+    use rodio::source::{SineWave, Source};
+    use rodio::{OutputStream, Sink};
+
+    // Opened per cue, so a headset plugged in since the last one is used
+    let (_stream, output) = OutputStream::try_default().map_err(|e| format!("No audio output: {}", e))?;
+    let sink = Sink::try_new(&output).map_err(|e| format!("Failed to open audio output: {}", e))?;
+    for &(frequency, ms) in cue.tones() {
+        let tone = SineWave::new(frequency)
+            .take_duration(Duration::from_millis(ms))
+            .fade_in(Duration::from_millis(10))
+            .amplify(volume * 0.5);
+        sink.append(tone);
+    }
+    sink.sleep_until_end();
+    Ok(())
+}
+
+#[cfg(mobile)]
+fn play(_cue: AudioCue, _volume: f32) -> Result<(), String> {
+    Err("Audio cues are not supported on this platform".to_string())
+}
+
+#[cfg(mobile)]
+fn in_meeting() -> bool {
+    false
+}
+
+fn run_worker(app: AppHandle, rx: Receiver<Request>) {
+    let cues = app.state::<AudioCues>();
+    for request in rx {
+        if cues.muted.load(Ordering::Relaxed) {
+            continue;
+        }
+        if request.check_meeting && cues.config().mute_during_meetings && in_meeting() {
+            continue;
+        }
+        if let Err(e) = play(request.cue, request.volume) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+fn queue(app: &AppHandle, request: Request) {
+    let cues = app.state::<AudioCues>();
+    let mut worker = cues.worker.lock().unwrap();
+    let tx = worker.get_or_insert_with(|| {
+        let (tx, rx) = mpsc::channel();
+        let app = app.clone();
+        thread::spawn(move || run_worker(app, rx));
+        tx
+    });
+    if tx.send(request).is_err() {
+        // The worker died; start another next time.
+        worker.take();
+    }
+}
+
+/// Play `cue` if cues are on and it has a volume.
+pub fn sound(app: &AppHandle, cue: AudioCue) {
+    let cues = app.state::<AudioCues>();
+    let config = cues.config();
+    let volume = config.volume(cue);
+    if !config.enabled || volume <= 0.0 || cues.muted.load(Ordering::Relaxed) {
+        return;
+    }
+    queue(
+        app,
+        Request {
+            cue,
+            volume,
+            check_meeting: true,
+        },
+    );
+}
+
+#[tauri::command]
+pub fn get_audio_cue_config(cues: State<'_, AudioCues>) -> AudioCueConfig {
+    cues.config()
+}
+
+#[tauri::command]
+pub fn set_audio_cue_config(app: AppHandle, cues: State<'_, AudioCues>, config: AudioCueConfig) -> Result<(), String> {
+    if config.volumes.values().any(|volume| !(0.0..=1.0).contains(volume)) {
+        return Err("Volumes must be between 0 and 1".to_string());
+    }
+    storage::save_json(&app, CONFIG_FILE, &config)?;
+    *cues.config.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Silence cues until unmuted. Not persisted.
+#[tauri::command]
+pub fn set_audio_cues_muted(cues: State<'_, AudioCues>, muted: bool) {
+    cues.muted.store(muted, Ordering::Relaxed);
+}
+
+/// Play a cue at `volume` (its configured one if omitted), for trying volumes out.
+#[tauri::command]
+pub fn preview_audio_cue(app: AppHandle, cues: State<'_, AudioCues>, cue: AudioCue, volume: Option<f32>) {
+    let volume = volume.unwrap_or_else(|| cues.config().volume(cue)).clamp(0.0, 1.0);
+    queue(
+        &app,
+        Request {
+            cue,
+            volume,
+            check_meeting: false,
+        },
+    );
+}
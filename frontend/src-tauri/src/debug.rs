@@ -0,0 +1,132 @@
+//! Debug console: `debug_execute` answers a handful of commands with JSON dumps of internal state
+//! that users can attach to bug reports. Off unless developer mode is switched on in settings.
+//!
+//! Dumps give counts, sizes and flags only. Document text, participant names and session tokens
+//! are left out, since the output is meant to be pasted into public issues.
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Manager, State};
+
+use crate::documents::DocumentStore;
+use crate::events::Events;
+use crate::highlight::{self, Highlights};
+use crate::server::SessionServer;
+use crate::session::{Role, Session};
+use crate::settings::SettingsStore;
+use crate::sharing::{self, SharingHub};
+use crate::viewer::ViewerClient;
+use crate::workers::{self, WorkerPool};
+use crate::{framebuf, transparency};
+
+const COMMANDS: [(&str, &str); 6] = [
+    ("help", "List the commands"),
+    ("windows", "Visibility, focus and privacy flags of each window"),
+    ("session", "Participants by role, the session server and any remote session"),
+    ("queues", "Pending events, worker tasks and viewer fan-out"),
+    ("caches", "Open documents, highlighting, late-joiner snapshots and frame pipelines"),
+    ("all", "Everything above"),
+];
+
+fn windows(app: &AppHandle) -> Value {
+    let windows: Vec<Value> = app
+        .webview_windows()
+        .values()
+        .map(|window| {
+            #[cfg(desktop)]
+            let (capture_protection, click_through) =
+                (crate::read_capture_protection(window).ok(), crate::read_click_through(window).ok());
+            #[cfg(not(desktop))]
+            let (capture_protection, click_through): (Option<()>, Option<bool>) = (None, None);
+            json!({
+                "label": window.label(),
+                "visible": window.is_visible().ok(),
+                "focused": window.is_focused().ok(),
+                "minimized": window.is_minimized().ok(),
+                "maximized": window.is_maximized().ok(),
+                "captureProtection": capture_protection,
+                "clickThrough": click_through,
+            })
+        })
+        .collect();
+    json!({
+        "windows": windows,
+        "privacyFeatures": transparency::active_features(app),
+    })
+}
+
+fn session(app: &AppHandle) -> Value {
+    let participants = app.state::<Session>().participants();
+    let count = |role: Role| participants.iter().filter(|p| p.role == role).count();
+    let server = app.state::<SessionServer>().url().is_some();
+    let remote = app.state::<ViewerClient>().remote();
+    json!({
+        "participants": {
+            "host": count(Role::Host),
+            "editor": count(Role::Editor),
+            "viewer": count(Role::Viewer),
+            "handsRaised": participants.iter().filter(|p| p.hand_raised).count(),
+        },
+        "serverRunning": server,
+        "remoteSession": remote.map(|(base, participant_id)| json!({ "host": base, "participantId": participant_id })),
+    })
+}
+
+fn queues(app: &AppHandle) -> Value {
+    let hub = app.state::<Arc<SharingHub>>();
+    json!({
+        "pendingEvents": app.state::<Events>().pending_count(),
+        "workers": workers::get_worker_stats(app.state::<WorkerPool>()),
+        "fanOut": sharing::get_room_capacity_estimate(hub.clone(), None),
+        "viewersAwaitingResync": hub.resyncs_pending(),
+    })
+}
+
+fn caches(app: &AppHandle) -> Value {
+    let store = app.state::<DocumentStore>();
+    let (documents, text_bytes) = {
+        let docs = store.docs.lock().unwrap();
+        (docs.len(), docs.values().map(|doc| doc.text.len()).sum::<usize>())
+    };
+    json!({
+        "documents": { "open": documents, "textBytes": text_bytes },
+        "highlight": highlight::get_highlight_stats(app.state::<Highlights>()),
+        "snapshots": sharing::get_sync_stats(app.state::<Arc<SharingHub>>(), store),
+        "framePipelines": framebuf::get_frame_stats(),
+    })
+}
+
+/// Run a console command, e.g. `session`, and return its dump.
+#[tauri::command]
+pub fn debug_execute(app: AppHandle, settings: State<'_, SettingsStore>, command: String) -> Result<Value, String> {
+    if !settings.get(&app)?.developer_mode {
+        return Err("Turn on developer mode to use the debug console".to_string());
+    }
+    Ok(match command.trim() {
+        "help" => json!(COMMANDS.map(|(name, about)| json!({ "command": name, "description": about }))),
+        "windows" => windows(&app),
+        "session" => session(&app),
+        "queues" => queues(&app),
+        "caches" => caches(&app),
+        "all" => json!({
+            "version": app.package_info().version.to_string(),
+            "os": std::env::consts::OS,
+            "windows": windows(&app),
+            "session": session(&app),
+            "queues": queues(&app),
+            "caches": caches(&app),
+        }),
+        other => return Err(format!("Unknown command {:?}; try \"help\"", other)),
+    })
+}
+
+#[tauri::command]
+pub fn get_developer_mode(app: AppHandle, settings: State<'_, SettingsStore>) -> Result<bool, String> {
+    Ok(settings.get(&app)?.developer_mode)
+}
+
+#[tauri::command]
+pub fn set_developer_mode(app: AppHandle, settings: State<'_, SettingsStore>, enabled: bool) -> Result<(), String> {
+    settings.update(&app, |s| s.developer_mode = enabled)
+}
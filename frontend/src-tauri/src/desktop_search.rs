@@ -0,0 +1,125 @@
+//! Making saved snippets findable from Spotlight and Windows Search.
+//!
+//! Both index plain text files in the user's Documents folder, so each snippet gets a small text
+//! sidecar there, in `ShareCode Snippets`, named after its title and holding its title, tags,
+//! language and description. The body is left out: it may hold code the user would not want in the
+//! system index, and the title and tags are what people search by. Linux desktop search (Tracker,
+//! Baloo) picks the same files up where it is enabled.
+//!
+//! The folder is kept in step with the library rather than edited file by file: after every change
+//! to the library, and at startup to catch a restored backup, sidecars of deleted snippets are
+//! removed and changed ones rewritten. Only `*.snippet.txt` files in that folder are touched.
+//! Turning indexing off removes them all, and the folder with them if nothing else is in it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use tauri::{AppHandle, Manager, State};
+
+use crate::dragout::file_stem;
+use crate::settings::SettingsStore;
+use crate::snippets::{Snippet, SnippetLibrary};
+
+const FOLDER_NAME: &str = "ShareCode Snippets";
+const SIDECAR_SUFFIX: &str = ".snippet.txt";
+
+fn folder(app: &AppHandle) -> Result<PathBuf, String> {
+    let documents = app
+        .path()
+        .document_dir()
+        .map_err(|e| format!("Failed to resolve the documents directory: {}", e))?;
+    Ok(documents.join(FOLDER_NAME))
+}
+
+/// Titles needn't be unique, so part of the id goes into the name.
+fn sidecar_name(snippet: &Snippet) -> String {
+    let id: String = snippet.id.chars().filter(|c| c.is_ascii_alphanumeric()).take(8).collect();
+    format!("{} ({}){}", file_stem(&snippet.title), id, SIDECAR_SUFFIX)
+}
+
+fn sidecar(snippet: &Snippet) -> String {
+    let mut out = format!("{}\n", snippet.title);
+    if !snippet.tags.is_empty() {
+        out.push_str(&format!("Tags: {}\n", snippet.tags.join(", ")));
+    }
+    if let Some(prefix) = &snippet.prefix {
+        out.push_str(&format!("Prefix: {}\n", prefix));
+    }
+    if let Some(language) = &snippet.language {
+        out.push_str(&format!("Language: {}\n", language));
+    }
+    if let Some(description) = &snippet.description {
+        out.push_str(&format!("\n{}\n", description));
+    }
+    out.push_str("\nA ShareCode snippet. Open ShareCode to use it.\n");
+    out
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.state::<SettingsStore>().get(app).is_ok_and(|settings| !settings.skip_snippet_indexing)
+}
+
+/// Bring the sidecars in line with `snippets`, or remove them all when indexing is off.
+pub fn sync(app: &AppHandle, snippets: &[Snippet]) -> Result<(), String> {
+    let dir = folder(app)?;
+    let indexing = enabled(app);
+    let mut wanted: HashMap<String, String> = HashMap::new();
+    if indexing {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        wanted.extend(snippets.iter().map(|snippet| (sidecar_name(snippet), sidecar(snippet))));
+    } else if !dir.exists() {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(SIDECAR_SUFFIX) {
+            continue;
+        }
+        match wanted.get(&name) {
+            // Left alone when unchanged, so the indexer doesn't redo it
+            Some(contents) if fs::read_to_string(entry.path()).is_ok_and(|old| &old == contents) => {
+                wanted.remove(&name);
+            }
+            Some(_) => {}
+            None => {
+                fs::remove_file(entry.path())
+                    .map_err(|e| format!("Failed to remove {}: {}", entry.path().display(), e))?;
+            }
+        }
+    }
+    for (name, contents) in wanted {
+        let path = dir.join(name);
+        fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+
+    if !indexing {
+        // Fails, as it should, if the user put anything else in the folder
+        fs::remove_dir(&dir).ok();
+    }
+    Ok(())
+}
+
+/// Sync against the library as it is on disk, logging rather than returning a failure.
+pub fn refresh(app: &AppHandle) {
+    let synced = app.state::<SnippetLibrary>().all(app).and_then(|snippets| sync(app, &snippets));
+    if let Err(e) = synced {
+        log::warn!("Failed to update snippet search sidecars: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_snippet_indexing(app: AppHandle, settings: State<'_, SettingsStore>) -> Result<bool, String> {
+    Ok(!settings.get(&app)?.skip_snippet_indexing)
+}
+
+/// Turn Spotlight and Windows Search indexing of the snippet library on or off. Turning it off
+/// removes the sidecars straight away.
+#[tauri::command]
+pub fn set_snippet_indexing(app: AppHandle, settings: State<'_, SettingsStore>, enabled: bool) -> Result<(), String> {
+    settings.update(&app, |s| s.skip_snippet_indexing = !enabled)?;
+    let snippets = app.state::<SnippetLibrary>().all(&app)?;
+    sync(&app, &snippets)
+}
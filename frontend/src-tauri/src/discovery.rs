@@ -0,0 +1,224 @@
+//! Finding other ShareCode instances on the local network over mDNS, so connecting to a colleague
+//! doesn't start with asking for their IP address.
+//!
+//! Each instance advertises itself as a `_sharecode._tcp` service under a display name, with the
+//! session server's port and `hosting=1` while it hosts a session, and browses for the others.
+//! Peers are emitted as `peer-discovered` when they appear or their advertisement changes, and as
+//! `peer-lost` when they go. Joining still needs the session link or room code: the advertisement
+//! says where a host is, not how to get in.
+//!
+//! Advertising tells everyone on the network that ShareCode is running, so it is off until
+//! `set_lan_discovery` turns it on.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::protocol::PROTOCOL_VERSION;
+use crate::server::{self, SessionServer};
+use crate::settings::SettingsStore;
+use crate::storage::new_id;
+
+const SERVICE_TYPE: &str = "_sharecode._tcp.local.";
+
+struct Running {
+    daemon: ServiceDaemon,
+    /// Our own advertisement, which browsing finds too.
+    fullname: String,
+    instance: String,
+    host_name: String,
+}
+
+#[derive(Default)]
+pub struct Discovery {
+    running: Mutex<Option<Running>>,
+    peers: Mutex<HashMap<String, DiscoveredPeer>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredPeer {
+    /// The mDNS service name, stable for as long as the peer runs.
+    pub id: String,
+    pub name: String,
+    pub address: String,
+    /// The session server's port; only meaningful while `hosting`.
+    pub port: u16,
+    pub hosting: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerLost {
+    id: String,
+}
+
+/// The name shown to others: the configured one, else the computer's.
+fn display_name(app: &AppHandle) -> String {
+    let configured = app
+        .state::<SettingsStore>()
+        .get(app)
+        .ok()
+        .and_then(|settings| settings.discovery_name)
+        .filter(|name| !name.trim().is_empty());
+    configured
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "ShareCode".to_string())
+}
+
+fn advertisement(app: &AppHandle, running: &Running) -> Result<ServiceInfo, String> {
+    let server = server::get_session_server(app.state::<SessionServer>());
+    let port = server.as_ref().map_or(0, |info| info.port);
+    let protocol = PROTOCOL_VERSION.to_string();
+    let properties = HashMap::from([
+        ("name".to_string(), display_name(app)),
+        ("hosting".to_string(), if server.is_some() { "1" } else { "0" }.to_string()),
+        ("protocol".to_string(), protocol),
+    ]);
+    ServiceInfo::new(SERVICE_TYPE, &running.instance, &running.host_name, "", port, properties)
+        .map(|info| info.enable_addr_auto())
+        .map_err(|e| format!("Failed to describe this instance: {}", e))
+}
+
+fn peer(info: &ServiceInfo) -> Option<DiscoveredPeer> {
+    // IPv4 first: link-local IPv6 addresses need a scope to be reachable
+    let mut addresses: Vec<_> = info.get_addresses().iter().collect();
+    addresses.sort_by_key(|address| !address.is_ipv4());
+    Some(DiscoveredPeer {
+        id: info.get_fullname().to_string(),
+        name: info.get_property_val_str("name").unwrap_or(info.get_fullname()).to_string(),
+        address: addresses.first()?.to_string(),
+        port: info.get_port(),
+        hosting: info.get_property_val_str("hosting") == Some("1"),
+    })
+}
+
+fn browse(app: AppHandle, daemon: &ServiceDaemon, own: String) -> Result<(), String> {
+    let events = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("Failed to look for peers: {}", e))?;
+    thread::spawn(move || {
+        // Ends when the daemon shuts down
+        while let Ok(event) = events.recv() {
+            let discovery = app.state::<Discovery>();
+            match event {
+                ServiceEvent::ServiceResolved(info) if info.get_fullname() != own => {
+                    let Some(peer) = peer(&info) else {
+                        continue;
+                    };
+                    discovery.peers.lock().unwrap().insert(peer.id.clone(), peer.clone());
+                    if let Err(e) = app.emit("peer-discovered", peer) {
+                        log::warn!("Failed to emit peer-discovered: {}", e);
+                    }
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    if discovery.peers.lock().unwrap().remove(&fullname).is_none() {
+                        continue;
+                    }
+                    if let Err(e) = app.emit("peer-lost", PeerLost { id: fullname }) {
+                        log::warn!("Failed to emit peer-lost: {}", e);
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Start advertising and browsing, if discovery is on. Called from setup and when it is turned on.
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    let enabled = app.state::<SettingsStore>().get(app)?.lan_discovery;
+    let discovery = app.state::<Discovery>();
+    let mut slot = discovery.running.lock().unwrap();
+    if !enabled || slot.is_some() {
+        return Ok(());
+    }
+    let daemon = ServiceDaemon::new().map_err(|e| format!("Failed to start mDNS: {}", e))?;
+    let id = new_id();
+    let instance = format!("sharecode-{}", id);
+    let running = Running {
+        fullname: format!("{}.{}", instance, SERVICE_TYPE),
+        host_name: format!("{}.local.", instance),
+        instance,
+        daemon,
+    };
+    running
+        .daemon
+        .register(advertisement(app, &running)?)
+        .map_err(|e| format!("Failed to advertise this instance: {}", e))?;
+    browse(app.clone(), &running.daemon, running.fullname.clone())?;
+    *slot = Some(running);
+    Ok(())
+}
+
+/// Stop advertising and browsing. Called when discovery is turned off and on exit.
+pub fn stop(app: &AppHandle) {
+    let discovery = app.state::<Discovery>();
+    if let Some(running) = discovery.running.lock().unwrap().take() {
+        // Shutting down sends goodbyes for our advertisement
+        if let Err(e) = running.daemon.shutdown() {
+            log::warn!("Failed to stop mDNS: {}", e);
+        }
+    }
+    discovery.peers.lock().unwrap().clear();
+}
+
+/// Re-advertise after hosting starts or stops, so peers see the port.
+pub fn update(app: &AppHandle) {
+    let discovery = app.state::<Discovery>();
+    let running = discovery.running.lock().unwrap();
+    let Some(running) = running.as_ref() else {
+        return;
+    };
+    let registered = advertisement(app, running).and_then(|info| {
+        running
+            .daemon
+            .register(info)
+            .map_err(|e| format!("Failed to update the advertisement: {}", e))
+    });
+    if let Err(e) = registered {
+        log::warn!("{}", e);
+    }
+}
+
+/// Instances seen on the network, excluding this one.
+#[tauri::command]
+pub fn discover_peers(discovery: State<'_, Discovery>) -> Vec<DiscoveredPeer> {
+    let mut peers: Vec<DiscoveredPeer> = discovery.peers.lock().unwrap().values().cloned().collect();
+    peers.sort_by(|a, b| a.name.cmp(&b.name));
+    peers
+}
+
+#[tauri::command]
+pub fn get_lan_discovery(app: AppHandle, settings: State<'_, SettingsStore>) -> Result<bool, String> {
+    Ok(settings.get(&app)?.lan_discovery)
+}
+
+/// Turn advertising and browsing on or off, optionally changing the name others see.
+#[tauri::command]
+pub fn set_lan_discovery(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+    name: Option<String>,
+) -> Result<(), String> {
+    settings.update(&app, |s| {
+        s.lan_discovery = enabled;
+        if let Some(name) = name {
+            s.discovery_name = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+        }
+    })?;
+    if enabled {
+        start(&app)?;
+        update(&app);
+    } else {
+        stop(&app);
+    }
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::encoding::{self, LineEnding, LineEndingCounts, TextEncoding};
+use crate::highlight::Highlights;
+use crate::history::{self, Timeline, UndoTree};
+use crate::language::{self, LanguageProfile};
+use crate::minimap::Minimaps;
+use crate::protocol::Message;
+use crate::recents::{self, RecentKind};
+use crate::session;
+use crate::settings::SettingsStore;
+use crate::sharing::SharingHub;
+use crate::storage::new_id;
+
+/// A local document opened in the desktop app.
+///
+/// `text` keeps the file's original line breaks; `encoding`, `bom` and `eol` describe how it is written back.
+pub struct Document {
+    pub path: Option<PathBuf>,
+    pub language: Option<String>,
+    pub text: String,
+    pub profile: LanguageProfile,
+    pub encoding: TextEncoding,
+    pub bom: bool,
+    pub eol: LineEnding,
+    pub history: UndoTree,
+    pub timeline: Timeline,
+    /// Frozen documents reject every edit until they are explicitly unfrozen.
+    pub frozen: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentInfo {
+    pub id: String,
+    pub path: Option<String>,
+    pub language: Option<String>,
+    pub profile: LanguageProfile,
+    pub length: usize,
+    pub frozen: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentFormat {
+    pub encoding: TextEncoding,
+    pub bom: bool,
+    pub eol: LineEnding,
+    pub line_endings: LineEndingCounts,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LanguageChanged {
+    id: String,
+    language: String,
+    profile: LanguageProfile,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FrozenChanged {
+    id: String,
+    frozen: bool,
+}
+
+#[derive(Default)]
+pub struct DocumentStore {
+    pub docs: Mutex<HashMap<String, Document>>,
+}
+
+impl Document {
+    /// A new in-memory document using the platform-neutral defaults (UTF-8, LF).
+    pub fn new(path: Option<PathBuf>, text: String) -> Self {
+        let eol = encoding::detect_line_ending(&text);
+        let timeline = Timeline::new(&text);
+        Self {
+            path,
+            language: None,
+            text,
+            profile: LanguageProfile::default(),
+            encoding: TextEncoding::Utf8,
+            bom: false,
+            eol,
+            history: UndoTree::default(),
+            timeline,
+            frozen: false,
+        }
+    }
+
+    pub fn ensure_writable(&self) -> Result<(), String> {
+        if self.frozen {
+            return Err("Document is frozen".to_string());
+        }
+        Ok(())
+    }
+
+    /// Replace the text, recording the change in the undo history.
+    pub fn set_text(&mut self, text: String, author: Option<&str>) -> Result<(), String> {
+        self.ensure_writable()?;
+        self.history.record(&self.text, &text, author);
+        self.timeline.record(&self.text, &text, author);
+        self.text = text;
+        Ok(())
+    }
+
+    /// Replace the text with what the editor holds, which has LF breaks only, in the document's
+    /// line-ending style. A mixed-ending document keeps each unchanged line's own break.
+    pub fn edit(&mut self, text: &str, author: Option<&str>) -> Result<(), String> {
+        let text = match self.eol {
+            LineEnding::Mixed => encoding::restore_line_endings(&self.text, text),
+            eol => encoding::convert_line_endings(text, eol),
+        };
+        self.set_text(text, author)
+    }
+
+    /// The bytes `save_document` writes.
+    fn contents(&self) -> Result<Vec<u8>, String> {
+        encoding::encode(&self.text, self.encoding, self.bom)
+    }
+
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat {
+            encoding: self.encoding,
+            bom: self.bom,
+            eol: self.eol,
+            line_endings: encoding::count_line_endings(&self.text),
+        }
+    }
+
+    pub fn info(&self, id: &str) -> DocumentInfo {
+        DocumentInfo {
+            id: id.to_string(),
+            path: self.path.as_ref().map(|p| p.to_string_lossy().into_owned()),
+            language: self.language.clone(),
+            profile: self.profile.clone(),
+            length: self.text.len(),
+            frozen: self.frozen,
+        }
+    }
+}
+
+impl DocumentStore {
+    pub fn insert(&self, doc: Document) -> DocumentInfo {
+        let id = new_id();
+        let info = doc.info(&id);
+        self.docs.lock().unwrap().insert(id, doc);
+        info
+    }
+
+    pub fn with<R>(&self, id: &str, f: impl FnOnce(&mut Document) -> R) -> Result<R, String> {
+        let mut docs = self.docs.lock().unwrap();
+        let doc = docs.get_mut(id).ok_or_else(|| format!("Document {} is not open", id))?;
+        Ok(f(doc))
+    }
+}
+
+/// Set a document's language and apply the matching profile, notifying the webview.
+pub fn apply_language(
+    app: &AppHandle,
+    store: &DocumentStore,
+    settings: &SettingsStore,
+    id: &str,
+    language: &str,
+) -> Result<LanguageProfile, String> {
+    let profile = language::resolve_profile(app, settings, language)?;
+    store.with(id, |doc| {
+        doc.language = Some(language.to_string());
+        doc.profile = profile.clone();
+    })?;
+    let event = LanguageChanged {
+        id: id.to_string(),
+        language: language.to_string(),
+        profile: profile.clone(),
+    };
+    app.emit("document-language-changed", event).map_err(|e| e.to_string())?;
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn open_document(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+) -> Result<DocumentInfo, String> {
+    let path = PathBuf::from(path);
+    let raw = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let decoded = encoding::decode(&raw)?;
+    let language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(language::language_for_extension);
+    let mut doc = Document::new(Some(path.clone()), decoded.text);
+    doc.history = history::load(&app, &path, &doc.text);
+    doc.encoding = decoded.encoding;
+    doc.bom = decoded.bom;
+    let info = store.insert(doc);
+    recents::note(&app, &path, RecentKind::Document);
+    match language {
+        Some(language) => {
+            apply_language(&app, &store, &settings, &info.id, language)?;
+            store.with(&info.id, |doc| doc.info(&info.id))
+        }
+        None => Ok(info),
+    }
+}
+
+#[tauri::command]
+pub fn create_document(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    text: String,
+    language: Option<String>,
+) -> Result<DocumentInfo, String> {
+    let info = store.insert(Document::new(None, text));
+    match language {
+        Some(language) => {
+            apply_language(&app, &store, &settings, &info.id, &language)?;
+            store.with(&info.id, |doc| doc.info(&info.id))
+        }
+        None => Ok(info),
+    }
+}
+
+#[tauri::command]
+pub fn get_document_text(store: State<'_, DocumentStore>, id: String) -> Result<String, String> {
+    store.with(&id, |doc| doc.text.clone())
+}
+
+/// Replace a document's text. The editor hands back LF-only text, so it is converted to the
+/// document's line-ending style here; see [`Document::edit`].
+///
+/// `author` attributes the change in the undo history when several participants edit.
+#[tauri::command]
+pub fn update_document_text(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    id: String,
+    text: String,
+    author: Option<String>,
+) -> Result<(), String> {
+    let patches = store.with(&id, |doc| {
+        let seq = doc.timeline.seq();
+        doc.edit(&text, author.as_deref())?;
+        Ok::<_, String>(doc.timeline.patches_after(seq).unwrap_or_default())
+    })??;
+    app.state::<Arc<SharingHub>>().publish_changes(&id, patches);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_document_format(store: State<'_, DocumentStore>, id: String) -> Result<DocumentFormat, String> {
+    store.with(&id, |doc| doc.format())
+}
+
+/// Convert a document's line endings and/or the encoding it will be saved with.
+#[tauri::command]
+pub fn convert_document(
+    store: State<'_, DocumentStore>,
+    id: String,
+    eol: Option<LineEnding>,
+    encoding: Option<TextEncoding>,
+    bom: Option<bool>,
+) -> Result<DocumentFormat, String> {
+    store.with(&id, |doc| {
+        doc.ensure_writable()?;
+        if let Some(encoding) = encoding {
+            // Fail early rather than at save time if the text does not fit the new encoding.
+            encoding::encode(&doc.text, encoding, false)?;
+            doc.encoding = encoding;
+        }
+        if let Some(bom) = bom {
+            doc.bom = bom;
+        }
+        if let Some(eol) = eol {
+            let text = encoding::convert_line_endings(&doc.text, eol);
+            doc.set_text(text, None)?;
+            doc.eol = encoding::detect_line_ending(&doc.text);
+        }
+        Ok::<_, String>(doc.format())
+    })?
+}
+
+/// Write a document to disk using its encoding, BOM and line endings. `path` saves to a new location.
+#[tauri::command]
+pub fn save_document(app: AppHandle, store: State<'_, DocumentStore>, id: String, path: Option<String>) -> Result<(), String> {
+    let (target, bytes) = store.with(&id, |doc| {
+        if let Some(path) = path {
+            doc.path = Some(PathBuf::from(path));
+        }
+        let target = doc.path.clone().ok_or("Document has no file path; choose where to save it")?;
+        Ok::<_, String>((target, doc.contents()?))
+    })??;
+    let tmp = target.with_extension("sharecode-tmp");
+    fs::write(&tmp, bytes).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, &target).map_err(|e| format!("Failed to save {}: {}", target.display(), e))?;
+    // History is only worth keeping once it matches what is on disk.
+    store.with(&id, |doc| history::save(&app, &target, &mut doc.history, &doc.text))?
+}
+
+#[tauri::command]
+pub fn close_document(app: AppHandle, store: State<'_, DocumentStore>, id: String) -> bool {
+    app.state::<Arc<SharingHub>>().forget(&id);
+    app.state::<Minimaps>().forget(&id);
+    app.state::<Highlights>().forget(&id);
+    store.docs.lock().unwrap().remove(&id).is_some()
+}
+
+#[tauri::command]
+pub fn set_document_language(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    id: String,
+    language: String,
+) -> Result<LanguageProfile, String> {
+    apply_language(&app, &store, &settings, &id, &language)
+}
+
+fn set_frozen(app: &AppHandle, store: &DocumentStore, id: &str, frozen: bool) -> Result<DocumentInfo, String> {
+    let info = store.with(id, |doc| {
+        doc.frozen = frozen;
+        doc.info(id)
+    })?;
+    app.emit("document-frozen", FrozenChanged { id: id.to_string(), frozen })
+        .map_err(|e| e.to_string())?;
+    app.state::<Arc<SharingHub>>().refresh(store, id)?;
+    let notice = Message::DocumentFrozen {
+        document_id: id.to_string(),
+        frozen,
+    };
+    session::broadcast(app, &notice)?;
+    Ok(info)
+}
+
+/// Make a document read-only for everyone, the host included, until `unfreeze_document`.
+#[tauri::command]
+pub fn freeze_document(app: AppHandle, store: State<'_, DocumentStore>, id: String) -> Result<DocumentInfo, String> {
+    set_frozen(&app, &store, &id, true)
+}
+
+#[tauri::command]
+pub fn unfreeze_document(app: AppHandle, store: State<'_, DocumentStore>, id: String) -> Result<DocumentInfo, String> {
+    set_frozen(&app, &store, &id, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_a_mixed_file_keeps_its_line_endings() {
+        let mut doc = Document::new(None, "fn a() {\r\n}\nfn b() {\r\n}\r\n".to_string());
+        assert!(doc.eol == LineEnding::Mixed);
+        doc.edit("fn a() {\n    1\n}\nfn b() {\n}\n", None).unwrap();
+        assert_eq!(doc.contents().unwrap(), b"fn a() {\r\n    1\r\n}\nfn b() {\r\n}\r\n");
+        doc.edit("fn a() {\n    1\n}\n", None).unwrap();
+        assert_eq!(doc.contents().unwrap(), b"fn a() {\r\n    1\r\n}\n");
+    }
+
+    #[test]
+    fn editing_converts_to_a_single_style() {
+        let mut doc = Document::new(None, "a\r\nb\r\n".to_string());
+        doc.edit("a\nb\nc\n", None).unwrap();
+        assert_eq!(doc.contents().unwrap(), b"a\r\nb\r\nc\r\n");
+    }
+}
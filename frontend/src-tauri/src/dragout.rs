@@ -0,0 +1,135 @@
+//! Dragging a snippet out of the app, into an editor, a file manager or an email.
+//!
+//! Other apps take drops as files far more readily than as text, so the snippet is written to a
+//! temp file named after it and that file is what the drag carries: `CF_HDROP` on Windows, a file
+//! URL on macOS and a URI list on GTK. A dropped file is kept for a while, since some targets only
+//! read it later (mail clients attach by reference until the message is sent); a cancelled drag's
+//! file is removed straight away, and anything older than [`KEEP_FOR`] is swept at startup and on
+//! the next drag.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State, WebviewWindow};
+
+use crate::imaging;
+use crate::language::extension_for_language;
+use crate::snippets::{Snippet, SnippetLibrary};
+use crate::storage::new_id;
+use crate::template::{self, ExpandContext};
+
+const KEEP_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Longest file name taken from a snippet title, before the extension.
+const MAX_NAME_CHARS: usize = 64;
+
+fn drag_dir() -> PathBuf {
+    std::env::temp_dir().join("sharecode-drag")
+}
+
+/// Remove drag files older than [`KEEP_FOR`].
+pub fn sweep() {
+    let Ok(entries) = fs::read_dir(drag_dir()) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|meta| meta.modified())
+            .map(|modified| now.duration_since(modified).unwrap_or_default() > KEEP_FOR)
+            .unwrap_or(false);
+        if stale {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                log::warn!("Failed to remove old drag file {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
+/// A snippet title as a file name stem that is valid everywhere.
+pub(crate) fn file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') { '_' } else { c })
+        .take(MAX_NAME_CHARS)
+        .collect();
+    // Windows drops trailing dots and spaces, and leading dots hide the file elsewhere.
+    let stem = stem.trim().trim_matches('.');
+    if stem.is_empty() { "snippet" } else { stem }.to_string()
+}
+
+fn file_name(snippet: &Snippet) -> String {
+    let ext = snippet.language.as_deref().and_then(extension_for_language).unwrap_or("txt");
+    format!("{}.{}", file_stem(&snippet.title), ext)
+}
+
+/// Write the snippet, placeholders expanded, to a directory of its own so the name can't clash.
+fn write(snippet: &Snippet) -> Result<PathBuf, String> {
+    let dir = drag_dir().join(new_id());
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let context = ExpandContext {
+        language: snippet.language.clone(),
+        ..Default::default()
+    };
+    let path = dir.join(file_name(snippet));
+    fs::write(&path, template::expand(&snippet.body, &context).text)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// The app icon, shown under the cursor while dragging.
+fn preview(app: &AppHandle) -> Result<drag::Image, String> {
+    let icon = app.default_window_icon().ok_or("No icon to drag with")?;
+    let png = imaging::encode_png(icon.width(), icon.height(), png::ColorType::Rgba, icon.rgba())?;
+    Ok(drag::Image::Raw(png))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DragFinished {
+    snippet_id: String,
+    dropped: bool,
+}
+
+/// Start dragging a snippet out of the window as a file. Call it from the webview's `mousedown`
+/// (or `dragstart`, after `preventDefault`) while the button is still held. `snippet-drag-finished`
+/// reports whether it was dropped.
+#[tauri::command]
+pub fn start_snippet_drag(
+    app: AppHandle,
+    window: WebviewWindow,
+    library: State<'_, SnippetLibrary>,
+    id: String,
+) -> Result<(), String> {
+    sweep();
+    let snippet = library.get(&app, &id)?;
+    let path = write(&snippet)?;
+    let item = drag::DragItem::Files(vec![path.clone()]);
+    let image = preview(&app)?;
+
+    let on_end = move |result: drag::DragResult, _cursor: drag::CursorPosition| {
+        let dropped = matches!(result, drag::DragResult::Dropped);
+        if !dropped {
+            if let Some(dir) = path.parent() {
+                fs::remove_dir_all(dir).ok();
+            }
+        }
+        let finished = DragFinished { snippet_id: id, dropped };
+        if let Err(e) = app.emit("snippet-drag-finished", finished) {
+            log::warn!("Failed to emit snippet-drag-finished: {}", e);
+        }
+    };
+
+    // GTK drags start from the GTK window rather than the native handle.
+    #[cfg(target_os = "linux")]
+    let source = window.gtk_window().map_err(|e| e.to_string())?;
+    #[cfg(not(target_os = "linux"))]
+    let source = window;
+
+    drag::start_drag(&source, item, image, on_end, drag::Options::default())
+        .map_err(|e| format!("Failed to start dragging: {}", e))
+}
@@ -0,0 +1,210 @@
+//! End-to-end encryption of what is sent to a peer, so code leaves the machine only as ciphertext.
+//!
+//! Each side holds an X25519 session key per peer and announces its public half over the
+//! connection. Both derive the same XChaCha20-Poly1305 key from the Diffie-Hellman result and the
+//! two public keys, and every message is sealed under a fresh random nonce. Nothing is sealed until
+//! the users have compared short authentication strings: six digits derived from the same secret,
+//! which only match when no one in the middle substituted their own key. Generating a new session
+//! key (or the peer announcing one) starts that over.
+//!
+//! Keys live in memory only and are dropped with the connection; the secret half is never exported.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const NONCE_LEN: usize = 24;
+const KEY_CONTEXT: &str = "ShareCode e2ee 2026-10 message key";
+const SAS_CONTEXT: &str = "ShareCode e2ee 2026-10 short authentication string";
+
+struct KeySession {
+    secret: StaticSecret,
+    public: PublicKey,
+    /// Kept so a new key of ours can be combined with it without the peer announcing again
+    peer: Option<PublicKey>,
+    shared: Option<Shared>,
+}
+
+struct Shared {
+    key: [u8; 32],
+    short_auth_string: String,
+    verified: bool,
+}
+
+impl KeySession {
+    fn generate() -> Self {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        let public = PublicKey::from(&secret);
+        KeySession {
+            secret,
+            public,
+            peer: None,
+            shared: None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct E2ee {
+    sessions: Mutex<HashMap<String, KeySession>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyStatus {
+    pub peer_id: String,
+    pub public_key: String,
+    /// Set once the peer's key has arrived; compare it with what the other side shows.
+    pub short_auth_string: Option<String>,
+    pub verified: bool,
+}
+
+fn status(peer_id: &str, session: &KeySession) -> KeyStatus {
+    KeyStatus {
+        peer_id: peer_id.to_string(),
+        public_key: STANDARD.encode(session.public.as_bytes()),
+        short_auth_string: session.shared.as_ref().map(|shared| shared.short_auth_string.clone()),
+        verified: session.shared.as_ref().is_some_and(|shared| shared.verified),
+    }
+}
+
+/// Both sides must derive the same values, so the public keys go in in a fixed order.
+fn derive(ours: &KeySession, theirs: &PublicKey) -> Shared {
+    let secret = ours.secret.diffie_hellman(theirs);
+    let (first, second) = if ours.public.as_bytes() <= theirs.as_bytes() {
+        (ours.public.as_bytes(), theirs.as_bytes())
+    } else {
+        (theirs.as_bytes(), ours.public.as_bytes())
+    };
+    let mut material = Vec::with_capacity(96);
+    material.extend_from_slice(secret.as_bytes());
+    material.extend_from_slice(first);
+    material.extend_from_slice(second);
+
+    let sas = blake3::derive_key(SAS_CONTEXT, &material);
+    let digits = u32::from_be_bytes([sas[0], sas[1], sas[2], sas[3]]) % 1_000_000;
+    Shared {
+        key: blake3::derive_key(KEY_CONTEXT, &material),
+        short_auth_string: format!("{:03} {:03}", digits / 1000, digits % 1000),
+        verified: false,
+    }
+}
+
+impl E2ee {
+    /// Our public key for `peer_id`, generating a session key if there is none yet.
+    pub fn public_key(&self, peer_id: &str) -> String {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.entry(peer_id.to_string()).or_insert_with(KeySession::generate);
+        STANDARD.encode(session.public.as_bytes())
+    }
+
+    /// Replace our session key for `peer_id`, returning the new public key to announce.
+    pub fn rotate(&self, peer_id: &str) -> String {
+        let mut session = KeySession::generate();
+        let mut sessions = self.sessions.lock().unwrap();
+        session.peer = sessions.get(peer_id).and_then(|old| old.peer);
+        session.shared = session.peer.map(|peer| derive(&session, &peer));
+        let public = STANDARD.encode(session.public.as_bytes());
+        sessions.insert(peer_id.to_string(), session);
+        public
+    }
+
+    /// Take the peer's announced public key, returning the short authentication string to compare.
+    pub fn establish(&self, peer_id: &str, public_key: &str) -> Result<String, String> {
+        let bytes: [u8; 32] = STANDARD
+            .decode(public_key.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or("The peer's public key is malformed")?;
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.entry(peer_id.to_string()).or_insert_with(KeySession::generate);
+        let peer = PublicKey::from(bytes);
+        let shared = derive(session, &peer);
+        session.peer = Some(peer);
+        let short_auth_string = shared.short_auth_string.clone();
+        session.shared = Some(shared);
+        Ok(short_auth_string)
+    }
+
+    pub fn forget(&self, peer_id: &str) {
+        self.sessions.lock().unwrap().remove(peer_id);
+    }
+
+    fn cipher(&self, peer_id: &str, require_verified: bool) -> Result<XChaCha20Poly1305, String> {
+        let sessions = self.sessions.lock().unwrap();
+        let shared = sessions
+            .get(peer_id)
+            .and_then(|session| session.shared.as_ref())
+            .ok_or("No encryption key has been agreed with this peer yet")?;
+        if require_verified && !shared.verified {
+            return Err("Compare the short authentication string with the peer before sending".to_string());
+        }
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&shared.key)))
+    }
+
+    /// Encrypt for `peer_id`: a random nonce followed by the ciphertext. Refuses until verified.
+    pub fn seal(&self, peer_id: &str, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let cipher = self.cipher(peer_id, true)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| "Failed to encrypt payload".to_string())?;
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Decrypt what `seal` produced on the other side. The peer can only have sealed it after
+    /// verifying, and a key substituted in the middle fails the integrity check here anyway.
+    pub fn open(&self, peer_id: &str, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Encrypted payload is truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher(peer_id, false)?
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Payload failed integrity check (keys differ or it was tampered with)".to_string())
+    }
+}
+
+/// Generate a new session key for a peer and announce it over the connection, if it is open.
+/// The short authentication string changes, so it has to be compared again.
+#[tauri::command]
+pub async fn generate_session_key(app: AppHandle, e2ee: State<'_, E2ee>, peer_id: String) -> Result<KeyStatus, String> {
+    let public_key = e2ee.rotate(&peer_id);
+    crate::p2p::announce_key(&app, &peer_id, &public_key).await?;
+    export_session_key(e2ee, peer_id)
+}
+
+/// Our public key for a peer with the agreed short authentication string, for showing to the user.
+#[tauri::command]
+pub fn export_session_key(e2ee: State<'_, E2ee>, peer_id: String) -> Result<KeyStatus, String> {
+    let mut sessions = e2ee.sessions.lock().unwrap();
+    let session = sessions.entry(peer_id.clone()).or_insert_with(KeySession::generate);
+    Ok(status(&peer_id, session))
+}
+
+/// Check the string the other side reads out against ours; sending is allowed once they match.
+#[tauri::command]
+pub fn verify_short_auth_string(e2ee: State<'_, E2ee>, peer_id: String, code: String) -> Result<bool, String> {
+    let mut sessions = e2ee.sessions.lock().unwrap();
+    let shared = sessions
+        .get_mut(&peer_id)
+        .and_then(|session| session.shared.as_mut())
+        .ok_or("No encryption key has been agreed with this peer yet")?;
+    let entered: String = code.chars().filter(|c| c.is_ascii_digit()).collect();
+    let expected: String = shared.short_auth_string.chars().filter(|c| c.is_ascii_digit()).collect();
+    shared.verified = entered == expected;
+    Ok(shared.verified)
+}
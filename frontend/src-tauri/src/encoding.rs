@@ -0,0 +1,346 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextEncoding {
+    Utf8,
+    Utf16le,
+    Utf16be,
+    /// ISO-8859-1; every byte maps to the code point of the same value, so any file decodes.
+    Latin1,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+    /// The file mixes styles; it is written back exactly as stored.
+    Mixed,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf | LineEnding::Mixed => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineEndingCounts {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+pub fn count_line_endings(text: &str) -> LineEndingCounts {
+    let mut counts = LineEndingCounts::default();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => {
+                counts.crlf += 1;
+                i += 1;
+            }
+            b'\r' => counts.cr += 1,
+            b'\n' => counts.lf += 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    counts
+}
+
+/// Classify a text's line endings; text without any line break is reported as `Lf`.
+pub fn detect_line_ending(text: &str) -> LineEnding {
+    let counts = count_line_endings(text);
+    match (counts.lf > 0, counts.crlf > 0, counts.cr > 0) {
+        (_, false, false) => LineEnding::Lf,
+        (false, true, false) => LineEnding::Crlf,
+        (false, false, true) => LineEnding::Cr,
+        _ => LineEnding::Mixed,
+    }
+}
+
+/// Rewrite every line break as `eol`. `Mixed` leaves the text untouched.
+pub fn convert_line_endings(text: &str, eol: LineEnding) -> String {
+    if eol == LineEnding::Mixed {
+        return text.to_string();
+    }
+    let target = eol.as_str();
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                out.push_str(target);
+            }
+            '\n' => out.push_str(target),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Lines of `text` with the break that ends each; the last line has none.
+fn split_lines(text: &str) -> Vec<(&str, &str)> {
+    let bytes = text.as_bytes();
+    let mut lines = Vec::new();
+    let (mut start, mut i) = (0, 0);
+    while i < bytes.len() {
+        let eol = match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => "\r\n",
+            b'\r' => "\r",
+            b'\n' => "\n",
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        lines.push((&text[start..i], eol));
+        i += eol.len();
+        start = i;
+    }
+    lines.push((&text[start..], ""));
+    lines
+}
+
+/// The break most lines of `text` end with, LF on a tie.
+fn dominant_line_ending(text: &str) -> &'static str {
+    let counts = count_line_endings(text);
+    if counts.crlf > counts.lf && counts.crlf >= counts.cr {
+        "\r\n"
+    } else if counts.cr > counts.lf && counts.cr > counts.crlf {
+        "\r"
+    } else {
+        "\n"
+    }
+}
+
+/// Beyond this many line pairs, lines in the changed stretch are not matched one by one.
+const MAX_MATCH_CELLS: usize = 1 << 20;
+
+/// Pairs of (old, new) indexes of equal lines, in order, by longest common subsequence.
+fn match_lines(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    if old.len() * new.len() > MAX_MATCH_CELLS {
+        return Vec::new();
+    }
+    let width = new.len() + 1;
+    let mut table = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j, mut pairs) = (0, 0, Vec::new());
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Give `edited`, which has LF breaks only, the breaks of `stored`, for documents that mix
+/// styles. Lines found unchanged keep their own break, as do lines edited in place; lines that
+/// are new get the break most of the document uses.
+pub fn restore_line_endings(stored: &str, edited: &str) -> String {
+    let old = split_lines(stored);
+    let new: Vec<&str> = edited.split('\n').collect();
+    let mut endings: Vec<Option<&str>> = vec![None; new.len()];
+
+    let prefix = old.iter().zip(&new).take_while(|((line, _), edited)| line == *edited).count();
+    let suffix = (0..(old.len() - prefix).min(new.len() - prefix))
+        .take_while(|&k| old[old.len() - 1 - k].0 == new[new.len() - 1 - k])
+        .count();
+    for i in 0..prefix {
+        endings[i] = Some(old[i].1);
+    }
+    for k in 0..suffix {
+        endings[new.len() - 1 - k] = Some(old[old.len() - 1 - k].1);
+    }
+
+    // Within the changed stretch, match equal lines, then pair up the lines between two matches
+    // when there are as many on both sides: those were edited in place.
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let old_mid: Vec<&str> = old[prefix..old_end].iter().map(|(line, _)| *line).collect();
+    let mut anchors: Vec<(usize, usize)> = match_lines(&old_mid, &new[prefix..new_end])
+        .into_iter()
+        .map(|(i, j)| (prefix + i, prefix + j))
+        .collect();
+    anchors.push((old_end, new_end));
+    let (mut old_from, mut new_from) = (prefix, prefix);
+    for (old_at, new_at) in anchors {
+        if old_at - old_from == new_at - new_from {
+            for k in 0..new_at - new_from {
+                endings[new_from + k] = Some(old[old_from + k].1);
+            }
+        }
+        if new_at < new_end {
+            endings[new_at] = Some(old[old_at].1);
+        }
+        (old_from, new_from) = (old_at + 1, new_at + 1);
+    }
+
+    let dominant = dominant_line_ending(stored);
+    let mut out = String::with_capacity(edited.len() + new.len());
+    for (i, line) in new.iter().enumerate() {
+        out.push_str(line);
+        if i + 1 < new.len() {
+            // The old last line had no break of its own
+            out.push_str(endings[i].filter(|eol| !eol.is_empty()).unwrap_or(dominant));
+        }
+    }
+    out
+}
+
+pub struct Decoded {
+    pub text: String,
+    pub encoding: TextEncoding,
+    pub bom: bool,
+}
+
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> Result<String, String> {
+    if bytes.len() % 2 != 0 {
+        return Err("UTF-16 data has an odd number of bytes".to_string());
+    }
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| format!("Invalid UTF-16 data: {}", e))
+}
+
+// Without a BOM, UTF-16 text is recognised by NUL bytes dominating one of the two byte lanes.
+fn guess_utf16(bytes: &[u8]) -> Option<bool> {
+    if bytes.len() < 4 || bytes.len() % 2 != 0 {
+        return None;
+    }
+    let sample = &bytes[..bytes.len().min(4096)];
+    let pairs = sample.len() / 2;
+    let even_nul = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_nul = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+    if odd_nul * 10 >= pairs * 7 && even_nul * 10 < pairs {
+        Some(true)
+    } else if even_nul * 10 >= pairs * 7 && odd_nul * 10 < pairs {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Detect the encoding of raw file contents and decode them, remembering any BOM for write-back.
+pub fn decode(bytes: &[u8]) -> Result<Decoded, String> {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        let text = String::from_utf8(rest.to_vec()).map_err(|e| format!("Invalid UTF-8 data: {}", e))?;
+        return Ok(Decoded { text, encoding: TextEncoding::Utf8, bom: true });
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return Ok(Decoded { text: decode_utf16(rest, true)?, encoding: TextEncoding::Utf16le, bom: true });
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return Ok(Decoded { text: decode_utf16(rest, false)?, encoding: TextEncoding::Utf16be, bom: true });
+    }
+    if let Some(little_endian) = guess_utf16(bytes) {
+        if let Ok(text) = decode_utf16(bytes, little_endian) {
+            let encoding = if little_endian { TextEncoding::Utf16le } else { TextEncoding::Utf16be };
+            return Ok(Decoded { text, encoding, bom: false });
+        }
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(Decoded { text: text.to_string(), encoding: TextEncoding::Utf8, bom: false }),
+        Err(_) => Ok(Decoded {
+            text: bytes.iter().map(|b| *b as char).collect(),
+            encoding: TextEncoding::Latin1,
+            bom: false,
+        }),
+    }
+}
+
+/// Encode text for writing to disk. Fails instead of silently substituting characters Latin-1 cannot hold.
+pub fn encode(text: &str, encoding: TextEncoding, bom: bool) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() + 3);
+    match encoding {
+        TextEncoding::Utf8 => {
+            if bom {
+                out.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+            }
+            out.extend_from_slice(text.as_bytes());
+        }
+        TextEncoding::Utf16le | TextEncoding::Utf16be => {
+            let little_endian = encoding == TextEncoding::Utf16le;
+            let units = (if bom { Some(0xFEFF) } else { None }).into_iter().chain(text.encode_utf16());
+            for unit in units {
+                let bytes = if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+                out.extend_from_slice(&bytes);
+            }
+        }
+        TextEncoding::Latin1 => {
+            for (line, content) in text.split('\n').enumerate() {
+                if line > 0 {
+                    out.push(b'\n');
+                }
+                for c in content.chars() {
+                    let code = c as u32;
+                    if code > 0xFF {
+                        return Err(format!("Character {:?} on line {} cannot be encoded as Latin-1", c, line + 1));
+                    }
+                    out.push(code as u8);
+                }
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_lines_keep_their_breaks() {
+        let stored = "a\r\nb\nc\r\nd";
+        assert_eq!(restore_line_endings(stored, "a\nb\nc\nd"), stored);
+        // Edited in place
+        assert_eq!(restore_line_endings(stored, "a\nbx\nc\nd"), "a\r\nbx\nc\r\nd");
+        // A line removed
+        assert_eq!(restore_line_endings(stored, "a\nc\nd"), "a\r\nc\r\nd");
+    }
+
+    #[test]
+    fn new_lines_take_the_dominant_break() {
+        let stored = "a\r\nb\nc\r\n";
+        assert_eq!(restore_line_endings(stored, "a\nnew\nb\nc\n"), "a\r\nnew\r\nb\nc\r\n");
+        assert_eq!(restore_line_endings(stored, "a\nb\nc\nd\ne"), "a\r\nb\nc\r\nd\r\ne");
+        assert_eq!(restore_line_endings("a\rb\nc\n", "a\nb\nc\nd"), "a\rb\nc\nd");
+    }
+
+    #[test]
+    fn lone_cr_lines_are_split() {
+        assert_eq!(split_lines("a\rb\r\nc\n"), [("a", "\r"), ("b", "\r\n"), ("c", "\n"), ("", "")]);
+    }
+}
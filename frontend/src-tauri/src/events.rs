@@ -0,0 +1,130 @@
+//! Event emission to the webview, with per-channel coalescing so rapid backend events (progress,
+//! presence, gestures) cannot flood the IPC bridge.
+//!
+//! Each event is declared as a [`Channel`] next to the code that emits it, with the policy that
+//! suits it:
+//!
+//! - `lossless` events are emitted straight away, every one of them.
+//! - `latest_wins` events are held for up to `FLUSH_INTERVAL`, and only the last payload per key
+//!   in that time is emitted.
+//! - `batch` events are collected for up to `FLUSH_INTERVAL` and emitted together as an array,
+//!   in the order they happened.
+
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// About two frames: often enough to look live, rarely enough to keep the webview idle.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(33);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Lossless,
+    LatestWins,
+    Batch,
+}
+
+/// An event name and how its emissions are coalesced.
+pub struct Channel {
+    pub name: &'static str,
+    pub policy: Policy,
+}
+
+impl Channel {
+    pub const fn lossless(name: &'static str) -> Self {
+        Self {
+            name,
+            policy: Policy::Lossless,
+        }
+    }
+
+    pub const fn latest_wins(name: &'static str) -> Self {
+        Self {
+            name,
+            policy: Policy::LatestWins,
+        }
+    }
+
+    pub const fn batch(name: &'static str) -> Self {
+        Self {
+            name,
+            policy: Policy::Batch,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Pending {
+    latest: HashMap<(&'static str, String), Value>,
+    batches: HashMap<&'static str, Vec<Value>>,
+}
+
+impl Pending {
+    fn is_empty(&self) -> bool {
+        self.latest.is_empty() && self.batches.is_empty()
+    }
+}
+
+#[derive(Default)]
+pub struct Events {
+    pending: Mutex<Pending>,
+    queued: Condvar,
+}
+
+impl Events {
+    /// Events waiting for the next flush.
+    pub fn pending_count(&self) -> usize {
+        let pending = self.pending.lock().unwrap();
+        pending.latest.len() + pending.batches.values().map(Vec::len).sum::<usize>()
+    }
+}
+
+/// Emit `payload` on `channel`. Latest-wins events with different keys do not replace each other.
+pub fn emit_keyed<S: Serialize>(app: &AppHandle, channel: &Channel, key: &str, payload: S) -> Result<(), String> {
+    let payload = match channel.policy {
+        Policy::Lossless => return app.emit(channel.name, payload).map_err(|e| e.to_string()),
+        _ => serde_json::to_value(payload).map_err(|e| e.to_string())?,
+    };
+    let events = app.state::<Events>();
+    let mut pending = events.pending.lock().unwrap();
+    if channel.policy == Policy::Batch {
+        pending.batches.entry(channel.name).or_default().push(payload);
+    } else {
+        pending.latest.insert((channel.name, key.to_string()), payload);
+    }
+    events.queued.notify_one();
+    Ok(())
+}
+
+pub fn emit<S: Serialize>(app: &AppHandle, channel: &Channel, payload: S) -> Result<(), String> {
+    emit_keyed(app, channel, "", payload)
+}
+
+/// Emit coalesced events, at most once per `FLUSH_INTERVAL` and only while there are any.
+pub fn spawn_flusher(app: AppHandle) {
+    thread::spawn(move || {
+        let events = app.state::<Events>();
+        loop {
+            let pending = events.pending.lock().unwrap();
+            drop(events.queued.wait_while(pending, |p| p.is_empty()).unwrap());
+            // Let the burst that woke us finish before emitting it.
+            thread::sleep(FLUSH_INTERVAL);
+            let Pending { latest, batches } = std::mem::take(&mut *events.pending.lock().unwrap());
+            for ((name, _), payload) in latest {
+                if let Err(e) = app.emit(name, payload) {
+                    log::warn!("Failed to emit {}: {}", name, e);
+                }
+            }
+            for (name, payloads) in batches {
+                if let Err(e) = app.emit(name, payloads) {
+                    log::warn!("Failed to emit {}: {}", name, e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,314 @@
+//! Backend rendering of highlighted code for exports (printing, recordings), independent of the
+//! webview: the text is split into lines of styled runs, and a theme maps styles to colours.
+
+use serde::Deserialize;
+
+use crate::accessibility;
+use crate::history::ReplayStep;
+use crate::syntax::{self, HighlightClass};
+
+/// An sRGB colour.
+pub type Rgb = [u8; 3];
+
+/// A stretch of one line in one style. Tabs are already expanded.
+#[derive(Clone)]
+pub struct Run {
+    pub text: String,
+    pub class: Option<HighlightClass>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeName {
+    #[default]
+    Light,
+    Dark,
+}
+
+pub struct Theme {
+    pub background: Rgb,
+    pub foreground: Rgb,
+    pub gutter: Rgb,
+    keyword: Rgb,
+    string: Rgb,
+    number: Rgb,
+    comment: Rgb,
+    kind: Rgb,
+    function: Rgb,
+}
+
+impl Theme {
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Light => Self {
+                background: [255, 255, 255],
+                foreground: [36, 41, 47],
+                gutter: [140, 149, 159],
+                keyword: [137, 29, 150],
+                string: [10, 110, 40],
+                number: [5, 80, 174],
+                comment: [110, 119, 129],
+                kind: [0, 110, 130],
+                function: [150, 80, 0],
+            },
+            ThemeName::Dark => Self {
+                background: [30, 30, 36],
+                foreground: [220, 222, 228],
+                gutter: [110, 114, 126],
+                keyword: [198, 146, 234],
+                string: [152, 195, 121],
+                number: [209, 154, 102],
+                comment: [120, 126, 140],
+                kind: [86, 182, 194],
+                function: [97, 175, 239],
+            },
+        }
+    }
+
+    /// Pure black and white with saturated classes, for the system high-contrast mode.
+    pub fn high_contrast(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Light => Self {
+                background: [255, 255, 255],
+                foreground: [0, 0, 0],
+                gutter: [60, 60, 60],
+                keyword: [110, 0, 140],
+                string: [0, 90, 0],
+                number: [0, 50, 160],
+                comment: [70, 70, 70],
+                kind: [0, 80, 100],
+                function: [120, 50, 0],
+            },
+            ThemeName::Dark => Self {
+                background: [0, 0, 0],
+                foreground: [255, 255, 255],
+                gutter: [200, 200, 200],
+                keyword: [255, 170, 255],
+                string: [140, 255, 140],
+                number: [255, 210, 110],
+                comment: [200, 200, 200],
+                kind: [120, 240, 255],
+                function: [150, 200, 255],
+            },
+        }
+    }
+
+    pub fn color(&self, class: Option<HighlightClass>) -> Rgb {
+        match class {
+            None => self.foreground,
+            Some(HighlightClass::Keyword) => self.keyword,
+            Some(HighlightClass::String) => self.string,
+            Some(HighlightClass::Number) => self.number,
+            Some(HighlightClass::Comment) => self.comment,
+            Some(HighlightClass::Type) => self.kind,
+            Some(HighlightClass::Function) => self.function,
+        }
+    }
+    /// Background of the line holding the caret: a faint tint of the foreground.
+    pub fn current_line(&self) -> Rgb {
+        [0, 1, 2].map(|i| {
+            let (bg, fg) = (self.background[i] as f32, self.foreground[i] as f32);
+            (bg + (fg - bg) * 0.08).round() as u8
+        })
+    }
+}
+
+/// The theme exports should use: `name`, in high contrast when the system asks for it.
+pub fn theme(name: ThemeName) -> Theme {
+    if accessibility::current().high_contrast {
+        Theme::high_contrast(name)
+    } else {
+        Theme::new(name)
+    }
+}
+
+fn push_run(line: &mut Vec<Run>, column: &mut usize, text: &str, class: Option<HighlightClass>, tab_width: usize) {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = tab_width - *column % tab_width;
+            expanded.extend(std::iter::repeat(' ').take(spaces));
+            *column += spaces;
+        } else {
+            expanded.push(c);
+            *column += 1;
+        }
+    }
+    match line.last_mut() {
+        Some(last) if last.class == class => last.text.push_str(&expanded),
+        _ if !expanded.is_empty() => line.push(Run { text: expanded, class }),
+        _ => {}
+    }
+}
+
+/// Split `text` into lines of highlighted runs. Without a grammar for `language` every run is plain.
+pub fn styled_lines(text: &str, language: Option<&str>, tab_width: usize) -> Vec<Vec<Run>> {
+    let tab_width = tab_width.max(1);
+    let spans = language
+        .and_then(|language| syntax::parse(language, text).ok().map(|tree| (language, tree)))
+        .map(|(language, tree)| syntax::highlight(language, &tree, text))
+        .unwrap_or_default();
+    // Spans are in document order, so their UTF-16 offsets convert to bytes in one forward pass.
+    let mut chars = text.char_indices().peekable();
+    let mut units = 0;
+    let mut to_byte = |utf16: usize| {
+        while let Some(&(_, c)) = chars.peek() {
+            if units >= utf16 {
+                break;
+            }
+            units += c.len_utf16();
+            chars.next();
+        }
+        chars.peek().map_or(text.len(), |&(byte, _)| byte)
+    };
+    let mut spans = spans
+        .into_iter()
+        .map(|span| (to_byte(span.start), to_byte(span.end), span.class))
+        .peekable();
+
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    let mut column = 0;
+    let mut pos = 0;
+    while pos < text.len() {
+        let (end, class) = match spans.peek() {
+            Some(&(start, end, class)) if start <= pos => {
+                if end <= pos {
+                    spans.next();
+                    continue;
+                }
+                (end, Some(class))
+            }
+            Some(&(start, _, _)) => (start, None),
+            None => (text.len(), None),
+        };
+        let chunk = &text[pos..end];
+        // Runs never span lines; split at each break, treating CRLF as one.
+        let mut rest = chunk;
+        while let Some(i) = rest.find('\n') {
+            push_run(&mut line, &mut column, rest[..i].trim_end_matches('\r'), class, tab_width);
+            lines.push(std::mem::take(&mut line));
+            column = 0;
+            rest = &rest[i + 1..];
+        }
+        push_run(&mut line, &mut column, rest, class, tab_width);
+        pos = end;
+    }
+    // A final line break ends the last line rather than starting an empty one.
+    if !line.is_empty() || !text.ends_with('\n') {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Number of characters in a styled line.
+pub fn line_width(line: &[Run]) -> usize {
+    line.iter().map(|run| run.text.chars().count()).sum()
+}
+
+/// Line and display column of byte offset `at`, with tabs expanded as in [`styled_lines`].
+pub fn caret_position(text: &str, at: usize, tab_width: usize) -> (usize, usize) {
+    let tab_width = tab_width.max(1);
+    let before = &text[..at.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().fold(0, |column, c| match c {
+        '\t' => column + tab_width - column % tab_width,
+        _ => column + 1,
+    });
+    (before.matches('\n').count(), column)
+}
+
+/// How a [`Viewport`] moves when the caret leaves it.
+#[derive(Clone, Copy)]
+enum Follow {
+    /// Just far enough to bring the caret back into view.
+    Scroll,
+    /// In one jump that centres the caret, when the system asks for reduced motion.
+    Jump,
+    /// Not at all, e.g. on a magnified region.
+    Fixed,
+}
+
+/// A window of `rows` by `columns` characters that scrolls to keep the caret in view.
+pub struct Viewport {
+    pub rows: usize,
+    pub columns: usize,
+    pub top: usize,
+    pub left: usize,
+    follow: Follow,
+}
+
+impl Viewport {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Self {
+            rows,
+            columns,
+            top: 0,
+            left: 0,
+            follow: if accessibility::current().reduce_motion {
+                Follow::Jump
+            } else {
+                Follow::Scroll
+            },
+        }
+    }
+
+    /// A window that stays where it is, wherever the caret goes.
+    pub fn fixed(rows: usize, columns: usize, top: usize, left: usize) -> Self {
+        Self {
+            rows,
+            columns,
+            top,
+            left,
+            follow: Follow::Fixed,
+        }
+    }
+
+    pub fn follow(&mut self, line: usize, column: usize) {
+        let jump = match self.follow {
+            Follow::Fixed => return,
+            Follow::Jump => true,
+            Follow::Scroll => false,
+        };
+        if line < self.top || line >= self.top + self.rows {
+            self.top = if jump {
+                line.saturating_sub(self.rows / 2)
+            } else if line < self.top {
+                line
+            } else {
+                line + 1 - self.rows
+            };
+        }
+        if column < self.left || column >= self.left + self.columns {
+            self.left = if jump {
+                column.saturating_sub(self.columns / 2)
+            } else if column < self.left {
+                column
+            } else {
+                column + 1 - self.columns
+            };
+        }
+    }
+
+    /// Row and column of a position within the window, if it is visible.
+    pub fn locate(&self, line: usize, column: usize) -> Option<(usize, usize)> {
+        let visible = (self.top..self.top + self.rows).contains(&line)
+            && (self.left..self.left + self.columns).contains(&column);
+        visible.then(|| (line - self.top, column - self.left))
+    }
+}
+
+/// When each step plays back, in milliseconds from `start_ms`: pauses are capped at
+/// `max_idle_ms`, then everything is sped up by `speed`.
+pub fn playback_times(steps: &[ReplayStep], start_ms: f64, max_idle_ms: u64, speed: f64) -> Vec<f64> {
+    let mut clock = start_ms;
+    let mut times = Vec::with_capacity(steps.len());
+    for (i, step) in steps.iter().enumerate() {
+        if i > 0 {
+            let gap = step.timestamp.saturating_sub(steps[i - 1].timestamp).min(max_idle_ms);
+            clock += gap as f64 / speed;
+        }
+        times.push(clock);
+    }
+    times
+}
@@ -0,0 +1,186 @@
+//! A ring of reusable frame buffers between a thread that draws frames and one that encodes
+//! them.
+//!
+//! Every buffer is allocated when the channel is made and then moves between the free list and
+//! the ready queue, so frames are neither allocated nor copied on the way to the encoder. When
+//! the encoder falls behind, the writer either waits (`Overflow::Block`, for exports that must
+//! keep every frame) or takes back the oldest frame not yet encoded (`Overflow::DropOldest`, for
+//! live streams that must keep up). Both are counted per pipeline for `get_frame_stats`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    Block,
+    /// For live streams; nothing in the tree streams frames live yet.
+    #[allow(dead_code)]
+    DropOldest,
+}
+
+#[derive(Default)]
+struct Counters {
+    active: AtomicU64,
+    frames: AtomicU64,
+    dropped: AtomicU64,
+    stalls: AtomicU64,
+}
+
+/// Counters for each pipeline name, kept after its channels close.
+fn pipelines() -> &'static Mutex<HashMap<&'static str, Arc<Counters>>> {
+    static PIPELINES: OnceLock<Mutex<HashMap<&'static str, Arc<Counters>>>> = OnceLock::new();
+    PIPELINES.get_or_init(Default::default)
+}
+
+struct State<M> {
+    free: Vec<Vec<u8>>,
+    ready: VecDeque<(Vec<u8>, M)>,
+    writer_closed: bool,
+    reader_closed: bool,
+}
+
+struct Ring<M> {
+    state: Mutex<State<M>>,
+    changed: Condvar,
+    overflow: Overflow,
+    counters: Arc<Counters>,
+}
+
+pub struct FrameWriter<M> {
+    ring: Arc<Ring<M>>,
+}
+
+pub struct FrameReader<M> {
+    ring: Arc<Ring<M>>,
+}
+
+/// A ring of `slots` buffers of `frame_len` bytes for `pipeline`. Each frame carries `M` along.
+pub fn channel<M>(pipeline: &'static str, slots: usize, frame_len: usize, overflow: Overflow) -> (FrameWriter<M>, FrameReader<M>) {
+    let counters = Arc::clone(pipelines().lock().unwrap().entry(pipeline).or_default());
+    counters.active.fetch_add(1, Ordering::Relaxed);
+    let ring = Arc::new(Ring {
+        state: Mutex::new(State {
+            free: (0..slots.max(2)).map(|_| Vec::with_capacity(frame_len)).collect(),
+            ready: VecDeque::new(),
+            writer_closed: false,
+            reader_closed: false,
+        }),
+        changed: Condvar::new(),
+        overflow,
+        counters,
+    });
+    (FrameWriter { ring: Arc::clone(&ring) }, FrameReader { ring })
+}
+
+impl<M> FrameWriter<M> {
+    /// A buffer to draw the next frame into, holding an old frame's bytes. `None` once the
+    /// reader has gone.
+    pub fn buffer(&self) -> Option<Vec<u8>> {
+        let ring = &*self.ring;
+        let mut state = ring.state.lock().unwrap();
+        let mut stalled = false;
+        loop {
+            if state.reader_closed {
+                return None;
+            }
+            if let Some(buffer) = state.free.pop() {
+                return Some(buffer);
+            }
+            if ring.overflow == Overflow::DropOldest {
+                if let Some((buffer, _)) = state.ready.pop_front() {
+                    ring.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Some(buffer);
+                }
+            }
+            if !stalled {
+                stalled = true;
+                ring.counters.stalls.fetch_add(1, Ordering::Relaxed);
+            }
+            state = ring.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Queue a drawn frame for the reader. `false` if the reader has gone.
+    pub fn publish(&self, buffer: Vec<u8>, meta: M) -> bool {
+        let mut state = self.ring.state.lock().unwrap();
+        if state.reader_closed {
+            return false;
+        }
+        state.ready.push_back((buffer, meta));
+        self.ring.counters.frames.fetch_add(1, Ordering::Relaxed);
+        self.ring.changed.notify_all();
+        true
+    }
+}
+
+impl<M> FrameReader<M> {
+    /// The next frame, waiting for the writer; `None` once it is done and every frame was read.
+    /// Hand the buffer back with `recycle` when finished with it.
+    pub fn next(&self) -> Option<(Vec<u8>, M)> {
+        let mut state = self.ring.state.lock().unwrap();
+        loop {
+            if let Some(frame) = state.ready.pop_front() {
+                self.ring.changed.notify_all();
+                return Some(frame);
+            }
+            if state.writer_closed {
+                return None;
+            }
+            state = self.ring.changed.wait(state).unwrap();
+        }
+    }
+
+    pub fn recycle(&self, buffer: Vec<u8>) {
+        self.ring.state.lock().unwrap().free.push(buffer);
+        self.ring.changed.notify_all();
+    }
+}
+
+impl<M> Drop for FrameWriter<M> {
+    fn drop(&mut self) {
+        self.ring.state.lock().unwrap().writer_closed = true;
+        self.ring.changed.notify_all();
+    }
+}
+
+impl<M> Drop for FrameReader<M> {
+    fn drop(&mut self) {
+        self.ring.state.lock().unwrap().reader_closed = true;
+        self.ring.changed.notify_all();
+        self.ring.counters.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameStats {
+    pub pipeline: &'static str,
+    /// Channels currently open.
+    pub active: u64,
+    pub frames: u64,
+    /// Frames replaced before the encoder got to them.
+    pub dropped: u64,
+    /// Times the writer had to wait for the encoder.
+    pub stalls: u64,
+}
+
+#[tauri::command]
+pub fn get_frame_stats() -> Vec<FrameStats> {
+    let mut stats: Vec<FrameStats> = pipelines()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(&pipeline, counters)| FrameStats {
+            pipeline,
+            active: counters.active.load(Ordering::Relaxed),
+            frames: counters.frames.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            stalls: counters.stalls.load(Ordering::Relaxed),
+        })
+        .collect();
+    stats.sort_by_key(|s| s.pipeline);
+    stats
+}
@@ -0,0 +1,95 @@
+//! Entry points for the targets in `fuzz/`, behind the `fuzzing` feature.
+//!
+//! The tests replay every seed in `fuzz/corpus` and every input in `fuzz/regressions` through
+//! them, so a crash found by fuzzing stays fixed without a fuzzer installed.
+//!
+//! Each takes arbitrary bytes and feeds them to a parser that sees untrusted input from the
+//! network or from disk. Parsers may reject the input with an error; they must not panic, hang
+//! or allocate without bound.
+
+use crate::{armor, artifact, handoff, importers, protocol, viewer};
+
+/// A frame from another participant, and the join fields negotiated from.
+pub fn protocol_message(data: &[u8]) {
+    if let Ok(message) = serde_json::from_slice::<protocol::Message>(data) {
+        message.capability();
+    }
+    if let Ok((version, names)) = serde_json::from_slice::<(u32, Option<Vec<String>>)>(data) {
+        protocol::Negotiated::with(version, names.as_deref()).ok();
+    }
+}
+
+/// A session link, as typed in or scanned from a QR code, and a Handoff payload carrying one.
+pub fn join_link(data: &[u8]) {
+    let text = String::from_utf8_lossy(data);
+    viewer::parse_link(&text).ok();
+    handoff::validate(&text).ok();
+}
+
+/// Snippet files in every format `import_snippets` reads. The first byte picks the format.
+pub fn snippet_import(data: &[u8]) {
+    let Some((&format, raw)) = data.split_first() else {
+        return;
+    };
+    let text = String::from_utf8_lossy(raw);
+    match format % 4 {
+        0 => importers::parse_vscode(&text, "fuzz", None).map(|_| ()),
+        1 => importers::parse_sublime(&text, "fuzz", "fuzz").map(|_| ()),
+        2 => importers::parse_textexpander_plist(raw, "fuzz").map(|_| ()),
+        _ => importers::parse_textexpander_csv(&text, "fuzz").map(|_| ()),
+    }
+    .ok();
+}
+
+/// Snippets pasted as armored text, and the compressed form inside it and inside PNGs.
+pub fn armored_snippet(data: &[u8]) {
+    armor::dearmor(&String::from_utf8_lossy(data)).ok();
+    armor::decompress(data).ok();
+}
+
+/// Files `validate_file` and the importers open: containers, backups, bundles and exports.
+pub fn artifact_file(data: &[u8]) {
+    if artifact::is_container(data) {
+        artifact::validate_container(data);
+        artifact::decode(data, artifact::Kind::SessionSnapshot).ok();
+    } else {
+        let tail = &data[data.len().saturating_sub(1024)..];
+        artifact::validate_other(&data[..data.len().min(64)], tail, data.len() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    /// Feed every file under `fuzz/<dir>/<target>` to the target's entry point.
+    fn replay(dir: &str) {
+        let targets: [(&str, fn(&[u8])); 5] = [
+            ("protocol_message", super::protocol_message),
+            ("join_link", super::join_link),
+            ("snippet_import", super::snippet_import),
+            ("armored_snippet", super::armored_snippet),
+            ("artifact_file", super::artifact_file),
+        ];
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz").join(dir);
+        for (target, run) in targets {
+            let dir = root.join(target);
+            let entries = fs::read_dir(&dir).unwrap_or_else(|e| panic!("{}: {}", dir.display(), e));
+            for entry in entries {
+                let path = entry.unwrap().path();
+                run(&fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e)));
+            }
+        }
+    }
+
+    #[test]
+    fn regressions_do_not_crash() {
+        replay("regressions");
+    }
+
+    #[test]
+    fn corpus_does_not_crash() {
+        replay("corpus");
+    }
+}
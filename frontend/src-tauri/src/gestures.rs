@@ -0,0 +1,160 @@
+//! Native trackpad gestures on macOS: pinch to zoom the shared view and three-finger swipes to
+//! switch documents.
+//!
+//! The webview either swallows these (pinch turns into page zoom) or reports them as ambiguous
+//! wheel events, so an application-wide NSEvent monitor picks them up first, forwards them as
+//! `trackpad-pinch` and `trackpad-swipe` events, and keeps them from the webview.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::AppHandle;
+
+/// Gestures are forwarded unless turned off; when off they reach the webview as before.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::cell::Cell;
+    use std::sync::atomic::Ordering;
+    use std::sync::OnceLock;
+
+    use block::ConcreteBlock;
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+    use serde::Serialize;
+    use tauri::{AppHandle, Emitter};
+
+    use super::ENABLED;
+
+    type Id = *mut Object;
+
+    #[derive(Clone, Copy, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum GesturePhase {
+        Began,
+        Changed,
+        Ended,
+        Cancelled,
+    }
+
+    #[derive(Clone, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct PinchEvent {
+        phase: GesturePhase,
+        /// Change since the previous event, as reported by AppKit (0.1 is 10% larger).
+        magnification: f64,
+        /// Cumulative scale since the gesture began.
+        scale: f64,
+    }
+
+    #[derive(Clone, Copy, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum SwipeDirection {
+        Previous,
+        Next,
+    }
+
+    const NS_EVENT_TYPE_MAGNIFY: u64 = 30;
+    const NS_EVENT_TYPE_SWIPE: u64 = 31;
+    const NS_EVENT_PHASE_BEGAN: u64 = 1 << 0;
+    const NS_EVENT_PHASE_ENDED: u64 = 1 << 3;
+    const NS_EVENT_PHASE_CANCELLED: u64 = 1 << 4;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    thread_local! {
+        /// Scale of the pinch in progress; events arrive on the main thread only.
+        static SCALE: Cell<f64> = const { Cell::new(1.0) };
+    }
+
+    fn pinch(app: &AppHandle, phase: u64, magnification: f64) {
+        let phase = match phase {
+            NS_EVENT_PHASE_BEGAN => GesturePhase::Began,
+            NS_EVENT_PHASE_ENDED => GesturePhase::Ended,
+            NS_EVENT_PHASE_CANCELLED => GesturePhase::Cancelled,
+            _ => GesturePhase::Changed,
+        };
+        let scale = SCALE.with(|scale| {
+            if let GesturePhase::Began = phase {
+                scale.set(1.0);
+            }
+            scale.set(scale.get() * (1.0 + magnification));
+            scale.get()
+        });
+        let event = PinchEvent {
+            phase,
+            magnification,
+            scale,
+        };
+        if let Err(e) = app.emit("trackpad-pinch", event) {
+            log::warn!("Failed to emit pinch gesture: {}", e);
+        }
+    }
+
+    /// Handles one event; returns it to let it through or nil to keep it from the webview.
+    unsafe fn handle(event: Id) -> Id {
+        let Some(app) = APP.get().filter(|_| ENABLED.load(Ordering::Relaxed)) else {
+            return event;
+        };
+        let kind: u64 = msg_send![event, type];
+        match kind {
+            NS_EVENT_TYPE_MAGNIFY => {
+                let phase: u64 = msg_send![event, phase];
+                let magnification: f64 = msg_send![event, magnification];
+                pinch(app, phase, magnification);
+                std::ptr::null_mut()
+            }
+            NS_EVENT_TYPE_SWIPE => {
+                let dx: f64 = msg_send![event, deltaX];
+                // Positive is a swipe back, as in the browser.
+                let direction = if dx > 0.0 {
+                    SwipeDirection::Previous
+                } else if dx < 0.0 {
+                    SwipeDirection::Next
+                } else {
+                    return event;
+                };
+                if let Err(e) = app.emit("trackpad-swipe", direction) {
+                    log::warn!("Failed to emit swipe gesture: {}", e);
+                }
+                std::ptr::null_mut()
+            }
+            _ => event,
+        }
+    }
+
+    /// Install the local event monitor. Must run on the main thread.
+    pub unsafe fn install(app: AppHandle) {
+        if APP.set(app).is_err() {
+            return;
+        }
+        let mask: u64 = (1 << NS_EVENT_TYPE_MAGNIFY) | (1 << NS_EVENT_TYPE_SWIPE);
+        let handler = ConcreteBlock::new(|event: Id| -> Id { unsafe { handle(event) } }).copy();
+        let monitor: Id = msg_send![class!(NSEvent), addLocalMonitorForEventsMatchingMask: mask handler: &*handler];
+        if monitor.is_null() {
+            log::warn!("Failed to install the trackpad gesture monitor");
+        }
+        // The monitor stays for the life of the app, and so must its handler.
+        std::mem::forget(handler);
+    }
+}
+
+/// Start forwarding trackpad gestures. Called once from setup, on the main thread.
+pub fn install(app: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    unsafe {
+        macos::install(app.clone());
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app;
+}
+
+/// Turn gesture forwarding on or off; when off, gestures reach the webview untouched.
+#[tauri::command]
+pub fn set_trackpad_gestures(enabled: bool) -> Result<(), String> {
+    if cfg!(not(target_os = "macos")) {
+        return Err("Trackpad gestures are only supported on macOS".to_string());
+    }
+    ENABLED.store(enabled, Ordering::Relaxed);
+    Ok(())
+}
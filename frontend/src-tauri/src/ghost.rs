@@ -0,0 +1,117 @@
+//! Ghost typing: typing prepared text into a shared document a character at a time, so viewers see
+//! it appear as if typed by hand.
+//!
+//! It runs here rather than in the webview so that it is recorded in transparency reports whenever
+//! it happens. Each character goes through the document's history and timeline like any edit, is
+//! sent to viewers, and is handed to the webview as a `document-patch` to show in the editor.
+//! Typing stops at the end of the text, with `stop_ghost_typing`, or if the document is closed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::documents::DocumentStore;
+use crate::protocol::Message;
+use crate::sharing::SharingHub;
+use crate::syntax::OffsetMap;
+use crate::transparency::{self, PrivacyFeature};
+
+const AUTHOR: &str = "ghost-typing";
+const DEFAULT_CHARS_PER_SECOND: f64 = 8.0;
+const MAX_CHARS_PER_SECOND: f64 = 100.0;
+
+#[derive(Default)]
+pub struct GhostTyping {
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+/// Insert `ch` at byte offset `at`, sending the change to viewers and the webview.
+fn type_char(app: &AppHandle, doc_id: &str, at: usize, ch: char) -> Result<(), String> {
+    let patches = app.state::<DocumentStore>().with(doc_id, |doc| {
+        let seq = doc.timeline.seq();
+        let mut text = doc.text.clone();
+        if !text.is_char_boundary(at) {
+            return Err("The document was shortened under the typing position".to_string());
+        }
+        text.insert(at, ch);
+        doc.set_text(text, Some(AUTHOR))?;
+        Ok::<_, String>(doc.timeline.patches_after(seq).unwrap_or_default())
+    })??;
+    for patch in &patches {
+        let message = Message::Patch {
+            document_id: doc_id.to_string(),
+            seq: patch.seq,
+            start: patch.start,
+            delete_count: patch.delete_count,
+            insert: patch.insert.clone(),
+        };
+        app.emit("document-patch", message).map_err(|e| e.to_string())?;
+    }
+    app.state::<Arc<SharingHub>>().publish_changes(doc_id, patches);
+    Ok(())
+}
+
+fn type_text(app: &AppHandle, doc_id: &str, mut at: usize, text: &str, interval: Duration, stop: &AtomicBool) {
+    for ch in text.chars() {
+        if stop.load(Ordering::Relaxed) {
+            break;
+        }
+        if let Err(e) = type_char(app, doc_id, at, ch) {
+            log::warn!("Stopped ghost typing: {}", e);
+            break;
+        }
+        at += ch.len_utf8();
+        thread::sleep(interval);
+    }
+}
+
+/// Type `text` into document `doc_id` at `offset` (UTF-16 units; the end if omitted), at
+/// `chars_per_second`. Fails if typing is already under way.
+#[tauri::command]
+pub fn start_ghost_typing(
+    app: AppHandle,
+    state: State<'_, GhostTyping>,
+    store: State<'_, DocumentStore>,
+    doc_id: String,
+    text: String,
+    offset: Option<usize>,
+    chars_per_second: Option<f64>,
+) -> Result<(), String> {
+    let rate = chars_per_second.unwrap_or(DEFAULT_CHARS_PER_SECOND);
+    if !(rate > 0.0 && rate <= MAX_CHARS_PER_SECOND) {
+        return Err(format!("Typing speed must be between 0 and {} characters a second", MAX_CHARS_PER_SECOND));
+    }
+    let at = store.with(&doc_id, |doc| {
+        let map = OffsetMap::new(&doc.text);
+        offset.map_or(doc.text.len(), |offset| map.to_byte(offset))
+    })?;
+    let mut running = state.stop.lock().unwrap();
+    if running.is_some() {
+        return Err("Ghost typing is already under way".to_string());
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *running = Some(Arc::clone(&stop));
+    drop(running);
+    transparency::note(&app, PrivacyFeature::GhostTyping, true);
+    thread::spawn(move || {
+        type_text(&app, &doc_id, at, &text, Duration::from_secs_f64(1.0 / rate), &stop);
+        *app.state::<GhostTyping>().stop.lock().unwrap() = None;
+        transparency::note(&app, PrivacyFeature::GhostTyping, false);
+    });
+    Ok(())
+}
+
+/// Stop typing where it is. Returns false if nothing was being typed.
+#[tauri::command]
+pub fn stop_ghost_typing(state: State<'_, GhostTyping>) -> bool {
+    match state.stop.lock().unwrap().as_ref() {
+        Some(stop) => {
+            stop.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,307 @@
+//! Thin wrapper around the `git` command line, used for blame and patch application.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::documents::DocumentStore;
+use crate::storage;
+use crate::workers::{Priority, WorkerPool};
+
+/// Run `git` in `dir` and return stdout, or stderr as the error.
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    run_git_with_input(dir, args, None)
+}
+
+pub fn run_git_with_input(dir: &Path, args: &[&str], input: Option<&str>) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        use std::io::Write;
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Failed to write to git: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Root of the work tree containing `path`.
+pub fn repo_root(path: &Path) -> Result<PathBuf, String> {
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(Path::new(".")) };
+    let root = run_git(dir, &["rev-parse", "--show-toplevel"])
+        .map_err(|e| format!("{} is not inside a git repository: {}", path.display(), e))?;
+    Ok(PathBuf::from(root.trim()))
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlameLine {
+    /// One-based line number in the current file.
+    pub line: usize,
+    pub commit: String,
+    pub author: String,
+    pub author_email: String,
+    /// Author time, seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub age_seconds: u64,
+    pub summary: String,
+    /// The line has local changes that are not committed yet.
+    pub uncommitted: bool,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineRange {
+    /// One-based, inclusive.
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Default)]
+struct CommitInfo {
+    author: String,
+    author_email: String,
+    timestamp: u64,
+    summary: String,
+}
+
+fn parse_porcelain(output: &str) -> Vec<BlameLine> {
+    let mut commits: HashMap<String, CommitInfo> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current: Option<(String, usize)> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // Content line closes the current entry.
+            if let Some((sha, final_line)) = current.take() {
+                let info = commits.get(&sha).cloned().unwrap_or_default();
+                let uncommitted = sha.bytes().all(|b| b == b'0');
+                lines.push(BlameLine {
+                    line: final_line,
+                    commit: sha[..sha.len().min(10)].to_string(),
+                    author: info.author,
+                    author_email: info.author_email.trim_matches(|c| c == '<' || c == '>').to_string(),
+                    timestamp: info.timestamp,
+                    age_seconds: storage::now_secs().saturating_sub(info.timestamp),
+                    summary: info.summary,
+                    uncommitted,
+                });
+            }
+            continue;
+        }
+        if current.is_none() {
+            let mut parts = line.split(' ');
+            if let (Some(sha), Some(_), Some(final_line)) = (parts.next(), parts.next(), parts.next()) {
+                if sha.len() == 40 {
+                    if let Ok(final_line) = final_line.parse() {
+                        commits.entry(sha.to_string()).or_default();
+                        current = Some((sha.to_string(), final_line));
+                    }
+                }
+            }
+            continue;
+        }
+        let Some((sha, _)) = current.as_ref() else {
+            continue;
+        };
+        let info = commits.entry(sha.clone()).or_default();
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => info.author = value.to_string(),
+            "author-mail" => info.author_email = value.to_string(),
+            "author-time" => info.timestamp = value.parse().unwrap_or(0),
+            "summary" => info.summary = value.to_string(),
+            _ => {}
+        }
+    }
+    lines
+}
+
+struct CachedBlame {
+    modified: Option<SystemTime>,
+    head: String,
+    lines: Vec<BlameLine>,
+}
+
+/// Whole-file blame results, invalidated when the file or `HEAD` changes.
+#[derive(Default)]
+pub struct BlameCache {
+    entries: Mutex<HashMap<PathBuf, CachedBlame>>,
+}
+
+fn blame_file(path: &Path) -> Result<(Vec<BlameLine>, String, Option<SystemTime>), String> {
+    let root = repo_root(path)?;
+    let head = run_git(&root, &["rev-parse", "HEAD"]).unwrap_or_default().trim().to_string();
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+    let file = path.to_string_lossy();
+    let output = run_git(&root, &["blame", "--porcelain", "--", &file])?;
+    Ok((parse_porcelain(&output), head, modified))
+}
+
+/// Last-change information for each line of `path`, optionally limited to `range`.
+#[tauri::command]
+pub async fn blame(
+    cache: State<'_, BlameCache>,
+    pool: State<'_, WorkerPool>,
+    path: String,
+    range: Option<LineRange>,
+) -> Result<Vec<BlameLine>, String> {
+    let path = fs::canonicalize(&path).map_err(|e| format!("Failed to resolve {}: {}", path, e))?;
+    let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let head = repo_root(&path)
+        .and_then(|root| run_git(&root, &["rev-parse", "HEAD"]))
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    let cached = cache
+        .entries
+        .lock()
+        .unwrap()
+        .get(&path)
+        .filter(|cached| cached.modified == modified && cached.head == head)
+        .map(|cached| cached.lines.clone());
+    let lines = match cached {
+        Some(lines) => lines,
+        None => {
+            let blamed = path.clone();
+            let (lines, head, modified) = pool
+                .run(Priority::Interactive, "Blame", move |_| blame_file(&blamed))
+                .await?;
+            let entry = CachedBlame {
+                modified,
+                head,
+                lines: lines.clone(),
+            };
+            cache.entries.lock().unwrap().insert(path, entry);
+            lines
+        }
+    };
+    let now = storage::now_secs();
+    Ok(lines
+        .iter()
+        .filter(|l| range.map_or(true, |r| l.line >= r.start && l.line <= r.end))
+        .map(|l| BlameLine {
+            age_seconds: now.saturating_sub(l.timestamp),
+            ..l.clone()
+        })
+        .collect())
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchFileStat {
+    pub path: String,
+    /// `None` for binary files.
+    pub added: Option<usize>,
+    pub removed: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchResult {
+    pub files: Vec<PatchFileStat>,
+    /// `git apply` error lines for hunks that do not apply; empty when the patch is clean.
+    pub conflicts: Vec<String>,
+    pub applied: bool,
+}
+
+fn parse_numstat(output: &str) -> Vec<PatchFileStat> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let (added, removed, path) = (parts.next()?, parts.next()?, parts.next()?);
+            Some(PatchFileStat {
+                path: path.to_string(),
+                added: added.parse().ok(),
+                removed: removed.parse().ok(),
+            })
+        })
+        .collect()
+}
+
+/// Apply the unified diff held in document `doc_id` to the work tree containing `target_path`.
+///
+/// Patch paths are taken relative to `target_path` when it is a directory, or limited to
+/// that file otherwise. With `dry_run` only the per-file summary and conflicts are returned;
+/// a patch with conflicts is never partially applied.
+#[tauri::command]
+pub fn apply_shared_patch(
+    store: State<'_, DocumentStore>,
+    doc_id: String,
+    target_path: String,
+    dry_run: Option<bool>,
+) -> Result<PatchResult, String> {
+    let patch = store.with(&doc_id, |doc| doc.text.clone())?;
+    apply_patch(patch, &target_path, dry_run.unwrap_or(false))
+}
+
+/// Apply the unified diff `patch` to the work tree containing `target_path`, as
+/// `apply_shared_patch` does.
+pub fn apply_patch(mut patch: String, target_path: &str, dry_run: bool) -> Result<PatchResult, String> {
+    if !patch.ends_with('\n') {
+        patch.push('\n');
+    }
+    let target = fs::canonicalize(&target_path).map_err(|e| format!("Failed to resolve {}: {}", target_path, e))?;
+    let root = repo_root(&target)?;
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let relative = target
+        .strip_prefix(&root)
+        .map_err(|_| format!("{} is outside {}", target.display(), root.display()))?
+        .to_string_lossy()
+        .replace('\\', "/");
+
+    let mut scope = Vec::new();
+    if target.is_dir() {
+        if !relative.is_empty() {
+            scope.push(format!("--directory={}", relative));
+        }
+    } else {
+        scope.push(format!("--include={}", relative));
+    }
+    let args = |extra: &[&'static str]| -> Vec<String> {
+        let mut args = vec!["apply".to_string()];
+        args.extend(extra.iter().map(|a| a.to_string()));
+        args.extend(scope.iter().cloned());
+        args.push("-".to_string());
+        args
+    };
+    let git = |args: Vec<String>| {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run_git_with_input(&root, &args, Some(&patch))
+    };
+
+    let files = parse_numstat(&git(args(&["--numstat"]))?);
+    if files.is_empty() {
+        return Err("The document does not contain a patch for this location".to_string());
+    }
+    let conflicts: Vec<String> = match git(args(&["--check"])) {
+        Ok(_) => Vec::new(),
+        Err(stderr) => {
+            let errors: Vec<String> = stderr.lines().filter(|l| l.starts_with("error:")).map(str::to_string).collect();
+            if errors.is_empty() { vec![stderr] } else { errors }
+        }
+    };
+    if dry_run || !conflicts.is_empty() {
+        return Ok(PatchResult { files, conflicts, applied: false });
+    }
+    git(args(&[]))?;
+    Ok(PatchResult { files, conflicts, applied: true })
+}
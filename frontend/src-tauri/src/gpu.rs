@@ -0,0 +1,280 @@
+//! Rasterizing recorded frames on the GPU, behind the `gpu` feature.
+//!
+//! The recorder lays each frame out as a grid of character cells; here a compute shader turns
+//! the grid into palette-indexed pixels, scaled, four to a word. The device is opened on first
+//! use and shared by every recording. Anything that goes wrong is returned as an error so the
+//! caller can fall back to drawing on the CPU.
+
+use std::borrow::Cow;
+use std::sync::{mpsc, Mutex, OnceLock};
+
+use wgpu::util::DeviceExt;
+
+const WORKGROUP_SIZE: u32 = 64;
+const MAX_GROUPS: u32 = 65535;
+
+const SHADER: &str = r#"
+struct Params {
+    width: u32,
+    scale: u32,
+    grid_columns: u32,
+    rows: u32,
+    current_row: u32,
+    caret_row: u32,
+    caret_x: u32,
+    pixels: u32,
+    stride: u32,
+    padding: u32,
+    cell_width: u32,
+    cell_height: u32,
+    background: u32,
+    current_line: u32,
+    caret: u32,
+    unused: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Three words per cell: glyph rows 0-3, glyph rows 4-7, palette index.
+@group(0) @binding(1) var<storage, read> cells: array<u32>;
+@group(0) @binding(2) var<storage, read_write> out: array<u32>;
+
+fn pixel(i: u32) -> u32 {
+    let scaled_width = params.width * params.scale;
+    let x = (i % scaled_width) / params.scale;
+    let y = (i / scaled_width) / params.scale;
+    var color = params.background;
+    if (y < params.padding) {
+        return color;
+    }
+    let row = (y - params.padding) / params.cell_height;
+    if (row == params.current_row) {
+        color = params.current_line;
+    }
+    if (x >= params.padding && row < params.rows) {
+        let column = (x - params.padding) / params.cell_width;
+        if (column < params.grid_columns) {
+            let cell = (row * params.grid_columns + column) * 3u;
+            // Glyph rows are drawn two pixels high.
+            let glyph_row = ((y - params.padding) % params.cell_height) / 2u;
+            let bits = (cells[cell + glyph_row / 4u] >> ((glyph_row % 4u) * 8u)) & 0xffu;
+            if (((bits >> ((x - params.padding) % params.cell_width)) & 1u) == 1u) {
+                color = cells[cell + 2u];
+            }
+        }
+    }
+    if (row == params.caret_row && x >= params.caret_x && x < params.caret_x + 2u) {
+        color = params.caret;
+    }
+    return color;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let word = id.x + id.y * params.stride;
+    let first = word * 4u;
+    if (first >= params.pixels) {
+        return;
+    }
+    var packed = 0u;
+    for (var k = 0u; k < 4u; k++) {
+        if (first + k < params.pixels) {
+            packed |= pixel(first + k) << (k * 8u);
+        }
+    }
+    out[word] = packed;
+}
+"#;
+
+/// A frame laid out as character cells, in the recorder's unscaled pixel coordinates.
+pub struct Grid<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub scale: usize,
+    pub columns: usize,
+    pub rows: usize,
+    /// Glyph bitmap and palette index of each cell, row by row.
+    pub cells: &'a [([u8; 8], u8)],
+    pub current_row: Option<usize>,
+    /// Row and left pixel of the caret.
+    pub caret: Option<(usize, usize)>,
+    pub padding: usize,
+    pub cell_width: usize,
+    pub cell_height: usize,
+    /// Palette indices of the background, the current line and the caret.
+    pub colors: [u8; 3],
+}
+
+struct Buffers {
+    words: u64,
+    output: wgpu::Buffer,
+    readback: wgpu::Buffer,
+}
+
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    /// Output buffers of the last frame size, reused while frames stay that size.
+    buffers: Mutex<Option<Buffers>>,
+}
+
+/// The shared GPU rasterizer, or `None` if there is no usable adapter.
+pub fn shared() -> Option<&'static Gpu> {
+    static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+    GPU.get_or_init(|| match pollster::block_on(Gpu::open()) {
+        Ok(gpu) => Some(gpu),
+        Err(e) => {
+            log::info!("GPU rasterization unavailable, drawing on the CPU: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+impl Gpu {
+    async fn open() -> Result<Self, String> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .ok_or("No GPU adapter")?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor::default(), None)
+            .await
+            .map_err(|e| e.to_string())?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("recorder frame"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("recorder frame"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Ok(Self {
+            device,
+            queue,
+            pipeline,
+            buffers: Mutex::new(None),
+        })
+    }
+
+    /// Draw `grid` into `out` as one palette index per pixel, at the grid's scale.
+    pub fn rasterize(&self, grid: &Grid, out: &mut Vec<u8>) -> Result<(), String> {
+        let pixels = grid.width * grid.scale * grid.height * grid.scale;
+        let pixels_u32 = u32::try_from(pixels).map_err(|_| "Frame is too large for the GPU")?;
+        let words = pixels.div_ceil(4) as u32;
+        let groups = words.div_ceil(WORKGROUP_SIZE);
+        let (groups_x, groups_y) = (groups.min(MAX_GROUPS), groups.div_ceil(MAX_GROUPS));
+        let none = u32::MAX;
+        let params: [u32; 16] = [
+            grid.width as u32,
+            grid.scale as u32,
+            grid.columns as u32,
+            grid.rows as u32,
+            grid.current_row.map_or(none, |row| row as u32),
+            grid.caret.map_or(none, |(row, _)| row as u32),
+            grid.caret.map_or(0, |(_, x)| x as u32),
+            pixels_u32,
+            groups_x * WORKGROUP_SIZE,
+            grid.padding as u32,
+            grid.cell_width as u32,
+            grid.cell_height as u32,
+            grid.colors[0] as u32,
+            grid.colors[1] as u32,
+            grid.colors[2] as u32,
+            0,
+        ];
+        let mut cells: Vec<u32> = Vec::with_capacity(grid.cells.len() * 3 + 3);
+        for (glyph, color) in grid.cells {
+            cells.push(u32::from_le_bytes([glyph[0], glyph[1], glyph[2], glyph[3]]));
+            cells.push(u32::from_le_bytes([glyph[4], glyph[5], glyph[6], glyph[7]]));
+            cells.push(*color as u32);
+        }
+        // Storage buffers may not be empty.
+        cells.resize(cells.len().max(3), 0);
+
+        let uniform = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("recorder params"),
+            contents: &words_to_bytes(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let cells = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("recorder cells"),
+            contents: &words_to_bytes(&cells),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let mut buffers = self.buffers.lock().unwrap();
+        let size = words as u64 * 4;
+        if buffers.as_ref().map_or(true, |b| b.words != words as u64) {
+            *buffers = Some(Buffers {
+                words: words as u64,
+                output: self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("recorder pixels"),
+                    size,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback: self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("recorder readback"),
+                    size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            });
+        }
+        let Buffers { output, readback, .. } = buffers.as_ref().unwrap();
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: cells.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: output.as_entire_binding(),
+                },
+            ],
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(output, 0, readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (tx, rx) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |mapped| {
+            tx.send(mapped).ok();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| e.to_string())?;
+        out.clear();
+        out.extend_from_slice(&slice.get_mapped_range()[..pixels]);
+        readback.unmap();
+        Ok(())
+    }
+}
+
+fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
@@ -0,0 +1,264 @@
+//! Apple Handoff: continue watching a session on another of the user's devices.
+//!
+//! The current session link, document and scroll position are advertised as an `NSUserActivity`
+//! whose user info holds one JSON payload. Payloads are built here and validated here when they
+//! arrive, since anything in a continued activity comes from outside the app.
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::server::SessionServer;
+use crate::storage;
+use crate::viewer::{self, ViewerClient};
+
+const PAYLOAD_VERSION: u32 = 1;
+/// Activities older than this are stale; the session has likely moved on or ended.
+const MAX_AGE_SECS: u64 = 10 * 60;
+/// Tolerated clock skew between devices for activities that seem to come from the future.
+const MAX_SKEW_SECS: u64 = 60;
+/// Apple recommends keeping user info small; Handoff is meant for a pointer, not content.
+const MAX_PAYLOAD_BYTES: usize = 3 * 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandoffPayload {
+    pub version: u32,
+    /// Session URL to join.
+    pub link: String,
+    pub document_id: Option<String>,
+    /// Timeline position the sender was looking at.
+    pub seq: Option<u64>,
+    /// UTF-16 offset of the first visible character, to restore the scroll position.
+    pub scroll_offset: Option<usize>,
+    /// Seconds since the Unix epoch.
+    pub issued_at: u64,
+}
+
+#[derive(Default)]
+pub struct HandoffState {
+    /// The activity currently advertised.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    current: std::sync::Mutex<Option<apple::Activity>>,
+}
+
+/// Parse and check a payload from a continued activity.
+pub fn validate(raw: &str) -> Result<HandoffPayload, String> {
+    if raw.len() > MAX_PAYLOAD_BYTES {
+        return Err("Handoff payload is too large".to_string());
+    }
+    let payload: HandoffPayload =
+        serde_json::from_str(raw).map_err(|e| format!("Invalid Handoff payload: {}", e))?;
+    if payload.version != PAYLOAD_VERSION {
+        return Err(format!("Unsupported Handoff payload version {}", payload.version));
+    }
+    let now = storage::now_secs();
+    if payload.issued_at > now + MAX_SKEW_SECS || now.saturating_sub(payload.issued_at) > MAX_AGE_SECS {
+        return Err("Handoff activity has expired".to_string());
+    }
+    viewer::parse_link(&payload.link)?;
+    let valid_id = |id: &String| !id.is_empty() && id.len() <= 32 && id.chars().all(|c| c.is_ascii_hexdigit());
+    if payload.document_id.as_ref().is_some_and(|id| !valid_id(id)) {
+        return Err("Invalid document id in Handoff payload".to_string());
+    }
+    Ok(payload)
+}
+
+/// Validate a continued activity and hand it to the webview as `handoff-received`.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn receive(app: &AppHandle, raw: &str) {
+    use tauri::Emitter;
+
+    let result = match validate(raw) {
+        Ok(payload) => app.emit("handoff-received", payload),
+        Err(e) => app.emit("handoff-rejected", e),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to emit Handoff activity: {}", e);
+    }
+}
+
+/// Accept continued activities on Apple platforms. Called once from setup.
+pub fn install(app: &AppHandle) {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    unsafe {
+        apple::install(app.clone());
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    let _ = app;
+}
+
+/// Advertise the session being hosted or watched for Handoff to the user's other devices.
+#[tauri::command]
+pub fn advertise_handoff(
+    app: AppHandle,
+    document_id: Option<String>,
+    seq: Option<u64>,
+    scroll_offset: Option<usize>,
+) -> Result<HandoffPayload, String> {
+    let link = app
+        .state::<SessionServer>()
+        .url()
+        .or_else(|| app.state::<ViewerClient>().link())
+        .ok_or("No session to hand off")?;
+    let payload = HandoffPayload {
+        version: PAYLOAD_VERSION,
+        link,
+        document_id,
+        seq,
+        scroll_offset,
+        issued_at: storage::now_secs(),
+    };
+    let raw = serde_json::to_string(&payload).map_err(|e| e.to_string())?;
+    // Catch anything the receiving side would reject before advertising it.
+    validate(&raw)?;
+
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        let handle = app.clone();
+        // NSUserActivity is main-thread only.
+        app.run_on_main_thread(move || {
+            let state = handle.state::<HandoffState>();
+            let mut current = state.current.lock().unwrap();
+            unsafe {
+                if let Some(previous) = current.take() {
+                    previous.invalidate();
+                }
+                *current = Some(apple::become_current(&raw, "ShareCode session"));
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        Ok(payload)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    Err("Handoff is not supported on this platform".to_string())
+}
+
+#[tauri::command]
+pub fn stop_handoff(app: AppHandle, state: State<'_, HandoffState>) -> Result<bool, String> {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    {
+        if state.current.lock().unwrap().is_none() {
+            return Ok(false);
+        }
+        let handle = app.clone();
+        app.run_on_main_thread(move || {
+            if let Some(activity) = handle.state::<HandoffState>().current.lock().unwrap().take() {
+                unsafe { activity.invalidate() };
+            }
+        })
+        .map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    {
+        let _ = (app, state);
+        Ok(false)
+    }
+}
+
+/// Validate a payload the webview received through another channel (e.g. a universal link).
+#[tauri::command]
+pub fn accept_handoff(payload: String) -> Result<HandoffPayload, String> {
+    validate(&payload)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+mod apple {
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+    use std::sync::OnceLock;
+
+    use objc::runtime::{class_addMethod, Class, Imp, Object, Sel, BOOL, NO, YES};
+    use objc::{class, msg_send, sel, sel_impl, Encode};
+    use tauri::AppHandle;
+
+    /// Declared under `NSUserActivityTypes` in Info.plist.
+    pub const ACTIVITY_TYPE: &str = "com.sharecode.desktop.session";
+    const PAYLOAD_KEY: &str = "payload";
+
+    type Id = *mut Object;
+
+    /// The delegate callback has no way to reach app state but this.
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    /// A retained `NSUserActivity`, only touched on the main thread.
+    pub struct Activity(Id);
+
+    unsafe impl Send for Activity {}
+
+    impl Activity {
+        pub unsafe fn invalidate(self) {
+            let _: () = msg_send![self.0, invalidate];
+            let _: () = msg_send![self.0, release];
+        }
+    }
+
+    unsafe fn ns_string(value: &str) -> Id {
+        let value = CString::new(value).unwrap_or_default();
+        msg_send![class!(NSString), stringWithUTF8String: value.as_ptr()]
+    }
+
+    unsafe fn rust_string(value: Id) -> Option<String> {
+        if value.is_null() {
+            return None;
+        }
+        let ptr: *const c_char = msg_send![value, UTF8String];
+        (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+
+    pub unsafe fn become_current(payload: &str, title: &str) -> Activity {
+        let activity: Id = msg_send![class!(NSUserActivity), alloc];
+        let activity: Id = msg_send![activity, initWithActivityType: ns_string(ACTIVITY_TYPE)];
+        let info: Id = msg_send![class!(NSDictionary), dictionaryWithObject: ns_string(payload) forKey: ns_string(PAYLOAD_KEY)];
+        let _: () = msg_send![activity, setTitle: ns_string(title)];
+        let _: () = msg_send![activity, setUserInfo: info];
+        let _: () = msg_send![activity, setEligibleForHandoff: YES];
+        let _: () = msg_send![activity, becomeCurrent];
+        Activity(activity)
+    }
+
+    /// `application:continueUserActivity:restorationHandler:`, the same selector on AppKit and UIKit.
+    extern "C" fn continue_activity(_this: &Object, _cmd: Sel, _app: Id, activity: Id, _handler: Id) -> BOOL {
+        unsafe {
+            let kind: Id = msg_send![activity, activityType];
+            if rust_string(kind).as_deref() != Some(ACTIVITY_TYPE) {
+                return NO;
+            }
+            let info: Id = msg_send![activity, userInfo];
+            if info.is_null() {
+                return NO;
+            }
+            let payload: Id = msg_send![info, objectForKey: ns_string(PAYLOAD_KEY)];
+            match (rust_string(payload), APP.get()) {
+                (Some(payload), Some(app)) => {
+                    super::receive(app, &payload);
+                    YES
+                }
+                _ => NO,
+            }
+        }
+    }
+
+    /// Add the continuation callback to the app delegate the windowing library installed.
+    pub unsafe fn install(app: AppHandle) {
+        if APP.set(app).is_err() {
+            return;
+        }
+        #[cfg(target_os = "macos")]
+        let shared: Id = msg_send![class!(NSApplication), sharedApplication];
+        #[cfg(target_os = "ios")]
+        let shared: Id = msg_send![class!(UIApplication), sharedApplication];
+        let delegate: Id = msg_send![shared, delegate];
+        if delegate.is_null() {
+            log::warn!("No app delegate; Handoff activities will not be received");
+            return;
+        }
+        let class = (*delegate).class() as *const Class as *mut Class;
+        let types = CString::new(format!("{}@:@@@", BOOL::encode().as_str())).unwrap();
+        let imp: Imp = std::mem::transmute(continue_activity as extern "C" fn(&Object, Sel, Id, Id, Id) -> BOOL);
+        // Leaves an existing implementation alone, should the delegate ever gain one.
+        class_addMethod(class, sel!(application:continueUserActivity:restorationHandler:), imp, types.as_ptr());
+    }
+}
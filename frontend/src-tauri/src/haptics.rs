@@ -0,0 +1,59 @@
+//! A tap on the trackpad when a hotkey lands, for the changes that show nothing on screen: capture
+//! protection, taskbar hiding and click-through look the same to the user either way, and panic
+//! hide is pressed precisely when the user can't look.
+//!
+//! Only Force Touch trackpads on macOS can do this, through `NSHapticFeedbackManager`, and only
+//! while a finger rests on the trackpad. Elsewhere `tap` does nothing.
+
+use tauri::{AppHandle, Manager, State};
+
+use crate::settings::SettingsStore;
+
+#[derive(Clone, Copy)]
+pub enum Haptic {
+    /// A privacy toggle was applied.
+    Toggled,
+    /// Panic hide (or restore) went through.
+    Confirmed,
+}
+
+#[cfg(target_os = "macos")]
+fn perform(app: &AppHandle, haptic: Haptic) {
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    // NSHapticFeedbackPattern: generic 0, alignment 1, level change 2
+    let pattern: i64 = match haptic {
+        Haptic::Toggled => 0,
+        Haptic::Confirmed => 2,
+    };
+    // NSHapticFeedbackPerformanceTimeNow
+    const NOW: u64 = 1;
+    let performed = app.run_on_main_thread(move || unsafe {
+        let performer: *mut Object = msg_send![class!(NSHapticFeedbackManager), defaultPerformer];
+        let _: () = msg_send![performer, performFeedbackPattern: pattern performanceTime: NOW];
+    });
+    if let Err(e) = performed {
+        log::warn!("Failed to play haptic feedback: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn perform(_app: &AppHandle, _haptic: Haptic) {}
+
+/// Tap the trackpad, unless haptic feedback is turned off.
+pub fn tap(app: &AppHandle, haptic: Haptic) {
+    if app.state::<SettingsStore>().get(app).is_ok_and(|settings| !settings.skip_haptics) {
+        perform(app, haptic);
+    }
+}
+
+#[tauri::command]
+pub fn get_haptic_feedback(app: AppHandle, settings: State<'_, SettingsStore>) -> Result<bool, String> {
+    Ok(!settings.get(&app)?.skip_haptics)
+}
+
+#[tauri::command]
+pub fn set_haptic_feedback(app: AppHandle, settings: State<'_, SettingsStore>, enabled: bool) -> Result<(), String> {
+    settings.update(&app, |s| s.skip_haptics = !enabled)
+}
@@ -0,0 +1,423 @@
+//! Incremental highlighting for documents that change a keystroke at a time.
+//!
+//! A [`Highlighter`] keeps the syntax tree and the highlight spans of every line. Edits are
+//! applied to the tree so the next parse reuses it, and only the lines an edit touched, plus
+//! those whose syntax the parser reports as changed, are highlighted again; every other line
+//! keeps its cached spans. The host's session server and the viewer client both render through
+//! it, and counters of the work done and saved are available from `get_highlight_stats`.
+//!
+//! Code that isn't a document, such as a snippet or a chat message, is highlighted in one go by
+//! `highlight_code`, coloured by the export themes.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::State;
+use tree_sitter::{InputEdit, Parser, Point, Tree};
+
+use crate::documents::DocumentStore;
+use crate::export::{self, ThemeName};
+use crate::syntax::{self, HighlightClass, HighlightSpan};
+use crate::workers::{Priority, WorkerPool};
+
+static FULL_PARSES: AtomicU64 = AtomicU64::new(0);
+static INCREMENTAL_PARSES: AtomicU64 = AtomicU64::new(0);
+static LINES_HIGHLIGHTED: AtomicU64 = AtomicU64::new(0);
+static LINES_REUSED: AtomicU64 = AtomicU64::new(0);
+static UPDATES: AtomicU64 = AtomicU64::new(0);
+static UPDATE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Part of a span on one line, in UTF-16 units from the start of the line.
+#[derive(Clone, Copy)]
+struct Piece {
+    start: usize,
+    end: usize,
+    class: HighlightClass,
+    /// The span carries on into the next line.
+    open: bool,
+}
+
+#[derive(Clone)]
+struct Line {
+    pieces: Vec<Piece>,
+    /// Length in UTF-16 units, line break included.
+    units: usize,
+}
+
+pub struct Highlighter {
+    language: String,
+    parser: Parser,
+    tree: Tree,
+    /// Byte offset of each line, for the text as of the last edit.
+    starts: Vec<usize>,
+    /// `None` for lines that have to be highlighted again.
+    lines: Vec<Option<Line>>,
+    /// Edits were applied to the tree since it was parsed.
+    edited: bool,
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+impl Highlighter {
+    /// A highlighter for `text`, or `None` without a grammar for `language`.
+    pub fn new(language: &str, text: &str) -> Option<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(&syntax::grammar(language)?).ok()?;
+        let tree = parser.parse(text, None)?;
+        FULL_PARSES.fetch_add(1, Ordering::Relaxed);
+        let starts = line_starts(text);
+        Some(Self {
+            language: language.to_string(),
+            parser,
+            tree,
+            lines: vec![None; starts.len()],
+            starts,
+            edited: false,
+        })
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    fn point(&self, byte: usize) -> Point {
+        let row = self.starts.partition_point(|&start| start <= byte) - 1;
+        Point::new(row, byte - self.starts[row])
+    }
+
+    /// Record that `delete_len` bytes at `start` were replaced by `insert`. Offsets are into the
+    /// text as it was before this edit; edits must be applied in the order they were made.
+    pub fn edit(&mut self, start: usize, delete_len: usize, insert: &str) {
+        let old_end = start + delete_len;
+        let start_position = self.point(start);
+        let old_end_position = self.point(old_end);
+        let new_end_position = match insert.rfind('\n') {
+            Some(last) => Point::new(start_position.row + insert.matches('\n').count(), insert.len() - last - 1),
+            None => Point::new(start_position.row, start_position.column + insert.len()),
+        };
+        self.tree.edit(&InputEdit {
+            start_byte: start,
+            old_end_byte: old_end,
+            new_end_byte: start + insert.len(),
+            start_position,
+            old_end_position,
+            new_end_position,
+        });
+        self.edited = true;
+
+        // Lines from the edited one to the one the deletion ended on are replaced by the lines
+        // of the inserted text, and the rest move by the change in length.
+        let (first, last) = (start_position.row, old_end_position.row);
+        let inserted: Vec<usize> = insert.match_indices('\n').map(|(i, _)| start + i + 1).collect();
+        self.lines
+            .splice(first..=last, std::iter::repeat(None).take(inserted.len() + 1));
+        let shifted: Vec<usize> = self.starts[last + 1..]
+            .iter()
+            .map(|&s| s + insert.len() - delete_len)
+            .collect();
+        self.starts.truncate(first + 1);
+        self.starts.extend(inserted);
+        self.starts.extend(shifted);
+    }
+
+    /// Highlight lines `range` of `text` again from the current tree.
+    fn highlight_lines(&mut self, text: &str, range: Range<usize>) {
+        let end_byte = |line: usize| self.starts.get(line + 1).copied().unwrap_or(text.len());
+        let bytes = self.starts[range.start]..end_byte(range.end - 1);
+        let spans = syntax::highlight_bytes(&self.language, &self.tree, bytes);
+        let mut next = 0;
+        for line in range {
+            let (start, end) = (self.starts[line], end_byte(line));
+            while next < spans.len() && spans[next].0.end <= start {
+                next += 1;
+            }
+            let units = |to: usize| text[start..to].encode_utf16().count();
+            let pieces = spans[next..]
+                .iter()
+                .take_while(|(span, _)| span.start < end)
+                .map(|(span, class)| Piece {
+                    start: units(span.start.max(start)),
+                    end: units(span.end.min(end)),
+                    class: *class,
+                    open: span.end > end,
+                })
+                .collect();
+            self.lines[line] = Some(Line { pieces, units: units(end) });
+        }
+    }
+
+    /// Spans for the whole of `text`, which must be the text after every edit so far.
+    pub fn spans(&mut self, text: &str) -> Vec<HighlightSpan> {
+        let started = Instant::now();
+        if self.edited {
+            // Reparsing reuses the unchanged parts of the edited tree.
+            let Some(tree) = self.parser.parse(text, Some(&self.tree)) else {
+                return Vec::new();
+            };
+            INCREMENTAL_PARSES.fetch_add(1, Ordering::Relaxed);
+            let last = self.lines.len() - 1;
+            for changed in self.tree.changed_ranges(&tree) {
+                let rows = changed.start_point.row.min(last)..=changed.end_point.row.min(last);
+                self.lines[rows].fill(None);
+            }
+            self.tree = tree;
+            self.edited = false;
+        }
+
+        let (mut highlighted, mut line) = (0, 0);
+        while line < self.lines.len() {
+            if self.lines[line].is_some() {
+                line += 1;
+                continue;
+            }
+            let end = (line..self.lines.len()).find(|&l| self.lines[l].is_some()).unwrap_or(self.lines.len());
+            self.highlight_lines(text, line..end);
+            highlighted += end - line;
+            line = end;
+        }
+        LINES_HIGHLIGHTED.fetch_add(highlighted as u64, Ordering::Relaxed);
+        LINES_REUSED.fetch_add((self.lines.len() - highlighted) as u64, Ordering::Relaxed);
+
+        let mut out: Vec<HighlightSpan> = Vec::new();
+        let mut open = false;
+        let mut offset = 0;
+        for line in self.lines.iter().flatten() {
+            for piece in &line.pieces {
+                match out.last_mut() {
+                    // The rest of a span from the line before.
+                    Some(last) if open && piece.start == 0 && last.class == piece.class => {
+                        last.end = offset + piece.end
+                    }
+                    _ => out.push(HighlightSpan {
+                        start: offset + piece.start,
+                        end: offset + piece.end,
+                        class: piece.class,
+                    }),
+                }
+                open = piece.open;
+            }
+            offset += line.units;
+        }
+        UPDATES.fetch_add(1, Ordering::Relaxed);
+        UPDATE_MICROS.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+        out
+    }
+}
+
+struct Cached {
+    seq: u64,
+    highlighter: Highlighter,
+}
+
+/// Highlighters for the host's own documents, brought up to date from their timelines.
+#[derive(Default)]
+pub struct Highlights {
+    docs: Mutex<HashMap<String, Cached>>,
+}
+
+impl Highlights {
+    /// The document's timeline position, language, text and spans.
+    pub fn render(
+        &self,
+        store: &DocumentStore,
+        doc_id: &str,
+    ) -> Result<(u64, Option<String>, String, Vec<HighlightSpan>), String> {
+        let mut docs = self.docs.lock().unwrap();
+        let cached = docs.remove(doc_id);
+        let (seq, language, text, steps) = store.with(doc_id, |doc| {
+            let seq = doc.timeline.seq();
+            let steps = cached
+                .as_ref()
+                .filter(|c| doc.language.as_deref() == Some(c.highlighter.language()))
+                .and_then(|c| doc.timeline.steps_after(c.seq));
+            (seq, doc.language.clone(), doc.text.clone(), steps)
+        })?;
+        let Some(language) = language else {
+            return Ok((seq, None, text, Vec::new()));
+        };
+        let highlighter = match (cached, steps) {
+            (Some(mut cached), Some(steps)) => {
+                for step in &steps {
+                    cached.highlighter.edit(step.start, step.delete_len, &step.insert);
+                }
+                Some(cached.highlighter)
+            }
+            _ => Highlighter::new(&language, &text),
+        };
+        let Some(mut highlighter) = highlighter else {
+            return Ok((seq, Some(language), text, Vec::new()));
+        };
+        let spans = highlighter.spans(&text);
+        docs.insert(doc_id.to_string(), Cached { seq, highlighter });
+        Ok((seq, Some(language), text, spans))
+    }
+
+    pub fn forget(&self, doc_id: &str) {
+        self.docs.lock().unwrap().remove(doc_id);
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightStats {
+    pub full_parses: u64,
+    pub incremental_parses: u64,
+    pub lines_highlighted: u64,
+    /// Lines whose cached spans were used as they were.
+    pub lines_reused: u64,
+    pub updates: u64,
+    pub average_update_micros: u64,
+    /// Host documents with a cached highlighter.
+    pub cached_documents: usize,
+}
+
+#[cfg(feature = "bench")]
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HighlightBenchmark {
+    pub lines: usize,
+    pub edits: usize,
+    /// Average time per edit to parse and highlight the whole document.
+    pub full_micros: u64,
+    /// Average time per edit through a [`Highlighter`].
+    pub incremental_micros: u64,
+    /// Both ways produced the same spans after every edit.
+    pub consistent: bool,
+}
+
+/// A highlighted stretch of text with the colour the theme gives it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StyledSpan {
+    /// UTF-16 offsets into the source.
+    pub start: usize,
+    pub end: usize,
+    pub class: HighlightClass,
+    /// `#rrggbb`.
+    pub color: String,
+}
+
+/// Highlight code that isn't an open document, with the same grammars and export themes as
+/// printing and rich copy, so every webview shows it alike.
+#[tauri::command]
+pub async fn highlight_code(
+    pool: State<'_, WorkerPool>,
+    source: String,
+    language: String,
+    theme: Option<ThemeName>,
+) -> Result<Vec<StyledSpan>, String> {
+    let theme = export::theme(theme.unwrap_or_default());
+    // Without a grammar for `language` there are no spans
+    let spans = pool
+        .run(Priority::Interactive, "Highlighting", move |_| {
+            Ok(syntax::parse(&language, &source)
+                .map(|tree| syntax::highlight(&language, &tree, &source))
+                .unwrap_or_default())
+        })
+        .await?;
+    let styled = spans.into_iter().map(|span| {
+        let [r, g, b] = theme.color(Some(span.class));
+        StyledSpan {
+            start: span.start,
+            end: span.end,
+            class: span.class,
+            color: format!("#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    });
+    Ok(styled.collect())
+}
+
+/// `source` as a `<pre>` with inline colours, as rich copy puts on the clipboard.
+#[cfg(desktop)]
+#[tauri::command]
+pub async fn highlight_code_html(
+    pool: State<'_, WorkerPool>,
+    source: String,
+    language: String,
+    theme: Option<ThemeName>,
+) -> Result<String, String> {
+    let theme = export::theme(theme.unwrap_or_default());
+    pool.run(Priority::Interactive, "Highlighting", move |_| {
+        let lines = export::styled_lines(&source, Some(&language), crate::richcopy::TAB_WIDTH);
+        Ok(crate::richcopy::html(&lines, &theme))
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn get_highlight_stats(highlights: State<'_, Highlights>) -> HighlightStats {
+    let updates = UPDATES.load(Ordering::Relaxed);
+    HighlightStats {
+        full_parses: FULL_PARSES.load(Ordering::Relaxed),
+        incremental_parses: INCREMENTAL_PARSES.load(Ordering::Relaxed),
+        lines_highlighted: LINES_HIGHLIGHTED.load(Ordering::Relaxed),
+        lines_reused: LINES_REUSED.load(Ordering::Relaxed),
+        updates,
+        average_update_micros: UPDATE_MICROS.load(Ordering::Relaxed) / updates.max(1),
+        cached_documents: highlights.docs.lock().unwrap().len(),
+    }
+}
+
+/// Time typing `edits` characters into the middle of an open document, highlighting after each
+/// one both from scratch and incrementally. The document itself is not changed. Only in builds
+/// made with the `bench` feature.
+#[cfg(feature = "bench")]
+#[tauri::command]
+pub async fn run_highlight_benchmark(
+    store: State<'_, DocumentStore>,
+    pool: State<'_, WorkerPool>,
+    doc_id: String,
+    edits: usize,
+) -> Result<HighlightBenchmark, String> {
+    let (language, mut text) = store.with(&doc_id, |doc| (doc.language.clone(), doc.text.clone()))?;
+    let language = language.ok_or("The document has no language to highlight")?;
+    let edits = edits.clamp(1, 1000);
+    pool.run(Priority::Background, "Highlighting benchmark", move |cancel| {
+        let mut highlighter = Highlighter::new(&language, &text).ok_or_else(|| format!("No syntax support for {}", language))?;
+        highlighter.spans(&text);
+        let mut at = text.len() / 2;
+        while !text.is_char_boundary(at) {
+            at -= 1;
+        }
+        let (mut full, mut incremental) = (0, 0);
+        let mut consistent = true;
+        for i in 0..edits {
+            cancel.check()?;
+            let insert = if i % 40 == 39 { "\n" } else { "x" };
+            text.insert_str(at, insert);
+
+            let started = Instant::now();
+            let tree = syntax::parse(&language, &text)?;
+            let expected = syntax::highlight(&language, &tree, &text);
+            full += started.elapsed().as_micros();
+
+            let started = Instant::now();
+            highlighter.edit(at, 0, insert);
+            let spans = highlighter.spans(&text);
+            incremental += started.elapsed().as_micros();
+
+            consistent &= spans.len() == expected.len()
+                && spans
+                    .iter()
+                    .zip(&expected)
+                    .all(|(a, b)| (a.start, a.end, a.class) == (b.start, b.end, b.class));
+            at += insert.len();
+        }
+        Ok(HighlightBenchmark {
+            lines: text.lines().count(),
+            edits,
+            full_micros: (full / edits as u128) as u64,
+            incremental_micros: (incremental / edits as u128) as u64,
+            consistent,
+        })
+    })
+    .await
+}
@@ -0,0 +1,701 @@
+//! Per-document undo tree kept in the backend so history survives webview reloads.
+//!
+//! Each node holds the edits that lead from its parent's text to its own. Undo moves to the
+//! parent, redo to the most recently visited child, and editing after an undo starts a new
+//! branch instead of discarding the old one.
+//!
+//! Alongside the tree, every change is appended to a linear timeline so the document can be
+//! reconstructed at any past sequence number for scrubbing.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::documents::DocumentStore;
+use crate::storage;
+use crate::syntax::OffsetMap;
+
+/// Edits by the same author closer together than this are undone as one step.
+const GROUP_WINDOW_MS: u64 = 1000;
+/// Upper bound on the text held by one document's history; the oldest states are dropped first.
+const MAX_HISTORY_BYTES: usize = 4 * 1024 * 1024;
+
+/// Replace `deleted` at byte offset `start` with `inserted`.
+#[derive(Clone, Serialize, Deserialize)]
+struct Edit {
+    start: usize,
+    deleted: String,
+    inserted: String,
+}
+
+impl Edit {
+    fn size(&self) -> usize {
+        self.deleted.len() + self.inserted.len()
+    }
+
+    fn apply(&self, text: &mut String) {
+        text.replace_range(self.start..self.start + self.deleted.len(), &self.inserted);
+    }
+
+    fn revert(&self, text: &mut String) {
+        text.replace_range(self.start..self.start + self.inserted.len(), &self.deleted);
+    }
+}
+
+/// The smallest single edit turning `old` into `new`, or `None` if they are equal.
+fn diff(old: &str, new: &str) -> Option<Edit> {
+    if old == new {
+        return None;
+    }
+    let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    Some(Edit {
+        start: prefix,
+        deleted: old[prefix..old.len() - suffix].to_string(),
+        inserted: new[prefix..new.len() - suffix].to_string(),
+    })
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct UndoNode {
+    parent: Option<u64>,
+    children: Vec<u64>,
+    edits: Vec<Edit>,
+    author: Option<String>,
+    /// Milliseconds since the Unix epoch of the first and last edit in the group.
+    started_at: u64,
+    updated_at: u64,
+    /// Child that redo follows; the most recently created or visited branch.
+    redo_child: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UndoTree {
+    nodes: HashMap<u64, UndoNode>,
+    root: u64,
+    current: u64,
+    next_id: u64,
+    bytes: usize,
+    /// Hash of the text at `current`, used to discard persisted history for files changed elsewhere.
+    #[serde(default)]
+    text_hash: String,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        let root = UndoNode {
+            parent: None,
+            children: Vec::new(),
+            edits: Vec::new(),
+            author: None,
+            started_at: 0,
+            updated_at: 0,
+            redo_child: None,
+        };
+        Self {
+            nodes: HashMap::from([(0, root)]),
+            root: 0,
+            current: 0,
+            next_id: 1,
+            bytes: 0,
+            text_hash: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub author: Option<String>,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub current: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryState {
+    pub text: String,
+    pub can_undo: bool,
+    pub can_redo: bool,
+    /// Author of the step that was undone or redone.
+    pub author: Option<String>,
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn hash(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl UndoTree {
+    /// Record the change from `old` to `new` made by `author`.
+    pub fn record(&mut self, old: &str, new: &str, author: Option<&str>) {
+        let Some(edit) = diff(old, new) else {
+            return;
+        };
+        let now = now_millis();
+        self.bytes += edit.size();
+        let current = &self.nodes[&self.current];
+        let extend = self.current != self.root
+            && current.children.is_empty()
+            && current.author.as_deref() == author
+            && now.saturating_sub(current.updated_at) < GROUP_WINDOW_MS;
+        if extend {
+            let node = self.nodes.get_mut(&self.current).unwrap();
+            node.edits.push(edit);
+            node.updated_at = now;
+        } else {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.nodes.insert(
+                id,
+                UndoNode {
+                    parent: Some(self.current),
+                    children: Vec::new(),
+                    edits: vec![edit],
+                    author: author.map(str::to_string),
+                    started_at: now,
+                    updated_at: now,
+                    redo_child: None,
+                },
+            );
+            let parent = self.nodes.get_mut(&self.current).unwrap();
+            parent.children.push(id);
+            parent.redo_child = Some(id);
+            self.current = id;
+        }
+        self.prune();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current != self.root
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.nodes[&self.current].redo_child.is_some()
+    }
+
+    /// Step back to the parent state, returning the author of the undone step.
+    pub fn undo(&mut self, text: &mut String) -> Option<Option<String>> {
+        let node = &self.nodes[&self.current];
+        let parent = node.parent?;
+        for edit in node.edits.iter().rev() {
+            edit.revert(text);
+        }
+        let author = node.author.clone();
+        // Close the group so the next edit after an undo never merges into the undone step.
+        self.nodes.get_mut(&self.current).unwrap().updated_at = 0;
+        self.nodes.get_mut(&parent).unwrap().redo_child = Some(self.current);
+        self.current = parent;
+        Some(author)
+    }
+
+    /// Re-apply a child state; `branch` picks a specific child instead of the last visited one.
+    pub fn redo(&mut self, text: &mut String, branch: Option<u64>) -> Option<Option<String>> {
+        let current = &self.nodes[&self.current];
+        let child = match branch {
+            Some(id) if current.children.contains(&id) => id,
+            Some(_) => return None,
+            None => current.redo_child?,
+        };
+        self.nodes.get_mut(&self.current).unwrap().redo_child = Some(child);
+        let node = &self.nodes[&child];
+        for edit in &node.edits {
+            edit.apply(text);
+        }
+        self.current = child;
+        Some(node.author.clone())
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = self
+            .nodes
+            .iter()
+            .map(|(&id, node)| HistoryEntry {
+                id,
+                parent: node.parent,
+                author: node.author.clone(),
+                started_at: node.started_at,
+                updated_at: node.updated_at,
+                current: id == self.current,
+            })
+            .collect();
+        entries.sort_by_key(|e| e.id);
+        entries
+    }
+
+    fn remove_subtree(&mut self, id: u64) {
+        if let Some(node) = self.nodes.remove(&id) {
+            self.bytes -= node.edits.iter().map(Edit::size).sum::<usize>();
+            for child in node.children {
+                self.remove_subtree(child);
+            }
+        }
+    }
+
+    /// Drop abandoned branches and then the oldest states until the history fits its budget.
+    fn prune(&mut self) {
+        while self.bytes > MAX_HISTORY_BYTES && self.root != self.current {
+            let mut on_path = self.current;
+            while let Some(parent) = self.nodes[&on_path].parent.filter(|&p| p != self.root) {
+                on_path = parent;
+            }
+            let root = self.nodes.get_mut(&self.root).unwrap();
+            let stale: Vec<u64> = root.children.iter().copied().filter(|&c| c != on_path).collect();
+            if let Some(&oldest) = stale.first() {
+                root.children.retain(|&c| c != oldest);
+                if root.redo_child == Some(oldest) {
+                    root.redo_child = Some(on_path);
+                }
+                self.remove_subtree(oldest);
+                continue;
+            }
+            // Only the path to the current state is left: its first step becomes the new root.
+            let old_root = self.root;
+            self.nodes.remove(&old_root);
+            let node = self.nodes.get_mut(&on_path).unwrap();
+            self.bytes -= node.edits.iter().map(Edit::size).sum::<usize>();
+            node.edits.clear();
+            node.parent = None;
+            self.root = on_path;
+        }
+    }
+}
+
+/// Patches between full-text snapshots in a document's timeline.
+const SNAPSHOT_INTERVAL: u64 = 100;
+/// Patches kept per document; older ones are dropped a snapshot interval at a time.
+const MAX_PATCHES: usize = 20_000;
+
+struct Patch {
+    seq: u64,
+    timestamp: u64,
+    author: Option<String>,
+    edit: Edit,
+    /// `edit.start` and `edit.deleted.len()` in UTF-16 units, for viewers applying the patch.
+    start_utf16: usize,
+    deleted_utf16: usize,
+}
+
+/// A recorded change as sent to viewers, in UTF-16 offsets.
+pub struct TailPatch {
+    pub seq: u64,
+    pub start: usize,
+    pub delete_count: usize,
+    pub insert: String,
+}
+
+/// A recorded change in byte offsets, for replaying a stretch of the timeline.
+pub struct ReplayStep {
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub author: Option<String>,
+    pub start: usize,
+    pub delete_len: usize,
+    pub insert: String,
+}
+
+impl ReplayStep {
+    pub fn apply(&self, text: &mut String) {
+        text.replace_range(self.start..self.start + self.delete_len, &self.insert);
+    }
+}
+
+/// Linear record of a document's changes with a full-text snapshot every `SNAPSHOT_INTERVAL` patches.
+pub struct Timeline {
+    patches: Vec<Patch>,
+    /// `(seq, text)`, ascending; the first entry is the oldest reconstructible state.
+    snapshots: Vec<(u64, String)>,
+    seq: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEntry {
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub author: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub seq: u64,
+    pub timestamp: Option<u64>,
+    pub author: Option<String>,
+    pub text: String,
+}
+
+impl Timeline {
+    pub fn new(text: &str) -> Self {
+        Self {
+            patches: Vec::new(),
+            snapshots: vec![(0, text.to_string())],
+            seq: 0,
+        }
+    }
+
+    pub fn record(&mut self, old: &str, new: &str, author: Option<&str>) {
+        let Some(edit) = diff(old, new) else {
+            return;
+        };
+        self.seq += 1;
+        let start_utf16 = OffsetMap::new(old).to_utf16(edit.start);
+        self.patches.push(Patch {
+            seq: self.seq,
+            timestamp: now_millis(),
+            author: author.map(str::to_string),
+            start_utf16,
+            deleted_utf16: edit.deleted.encode_utf16().count(),
+            edit,
+        });
+        if self.seq % SNAPSHOT_INTERVAL == 0 {
+            self.snapshots.push((self.seq, new.to_string()));
+        }
+        if self.patches.len() > MAX_PATCHES && self.snapshots.len() > 1 {
+            self.snapshots.remove(0);
+            let oldest = self.snapshots[0].0;
+            self.patches.retain(|p| p.seq > oldest);
+        }
+    }
+
+    /// Sequence number of the latest change; `0` before any edit.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// The oldest position the text can still be reconstructed at.
+    pub fn first_seq(&self) -> u64 {
+        self.snapshots[0].0
+    }
+
+    /// Changes after `seq`, or `None` if some of them have already been dropped.
+    pub fn patches_after(&self, seq: u64) -> Option<Vec<TailPatch>> {
+        let first_kept = self.patches.first().map_or(self.seq + 1, |p| p.seq);
+        if seq + 1 < first_kept {
+            return None;
+        }
+        let start = self.patches.partition_point(|p| p.seq <= seq);
+        Some(
+            self.patches[start..]
+                .iter()
+                .map(|p| TailPatch {
+                    seq: p.seq,
+                    start: p.start_utf16,
+                    delete_count: p.deleted_utf16,
+                    insert: p.edit.inserted.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Changes after `seq` in byte offsets, or `None` if some of them have already been dropped.
+    pub fn steps_after(&self, seq: u64) -> Option<Vec<ReplayStep>> {
+        let first_kept = self.patches.first().map_or(self.seq + 1, |p| p.seq);
+        if seq + 1 < first_kept {
+            return None;
+        }
+        let start = self.patches.partition_point(|p| p.seq <= seq);
+        Some(
+            self.patches[start..]
+                .iter()
+                .map(|p| ReplayStep {
+                    seq: p.seq,
+                    timestamp: p.timestamp,
+                    author: p.author.clone(),
+                    start: p.edit.start,
+                    delete_len: p.edit.deleted.len(),
+                    insert: p.edit.inserted.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Bytes of change text recorded after `seq`.
+    pub fn bytes_after(&self, seq: u64) -> usize {
+        let start = self.patches.partition_point(|p| p.seq <= seq);
+        self.patches[start..].iter().map(|p| p.edit.size()).sum()
+    }
+
+    pub fn entries(&self) -> Vec<TimelineEntry> {
+        self.patches
+            .iter()
+            .map(|p| TimelineEntry {
+                seq: p.seq,
+                timestamp: p.timestamp,
+                author: p.author.clone(),
+            })
+            .collect()
+    }
+
+    /// The text as it was right after change `seq`, starting from the nearest earlier snapshot.
+    pub fn text_at(&self, seq: u64) -> Result<Revision, String> {
+        let (base_seq, base) = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|(snapshot, _)| *snapshot <= seq)
+            .ok_or_else(|| format!("Revision {} is older than the kept history", seq))?;
+        if seq > self.seq {
+            return Err(format!("Revision {} does not exist yet (latest is {})", seq, self.seq));
+        }
+        let mut text = base.clone();
+        let start = self.patches.partition_point(|p| p.seq <= *base_seq);
+        let end = self.patches.partition_point(|p| p.seq <= seq);
+        for patch in &self.patches[start..end] {
+            patch.edit.apply(&mut text);
+        }
+        let last = end.checked_sub(1).map(|i| &self.patches[i]).filter(|p| p.seq == seq);
+        Ok(Revision {
+            seq,
+            timestamp: last.map(|p| p.timestamp),
+            author: last.and_then(|p| p.author.clone()),
+            text,
+        })
+    }
+
+    /// The text right after change `from`, and the changes leading from there to `to`.
+    pub fn replay(&self, from: u64, to: u64) -> Result<(String, Vec<ReplayStep>), String> {
+        let base = self.text_at(from)?.text;
+        let to = to.min(self.seq);
+        let start = self.patches.partition_point(|p| p.seq <= from);
+        let end = self.patches.partition_point(|p| p.seq <= to);
+        let steps = self.patches[start..end]
+            .iter()
+            .map(|p| ReplayStep {
+                seq: p.seq,
+                timestamp: p.timestamp,
+                author: p.author.clone(),
+                start: p.edit.start,
+                delete_len: p.edit.deleted.len(),
+                insert: p.edit.inserted.clone(),
+            })
+            .collect();
+        Ok((base, steps))
+    }
+}
+
+fn history_path(app: &AppHandle, file: &Path) -> Result<PathBuf, String> {
+    let dir = storage::data_dir(app)?.join("history");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir.join(format!("{}.json", &hash(&file.to_string_lossy())[..16])))
+}
+
+/// History saved for `file`, if it still matches the file's current `text`.
+pub fn load(app: &AppHandle, file: &Path, text: &str) -> UndoTree {
+    let tree = history_path(app, file)
+        .ok()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|raw| serde_json::from_slice::<UndoTree>(&raw).ok());
+    match tree {
+        Some(tree) if tree.text_hash == hash(text) => tree,
+        _ => UndoTree::default(),
+    }
+}
+
+/// Persist `tree` for `file`, where `text` is the document text at the tree's current state.
+pub fn save(app: &AppHandle, file: &Path, tree: &mut UndoTree, text: &str) -> Result<(), String> {
+    tree.text_hash = hash(text);
+    let path = history_path(app, file)?;
+    let raw = serde_json::to_vec(tree).map_err(|e| format!("Failed to serialize history: {}", e))?;
+    fs::write(&path, raw).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn step(
+    store: &DocumentStore,
+    id: &str,
+    f: impl FnOnce(&mut UndoTree, &mut String) -> Option<Option<String>>,
+) -> Result<HistoryState, String> {
+    store.with(id, |doc| {
+        doc.ensure_writable()?;
+        let before = doc.text.clone();
+        let mut text = std::mem::take(&mut doc.text);
+        let author = f(&mut doc.history, &mut text);
+        doc.timeline.record(&before, &text, author.clone().flatten().as_deref());
+        doc.text = text;
+        Ok(HistoryState {
+            text: doc.text.clone(),
+            can_undo: doc.history.can_undo(),
+            can_redo: doc.history.can_redo(),
+            author: author.flatten(),
+        })
+    })?
+}
+
+#[tauri::command]
+pub fn undo_document(store: State<'_, DocumentStore>, id: String) -> Result<HistoryState, String> {
+    step(&store, &id, |tree, text| tree.undo(text))
+}
+
+/// Redo the last undone step, or the step leading to `branch` (see `get_document_history`).
+#[tauri::command]
+pub fn redo_document(store: State<'_, DocumentStore>, id: String, branch: Option<u64>) -> Result<HistoryState, String> {
+    step(&store, &id, |tree, text| tree.redo(text, branch))
+}
+
+#[tauri::command]
+pub fn get_document_history(store: State<'_, DocumentStore>, id: String) -> Result<Vec<HistoryEntry>, String> {
+    store.with(&id, |doc| doc.history.entries())
+}
+
+#[tauri::command]
+pub fn get_document_timeline(store: State<'_, DocumentStore>, id: String) -> Result<Vec<TimelineEntry>, String> {
+    store.with(&id, |doc| doc.timeline.entries())
+}
+
+/// Reconstruct a document as it was after change `seq`; `0` is the text it was opened with.
+#[tauri::command]
+pub fn get_document_at(store: State<'_, DocumentStore>, id: String, seq: u64) -> Result<Revision, String> {
+    store.with(&id, |doc| doc.timeline.text_at(seq))?
+}
+
+// The backend has no CRDT merge and does not re-anchor annotations (strokes keep the line and
+// column they were drawn at), so the properties cover what shared documents do rely on: diffing,
+// and a joiner catching up from a snapshot through the timeline.
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use proptest::sample::Index;
+
+    use super::*;
+
+    /// Text heavy in multi-byte characters, so edits often start or end next to one.
+    const TEXT: &str = "[aé漢😀\n ]{0,24}";
+
+    /// Replace `delete` characters after character `at` with `insert`.
+    fn edit_chars(text: &str, at: Index, delete: usize, insert: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let start = at.index(chars.len() + 1);
+        let end = (start + delete).min(chars.len());
+        let mut edited: String = chars[..start].iter().collect();
+        edited.push_str(insert);
+        edited.extend(&chars[end..]);
+        edited
+    }
+
+    proptest! {
+        #[test]
+        fn diff_then_apply_gives_new(old in any::<String>(), new in any::<String>()) {
+            let mut text = old.clone();
+            match diff(&old, &new) {
+                Some(edit) => edit.apply(&mut text),
+                None => prop_assert_eq!(&old, &new),
+            }
+            prop_assert_eq!(text, new);
+        }
+
+        #[test]
+        fn diff_then_apply_gives_new_multibyte(old in TEXT, new in TEXT) {
+            let mut text = old.clone();
+            if let Some(edit) = diff(&old, &new) {
+                edit.apply(&mut text);
+            }
+            prop_assert_eq!(text, new);
+        }
+
+        #[test]
+        fn revert_undoes_apply(old in TEXT, at in any::<Index>(), delete in 0usize..6, insert in TEXT) {
+            let new = edit_chars(&old, at, delete, &insert);
+            if let Some(edit) = diff(&old, &new) {
+                let mut text = old.clone();
+                edit.apply(&mut text);
+                prop_assert_eq!(&text, &new);
+                edit.revert(&mut text);
+                prop_assert_eq!(text, old);
+            }
+        }
+    }
+
+    proptest! {
+        // Each case records enough patches to cross at least one snapshot boundary
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn text_at_replays_patches_in_order(
+            initial in TEXT,
+            edits in prop::collection::vec((any::<Index>(), 0usize..4, "[aé漢😀\n ]{0,3}"), 150..320),
+        ) {
+            let mut timeline = Timeline::new(&initial);
+            let mut texts = vec![initial.clone()];
+            let mut text = initial;
+            for (at, delete, insert) in edits {
+                let new = edit_chars(&text, at, delete, &insert);
+                timeline.record(&text, &new, None);
+                if new != text {
+                    texts.push(new.clone());
+                }
+                text = new;
+            }
+            prop_assert_eq!(timeline.seq(), texts.len() as u64 - 1);
+            for (seq, expected) in texts.iter().enumerate() {
+                prop_assert_eq!(&timeline.text_at(seq as u64).unwrap().text, expected);
+            }
+            prop_assert!(timeline.text_at(timeline.seq() + 1).is_err());
+        }
+
+        #[test]
+        fn tail_after_any_revision_rebuilds_the_text(
+            initial in TEXT,
+            edits in prop::collection::vec((any::<Index>(), 0usize..4, "[aé漢😀\n ]{0,3}"), 1..120),
+            from in any::<Index>(),
+        ) {
+            let mut timeline = Timeline::new(&initial);
+            let mut text = initial;
+            for (at, delete, insert) in edits {
+                let new = edit_chars(&text, at, delete, &insert);
+                timeline.record(&text, &new, None);
+                text = new;
+            }
+            let seq = from.index(timeline.seq() as usize + 1) as u64;
+            let base = timeline.text_at(seq).unwrap().text;
+
+            // As a viewer applies them, in UTF-16 offsets
+            let mut viewer = base.clone();
+            let mut at_seq = seq;
+            for patch in timeline.patches_after(seq).unwrap() {
+                prop_assert_eq!(patch.seq, at_seq + 1);
+                at_seq = patch.seq;
+                let offsets = OffsetMap::new(&viewer);
+                let start = offsets.to_byte(patch.start);
+                let end = offsets.to_byte(patch.start + patch.delete_count);
+                viewer.replace_range(start..end, &patch.insert);
+            }
+            prop_assert_eq!(at_seq, timeline.seq());
+            prop_assert_eq!(&viewer, &text);
+
+            // As a replay applies them, in bytes
+            let mut replayed = base;
+            for step in timeline.steps_after(seq).unwrap() {
+                step.apply(&mut replayed);
+            }
+            prop_assert_eq!(replayed, text);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! System-wide shortcuts for the window's privacy controls: capture protection, taskbar
+//! visibility, hiding the window, stepping its opacity, click-through and panic hide.
+//!
+//! The shortcuts are registered with the OS, so they fire while another application has focus.
+//! Each press is applied to the main window and reported with a `hotkey-triggered` event
+//! carrying the resulting state, so the frontend can follow changes it did not make. Toggles go
+//! through the same paths as the settings screen and are recorded in transparency reports. Those
+//! whose result can't be seen tap the trackpad where `haptics` can.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::haptics::{self, Haptic};
+use crate::transparency::{self, PrivacyFeature};
+use crate::{panic_hide, remote_actions, storage, tray, CaptureProtection};
+
+const CONFIG_FILE: &str = "hotkeys.json";
+const MAIN_WINDOW: &str = "main";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HotkeyAction {
+    ToggleCaptureProtection,
+    ToggleTaskbar,
+    /// Hide the window, or bring it back if hidden.
+    HideWindow,
+    OpacityUp,
+    OpacityDown,
+    ToggleClickThrough,
+    /// Panic hide, or restore if already hidden.
+    PanicHide,
+    /// Allow the remote action waiting for approval.
+    ApproveRemoteAction,
+    /// Refuse the remote action waiting for approval.
+    DenyRemoteAction,
+}
+
+impl HotkeyAction {
+    /// Taps the trackpad on success, as the result may not be visible.
+    fn haptic(self) -> Option<Haptic> {
+        match self {
+            Self::ToggleCaptureProtection | Self::ToggleTaskbar | Self::ToggleClickThrough => Some(Haptic::Toggled),
+            Self::PanicHide | Self::ApproveRemoteAction | Self::DenyRemoteAction => Some(Haptic::Confirmed),
+            Self::HideWindow | Self::OpacityUp | Self::OpacityDown => None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyBinding {
+    pub action: HotkeyAction,
+    /// Accelerator such as `CommandOrControl+Alt+H`.
+    pub shortcut: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HotkeyConfig {
+    pub enabled: bool,
+    pub bindings: Vec<HotkeyBinding>,
+    /// How much one opacity step changes the window's opacity.
+    pub opacity_step: f64,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        let binding = |action, shortcut: &str| HotkeyBinding {
+            action,
+            shortcut: shortcut.to_string(),
+        };
+        Self {
+            enabled: false,
+            bindings: vec![
+                binding(HotkeyAction::ToggleCaptureProtection, "CommandOrControl+Alt+Shift+C"),
+                binding(HotkeyAction::ToggleTaskbar, "CommandOrControl+Alt+Shift+T"),
+                binding(HotkeyAction::HideWindow, "CommandOrControl+Alt+Shift+H"),
+                binding(HotkeyAction::OpacityUp, "CommandOrControl+Alt+Shift+Up"),
+                binding(HotkeyAction::OpacityDown, "CommandOrControl+Alt+Shift+Down"),
+                binding(HotkeyAction::ToggleClickThrough, "CommandOrControl+Alt+Shift+X"),
+                binding(HotkeyAction::PanicHide, "CommandOrControl+Alt+Shift+P"),
+                binding(HotkeyAction::ApproveRemoteAction, "CommandOrControl+Alt+Shift+Y"),
+                binding(HotkeyAction::DenyRemoteAction, "CommandOrControl+Alt+Shift+N"),
+            ],
+            opacity_step: 0.1,
+        }
+    }
+}
+
+/// Sent as `hotkey-triggered` after every press.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyTriggered {
+    pub action: HotkeyAction,
+    pub capture_protection: CaptureProtection,
+    pub taskbar_visible: bool,
+    pub window_visible: bool,
+    pub opacity: f64,
+    pub click_through: bool,
+    /// Why the action could not be applied, if it failed.
+    pub error: Option<String>,
+}
+
+pub struct Hotkeys {
+    config: Mutex<HotkeyConfig>,
+    registered: Mutex<Vec<Shortcut>>,
+}
+
+impl Hotkeys {
+    fn config(&self) -> HotkeyConfig {
+        self.config.lock().unwrap().clone()
+    }
+}
+
+fn parse(config: &HotkeyConfig) -> Result<Vec<(Shortcut, &HotkeyBinding)>, String> {
+    if !(config.opacity_step > 0.0 && config.opacity_step <= 1.0) {
+        return Err("Opacity step must be between 0 and 1".to_string());
+    }
+    let mut seen = HashSet::new();
+    let mut parsed = Vec::new();
+    for binding in &config.bindings {
+        let shortcut: Shortcut =
+            binding.shortcut.parse().map_err(|e| format!("Invalid hotkey {}: {}", binding.shortcut, e))?;
+        if !seen.insert(shortcut) {
+            return Err(format!("{} is bound to more than one action", binding.shortcut));
+        }
+        parsed.push((shortcut, binding));
+    }
+    Ok(parsed)
+}
+
+fn unregister_all(app: &AppHandle) {
+    for shortcut in app.state::<Hotkeys>().registered.lock().unwrap().drain(..) {
+        if let Err(e) = app.global_shortcut().unregister(shortcut) {
+            log::warn!("Failed to unregister hotkey: {}", e);
+        }
+    }
+}
+
+/// Bind the shortcuts of `config`, or release them all when it is disabled.
+fn apply(app: &AppHandle, config: &HotkeyConfig) -> Result<(), String> {
+    let parsed = parse(config)?;
+    unregister_all(app);
+    if !config.enabled {
+        return Ok(());
+    }
+    for (shortcut, binding) in parsed {
+        let action = binding.action;
+        let registered = app.global_shortcut().on_shortcut(shortcut, move |app, _, event| {
+            if let ShortcutState::Pressed = event.state() {
+                trigger(app, action);
+            }
+        });
+        if let Err(e) = registered {
+            // Leave none bound rather than some, so the settings match what works.
+            unregister_all(app);
+            return Err(format!("Failed to register {}: {}", binding.shortcut, e));
+        }
+        app.state::<Hotkeys>().registered.lock().unwrap().push(shortcut);
+    }
+    Ok(())
+}
+
+fn perform(app: &AppHandle, window: &WebviewWindow, action: HotkeyAction) -> Result<(), String> {
+    match action {
+        HotkeyAction::ToggleCaptureProtection => {
+            let protected = !matches!(crate::read_capture_protection(window)?, CaptureProtection::Off);
+            crate::protect_from_capture(window, !protected).map(|_| ())
+        }
+        HotkeyAction::ToggleTaskbar => {
+            let hidden = transparency::is_active(app, PrivacyFeature::TaskbarHidden);
+            crate::apply_taskbar_visibility(window, hidden)
+        }
+        HotkeyAction::HideWindow => {
+            if window.is_visible().map_err(|e| e.to_string())? {
+                window.hide().map_err(|e| e.to_string())
+            } else {
+                window.show().map_err(|e| e.to_string())?;
+                window.set_focus().map_err(|e| e.to_string())
+            }
+        }
+        HotkeyAction::OpacityUp | HotkeyAction::OpacityDown => {
+            let step = app.state::<Hotkeys>().config().opacity_step;
+            let opacity = crate::read_window_opacity(window)?;
+            let next = match action {
+                HotkeyAction::OpacityUp => opacity + step,
+                _ => opacity - step,
+            }
+            .clamp(crate::MIN_WINDOW_OPACITY, 1.0);
+            crate::apply_window_opacity(window, next)
+        }
+        HotkeyAction::ToggleClickThrough => {
+            let enabled = crate::read_click_through(window)?;
+            crate::apply_click_through(window, !enabled)
+        }
+        HotkeyAction::PanicHide => {
+            let outcome = if panic_hide::is_hidden(app) {
+                panic_hide::restore(app)
+            } else {
+                panic_hide::hide(app)
+            };
+            if outcome.errors.is_empty() {
+                Ok(())
+            } else {
+                Err(outcome.errors.join("; "))
+            }
+        }
+        HotkeyAction::ApproveRemoteAction => remote_actions::answer(app, None, true),
+        HotkeyAction::DenyRemoteAction => remote_actions::answer(app, None, false),
+    }
+}
+
+fn trigger(app: &AppHandle, action: HotkeyAction) {
+    let Some(window) = app.get_webview_window(MAIN_WINDOW) else {
+        return;
+    };
+    // Window attributes belong to the main thread on macOS.
+    let (app, target) = (app.clone(), window.clone());
+    let ran = window.run_on_main_thread(move || {
+        let error = perform(&app, &target, action).err();
+        match (&error, action.haptic()) {
+            (Some(e), _) => log::warn!("Hotkey action failed: {}", e),
+            (None, Some(haptic)) => haptics::tap(&app, haptic),
+            (None, None) => {}
+        }
+        tray::refresh(&app);
+        let triggered = HotkeyTriggered {
+            action,
+            capture_protection: crate::read_capture_protection(&target).unwrap_or(CaptureProtection::Off),
+            taskbar_visible: !transparency::is_active(&app, PrivacyFeature::TaskbarHidden),
+            window_visible: target.is_visible().unwrap_or(true),
+            opacity: crate::read_window_opacity(&target).unwrap_or(1.0),
+            click_through: crate::read_click_through(&target).unwrap_or(false),
+            error,
+        };
+        if let Err(e) = app.emit("hotkey-triggered", triggered) {
+            log::warn!("Failed to emit hotkey-triggered: {}", e);
+        }
+    });
+    if let Err(e) = ran {
+        log::warn!("Failed to run hotkey action: {}", e);
+    }
+}
+
+/// Manage the hotkey state and bind the saved shortcuts. Called once from setup, after the
+/// global shortcut plugin is registered.
+pub fn install(app: &AppHandle) {
+    let config: HotkeyConfig = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+        log::warn!("Using default hotkey config: {}", e);
+        HotkeyConfig::default()
+    });
+    app.manage(Hotkeys {
+        config: Mutex::new(config.clone()),
+        registered: Mutex::new(Vec::new()),
+    });
+    if let Err(e) = apply(app, &config) {
+        log::warn!("Hotkeys unavailable: {}", e);
+    }
+}
+
+#[tauri::command]
+pub fn get_hotkey_config(state: State<'_, Hotkeys>) -> HotkeyConfig {
+    state.config()
+}
+
+/// Validate and bind `config`, then save it. Nothing is saved if a shortcut can't be bound.
+#[tauri::command]
+pub fn set_hotkey_config(app: AppHandle, config: HotkeyConfig) -> Result<(), String> {
+    if let Err(e) = apply(&app, &config) {
+        // Put back the shortcuts that were working.
+        let previous = app.state::<Hotkeys>().config();
+        if let Err(e) = apply(&app, &previous) {
+            log::warn!("Failed to restore hotkeys: {}", e);
+        }
+        return Err(e);
+    }
+    storage::save_json(&app, CONFIG_FILE, &config)?;
+    *app.state::<Hotkeys>().config.lock().unwrap() = config;
+    Ok(())
+}
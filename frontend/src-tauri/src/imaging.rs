@@ -0,0 +1,61 @@
+//! Reading and writing PNGs as plain pixel buffers for the image features.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Decoded images beyond this are refused rather than held in memory.
+const MAX_IMAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// An 8-bit RGBA image.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub fn decode_png(data: impl Read) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new_with_limits(data, png::Limits { bytes: MAX_IMAGE_BYTES });
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| format!("Not a readable PNG: {}", e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf).map_err(|e| format!("Not a readable PNG: {}", e))?;
+    let buf = &buf[..frame.buffer_size()];
+
+    let pixels = match frame.color_type {
+        png::ColorType::Rgba => buf.to_vec(),
+        png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => return Err("Unsupported PNG colour type".to_string()),
+    };
+    Ok(Image {
+        width: frame.width,
+        height: frame.height,
+        pixels,
+    })
+}
+
+pub fn read_png(path: &Path) -> Result<Image, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    decode_png(file)
+}
+
+/// Encode 8-bit pixels of the given colour type (`Rgba` or `Grayscale`).
+pub fn encode_png(width: u32, height: u32, color: png::ColorType, pixels: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+pub fn write_png(path: &Path, image: &Image) -> Result<(), String> {
+    let data = encode_png(image.width, image.height, png::ColorType::Rgba, &image.pixels)?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to finalize {}: {}", path.display(), e))
+}
@@ -0,0 +1,486 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, State};
+
+use crate::snippets::{Snippet, SnippetInput, SnippetLibrary};
+
+#[derive(Clone, Copy)]
+enum ImportFormat {
+    VsCode,
+    Sublime,
+    TextExpanderPlist,
+    TextExpanderCsv,
+}
+
+impl ImportFormat {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "vscode" => Ok(Self::VsCode),
+            "sublime" => Ok(Self::Sublime),
+            "textexpander" => Ok(Self::TextExpanderPlist),
+            "textexpander-csv" => Ok(Self::TextExpanderCsv),
+            other => Err(format!("Unknown snippet format: {}", other)),
+        }
+    }
+
+    fn detect(path: &Path) -> Result<Self, String> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "code-snippets" | "json" => Ok(Self::VsCode),
+            "sublime-snippet" => Ok(Self::Sublime),
+            "textexpander" | "plist" => Ok(Self::TextExpanderPlist),
+            "csv" => Ok(Self::TextExpanderCsv),
+            _ => Err(format!("Cannot detect snippet format of {}", path.display())),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub imported: usize,
+    /// One message per entry that could not be mapped or was already in the library; the rest of
+    /// the file is still imported.
+    pub skipped: Vec<String>,
+}
+
+pub(crate) struct Parsed {
+    snippets: Vec<SnippetInput>,
+    skipped: Vec<String>,
+}
+
+fn snippet(title: String, prefix: Option<String>, body: String, language: Option<String>, source: &str) -> SnippetInput {
+    SnippetInput {
+        id: None,
+        title,
+        prefix,
+        body,
+        language,
+        description: None,
+        tags: Vec::new(),
+        source: Some(source.to_string()),
+    }
+}
+
+/// Strip `//` and `/* */` comments plus trailing commas, which VS Code accepts in snippet files.
+/// Comments go first, so a comma followed by one before the closing bracket is still trailing.
+fn strip_jsonc(input: &str) -> String {
+    strip_trailing_commas(&strip_comments(input))
+}
+
+fn strip_comments(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for next in chars.by_ref() {
+                    if prev == '*' && next == '/' {
+                        break;
+                    }
+                    prev = next;
+                }
+                // `1/* */2` is two tokens, not `12`
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn strip_trailing_commas(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    out.push(next);
+                }
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            ',' => {
+                let next = chars.clone().find(|c| !c.is_whitespace());
+                if next != Some('}') && next != Some(']') {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn string_or_lines(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) => Some(
+            items
+                .iter()
+                .map(|item| item.as_str().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_vscode(raw: &str, source: &str, file_language: Option<String>) -> Result<Parsed, String> {
+    let root: Value = serde_json::from_str(&strip_jsonc(raw)).map_err(|e| format!("Invalid snippet JSON: {}", e))?;
+    let entries = root.as_object().ok_or("Snippet file must contain a JSON object")?;
+    let mut parsed = Parsed { snippets: Vec::new(), skipped: Vec::new() };
+    for (name, entry) in entries {
+        let Some(body) = string_or_lines(entry.get("body")) else {
+            parsed.skipped.push(format!("{}: missing body", name));
+            continue;
+        };
+        // Only the first prefix is kept; aliases are recorded as tags so they stay searchable.
+        let prefixes = match entry.get("prefix") {
+            Some(Value::String(p)) => vec![p.clone()],
+            Some(Value::Array(items)) => items.iter().filter_map(|p| p.as_str().map(str::to_string)).collect(),
+            _ => Vec::new(),
+        };
+        let language = entry
+            .get("scope")
+            .and_then(Value::as_str)
+            .and_then(|scope| scope.split(',').next())
+            .map(|scope| scope.trim().to_string())
+            .filter(|scope| !scope.is_empty())
+            .or_else(|| file_language.clone());
+        let mut input = snippet(name.clone(), prefixes.first().cloned(), body, language, source);
+        input.description = entry.get("description").and_then(string_or_lines);
+        input.tags = prefixes.into_iter().skip(1).collect();
+        parsed.snippets.push(input);
+    }
+    Ok(parsed)
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Extract the text of the first `<tag>` element, unwrapping CDATA sections.
+fn xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    let inner = xml[start..end].trim();
+    match inner.strip_prefix("<![CDATA[").and_then(|s| s.strip_suffix("]]>")) {
+        Some(cdata) => Some(cdata.to_string()),
+        None => Some(xml_unescape(inner)),
+    }
+}
+
+pub(crate) fn parse_sublime(raw: &str, source: &str, file_stem: &str) -> Result<Parsed, String> {
+    if !raw.contains("<snippet") {
+        return Err("Not a Sublime snippet: missing <snippet> element".to_string());
+    }
+    let body = xml_tag(raw, "content").ok_or("Sublime snippet has no <content>")?;
+    // `source.python` / `text.html.basic` -> `python` / `html`
+    let language = xml_tag(raw, "scope").and_then(|scope| {
+        scope
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            .and_then(|s| s.split('.').nth(1))
+            .map(str::to_string)
+    });
+    let title = xml_tag(raw, "description").unwrap_or_else(|| file_stem.to_string());
+    let input = snippet(title, xml_tag(raw, "tabTrigger"), body, language, source);
+    Ok(Parsed { snippets: vec![input], skipped: Vec::new() })
+}
+
+/// Map TextExpander macros onto TextMate placeholders; unknown macros are left untouched.
+fn convert_textexpander(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut tab_stop = 1;
+    let mut rest = text;
+    while let Some(pos) = rest.find('%') {
+        out.push_str(&rest[..pos].replace('$', "\\$"));
+        rest = &rest[pos..];
+        let replaced = if let Some(after) = rest.strip_prefix("%|") {
+            out.push_str("$0");
+            Some(after)
+        } else if let Some(after) = rest.strip_prefix("%clipboard") {
+            out.push_str("$CLIPBOARD");
+            Some(after)
+        } else if let Some(after) = rest.strip_prefix("%filltext:") {
+            // %filltext:name=field:default=value%
+            after.find('%').map(|end| {
+                let default = after[..end]
+                    .split(':')
+                    .find_map(|part| part.strip_prefix("default="))
+                    .unwrap_or_default();
+                out.push_str(&format!("${{{}:{}}}", tab_stop, default));
+                tab_stop += 1;
+                &after[end + 1..]
+            })
+        } else {
+            [("%Y", "$CURRENT_YEAR"), ("%m", "$CURRENT_MONTH"), ("%d", "$CURRENT_DATE"), ("%H", "$CURRENT_HOUR"), ("%M", "$CURRENT_MINUTE"), ("%S", "$CURRENT_SECOND")]
+                .iter()
+                .find_map(|(macro_, variable)| {
+                    rest.strip_prefix(macro_).map(|after| {
+                        out.push_str(variable);
+                        after
+                    })
+                })
+        };
+        match replaced {
+            Some(after) => rest = after,
+            None => {
+                out.push('%');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(&rest.replace('$', "\\$"));
+    out
+}
+
+pub(crate) fn parse_textexpander_plist(raw: &[u8], source: &str) -> Result<Parsed, String> {
+    let root = plist::Value::from_reader(std::io::Cursor::new(raw)).map_err(|e| format!("Invalid TextExpander export: {}", e))?;
+    let items = root
+        .as_dictionary()
+        .and_then(|dict| dict.get("snippetsTE2").or_else(|| dict.get("snippets")))
+        .and_then(plist::Value::as_array)
+        .ok_or("TextExpander export has no snippets")?;
+    let mut parsed = Parsed { snippets: Vec::new(), skipped: Vec::new() };
+    for (index, item) in items.iter().enumerate() {
+        let Some(dict) = item.as_dictionary() else {
+            parsed.skipped.push(format!("entry {}: not a dictionary", index));
+            continue;
+        };
+        let field = |key: &str| dict.get(key).and_then(plist::Value::as_string).map(str::to_string);
+        let Some(text) = field("plainText").or_else(|| field("snippetText")) else {
+            parsed.skipped.push(format!("entry {}: no plain-text content", index));
+            continue;
+        };
+        let abbreviation = field("abbreviation").filter(|a| !a.is_empty());
+        let title = field("label")
+            .filter(|l| !l.is_empty())
+            .or_else(|| abbreviation.clone())
+            .unwrap_or_else(|| format!("TextExpander snippet {}", index + 1));
+        parsed.snippets.push(snippet(title, abbreviation, convert_textexpander(&text), None, source));
+    }
+    Ok(parsed)
+}
+
+/// Minimal RFC 4180 reader: quoted fields, doubled quotes and embedded newlines.
+fn parse_csv(raw: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+pub(crate) fn parse_textexpander_csv(raw: &str, source: &str) -> Result<Parsed, String> {
+    let mut parsed = Parsed { snippets: Vec::new(), skipped: Vec::new() };
+    for (index, row) in parse_csv(raw).into_iter().enumerate() {
+        if row.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        // TextExpander CSV columns: abbreviation, content, label
+        if row.len() < 2 {
+            parsed.skipped.push(format!("line {}: expected at least 2 columns", index + 1));
+            continue;
+        }
+        let abbreviation = Some(row[0].clone()).filter(|a| !a.is_empty());
+        let title = row
+            .get(2)
+            .filter(|l| !l.is_empty())
+            .cloned()
+            .or_else(|| abbreviation.clone())
+            .unwrap_or_else(|| format!("TextExpander snippet {}", index + 1));
+        parsed.snippets.push(snippet(title, abbreviation, convert_textexpander(&row[1]), None, source));
+    }
+    Ok(parsed)
+}
+
+/// Leave out what an earlier import already brought in. A snippet from the same file with the same
+/// title replaces the one imported before; one with the title and body of another snippet is
+/// skipped as a duplicate.
+fn dedupe(existing: &[Snippet], parsed: &mut Parsed) {
+    let mut kept: Vec<SnippetInput> = Vec::with_capacity(parsed.snippets.len());
+    for mut input in parsed.snippets.drain(..) {
+        let previous = existing
+            .iter()
+            .find(|snippet| snippet.source == input.source && snippet.title == input.title);
+        if let Some(previous) = previous {
+            input.id = Some(previous.id.clone());
+        } else if existing.iter().any(|snippet| snippet.title == input.title && snippet.body == input.body)
+            || kept.iter().any(|other| other.title == input.title && other.body == input.body)
+        {
+            parsed.skipped.push(format!("{}: already in the library", input.title));
+            continue;
+        }
+        kept.push(input);
+    }
+    parsed.snippets = kept;
+}
+
+#[tauri::command]
+pub fn import_snippets(
+    app: AppHandle,
+    library: State<'_, SnippetLibrary>,
+    path: String,
+    format: Option<String>,
+) -> Result<ImportReport, String> {
+    let path = Path::new(&path);
+    let format = match format {
+        Some(name) => ImportFormat::parse(&name)?,
+        None => ImportFormat::detect(path)?,
+    };
+    let raw = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let file_stem = path.file_stem().and_then(|n| n.to_str()).unwrap_or_default();
+    let text = || String::from_utf8_lossy(&raw).trim_start_matches('\u{feff}').to_string();
+
+    let mut parsed = match format {
+        ImportFormat::VsCode => {
+            // User snippet files are named after their language (`python.json`); `.code-snippets` are global.
+            let file_language = path
+                .extension()
+                .filter(|ext| *ext == "json")
+                .map(|_| file_stem.to_string());
+            parse_vscode(&text(), &format!("vscode:{}", file_name), file_language)?
+        }
+        ImportFormat::Sublime => parse_sublime(&text(), &format!("sublime:{}", file_name), file_stem)?,
+        ImportFormat::TextExpanderPlist => parse_textexpander_plist(&raw, &format!("textexpander:{}", file_name))?,
+        ImportFormat::TextExpanderCsv => parse_textexpander_csv(&text(), &format!("textexpander:{}", file_name))?,
+    };
+
+    dedupe(&library.all(&app)?, &mut parsed);
+    let imported = library.upsert_many(&app, parsed.snippets)?.len();
+    Ok(ImportReport { imported, skipped: parsed.skipped })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Value {
+        serde_json::from_str(&strip_jsonc(raw)).unwrap()
+    }
+
+    fn saved(id: &str, title: &str, body: &str, source: &str) -> Snippet {
+        Snippet {
+            id: id.to_string(),
+            title: title.to_string(),
+            prefix: None,
+            body: body.to_string(),
+            language: None,
+            description: None,
+            tags: Vec::new(),
+            source: Some(source.to_string()),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn trailing_comma_before_a_comment_is_stripped() {
+        assert_eq!(parse("{\"a\":1, // c\n}"), serde_json::json!({ "a": 1 }));
+        assert_eq!(parse("[1, /* c */ ]"), serde_json::json!([1]));
+        assert_eq!(parse("{\"a\": [1, 2,\n// one\n// two\n],\n}"), serde_json::json!({ "a": [1, 2] }));
+    }
+
+    #[test]
+    fn strings_are_left_alone() {
+        assert_eq!(
+            parse(r#"{"a": "// not a comment,}", "b": "/* nor */ this", "c": "\",]"}"#),
+            serde_json::json!({ "a": "// not a comment,}", "b": "/* nor */ this", "c": "\",]" })
+        );
+    }
+
+    #[test]
+    fn reimports_update_and_duplicates_are_skipped() {
+        let existing = [
+            saved("1", "Log", "console.log($1)", "vscode:js.json"),
+            saved("2", "Print", "print($1)", "vscode:py.json"),
+        ];
+        let mut parsed = Parsed {
+            snippets: vec![
+                snippet("Log".to_string(), None, "console.log($1);".to_string(), None, "vscode:js.json"),
+                snippet("Print".to_string(), None, "print($1)".to_string(), None, "vscode:other.json"),
+                snippet("New".to_string(), None, "new".to_string(), None, "vscode:js.json"),
+                snippet("New".to_string(), None, "new".to_string(), None, "vscode:js.json"),
+            ],
+            skipped: Vec::new(),
+        };
+        dedupe(&existing, &mut parsed);
+        let ids: Vec<_> = parsed.snippets.iter().map(|s| (s.title.as_str(), s.id.as_deref())).collect();
+        assert_eq!(ids, [("Log", Some("1")), ("New", None)]);
+        assert_eq!(parsed.skipped.len(), 2);
+    }
+}
@@ -0,0 +1,201 @@
+//! Background symbol index over an imported project directory.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+use crate::events::{self, Channel};
+use crate::language;
+use crate::syntax::{self, CallSite, Symbol};
+
+/// Files larger than this are skipped; they are almost always generated or minified.
+const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+const IGNORED_DIRS: &[&str] = &[".git", "node_modules", "target", "dist", "build", "vendor", "__pycache__", ".venv"];
+
+pub struct IndexedFile {
+    pub symbols: Vec<Symbol>,
+    pub calls: Vec<CallSite>,
+}
+
+#[derive(Default)]
+pub struct ProjectIndex {
+    pub root: RwLock<Option<PathBuf>>,
+    pub files: RwLock<HashMap<PathBuf, IndexedFile>>,
+    /// Bumped on every import so a superseded background run stops publishing results.
+    generation: AtomicU64,
+}
+
+/// Sent after every file; only the latest reaches the webview.
+const INDEX_PROGRESS: Channel = Channel::latest_wins("index-progress");
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexProgress {
+    indexed: usize,
+    total: usize,
+    done: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolLocation {
+    pub file: String,
+    pub symbol: Symbol,
+}
+
+fn collect_sources(dir: &Path, out: &mut Vec<(PathBuf, &'static str)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !IGNORED_DIRS.contains(&&*name) {
+                collect_sources(&path, out);
+            }
+        } else if file_type.is_file() {
+            let language = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .and_then(language::language_for_extension)
+                .filter(|l| syntax::grammar(l).is_some());
+            let small = entry.metadata().map(|m| m.len() <= MAX_FILE_BYTES).unwrap_or(false);
+            if let (Some(language), true) = (language, small) {
+                out.push((path, language));
+            }
+        }
+    }
+}
+
+pub fn index_file(path: &Path, language: &str) -> Result<IndexedFile, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let tree = syntax::parse(language, &text)?;
+    Ok(IndexedFile {
+        symbols: syntax::symbols(language, &tree, &text),
+        calls: syntax::calls(language, &tree, &text),
+    })
+}
+
+impl ProjectIndex {
+    pub fn resolve(&self, file: &str) -> PathBuf {
+        let path = PathBuf::from(file);
+        match self.root.read().unwrap().as_ref() {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path,
+        }
+    }
+}
+
+/// Import a project directory and index it on a background thread.
+///
+/// Emits `index-progress` while running; the final event has `done: true`.
+#[tauri::command]
+pub fn import_project(app: AppHandle, index: State<'_, Arc<ProjectIndex>>, root: String) -> Result<usize, String> {
+    let root = PathBuf::from(root);
+    if !root.is_dir() {
+        return Err(format!("{} is not a directory", root.display()));
+    }
+    let mut sources = Vec::new();
+    collect_sources(&root, &mut sources);
+    let total = sources.len();
+
+    let generation = index.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    *index.root.write().unwrap() = Some(root);
+    index.files.write().unwrap().clear();
+
+    let index = Arc::clone(&index);
+    thread::spawn(move || {
+        for (done, (path, language)) in sources.into_iter().enumerate() {
+            if index.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            match index_file(&path, language) {
+                Ok(indexed) => {
+                    index.files.write().unwrap().insert(path, indexed);
+                }
+                Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+            }
+            events::emit(&app, &INDEX_PROGRESS, IndexProgress { indexed: done + 1, total, done: false }).ok();
+        }
+        events::emit(&app, &INDEX_PROGRESS, IndexProgress { indexed: total, total, done: true }).ok();
+    });
+    Ok(total)
+}
+
+/// Symbols defined in `file` (absolute, or relative to the imported root), in document order.
+#[tauri::command]
+pub fn list_symbols(index: State<'_, Arc<ProjectIndex>>, file: String) -> Result<Vec<Symbol>, String> {
+    let path = index.resolve(&file);
+    if let Some(indexed) = index.files.read().unwrap().get(&path) {
+        return Ok(indexed.symbols.clone());
+    }
+    // Not part of the import (or not indexed yet): parse it on the spot.
+    let language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(language::language_for_extension)
+        .ok_or_else(|| format!("Unsupported file type: {}", path.display()))?;
+    Ok(index_file(&path, language)?.symbols)
+}
+
+/// Split `Type::method` / `obj.method` into an optional qualifier and the final name.
+pub fn split_qualified(symbol: &str) -> (Option<&str>, &str) {
+    match symbol.rfind([':', '.']) {
+        Some(pos) => (Some(symbol[..pos].trim_end_matches(':')), &symbol[pos + 1..]),
+        None => (None, symbol),
+    }
+}
+
+impl ProjectIndex {
+    /// Definitions named `symbol` across the imported files. Results from `from` come first,
+    /// then files in the same directory, then everything else; a matching qualifier ranks higher.
+    pub fn find_definitions(&self, symbol: &str, from: Option<&Path>) -> Vec<SymbolLocation> {
+        let (qualifier, name) = split_qualified(symbol);
+        let files = self.files.read().unwrap();
+        let mut found: Vec<(u8, SymbolLocation)> = Vec::new();
+        for (path, indexed) in files.iter() {
+            for sym in indexed.symbols.iter().filter(|s| s.name == name) {
+                let mut rank = if from == Some(path.as_path()) {
+                    0
+                } else if from.and_then(|f| f.parent()) == path.parent() {
+                    2
+                } else {
+                    4
+                };
+                if qualifier.is_some() && sym.container.as_deref() != qualifier {
+                    rank += 1;
+                }
+                found.push((
+                    rank,
+                    SymbolLocation {
+                        file: path.to_string_lossy().into_owned(),
+                        symbol: sym.clone(),
+                    },
+                ));
+            }
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.file.cmp(&b.1.file)));
+        found.into_iter().map(|(_, location)| location).collect()
+    }
+}
+
+#[tauri::command]
+pub fn goto_definition(
+    index: State<'_, Arc<ProjectIndex>>,
+    symbol: String,
+    from_file: Option<String>,
+) -> Vec<SymbolLocation> {
+    let from = from_file.map(|f| index.resolve(&f));
+    index.find_definitions(&symbol, from.as_deref())
+}
@@ -0,0 +1,481 @@
+//! Interview mode: load a task pack, reveal tasks one at a time, run hidden tests and compile
+//! a scored report.
+//!
+//! A pack is a JSON file:
+//!
+//! ```json
+//! {
+//!   "title": "Backend screen",
+//!   "language": "python",
+//!   "tasks": [{
+//!     "id": "two-sum",
+//!     "title": "Two sum",
+//!     "prompt": "Read numbers from stdin ...",
+//!     "starterCode": "def solve(): ...",
+//!     "tests": [{ "name": "basic", "stdin": "1 2\n", "expectedStdout": "3\n", "points": 2, "hidden": true }],
+//!     "rubric": [{ "criterion": "Readable code", "points": 3 }]
+//!   }]
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::documents::{self, Document, DocumentInfo, DocumentStore};
+use crate::protocol::Message;
+use crate::runner::{self, RunOutput};
+use crate::session;
+use crate::settings::SettingsStore;
+use crate::storage;
+use crate::webhooks;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCase {
+    pub name: String,
+    #[serde(default)]
+    pub stdin: String,
+    pub expected_stdout: String,
+    #[serde(default = "one")]
+    pub points: u32,
+    /// Hidden tests are never shown to the candidate, only their pass/fail count.
+    #[serde(default = "yes")]
+    pub hidden: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RubricItem {
+    pub criterion: String,
+    pub points: u32,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Task {
+    id: String,
+    title: String,
+    prompt: String,
+    #[serde(default)]
+    starter_code: String,
+    language: Option<String>,
+    #[serde(default)]
+    tests: Vec<TestCase>,
+    #[serde(default)]
+    rubric: Vec<RubricItem>,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pack {
+    title: String,
+    language: String,
+    tasks: Vec<Task>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// What the candidate sees of a task: no hidden tests and no rubric.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevealedTask {
+    pub index: usize,
+    pub id: String,
+    pub title: String,
+    pub prompt: String,
+    pub starter_code: String,
+    pub language: String,
+    pub examples: Vec<TestCase>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskReveal {
+    pub task: RevealedTask,
+    /// The candidate's working copy, seeded with the starter code.
+    pub document: DocumentInfo,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResult {
+    pub name: String,
+    pub hidden: bool,
+    pub passed: bool,
+    pub points: u32,
+    /// Omitted for hidden tests.
+    pub output: Option<RunOutput>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestRun {
+    pub task_id: String,
+    pub passed: usize,
+    pub total: usize,
+    pub points: u32,
+    pub max_points: u32,
+    pub results: Vec<TestResult>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RubricScore {
+    pub criterion: String,
+    pub points: u32,
+    pub max_points: u32,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskReport {
+    pub id: String,
+    pub title: String,
+    pub revealed: bool,
+    pub test_points: u32,
+    pub test_max_points: u32,
+    pub tests_passed: usize,
+    pub tests_total: usize,
+    pub rubric: Vec<RubricScore>,
+    pub final_code: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterviewReport {
+    pub title: String,
+    pub candidate: Option<String>,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub tasks: Vec<TaskReport>,
+    pub points: u32,
+    pub max_points: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterviewOverview {
+    pub title: String,
+    pub task_count: usize,
+    pub revealed: usize,
+}
+
+struct Interview {
+    pack: Pack,
+    candidate: Option<String>,
+    started_at: u64,
+    revealed: usize,
+    /// Task id -> candidate document.
+    documents: HashMap<String, String>,
+    /// Task id -> most recent test run.
+    runs: HashMap<String, TestRun>,
+    /// Task id -> criterion -> (points, note).
+    scores: HashMap<String, HashMap<String, (u32, Option<String>)>>,
+    report: Option<InterviewReport>,
+}
+
+#[derive(Default)]
+pub struct InterviewState {
+    active: Mutex<Option<Interview>>,
+}
+
+impl Interview {
+    fn task(&self, task_id: &str) -> Result<(usize, &Task), String> {
+        self.pack
+            .tasks
+            .iter()
+            .enumerate()
+            .find(|(_, t)| t.id == task_id)
+            .ok_or_else(|| format!("Unknown task {}", task_id))
+    }
+
+    fn language<'a>(&'a self, task: &'a Task) -> &'a str {
+        task.language.as_deref().unwrap_or(&self.pack.language)
+    }
+}
+
+fn with_interview<R>(state: &InterviewState, f: impl FnOnce(&mut Interview) -> Result<R, String>) -> Result<R, String> {
+    let mut active = state.active.lock().unwrap();
+    let interview = active.as_mut().ok_or("No interview is loaded")?;
+    f(interview)
+}
+
+/// Outputs match when they agree line by line, ignoring trailing whitespace.
+fn outputs_match(actual: &str, expected: &str) -> bool {
+    let normalize = |s: &str| -> Vec<String> {
+        let mut lines: Vec<String> = s.lines().map(|l| l.trim_end().to_string()).collect();
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        lines
+    };
+    normalize(actual) == normalize(expected)
+}
+
+#[tauri::command]
+pub fn load_interview_pack(
+    state: State<'_, InterviewState>,
+    path: String,
+    candidate: Option<String>,
+) -> Result<InterviewOverview, String> {
+    let raw = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let pack: Pack = serde_json::from_slice(&raw).map_err(|e| format!("Invalid interview pack: {}", e))?;
+    if pack.tasks.is_empty() {
+        return Err("Interview pack has no tasks".to_string());
+    }
+    let overview = InterviewOverview {
+        title: pack.title.clone(),
+        task_count: pack.tasks.len(),
+        revealed: 0,
+    };
+    *state.active.lock().unwrap() = Some(Interview {
+        pack,
+        candidate,
+        started_at: storage::now_secs(),
+        revealed: 0,
+        documents: HashMap::new(),
+        runs: HashMap::new(),
+        scores: HashMap::new(),
+        report: None,
+    });
+    Ok(overview)
+}
+
+/// Reveal the next task: create a document with its starter code and send it to participants.
+#[tauri::command]
+pub fn reveal_next_task(
+    app: AppHandle,
+    state: State<'_, InterviewState>,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+) -> Result<TaskReveal, String> {
+    let task = with_interview(&state, |interview| {
+        let index = interview.revealed;
+        let task = interview.pack.tasks.get(index).ok_or("All tasks have been revealed")?;
+        interview.revealed += 1;
+        Ok(RevealedTask {
+            index,
+            id: task.id.clone(),
+            title: task.title.clone(),
+            prompt: task.prompt.clone(),
+            starter_code: task.starter_code.clone(),
+            language: interview.language(task).to_string(),
+            examples: task.tests.iter().filter(|t| !t.hidden).cloned().collect(),
+        })
+    })?;
+    let info = store.insert(Document::new(None, task.starter_code.clone()));
+    documents::apply_language(&app, &store, &settings, &info.id, &task.language)?;
+    with_interview(&state, |interview| {
+        interview.documents.insert(task.id.clone(), info.id.clone());
+        Ok(())
+    })?;
+    session::broadcast(&app, &Message::InterviewTask { task: task.clone() })?;
+    let document = store.with(&info.id, |doc| doc.info(&info.id))?;
+    Ok(TaskReveal { task, document })
+}
+
+/// Run every test of a revealed task against the candidate's document.
+#[tauri::command]
+pub async fn run_interview_tests(
+    app: AppHandle,
+    state: State<'_, InterviewState>,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    task_id: String,
+) -> Result<TestRun, String> {
+    let (language, tests, doc_id) = with_interview(&state, |interview| {
+        let (index, task) = interview.task(&task_id)?;
+        if index >= interview.revealed {
+            return Err("Task has not been revealed yet".to_string());
+        }
+        let doc_id = interview.documents.get(&task_id).cloned().ok_or("Task has no document")?;
+        Ok((interview.language(task).to_string(), task.tests.clone(), doc_id))
+    })?;
+    let code = store.with(&doc_id, |doc| doc.text.clone())?;
+    let command = runner::run_command(&app, &settings, &language)?;
+
+    let id = task_id.clone();
+    let run = tauri::async_runtime::spawn_blocking(move || {
+        let mut results = Vec::new();
+        for test in &tests {
+            let output = runner::run(&command, &language, &code, &test.stdin, runner::DEFAULT_TIMEOUT)?;
+            let passed = !output.timed_out
+                && output.exit_code == Some(0)
+                && outputs_match(&output.stdout, &test.expected_stdout);
+            results.push(TestResult {
+                name: test.name.clone(),
+                hidden: test.hidden,
+                passed,
+                points: if passed { test.points } else { 0 },
+                output: (!test.hidden).then_some(output),
+            });
+        }
+        Ok::<_, String>(TestRun {
+            task_id: id,
+            passed: results.iter().filter(|r| r.passed).count(),
+            total: results.len(),
+            points: results.iter().map(|r| r.points).sum(),
+            max_points: tests.iter().map(|t| t.points).sum(),
+            results,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    with_interview(&state, |interview| {
+        interview.runs.insert(task_id, run.clone());
+        Ok(())
+    })?;
+    Ok(run)
+}
+
+/// Host's score for one rubric criterion of a task.
+#[tauri::command]
+pub fn score_rubric(
+    state: State<'_, InterviewState>,
+    task_id: String,
+    criterion: String,
+    points: u32,
+    note: Option<String>,
+) -> Result<(), String> {
+    with_interview(&state, |interview| {
+        let (_, task) = interview.task(&task_id)?;
+        let item = task
+            .rubric
+            .iter()
+            .find(|r| r.criterion == criterion)
+            .ok_or_else(|| format!("Task {} has no criterion {}", task_id, criterion))?;
+        if points > item.points {
+            return Err(format!("{} is worth at most {} points", criterion, item.points));
+        }
+        interview.scores.entry(task_id).or_default().insert(criterion, (points, note));
+        Ok(())
+    })
+}
+
+/// Compile the final report. The interview stays loaded so the report can be exported.
+#[tauri::command]
+pub fn finish_interview(state: State<'_, InterviewState>, store: State<'_, DocumentStore>) -> Result<InterviewReport, String> {
+    with_interview(&state, |interview| {
+        let tasks: Vec<TaskReport> = interview
+            .pack
+            .tasks
+            .iter()
+            .enumerate()
+            .map(|(index, task)| {
+                let run = interview.runs.get(&task.id);
+                let scores = interview.scores.get(&task.id);
+                let rubric = task
+                    .rubric
+                    .iter()
+                    .map(|item| {
+                        let (points, note) = scores.and_then(|s| s.get(&item.criterion)).cloned().unwrap_or((0, None));
+                        RubricScore {
+                            criterion: item.criterion.clone(),
+                            points,
+                            max_points: item.points,
+                            note,
+                        }
+                    })
+                    .collect();
+                TaskReport {
+                    id: task.id.clone(),
+                    title: task.title.clone(),
+                    revealed: index < interview.revealed,
+                    test_points: run.map_or(0, |r| r.points),
+                    test_max_points: task.tests.iter().map(|t| t.points).sum(),
+                    tests_passed: run.map_or(0, |r| r.passed),
+                    tests_total: task.tests.len(),
+                    rubric,
+                    final_code: interview
+                        .documents
+                        .get(&task.id)
+                        .and_then(|id| store.with(id, |doc| doc.text.clone()).ok()),
+                }
+            })
+            .collect();
+        let points = tasks
+            .iter()
+            .map(|t| t.test_points + t.rubric.iter().map(|r| r.points).sum::<u32>())
+            .sum();
+        let max_points = tasks
+            .iter()
+            .map(|t| t.test_max_points + t.rubric.iter().map(|r| r.max_points).sum::<u32>())
+            .sum();
+        let report = InterviewReport {
+            title: interview.pack.title.clone(),
+            candidate: interview.candidate.clone(),
+            started_at: interview.started_at,
+            finished_at: storage::now_secs(),
+            tasks,
+            points,
+            max_points,
+        };
+        interview.report = Some(report.clone());
+        Ok(report)
+    })
+}
+
+fn report_markdown(report: &InterviewReport) -> String {
+    let mut out = format!("# {}\n\n", report.title);
+    if let Some(candidate) = &report.candidate {
+        out.push_str(&format!("Candidate: {}\n\n", candidate));
+    }
+    out.push_str(&format!("**Score: {} / {}**\n", report.points, report.max_points));
+    for task in &report.tasks {
+        out.push_str(&format!("\n## {}\n\n", task.title));
+        if !task.revealed {
+            out.push_str("Not reached.\n");
+            continue;
+        }
+        out.push_str(&format!(
+            "Tests: {}/{} passed ({} / {} points)\n",
+            task.tests_passed, task.tests_total, task.test_points, task.test_max_points
+        ));
+        for score in &task.rubric {
+            out.push_str(&format!("- {}: {} / {}", score.criterion, score.points, score.max_points));
+            if let Some(note) = &score.note {
+                out.push_str(&format!(" — {}", note));
+            }
+            out.push('\n');
+        }
+        if let Some(code) = &task.final_code {
+            out.push_str(&format!("\n```\n{}\n```\n", code.trim_end()));
+        }
+    }
+    out
+}
+
+/// Write the finished report as `json` or `markdown` (the default).
+#[tauri::command]
+pub fn export_interview_report(
+    app: AppHandle,
+    state: State<'_, InterviewState>,
+    path: String,
+    format: Option<String>,
+) -> Result<(), String> {
+    let report = with_interview(&state, |interview| {
+        interview.report.clone().ok_or_else(|| "Finish the interview first".to_string())
+    })?;
+    let contents = match format.as_deref().unwrap_or("markdown") {
+        "json" => serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?,
+        "markdown" | "md" => report_markdown(&report),
+        other => return Err(format!("Unknown report format: {}", other)),
+    };
+    let path = PathBuf::from(path);
+    fs::write(&path, contents).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    webhooks::export_created(&app, "interview-report", &path);
+    Ok(())
+}
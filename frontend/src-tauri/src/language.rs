@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::settings::SettingsStore;
+use crate::syntax;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LanguageProfile {
+    pub tab_width: u32,
+    pub insert_spaces: bool,
+    /// Editor theme to force for this language; `None` follows the app theme.
+    pub theme: Option<String>,
+    /// Formatter command line; `{file}` is replaced with the document path.
+    pub formatter: Option<String>,
+    /// Run command line; `{file}` is replaced with the document path.
+    pub run_command: Option<String>,
+}
+
+impl Default for LanguageProfile {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            insert_spaces: true,
+            theme: None,
+            formatter: None,
+            run_command: None,
+        }
+    }
+}
+
+fn profile(tab_width: u32, insert_spaces: bool, formatter: Option<&str>, run_command: Option<&str>) -> LanguageProfile {
+    LanguageProfile {
+        tab_width,
+        insert_spaces,
+        theme: None,
+        formatter: formatter.map(str::to_string),
+        run_command: run_command.map(str::to_string),
+    }
+}
+
+/// Shipped defaults for the most common languages; anything else gets `LanguageProfile::default()`.
+/// Run commands get a lone file and no shell, so compiled languages that need a build step or a
+/// project have none until the user configures one.
+pub fn default_profile(language: &str) -> LanguageProfile {
+    match language {
+        "javascript" => profile(2, true, Some("prettier --write {file}"), Some("node {file}")),
+        "typescript" => profile(2, true, Some("prettier --write {file}"), Some("npx tsx {file}")),
+        "python" => profile(4, true, Some("black {file}"), Some("python3 {file}")),
+        "java" => profile(4, true, Some("google-java-format -i {file}"), Some("java {file}")),
+        "c" => profile(4, true, Some("clang-format -i {file}"), None),
+        "cpp" => profile(4, true, Some("clang-format -i {file}"), None),
+        "csharp" => profile(4, true, Some("dotnet format"), None),
+        "go" => profile(4, false, Some("gofmt -w {file}"), Some("go run {file}")),
+        "rust" => profile(4, true, Some("rustfmt {file}"), None),
+        "php" => profile(4, true, Some("php-cs-fixer fix {file}"), Some("php {file}")),
+        "ruby" => profile(2, true, Some("rubocop -a {file}"), Some("ruby {file}")),
+        "swift" => profile(4, true, Some("swift-format -i {file}"), Some("swift {file}")),
+        "kotlin" => profile(4, true, Some("ktlint -F {file}"), Some("kotlinc -script {file}")),
+        "shell" => profile(2, true, Some("shfmt -w {file}"), Some("sh {file}")),
+        "sql" => profile(2, true, None, None),
+        "html" => profile(2, true, Some("prettier --write {file}"), None),
+        "css" => profile(2, true, Some("prettier --write {file}"), None),
+        "json" => profile(2, true, Some("prettier --write {file}"), None),
+        "yaml" => profile(2, true, Some("prettier --write {file}"), None),
+        "markdown" => profile(2, true, Some("prettier --write {file}"), None),
+        _ => LanguageProfile::default(),
+    }
+}
+
+/// Map a file extension to the language ids used by the editor.
+pub fn language_for_extension(ext: &str) -> Option<&'static str> {
+    let language = match ext.to_ascii_lowercase().as_str() {
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "mts" | "cts" | "tsx" => "typescript",
+        "py" | "pyw" => "python",
+        "java" => "java",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "go" => "go",
+        "rs" => "rust",
+        "php" => "php",
+        "rb" => "ruby",
+        "swift" => "swift",
+        "kt" | "kts" => "kotlin",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" | "scss" | "less" => "css",
+        "json" => "json",
+        "yml" | "yaml" => "yaml",
+        "md" | "markdown" => "markdown",
+        "diff" | "patch" => "diff",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// The usual file extension for a language, for files written on the user's behalf.
+pub fn extension_for_language(language: &str) -> Option<&'static str> {
+    let ext = match language {
+        "javascript" => "js",
+        "typescript" => "ts",
+        "python" => "py",
+        "java" => "java",
+        "c" => "c",
+        "cpp" => "cpp",
+        "csharp" => "cs",
+        "go" => "go",
+        "rust" => "rs",
+        "php" => "php",
+        "ruby" => "rb",
+        "swift" => "swift",
+        "kotlin" => "kt",
+        "shell" => "sh",
+        "sql" => "sql",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" => "yaml",
+        "markdown" => "md",
+        "diff" => "diff",
+        _ => return None,
+    };
+    Some(ext)
+}
+
+/// Tell-tale lines of each language and how much each counts towards it. Patterns match per line.
+const SIGNS: &[(&str, &str, f32)] = &[
+    ("rust", r"\bfn\s+\w+\s*(<[^>]*>)?\s*\(", 2.0),
+    ("rust", r"\blet\s+mut\b", 3.0),
+    ("rust", r"^\s*use\s+\w+(::\w+)+", 3.0),
+    ("rust", r"println!\(|vec!\[|#\[derive\(", 3.0),
+    ("rust", r"^\s*impl\b.*\{", 2.0),
+    ("python", r"^\s*def\s+\w+\s*\(.*\)\s*(->.*)?:\s*$", 3.0),
+    ("python", r"^\s*class\s+\w+(\(.*\))?:\s*$", 3.0),
+    ("python", r"^\s*(elif|except|finally)\b.*:\s*$", 3.0),
+    ("python", r"^\s*(from\s+[\w.]+\s+)?import\s+[\w.]+(\s+as\s+\w+)?\s*$", 2.0),
+    ("python", r"\bself\.\w+", 1.5),
+    ("python", r"__name__\s*==", 3.0),
+    ("javascript", r"\b(const|let)\s+\w+\s*=", 1.5),
+    ("javascript", r"\bfunction\s*\w*\s*\(", 2.0),
+    ("javascript", r"console\.log\(", 3.0),
+    ("javascript", r"require\(['\x22]", 3.0),
+    ("javascript", r"\b(document|window)\.\w+", 2.0),
+    ("javascript", r"=>", 1.0),
+    ("typescript", r":\s*(string|number|boolean|any|void|unknown)\b", 3.0),
+    ("typescript", r"^\s*(export\s+)?interface\s+\w+", 2.0),
+    ("typescript", r"^\s*(export\s+)?type\s+\w+\s*=", 2.0),
+    ("typescript", r"\b(const|let)\s+\w+\s*(:\s*[\w<>\[\]]+)?\s*=", 1.0),
+    ("typescript", r"console\.log\(", 2.0),
+    ("go", r"^package\s+\w+\s*$", 4.0),
+    ("go", r"\bfunc\s+(\(\w+\s+\*?\w+\)\s*)?\w+\s*\(", 3.0),
+    ("go", r"\bfmt\.\w+\(", 3.0),
+    ("go", r":=", 1.5),
+    ("go", r"\bgo\s+func\b|\bchan\s+\w+", 2.0),
+    ("java", r"\bpublic\s+(static\s+)?(final\s+)?(class|void|int|String)\b", 3.0),
+    ("java", r"System\.out\.print", 3.0),
+    ("java", r"^import\s+java\.", 4.0),
+    ("java", r"@Override", 3.0),
+    ("c", r"^#include\s*<\w+\.h>", 3.0),
+    ("c", r"\bint\s+main\s*\(", 2.0),
+    ("c", r"\bprintf\s*\(", 1.5),
+    ("c", r"\b(malloc|free)\s*\(", 2.0),
+    ("cpp", r"^#include\s*<\w+>", 3.0),
+    ("cpp", r"\bstd::", 3.0),
+    ("cpp", r"\bcout\s*<<|\bcin\s*>>", 3.0),
+    ("cpp", r"\btemplate\s*<", 3.0),
+    ("cpp", r"\bint\s+main\s*\(", 1.5),
+    ("csharp", r"^using\s+System", 4.0),
+    ("csharp", r"Console\.Write", 3.0),
+    ("csharp", r"\{\s*get;", 3.0),
+    ("php", r"<\?php", 5.0),
+    ("php", r"\$\w+\s*=", 2.0),
+    ("php", r"\bfunction\s+\w+\s*\(\$", 3.0),
+    ("ruby", r"^\s*def\s+\w+[?!]?(\(.*\))?\s*$", 2.0),
+    ("ruby", r"^\s*end\s*$", 2.0),
+    ("ruby", r"\bdo\s*\|\w+(,\s*\w+)*\|", 3.0),
+    ("ruby", r"\bputs\s", 2.0),
+    ("ruby", r"\battr_(accessor|reader|writer)\b", 3.0),
+    ("swift", r"^import\s+(Foundation|UIKit|SwiftUI)", 4.0),
+    ("swift", r"\b(guard|if)\s+let\b", 3.0),
+    ("swift", r"\bfunc\s+\w+\s*\(.*\)\s*(->\s*\w+)?\s*\{", 2.0),
+    ("kotlin", r"\bfun\s+\w+\s*\(", 3.0),
+    ("kotlin", r"\bval\s+\w+\s*(:\s*\w+)?\s*=", 2.0),
+    ("kotlin", r"\bdata\s+class\b", 3.0),
+    ("shell", r"^\s*(echo|export|cd|sudo|apt|brew|npm|git|curl)\s", 1.5),
+    ("shell", r"^\s*(if\s+\[|then|fi|done|esac)\b", 3.0),
+    ("shell", r"\|\s*(grep|awk|sed|xargs)\b", 2.0),
+    ("sql", r"(?i)^\s*(select\s.+\sfrom|insert\s+into|update\s+\w+\s+set|create\s+table|delete\s+from)\b", 4.0),
+    ("sql", r"(?i)\b(inner\s+join|left\s+join|group\s+by|order\s+by)\b", 1.0),
+    ("html", r"(?i)<(!doctype|html|head|body|div|span|p|a|ul|li)\b[^>]*>", 3.0),
+    ("html", r"</\w+>", 1.0),
+    ("css", r"^\s*[\w.#:\[\]=\x22'-]+(\s*[,>+~]?\s*[\w.#:-]+)*\s*\{\s*$", 1.5),
+    ("css", r"^\s*[\w-]+\s*:\s*[^;{}]+;\s*$", 2.0),
+    ("css", r"^\s*@(media|import|keyframes)\b", 2.0),
+    ("yaml", r"^---\s*$", 2.0),
+    ("yaml", r"^[\w-]+:(\s+\S.*)?$", 1.0),
+    ("yaml", r"^\s+-\s+[\w\x22']", 1.0),
+    ("markdown", r"^#{1,6}\s+\S", 2.0),
+    ("markdown", r"^```", 3.0),
+    ("markdown", r"\[[^\]]+\]\([^)]+\)", 2.0),
+    ("diff", r"^(diff --git |@@ -\d+(,\d+)? \+\d+(,\d+)? @@|--- a/|\+\+\+ b/)", 5.0),
+];
+
+/// Below this score nothing stands out, and no language is guessed.
+const MIN_SCORE: f32 = 2.0;
+/// Only the start of long text is looked at.
+const DETECT_LIMIT: usize = 16 * 1024;
+
+fn signs() -> &'static [(&'static str, Regex, f32)] {
+    static SIGNS_COMPILED: OnceLock<Vec<(&str, Regex, f32)>> = OnceLock::new();
+    SIGNS_COMPILED.get_or_init(|| {
+        SIGNS
+            .iter()
+            .map(|&(language, pattern, weight)| (language, Regex::new(&format!("(?m){}", pattern)).unwrap(), weight))
+            .collect()
+    })
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageGuess {
+    /// `None` when the text doesn't look like any language in particular.
+    pub language: Option<String>,
+    pub extension: Option<String>,
+    /// 0 to 1.
+    pub confidence: f32,
+    /// Other likely languages, most likely first.
+    pub alternatives: Vec<String>,
+}
+
+impl LanguageGuess {
+    fn certain(language: &str) -> Self {
+        Self {
+            language: Some(language.to_string()),
+            extension: extension_for_language(language).map(str::to_string),
+            confidence: 0.99,
+            alternatives: Vec::new(),
+        }
+    }
+}
+
+/// Interpreter named on a `#!` line.
+fn shebang(source: &str) -> Option<&'static str> {
+    let line = source.lines().next()?.strip_prefix("#!")?;
+    let interpreter = line.split_whitespace().flat_map(|word| word.rsplit('/').next()).find(|word| *word != "env")?;
+    let language = match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+        "python" => "python",
+        "node" | "deno" => "javascript",
+        "sh" | "bash" | "zsh" | "dash" => "shell",
+        "ruby" => "ruby",
+        "php" => "php",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// Guess the language of `source` from its shebang, the constructs typical of each language and,
+/// where a grammar is bundled, whether it parses cleanly.
+pub fn detect(source: &str) -> LanguageGuess {
+    let mut end = source.len().min(DETECT_LIMIT);
+    while !source.is_char_boundary(end) {
+        end -= 1;
+    }
+    let source = source[..end].trim();
+    if let Some(language) = shebang(source) {
+        return LanguageGuess::certain(language);
+    }
+    if (source.starts_with('{') || source.starts_with('[')) && serde_json::from_str::<serde_json::Value>(source).is_ok() {
+        return LanguageGuess::certain("json");
+    }
+
+    let mut scores: HashMap<&str, f32> = HashMap::new();
+    for &(language, ref pattern, weight) in signs() {
+        if pattern.is_match(source) {
+            *scores.entry(language).or_default() += weight;
+        }
+    }
+    // Heuristics get close; the grammar settles it between languages that look alike
+    for (language, score) in scores.iter_mut() {
+        if let Ok(tree) = syntax::parse(language, source) {
+            *score += if tree.root_node().has_error() { -1.0 } else { 1.5 };
+        }
+    }
+    let mut ranked: Vec<(&str, f32)> = scores.into_iter().filter(|&(_, score)| score > 0.0).collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let runner_up = ranked.get(1).map_or(0.0, |&(_, score)| score);
+    let Some(&(language, score)) = ranked.first().filter(|&&(_, score)| score >= MIN_SCORE) else {
+        return LanguageGuess {
+            language: None,
+            extension: None,
+            confidence: 0.0,
+            alternatives: ranked.iter().take(3).map(|(language, _)| language.to_string()).collect(),
+        };
+    };
+    // Sure when it stands well clear of the rest, and of the threshold
+    let confidence = score / (score + runner_up) * (score / (score + MIN_SCORE));
+    LanguageGuess {
+        language: Some(language.to_string()),
+        extension: extension_for_language(language).map(str::to_string),
+        confidence,
+        alternatives: ranked.iter().skip(1).take(3).map(|(language, _)| language.to_string()).collect(),
+    }
+}
+
+/// The effective profile: the user's override if there is one, else the shipped default.
+pub fn resolve_profile(app: &AppHandle, settings: &SettingsStore, language: &str) -> Result<LanguageProfile, String> {
+    Ok(settings
+        .get(app)?
+        .language_profiles
+        .get(language)
+        .cloned()
+        .unwrap_or_else(|| default_profile(language)))
+}
+
+#[tauri::command]
+pub fn get_language_profile(app: AppHandle, settings: State<'_, SettingsStore>, language: String) -> Result<LanguageProfile, String> {
+    resolve_profile(&app, &settings, &language)
+}
+
+/// Store an override for `language`; passing `None` reverts to the shipped default.
+#[tauri::command]
+pub fn set_language_profile(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    language: String,
+    profile: Option<LanguageProfile>,
+) -> Result<LanguageProfile, String> {
+    settings.update(&app, |s| match profile {
+        Some(profile) => {
+            s.language_profiles.insert(language.clone(), profile);
+        }
+        None => {
+            s.language_profiles.remove(&language);
+        }
+    })?;
+    resolve_profile(&app, &settings, &language)
+}
+
+/// Guess the language of pasted code, so it can be highlighted and saved without asking.
+#[tauri::command]
+pub fn detect_language(source: String) -> LanguageGuess {
+    detect(&source)
+}
@@ -1,106 +1,351 @@
 use tauri::Manager;
 
+mod accessibility;
+mod armor;
+mod artifact;
+mod backup;
+mod bandwidth;
+mod bigfile;
+mod binary;
+mod ble;
+mod breakout;
+mod bundle;
+mod callgraph;
+#[cfg(desktop)]
+mod clipboard;
+mod colors;
+mod cues;
+mod debug;
+#[cfg(desktop)]
+mod desktop_search;
+mod discovery;
+mod documents;
+#[cfg(desktop)]
+mod dragout;
+mod e2ee;
+mod encoding;
+mod events;
+mod export;
+mod framebuf;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzz;
+mod gestures;
+mod ghost;
+mod git;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod handoff;
+#[cfg(desktop)]
+mod haptics;
+mod highlight;
+mod history;
+#[cfg(desktop)]
+mod hotkeys;
+mod imaging;
+mod importers;
+mod indexer;
+mod interview;
+mod language;
+mod lifecycle;
+mod magnifier;
+mod minimap;
+mod mqtt;
+mod netsim;
+mod ocr;
+mod p2p;
+#[cfg(desktop)]
+mod opener;
+#[cfg(desktop)]
+mod overlay;
+#[cfg(desktop)]
+mod panic_hide;
+mod pen;
+mod polls;
+mod print;
+mod protocol;
+#[cfg(desktop)]
+mod quicklook;
+mod receipts;
+mod recents;
+mod recorder;
+mod releases;
+mod remote_actions;
+mod replay;
+mod review;
+#[cfg(desktop)]
+mod richcopy;
+mod rooms;
+mod runner;
+mod screenlock;
+mod server;
+mod session;
+mod settings;
+#[cfg(desktop)]
+mod share_detector;
+mod sharing;
+mod shutdown;
+#[cfg(feature = "sim")]
+mod sim;
+mod snapshot;
+mod snippets;
+mod speech;
+mod status_sync;
+mod stego;
+mod storage;
+mod structure;
+mod symbol_share;
+mod syntax;
+mod template;
+mod timetrack;
+mod transparency;
+#[cfg(desktop)]
+mod tray;
+mod viewer;
+mod webhooks;
+#[cfg(all(desktop, feature = "voice"))]
+mod voice;
+mod workers;
+
+pub use sharecode_core::CaptureProtection;
+
+/// Capture protection given to windows as they open, since protection is per window.
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProtectionPolicy {
+    Off,
+    Always,
+    /// Protected whenever the main window is.
+    #[default]
+    FollowMain,
+}
+
+#[cfg(target_os = "linux")]
+use sharecode_core::linux as linux_impl;
+#[cfg(target_os = "macos")]
+use sharecode_core::macos as macos_impl;
+#[cfg(target_os = "windows")]
+use sharecode_core::windows as windows_impl;
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+use sharecode_core::WindowStealth;
+
+/// The window's native handle, for the calls `WindowStealth` covers.
 #[cfg(target_os = "windows")]
-mod windows_impl {
+fn stealth(window: &tauri::WebviewWindow) -> Result<windows_impl::Win32Window, String> {
     use windows::Win32::Foundation::HWND;
-    use windows::Win32::Graphics::Dwm::{DwmSetWindowAttribute, DWMWA_EXCLUDED_FROM_PEEK, DWMWA_CLOAK};
-    use windows::Win32::UI::WindowsAndMessaging::{SetWindowLongPtrW, GetWindowLongPtrW, GWL_EXSTYLE, WS_EX_TOOLWINDOW, WS_EX_APPWINDOW};
-
-    pub unsafe fn hide_from_capture(hwnd: HWND) -> Result<(), String> {
-        // Exclude from screen capture (Windows 10+)
-        let excluded: i32 = 1;
-        DwmSetWindowAttribute(
-            hwnd,
-            DWMWA_EXCLUDED_FROM_PEEK,
-            &excluded as *const _ as *const _,
-            std::mem::size_of::<i32>() as u32,
-        ).map_err(|e| format!("Failed to exclude from capture: {}", e))?;
-
-        // Also try to cloak the window from screen sharing
-        let cloak: i32 = 1;
-        DwmSetWindowAttribute(
-            hwnd,
-            DWMWA_CLOAK,
-            &cloak as *const _ as *const _,
-            std::mem::size_of::<i32>() as u32,
-        ).ok(); // This may fail on some Windows versions, so we don't return error
 
-        Ok(())
-    }
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+    // The webview window owns the handle and outlives every use of it here
+    Ok(unsafe { windows_impl::Win32Window::from_raw(HWND(hwnd.0 as _)) })
+}
 
-    pub unsafe fn show_in_capture(hwnd: HWND) -> Result<(), String> {
-        let excluded: i32 = 0;
-        DwmSetWindowAttribute(
-            hwnd,
-            DWMWA_EXCLUDED_FROM_PEEK,
-            &excluded as *const _ as *const _,
-            std::mem::size_of::<i32>() as u32,
-        ).map_err(|e| format!("Failed to include in capture: {}", e))?;
+#[cfg(target_os = "macos")]
+fn stealth(window: &tauri::WebviewWindow) -> Result<macos_impl::CocoaWindow, String> {
+    let ns_window = window.ns_window().map_err(|e| e.to_string())? as cocoa::base::id;
+    Ok(unsafe { macos_impl::CocoaWindow::from_raw(ns_window) })
+}
 
-        let cloak: i32 = 0;
-        DwmSetWindowAttribute(
-            hwnd,
-            DWMWA_CLOAK,
-            &cloak as *const _ as *const _,
-            std::mem::size_of::<i32>() as u32,
-        ).ok();
+/// Labels of the windows the user asked to hide from capture, which the watchdog keeps hidden.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+static CAPTURE_PROTECTED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+/// How often the watchdog checks that protection is still applied.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+const CAPTURE_WATCHDOG_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
 
-        Ok(())
+#[cfg(desktop)]
+fn apply_capture_protection(window: &tauri::WebviewWindow, enabled: bool) -> Result<CaptureProtection, String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        let native = stealth(window)?;
+        if enabled {
+            native.hide_from_capture()
+        } else {
+            native.show_in_capture()?;
+            Ok(CaptureProtection::Off)
+        }
     }
 
-    pub unsafe fn hide_from_taskbar(hwnd: HWND) -> Result<(), String> {
-        let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
-
-        // Remove WS_EX_APPWINDOW and add WS_EX_TOOLWINDOW to hide from taskbar
-        ex_style &= !(WS_EX_APPWINDOW.0 as isize);
-        ex_style |= WS_EX_TOOLWINDOW.0 as isize;
+    #[cfg(target_os = "linux")]
+    {
+        // Nothing was hidden, so turning protection off always succeeds.
+        if enabled {
+            linux_impl::hide_from_capture()?;
+        }
+        let _ = window;
+        Ok(CaptureProtection::Off)
+    }
 
-        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
-        Ok(())
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (window, enabled);
+        Err("Screen capture protection is not supported on this platform".to_string())
     }
+}
 
-    pub unsafe fn show_in_taskbar(hwnd: HWND) -> Result<(), String> {
-        let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+#[cfg(desktop)]
+fn read_capture_protection(window: &tauri::WebviewWindow) -> Result<CaptureProtection, String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        stealth(window)?.capture_protection()
+    }
 
-        // Add WS_EX_APPWINDOW and remove WS_EX_TOOLWINDOW to show in taskbar
-        ex_style |= WS_EX_APPWINDOW.0 as isize;
-        ex_style &= !(WS_EX_TOOLWINDOW.0 as isize);
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // Nothing else can hide the window, so it is never protected.
+        let _ = window;
+        Ok(CaptureProtection::Off)
+    }
+}
 
-        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
-        Ok(())
+/// Apply protection, keep the watchdog's list in step and record the change for transparency reports.
+#[cfg(desktop)]
+fn protect_from_capture(window: &tauri::WebviewWindow, enabled: bool) -> Result<CaptureProtection, String> {
+    let applied = apply_capture_protection(window, enabled)?;
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        let mut protected = CAPTURE_PROTECTED.lock().unwrap();
+        protected.retain(|label| label != window.label());
+        if enabled {
+            protected.push(window.label().to_string());
+        }
+        drop(protected);
+        transparency::note(window.app_handle(), transparency::PrivacyFeature::CaptureHidden, enabled);
     }
+    Ok(applied)
 }
 
-#[cfg(target_os = "macos")]
-mod macos_impl {
-    use cocoa::appkit::{NSWindow, NSWindowSharingType};
-    use cocoa::base::{id, BOOL, NO, YES};
-    use objc::runtime::Object;
-    use objc::*;
+#[cfg(desktop)]
+#[tauri::command]
+fn set_screen_capture_protection(window: tauri::WebviewWindow, enabled: bool) -> Result<CaptureProtection, String> {
+    let applied = protect_from_capture(&window, enabled);
+    tray::refresh(window.app_handle());
+    applied
+}
 
-    pub unsafe fn hide_from_capture(ns_window: id) {
-        // Prevent window from being captured in screen recordings
-        let _: () = msg_send![ns_window, setSharingType: NSWindowSharingType::NSWindowSharingNone];
-    }
+/// The protection the OS is actually applying to the window, which may have been dropped
+/// behind the app's back (display changes, GPU driver resets).
+#[cfg(desktop)]
+#[tauri::command]
+fn get_screen_capture_protection(window: tauri::WebviewWindow) -> Result<CaptureProtection, String> {
+    read_capture_protection(&window)
+}
 
-    pub unsafe fn show_in_capture(ns_window: id) {
-        // Allow window to be captured in screen recordings
-        let _: () = msg_send![ns_window, setSharingType: NSWindowSharingType::NSWindowSharingReadOnly];
+/// Apply the default protection policy to a window that has just opened.
+#[cfg(desktop)]
+fn protect_new_window(window: &tauri::WebviewWindow) {
+    let app = window.app_handle();
+    let policy = app
+        .state::<settings::SettingsStore>()
+        .get(app)
+        .map(|settings| settings.default_protection)
+        .unwrap_or_default();
+    let enabled = match policy {
+        ProtectionPolicy::Off => false,
+        ProtectionPolicy::Always => true,
+        ProtectionPolicy::FollowMain => app
+            .get_webview_window("main")
+            .and_then(|main| read_capture_protection(&main).ok())
+            .is_some_and(|p| !matches!(p, CaptureProtection::Off)),
+    };
+    if enabled {
+        if let Err(e) = protect_from_capture(window, true) {
+            log::warn!("Failed to protect new window {} from capture: {}", window.label(), e);
+        }
     }
+}
 
-    pub unsafe fn hide_from_dock(ns_app: id) {
-        // Hide from dock by setting activation policy to accessory
-        let _: BOOL = msg_send![ns_app, setActivationPolicy: 1]; // NSApplicationActivationPolicyAccessory = 1
-    }
+#[cfg(desktop)]
+#[tauri::command]
+fn get_default_protection_policy(
+    app: tauri::AppHandle,
+    settings: tauri::State<'_, settings::SettingsStore>,
+) -> Result<ProtectionPolicy, String> {
+    Ok(settings.get(&app)?.default_protection)
+}
+
+/// Choose the protection windows get when they open. Windows already open keep theirs.
+#[cfg(desktop)]
+#[tauri::command]
+fn set_default_protection_policy(
+    app: tauri::AppHandle,
+    settings: tauri::State<'_, settings::SettingsStore>,
+    policy: ProtectionPolicy,
+) -> Result<(), String> {
+    settings.update(&app, |s| s.default_protection = policy)
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+struct CaptureProtectionLost {
+    window: String,
+    error: String,
+}
+
+/// Put back protection the OS dropped. Windows resets display affinity on some monitor
+/// hot-plugs, DPI changes and session unlocks, so the real state is compared with the request
+/// for the life of the app. Emits `capture-protection-lost` if it cannot be re-applied.
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn spawn_capture_watchdog(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CAPTURE_WATCHDOG_INTERVAL);
+        let labels = CAPTURE_PROTECTED.lock().unwrap().clone();
+        for label in labels {
+            let Some(window) = app.get_webview_window(&label) else {
+                continue;
+            };
+            // Window attributes belong to the main thread on macOS.
+            let target = window.clone();
+            let checked = window.run_on_main_thread(move || {
+                // Turned off by the user in the meantime.
+                if !CAPTURE_PROTECTED.lock().unwrap().contains(&label) {
+                    return;
+                }
+                match stealth(&target).and_then(|native| sharecode_core::reapply_protection(&native)) {
+                    Ok(None) => {}
+                    Ok(Some(_)) => log::warn!("Capture protection of window {} was dropped; re-applied", label),
+                    Err(error) => {
+                        use tauri::Emitter;
+                        let lost = CaptureProtectionLost { window: label, error };
+                        if let Err(e) = target.app_handle().emit("capture-protection-lost", lost) {
+                            log::warn!("Failed to emit capture-protection-lost: {}", e);
+                        }
+                    }
+                }
+            });
+            if let Err(e) = checked {
+                log::warn!("Failed to check capture protection: {}", e);
+            }
+        }
+    });
+}
 
-    pub unsafe fn show_in_dock(ns_app: id) {
-        // Show in dock by setting activation policy to regular
-        let _: BOOL = msg_send![ns_app, setActivationPolicy: 0]; // NSApplicationActivationPolicyRegular = 0
+/// Shut down cleanly when the user logs off or Windows shuts down, which ends the process without
+/// the event loop ever exiting, and pause the session while the workstation is locked.
+#[cfg(target_os = "windows")]
+fn watch_session(app: &tauri::AppHandle) {
+    use windows::Win32::Foundation::HWND;
+
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let app = app.clone();
+    let on_event = move |event| match event {
+        // Windows ends the process once this returns, without going through the event loop's exit
+        windows_impl::SessionEvent::Ending => shutdown::run(&app),
+        windows_impl::SessionEvent::Locked => screenlock::locked(&app),
+        windows_impl::SessionEvent::Unlocked => screenlock::unlocked(&app),
+    };
+    let watched = window
+        .hwnd()
+        .map_err(|e| e.to_string())
+        .and_then(|hwnd| unsafe { windows_impl::watch_session(HWND(hwnd.0 as _), on_event) });
+    if let Err(e) = watched {
+        log::warn!("{}", e);
     }
 }
 
-#[tauri::command]
-fn set_screen_capture_protection(window: tauri::Window, enabled: bool) -> Result<(), String> {
+#[cfg(desktop)]
+fn apply_taskbar_visibility(window: &tauri::WebviewWindow, visible: bool) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         use windows::Win32::Foundation::HWND;
@@ -109,12 +354,17 @@ fn set_screen_capture_protection(window: tauri::Window, enabled: bool) -> Result
         let hwnd = HWND(hwnd.0 as _);
 
         unsafe {
-            if enabled {
-                windows_impl::hide_from_capture(hwnd)?;
+            if visible {
+                // The same window style puts it back in Alt+Tab
+                windows_impl::show_in_switcher(hwnd, true)?;
             } else {
-                windows_impl::show_in_capture(hwnd)?;
+                windows_impl::hide_from_taskbar(hwnd)?;
             }
         }
+        transparency::note(window.app_handle(), transparency::PrivacyFeature::TaskbarHidden, !visible);
+        if visible {
+            transparency::note(window.app_handle(), transparency::PrivacyFeature::SwitcherHidden, false);
+        }
         Ok(())
     }
 
@@ -122,40 +372,56 @@ fn set_screen_capture_protection(window: tauri::Window, enabled: bool) -> Result
     {
         use cocoa::appkit::NSApp;
 
-        let ns_window = window.ns_window().map_err(|e| e.to_string())? as cocoa::base::id;
-
         unsafe {
-            if enabled {
-                macos_impl::hide_from_capture(ns_window);
+            let ns_app = NSApp();
+            if visible {
+                macos_impl::show_in_dock(ns_app);
             } else {
-                macos_impl::show_in_capture(ns_window);
+                macos_impl::hide_from_dock(ns_app);
             }
         }
+        transparency::note(window.app_handle(), transparency::PrivacyFeature::TaskbarHidden, !visible);
         Ok(())
     }
 
     #[cfg(not(any(target_os = "windows", target_os = "macos")))]
     {
-        Err("Screen capture protection is not supported on this platform".to_string())
+        let _ = (window, visible);
+        Err("Taskbar visibility control is not supported on this platform".to_string())
     }
 }
 
+#[cfg(desktop)]
 #[tauri::command]
-fn set_taskbar_visibility(window: tauri::Window, visible: bool) -> Result<(), String> {
+fn set_taskbar_visibility(window: tauri::WebviewWindow, visible: bool) -> Result<(), String> {
+    let applied = apply_taskbar_visibility(&window, visible);
+    tray::refresh(window.app_handle());
+    applied
+}
+
+/// Leave the app out of the app switcher (Alt+Tab, Cmd-Tab) and Task View or Mission Control.
+/// Both OSes only offer this together with leaving the taskbar or Dock, so hiding here hides there
+/// too, and showing here only shows there if the taskbar or Dock was not hidden on its own. On
+/// Windows the window is also given a hidden owner, and briefly hidden so the shell notices.
+#[cfg(desktop)]
+fn apply_switcher_visibility(window: &tauri::WebviewWindow, visible: bool) -> Result<(), String> {
+    let app = window.app_handle();
+    let keep_taskbar_hidden = visible && transparency::is_active(app, transparency::PrivacyFeature::TaskbarHidden);
+
     #[cfg(target_os = "windows")]
     {
         use windows::Win32::Foundation::HWND;
 
         let hwnd = window.hwnd().map_err(|e| e.to_string())?;
         let hwnd = HWND(hwnd.0 as _);
-
         unsafe {
             if visible {
-                windows_impl::show_in_taskbar(hwnd)?;
+                windows_impl::show_in_switcher(hwnd, !keep_taskbar_hidden)?;
             } else {
-                windows_impl::hide_from_taskbar(hwnd)?;
+                windows_impl::hide_from_switcher(hwnd)?;
             }
         }
+        transparency::note(app, transparency::PrivacyFeature::SwitcherHidden, !visible);
         Ok(())
     }
 
@@ -163,26 +429,179 @@ fn set_taskbar_visibility(window: tauri::Window, visible: bool) -> Result<(), St
     {
         use cocoa::appkit::NSApp;
 
+        let ns_window = window.ns_window().map_err(|e| e.to_string())? as cocoa::base::id;
         unsafe {
-            let ns_app = NSApp();
+            // Cmd-Tab lists exactly the apps in the Dock
             if visible {
-                macos_impl::show_in_dock(ns_app);
+                macos_impl::show_in_expose(ns_window);
+                if !keep_taskbar_hidden {
+                    macos_impl::show_in_dock(NSApp());
+                }
             } else {
-                macos_impl::hide_from_dock(ns_app);
+                macos_impl::hide_from_expose(ns_window);
+                macos_impl::hide_from_dock(NSApp());
             }
         }
+        transparency::note(app, transparency::PrivacyFeature::SwitcherHidden, !visible);
         Ok(())
     }
 
     #[cfg(not(any(target_os = "windows", target_os = "macos")))]
     {
-        Err("Taskbar visibility control is not supported on this platform".to_string())
+        let _ = (window, keep_taskbar_hidden);
+        Err("App switcher visibility control is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(desktop)]
+#[tauri::command]
+fn set_switcher_visibility(window: tauri::WebviewWindow, visible: bool) -> Result<(), String> {
+    let applied = apply_switcher_visibility(&window, visible);
+    tray::refresh(window.app_handle());
+    applied
+}
+
+/// Windows are never made fainter than this, so one can't be lost by making it invisible.
+#[cfg(desktop)]
+const MIN_WINDOW_OPACITY: f64 = 0.2;
+
+/// Make the whole window translucent, `opacity` running from 0 (invisible) to 1 (opaque).
+#[cfg(desktop)]
+fn apply_window_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        stealth(window)?.set_opacity(opacity)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = (window, opacity);
+        Err("Window opacity is not supported on this platform".to_string())
+    }
+}
+
+#[cfg(desktop)]
+fn read_window_opacity(window: &tauri::WebviewWindow) -> Result<f64, String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        stealth(window)?.opacity()
+    }
+
+    // Opacity can't be changed here, so windows are always opaque
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = window;
+        Ok(1.0)
+    }
+}
+
+/// Fade the window to `alpha`, from `MIN_WINDOW_OPACITY` to 1 (opaque), e.g. to read reference
+/// code on top of an editor.
+#[cfg(desktop)]
+#[tauri::command]
+fn set_window_opacity(window: tauri::WebviewWindow, alpha: f64) -> Result<(), String> {
+    if !(MIN_WINDOW_OPACITY..=1.0).contains(&alpha) {
+        return Err(format!("Opacity must be between {} and 1", MIN_WINDOW_OPACITY));
+    }
+    apply_window_opacity(&window, alpha)
+}
+
+#[cfg(desktop)]
+#[tauri::command]
+fn get_window_opacity(window: tauri::WebviewWindow) -> Result<f64, String> {
+    read_window_opacity(&window)
+}
+
+/// Labels of the windows that let clicks through, where the OS can't be asked.
+#[cfg(all(desktop, not(any(target_os = "windows", target_os = "macos"))))]
+static CLICK_THROUGH: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+/// Let mouse events pass through the window to whatever is beneath it, for using it as an overlay.
+#[cfg(desktop)]
+fn apply_click_through(window: &tauri::WebviewWindow, enabled: bool) -> Result<(), String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        stealth(window)?.set_click_through(enabled)
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        // GTK's input shape, which X11 honours; Wayland compositors may not.
+        window.set_ignore_cursor_events(enabled).map_err(|e| e.to_string())?;
+        let mut labels = CLICK_THROUGH.lock().unwrap();
+        labels.retain(|label| label != window.label());
+        if enabled {
+            labels.push(window.label().to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(desktop)]
+fn read_click_through(window: &tauri::WebviewWindow) -> Result<bool, String> {
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    {
+        stealth(window)?.is_click_through()
     }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        Ok(CLICK_THROUGH.lock().unwrap().iter().any(|label| label == window.label()))
+    }
+}
+
+/// While enabled the window can't be clicked, so turn it back off from the tray or with a hotkey.
+#[cfg(desktop)]
+#[tauri::command]
+fn set_click_through(window: tauri::WebviewWindow, enabled: bool) -> Result<(), String> {
+    let applied = apply_click_through(&window, enabled);
+    tray::refresh(window.app_handle());
+    applied
+}
+
+#[cfg(desktop)]
+#[tauri::command]
+fn get_click_through(window: tauri::WebviewWindow) -> Result<bool, String> {
+    read_click_through(&window)
+}
+
+/// Real titles of disguised windows, by label, to put back when the disguise comes off.
+#[cfg(desktop)]
+static DISGUISED: std::sync::Mutex<Vec<(String, String)>> = std::sync::Mutex::new(Vec::new());
+
+/// Show the window under another application's title, or its own again with `None`. Recorded
+/// for transparency reports while any window is disguised.
+#[cfg(desktop)]
+#[tauri::command]
+fn set_window_disguise(window: tauri::WebviewWindow, title: Option<String>) -> Result<(), String> {
+    let mut disguised = DISGUISED.lock().unwrap();
+    let real = disguised.iter().position(|(label, _)| label == window.label());
+    match (title, real) {
+        (Some(title), real) => {
+            if real.is_none() {
+                disguised.push((window.label().to_string(), window.title().map_err(|e| e.to_string())?));
+            }
+            window.set_title(&title).map_err(|e| e.to_string())?;
+        }
+        (None, Some(index)) => {
+            window.set_title(&disguised[index].1).map_err(|e| e.to_string())?;
+            disguised.remove(index);
+        }
+        (None, None) => {}
+    }
+    transparency::note(window.app_handle(), transparency::PrivacyFeature::Disguise, !disguised.is_empty());
+    Ok(())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-  tauri::Builder::default()
+  let builder = tauri::Builder::default();
+  // Registered first, so a second launch is handed over before anything else starts
+  #[cfg(desktop)]
+  let builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+    opener::second_instance(app, args, cwd);
+  }));
+  builder
     .setup(|app| {
       if cfg!(debug_assertions) {
         app.handle().plugin(
@@ -191,12 +610,390 @@ pub fn run() {
             .build(),
         )?;
       }
+      app.manage(events::Events::default());
+      app.manage(backup::BackupState::load(app.handle()));
+      app.manage(snippets::SnippetLibrary::default());
+      app.manage(settings::SettingsStore::default());
+      app.manage(recents::RecentFiles::default());
+      app.manage(documents::DocumentStore::default());
+      app.manage(bigfile::LargeFileStore::default());
+      app.manage(std::sync::Arc::new(indexer::ProjectIndex::default()));
+      app.manage(std::sync::Arc::new(symbol_share::SymbolShares::default()));
+      app.manage(git::BlameCache::default());
+      app.manage(review::ReviewStore::default());
+      app.manage(session::Session::default());
+      app.manage(remote_actions::RemoteActions::default());
+      app.manage(receipts::Receipts::default());
+      app.manage(std::sync::Arc::new(sharing::SharingHub::default()));
+      app.manage(polls::PollStore::default());
+      app.manage(breakout::Breakouts::default());
+      app.manage(interview::InterviewState::default());
+      app.manage(transparency::TransparencyState::default());
+      app.manage(ghost::GhostTyping::default());
+      app.manage(server::SessionServer::default());
+      app.manage(lifecycle::Lifecycle::default());
+      app.manage(timetrack::TimeTracker::default());
+      app.manage(viewer::ViewerClient::default());
+      app.manage(handoff::HandoffState::default());
+      app.manage(ble::BleState::default());
+      app.manage(p2p::P2pState::default());
+      app.manage(e2ee::E2ee::default());
+      app.manage(webhooks::Webhooks::default());
+      app.manage(mqtt::Mqtt::default());
+      app.manage(status_sync::StatusSync::default());
+      app.manage(rooms::Rooms::default());
+      app.manage(discovery::Discovery::default());
+      app.manage(recorder::Recorder::default());
+      app.manage(speech::Speech::load(app.handle()));
+      app.manage(cues::AudioCues::load(app.handle()));
+      app.manage(releases::Releases::load(app.handle()));
+      app.manage(pen::PenState::default());
+      app.manage(colors::ColorSettings::load(app.handle()));
+      app.manage(magnifier::Magnifier::default());
+      app.manage(minimap::Minimaps::default());
+      app.manage(highlight::Highlights::default());
+      app.manage(workers::WorkerPool::default());
+      backup::spawn_scheduler(app.handle().clone());
+      releases::spawn_scheduler(app.handle().clone());
+      events::spawn_flusher(app.handle().clone());
+      webhooks::spawn_worker(app.handle().clone());
+      mqtt::start(app.handle());
+      status_sync::restore(app.handle());
+      if let Err(e) = discovery::start(app.handle()) {
+        log::warn!("LAN discovery is unavailable: {}", e);
+      }
+      accessibility::spawn_watcher(app.handle().clone());
+      #[cfg(any(target_os = "windows", target_os = "macos"))]
+      spawn_capture_watchdog(app.handle().clone());
+      #[cfg(target_os = "windows")]
+      watch_session(app.handle());
+      sharing::spawn_compactor(app.handle().clone());
+      sharing::spawn_coalescer(app.handle().clone());
+      bandwidth::restore(app.handle());
+      handoff::install(app.handle());
+      gestures::install(app.handle());
+      pen::install(app.handle());
+      screenlock::install(app.handle());
+      #[cfg(desktop)]
+      {
+        app.manage(panic_hide::PanicState::default());
+        app.manage(clipboard::ClipboardWatch::default());
+        tray::install(app.handle())?;
+        app.handle().plugin(tauri_plugin_global_shortcut::Builder::new().build())?;
+        app.handle().plugin(tauri_plugin_dialog::init())?;
+        // Every window opened from here on, which excludes the main window
+        app.handle().plugin(
+          tauri::plugin::Builder::<tauri::Wry>::new("window-protection")
+            .on_webview_ready(|webview| {
+              let Some(window) = webview.app_handle().get_webview_window(webview.label()) else {
+                return;
+              };
+              let target = window.clone();
+              if let Err(e) = window.run_on_main_thread(move || protect_new_window(&target)) {
+                log::warn!("Failed to protect new window: {}", e);
+              }
+            })
+            .build(),
+        )?;
+        hotkeys::install(app.handle());
+        overlay::install(app.handle());
+        dragout::sweep();
+        // Catches up with a backup restored since the last run
+        desktop_search::refresh(app.handle());
+        share_detector::spawn_watcher(app.handle().clone());
+        app.manage(opener::OpenedFiles::default());
+        opener::open_files(app.handle(), opener::files_in_args(std::env::args()));
+      }
+      #[cfg(all(desktop, feature = "voice"))]
+      voice::install(app.handle());
       Ok(())
     })
     .invoke_handler(tauri::generate_handler![
+        #[cfg(desktop)]
         set_screen_capture_protection,
-        set_taskbar_visibility
+        #[cfg(desktop)]
+        get_screen_capture_protection,
+        #[cfg(desktop)]
+        get_default_protection_policy,
+        #[cfg(desktop)]
+        set_default_protection_policy,
+        #[cfg(desktop)]
+        set_taskbar_visibility,
+        #[cfg(desktop)]
+        set_switcher_visibility,
+        #[cfg(desktop)]
+        set_click_through,
+        #[cfg(desktop)]
+        get_click_through,
+        #[cfg(desktop)]
+        set_window_disguise,
+        #[cfg(desktop)]
+        set_window_opacity,
+        #[cfg(desktop)]
+        get_window_opacity,
+        #[cfg(desktop)]
+        overlay::set_overlay_mode,
+        #[cfg(desktop)]
+        overlay::get_overlay_mode,
+        #[cfg(desktop)]
+        richcopy::copy_rich,
+        #[cfg(desktop)]
+        dragout::start_snippet_drag,
+        #[cfg(desktop)]
+        desktop_search::get_snippet_indexing,
+        #[cfg(desktop)]
+        desktop_search::set_snippet_indexing,
+        #[cfg(desktop)]
+        share_detector::get_screen_share,
+        #[cfg(desktop)]
+        share_detector::get_share_detection,
+        #[cfg(desktop)]
+        share_detector::set_share_detection,
+        #[cfg(desktop)]
+        clipboard::start_clipboard_watch,
+        #[cfg(desktop)]
+        clipboard::stop_clipboard_watch,
+        #[cfg(desktop)]
+        quicklook::preview_file,
+        #[cfg(desktop)]
+        opener::get_opened_file,
+        #[cfg(desktop)]
+        opener::unlock_opened_file,
+        #[cfg(desktop)]
+        panic_hide::panic_hide,
+        #[cfg(desktop)]
+        panic_hide::panic_restore,
+        #[cfg(desktop)]
+        hotkeys::get_hotkey_config,
+        #[cfg(desktop)]
+        hotkeys::set_hotkey_config,
+        #[cfg(desktop)]
+        haptics::get_haptic_feedback,
+        #[cfg(desktop)]
+        haptics::set_haptic_feedback,
+        backup::get_backup_config,
+        backup::set_backup_config,
+        backup::create_backup,
+        backup::list_backups,
+        backup::restore_backup,
+        snippets::list_snippets,
+        snippets::get_snippet,
+        snippets::search_snippets,
+        snippets::save_snippet,
+        snippets::delete_snippet,
+        snippets::broadcast_snippet,
+        releases::schedule_release,
+        releases::list_releases,
+        releases::cancel_release,
+        releases::pull_release_trigger,
+        importers::import_snippets,
+        template::expand_template,
+        template::expand_snippet,
+        language::get_language_profile,
+        language::set_language_profile,
+        language::detect_language,
+        documents::open_document,
+        documents::create_document,
+        documents::get_document_text,
+        documents::update_document_text,
+        documents::close_document,
+        documents::set_document_language,
+        documents::get_document_format,
+        documents::convert_document,
+        documents::save_document,
+        documents::freeze_document,
+        documents::unfreeze_document,
+        bigfile::open_large_file,
+        bigfile::index_large_file,
+        bigfile::get_large_file_info,
+        bigfile::read_large_file_lines,
+        bigfile::read_large_file_range,
+        bigfile::extract_large_file_region,
+        bigfile::close_large_file,
+        indexer::import_project,
+        indexer::list_symbols,
+        indexer::goto_definition,
+        structure::get_fold_ranges,
+        structure::expand_selection,
+        structure::select_enclosing_symbol,
+        symbol_share::share_symbol,
+        symbol_share::stop_tracking_symbol,
+        callgraph::get_callers,
+        callgraph::get_callees,
+        git::blame,
+        git::apply_shared_patch,
+        review::open_review,
+        review::post_review_comment,
+        review::set_review_token,
+        review::has_review_token,
+        review::close_review,
+        history::undo_document,
+        history::redo_document,
+        history::get_document_history,
+        history::get_document_timeline,
+        history::get_document_at,
+        session::add_participant,
+        session::remove_participant,
+        session::list_participants,
+        session::send_signal,
+        session::receive_message,
+        session::get_session_history,
+        remote_actions::get_audit_log,
+        receipts::get_view_status,
+        timetrack::get_time_report,
+        timetrack::export_time_report,
+        timetrack::note_activity,
+        session::set_classroom_mode,
+        sharing::attach_viewer,
+        sharing::detach_viewer,
+        sharing::get_room_capacity_estimate,
+        sharing::get_sync_stats,
+        bandwidth::get_bandwidth_usage,
+        bandwidth::set_upload_cap,
+        polls::create_poll,
+        polls::vote_poll,
+        polls::close_poll,
+        polls::list_polls,
+        breakout::open_scratchpad,
+        breakout::list_scratchpads,
+        breakout::view_scratchpad,
+        breakout::pull_scratchpad,
+        breakout::close_scratchpad,
+        runner::run_code,
+        interview::load_interview_pack,
+        interview::reveal_next_task,
+        interview::run_interview_tests,
+        interview::score_rubric,
+        interview::finish_interview,
+        interview::export_interview_report,
+        transparency::start_transparency_report,
+        transparency::finish_transparency_report,
+        transparency::verify_transparency_report,
+        transparency::get_transparency_public_key,
+        ghost::start_ghost_typing,
+        ghost::stop_ghost_typing,
+        server::start_session_server,
+        server::stop_session_server,
+        server::start_session,
+        server::stop_session,
+        server::get_session_server,
+        server::get_session_qr,
+        lifecycle::get_session_state,
+        lifecycle::pause_session,
+        lifecycle::resume_session,
+        viewer::parse_join_link,
+        viewer::join_remote_session,
+        viewer::leave_remote_session,
+        viewer::set_viewer_power_mode,
+        viewer::resume_remote_session,
+        handoff::advertise_handoff,
+        handoff::stop_handoff,
+        handoff::accept_handoff,
+        ble::scan_ble_peers,
+        ble::send_snippet_ble,
+        ble::receive_ble_frame,
+        ble::confirm_ble_pairing,
+        p2p::create_offer,
+        p2p::accept_offer,
+        p2p::accept_answer,
+        p2p::send_payload,
+        p2p::close_peer,
+        e2ee::generate_session_key,
+        e2ee::export_session_key,
+        e2ee::verify_short_auth_string,
+        webhooks::list_webhooks,
+        webhooks::add_webhook,
+        webhooks::remove_webhook,
+        webhooks::set_webhook_enabled,
+        webhooks::get_webhook_outbox,
+        mqtt::get_mqtt_settings,
+        mqtt::set_mqtt_settings,
+        status_sync::get_status_sync,
+        status_sync::set_status_sync,
+        status_sync::set_status_token,
+        rooms::create_room,
+        rooms::join_room,
+        rooms::leave_room,
+        rooms::list_peers,
+        discovery::discover_peers,
+        discovery::get_lan_discovery,
+        discovery::set_lan_discovery,
+        bundle::export_offline_bundle,
+        bundle::import_offline_bundle,
+        snapshot::export_session_snapshot,
+        snapshot::import_session_snapshot,
+        recents::get_recent_files,
+        recents::remove_recent_file,
+        recents::clear_recent_files,
+        artifact::validate_file,
+        armor::armor_snippet,
+        armor::dearmor_snippet,
+        stego::hide_snippet_in_png,
+        stego::reveal_snippet_from_png,
+        print::print_document,
+        ocr::prepare_code_screenshot,
+        recorder::start_recording,
+        recorder::stop_recording,
+        recorder::cancel_recording,
+        replay::export_typing_replay,
+        speech::get_speech_config,
+        speech::set_speech_config,
+        speech::set_speech_muted,
+        speech::announce_event,
+        cues::get_audio_cue_config,
+        cues::set_audio_cue_config,
+        cues::set_audio_cues_muted,
+        cues::preview_audio_cue,
+        gestures::set_trackpad_gestures,
+        pen::set_pen_canvas,
+        pen::add_pen_samples,
+        colors::get_participant_color,
+        colors::get_color_palette,
+        colors::set_color_palette,
+        accessibility::get_system_preferences,
+        magnifier::get_magnifier,
+        magnifier::set_magnifier,
+        minimap::get_minimap,
+        highlight::get_highlight_stats,
+        #[cfg(feature = "bench")]
+        highlight::run_highlight_benchmark,
+        highlight::highlight_code,
+        #[cfg(desktop)]
+        highlight::highlight_code_html,
+        workers::get_worker_stats,
+        workers::cancel_task,
+        #[cfg(feature = "bench")]
+        binary::ipc_benchmark_payload,
+        minimap::get_minimap_binary,
+        ocr::prepare_code_screenshot_binary,
+        bigfile::read_large_file_range_binary,
+        framebuf::get_frame_stats,
+        debug::debug_execute,
+        debug::get_developer_mode,
+        debug::set_developer_mode,
+        netsim::get_network_impairment,
+        netsim::set_network_impairment,
+        #[cfg(feature = "sim")]
+        sim::run_simulation,
+        #[cfg(all(desktop, feature = "voice"))]
+        voice::get_voice_config,
+        #[cfg(all(desktop, feature = "voice"))]
+        voice::set_voice_config,
+        #[cfg(all(desktop, feature = "voice"))]
+        voice::start_voice_listening,
+        #[cfg(all(desktop, feature = "voice"))]
+        voice::stop_voice_listening
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| match event {
+      tauri::RunEvent::ExitRequested { .. } | tauri::RunEvent::Exit => shutdown::run(app),
+      // Files opened from Finder, at launch or later
+      #[cfg(target_os = "macos")]
+      tauri::RunEvent::Opened { urls } => {
+        let paths = urls.into_iter().filter_map(|url| url.to_file_path().ok()).collect();
+        opener::open_files(app, paths);
+      }
+      _ => {}
+    });
 }
@@ -0,0 +1,233 @@
+//! The session lifecycle as two state machines: hosting (idle → hosting ⇄ paused → ended) and
+//! watching a remote session (idle → joining → connected ⇄ reconnecting → left).
+//!
+//! Starting, pausing and ending a session all go through here, so a call out of order (pausing a
+//! session that never started, say) fails with an error naming the state instead of leaving the
+//! server and the UI disagreeing. Every change is sent as `session-state-changed`.
+
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::cues::{self, AudioCue};
+use crate::discovery;
+use crate::mqtt;
+use crate::sharing::SharingHub;
+use crate::{bandwidth, receipts, status_sync, timetrack};
+use crate::webhooks::{self, WebhookEvent};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HostState {
+    #[default]
+    Idle,
+    Hosting,
+    /// Still running, but sending viewers nothing and letting no one join.
+    Paused,
+    Ended,
+}
+
+#[derive(Clone, Copy)]
+pub enum HostEvent {
+    Start,
+    Pause,
+    Resume,
+    Stop,
+}
+
+impl HostState {
+    fn next(self, event: HostEvent) -> Option<Self> {
+        match (self, event) {
+            (Self::Idle | Self::Ended, HostEvent::Start) => Some(Self::Hosting),
+            (Self::Hosting, HostEvent::Pause) => Some(Self::Paused),
+            (Self::Paused, HostEvent::Resume) => Some(Self::Hosting),
+            (Self::Hosting | Self::Paused, HostEvent::Stop) => Some(Self::Ended),
+            _ => None,
+        }
+    }
+
+    fn error(self, event: HostEvent) -> String {
+        let verb = match event {
+            HostEvent::Start => "start",
+            HostEvent::Pause => "pause",
+            HostEvent::Resume => "resume",
+            HostEvent::Stop => "stop",
+        };
+        let state = match self {
+            Self::Idle => "has not started",
+            Self::Hosting => "is running",
+            Self::Paused => "is paused",
+            Self::Ended => "has ended",
+        };
+        format!("Cannot {} the session: it {}", verb, state)
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteState {
+    #[default]
+    Idle,
+    Joining,
+    Connected,
+    /// Lost the host and retrying with backoff.
+    Reconnecting,
+    Left,
+}
+
+#[derive(Clone, Copy)]
+pub enum RemoteEvent {
+    Joined,
+    Lost,
+    Leave,
+}
+
+impl RemoteState {
+    fn next(self, event: RemoteEvent) -> Option<Self> {
+        match (self, event) {
+            (Self::Joining | Self::Reconnecting, RemoteEvent::Joined) => Some(Self::Connected),
+            (Self::Connected | Self::Reconnecting, RemoteEvent::Lost) => Some(Self::Reconnecting),
+            (Self::Joining | Self::Connected | Self::Reconnecting, RemoteEvent::Leave) => Some(Self::Left),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Remote {
+    state: RemoteState,
+    /// Bumped on every join and leave, so a connection that was replaced cannot move the state.
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct Lifecycle {
+    host: Mutex<HostState>,
+    remote: Mutex<Remote>,
+}
+
+/// Sent as `session-state-changed`, and returned by `get_session_state`.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionState {
+    pub host: HostState,
+    pub remote: RemoteState,
+}
+
+impl Lifecycle {
+    pub fn snapshot(&self) -> SessionState {
+        SessionState {
+            host: *self.host.lock().unwrap(),
+            remote: self.remote.lock().unwrap().state,
+        }
+    }
+}
+
+fn changed(app: &AppHandle) {
+    let state = app.state::<Lifecycle>().snapshot();
+    if let Err(e) = app.emit("session-state-changed", state) {
+        log::warn!("Failed to emit session-state-changed: {}", e);
+    }
+}
+
+/// Move the host session through `event`, running `apply` to do the work. The state only changes
+/// if `apply` succeeds, and no other host transition can run meanwhile.
+pub fn host<R>(app: &AppHandle, event: HostEvent, apply: impl FnOnce() -> Result<R, String>) -> Result<R, String> {
+    let lifecycle = app.state::<Lifecycle>();
+    let mut state = lifecycle.host.lock().unwrap();
+    let next = state.next(event).ok_or_else(|| state.error(event))?;
+    let result = apply()?;
+    *state = next;
+    drop(state);
+    timetrack::host_changed(app, next);
+    match event {
+        HostEvent::Start => {
+            bandwidth::reset(app);
+            webhooks::notify(app, WebhookEvent::SessionStarted, json!({}));
+            status_sync::session_changed(app, true);
+        }
+        HostEvent::Stop => {
+            webhooks::notify(app, WebhookEvent::SessionEnded, json!({}));
+            status_sync::session_changed(app, false);
+            receipts::clear(app);
+        }
+        HostEvent::Pause | HostEvent::Resume => {}
+    }
+    mqtt::publish_status(app);
+    discovery::update(app);
+    changed(app);
+    Ok(result)
+}
+
+/// Start joining a remote session, replacing any current one. Returns the generation the new
+/// connection reports its progress under.
+pub fn join(app: &AppHandle) -> u64 {
+    let lifecycle = app.state::<Lifecycle>();
+    let mut remote = lifecycle.remote.lock().unwrap();
+    remote.generation += 1;
+    remote.state = RemoteState::Joining;
+    let generation = remote.generation;
+    drop(remote);
+    changed(app);
+    generation
+}
+
+/// Report a connection's progress. Ignored once the connection has been replaced or left.
+pub fn remote(app: &AppHandle, generation: u64, event: RemoteEvent) {
+    let lifecycle = app.state::<Lifecycle>();
+    let mut remote = lifecycle.remote.lock().unwrap();
+    if remote.generation != generation {
+        return;
+    }
+    let Some(next) = remote.state.next(event) else {
+        return;
+    };
+    let moved = next != remote.state;
+    remote.state = next;
+    drop(remote);
+    if moved {
+        if next == RemoteState::Reconnecting {
+            cues::sound(app, AudioCue::ConnectionLost);
+        }
+        changed(app);
+    }
+}
+
+/// Leave the remote session, if any. Whatever its connection reports afterwards is ignored.
+pub fn leave(app: &AppHandle) -> bool {
+    let lifecycle = app.state::<Lifecycle>();
+    let mut remote = lifecycle.remote.lock().unwrap();
+    remote.generation += 1;
+    let Some(next) = remote.state.next(RemoteEvent::Leave) else {
+        return false;
+    };
+    remote.state = next;
+    drop(remote);
+    changed(app);
+    true
+}
+
+#[tauri::command]
+pub fn get_session_state(lifecycle: State<'_, Lifecycle>) -> SessionState {
+    lifecycle.snapshot()
+}
+
+/// Stop sending viewers anything and turn away joins, without ending the session.
+#[tauri::command]
+pub fn pause_session(app: AppHandle) -> Result<(), String> {
+    host(&app, HostEvent::Pause, || {
+        app.state::<Arc<SharingHub>>().set_paused(true);
+        Ok(())
+    })
+}
+
+/// Pick up where `pause_session` left off. Viewers are resent each document.
+#[tauri::command]
+pub fn resume_session(app: AppHandle) -> Result<(), String> {
+    host(&app, HostEvent::Resume, || {
+        app.state::<Arc<SharingHub>>().set_paused(false);
+        Ok(())
+    })
+}
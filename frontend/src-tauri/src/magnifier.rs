@@ -0,0 +1,106 @@
+//! The host's magnifier: a focus rectangle on a shared document that viewers on small screens
+//! zoom to, and that exports enlarge instead of showing the whole window.
+//!
+//! The region is kept here, broadcast as [`Message::Magnifier`] whenever the host moves it, and
+//! sent to late joiners along with the document snapshots.
+
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::documents::DocumentStore;
+use crate::export::Viewport;
+use crate::protocol::{Capability, Message};
+use crate::session;
+use crate::sharing::{self, SharingHub};
+
+/// Largest region the host can select, in characters.
+const MAX_ROWS: usize = 200;
+const MAX_COLUMNS: usize = 400;
+/// Exports enlarge the region at most this many times.
+const MAX_ZOOM: f64 = 4.0;
+
+/// A rectangle of a document in lines and display columns (tabs expanded), from zero.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusRegion {
+    pub document_id: String,
+    pub top: usize,
+    pub left: usize,
+    pub rows: usize,
+    pub columns: usize,
+}
+
+impl FocusRegion {
+    /// A window on exactly this region, which does not scroll after the caret.
+    pub fn viewport(&self) -> Viewport {
+        Viewport::fixed(self.rows, self.columns, self.top, self.left)
+    }
+
+    /// How many times larger the region can be drawn in an export sized for `rows` by `columns`.
+    pub fn zoom(&self, rows: usize, columns: usize) -> f64 {
+        let fit = (rows as f64 / self.rows as f64).min(columns as f64 / self.columns as f64);
+        fit.clamp(1.0, MAX_ZOOM)
+    }
+}
+
+#[derive(Default)]
+pub struct Magnifier {
+    region: Mutex<Option<FocusRegion>>,
+}
+
+impl Magnifier {
+    /// The focus region, if the host is magnifying part of `doc_id`.
+    pub fn region_for(&self, doc_id: &str) -> Option<FocusRegion> {
+        self.region
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|region| region.document_id == doc_id)
+    }
+}
+
+/// Send the current region to a viewer that just joined, so it does not wait for the next move.
+pub fn catch_up(app: &AppHandle, viewer_id: &str) {
+    let Some(region) = app.state::<Magnifier>().region.lock().unwrap().clone() else {
+        return;
+    };
+    match sharing::encode(&Message::Magnifier { region: Some(region) }) {
+        Ok(frame) => {
+            app.state::<Arc<SharingHub>>().send_to(viewer_id, Capability::Magnifier, frame);
+        }
+        Err(e) => log::warn!("{}", e),
+    }
+}
+
+#[tauri::command]
+pub fn get_magnifier(magnifier: State<'_, Magnifier>) -> Option<FocusRegion> {
+    magnifier.region.lock().unwrap().clone()
+}
+
+/// Move the magnifier to `region`, or put it away with `None`, and tell every viewer.
+#[tauri::command]
+pub fn set_magnifier(
+    app: AppHandle,
+    magnifier: State<'_, Magnifier>,
+    store: State<'_, DocumentStore>,
+    region: Option<FocusRegion>,
+) -> Result<(), String> {
+    if let Some(region) = &region {
+        if !(1..=MAX_ROWS).contains(&region.rows) || !(1..=MAX_COLUMNS).contains(&region.columns) {
+            return Err(format!(
+                "The magnified region must be 1 to {} lines by 1 to {} columns",
+                MAX_ROWS, MAX_COLUMNS
+            ));
+        }
+        store.with(&region.document_id, |_| ())?;
+    }
+    let mut current = magnifier.region.lock().unwrap();
+    if *current == region {
+        return Ok(());
+    }
+    current.clone_from(&region);
+    drop(current);
+    session::broadcast(&app, &Message::Magnifier { region })
+}
@@ -0,0 +1,302 @@
+//! Minimaps of shared documents, drawn once on the host instead of by every viewer.
+//!
+//! Each line is reduced to a row of token classes, one pixel per character up to `MAX_WIDTH`,
+//! and the rows are cached with the document's timeline position. When the document changes,
+//! only the lines touched by the new patches are highlighted again, continuing downwards while
+//! lines come out differently (opening a comment recolours the lines after it). Viewers get the
+//! rows folded into a bitmap of at most `MAX_HEIGHT` rows at four bits per pixel.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use tauri::ipc::Response;
+use tauri::State;
+
+use crate::binary;
+use crate::documents::DocumentStore;
+use crate::history::ReplayStep;
+use crate::syntax::{self, HighlightClass};
+
+const MAX_WIDTH: usize = 120;
+const MAX_HEIGHT: usize = 2048;
+/// Lines highlighted at a time when a change spills past the edited lines.
+const SPILL_CHUNK: usize = 64;
+
+// Pixel values; 2 and up are highlight classes.
+const BLANK: u8 = 0;
+const TEXT: u8 = 1;
+
+fn class_code(class: HighlightClass) -> u8 {
+    match class {
+        HighlightClass::Keyword => 2,
+        HighlightClass::String => 3,
+        HighlightClass::Number => 4,
+        HighlightClass::Comment => 5,
+        HighlightClass::Type => 6,
+        HighlightClass::Function => 7,
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+struct Row {
+    pixels: Vec<u8>,
+    /// Class of the span the line break is inside, if any, so a change that carries over into
+    /// the next line is noticed even when this line looks the same.
+    carry: u8,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinimapHeader {
+    pub document_id: String,
+    pub seq: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Document lines folded into each bitmap row.
+    pub lines_per_row: usize,
+}
+
+#[derive(Serialize)]
+pub struct MinimapBitmap {
+    #[serde(flatten)]
+    pub header: MinimapHeader,
+    /// Base64 of `height` rows of `width` pixels at four bits each, high nibble first, every row
+    /// padded to a whole byte. 0 is background, 1 plain text, and 2 to 7 keyword, string, number,
+    /// comment, type and function.
+    pub data: String,
+}
+
+struct Cached {
+    seq: u64,
+    language: Option<String>,
+    tab_width: usize,
+    rows: Vec<Row>,
+    header: MinimapHeader,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct Minimaps {
+    cache: Mutex<HashMap<String, Cached>>,
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Rows for `lines` of `text`, given the highlight spans overlapping them in document order.
+fn render_rows(
+    text: &str,
+    starts: &[usize],
+    lines: Range<usize>,
+    spans: &[(Range<usize>, HighlightClass)],
+    tab_width: usize,
+) -> Vec<Row> {
+    let mut spans = spans.iter().peekable();
+    // Only ever asked about increasing offsets.
+    let mut class_at = |byte: usize| {
+        while spans.next_if(|(range, _)| range.end <= byte).is_some() {}
+        spans
+            .peek()
+            .filter(|(range, _)| range.start <= byte)
+            .map(|(_, class)| *class)
+    };
+    lines
+        .map(|line| {
+            let start = starts[line];
+            let end = starts.get(line + 1).map_or(text.len(), |next| next - 1);
+            let mut pixels = Vec::new();
+            let mut column = 0;
+            for (i, c) in text[start..end].char_indices() {
+                if column >= MAX_WIDTH {
+                    break;
+                }
+                let width = if c == '\t' { tab_width - column % tab_width } else { 1 };
+                let pixel = if c.is_whitespace() {
+                    BLANK
+                } else {
+                    class_at(start + i).map_or(TEXT, class_code)
+                };
+                pixels.extend(std::iter::repeat(pixel).take(width.min(MAX_WIDTH - column)));
+                column += width;
+            }
+            while pixels.last() == Some(&BLANK) {
+                pixels.pop();
+            }
+            let carry = if end < text.len() {
+                class_at(end).map_or(BLANK, class_code)
+            } else {
+                BLANK
+            };
+            Row { pixels, carry }
+        })
+        .collect()
+}
+
+/// Replace the rows `steps` touched, starting from the rows of `base`, and render them again
+/// along with any lines below that changed colour. `None` if the rows do not match `base`.
+fn patch_rows(
+    mut rows: Vec<Row>,
+    mut text: String,
+    steps: &[ReplayStep],
+    render: impl Fn(Range<usize>) -> Vec<Row>,
+) -> Option<Vec<Row>> {
+    let mut dirty = vec![false; rows.len()];
+    for step in steps {
+        let first = text.get(..step.start)?.matches('\n').count();
+        let removed = text.get(step.start..step.start + step.delete_len)?.matches('\n').count() + 1;
+        let added = step.insert.matches('\n').count() + 1;
+        if first + removed > rows.len() {
+            return None;
+        }
+        rows.splice(first..first + removed, std::iter::repeat(Row::default()).take(added));
+        dirty.splice(first..first + removed, std::iter::repeat(true).take(added));
+        step.apply(&mut text);
+    }
+
+    let mut line = 0;
+    while line < rows.len() {
+        if !dirty[line] {
+            line += 1;
+            continue;
+        }
+        let mut end = line;
+        while end < rows.len() && dirty[end] {
+            end += 1;
+        }
+        rows.splice(line..end, render(line..end));
+        // Stop at the first untouched line that comes out as it was; the lines after it can
+        // only have changed if it had.
+        'spill: while end < rows.len() {
+            let chunk = end..(end + SPILL_CHUNK).min(rows.len());
+            for (offset, row) in render(chunk.clone()).into_iter().enumerate() {
+                let at = chunk.start + offset;
+                if !dirty[at] && rows[at] == row {
+                    break 'spill;
+                }
+                rows[at] = row;
+                dirty[at] = false;
+            }
+            end = chunk.end;
+        }
+        line = end;
+    }
+    Some(rows)
+}
+
+fn compose(doc_id: &str, seq: u64, rows: &[Row]) -> (MinimapHeader, Vec<u8>) {
+    let width = rows.iter().map(|row| row.pixels.len()).max().unwrap_or(0);
+    let lines_per_row = rows.len().div_ceil(MAX_HEIGHT).max(1);
+    let height = rows.len().div_ceil(lines_per_row);
+    let stride = width.div_ceil(2);
+    let mut data = vec![0u8; stride * height];
+    for (line, row) in rows.iter().enumerate() {
+        let out = &mut data[line / lines_per_row * stride..][..stride];
+        for (x, &pixel) in row.pixels.iter().enumerate() {
+            let shift = if x % 2 == 0 { 4 } else { 0 };
+            // Where lines are folded together, the first one drawn at a pixel keeps it.
+            if (out[x / 2] >> shift) & 0xf == BLANK {
+                out[x / 2] |= pixel << shift;
+            }
+        }
+    }
+    let header = MinimapHeader {
+        document_id: doc_id.to_string(),
+        seq,
+        width,
+        height,
+        lines_per_row,
+    };
+    (header, data)
+}
+
+impl Minimaps {
+    pub fn bitmap(&self, store: &DocumentStore, doc_id: &str) -> Result<MinimapBitmap, String> {
+        let (header, data) = self.pixels(store, doc_id)?;
+        Ok(MinimapBitmap {
+            header,
+            data: STANDARD.encode(data),
+        })
+    }
+
+    /// The document's current minimap and its packed pixels, brought up to date from the cached
+    /// one where possible.
+    pub fn pixels(&self, store: &DocumentStore, doc_id: &str) -> Result<(MinimapHeader, Vec<u8>), String> {
+        let mut cache = self.cache.lock().unwrap();
+        let cached = cache.remove(doc_id);
+        let (seq, language, tab_width, text, replay) = store.with(doc_id, |doc| {
+            let seq = doc.timeline.seq();
+            let tab_width = (doc.profile.tab_width as usize).max(1);
+            let replay = cached
+                .as_ref()
+                .filter(|c| c.seq != seq && c.language == doc.language && c.tab_width == tab_width)
+                .and_then(|c| doc.timeline.replay(c.seq, seq).ok());
+            (seq, doc.language.clone(), tab_width, doc.text.clone(), replay)
+        })?;
+        if let Some(cached) = cached.filter(|c| c.seq == seq && c.language == language && c.tab_width == tab_width) {
+            let pixels = (cached.header.clone(), cached.data.clone());
+            cache.insert(doc_id.to_string(), cached);
+            return Ok(pixels);
+        }
+
+        let starts = line_starts(&text);
+        let tree = language
+            .as_deref()
+            .and_then(|language| syntax::parse(language, &text).ok().map(|tree| (language, tree)));
+        let render = |lines: Range<usize>| {
+            let bytes = starts[lines.start]..starts.get(lines.end).copied().unwrap_or(text.len());
+            let spans = tree
+                .as_ref()
+                .map(|(language, tree)| syntax::highlight_bytes(language, tree, bytes))
+                .unwrap_or_default();
+            render_rows(&text, &starts, lines, &spans, tab_width)
+        };
+        let rows = cached
+            .zip(replay)
+            .and_then(|(cached, (base, steps))| patch_rows(cached.rows, base, &steps, &render))
+            .filter(|rows| rows.len() == starts.len())
+            .unwrap_or_else(|| render(0..starts.len()));
+
+        let (header, data) = compose(doc_id, seq, &rows);
+        let entry = Cached {
+            seq,
+            language,
+            tab_width,
+            rows,
+            header: header.clone(),
+            data: data.clone(),
+        };
+        cache.insert(doc_id.to_string(), entry);
+        Ok((header, data))
+    }
+
+    pub fn forget(&self, doc_id: &str) {
+        self.cache.lock().unwrap().remove(doc_id);
+    }
+}
+
+#[tauri::command]
+pub fn get_minimap(
+    store: State<'_, DocumentStore>,
+    minimaps: State<'_, Minimaps>,
+    doc_id: String,
+) -> Result<MinimapBitmap, String> {
+    minimaps.bitmap(&store, &doc_id)
+}
+
+/// The minimap as a binary frame: a [`MinimapHeader`] and the packed pixels, without base64.
+#[tauri::command]
+pub fn get_minimap_binary(
+    store: State<'_, DocumentStore>,
+    minimaps: State<'_, Minimaps>,
+    doc_id: String,
+) -> Result<Response, String> {
+    let (header, data) = minimaps.pixels(&store, &doc_id)?;
+    binary::frame(&header, &data)
+}
@@ -0,0 +1,281 @@
+//! Publishing stealth and session status to an MQTT broker, for home automation: an "on air" light
+//! that turns on while a session is live, say.
+//!
+//! When enabled, a retained JSON status is published to the configured topic (`sharecode/status`
+//! by default) on connecting and whenever capture protection or the hosted session changes. The
+//! broker holds a retained last will saying the app is offline, which it publishes if ShareCode
+//! crashes or loses the connection. On quitting the app publishes the same itself, since a clean
+//! disconnect discards the will.
+//!
+//! The broker address lives in `mqtt.json`; the password goes in the OS keychain. TLS uses the
+//! system's root certificates. A dropped connection is retried every `RECONNECT_DELAY`.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS, Transport};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::lifecycle::{HostState, Lifecycle};
+use crate::storage::{self, new_id, now_secs};
+
+const CONFIG_FILE: &str = "mqtt.json";
+const KEYCHAIN_SERVICE: &str = "sharecode-mqtt";
+const KEYCHAIN_ACCOUNT: &str = "broker";
+#[cfg(desktop)]
+const MAIN_WINDOW: &str = "main";
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Requests queued for the connection thread before publishing starts failing.
+const QUEUE_CAPACITY: usize = 16;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub username: Option<String>,
+    pub topic: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            enabled: false,
+            host: String::new(),
+            port: 8883,
+            tls: true,
+            username: None,
+            topic: "sharecode/status".to_string(),
+        }
+    }
+}
+
+/// What is published to the topic.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    online: bool,
+    /// The main window is hidden from screen capture.
+    stealth: bool,
+    session: HostState,
+    updated_at: u64,
+}
+
+struct Connection {
+    client: Client,
+    topic: String,
+}
+
+#[derive(Default)]
+pub struct Mqtt {
+    lock: Mutex<()>,
+    connection: Mutex<Option<Connection>>,
+    /// Bumped on every reconnect, so the thread of a replaced connection knows to stop.
+    generation: AtomicU64,
+    connected: AtomicBool,
+}
+
+impl Mqtt {
+    fn config(&self, app: &AppHandle) -> Result<MqttConfig, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, CONFIG_FILE)
+    }
+
+    fn save_config(&self, app: &AppHandle, config: &MqttConfig) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::save_json(app, CONFIG_FILE, config)
+    }
+}
+
+fn password() -> Option<String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .and_then(|entry| entry.get_password())
+        .ok()
+}
+
+fn set_password(password: &str) -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+        .map_err(|e| format!("Failed to open the keychain: {}", e))?;
+    let stored = if password.is_empty() {
+        match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        }
+    } else {
+        entry.set_password(password)
+    };
+    stored.map_err(|e| format!("Failed to store the MQTT password: {}", e))
+}
+
+#[cfg(desktop)]
+fn stealth(app: &AppHandle) -> bool {
+    app.get_webview_window(MAIN_WINDOW)
+        .and_then(|window| crate::read_capture_protection(&window).ok())
+        .is_some_and(|p| !matches!(p, crate::CaptureProtection::Off))
+}
+
+#[cfg(not(desktop))]
+fn stealth(_app: &AppHandle) -> bool {
+    false
+}
+
+fn status(app: &AppHandle, online: bool) -> String {
+    let status = Status {
+        online,
+        stealth: online && stealth(app),
+        session: if online {
+            app.state::<Lifecycle>().snapshot().host
+        } else {
+            HostState::Idle
+        },
+        updated_at: now_secs(),
+    };
+    serde_json::to_string(&status).unwrap_or_default()
+}
+
+/// Publish the current status, if connected. Called whenever stealth or the session changes.
+pub fn publish_status(app: &AppHandle) {
+    let Some(mqtt) = app.try_state::<Mqtt>() else {
+        return;
+    };
+    let connection = mqtt.connection.lock().unwrap();
+    let Some(connection) = connection.as_ref() else {
+        return;
+    };
+    // Never blocks: with the broker unreachable the queue fills and updates are dropped, which is
+    // fine as the status is republished on reconnecting.
+    if let Err(e) = connection
+        .client
+        .try_publish(&connection.topic, QoS::AtLeastOnce, true, status(app, true))
+    {
+        log::debug!("Skipped an MQTT status update: {}", e);
+    }
+}
+
+/// Publish that the app is offline and close the connection, if any.
+pub fn disconnect(app: &AppHandle) {
+    let mqtt = app.state::<Mqtt>();
+    mqtt.generation.fetch_add(1, Ordering::SeqCst);
+    mqtt.connected.store(false, Ordering::Relaxed);
+    let Some(connection) = mqtt.connection.lock().unwrap().take() else {
+        return;
+    };
+    // If either is lost to the process exiting, the broker sees the connection drop and publishes
+    // the will instead.
+    connection
+        .client
+        .try_publish(&connection.topic, QoS::AtLeastOnce, true, status(app, false))
+        .ok();
+    connection.client.try_disconnect().ok();
+}
+
+/// (Re)connect with the saved configuration, or just disconnect if publishing is off.
+pub fn connect(app: &AppHandle) -> Result<(), String> {
+    disconnect(app);
+    let mqtt = app.state::<Mqtt>();
+    let config = mqtt.config(app)?;
+    if !config.enabled || config.host.is_empty() {
+        return Ok(());
+    }
+
+    let mut options = MqttOptions::new(format!("sharecode-{}", new_id()), &config.host, config.port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_last_will(LastWill::new(&config.topic, status(app, false), QoS::AtLeastOnce, true));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, password().unwrap_or_default());
+    }
+    if config.tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+
+    let (client, mut connection) = Client::new(options, QUEUE_CAPACITY);
+    let generation = mqtt.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    *mqtt.connection.lock().unwrap() = Some(Connection {
+        client,
+        topic: config.topic.clone(),
+    });
+
+    let app = app.clone();
+    thread::spawn(move || {
+        let mqtt = app.state::<Mqtt>();
+        for notification in connection.iter() {
+            if mqtt.generation.load(Ordering::SeqCst) != generation {
+                break;
+            }
+            match notification {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    log::info!("Connected to MQTT broker {}", config.host);
+                    mqtt.connected.store(true, Ordering::Relaxed);
+                    publish_status(&app);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if mqtt.connected.swap(false, Ordering::Relaxed) {
+                        log::warn!("Lost the MQTT broker connection: {}", e);
+                    } else {
+                        log::debug!("MQTT broker unreachable: {}", e);
+                    }
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Connect at startup, if configured. Failures are logged and retried by the connection thread.
+pub fn start(app: &AppHandle) {
+    if let Err(e) = connect(app) {
+        log::warn!("Failed to start MQTT status publishing: {}", e);
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MqttSettings {
+    pub config: MqttConfig,
+    pub has_password: bool,
+    pub connected: bool,
+}
+
+#[tauri::command]
+pub fn get_mqtt_settings(app: AppHandle, mqtt: State<'_, Mqtt>) -> Result<MqttSettings, String> {
+    Ok(MqttSettings {
+        config: mqtt.config(&app)?,
+        has_password: password().is_some(),
+        connected: mqtt.connected.load(Ordering::Relaxed),
+    })
+}
+
+/// Save the broker settings and reconnect. A `password` of `None` keeps the stored one; an empty
+/// one removes it.
+#[tauri::command]
+pub fn set_mqtt_settings(
+    app: AppHandle,
+    mqtt: State<'_, Mqtt>,
+    config: MqttConfig,
+    password: Option<String>,
+) -> Result<(), String> {
+    if config.enabled && config.host.trim().is_empty() {
+        return Err("Enter the MQTT broker's host name".to_string());
+    }
+    if config.topic.is_empty() || config.topic.contains(['#', '+']) {
+        return Err("The MQTT topic can't be empty or contain wildcards".to_string());
+    }
+    if let Some(password) = password {
+        set_password(&password)?;
+    }
+    let config = MqttConfig {
+        host: config.host.trim().to_string(),
+        ..config
+    };
+    mqtt.save_config(&app, &config)?;
+    connect(&app)
+}
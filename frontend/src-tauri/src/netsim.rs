@@ -0,0 +1,118 @@
+//! Simulated network conditions for QA: latency, jitter, loss, bandwidth caps and dropped
+//! connections on the session transports, switched on with `set_network_impairment`.
+//!
+//! Everything the session server sends or receives goes through [`transmit`], as does what the
+//! viewer client receives from a remote host. Both ride on TCP, which retransmits lost packets,
+//! so loss shows up to the app the way it does on a real network: as stalls of a retransmission
+//! timeout each, not as missing frames. Dropped connections are simulated separately.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// The shortest retransmission timeout TCP stacks use.
+const MIN_RTO: Duration = Duration::from_millis(200);
+/// A packet lost this many times in a row is given up on, as the connection would be.
+const MAX_RETRANSMITS: u32 = 5;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Impairment {
+    /// One-way delay added to every frame and request.
+    pub latency_ms: u64,
+    /// The delay varies by up to this much either side of `latency_ms`.
+    pub jitter_ms: u64,
+    /// Chance, in percent, that a frame is lost and has to be retransmitted.
+    pub loss_percent: f64,
+    /// Throughput in each direction, shared by every connection; 0 for no cap.
+    pub bandwidth_kbps: u64,
+    /// Chance, in percent, that a frame breaks its connection instead of arriving.
+    pub disconnect_percent: f64,
+}
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+static IMPAIRMENT: Mutex<Option<Impairment>> = Mutex::new(None);
+/// When each direction's simulated link is next free, for the bandwidth cap.
+static LINK_FREE_AT: Mutex<[Option<Instant>; 2]> = Mutex::new([None, None]);
+
+fn chance(percent: f64) -> bool {
+    percent > 0.0 && rand::thread_rng().gen_bool((percent / 100.0).min(1.0))
+}
+
+/// Hold `bytes` in `direction` for as long as the impaired network would. An error means the
+/// connection they travel on should be dropped.
+pub fn transmit(direction: Direction, bytes: usize) -> Result<(), String> {
+    let Some(impairment) = *IMPAIRMENT.lock().unwrap() else {
+        return Ok(());
+    };
+    if chance(impairment.disconnect_percent) {
+        return Err("Connection dropped by the network simulator".to_string());
+    }
+
+    let latency = Duration::from_millis(impairment.latency_ms);
+    let jitter = impairment.jitter_ms as i64;
+    let offset = if jitter > 0 { rand::thread_rng().gen_range(-jitter..=jitter) } else { 0 };
+    let mut delay = Duration::from_millis((impairment.latency_ms as i64 + offset).max(0) as u64);
+    let mut attempts = 0;
+    while chance(impairment.loss_percent) {
+        attempts += 1;
+        if attempts > MAX_RETRANSMITS {
+            return Err("Connection timed out in the network simulator".to_string());
+        }
+        delay += (latency * 2).max(MIN_RTO);
+    }
+
+    // Frames queue behind each other on the capped link.
+    let mut sent_at = Instant::now();
+    if impairment.bandwidth_kbps > 0 {
+        let on_wire = Duration::from_secs_f64(bytes as f64 * 8.0 / (impairment.bandwidth_kbps as f64 * 1000.0));
+        let mut links = LINK_FREE_AT.lock().unwrap();
+        let free_at = &mut links[direction as usize];
+        let start = free_at.map_or(sent_at, |free| free.max(sent_at));
+        sent_at = start + on_wire;
+        *free_at = Some(sent_at);
+    }
+    let arrival = sent_at + delay;
+    if let Some(wait) = arrival.checked_duration_since(Instant::now()) {
+        thread::sleep(wait);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_network_impairment() -> Option<Impairment> {
+    *IMPAIRMENT.lock().unwrap()
+}
+
+/// Impair the session transports, or restore the real network with `None`.
+#[tauri::command]
+pub fn set_network_impairment(impairment: Option<Impairment>) -> Result<(), String> {
+    if let Some(impairment) = &impairment {
+        let percents = [impairment.loss_percent, impairment.disconnect_percent];
+        if percents.iter().any(|p| !(0.0..=100.0).contains(p)) {
+            return Err("Loss and disconnect rates must be between 0 and 100 percent".to_string());
+        }
+    }
+    *IMPAIRMENT.lock().unwrap() = impairment;
+    *LINK_FREE_AT.lock().unwrap() = [None, None];
+    match impairment {
+        Some(i) => log::warn!(
+            "Network simulator on: {} ms ± {} ms, {}% loss, {} kbps, {}% disconnects",
+            i.latency_ms,
+            i.jitter_ms,
+            i.loss_percent,
+            i.bandwidth_kbps,
+            i.disconnect_percent
+        ),
+        None => log::info!("Network simulator off"),
+    }
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Cleaning up pasted screenshots of code before text recognition.
+//!
+//! Screenshots of editors come with window chrome, dark themes, low contrast and the odd tilt of
+//! a phone photo of a monitor. Recognition does much better on dark text on a light background,
+//! cropped to the code and level, so each paste goes through: greyscale, inversion of dark themes,
+//! a contrast stretch, cropping to the text region, deskewing, and upscaling of small crops.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use tauri::ipc::{InvokeBody, Request, Response};
+use tauri::State;
+
+use crate::binary;
+use crate::imaging::{self, Image};
+use crate::workers::{Priority, WorkerPool};
+
+/// Base64 data URLs over this size are refused before decoding.
+const MAX_INPUT_CHARS: usize = 48 * 1024 * 1024;
+/// Fraction of pixels clipped at either end of the contrast stretch.
+const CLIP_FRACTION: f64 = 0.01;
+/// Rows or columns inked beyond this fraction are solid panels (sidebars, tab bars), not text.
+const PANEL_FRACTION: f64 = 0.6;
+const CROP_PADDING: usize = 8;
+/// Skew is searched within ±`MAX_SKEW` degrees in steps of `SKEW_STEP`.
+const MAX_SKEW: f32 = 5.0;
+const SKEW_STEP: f32 = 0.25;
+/// Ink pixels sampled for the skew search, to bound its cost on large screenshots.
+const SKEW_SAMPLES: usize = 100_000;
+/// Crops narrower than this are doubled; small glyphs recognize poorly.
+const UPSCALE_BELOW: usize = 1000;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedScreenshot {
+    /// Greyscale PNG as a `data:` URL, ready for the recognizer.
+    pub data_url: String,
+    #[serde(flatten)]
+    pub image: PreparedImage,
+}
+
+/// What was done to a screenshot; the header of the binary response.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedImage {
+    pub width: usize,
+    pub height: usize,
+    /// Text region within the original image.
+    pub crop: Region,
+    /// Tilt that was corrected, in degrees; positive means the text sloped downwards.
+    pub skew_degrees: f32,
+    /// Whether a dark theme was inverted.
+    pub inverted: bool,
+    pub scale: usize,
+}
+
+struct Gray {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Gray {
+    fn at(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Bilinear sample; outside the image is white background.
+    fn sample(&self, x: f32, y: f32) -> u8 {
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f32 || y > (self.height - 1) as f32 {
+            return 255;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let top = self.at(x0, y0) as f32 * (1.0 - fx) + self.at(x1, y0) as f32 * fx;
+        let bottom = self.at(x0, y1) as f32 * (1.0 - fx) + self.at(x1, y1) as f32 * fx;
+        (top * (1.0 - fy) + bottom * fy).round() as u8
+    }
+
+    fn histogram(&self) -> [u64; 256] {
+        let mut hist = [0u64; 256];
+        for &v in &self.pixels {
+            hist[v as usize] += 1;
+        }
+        hist
+    }
+}
+
+/// Luminance, with transparency composited over white.
+fn to_gray(image: &Image) -> Gray {
+    let pixels = image
+        .pixels
+        .chunks_exact(4)
+        .map(|p| {
+            let luma = (299 * p[0] as u32 + 587 * p[1] as u32 + 114 * p[2] as u32) / 1000;
+            let alpha = p[3] as u32;
+            ((luma * alpha + 255 * (255 - alpha)) / 255) as u8
+        })
+        .collect();
+    Gray {
+        width: image.width as usize,
+        height: image.height as usize,
+        pixels,
+    }
+}
+
+/// The first level, walking `levels`, past which more than `clip` pixels have been seen.
+fn clip_level(hist: &[u64; 256], clip: u64, levels: impl Iterator<Item = usize>) -> Option<usize> {
+    let mut seen = 0;
+    for level in levels {
+        seen += hist[level];
+        if seen > clip {
+            return Some(level);
+        }
+    }
+    None
+}
+
+/// Invert dark themes and stretch the contrast. Returns whether the image was inverted.
+fn normalize(gray: &mut Gray) -> bool {
+    // The border is almost entirely background, whatever the theme.
+    let (w, h) = (gray.width, gray.height);
+    let mut border: Vec<u8> = (0..w).flat_map(|x| [gray.at(x, 0), gray.at(x, h - 1)]).collect();
+    border.extend((0..h).flat_map(|y| [gray.at(0, y), gray.at(w - 1, y)]));
+    border.sort_unstable();
+    let inverted = border[border.len() / 2] < 128;
+    if inverted {
+        gray.pixels.iter_mut().for_each(|v| *v = 255 - *v);
+    }
+
+    let hist = gray.histogram();
+    let clip = (gray.pixels.len() as f64 * CLIP_FRACTION) as u64;
+    let low = clip_level(&hist, clip, 0..256).unwrap_or(0) as f32;
+    let high = clip_level(&hist, clip, (0..256).rev()).unwrap_or(255) as f32;
+    if high - low >= 16.0 {
+        gray.pixels
+            .iter_mut()
+            .for_each(|v| *v = ((*v as f32 - low) * 255.0 / (high - low)).clamp(0.0, 255.0) as u8);
+    }
+    inverted
+}
+
+/// Otsu's threshold: the level that best separates ink from background.
+fn threshold(gray: &Gray) -> u8 {
+    let hist = gray.histogram();
+    let total = gray.pixels.len() as f64;
+    let sum: f64 = hist.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+    let (mut weight, mut partial, mut best, mut best_level) = (0.0, 0.0, 0.0, 128);
+    for (level, &count) in hist.iter().enumerate() {
+        weight += count as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        partial += level as f64 * count as f64;
+        let (mean_low, mean_high) = (partial / weight, (sum - partial) / (total - weight));
+        let between = weight * (total - weight) * (mean_low - mean_high).powi(2);
+        if between > best {
+            best = between;
+            best_level = level as u8;
+        }
+    }
+    best_level
+}
+
+/// Bounding box of the rows and columns that look like text, padded a little.
+fn text_region(gray: &Gray, ink: u8) -> Region {
+    let (w, h) = (gray.width, gray.height);
+    let mut rows = vec![0usize; h];
+    let mut cols = vec![0usize; w];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for (x, col) in cols.iter_mut().enumerate() {
+            if gray.at(x, y) <= ink {
+                *row += 1;
+                *col += 1;
+            }
+        }
+    }
+    let texty = |count: usize, extent: usize| count >= 2 && (count as f64) <= extent as f64 * PANEL_FRACTION;
+    let span = |counts: &[usize], extent: usize| {
+        let first = counts.iter().position(|&c| texty(c, extent))?;
+        let last = counts.iter().rposition(|&c| texty(c, extent))?;
+        Some((first, last))
+    };
+    match (span(&rows, w), span(&cols, h)) {
+        (Some((top, bottom)), Some((left, right))) => {
+            let (x, y) = (left.saturating_sub(CROP_PADDING), top.saturating_sub(CROP_PADDING));
+            Region {
+                x,
+                y,
+                width: (right + CROP_PADDING + 1).min(w) - x,
+                height: (bottom + CROP_PADDING + 1).min(h) - y,
+            }
+        }
+        _ => Region {
+            x: 0,
+            y: 0,
+            width: w,
+            height: h,
+        },
+    }
+}
+
+fn crop(gray: &Gray, region: Region) -> Gray {
+    let pixels = (region.y..region.y + region.height)
+        .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+        .map(|(x, y)| gray.at(x, y))
+        .collect();
+    Gray {
+        width: region.width,
+        height: region.height,
+        pixels,
+    }
+}
+
+/// The angle at which text rows line up best: projecting ink along that slope gives the sharpest
+/// alternation between busy rows (text) and empty ones (leading).
+fn estimate_skew(gray: &Gray, ink: u8) -> f32 {
+    let points: Vec<(f32, f32)> = (0..gray.height)
+        .flat_map(|y| (0..gray.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| gray.at(x, y) <= ink)
+        .map(|(x, y)| (x as f32, y as f32))
+        .collect();
+    let stride = points.len().div_ceil(SKEW_SAMPLES).max(1);
+    let margin = gray.width as f32 * MAX_SKEW.to_radians().tan();
+    let bins = gray.height + 2 * margin.ceil() as usize + 2;
+
+    let steps = (MAX_SKEW / SKEW_STEP) as i32;
+    let (mut best, mut best_score) = (0.0f32, f64::MIN);
+    for step in -steps..=steps {
+        let angle = step as f32 * SKEW_STEP;
+        let slope = angle.to_radians().tan();
+        let mut profile = vec![0u32; bins];
+        for &(x, y) in points.iter().step_by(stride) {
+            let row = (y - x * slope + margin).round().max(0.0) as usize;
+            profile[row.min(bins - 1)] += 1;
+        }
+        let score: f64 = profile.windows(2).map(|p| (p[1] as f64 - p[0] as f64).powi(2)).sum();
+        // Prefer no rotation on ties; every resample costs some sharpness.
+        if score > best_score || (score == best_score && angle.abs() < best.abs()) {
+            best = angle;
+            best_score = score;
+        }
+    }
+    best
+}
+
+/// Rotate by `-degrees` about the centre, so text sloping by `degrees` comes out level.
+fn deskew(gray: &Gray, degrees: f32) -> Gray {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (gray.width as f32 / 2.0, gray.height as f32 / 2.0);
+    let mut pixels = Vec::with_capacity(gray.pixels.len());
+    for y in 0..gray.height {
+        for x in 0..gray.width {
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            pixels.push(gray.sample(cx + dx * cos - dy * sin, cy + dx * sin + dy * cos));
+        }
+    }
+    Gray {
+        width: gray.width,
+        height: gray.height,
+        pixels,
+    }
+}
+
+fn upscale(gray: &Gray, scale: usize) -> Gray {
+    let (width, height) = (gray.width * scale, gray.height * scale);
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let sx = (x as f32 + 0.5) / scale as f32 - 0.5;
+            let sy = (y as f32 + 0.5) / scale as f32 - 0.5;
+            pixels.push(gray.sample(sx.max(0.0), sy.max(0.0)));
+        }
+    }
+    Gray { width, height, pixels }
+}
+
+fn decode_input(image: &str) -> Result<Image, String> {
+    if image.len() > MAX_INPUT_CHARS {
+        return Err("Image is too large".to_string());
+    }
+    let encoded = match image.split_once(',') {
+        Some((header, data)) if header.starts_with("data:") => {
+            if !header.starts_with("data:image/png") {
+                return Err("Only PNG screenshots are supported".to_string());
+            }
+            data
+        }
+        _ => image,
+    };
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|_| "Image data is not valid base64".to_string())?;
+    imaging::decode_png(bytes.as_slice())
+}
+
+fn prepare(decoded: Image) -> Result<(PreparedImage, Vec<u8>), String> {
+    if decoded.width < 2 || decoded.height < 2 {
+        return Err("Image is too small".to_string());
+    }
+    let mut gray = to_gray(&decoded);
+    drop(decoded);
+    let inverted = normalize(&mut gray);
+    let ink = threshold(&gray);
+    let region = text_region(&gray, ink);
+    let cropped = crop(&gray, region);
+    let skew_degrees = estimate_skew(&cropped, ink);
+    let level = if skew_degrees == 0.0 {
+        cropped
+    } else {
+        deskew(&cropped, skew_degrees)
+    };
+    let scale = if level.width < UPSCALE_BELOW { 2 } else { 1 };
+    let output = if scale == 1 { level } else { upscale(&level, scale) };
+
+    let png = imaging::encode_png(
+        output.width as u32,
+        output.height as u32,
+        png::ColorType::Grayscale,
+        &output.pixels,
+    )?;
+    let image = PreparedImage {
+        width: output.width,
+        height: output.height,
+        crop: region,
+        skew_degrees,
+        inverted,
+        scale,
+    };
+    Ok((image, png))
+}
+
+/// Prepare a pasted screenshot (a PNG `data:` URL or bare base64) for text recognition.
+#[tauri::command]
+pub async fn prepare_code_screenshot(pool: State<'_, WorkerPool>, image: String) -> Result<PreparedScreenshot, String> {
+    pool.run(Priority::Normal, "Screenshot preparation", move |_| {
+        let (image, png) = prepare(decode_input(&image)?)?;
+        Ok(PreparedScreenshot {
+            data_url: format!("data:image/png;base64,{}", STANDARD.encode(png)),
+            image,
+        })
+    })
+    .await
+}
+
+/// Like `prepare_code_screenshot`, without base64 either way: the request body may be the PNG
+/// bytes themselves, and the response is a binary frame of a [`PreparedImage`] and the PNG.
+#[tauri::command]
+pub async fn prepare_code_screenshot_binary(pool: State<'_, WorkerPool>, request: Request<'_>) -> Result<Response, String> {
+    let input = request.body().clone();
+    let (image, png) = pool
+        .run(Priority::Normal, "Screenshot preparation", move |_| {
+            let decoded = match input {
+                InvokeBody::Raw(bytes) if bytes.len() > MAX_INPUT_CHARS => Err("Image is too large".to_string()),
+                InvokeBody::Raw(bytes) => imaging::decode_png(bytes.as_slice()),
+                InvokeBody::Json(value) => decode_input(value.as_str().ok_or("Expected PNG bytes or base64")?),
+            }?;
+            prepare(decoded)
+        })
+        .await?;
+    binary::frame(&image, &png)
+}
@@ -0,0 +1,209 @@
+//! Opening `.sharecode` files from the file manager, each in a read-only window of its own.
+//!
+//! The files arrive on the command line when the app starts (Windows and Linux), from a second
+//! launch that the single-instance plugin hands to the running app, or as `RunEvent::Opened` on
+//! macOS. Each is checked with `artifact::validate_file` before anything is loaded. A session
+//! snapshot opens straight away with its documents frozen; an offline bundle opens locked, and its
+//! window asks for the passphrase and calls `unlock_opened_file`. The window's documents are closed
+//! with it. Files that can't be opened are reported to the main window as `file-open-failed`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent};
+
+use crate::artifact;
+use crate::bundle::{self, BundleAnnotation, ImportedDocument};
+use crate::documents::DocumentStore;
+use crate::recents::{self, RecentKind};
+use crate::snapshot;
+use crate::storage::new_id;
+
+const FILE_EXTENSION: &str = "sharecode";
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenedFile {
+    pub path: String,
+    /// `sessionSnapshot` or `offlineBundle`, as `validate_file` names them.
+    pub kind: String,
+    /// An offline bundle whose passphrase has not been given yet.
+    pub locked: bool,
+    pub documents: Vec<ImportedDocument>,
+    pub annotations: Vec<BundleAnnotation>,
+}
+
+/// Opened files by the label of the window showing them.
+#[derive(Default)]
+pub struct OpenedFiles {
+    files: Mutex<HashMap<String, OpenedFile>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileOpenFailed {
+    path: String,
+    error: String,
+}
+
+fn freeze(store: &DocumentStore, documents: &mut [ImportedDocument]) -> Result<(), String> {
+    for imported in documents {
+        let id = imported.document.id.clone();
+        imported.document = store.with(&id, |doc| {
+            doc.frozen = true;
+            doc.info(&id)
+        })?;
+    }
+    Ok(())
+}
+
+fn close_documents(store: &DocumentStore, documents: &[ImportedDocument]) {
+    let mut docs = store.docs.lock().unwrap();
+    for imported in documents {
+        docs.remove(&imported.document.id);
+    }
+}
+
+fn open_window(app: &AppHandle, label: &str, path: &Path) -> Result<WebviewWindow, String> {
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+    let window = WebviewWindowBuilder::new(app, label, WebviewUrl::App(format!("index.html?opened={}", label).into()))
+        .title(format!("{} (read-only) - ShareCode", name))
+        .inner_size(1000.0, 700.0)
+        .min_inner_size(600.0, 400.0)
+        .build()
+        .map_err(|e| format!("Failed to open a window: {}", e))?;
+    let app = app.clone();
+    let label = label.to_string();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            if let Some(file) = app.state::<OpenedFiles>().files.lock().unwrap().remove(&label) {
+                close_documents(&app.state(), &file.documents);
+            }
+        }
+    });
+    Ok(window)
+}
+
+/// Check and load one file, and open it in a new window.
+pub fn open_file(app: &AppHandle, path: &Path) -> Result<(), String> {
+    let path_string = path.to_string_lossy().into_owned();
+    let report = artifact::validate_file(path_string.clone())?;
+    if !report.valid {
+        return Err(format!("The file is damaged: {}", report.problems.join("; ")));
+    }
+    let mut file = OpenedFile {
+        path: path_string.clone(),
+        kind: report.kind.clone(),
+        locked: false,
+        documents: Vec::new(),
+        annotations: Vec::new(),
+    };
+    match report.kind.as_str() {
+        "sessionSnapshot" => {
+            file.documents = snapshot::import_session_snapshot(app.clone(), app.state(), app.state(), path_string)?;
+            freeze(&app.state(), &mut file.documents)?;
+        }
+        "offlineBundle" => {
+            file.locked = true;
+            recents::note(app, path, RecentKind::Bundle);
+        }
+        _ => return Err("ShareCode can only open session snapshots and offline bundles".to_string()),
+    }
+
+    let label = format!("opened-{}", new_id());
+    let documents = file.documents.clone();
+    app.state::<OpenedFiles>().files.lock().unwrap().insert(label.clone(), file);
+    if let Err(e) = open_window(app, &label, path) {
+        app.state::<OpenedFiles>().files.lock().unwrap().remove(&label);
+        close_documents(&app.state(), &documents);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Open every file in `paths`, reporting the ones that fail.
+pub fn open_files(app: &AppHandle, paths: Vec<PathBuf>) {
+    for path in paths {
+        if let Err(error) = open_file(app, &path) {
+            log::warn!("Failed to open {}: {}", path.display(), error);
+            let failed = FileOpenFailed {
+                path: path.display().to_string(),
+                error,
+            };
+            if let Err(e) = app.emit_to("main", "file-open-failed", failed) {
+                log::warn!("Failed to emit file-open-failed: {}", e);
+            }
+        }
+    }
+}
+
+/// The `.sharecode` files among command-line arguments.
+pub fn files_in_args(args: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+    args.into_iter()
+        .skip(1)
+        .map(PathBuf::from)
+        .filter(|path| {
+            let ours = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(FILE_EXTENSION));
+            ours && path.is_file()
+        })
+        .collect()
+}
+
+/// A second launch of the app, handed over by the single-instance plugin. Its files open here;
+/// launched without any, it brings the main window forward instead.
+pub fn second_instance(app: &AppHandle, args: Vec<String>, cwd: String) {
+    let paths: Vec<PathBuf> = files_in_args(args.into_iter().map(|arg| {
+        // Relative to where the second instance was started, not to this one
+        let path = Path::new(&cwd).join(&arg);
+        if path.is_file() {
+            path.to_string_lossy().into_owned()
+        } else {
+            arg
+        }
+    }));
+    if paths.is_empty() {
+        if let Some(window) = app.get_webview_window("main") {
+            window.unminimize().ok();
+            window.show().ok();
+            window.set_focus().ok();
+        }
+        return;
+    }
+    open_files(app, paths);
+}
+
+/// The file shown in the calling window, if it was opened from one.
+#[tauri::command]
+pub fn get_opened_file(window: WebviewWindow, opened: State<'_, OpenedFiles>) -> Option<OpenedFile> {
+    opened.files.lock().unwrap().get(window.label()).cloned()
+}
+
+/// Decrypt the offline bundle shown in the calling window and load its documents, read-only.
+#[tauri::command]
+pub fn unlock_opened_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    opened: State<'_, OpenedFiles>,
+    passphrase: String,
+) -> Result<OpenedFile, String> {
+    let path = match opened.files.lock().unwrap().get(window.label()) {
+        Some(file) if file.locked => file.path.clone(),
+        Some(_) => return Err("The file is already open".to_string()),
+        None => return Err("No file was opened in this window".to_string()),
+    };
+    let mut imported = bundle::import_offline_bundle(app.clone(), app.state(), app.state(), path, passphrase)?;
+    freeze(&app.state(), &mut imported.documents)?;
+
+    let mut files = opened.files.lock().unwrap();
+    let Some(file) = files.get_mut(window.label()) else {
+        // Closed while decrypting.
+        close_documents(&app.state(), &imported.documents);
+        return Err("The window was closed".to_string());
+    };
+    file.locked = false;
+    file.documents = imported.documents;
+    file.annotations = imported.annotations;
+    Ok(file.clone())
+}
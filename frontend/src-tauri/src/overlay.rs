@@ -0,0 +1,135 @@
+//! Overlay mode: keep a window above everything else and on every workspace, so reference code
+//! stays in view while switching desktops.
+//!
+//! On macOS the window joins all Spaces, stays put during Mission Control and shows over
+//! full-screen apps. On Linux it is made sticky, which most window managers honour. Windows has no
+//! public API to pin a window to every virtual desktop, so there it is only kept topmost; Win+Tab
+//! and some full-screen apps can drop it from the topmost band, so a watchdog puts it back.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+/// How often overlay windows are checked to still be on top.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Labels of the windows in overlay mode.
+static OVERLAY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlayMode {
+    pub always_on_top: bool,
+    /// Shown on every virtual desktop or Space; always `false` on Windows.
+    pub all_workspaces: bool,
+}
+
+fn on_all_workspaces(window: &WebviewWindow) -> Result<bool, String> {
+    #[cfg(target_os = "macos")]
+    {
+        let ns_window = window.ns_window().map_err(|e| e.to_string())? as cocoa::base::id;
+        Ok(unsafe { crate::macos_impl::is_on_all_spaces(ns_window) })
+    }
+
+    // GTK has no getter for stickiness
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(cfg!(target_os = "linux") && OVERLAY.lock().unwrap().iter().any(|label| label == window.label()))
+    }
+}
+
+fn read(window: &WebviewWindow) -> Result<OverlayMode, String> {
+    Ok(OverlayMode {
+        always_on_top: window.is_always_on_top().map_err(|e| e.to_string())?,
+        all_workspaces: on_all_workspaces(window)?,
+    })
+}
+
+fn apply(window: &WebviewWindow, enabled: bool) -> Result<(), String> {
+    window.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+
+    #[cfg(target_os = "macos")]
+    {
+        let ns_window = window.ns_window().map_err(|e| e.to_string())? as cocoa::base::id;
+        unsafe { crate::macos_impl::set_all_spaces(ns_window, enabled) };
+    }
+
+    #[cfg(target_os = "linux")]
+    window.set_visible_on_all_workspaces(enabled).map_err(|e| e.to_string())?;
+
+    let mut overlay = OVERLAY.lock().unwrap();
+    overlay.retain(|label| label != window.label());
+    if enabled {
+        overlay.push(window.label().to_string());
+    }
+    Ok(())
+}
+
+/// Put overlay windows back on top if something pushed them down.
+fn raise(window: &WebviewWindow) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::Foundation::HWND;
+
+        let hwnd = HWND(window.hwnd().map_err(|e| e.to_string())?.0 as _);
+        unsafe {
+            if crate::windows_impl::is_topmost(hwnd) {
+                return Ok(());
+            }
+            log::warn!("Window {} lost its place on top; raising it again", window.label());
+            crate::windows_impl::set_topmost(hwnd)
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        if !window.is_always_on_top().map_err(|e| e.to_string())? {
+            log::warn!("Window {} lost its place on top; raising it again", window.label());
+            window.set_always_on_top(true).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// Start the watchdog. Called once from setup.
+pub fn install(app: &AppHandle) {
+    let app = app.clone();
+    thread::spawn(move || loop {
+        thread::sleep(WATCHDOG_INTERVAL);
+        let labels = OVERLAY.lock().unwrap().clone();
+        for label in labels {
+            let Some(window) = app.get_webview_window(&label) else {
+                continue;
+            };
+            // Window attributes belong to the main thread on macOS.
+            let target = window.clone();
+            let checked = window.run_on_main_thread(move || {
+                // Turned off in the meantime.
+                if !OVERLAY.lock().unwrap().contains(&label) {
+                    return;
+                }
+                if let Err(e) = raise(&target) {
+                    log::warn!("Failed to keep window {} on top: {}", label, e);
+                }
+            });
+            if let Err(e) = checked {
+                log::warn!("Failed to check overlay window: {}", e);
+            }
+        }
+    });
+}
+
+/// Keep the window above all others and on every workspace, or put it back to normal.
+#[tauri::command]
+pub fn set_overlay_mode(window: WebviewWindow, enabled: bool) -> Result<OverlayMode, String> {
+    apply(&window, enabled)?;
+    read(&window)
+}
+
+#[tauri::command]
+pub fn get_overlay_mode(window: WebviewWindow) -> Result<OverlayMode, String> {
+    read(&window)
+}
@@ -0,0 +1,273 @@
+//! Two-party sharing over a WebRTC data channel, for peers with no LAN in common and no relay.
+//!
+//! Signalling is left to the users: one side calls `create_offer` and sends the resulting
+//! description to the other by any means (chat, email, a QR code), who passes it to `accept_offer`
+//! and sends back the answer for `accept_answer`. ICE candidates are gathered before a
+//! description is returned, so each side sends exactly one message. STUN finds each side's public
+//! address; there is no TURN server, so two peers behind symmetric NATs cannot connect.
+//!
+//! When the channel opens each side announces its session key (see `e2ee`) and the agreed short
+//! authentication string is emitted as `p2p-key-agreed`. Once the users have compared it,
+//! `send_payload` seals text for the peer and what arrives is opened and emitted as `p2p-payload`;
+//! nothing is sent or accepted in the clear. Connection changes are emitted as `p2p-state-changed`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use webrtc::api::APIBuilder;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::ice_transport::ice_server::RTCIceServer;
+use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+
+use crate::e2ee::E2ee;
+use crate::storage::new_id;
+
+const CHANNEL_LABEL: &str = "sharecode";
+/// Frames on the channel are a key announcement or a sealed payload, both base64 after the prefix.
+const KEY_FRAME: &str = "key:";
+const SEALED_FRAME: &str = "sealed:";
+const STUN_SERVERS: [&str; 2] = ["stun:stun.l.google.com:19302", "stun:stun.cloudflare.com:3478"];
+/// Data channel messages beyond this are not reliably delivered by every WebRTC stack.
+const MAX_FRAME: usize = 64 * 1024;
+
+/// Set once the channel is created (offering side) or announced (answering side).
+type Channel = Arc<Mutex<Option<Arc<RTCDataChannel>>>>;
+
+struct Peer {
+    connection: Arc<RTCPeerConnection>,
+    channel: Channel,
+}
+
+#[derive(Default)]
+pub struct P2pState {
+    peers: Mutex<HashMap<String, Peer>>,
+}
+
+/// A session description to hand to the other side, with the id to refer to the connection by.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerOffer {
+    pub peer_id: String,
+    pub sdp: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerStateChanged {
+    peer_id: String,
+    /// `new`, `connecting`, `connected`, `disconnected`, `failed` or `closed`, as WebRTC names them;
+    /// `open` once the data channel can be sent on.
+    state: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyAgreed {
+    peer_id: String,
+    short_auth_string: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerPayload {
+    peer_id: String,
+    payload: String,
+}
+
+fn rtc(error: webrtc::Error) -> String {
+    format!("WebRTC error: {}", error)
+}
+
+fn state_changed(app: &AppHandle, peer_id: &str, state: String) {
+    let changed = PeerStateChanged {
+        peer_id: peer_id.to_string(),
+        state,
+    };
+    if let Err(e) = app.emit("p2p-state-changed", changed) {
+        log::warn!("Failed to emit p2p-state-changed: {}", e);
+    }
+}
+
+/// Handle one frame from the peer: take a key announcement, or open a sealed payload and emit it.
+fn receive(app: &AppHandle, peer_id: &str, frame: &str) -> Result<(), String> {
+    let e2ee = app.state::<E2ee>();
+    if let Some(public_key) = frame.strip_prefix(KEY_FRAME) {
+        let agreed = KeyAgreed {
+            peer_id: peer_id.to_string(),
+            short_auth_string: e2ee.establish(peer_id, public_key)?,
+        };
+        if let Err(e) = app.emit("p2p-key-agreed", agreed) {
+            log::warn!("Failed to emit p2p-key-agreed: {}", e);
+        }
+        return Ok(());
+    }
+    let sealed = frame
+        .strip_prefix(SEALED_FRAME)
+        .and_then(|sealed| STANDARD.decode(sealed).ok())
+        .ok_or("Dropped an unencrypted frame")?;
+    let payload = PeerPayload {
+        peer_id: peer_id.to_string(),
+        payload: String::from_utf8_lossy(&e2ee.open(peer_id, &sealed)?).into_owned(),
+    };
+    if let Err(e) = app.emit("p2p-payload", payload) {
+        log::warn!("Failed to emit p2p-payload: {}", e);
+    }
+    Ok(())
+}
+
+/// Announce our key when the channel opens and pass on what arrives on it.
+fn watch_channel(app: &AppHandle, peer_id: &str, channel: &Arc<RTCDataChannel>) {
+    // Weak, as the channel owns this handler
+    let (app_open, id_open, opened) = (app.clone(), peer_id.to_string(), Arc::downgrade(channel));
+    channel.on_open(Box::new(move || {
+        state_changed(&app_open, &id_open, "open".to_string());
+        let public_key = app_open.state::<E2ee>().public_key(&id_open);
+        Box::pin(async move {
+            if let Some(channel) = Weak::upgrade(&opened) {
+                if let Err(e) = channel.send_text(format!("{}{}", KEY_FRAME, public_key)).await {
+                    log::warn!("Failed to announce session key to {}: {}", id_open, e);
+                }
+            }
+        })
+    }));
+    let (app, peer_id) = (app.clone(), peer_id.to_string());
+    channel.on_message(Box::new(move |message: DataChannelMessage| {
+        if let Err(e) = receive(&app, &peer_id, &String::from_utf8_lossy(&message.data)) {
+            log::warn!("Ignored a frame from peer {}: {}", peer_id, e);
+        }
+        Box::pin(async {})
+    }));
+}
+
+async fn new_peer(app: &AppHandle, peer_id: &str) -> Result<Peer, String> {
+    let config = RTCConfiguration {
+        ice_servers: vec![RTCIceServer {
+            urls: STUN_SERVERS.iter().map(|url| url.to_string()).collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let connection = Arc::new(APIBuilder::new().build().new_peer_connection(config).await.map_err(rtc)?);
+    let channel: Channel = Arc::default();
+
+    let (app_state, id_state) = (app.clone(), peer_id.to_string());
+    connection.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+        state_changed(&app_state, &id_state, state.to_string());
+        Box::pin(async {})
+    }));
+    // The answering side learns of the channel the offer created.
+    let (app_channel, id_channel, announced) = (app.clone(), peer_id.to_string(), Arc::clone(&channel));
+    connection.on_data_channel(Box::new(move |data_channel: Arc<RTCDataChannel>| {
+        watch_channel(&app_channel, &id_channel, &data_channel);
+        *announced.lock().unwrap() = Some(data_channel);
+        Box::pin(async {})
+    }));
+    Ok(Peer { connection, channel })
+}
+
+/// Set `description` as ours and wait for ICE gathering, returning it with the candidates in.
+async fn describe(connection: &RTCPeerConnection, description: RTCSessionDescription) -> Result<String, String> {
+    let mut gathered = connection.gathering_complete_promise().await;
+    connection.set_local_description(description).await.map_err(rtc)?;
+    let _ = gathered.recv().await;
+    let local = connection.local_description().await.ok_or("No local description was set")?;
+    Ok(local.sdp)
+}
+
+fn peer(state: &P2pState, peer_id: &str) -> Result<(Arc<RTCPeerConnection>, Channel), String> {
+    let peers = state.peers.lock().unwrap();
+    let peer = peers.get(peer_id).ok_or_else(|| format!("Unknown peer {}", peer_id))?;
+    Ok((Arc::clone(&peer.connection), Arc::clone(&peer.channel)))
+}
+
+/// Start a connection, returning the offer to send to the other side.
+#[tauri::command]
+pub async fn create_offer(app: AppHandle, state: State<'_, P2pState>) -> Result<PeerOffer, String> {
+    let peer_id = new_id();
+    let peer = new_peer(&app, &peer_id).await?;
+    let channel = peer.connection.create_data_channel(CHANNEL_LABEL, None).await.map_err(rtc)?;
+    watch_channel(&app, &peer_id, &channel);
+    *peer.channel.lock().unwrap() = Some(channel);
+    let offer = peer.connection.create_offer(None).await.map_err(rtc)?;
+    let connection = Arc::clone(&peer.connection);
+    state.peers.lock().unwrap().insert(peer_id.clone(), peer);
+    let sdp = describe(&connection, offer).await?;
+    Ok(PeerOffer { peer_id, sdp })
+}
+
+/// Take the other side's offer, returning the answer to send back.
+#[tauri::command]
+pub async fn accept_offer(app: AppHandle, state: State<'_, P2pState>, sdp: String) -> Result<PeerOffer, String> {
+    let peer_id = new_id();
+    let peer = new_peer(&app, &peer_id).await?;
+    let offer = RTCSessionDescription::offer(sdp).map_err(rtc)?;
+    peer.connection.set_remote_description(offer).await.map_err(rtc)?;
+    let answer = peer.connection.create_answer(None).await.map_err(rtc)?;
+    let connection = Arc::clone(&peer.connection);
+    state.peers.lock().unwrap().insert(peer_id.clone(), peer);
+    let sdp = describe(&connection, answer).await?;
+    Ok(PeerOffer { peer_id, sdp })
+}
+
+/// Complete a connection started with `create_offer` with the other side's answer.
+#[tauri::command]
+pub async fn accept_answer(state: State<'_, P2pState>, peer_id: String, sdp: String) -> Result<(), String> {
+    let (connection, _) = peer(&state, &peer_id)?;
+    let answer = RTCSessionDescription::answer(sdp).map_err(rtc)?;
+    connection.set_remote_description(answer).await.map_err(rtc)
+}
+
+/// The peer's data channel, if it is open.
+fn open_channel(state: &P2pState, peer_id: &str) -> Result<Option<Arc<RTCDataChannel>>, String> {
+    let (_, channel) = peer(state, peer_id)?;
+    let channel = channel.lock().unwrap().clone();
+    Ok(channel.filter(|channel| channel.ready_state() == RTCDataChannelState::Open))
+}
+
+/// Send a freshly generated session key to the peer. Before the channel opens there is nothing to
+/// do: the key is announced when it does.
+pub async fn announce_key(app: &AppHandle, peer_id: &str, public_key: &str) -> Result<(), String> {
+    if let Some(channel) = open_channel(&app.state::<P2pState>(), peer_id)? {
+        channel.send_text(format!("{}{}", KEY_FRAME, public_key)).await.map_err(rtc)?;
+    }
+    Ok(())
+}
+
+/// Seal text for the peer and send it over the data channel, once it is `open` and the short
+/// authentication string has been verified.
+#[tauri::command]
+pub async fn send_payload(
+    state: State<'_, P2pState>,
+    e2ee: State<'_, E2ee>,
+    peer_id: String,
+    payload: String,
+) -> Result<(), String> {
+    let channel = open_channel(&state, &peer_id)?.ok_or("The connection is not open yet")?;
+    let sealed = e2ee.seal(&peer_id, payload.as_bytes())?;
+    let frame = format!("{}{}", SEALED_FRAME, STANDARD.encode(sealed));
+    // Base64 adds a third, so this is about 48 KiB of text
+    if frame.len() > MAX_FRAME {
+        return Err(format!("Payloads over {} KiB can't be sent to a peer", MAX_FRAME * 3 / 4 / 1024));
+    }
+    channel.send_text(frame).await.map_err(rtc)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn close_peer(state: State<'_, P2pState>, e2ee: State<'_, E2ee>, peer_id: String) -> Result<bool, String> {
+    e2ee.forget(&peer_id);
+    let Some(peer) = state.peers.lock().unwrap().remove(&peer_id) else {
+        return Ok(false);
+    };
+    peer.connection.close().await.map_err(rtc)?;
+    Ok(true)
+}
@@ -0,0 +1,162 @@
+//! Panic hide: take the app off screen in one call, and put it back as it was.
+//!
+//! Doing the steps from the frontend takes several round trips, long enough for a screen share
+//! to catch the window in between. Here they run back to back on the main thread, with capture
+//! protection first, so anything a capture sees before the window is gone is already protected.
+//! Each step goes through the same path as its own setting and is recorded in transparency
+//! reports. A step that fails does not stop the others.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::speech::{self, Speech};
+use crate::transparency::{self, PrivacyFeature};
+use crate::{tray, CaptureProtection};
+
+const MAIN_WINDOW: &str = "main";
+
+/// What panic hide changed, so restoring undoes only that.
+struct Saved {
+    /// Windows that were visible.
+    shown: Vec<String>,
+    /// Windows that were not protected from capture already.
+    unprotected: Vec<String>,
+    taskbar_was_hidden: bool,
+    speech_was_muted: bool,
+}
+
+#[derive(Default)]
+pub struct PanicState {
+    saved: Mutex<Option<Saved>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PanicOutcome {
+    /// Whether the app is now hidden.
+    pub hidden: bool,
+    /// Steps that could not be applied, e.g. capture protection where the OS has none.
+    pub errors: Vec<String>,
+}
+
+pub fn is_hidden(app: &AppHandle) -> bool {
+    app.state::<PanicState>().saved.lock().unwrap().is_some()
+}
+
+/// Hide every window, protect them from capture, leave the taskbar or dock and mute speech.
+pub fn hide(app: &AppHandle) -> PanicOutcome {
+    let state = app.state::<PanicState>();
+    let mut saved = state.saved.lock().unwrap();
+    if saved.is_some() {
+        return PanicOutcome {
+            hidden: true,
+            errors: Vec::new(),
+        };
+    }
+    let mut errors = Vec::new();
+    let windows = app.webview_windows();
+
+    let mut unprotected = Vec::new();
+    for (label, window) in &windows {
+        if crate::read_capture_protection(window).is_ok_and(|p| !matches!(p, CaptureProtection::Off)) {
+            continue;
+        }
+        match crate::protect_from_capture(window, true) {
+            Ok(_) => unprotected.push(label.clone()),
+            Err(e) => errors.push(e),
+        }
+    }
+    let mut shown = Vec::new();
+    for (label, window) in &windows {
+        if window.is_visible().unwrap_or(true) {
+            match window.hide() {
+                Ok(()) => shown.push(label.clone()),
+                Err(e) => errors.push(format!("Failed to hide window {}: {}", label, e)),
+            }
+        }
+    }
+    let taskbar_was_hidden = transparency::is_active(app, PrivacyFeature::TaskbarHidden);
+    if !taskbar_was_hidden {
+        if let Some(window) = windows.get(MAIN_WINDOW) {
+            if let Err(e) = crate::apply_taskbar_visibility(window, false) {
+                errors.push(e);
+            }
+        }
+    }
+    let speech_was_muted = speech::is_muted(app);
+    speech::set_speech_muted(app.state::<Speech>(), true);
+
+    *saved = Some(Saved {
+        shown,
+        unprotected,
+        taskbar_was_hidden,
+        speech_was_muted,
+    });
+    drop(saved);
+    finish(app, true, errors)
+}
+
+/// Undo `hide`, leaving alone whatever was already hidden, protected or muted before it.
+pub fn restore(app: &AppHandle) -> PanicOutcome {
+    let Some(saved) = app.state::<PanicState>().saved.lock().unwrap().take() else {
+        return PanicOutcome {
+            hidden: false,
+            errors: Vec::new(),
+        };
+    };
+    let mut errors = Vec::new();
+    let windows = app.webview_windows();
+
+    if !saved.taskbar_was_hidden {
+        if let Some(window) = windows.get(MAIN_WINDOW) {
+            if let Err(e) = crate::apply_taskbar_visibility(window, true) {
+                errors.push(e);
+            }
+        }
+    }
+    for label in &saved.shown {
+        if let Some(window) = windows.get(label) {
+            if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+                errors.push(format!("Failed to show window {}: {}", label, e));
+            }
+        }
+    }
+    // Only once the windows are back, so they are never visible in a capture while hidden.
+    for label in &saved.unprotected {
+        if let Some(window) = windows.get(label) {
+            if let Err(e) = crate::protect_from_capture(window, false) {
+                errors.push(e);
+            }
+        }
+    }
+    if !saved.speech_was_muted {
+        speech::set_speech_muted(app.state::<Speech>(), false);
+    }
+    finish(app, false, errors)
+}
+
+fn finish(app: &AppHandle, hidden: bool, errors: Vec<String>) -> PanicOutcome {
+    for e in &errors {
+        log::warn!("Panic {}: {}", if hidden { "hide" } else { "restore" }, e);
+    }
+    tray::refresh(app);
+    let outcome = PanicOutcome { hidden, errors };
+    // The frontend holds back its own notifications while hidden.
+    if let Err(e) = app.emit("panic-hide-changed", outcome.clone()) {
+        log::warn!("Failed to emit panic-hide-changed: {}", e);
+    }
+    outcome
+}
+
+/// Take the app off screen at once. Runs on the main thread, like every synchronous command.
+#[tauri::command]
+pub fn panic_hide(app: AppHandle) -> PanicOutcome {
+    hide(&app)
+}
+
+#[tauri::command]
+pub fn panic_restore(app: AppHandle) -> PanicOutcome {
+    restore(&app)
+}
@@ -0,0 +1,396 @@
+//! Pen annotations on the shared view: raw stylus samples with pressure and tilt go in, smooth
+//! variable-width strokes come out and are sent to the session.
+//!
+//! On macOS the samples come straight from AppKit's tablet data through an NSEvent monitor. On
+//! Windows the webview's input window belongs to the WebView2 browser process, out of reach of a
+//! window hook, so the frontend forwards the pressure and tilt Chromium reads from Windows Ink.
+//! Either way the samples are reduced and fitted here, so every participant receives the same
+//! compact curves instead of a dense mouse polyline.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::protocol::Message;
+use crate::session;
+use crate::storage;
+
+/// Samples closer than this to the previous one add nothing, in CSS pixels.
+const MIN_DISTANCE: f64 = 0.5;
+/// How far the fitted curve may stray from the samples, in CSS pixels (width counts too).
+const TOLERANCE: f64 = 0.6;
+/// Longer strokes are cut here; nobody draws this much in one go.
+const MAX_SAMPLES: usize = 10_000;
+
+/// One raw pen sample, in CSS pixels from the top-left of the window's content.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PenSample {
+    pub x: f64,
+    pub y: f64,
+    /// 0 to 1.
+    pub pressure: f64,
+    /// Degrees from vertical, -90 to 90, as in `PointerEvent`.
+    #[serde(default)]
+    pub tilt_x: f64,
+    #[serde(default)]
+    pub tilt_y: f64,
+}
+
+/// Where the annotated document is on screen, to map samples onto lines and columns.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PenCanvas {
+    pub document_id: String,
+    pub participant_id: String,
+    /// The text area, in CSS pixels from the top-left of the window's content.
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+    pub column_width: f64,
+    pub line_height: f64,
+    /// Line and column at the top-left corner, after scrolling; zero-based and fractional.
+    pub first_line: f64,
+    pub first_column: f64,
+    /// CSS colour of the ink.
+    pub color: String,
+    /// Stroke width at full pressure, in CSS pixels.
+    pub pen_width: f64,
+}
+
+impl PenCanvas {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        (self.left..self.left + self.width).contains(&x) && (self.top..self.top + self.height).contains(&y)
+    }
+
+    fn to_document(&self, [x, y]: [f64; 2]) -> [f64; 2] {
+        [
+            self.first_column + (x - self.left) / self.column_width,
+            self.first_line + (y - self.top) / self.line_height,
+        ]
+    }
+}
+
+/// A cubic Bézier piece of a stroke. Points are `[column, line]` in the document and widths are
+/// in lines, so strokes stay attached to the code at any font size or scroll position.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrokeSegment {
+    pub from: [f64; 2],
+    pub c1: [f64; 2],
+    pub c2: [f64; 2],
+    pub to: [f64; 2],
+    pub start_width: f64,
+    pub end_width: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stroke {
+    pub id: String,
+    pub document_id: String,
+    pub color: String,
+    pub segments: Vec<StrokeSegment>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrokeEvent {
+    pub participant_id: String,
+    pub stroke: Stroke,
+}
+
+#[derive(Default)]
+pub struct PenState {
+    canvas: Mutex<Option<PenCanvas>>,
+    samples: Mutex<Vec<PenSample>>,
+}
+
+/// A reduced stroke point: position in CSS pixels and width.
+#[derive(Clone, Copy)]
+struct Node {
+    at: [f64; 2],
+    width: f64,
+}
+
+/// Pressure sets the width; a tilted pen lays down a broader line, like a pencil on its side.
+fn width(sample: &PenSample, pressure: f64, pen_width: f64) -> f64 {
+    let tilt = (sample.tilt_x.hypot(sample.tilt_y) / 90.0).min(1.0);
+    pen_width * (0.25 + 0.75 * pressure.clamp(0.0, 1.0)) * (1.0 + 0.5 * tilt)
+}
+
+/// Deviation of `node` from the chord between `a` and `b`, counting width as a third axis.
+fn deviation(node: &Node, a: &Node, b: &Node) -> f64 {
+    let [dx, dy] = [b.at[0] - a.at[0], b.at[1] - a.at[1]];
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((node.at[0] - a.at[0]) * dx + (node.at[1] - a.at[1]) * dy) / len2).clamp(0.0, 1.0)
+    };
+    let (px, py) = (a.at[0] + t * dx, a.at[1] + t * dy);
+    let dw = node.width - (a.width + t * (b.width - a.width));
+    ((node.at[0] - px).powi(2) + (node.at[1] - py).powi(2) + dw * dw).sqrt()
+}
+
+/// Ramer–Douglas–Peucker over position and width.
+fn simplify(nodes: &[Node]) -> Vec<Node> {
+    let mut keep = vec![false; nodes.len()];
+    keep[0] = true;
+    keep[nodes.len() - 1] = true;
+    let mut spans = vec![(0, nodes.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, deviation(&nodes[i], &nodes[first], &nodes[last])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = farthest.filter(|&(_, d)| d > TOLERANCE) {
+            keep[i] = true;
+            spans.push((first, i));
+            spans.push((i, last));
+        }
+    }
+    nodes.iter().zip(keep).filter(|(_, k)| *k).map(|(n, _)| *n).collect()
+}
+
+/// Fit a smooth stroke through `samples`, in document coordinates.
+fn smooth(samples: &[PenSample], canvas: &PenCanvas) -> Vec<StrokeSegment> {
+    let mut nodes: Vec<Node> = Vec::with_capacity(samples.len());
+    let mut pressure = samples.first().map_or(0.0, |s| s.pressure);
+    for (i, sample) in samples.iter().enumerate() {
+        // Digitizers report jittery pressure; a light average keeps the edge clean.
+        pressure = 0.5 * pressure + 0.5 * sample.pressure;
+        let node = Node {
+            at: [sample.x, sample.y],
+            width: width(sample, pressure, canvas.pen_width),
+        };
+        let close = nodes.last().is_some_and(|last| {
+            (last.at[0] - node.at[0]).hypot(last.at[1] - node.at[1]) < MIN_DISTANCE
+        });
+        // The last sample always counts: it is where the pen lifted.
+        if !close || i == samples.len() - 1 {
+            nodes.push(node);
+        }
+    }
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+    let nodes = simplify(&nodes);
+    let scale = canvas.line_height;
+    let point = |at: [f64; 2]| canvas.to_document(at);
+
+    if nodes.len() == 1 {
+        // A tap: a dot.
+        let at = point(nodes[0].at);
+        return vec![StrokeSegment {
+            from: at,
+            c1: at,
+            c2: at,
+            to: at,
+            start_width: nodes[0].width / scale,
+            end_width: nodes[0].width / scale,
+        }];
+    }
+    // Catmull-Rom through the nodes, as Béziers, with the ends repeated.
+    (0..nodes.len() - 1)
+        .map(|i| {
+            let p0 = nodes[i.saturating_sub(1)].at;
+            let (p1, p2) = (nodes[i].at, nodes[i + 1].at);
+            let p3 = nodes[(i + 2).min(nodes.len() - 1)].at;
+            let c1 = [p1[0] + (p2[0] - p0[0]) / 6.0, p1[1] + (p2[1] - p0[1]) / 6.0];
+            let c2 = [p2[0] - (p3[0] - p1[0]) / 6.0, p2[1] - (p3[1] - p1[1]) / 6.0];
+            StrokeSegment {
+                from: point(p1),
+                c1: point(c1),
+                c2: point(c2),
+                to: point(p2),
+                start_width: nodes[i].width / scale,
+                end_width: nodes[i + 1].width / scale,
+            }
+        })
+        .collect()
+}
+
+/// Turn the collected samples into a stroke and send it to the session.
+fn finish(app: &AppHandle, state: &PenState) -> Result<Option<Stroke>, String> {
+    let samples = std::mem::take(&mut *state.samples.lock().unwrap());
+    let Some(canvas) = state.canvas.lock().unwrap().clone() else {
+        return Ok(None);
+    };
+    let segments = smooth(&samples, &canvas);
+    if segments.is_empty() {
+        return Ok(None);
+    }
+    let stroke = Stroke {
+        id: storage::new_id(),
+        document_id: canvas.document_id,
+        color: canvas.color,
+        segments,
+    };
+    let event = StrokeEvent {
+        participant_id: canvas.participant_id.clone(),
+        stroke: stroke.clone(),
+    };
+    app.emit("annotation-stroke", event).map_err(|e| e.to_string())?;
+    session::broadcast(
+        app,
+        &Message::Stroke {
+            participant_id: canvas.participant_id,
+            stroke: stroke.clone(),
+        },
+    )?;
+    Ok(Some(stroke))
+}
+
+/// Add samples to the stroke in progress. A stroke starts inside the canvas and may leave it.
+fn push(state: &PenState, samples: &[PenSample]) {
+    let canvas = state.canvas.lock().unwrap();
+    let Some(canvas) = canvas.as_ref() else {
+        return;
+    };
+    let mut stroke = state.samples.lock().unwrap();
+    for sample in samples {
+        if stroke.is_empty() && !canvas.contains(sample.x, sample.y) {
+            continue;
+        }
+        if stroke.len() < MAX_SAMPLES {
+            stroke.push(*sample);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::sync::OnceLock;
+
+    use block::ConcreteBlock;
+    use cocoa::foundation::{NSPoint, NSRect};
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::{AppHandle, Manager};
+
+    use super::{PenSample, PenState};
+
+    type Id = *mut Object;
+
+    const NS_EVENT_TYPE_LEFT_MOUSE_DOWN: u64 = 1;
+    const NS_EVENT_TYPE_LEFT_MOUSE_UP: u64 = 2;
+    const NS_EVENT_TYPE_LEFT_MOUSE_DRAGGED: u64 = 6;
+    const NS_EVENT_TYPE_TABLET_POINT: u64 = 23;
+    const NS_EVENT_SUBTYPE_TABLET_POINT: i16 = 1;
+
+    static APP: OnceLock<AppHandle> = OnceLock::new();
+
+    unsafe fn sample(event: Id) -> Option<PenSample> {
+        let window: Id = msg_send![event, window];
+        if window.is_null() {
+            return None;
+        }
+        let view: Id = msg_send![window, contentView];
+        let frame: NSRect = msg_send![view, frame];
+        let location: NSPoint = msg_send![event, locationInWindow];
+        let pressure: f32 = msg_send![event, pressure];
+        let tilt: NSPoint = msg_send![event, tilt];
+        Some(PenSample {
+            x: location.x,
+            // AppKit counts from the bottom.
+            y: frame.size.height - location.y,
+            pressure: pressure as f64,
+            tilt_x: tilt.x * 90.0,
+            tilt_y: tilt.y * 90.0,
+        })
+    }
+
+    /// Handles one event; pen events on the canvas are kept from the webview.
+    unsafe fn handle(event: Id) -> Id {
+        let Some(app) = APP.get() else {
+            return event;
+        };
+        let state = app.state::<PenState>();
+        if state.canvas.lock().unwrap().is_none() {
+            return event;
+        }
+        let kind: u64 = msg_send![event, type];
+        let pen = kind == NS_EVENT_TYPE_TABLET_POINT || {
+            let subtype: i16 = msg_send![event, subtype];
+            subtype == NS_EVENT_SUBTYPE_TABLET_POINT
+        };
+        let drawing = !state.samples.lock().unwrap().is_empty();
+        if !pen || (kind != NS_EVENT_TYPE_LEFT_MOUSE_DOWN && !drawing) {
+            return event;
+        }
+        let Some(sample) = sample(event) else {
+            return event;
+        };
+        super::push(&state, &[sample]);
+        if kind == NS_EVENT_TYPE_LEFT_MOUSE_UP {
+            if let Err(e) = super::finish(app, &state) {
+                log::warn!("Failed to send pen stroke: {}", e);
+            }
+        } else if state.samples.lock().unwrap().is_empty() {
+            // Pen down outside the canvas.
+            return event;
+        }
+        std::ptr::null_mut()
+    }
+
+    /// Install the local event monitor. Must run on the main thread.
+    pub unsafe fn install(app: AppHandle) {
+        if APP.set(app).is_err() {
+            return;
+        }
+        let mask: u64 = (1 << NS_EVENT_TYPE_LEFT_MOUSE_DOWN)
+            | (1 << NS_EVENT_TYPE_LEFT_MOUSE_UP)
+            | (1 << NS_EVENT_TYPE_LEFT_MOUSE_DRAGGED)
+            | (1 << NS_EVENT_TYPE_TABLET_POINT);
+        let handler = ConcreteBlock::new(|event: Id| -> Id { unsafe { handle(event) } }).copy();
+        let monitor: Id = msg_send![class!(NSEvent), addLocalMonitorForEventsMatchingMask: mask handler: &*handler];
+        if monitor.is_null() {
+            log::warn!("Failed to install the pen input monitor");
+        }
+        // The monitor stays for the life of the app, and so must its handler.
+        std::mem::forget(handler);
+    }
+}
+
+/// Start reading native pen input where available. Called once from setup, on the main thread.
+pub fn install(app: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    unsafe {
+        macos::install(app.clone());
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app;
+}
+
+/// Arm pen annotation over a document, or disarm it with `None`. Call again after scrolling.
+/// Returns whether pen input is read natively; when it is not, forward pointer events with
+/// [`add_pen_samples`].
+#[tauri::command]
+pub fn set_pen_canvas(state: State<'_, PenState>, canvas: Option<PenCanvas>) -> Result<bool, String> {
+    if let Some(canvas) = &canvas {
+        if canvas.column_width <= 0.0 || canvas.line_height <= 0.0 || canvas.pen_width <= 0.0 {
+            return Err("Invalid pen canvas".to_string());
+        }
+    } else {
+        state.samples.lock().unwrap().clear();
+    }
+    *state.canvas.lock().unwrap() = canvas;
+    Ok(cfg!(target_os = "macos"))
+}
+
+/// Add pen samples from the webview; `end` completes the stroke and returns it once sent.
+#[tauri::command]
+pub fn add_pen_samples(
+    app: AppHandle,
+    state: State<'_, PenState>,
+    samples: Vec<PenSample>,
+    end: bool,
+) -> Result<Option<Stroke>, String> {
+    push(&state, &samples);
+    if end {
+        return finish(&app, &state);
+    }
+    Ok(None)
+}
@@ -0,0 +1,188 @@
+//! Polls and quick quizzes run by the host: one vote per participant, live tallies, and
+//! results kept in the session history.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::protocol::Message;
+use crate::session::{self, Session, SessionRecord};
+use crate::storage::{self, new_id};
+
+struct Poll {
+    question: String,
+    options: Vec<String>,
+    anonymous: bool,
+    created_at: u64,
+    closed_at: Option<u64>,
+    /// Participant id -> option index.
+    votes: HashMap<String, usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PollTally {
+    pub id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    pub counts: Vec<usize>,
+    pub total: usize,
+    pub anonymous: bool,
+    pub created_at: u64,
+    pub closed_at: Option<u64>,
+    /// Participant name -> option index; omitted for anonymous polls.
+    pub voters: Option<HashMap<String, usize>>,
+}
+
+#[derive(Default)]
+pub struct PollStore {
+    polls: Mutex<HashMap<String, Poll>>,
+}
+
+impl Poll {
+    fn tally(&self, id: &str, session: &Session) -> PollTally {
+        let mut counts = vec![0; self.options.len()];
+        for &option in self.votes.values() {
+            counts[option] += 1;
+        }
+        let voters = (!self.anonymous).then(|| {
+            self.votes
+                .iter()
+                .map(|(participant, &option)| {
+                    let name = session
+                        .participant(participant)
+                        .map(|p| p.name)
+                        .unwrap_or_else(|_| participant.clone());
+                    (name, option)
+                })
+                .collect()
+        });
+        PollTally {
+            id: id.to_string(),
+            question: self.question.clone(),
+            options: self.options.clone(),
+            counts,
+            total: self.votes.len(),
+            anonymous: self.anonymous,
+            created_at: self.created_at,
+            closed_at: self.closed_at,
+            voters,
+        }
+    }
+}
+
+fn publish(app: &AppHandle, tally: &PollTally) -> Result<(), String> {
+    app.emit("poll-updated", tally).map_err(|e| e.to_string())?;
+    // Participants see counts but never who voted for what.
+    let public = PollTally { voters: None, ..tally.clone() };
+    session::broadcast(app, &Message::PollUpdated { poll: public })
+}
+
+/// Count a vote; each participant votes once per poll.
+pub fn handle_vote(
+    app: &AppHandle,
+    polls: &PollStore,
+    session: &Session,
+    poll_id: &str,
+    participant_id: &str,
+    option: usize,
+) -> Result<PollTally, String> {
+    session.participant(participant_id)?;
+    let tally = {
+        let mut polls = polls.polls.lock().unwrap();
+        let poll = polls.get_mut(poll_id).ok_or_else(|| format!("Unknown poll {}", poll_id))?;
+        if poll.closed_at.is_some() {
+            return Err("The poll is closed".to_string());
+        }
+        if option >= poll.options.len() {
+            return Err(format!("Poll has no option {}", option));
+        }
+        if poll.votes.contains_key(participant_id) {
+            return Err("Already voted in this poll".to_string());
+        }
+        poll.votes.insert(participant_id.to_string(), option);
+        poll.tally(poll_id, session)
+    };
+    publish(app, &tally)?;
+    Ok(tally)
+}
+
+#[tauri::command]
+pub fn create_poll(
+    app: AppHandle,
+    polls: State<'_, PollStore>,
+    session: State<'_, Session>,
+    question: String,
+    options: Vec<String>,
+    anonymous: Option<bool>,
+) -> Result<PollTally, String> {
+    if options.len() < 2 {
+        return Err("A poll needs at least two options".to_string());
+    }
+    let id = new_id();
+    let poll = Poll {
+        question,
+        options,
+        anonymous: anonymous.unwrap_or(false),
+        created_at: storage::now_secs(),
+        closed_at: None,
+        votes: HashMap::new(),
+    };
+    let tally = poll.tally(&id, &session);
+    polls.polls.lock().unwrap().insert(id, poll);
+    publish(&app, &tally)?;
+    Ok(tally)
+}
+
+/// Vote in a poll. Votes for polls hosted elsewhere are sent to the host and return `None`;
+/// the updated tally arrives as `poll-updated`.
+#[tauri::command]
+pub fn vote_poll(
+    app: AppHandle,
+    polls: State<'_, PollStore>,
+    session: State<'_, Session>,
+    poll_id: String,
+    participant_id: String,
+    option: usize,
+) -> Result<Option<PollTally>, String> {
+    let hosted_here = polls.polls.lock().unwrap().contains_key(&poll_id);
+    if !hosted_here {
+        session::broadcast(&app, &Message::Vote { poll_id, participant_id, option })?;
+        return Ok(None);
+    }
+    handle_vote(&app, &polls, &session, &poll_id, &participant_id, option).map(Some)
+}
+
+/// Stop accepting votes and store the final result in the session history.
+#[tauri::command]
+pub fn close_poll(
+    app: AppHandle,
+    polls: State<'_, PollStore>,
+    session: State<'_, Session>,
+    poll_id: String,
+) -> Result<PollTally, String> {
+    let tally = {
+        let mut polls = polls.polls.lock().unwrap();
+        let poll = polls.get_mut(&poll_id).ok_or_else(|| format!("Unknown poll {}", poll_id))?;
+        poll.closed_at.get_or_insert_with(storage::now_secs);
+        poll.tally(&poll_id, &session)
+    };
+    session::record(&app, SessionRecord::Poll(tally.clone()))?;
+    publish(&app, &tally)?;
+    Ok(tally)
+}
+
+#[tauri::command]
+pub fn list_polls(polls: State<'_, PollStore>, session: State<'_, Session>) -> Vec<PollTally> {
+    let mut list: Vec<PollTally> = polls
+        .polls
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, poll)| poll.tally(id, &session))
+        .collect();
+    list.sort_by_key(|p| p.created_at);
+    list
+}
@@ -0,0 +1,454 @@
+//! Printing documents as paginated, highlighted PDFs.
+//!
+//! The webview prints code poorly (no line numbers, lines cut at the page edge, colours dropped),
+//! so the backend lays out the pages itself and hands the resulting PDF to the system print
+//! spooler, or writes it where the user asked.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::documents::DocumentStore;
+use crate::export::{self, Rgb, Run, Theme, ThemeName};
+use crate::syntax::HighlightClass;
+use crate::workers::{Priority, WorkerPool};
+
+const MARGIN: f32 = 40.0;
+const HEADER_HEIGHT: f32 = 34.0;
+const HEADER_FONT_SIZE: f32 = 9.0;
+/// Courier advances every glyph by 600/1000 em.
+const CHAR_WIDTH_EM: f32 = 0.6;
+const LINE_HEIGHT_EM: f32 = 1.25;
+
+// Standard Type 1 fonts every PDF reader has, so nothing needs embedding.
+const FONTS: [&str; 5] = ["Courier", "Courier-Bold", "Courier-Oblique", "Helvetica", "Helvetica-Bold"];
+const CODE: &str = "/F1";
+const CODE_BOLD: &str = "/F2";
+const CODE_ITALIC: &str = "/F3";
+const LABEL: &str = "/F4";
+const LABEL_BOLD: &str = "/F5";
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Paper {
+    #[default]
+    A4,
+    Letter,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PrintOptions {
+    pub paper: Paper,
+    pub landscape: bool,
+    pub font_size: f32,
+    pub line_numbers: bool,
+    /// File name, date and page numbers at the top of each page.
+    pub header: bool,
+    /// Wrap long lines onto continuation rows instead of cutting them at the margin.
+    pub wrap: bool,
+    /// `false` prints everything in black, keeping bold keywords and italic comments.
+    pub color: bool,
+    /// Save the PDF here instead of sending it to the printer.
+    pub output_path: Option<String>,
+    /// Printer to use; the system default when `None`.
+    pub printer: Option<String>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            paper: Paper::A4,
+            landscape: false,
+            font_size: 9.0,
+            line_numbers: true,
+            header: true,
+            wrap: true,
+            color: true,
+            output_path: None,
+            printer: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrintJob {
+    pub pages: usize,
+    /// Where the PDF was saved, when `outputPath` was given.
+    pub path: Option<String>,
+    pub printed: bool,
+}
+
+/// One printed row: a source line or the continuation of a wrapped one.
+struct Row {
+    number: Option<usize>,
+    runs: Vec<Run>,
+}
+
+fn wrap_rows(lines: Vec<Vec<Run>>, columns: usize, wrap: bool) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (index, line) in lines.into_iter().enumerate() {
+        let mut row = Row {
+            number: Some(index + 1),
+            runs: Vec::new(),
+        };
+        let mut width = 0;
+        for run in line {
+            let mut rest = run.text.as_str();
+            while !rest.is_empty() {
+                if width == columns {
+                    if !wrap {
+                        break;
+                    }
+                    rows.push(std::mem::replace(
+                        &mut row,
+                        Row {
+                            number: None,
+                            runs: Vec::new(),
+                        },
+                    ));
+                    width = 0;
+                }
+                let take = rest
+                    .char_indices()
+                    .nth(columns - width)
+                    .map_or(rest.len(), |(i, _)| i);
+                width += rest[..take].chars().count();
+                row.runs.push(Run {
+                    text: rest[..take].to_string(),
+                    class: run.class,
+                });
+                rest = &rest[take..];
+            }
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+/// A PDF literal string in WinAnsi encoding; characters it cannot represent print as `?`.
+fn pdf_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('(');
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            // WinAnsi matches Latin-1 here.
+            '\u{a0}'..='\u{ff}' => out.push_str(&format!("\\{:03o}", c as u32)),
+            _ => out.push('?'),
+        }
+    }
+    out.push(')');
+    out
+}
+
+fn fill(color: Rgb) -> String {
+    format!(
+        "{:.3} {:.3} {:.3} rg\n",
+        color[0] as f32 / 255.0,
+        color[1] as f32 / 255.0,
+        color[2] as f32 / 255.0
+    )
+}
+
+struct Layout<'a> {
+    options: &'a PrintOptions,
+    theme: Theme,
+    width: f32,
+    height: f32,
+    line_height: f32,
+    char_width: f32,
+    gutter: usize,
+    title: String,
+    printed_at: String,
+}
+
+impl Layout<'_> {
+    fn color(&self, class: Option<HighlightClass>) -> Rgb {
+        if self.options.color {
+            self.theme.color(class)
+        } else {
+            [0, 0, 0]
+        }
+    }
+
+    fn page(&self, rows: &[Row], page: usize, pages: usize) -> String {
+        let size = self.options.font_size;
+        let mut out = String::new();
+        let mut y = self.height - MARGIN;
+        if self.options.header {
+            let baseline = y - HEADER_FONT_SIZE;
+            let label = format!("Page {} of {}", page, pages);
+            let label_width = label.len() as f32 * HEADER_FONT_SIZE * 0.5;
+            out.push_str(&fill([0, 0, 0]));
+            out.push_str(&format!(
+                "BT {} {} Tf {:.2} {:.2} Td {} Tj ET\n",
+                LABEL_BOLD,
+                HEADER_FONT_SIZE,
+                MARGIN,
+                baseline,
+                pdf_string(&self.title)
+            ));
+            out.push_str(&format!(
+                "BT {} {} Tf {:.2} {:.2} Td {} Tj ET\n",
+                LABEL,
+                HEADER_FONT_SIZE,
+                self.width - MARGIN - label_width,
+                baseline,
+                pdf_string(&label)
+            ));
+            out.push_str(&fill(self.theme.gutter));
+            out.push_str(&format!(
+                "BT {} {} Tf {:.2} {:.2} Td {} Tj ET\n",
+                LABEL,
+                HEADER_FONT_SIZE - 1.0,
+                MARGIN,
+                baseline - HEADER_FONT_SIZE - 3.0,
+                pdf_string(&self.printed_at)
+            ));
+            let rule = y - HEADER_HEIGHT + 8.0;
+            out.push_str(&format!(
+                "0.5 w 0.7 G {:.2} {:.2} m {:.2} {:.2} l S\n",
+                MARGIN,
+                rule,
+                self.width - MARGIN,
+                rule
+            ));
+            y -= HEADER_HEIGHT;
+        }
+
+        let code_x = MARGIN + self.gutter as f32 * self.char_width;
+        for row in rows {
+            y -= self.line_height;
+            if let Some(number) = row.number.filter(|_| self.options.line_numbers) {
+                out.push_str(&fill(self.theme.gutter));
+                let label = format!("{:>width$}", number, width = self.gutter - 2);
+                out.push_str(&format!("BT {} {} Tf {:.2} {:.2} Td {} Tj ET\n", CODE, size, MARGIN, y, pdf_string(&label)));
+            }
+            if row.runs.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("BT {:.2} {:.2} Td\n", code_x, y));
+            for run in &row.runs {
+                let font = match run.class {
+                    Some(HighlightClass::Keyword) => CODE_BOLD,
+                    Some(HighlightClass::Comment) => CODE_ITALIC,
+                    _ => CODE,
+                };
+                out.push_str(&format!("{} {} Tf ", font, size));
+                out.push_str(&fill(self.color(run.class)));
+                out.push_str(&pdf_string(&run.text));
+                out.push_str(" Tj\n");
+            }
+            out.push_str("ET\n");
+        }
+        out
+    }
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// Assemble a PDF from page content streams.
+fn write_pdf(width: f32, height: f32, pages: &[String]) -> Result<Vec<u8>, String> {
+    // Objects: 1 catalog, 2 page tree, then the fonts, then a page and its contents per page.
+    let first_page = 3 + FONTS.len();
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", first_page + i * 2)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    for font in FONTS {
+        objects.push(
+            format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>", font).into_bytes(),
+        );
+    }
+    let fonts: Vec<String> = (0..FONTS.len()).map(|i| format!("/F{} {} 0 R", i + 1, 3 + i)).collect();
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                width,
+                height,
+                fonts.join(" "),
+                first_page + i * 2 + 1
+            )
+            .into_bytes(),
+        );
+        let stream = deflate(content.as_bytes())?;
+        let mut object = format!("<< /Length {} /Filter /FlateDecode >>\nstream\n", stream.len()).into_bytes();
+        object.extend_from_slice(&stream);
+        object.extend_from_slice(b"\nendstream");
+        objects.push(object);
+    }
+
+    let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        out.extend_from_slice(object);
+        out.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        out.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    out.extend_from_slice(
+        format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes(),
+    );
+    Ok(out)
+}
+
+/// Lay out a document and return the PDF and its page count.
+fn render(
+    text: &str,
+    language: Option<&str>,
+    tab_width: usize,
+    title: String,
+    options: &PrintOptions,
+) -> Result<(Vec<u8>, usize), String> {
+    if !(4.0..=24.0).contains(&options.font_size) {
+        return Err("Font size must be between 4 and 24 points".to_string());
+    }
+    let (mut width, mut height) = match options.paper {
+        Paper::A4 => (595.28, 841.89),
+        Paper::Letter => (612.0, 792.0),
+    };
+    if options.landscape {
+        std::mem::swap(&mut width, &mut height);
+    }
+    let lines = export::styled_lines(text, language, tab_width);
+    let gutter = if options.line_numbers {
+        lines.len().to_string().len() + 2
+    } else {
+        0
+    };
+    let layout = Layout {
+        options,
+        theme: export::theme(ThemeName::Light),
+        width,
+        height,
+        line_height: options.font_size * LINE_HEIGHT_EM,
+        char_width: options.font_size * CHAR_WIDTH_EM,
+        gutter,
+        title,
+        printed_at: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+    };
+
+    let columns = ((width - 2.0 * MARGIN) / layout.char_width) as usize;
+    let columns = columns.saturating_sub(gutter).max(1);
+    let body = height - 2.0 * MARGIN - if options.header { HEADER_HEIGHT } else { 0.0 };
+    let rows_per_page = ((body / layout.line_height) as usize).max(1);
+
+    let rows = wrap_rows(lines, columns, options.wrap);
+    let chunks: Vec<&[Row]> = rows.chunks(rows_per_page).collect();
+    let pages: Vec<String> = chunks
+        .iter()
+        .enumerate()
+        .map(|(i, rows)| layout.page(rows, i + 1, chunks.len()))
+        .collect();
+    Ok((write_pdf(width, height, &pages)?, pages.len()))
+}
+
+/// Hand a PDF to the system print spooler.
+#[cfg(desktop)]
+fn send_to_printer(path: &Path, printer: Option<&str>) -> Result<(), String> {
+    use std::process::Command;
+
+    #[cfg(target_os = "windows")]
+    let output = {
+        let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+        let script = match printer {
+            Some(printer) => format!(
+                "Start-Process -FilePath {} -Verb PrintTo -ArgumentList {}",
+                quote(&path.to_string_lossy()),
+                quote(&format!("\"{}\"", printer))
+            ),
+            None => format!("Start-Process -FilePath {} -Verb Print", quote(&path.to_string_lossy())),
+        };
+        Command::new("powershell").args(["-NoProfile", "-Command", &script]).output()
+    };
+    #[cfg(not(target_os = "windows"))]
+    let output = {
+        let mut command = Command::new("lp");
+        if let Some(printer) = printer {
+            command.args(["-d", printer]);
+        }
+        command.arg(path).output()
+    };
+
+    let output = output.map_err(|e| format!("Failed to start the print spooler: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Printing failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+#[cfg(mobile)]
+fn send_to_printer(_path: &Path, _printer: Option<&str>) -> Result<(), String> {
+    Err("Printing is not supported on this platform; save the PDF instead".to_string())
+}
+
+/// Print a document, or save the print-ready PDF when `options.outputPath` is set.
+#[tauri::command]
+pub async fn print_document(
+    store: State<'_, DocumentStore>,
+    pool: State<'_, WorkerPool>,
+    doc_id: String,
+    options: Option<PrintOptions>,
+) -> Result<PrintJob, String> {
+    let options = options.unwrap_or_default();
+    let (text, language, tab_width, title) = store.with(&doc_id, |doc| {
+        let title = doc
+            .path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map_or_else(|| "Untitled".to_string(), |n| n.to_string_lossy().into_owned());
+        (doc.text.clone(), doc.language.clone(), doc.profile.tab_width as usize, title)
+    })?;
+    let rendering = options.clone();
+    let (pdf, pages) = pool
+        .run(Priority::Background, "Print rendering", move |_| {
+            render(&text, language.as_deref(), tab_width, title, &rendering)
+        })
+        .await?;
+
+    if let Some(output) = &options.output_path {
+        let path = PathBuf::from(output);
+        fs::write(&path, &pdf).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        return Ok(PrintJob {
+            pages,
+            path: Some(output.clone()),
+            printed: false,
+        });
+    }
+
+    let path = std::env::temp_dir().join(format!("sharecode-print-{}.pdf", crate::storage::new_id()));
+    fs::write(&path, &pdf).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let result = send_to_printer(&path, options.printer.as_deref());
+    // `lp` has spooled its own copy by the time it returns; Windows hands the file to another
+    // app that reads it later, so it stays in the temp directory there.
+    if cfg!(not(target_os = "windows")) {
+        fs::remove_file(&path).ok();
+    }
+    result?;
+    Ok(PrintJob {
+        pages,
+        path: None,
+        printed: true,
+    })
+}
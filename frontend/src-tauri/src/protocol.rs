@@ -0,0 +1,417 @@
+//! Messages exchanged between the participants of a sharing session.
+//!
+//! Messages are JSON objects tagged by `type`. Outgoing messages are emitted to the webview as
+//! `session-message` (wrapped in an [`Envelope`]) for whichever transport is active; incoming
+//! ones arrive via `receive_message`.
+//!
+//! Peers agree on a protocol version and a set of capabilities when a viewer joins. Clients
+//! that say nothing are taken to speak version 1, which had every capability up to the
+//! magnifier. A peer is only sent the messages of capabilities both sides have. A message type
+//! a peer does not know is read as [`Message::Unsupported`] and ignored. So when the two sides
+//! differ, the features one of them lacks are switched off, and nobody is disconnected:
+//!
+//! | Host \ client | Version 1                 | Version 2                                       |
+//! |---------------|---------------------------|-------------------------------------------------|
+//! | Version 1     | Everything                | Everything; the reply has no version, so 1      |
+//! | Version 2     | Version 1 capabilities    | The capabilities both list                      |
+//!
+//! A later host or client lists its new capabilities. The other side leaves them out of the
+//! negotiated set, and new message types reach it only as `Unsupported`. A client older than
+//! [`MIN_PROTOCOL_VERSION`] is refused at join.
+
+use serde::{Deserialize, Serialize};
+
+use crate::interview::RevealedTask;
+use crate::magnifier::FocusRegion;
+use crate::pen::Stroke;
+use crate::polls::PollTally;
+use crate::remote_actions::{ActionOutput, RemoteAction};
+use crate::snippets::PortableSnippet;
+
+/// Version this build speaks. 2 added negotiation itself, 3 the lifecycle capability, 4 snippets,
+/// 5 remote actions, 6 read receipts, 7 frozen documents.
+pub const PROTOCOL_VERSION: u32 = 7;
+/// Oldest version still accepted from a joining client.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// A group of message types that a peer may or may not support.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Document snapshots and patches.
+    Documents,
+    Signals,
+    Polls,
+    Scratchpad,
+    Interview,
+    Strokes,
+    Magnifier,
+    /// Notice that the host is ending the session.
+    Lifecycle,
+    /// Snippets the host sends from their library.
+    Snippets,
+    /// Requests to apply a patch or run code on the host, which the host approves.
+    RemoteActions,
+    /// Receipts for the document versions a viewer received and displayed.
+    Receipts,
+    /// Notice that the host froze or unfroze a document.
+    Frozen,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 12] = [
+        Capability::Documents,
+        Capability::Signals,
+        Capability::Polls,
+        Capability::Scratchpad,
+        Capability::Interview,
+        Capability::Strokes,
+        Capability::Magnifier,
+        Capability::Lifecycle,
+        Capability::Snippets,
+        Capability::RemoteActions,
+        Capability::Receipts,
+        Capability::Frozen,
+    ];
+
+    /// Protocol version that introduced the capability; a client that does not list its
+    /// capabilities has every one up to its version.
+    fn since(self) -> u32 {
+        match self {
+            Capability::Lifecycle => 3,
+            Capability::Snippets => 4,
+            Capability::RemoteActions => 5,
+            Capability::Receipts => 6,
+            Capability::Frozen => 7,
+            _ => 1,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+}
+
+/// What a host and a joining client agreed on.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Negotiated {
+    pub protocol: u32,
+    pub capabilities: Vec<Capability>,
+}
+
+impl Negotiated {
+    /// What a client that joined without negotiating gets.
+    pub fn legacy() -> Self {
+        Self::with(1, None).unwrap()
+    }
+
+    /// Agree on the lower of the two versions and the capabilities both sides have. Names this
+    /// build does not know are ignored.
+    pub fn with(protocol: u32, capabilities: Option<&[String]>) -> Result<Self, String> {
+        Self::between(PROTOCOL_VERSION, protocol, capabilities)
+    }
+
+    /// `with`, for a side that speaks `ours` rather than this build's version.
+    fn between(ours: u32, protocol: u32, capabilities: Option<&[String]>) -> Result<Self, String> {
+        if protocol < MIN_PROTOCOL_VERSION {
+            return Err(format!(
+                "Protocol version {} is no longer supported; update to version {} or later",
+                protocol, MIN_PROTOCOL_VERSION
+            ));
+        }
+        let theirs: Vec<Capability> = match capabilities {
+            Some(names) => names.iter().filter_map(|name| Capability::from_name(name)).collect(),
+            None => Capability::ALL.into_iter().filter(|c| c.since() <= protocol).collect(),
+        };
+        Ok(Self {
+            protocol: protocol.min(ours),
+            capabilities: theirs.into_iter().filter(|c| c.since() <= ours).collect(),
+        })
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Message {
+    /// A lightweight participant signal: a raised hand or a reaction.
+    #[serde(rename_all = "camelCase")]
+    Signal {
+        participant_id: String,
+        signal: Signal,
+        /// Seconds since the Unix epoch, set by the sender.
+        at: u64,
+    },
+    /// The host opened, updated or closed a poll.
+    PollUpdated { poll: PollTally },
+    /// A participant's vote, sent to the host.
+    #[serde(rename_all = "camelCase")]
+    Vote {
+        poll_id: String,
+        participant_id: String,
+        option: usize,
+    },
+    /// Full text of a participant's private scratchpad: from the host when it is opened,
+    /// from the participant as they edit.
+    #[serde(rename_all = "camelCase")]
+    Scratchpad {
+        participant_id: String,
+        text: String,
+        language: Option<String>,
+    },
+    /// The interviewer revealed the next task.
+    InterviewTask { task: RevealedTask },
+    /// Full text of a shared document at timeline position `seq`, sent to late joiners. Hosts
+    /// before version 7 leave out `frozen`.
+    #[serde(rename_all = "camelCase")]
+    Snapshot {
+        document_id: String,
+        seq: u64,
+        text: String,
+        language: Option<String>,
+        #[serde(default)]
+        frozen: bool,
+    },
+    /// One change to a shared document, applied on top of the snapshot with the preceding `seq`.
+    /// Offsets and lengths are UTF-16 code units.
+    #[serde(rename_all = "camelCase")]
+    Patch {
+        document_id: String,
+        seq: u64,
+        start: usize,
+        delete_count: usize,
+        insert: String,
+    },
+    /// A finished pen annotation on a shared document.
+    #[serde(rename_all = "camelCase")]
+    Stroke { participant_id: String, stroke: Stroke },
+    /// The host moved the magnifier, or put it away (`None`).
+    Magnifier { region: Option<FocusRegion> },
+    /// The host is ending the session, e.g. because the app is quitting; don't reconnect.
+    SessionEnded { reason: String },
+    /// A snippet from the host's library, for participants to save to theirs.
+    Snippet { snippet: PortableSnippet },
+    /// A participant asks the host to do something on the host's machine.
+    #[serde(rename_all = "camelCase")]
+    ActionRequest {
+        request_id: String,
+        participant_id: String,
+        action: RemoteAction,
+    },
+    /// The host's answer to an `ActionRequest`: what happened if approved, why not otherwise.
+    #[serde(rename_all = "camelCase")]
+    ActionResult {
+        request_id: String,
+        approved: bool,
+        output: Option<ActionOutput>,
+        error: Option<String>,
+    },
+    /// A viewer holds `document_id` up to `seq`, and has displayed it if `viewed`.
+    #[serde(rename_all = "camelCase")]
+    Receipt {
+        participant_id: String,
+        document_id: String,
+        seq: u64,
+        viewed: bool,
+    },
+    /// The host froze a shared document, making it read-only for everyone, or unfroze it.
+    #[serde(rename_all = "camelCase")]
+    DocumentFrozen { document_id: String, frozen: bool },
+    /// A message type from a newer peer. Never sent.
+    #[serde(other)]
+    Unsupported,
+}
+
+impl Message {
+    /// The capability a peer needs to be sent this message; `None` for `Unsupported`.
+    pub fn capability(&self) -> Option<Capability> {
+        Some(match self {
+            Message::Signal { .. } => Capability::Signals,
+            Message::PollUpdated { .. } | Message::Vote { .. } => Capability::Polls,
+            Message::Scratchpad { .. } => Capability::Scratchpad,
+            Message::InterviewTask { .. } => Capability::Interview,
+            Message::Snapshot { .. } | Message::Patch { .. } => Capability::Documents,
+            Message::Stroke { .. } => Capability::Strokes,
+            Message::Magnifier { .. } => Capability::Magnifier,
+            Message::SessionEnded { .. } => Capability::Lifecycle,
+            Message::Snippet { .. } => Capability::Snippets,
+            Message::ActionRequest { .. } | Message::ActionResult { .. } => Capability::RemoteActions,
+            Message::Receipt { .. } => Capability::Receipts,
+            Message::DocumentFrozen { .. } => Capability::Frozen,
+            Message::Unsupported => return None,
+        })
+    }
+
+    /// The participant a message comes from, for the kinds participants send. Receipts are left
+    /// out: clients send them on their own, so they say nothing about the participant's activity.
+    pub fn participant_id(&self) -> Option<&str> {
+        match self {
+            Message::Signal { participant_id, .. }
+            | Message::Vote { participant_id, .. }
+            | Message::Scratchpad { participant_id, .. }
+            | Message::Stroke { participant_id, .. }
+            | Message::ActionRequest { participant_id, .. } => Some(participant_id),
+            _ => None,
+        }
+    }
+}
+
+/// An outgoing message and its recipient; `to: None` goes to every participant.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope<'a> {
+    pub to: Option<&'a str>,
+    pub message: &'a Message,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Signal {
+    RaiseHand,
+    LowerHand,
+    ThumbsUp,
+    GoSlower,
+    GoFaster,
+    Confused,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The names a client at `version` lists when it negotiates.
+    fn listed(version: u32) -> Vec<String> {
+        Capability::ALL
+            .into_iter()
+            .filter(|c| c.since() <= version)
+            .map(|c| serde_json::to_value(c).unwrap().as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn receipt() -> Message {
+        Message::Receipt {
+            participant_id: "p".to_string(),
+            document_id: "d".to_string(),
+            seq: 1,
+            viewed: true,
+        }
+    }
+
+    #[test]
+    fn every_version_pair_gets_what_both_sides_have() {
+        for host in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+            for client in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+                let names = listed(client);
+                // Version 1 clients don't negotiate; later ones list their capabilities.
+                let capabilities = (client >= 2).then_some(names.as_slice());
+                let negotiated = Negotiated::between(host, client, capabilities).unwrap();
+                let both = host.min(client);
+                assert_eq!(negotiated.protocol, both, "host {} client {}", host, client);
+                for capability in Capability::ALL {
+                    assert_eq!(
+                        negotiated.allows(capability),
+                        capability.since() <= both,
+                        "host {} client {} {:?}",
+                        host,
+                        client,
+                        serde_json::to_value(capability).unwrap()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn newer_features_need_both_sides() {
+        for host in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+            for client in MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION {
+                let negotiated = Negotiated::between(host, client, Some(&listed(client))).unwrap();
+                let both = host.min(client);
+                assert_eq!(negotiated.allows(Capability::Snippets), both >= 4);
+                assert_eq!(negotiated.allows(Capability::RemoteActions), both >= 5);
+                assert_eq!(negotiated.allows(Capability::Receipts), both >= 6);
+                assert_eq!(negotiated.allows(Capability::Frozen), both >= 7);
+            }
+        }
+    }
+
+    #[test]
+    fn messages_map_to_their_capabilities() {
+        let snippet = Message::Snippet {
+            snippet: PortableSnippet {
+                title: "t".to_string(),
+                prefix: None,
+                body: "b".to_string(),
+                language: None,
+                description: None,
+                tags: Vec::new(),
+            },
+        };
+        let result = Message::ActionResult {
+            request_id: "r".to_string(),
+            approved: false,
+            output: None,
+            error: None,
+        };
+        let frozen = Message::DocumentFrozen {
+            document_id: "d".to_string(),
+            frozen: true,
+        };
+        assert!(snippet.capability() == Some(Capability::Snippets));
+        assert!(result.capability() == Some(Capability::RemoteActions));
+        assert!(receipt().capability() == Some(Capability::Receipts));
+        assert!(frozen.capability() == Some(Capability::Frozen));
+        assert!(Message::Unsupported.capability().is_none());
+    }
+
+    #[test]
+    fn clients_older_than_the_minimum_are_refused() {
+        for client in 0..MIN_PROTOCOL_VERSION {
+            assert!(Negotiated::with(client, None).is_err());
+            assert!(Negotiated::with(client, Some(&listed(PROTOCOL_VERSION))).is_err());
+        }
+    }
+
+    #[test]
+    fn future_clients_are_downgraded_to_this_version() {
+        for client in PROTOCOL_VERSION + 1..=PROTOCOL_VERSION + 3 {
+            let mut names = listed(PROTOCOL_VERSION);
+            names.push("telepathy".to_string());
+            let negotiated = Negotiated::with(client, Some(&names)).unwrap();
+            assert_eq!(negotiated.protocol, PROTOCOL_VERSION);
+            assert_eq!(negotiated.capabilities.len(), Capability::ALL.len());
+
+            let silent = Negotiated::with(client, None).unwrap();
+            assert_eq!(silent.protocol, PROTOCOL_VERSION);
+            assert_eq!(silent.capabilities.len(), Capability::ALL.len());
+        }
+    }
+
+    #[test]
+    fn legacy_clients_get_version_one_capabilities() {
+        let legacy = Negotiated::legacy();
+        assert_eq!(legacy.protocol, 1);
+        assert!(legacy.allows(Capability::Magnifier));
+        assert!(!legacy.allows(Capability::Lifecycle));
+        assert!(!legacy.allows(Capability::Receipts));
+    }
+
+    #[test]
+    fn unknown_message_types_are_unsupported() {
+        let message: Message = serde_json::from_str(r#"{"type":"hologram","depth":3}"#).unwrap();
+        assert!(matches!(message, Message::Unsupported));
+        let known: Message = serde_json::from_value(serde_json::to_value(receipt()).unwrap()).unwrap();
+        assert!(matches!(known, Message::Receipt { seq: 1, viewed: true, .. }));
+    }
+
+    #[test]
+    fn snapshots_from_older_hosts_are_not_frozen() {
+        let json = r#"{"type":"snapshot","documentId":"d","seq":2,"text":"x","language":null}"#;
+        let message: Message = serde_json::from_str(json).unwrap();
+        assert!(matches!(message, Message::Snapshot { seq: 2, frozen: false, .. }));
+    }
+}
@@ -0,0 +1,175 @@
+//! Quick Look previews of ShareCode's own files, which the system can only show as raw bytes.
+//!
+//! `preview_file` renders a readable page for the file (what it is, whether it checks out, and for
+//! session snapshots the documents in it, highlighted) and shows that page in the Quick Look panel.
+//! Previewing from Finder with the space bar needs a Quick Look extension bundled with the app,
+//! which the build does not produce yet; this is what the app shows in the meantime.
+
+#[cfg(target_os = "macos")]
+use std::fs;
+use std::path::PathBuf;
+
+use crate::artifact::ArtifactReport;
+use crate::export::{self, ThemeName};
+use crate::richcopy::{self, escape_html};
+use crate::snapshot;
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn kind_name(kind: &str) -> &str {
+    match kind {
+        "sessionSnapshot" => "Session snapshot",
+        "recording" => "Recording",
+        "offlineBundle" => "Offline bundle",
+        "backup" => "Backup",
+        "gifRecording" => "GIF recording",
+        "webmRecording" => "WebM recording",
+        "replay" => "Replay",
+        "pdf" => "PDF",
+        _ => "Unknown file",
+    }
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn row(out: &mut String, label: &str, value: &str) {
+    out.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>", escape_html(label), escape_html(value)));
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn render(path: &str, report: &ArtifactReport) -> String {
+    let name = PathBuf::from(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    let mut out = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title><style>\
+         body{{font-family:-apple-system,sans-serif;margin:24px}}\
+         th{{text-align:left;padding-right:16px;color:#666;font-weight:normal}}\
+         h2{{font-size:14px;margin-top:24px}}</style></head><body><h1>{}</h1><table>",
+        escape_html(&name),
+        escape_html(kind_name(&report.kind))
+    );
+    row(&mut out, "File", &name);
+    row(&mut out, "Size", &format!("{} bytes", report.size));
+    if let Some(version) = &report.version {
+        row(&mut out, "Format version", version);
+    }
+    for (key, value) in &report.metadata {
+        row(&mut out, key, value);
+    }
+    row(&mut out, "Integrity", if report.valid { "OK" } else { "Failed" });
+    for problem in &report.problems {
+        row(&mut out, "Problem", problem);
+    }
+    out.push_str("</table>");
+
+    match report.kind.as_str() {
+        "sessionSnapshot" if report.valid => match snapshot::read(path) {
+            Ok(documents) => {
+                let theme = export::theme(ThemeName::default());
+                for document in documents {
+                    let title = document.name.as_deref().unwrap_or("Untitled");
+                    out.push_str(&format!("<h2>{}</h2>", escape_html(title)));
+                    let language = document.language.as_deref();
+                    let lines = export::styled_lines(&document.text, language, richcopy::TAB_WIDTH);
+                    out.push_str(&richcopy::html(&lines, &theme));
+                }
+            }
+            Err(e) => out.push_str(&format!("<p>{}</p>", escape_html(&e))),
+        },
+        "offlineBundle" | "backup" => {
+            out.push_str("<p>The contents are encrypted. Open the file in ShareCode to see them.</p>")
+        }
+        _ => {}
+    }
+    out.push_str("</body></html>");
+    out
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::sync::{Mutex, Once, OnceLock};
+
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::declare::ClassDecl;
+    use objc::runtime::{Class, Object, Sel};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    #[link(name = "Quartz", kind = "framework")]
+    extern "C" {}
+
+    /// The page the panel shows.
+    static ITEM: Mutex<String> = Mutex::new(String::new());
+
+    extern "C" fn item_count(_this: &Object, _cmd: Sel, _panel: id) -> isize {
+        1
+    }
+
+    extern "C" fn item_at(_this: &Object, _cmd: Sel, _panel: id, _index: isize) -> id {
+        let path = ITEM.lock().unwrap().clone();
+        unsafe {
+            let path = NSString::alloc(nil).init_str(&path);
+            let _: id = msg_send![path, autorelease];
+            // NSURL is a QLPreviewItem
+            msg_send![class!(NSURL), fileURLWithPath: path]
+        }
+    }
+
+    /// A `QLPreviewPanelDataSource` serving `ITEM`.
+    fn data_source_class() -> &'static Class {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            let mut decl = ClassDecl::new("ShareCodePreviewSource", class!(NSObject)).unwrap();
+            unsafe {
+                decl.add_method(
+                    sel!(numberOfPreviewItemsInPreviewPanel:),
+                    item_count as extern "C" fn(&Object, Sel, id) -> isize,
+                );
+                decl.add_method(
+                    sel!(previewPanel:previewItemAtIndex:),
+                    item_at as extern "C" fn(&Object, Sel, id, isize) -> id,
+                );
+            }
+            decl.register();
+        });
+        Class::get("ShareCodePreviewSource").unwrap()
+    }
+
+    /// Show `path` in the Quick Look panel. Must run on the main thread.
+    pub unsafe fn show(path: String) {
+        *ITEM.lock().unwrap() = path;
+        // Created once and kept, as the panel does not retain its data source
+        static SOURCE: OnceLock<usize> = OnceLock::new();
+        let source = *SOURCE.get_or_init(|| {
+            let source: id = msg_send![data_source_class(), new];
+            source as usize
+        }) as id;
+        let panel: id = msg_send![class!(QLPreviewPanel), sharedPreviewPanel];
+        // No window of the app asks to control the panel, so the source is set directly
+        let _: () = msg_send![panel, makeKeyAndOrderFront: nil];
+        let _: () = msg_send![panel, setDataSource: source];
+        let _: () = msg_send![panel, reloadData];
+    }
+}
+
+/// Show a readable preview of a file ShareCode wrote in the Quick Look panel (macOS only).
+#[tauri::command]
+pub fn preview_file(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        let report = crate::artifact::validate_file(path.clone())?;
+        // One page at a time, overwritten by the next preview
+        let page = std::env::temp_dir().join("sharecode-preview.html");
+        fs::write(&page, render(&path, &report))
+            .map_err(|e| format!("Failed to write {}: {}", page.display(), e))?;
+        let page = page.to_string_lossy().into_owned();
+        app.run_on_main_thread(move || unsafe { macos::show(page) })
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (app, path);
+        Err("Quick Look is only available on macOS".to_string())
+    }
+}
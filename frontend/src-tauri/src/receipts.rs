@@ -0,0 +1,131 @@
+//! Read receipts: which version of each shared document every participant has received and has
+//! had on screen, so a presenter can tell everyone is caught up before moving on.
+//!
+//! Viewers send a `Receipt` for the newest timeline position they hold, marked `viewed` once it
+//! was displayed while their window was visible. Receipts only move forward. The host reads the
+//! state of a document with `get_view_status`, and is told of changes as `view-status-changed`.
+//! Participants whose client sends no receipts show as never having received anything.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::documents::DocumentStore;
+use crate::events::{self, Channel};
+use crate::session::{Role, Session};
+
+/// Per document, so one busy document doesn't hide the others' updates.
+const VIEW_STATUS_CHANGED: Channel = Channel::latest_wins("view-status-changed");
+
+#[derive(Clone, Copy, Default)]
+struct Receipt {
+    received: Option<u64>,
+    viewed: Option<u64>,
+}
+
+/// Receipts by document, then by participant.
+#[derive(Default)]
+pub struct Receipts {
+    docs: Mutex<HashMap<String, HashMap<String, Receipt>>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantView {
+    pub participant_id: String,
+    pub name: String,
+    /// Newest timeline position received, if any.
+    pub received_seq: Option<u64>,
+    /// Newest timeline position displayed, if any.
+    pub viewed_seq: Option<u64>,
+    /// Has seen the current version.
+    pub caught_up: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewStatus {
+    pub document_id: String,
+    /// The document's current timeline position.
+    pub seq: u64,
+    /// Everyone but the host, in order of arrival.
+    pub participants: Vec<ParticipantView>,
+    pub all_caught_up: bool,
+}
+
+fn status(app: &AppHandle, session: &Session, receipts: &Receipts, document_id: &str) -> Result<ViewStatus, String> {
+    let seq = app.state::<DocumentStore>().with(document_id, |doc| doc.timeline.seq())?;
+    let docs = receipts.docs.lock().unwrap();
+    let known = docs.get(document_id);
+    let participants: Vec<ParticipantView> = session
+        .participants()
+        .into_iter()
+        .filter(|p| p.role != Role::Host)
+        .map(|p| {
+            let receipt = known.and_then(|k| k.get(&p.id)).copied().unwrap_or_default();
+            ParticipantView {
+                caught_up: receipt.viewed.is_some_and(|viewed| viewed >= seq),
+                participant_id: p.id,
+                name: p.name,
+                received_seq: receipt.received,
+                viewed_seq: receipt.viewed,
+            }
+        })
+        .collect();
+    Ok(ViewStatus {
+        document_id: document_id.to_string(),
+        seq,
+        all_caught_up: participants.iter().all(|p| p.caught_up),
+        participants,
+    })
+}
+
+/// Called by the session for a `Receipt`. Returns false if it told the host nothing new.
+pub fn record(
+    app: &AppHandle,
+    session: &Session,
+    participant_id: &str,
+    document_id: &str,
+    seq: u64,
+    viewed: bool,
+) -> Result<bool, String> {
+    session.participant(participant_id)?;
+    let receipts = app.state::<Receipts>();
+    {
+        let mut docs = receipts.docs.lock().unwrap();
+        let receipt = docs
+            .entry(document_id.to_string())
+            .or_default()
+            .entry(participant_id.to_string())
+            .or_default();
+        let before = *receipt;
+        receipt.received = receipt.received.max(Some(seq));
+        if viewed {
+            receipt.viewed = receipt.viewed.max(Some(seq));
+        }
+        if (before.received, before.viewed) == (receipt.received, receipt.viewed) {
+            return Ok(false);
+        }
+    }
+    let status = status(app, session, &receipts, document_id)?;
+    events::emit_keyed(app, &VIEW_STATUS_CHANGED, document_id, status)?;
+    Ok(true)
+}
+
+/// Drop every receipt, when a new session starts.
+pub fn clear(app: &AppHandle) {
+    app.state::<Receipts>().docs.lock().unwrap().clear();
+}
+
+/// Who has received and seen which version of `doc_id`.
+#[tauri::command]
+pub fn get_view_status(
+    app: AppHandle,
+    session: State<'_, Session>,
+    receipts: State<'_, Receipts>,
+    doc_id: String,
+) -> Result<ViewStatus, String> {
+    status(&app, &session, &receipts, &doc_id)
+}
@@ -0,0 +1,201 @@
+//! Recently opened files: the app's own list, and the OS's recent documents (the Dock menu's Open
+//! Recent on macOS, the taskbar jump list on Windows).
+//!
+//! Opening a document, snapshot or bundle records it in both. The Dock only lists files of types
+//! the app declares, which is `.sharecode`; other documents still appear in the app's list.
+//! Linux keeps recents in GTK's `recently-used.xbel`, which the app does not write, so there the
+//! list is the app's alone. `clear` empties both and is what wiping the app's traces should call.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::storage;
+
+const RECENT_FILE: &str = "recent.json";
+const MAX_RECENT: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecentKind {
+    Document,
+    Snapshot,
+    Bundle,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentFile {
+    pub path: String,
+    pub kind: RecentKind,
+    /// Seconds since the Unix epoch.
+    pub opened_at: u64,
+}
+
+#[derive(Default)]
+pub struct RecentFiles {
+    lock: Mutex<()>,
+}
+
+impl RecentFiles {
+    fn get(&self, app: &AppHandle) -> Result<Vec<RecentFile>, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, RECENT_FILE)
+    }
+
+    fn update(&self, app: &AppHandle, f: impl FnOnce(&mut Vec<RecentFile>)) -> Result<Vec<RecentFile>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut recent: Vec<RecentFile> = storage::load_json(app, RECENT_FILE)?;
+        f(&mut recent);
+        storage::save_json(app, RECENT_FILE, &recent)?;
+        Ok(recent)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use std::ffi::c_void;
+    use std::path::Path;
+
+    use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+    use windows::Win32::UI::Shell::{
+        ApplicationDestinations, IApplicationDestinations, SHAddToRecentDocs, SHARD_PATHW,
+    };
+
+    pub fn add(path: &Path) {
+        let wide: Vec<u16> = path.as_os_str().to_string_lossy().encode_utf16().chain([0]).collect();
+        unsafe { SHAddToRecentDocs(SHARD_PATHW.0 as u32, Some(wide.as_ptr() as *const c_void)) };
+    }
+
+    /// Empty this app's jump list. Clearing through `SHAddToRecentDocs` would clear every app's.
+    pub fn clear() -> Result<(), String> {
+        unsafe {
+            let destinations: IApplicationDestinations =
+                CoCreateInstance(&ApplicationDestinations, None, CLSCTX_INPROC_SERVER)
+                    .map_err(|e| format!("Failed to open the jump list: {}", e))?;
+            destinations
+                .RemoveAllDestinations()
+                .map_err(|e| format!("Failed to clear the jump list: {}", e))
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::path::Path;
+
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    pub unsafe fn add(path: &Path) {
+        let path = NSString::alloc(nil).init_str(&path.to_string_lossy());
+        let url: id = msg_send![class!(NSURL), fileURLWithPath: path];
+        let controller: id = msg_send![class!(NSDocumentController), sharedDocumentController];
+        let _: () = msg_send![controller, noteNewRecentDocumentURL: url];
+        let _: () = msg_send![path, release];
+    }
+
+    pub unsafe fn clear() {
+        let controller: id = msg_send![class!(NSDocumentController), sharedDocumentController];
+        let _: () = msg_send![controller, clearRecentDocuments: nil];
+    }
+}
+
+/// Add the OS's recent documents. AppKit wants the main thread.
+fn add_to_os(app: &AppHandle, path: &Path) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = app;
+        win32::add(path);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let path = path.to_path_buf();
+        if let Err(e) = app.run_on_main_thread(move || unsafe { macos::add(&path) }) {
+            log::warn!("Failed to add a recent document: {}", e);
+        }
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let _ = (app, path);
+}
+
+fn clear_os(app: &AppHandle) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = app;
+        win32::clear()
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        app.run_on_main_thread(|| unsafe { macos::clear() }).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        let _ = app;
+        Ok(())
+    }
+}
+
+fn changed(app: &AppHandle, recent: Vec<RecentFile>) {
+    if let Err(e) = app.emit("recent-files-changed", recent) {
+        log::warn!("Failed to emit recent-files-changed: {}", e);
+    }
+}
+
+/// Record that `path` was opened. Failing to record it never fails the open.
+pub fn note(app: &AppHandle, path: &Path, kind: RecentKind) {
+    let path_string = path.to_string_lossy().into_owned();
+    let updated = app.state::<RecentFiles>().update(app, |recent| {
+        recent.retain(|file| file.path != path_string);
+        recent.insert(
+            0,
+            RecentFile {
+                path: path_string.clone(),
+                kind,
+                opened_at: storage::now_secs(),
+            },
+        );
+        recent.truncate(MAX_RECENT);
+    });
+    match updated {
+        Ok(recent) => changed(app, recent),
+        Err(e) => log::warn!("Failed to record a recent file: {}", e),
+    }
+    add_to_os(app, path);
+}
+
+/// Forget every recent file, in the app and in the OS.
+pub fn clear(app: &AppHandle) -> Result<(), String> {
+    let recent = app.state::<RecentFiles>().update(app, Vec::clear)?;
+    changed(app, recent);
+    clear_os(app)
+}
+
+#[tauri::command]
+pub fn get_recent_files(app: AppHandle, recents: State<'_, RecentFiles>) -> Result<Vec<RecentFile>, String> {
+    recents.get(&app)
+}
+
+/// Take one file off the app's list; the OS's recents keep it until they are cleared.
+#[tauri::command]
+pub fn remove_recent_file(
+    app: AppHandle,
+    recents: State<'_, RecentFiles>,
+    path: String,
+) -> Result<Vec<RecentFile>, String> {
+    let recent = recents.update(&app, |recent| recent.retain(|file| file.path != path))?;
+    changed(&app, recent.clone());
+    Ok(recent)
+}
+
+#[tauri::command]
+pub fn clear_recent_files(app: AppHandle) -> Result<(), String> {
+    clear(&app)
+}
@@ -0,0 +1,629 @@
+//! Recording the shared document view as an animated GIF or a WebM clip.
+//!
+//! Frames are drawn by the backend from the document's timeline rather than captured from the
+//! screen, so clips look the same on every platform and contain nothing but the code. Starting a
+//! recording marks a timeline position; stopping it renders every change made since, with idle
+//! stretches shortened so the clip keeps moving.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+
+use font8x8::{UnicodeFonts, BASIC_FONTS, LATIN_FONTS};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::accessibility;
+use crate::documents::DocumentStore;
+use crate::export::{self, Rgb, Run, Theme, ThemeName, Viewport};
+use crate::framebuf::{self, FrameReader, FrameWriter, Overflow};
+#[cfg(feature = "gpu")]
+use crate::gpu;
+use crate::history::ReplayStep;
+use crate::magnifier::{FocusRegion, Magnifier};
+use crate::syntax::HighlightClass;
+use crate::workers::{Cancel, Priority, WorkerPool};
+
+/// Glyphs are 8×8 bitmaps drawn at double height, the proportions of a terminal cell.
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 16;
+const PADDING: usize = 8;
+const MAX_FPS: u32 = 30;
+/// Longer recordings are rendered at a lower frame rate rather than refused.
+const MAX_FRAMES: usize = 3000;
+/// How long the starting state shows before the first change, and the end state after the last.
+const LEAD_IN_MS: f64 = 500.0;
+const HOLD_END_MS: f64 = 1500.0;
+/// Frames drawn ahead of the encoder.
+const FRAME_SLOTS: usize = 4;
+
+// Palette indices; the palette itself comes from the theme.
+const BACKGROUND: u8 = 0;
+const FOREGROUND: u8 = 1;
+const GUTTER: u8 = 2;
+const CARET: u8 = 9;
+const CURRENT_LINE: u8 = 10;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipFormat {
+    #[default]
+    Gif,
+    Webm,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RecordingOptions {
+    pub format: ClipFormat,
+    pub theme: ThemeName,
+    /// Size of the visible area, in characters.
+    pub columns: usize,
+    pub rows: usize,
+    pub fps: u32,
+    /// Playback speed relative to the original typing.
+    pub speed: f64,
+    /// Pauses longer than this are shortened to it, before `speed` applies.
+    pub max_idle_ms: u64,
+    pub line_numbers: bool,
+    /// Integer pixel scale, 1 to 3.
+    pub scale: usize,
+    /// Show only the host's magnified region, enlarged, when it is on this document.
+    pub magnify: bool,
+    /// Rasterize on the GPU when built with the `gpu` feature and one is available.
+    #[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+    pub gpu: bool,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self {
+            format: ClipFormat::Gif,
+            theme: ThemeName::Dark,
+            columns: 80,
+            rows: 24,
+            fps: 10,
+            speed: 1.0,
+            max_idle_ms: 1000,
+            line_numbers: true,
+            scale: 1,
+            magnify: true,
+            gpu: true,
+        }
+    }
+}
+
+struct Recording {
+    start_seq: u64,
+    options: RecordingOptions,
+}
+
+#[derive(Default)]
+pub struct Recorder {
+    active: Mutex<HashMap<String, Recording>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    pub path: String,
+    pub frames: usize,
+    pub duration_ms: u64,
+    pub width: usize,
+    pub height: usize,
+    /// `gpu` or `cpu`.
+    pub renderer: &'static str,
+}
+
+fn class_index(class: Option<HighlightClass>) -> u8 {
+    match class {
+        None => FOREGROUND,
+        Some(HighlightClass::Keyword) => 3,
+        Some(HighlightClass::String) => 4,
+        Some(HighlightClass::Number) => 5,
+        Some(HighlightClass::Comment) => 6,
+        Some(HighlightClass::Type) => 7,
+        Some(HighlightClass::Function) => 8,
+    }
+}
+
+fn palette(theme: &Theme) -> Vec<Rgb> {
+    let mut colors = vec![theme.background, theme.foreground, theme.gutter];
+    colors.extend(
+        [
+            HighlightClass::Keyword,
+            HighlightClass::String,
+            HighlightClass::Number,
+            HighlightClass::Comment,
+            HighlightClass::Type,
+            HighlightClass::Function,
+        ]
+        .map(|class| theme.color(Some(class))),
+    );
+    colors.push(theme.foreground);
+    colors.push(theme.current_line());
+    colors
+}
+
+fn glyph(c: char) -> [u8; 8] {
+    BASIC_FONTS
+        .get(c)
+        .or_else(|| LATIN_FONTS.get(c))
+        .or_else(|| BASIC_FONTS.get('?'))
+        .unwrap_or_default()
+}
+
+/// Draws frames of a fixed-size window that scrolls to follow the caret.
+struct Canvas {
+    options: RecordingOptions,
+    language: Option<String>,
+    tab_width: usize,
+    gutter: usize,
+    width: usize,
+    height: usize,
+    viewport: Viewport,
+    scratch: Vec<u8>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<&'static gpu::Gpu>,
+}
+
+/// A frame as a grid of character cells, before it is rasterized.
+struct Layout {
+    /// Glyph and palette index of each cell, `gutter + columns` to a row.
+    cells: Vec<([u8; 8], u8)>,
+    current_row: Option<usize>,
+    /// Row and left pixel of the caret.
+    caret: Option<(usize, usize)>,
+}
+
+impl Canvas {
+    fn new(
+        options: RecordingOptions,
+        viewport: Viewport,
+        language: Option<String>,
+        tab_width: usize,
+        max_lines: usize,
+    ) -> Self {
+        let gutter = if options.line_numbers {
+            max_lines.to_string().len().max(3) + 1
+        } else {
+            0
+        };
+        // Video encoders want even dimensions.
+        let width = ((gutter + options.columns) * CELL_WIDTH + 2 * PADDING).next_multiple_of(2);
+        let height = (options.rows * CELL_HEIGHT + 2 * PADDING).next_multiple_of(2);
+        Self {
+            #[cfg(feature = "gpu")]
+            gpu: options.gpu.then(gpu::shared).flatten(),
+            viewport,
+            options,
+            language,
+            tab_width,
+            gutter,
+            width,
+            height,
+            scratch: Vec::new(),
+        }
+    }
+
+    fn grid_columns(&self) -> usize {
+        self.gutter + self.options.columns
+    }
+
+    /// Lay out `text` with the caret at byte offset `caret`, scrolling as needed.
+    fn layout(&mut self, text: &str, caret: usize) -> Layout {
+        let (line, column) = export::caret_position(text, caret, self.tab_width);
+        self.viewport.follow(line, column);
+        let Viewport { rows, columns, top, left, .. } = self.viewport;
+        let grid_columns = self.grid_columns();
+        let mut cells = vec![([0; 8], BACKGROUND); grid_columns * self.options.rows];
+        let lines = export::styled_lines(text, self.language.as_deref(), self.tab_width);
+        for (row, runs) in lines.iter().skip(top).take(rows).enumerate() {
+            let cells = &mut cells[row * grid_columns..][..grid_columns];
+            if self.gutter > 0 {
+                let number = format!("{:>width$}", top + row + 1, width = self.gutter - 1);
+                for (cell, c) in cells.iter_mut().zip(number.chars()) {
+                    *cell = (glyph(c), GUTTER);
+                }
+            }
+            let mut cell = 0;
+            for Run { text, class } in runs {
+                for c in text.chars() {
+                    if cell >= left && cell < left + columns && c != ' ' {
+                        cells[self.gutter + cell - left] = (glyph(c), class_index(*class));
+                    }
+                    cell += 1;
+                }
+            }
+        }
+        // A fixed viewport may not show the caret at all.
+        let caret_cell = self.viewport.locate(line, column);
+        Layout {
+            cells,
+            current_row: caret_cell.map(|(row, _)| row),
+            caret: caret_cell.map(|(row, cell)| (row, PADDING + (self.gutter + cell) * CELL_WIDTH)),
+        }
+    }
+
+    fn fill(&self, pixels: &mut [u8], x: usize, y: usize, w: usize, h: usize, color: u8) {
+        for row in y..(y + h).min(self.height) {
+            let start = row * self.width + x.min(self.width);
+            let end = row * self.width + (x + w).min(self.width);
+            pixels[start..end].fill(color);
+        }
+    }
+
+    fn draw_char(&self, pixels: &mut [u8], glyph: &[u8; 8], x: usize, y: usize, color: u8) {
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..8 {
+                if bits & (1 << col) != 0 {
+                    self.fill(pixels, x + col, y + row * 2, 1, 2, color);
+                }
+            }
+        }
+    }
+
+    /// Render `text` with the caret at byte offset `caret` into `out`, on the GPU if there is
+    /// one and on the CPU otherwise.
+    fn frame(&mut self, text: &str, caret: usize, out: &mut Vec<u8>) {
+        let layout = self.layout(text, caret);
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = self.gpu {
+            let grid = gpu::Grid {
+                width: self.width,
+                height: self.height,
+                scale: self.options.scale,
+                columns: self.grid_columns(),
+                rows: self.options.rows,
+                cells: &layout.cells,
+                current_row: layout.current_row,
+                caret: layout.caret,
+                padding: PADDING,
+                cell_width: CELL_WIDTH,
+                cell_height: CELL_HEIGHT,
+                colors: [BACKGROUND, CURRENT_LINE, CARET],
+            };
+            match gpu.rasterize(&grid, out) {
+                Ok(()) => return,
+                Err(e) => {
+                    log::warn!("GPU rasterization failed, drawing on the CPU: {}", e);
+                    self.gpu = None;
+                }
+            }
+        }
+        self.rasterize(&layout, out);
+    }
+
+    fn rasterize(&mut self, layout: &Layout, out: &mut Vec<u8>) {
+        // Unscaled frames are drawn straight into `out`; scaled ones into a reused scratch buffer.
+        let scale = self.options.scale;
+        let mut pixels = if scale == 1 {
+            std::mem::take(out)
+        } else {
+            std::mem::take(&mut self.scratch)
+        };
+        pixels.clear();
+        pixels.resize(self.width * self.height, BACKGROUND);
+        if let Some(row) = layout.current_row {
+            self.fill(&mut pixels, 0, PADDING + row * CELL_HEIGHT, self.width, CELL_HEIGHT, CURRENT_LINE);
+        }
+        let grid_columns = self.grid_columns();
+        for (i, (glyph, color)) in layout.cells.iter().enumerate() {
+            let (row, column) = (i / grid_columns, i % grid_columns);
+            let (x, y) = (PADDING + column * CELL_WIDTH, PADDING + row * CELL_HEIGHT);
+            self.draw_char(&mut pixels, glyph, x, y, *color);
+        }
+        if let Some((row, x)) = layout.caret {
+            self.fill(&mut pixels, x, PADDING + row * CELL_HEIGHT, 2, CELL_HEIGHT, CARET);
+        }
+
+        if scale == 1 {
+            *out = pixels;
+            return;
+        }
+        out.clear();
+        for row in pixels.chunks_exact(self.width) {
+            let start = out.len();
+            for &p in row {
+                out.extend(std::iter::repeat(p).take(scale));
+            }
+            let end = out.len();
+            for _ in 1..scale {
+                out.extend_from_within(start..end);
+            }
+        }
+        self.scratch = pixels;
+    }
+
+    /// Size of each frame in pixels, after scaling.
+    fn size(&self) -> (usize, usize) {
+        (self.width * self.options.scale, self.height * self.options.scale)
+    }
+
+    /// Which rasterizer drew the frames, as reported in `RecordingInfo`.
+    fn renderer(&self) -> &'static str {
+        #[cfg(feature = "gpu")]
+        if self.gpu.is_some() {
+            return "gpu";
+        }
+        "cpu"
+    }
+}
+
+/// When each step plays on the compressed clock, the clip's length, and the length of a tick.
+fn clock(steps: &[ReplayStep], options: &RecordingOptions) -> (Vec<f64>, f64, f64) {
+    let times = export::playback_times(steps, LEAD_IN_MS, options.max_idle_ms, options.speed);
+    let total = times.last().copied().unwrap_or(LEAD_IN_MS) + HOLD_END_MS;
+    let tick = (1000.0 / options.fps as f64).max(total / MAX_FRAMES as f64);
+    (times, total, tick)
+}
+
+/// Play `steps` over `base`, one frame per tick that shows a change, each published with the
+/// number of ticks it stays on screen. Stops early if the encoder gives up or on cancellation.
+fn render_frames(
+    canvas: &mut Canvas,
+    base: String,
+    steps: &[ReplayStep],
+    (times, total, tick): (&[f64], f64, f64),
+    frames: FrameWriter<usize>,
+    cancel: &Cancel,
+) {
+    let mut text = base;
+    let mut caret = 0;
+    let Some(mut current) = frames.buffer() else {
+        return;
+    };
+    canvas.frame(&text, caret, &mut current);
+    let mut ticks = 1;
+    let mut next = 0;
+    for k in 1..(total / tick).ceil() as usize {
+        let now = k as f64 * tick;
+        if next >= steps.len() || times[next] > now {
+            ticks += 1;
+            continue;
+        }
+        while next < steps.len() && times[next] <= now {
+            steps[next].apply(&mut text);
+            caret = steps[next].start + steps[next].insert.len();
+            next += 1;
+        }
+        if cancel.is_cancelled() {
+            return;
+        }
+        let Some(mut buffer) = frames.buffer() else {
+            return;
+        };
+        canvas.frame(&text, caret, &mut buffer);
+        if !frames.publish(std::mem::replace(&mut current, buffer), ticks) {
+            return;
+        }
+        ticks = 1;
+    }
+    frames.publish(current, ticks);
+}
+
+/// Frames and ticks written by an encoder.
+type Written = (usize, usize);
+
+fn write_gif(
+    path: &Path,
+    (width, height): (usize, usize),
+    colors: &[Rgb],
+    frames: &FrameReader<usize>,
+    tick: f64,
+    cancel: &Cancel,
+) -> Result<Written, String> {
+    let mut palette: Vec<u8> = colors.iter().flatten().copied().collect();
+    palette.resize(16 * 3, 0);
+    let too_large = || "Clip is too large for a GIF; use fewer rows or columns".to_string();
+    let (width, height) = (
+        u16::try_from(width).map_err(|_| too_large())?,
+        u16::try_from(height).map_err(|_| too_large())?,
+    );
+
+    let file = File::create(path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &palette).map_err(|e| e.to_string())?;
+    // Loop unless the system asks for reduced motion; then play once and stop on the last frame.
+    let repeat = if accessibility::current().reduce_motion {
+        gif::Repeat::Finite(0)
+    } else {
+        gif::Repeat::Infinite
+    };
+    encoder.set_repeat(repeat).map_err(|e| e.to_string())?;
+    let mut written = (0, 0);
+    while let Some((pixels, ticks)) = frames.next() {
+        cancel.check()?;
+        let gif_frame = gif::Frame {
+            width,
+            height,
+            // GIF delays are in hundredths of a second; browsers clamp anything under 2.
+            delay: ((ticks as f64 * tick / 10.0).round() as u16).max(2),
+            buffer: Cow::Borrowed(&pixels),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&gif_frame).map_err(|e| e.to_string())?;
+        frames.recycle(pixels);
+        written = (written.0 + 1, written.1 + ticks);
+    }
+    cancel.check()?;
+    Ok(written)
+}
+
+/// Pipe raw frames through `ffmpeg`, which must be on the `PATH`, to get a VP9 WebM.
+fn write_webm(
+    path: &Path,
+    (width, height): (usize, usize),
+    colors: &[Rgb],
+    frames: &FrameReader<usize>,
+    tick: f64,
+    cancel: &Cancel,
+) -> Result<Written, String> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+        .args(["-s", &format!("{}x{}", width, height), "-r", &format!("{:.3}", 1000.0 / tick)])
+        .args(["-i", "-", "-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("WebM recording needs ffmpeg on the PATH ({}); record a GIF instead", e))?;
+
+    let mut stdin = child.stdin.take().ok_or("Missing ffmpeg stdin")?;
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    let mut written = (0, 0);
+    let mut streamed = Ok(());
+    while let Some((pixels, ticks)) = frames.next() {
+        if cancel.is_cancelled() {
+            break;
+        }
+        rgb.clear();
+        rgb.extend(pixels.iter().flat_map(|&i| colors[i as usize]));
+        frames.recycle(pixels);
+        streamed = (0..ticks).try_for_each(|_| stdin.write_all(&rgb));
+        if streamed.is_err() {
+            break;
+        }
+        written = (written.0 + 1, written.1 + ticks);
+    }
+    drop(stdin);
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    cancel.check()?;
+    if !output.status.success() {
+        return Err(format!("ffmpeg failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    streamed.map_err(|e| format!("Failed to stream frames to ffmpeg: {}", e))?;
+    Ok(written)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render(
+    path: &Path,
+    mut options: RecordingOptions,
+    focus: Option<FocusRegion>,
+    language: Option<String>,
+    tab_width: usize,
+    base: String,
+    steps: Vec<ReplayStep>,
+    cancel: &Cancel,
+) -> Result<RecordingInfo, String> {
+    let mut text = base.clone();
+    let mut max_lines = text.lines().count();
+    for step in &steps {
+        step.apply(&mut text);
+        max_lines = max_lines.max(text.lines().count());
+    }
+
+    let theme = export::theme(options.theme);
+    let colors = palette(&theme);
+    // A magnified region is drawn at a larger pixel scale, so the clip keeps about its size.
+    let viewport = match &focus {
+        Some(region) => {
+            options.scale *= region.zoom(options.rows, options.columns).floor() as usize;
+            options.rows = region.rows;
+            options.columns = region.columns;
+            region.viewport()
+        }
+        None => Viewport::new(options.rows, options.columns),
+    };
+    let mut canvas = Canvas::new(options.clone(), viewport, language, tab_width, max_lines);
+    let size = canvas.size();
+    let (times, total, tick) = clock(&steps, &options);
+
+    // Frames are drawn on one thread while the encoder writes the previous ones on this one.
+    let (writer, reader) = framebuf::channel("recording", FRAME_SLOTS, size.0 * size.1, Overflow::Block);
+    let tmp = path.with_extension("tmp");
+    let written = thread::scope(|scope| {
+        let (canvas, steps) = (&mut canvas, &steps);
+        scope.spawn(move || render_frames(canvas, base, steps, (&times, total, tick), writer, cancel));
+        let written = match options.format {
+            ClipFormat::Gif => write_gif(&tmp, size, &colors, &reader, tick, cancel),
+            ClipFormat::Webm => write_webm(&tmp, size, &colors, &reader, tick, cancel),
+        };
+        // Lets the drawing thread stop if the encoder failed part way.
+        drop(reader);
+        written
+    });
+    let (frames, ticks) = match written {
+        Ok(written) => written,
+        Err(e) => {
+            fs::remove_file(&tmp).ok();
+            return Err(e);
+        }
+    };
+    fs::rename(&tmp, path).map_err(|e| format!("Failed to finalize {}: {}", path.display(), e))?;
+
+    Ok(RecordingInfo {
+        path: path.to_string_lossy().into_owned(),
+        frames,
+        duration_ms: (ticks as f64 * tick) as u64,
+        width: size.0,
+        height: size.1,
+        renderer: canvas.renderer(),
+    })
+}
+
+/// Start recording a document's view. Returns the timeline position the clip starts from.
+#[tauri::command]
+pub fn start_recording(
+    store: State<'_, DocumentStore>,
+    recorder: State<'_, Recorder>,
+    doc_id: String,
+    options: Option<RecordingOptions>,
+) -> Result<u64, String> {
+    let options = options.unwrap_or_default();
+    if !(1..=MAX_FPS).contains(&options.fps) {
+        return Err(format!("Frame rate must be between 1 and {}", MAX_FPS));
+    }
+    if !(1..=3).contains(&options.scale) || options.columns == 0 || options.rows == 0 {
+        return Err("Invalid clip size".to_string());
+    }
+    if options.speed.is_nan() || options.speed <= 0.0 || options.speed > 100.0 {
+        return Err("Speed must be above 0 and at most 100".to_string());
+    }
+    let start_seq = store.with(&doc_id, |doc| doc.timeline.seq())?;
+    recorder
+        .active
+        .lock()
+        .unwrap()
+        .insert(doc_id, Recording { start_seq, options });
+    Ok(start_seq)
+}
+
+/// Stop recording and write the clip to `path`.
+#[tauri::command]
+pub async fn stop_recording(
+    store: State<'_, DocumentStore>,
+    recorder: State<'_, Recorder>,
+    magnifier: State<'_, Magnifier>,
+    pool: State<'_, WorkerPool>,
+    doc_id: String,
+    path: String,
+) -> Result<RecordingInfo, String> {
+    let recording = recorder
+        .active
+        .lock()
+        .unwrap()
+        .remove(&doc_id)
+        .ok_or("This document is not being recorded")?;
+    let (language, tab_width, replay) = store.with(&doc_id, |doc| {
+        let replay = doc.timeline.replay(recording.start_seq, doc.timeline.seq());
+        (doc.language.clone(), doc.profile.tab_width as usize, replay)
+    })?;
+    let (base, steps) = replay?;
+    let focus = recording.options.magnify.then(|| magnifier.region_for(&doc_id)).flatten();
+    pool.run(Priority::Background, "Recording export", move |cancel| {
+        render(Path::new(&path), recording.options, focus, language, tab_width.max(1), base, steps, cancel)
+    })
+    .await
+}
+
+#[tauri::command]
+pub fn cancel_recording(recorder: State<'_, Recorder>, doc_id: String) -> bool {
+    recorder.active.lock().unwrap().remove(&doc_id).is_some()
+}
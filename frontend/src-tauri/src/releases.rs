@@ -0,0 +1,203 @@
+//! Releases: snippets and chat messages queued by the host to go out later, at a set time or
+//! when the host pulls a named trigger (e.g. `solution` once the exercise is over).
+//!
+//! The queue is kept in `releases.json`, so it survives a restart. A release whose time comes
+//! while no session is hosted waits for the next one. Snippets are read from the library when
+//! they go out, so edits made in the meantime are included, and are broadcast as usual. Chat
+//! lives in the webview, so a due message is handed back to it as `release-message` to send.
+//! Every release is reported as `release-sent`, or `release-failed` if it could not go out.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::protocol::Message;
+use crate::server::{self, SessionServer};
+use crate::session;
+use crate::snippets::{PortableSnippet, SnippetLibrary};
+use crate::storage::{self, new_id};
+
+const QUEUE_FILE: &str = "releases.json";
+const TICK: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ReleaseItem {
+    #[serde(rename_all = "camelCase")]
+    Snippet { snippet_id: String },
+    Message { text: String },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Release {
+    pub id: String,
+    pub item: ReleaseItem,
+    /// Seconds since the Unix epoch to release at.
+    pub at: Option<u64>,
+    /// Name of a trigger that releases it early, or at all when there is no `at`.
+    pub trigger: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReleaseMessage {
+    id: String,
+    text: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReleaseFailed {
+    release: Release,
+    error: String,
+}
+
+#[derive(Default)]
+pub struct Releases {
+    queue: Mutex<Vec<Release>>,
+}
+
+impl Releases {
+    pub fn load(app: &AppHandle) -> Self {
+        let queue = storage::load_json(app, QUEUE_FILE).unwrap_or_else(|e| {
+            log::warn!("Starting with no queued releases: {}", e);
+            Vec::new()
+        });
+        Self {
+            queue: Mutex::new(queue),
+        }
+    }
+
+    /// Apply `f` to the queue and save it.
+    fn update<R>(&self, app: &AppHandle, f: impl FnOnce(&mut Vec<Release>) -> R) -> Result<R, String> {
+        let mut queue = self.queue.lock().unwrap();
+        let result = f(&mut queue);
+        storage::save_json(app, QUEUE_FILE, &*queue)?;
+        Ok(result)
+    }
+
+    /// Take the releases `due` picks out of the queue.
+    fn take(&self, app: &AppHandle, due: impl Fn(&Release) -> bool) -> Result<Vec<Release>, String> {
+        // Checked first, as the scheduler asks every second
+        if !self.queue.lock().unwrap().iter().any(&due) {
+            return Ok(Vec::new());
+        }
+        self.update(app, |queue| {
+            let (taken, kept) = std::mem::take(queue).into_iter().partition(|release| due(release));
+            *queue = kept;
+            taken
+        })
+    }
+}
+
+fn send(app: &AppHandle, release: &Release) -> Result<(), String> {
+    match &release.item {
+        ReleaseItem::Snippet { snippet_id } => {
+            let snippet = PortableSnippet::from(app.state::<SnippetLibrary>().get(app, snippet_id)?);
+            session::broadcast(app, &Message::Snippet { snippet })
+        }
+        ReleaseItem::Message { text } => {
+            let message = ReleaseMessage {
+                id: release.id.clone(),
+                text: text.clone(),
+            };
+            app.emit("release-message", message).map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn release_all(app: &AppHandle, releases: Vec<Release>) {
+    for release in releases {
+        match send(app, &release) {
+            Ok(()) => {
+                if let Err(e) = app.emit("release-sent", &release) {
+                    log::warn!("Failed to emit release-sent: {}", e);
+                }
+            }
+            Err(error) => {
+                log::warn!("Failed to release {}: {}", release.id, error);
+                if let Err(e) = app.emit("release-failed", ReleaseFailed { release, error }) {
+                    log::warn!("Failed to emit release-failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+fn hosting(app: &AppHandle) -> bool {
+    server::get_session_server(app.state::<SessionServer>()).is_some()
+}
+
+/// Release whatever has come due, once a second, while a session is hosted.
+pub fn spawn_scheduler(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(TICK);
+        if !hosting(&app) {
+            continue;
+        }
+        let now = storage::now_secs();
+        match app.state::<Releases>().take(&app, |release| release.at.is_some_and(|at| at <= now)) {
+            Ok(due) => release_all(&app, due),
+            Err(e) => log::warn!("Failed to update the release queue: {}", e),
+        }
+    });
+}
+
+/// Queue `item` for release at `at` (seconds since the Unix epoch), on `trigger`, or whichever
+/// comes first.
+#[tauri::command]
+pub fn schedule_release(
+    app: AppHandle,
+    releases: State<'_, Releases>,
+    item: ReleaseItem,
+    at: Option<u64>,
+    trigger: Option<String>,
+) -> Result<Release, String> {
+    let trigger = trigger.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    if at.is_none() && trigger.is_none() {
+        return Err("A release needs a time or a trigger".to_string());
+    }
+    if let ReleaseItem::Snippet { snippet_id } = &item {
+        app.state::<SnippetLibrary>().get(&app, snippet_id)?;
+    }
+    let release = Release {
+        id: new_id(),
+        item,
+        at,
+        trigger,
+        created_at: storage::now_secs(),
+    };
+    releases.update(&app, |queue| queue.push(release.clone()))?;
+    Ok(release)
+}
+
+/// Everything queued, soonest first; releases waiting only on a trigger come last.
+#[tauri::command]
+pub fn list_releases(releases: State<'_, Releases>) -> Vec<Release> {
+    let mut queue = releases.queue.lock().unwrap().clone();
+    queue.sort_by_key(|release| (release.at.is_none(), release.at, release.created_at));
+    queue
+}
+
+#[tauri::command]
+pub fn cancel_release(app: AppHandle, releases: State<'_, Releases>, id: String) -> Result<bool, String> {
+    Ok(!releases.take(&app, |release| release.id == id)?.is_empty())
+}
+
+/// Release everything queued on `trigger` now. Returns the releases pulled; any that fail to go
+/// out are reported as `release-failed`.
+#[tauri::command]
+pub fn pull_release_trigger(app: AppHandle, releases: State<'_, Releases>, trigger: String) -> Result<Vec<Release>, String> {
+    if !hosting(&app) {
+        return Err("Start hosting a session first".to_string());
+    }
+    let trigger = trigger.trim();
+    let pulled = releases.take(&app, |release| release.trigger.as_deref() == Some(trigger))?;
+    release_all(&app, pulled.clone());
+    Ok(pulled)
+}
@@ -0,0 +1,350 @@
+//! Remote actions: a participant asking the host's machine to do something beyond editing the
+//! shared documents, namely applying a patch document to a work tree on disk or running a
+//! document with its language's run command.
+//!
+//! Every request is a challenge the host answers on this machine within `TIMEOUT`, through a
+//! native dialog or the approve and deny hotkeys. The webview hears about it as
+//! `remote-action-requested` and `remote-action-settled` but has no way to answer, so nothing
+//! running in a page can approve on the host's behalf. One request waits at a time; others are
+//! refused until it is settled. Only editors may ask. Every request and its outcome go into the
+//! audit log (`get_audit_log`), and the participant is sent an `ActionResult`.
+//!
+//! The host approves the document as it was when asked: its timeline position is taken with the
+//! request and shown in the dialog, and an approved action runs on the text at that position, so
+//! editing the document while the host decides changes nothing.
+
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::documents::DocumentStore;
+use crate::git::{self, PatchResult};
+use crate::protocol::Message;
+use crate::runner::{self, RunOutput};
+use crate::session::{self, Participant, Role, Session};
+use crate::settings::SettingsStore;
+use crate::storage;
+
+/// How long the host has to answer before the request is denied.
+const TIMEOUT: Duration = Duration::from_secs(30);
+const AUDIT_FILE: &str = "audit-log.json";
+/// Oldest entries are dropped beyond this many.
+const MAX_AUDIT_ENTRIES: usize = 1000;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum RemoteAction {
+    /// Apply the unified diff in a shared document to the work tree containing `target_path`.
+    #[serde(rename_all = "camelCase")]
+    ApplyPatch { document_id: String, target_path: String },
+    /// Run a shared document with its language's run command.
+    #[serde(rename_all = "camelCase")]
+    RunDocument {
+        document_id: String,
+        #[serde(default)]
+        stdin: Option<String>,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ActionOutput {
+    Patch { result: PatchResult },
+    Run { output: RunOutput },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    Approved,
+    Denied,
+    TimedOut,
+    /// Turned down without asking: a viewer asked, or another request was waiting.
+    Refused,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub request_id: String,
+    pub participant_id: String,
+    pub participant_name: String,
+    pub action: RemoteAction,
+    /// Timeline position of the document the host was asked about.
+    #[serde(default)]
+    pub seq: Option<u64>,
+    pub decision: Decision,
+    /// Seconds since the Unix epoch, when the request was settled.
+    pub at: u64,
+    /// Why an approved action failed, or why the request was refused.
+    pub error: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActionRequested {
+    request_id: String,
+    participant_id: String,
+    participant_name: String,
+    action: RemoteAction,
+    seq: u64,
+    description: String,
+    timeout_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ActionSettled {
+    request_id: String,
+    decision: Decision,
+}
+
+struct Pending {
+    request_id: String,
+    answer: Sender<bool>,
+}
+
+#[derive(Default)]
+pub struct RemoteActions {
+    pending: Mutex<Option<Pending>>,
+    lock: Mutex<()>,
+}
+
+fn record(app: &AppHandle, entry: AuditEntry) {
+    let actions = app.state::<RemoteActions>();
+    let _guard = actions.lock.lock().unwrap();
+    let recorded = storage::load_json::<AuditLog>(app, AUDIT_FILE).and_then(|mut log| {
+        log.entries.push(entry);
+        let excess = log.entries.len().saturating_sub(MAX_AUDIT_ENTRIES);
+        log.entries.drain(..excess);
+        storage::save_json(app, AUDIT_FILE, &log)
+    });
+    if let Err(e) = recorded {
+        log::warn!("Failed to record remote action in the audit log: {}", e);
+    }
+}
+
+fn document_id(action: &RemoteAction) -> &str {
+    match action {
+        RemoteAction::ApplyPatch { document_id, .. } | RemoteAction::RunDocument { document_id, .. } => document_id,
+    }
+}
+
+/// What the host is asked to allow, in words.
+fn describe(app: &AppHandle, participant: &Participant, action: &RemoteAction, seq: u64) -> String {
+    let document = |id: &str| {
+        app.state::<DocumentStore>()
+            .with(id, |doc| doc.path.as_ref().map(|path| path.display().to_string()))
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| format!("document {}", id))
+    };
+    let asked = match action {
+        RemoteAction::ApplyPatch {
+            document_id,
+            target_path,
+        } => format!(
+            "{} wants to apply the patch in {} to {}.",
+            participant.name,
+            document(document_id),
+            target_path
+        ),
+        RemoteAction::RunDocument { document_id, .. } => {
+            format!("{} wants to run {} on this computer.", participant.name, document(document_id))
+        }
+    };
+    format!("{}\n\nThis is the document as of change {}; later edits are not included.", asked, seq)
+}
+
+/// Carry out `action` on the document as it was at `seq`.
+fn perform(app: &AppHandle, action: RemoteAction, seq: u64) -> Result<ActionOutput, String> {
+    let store = app.state::<DocumentStore>();
+    let (text, language) = store.with(document_id(&action), |doc| {
+        Ok::<_, String>((doc.timeline.text_at(seq)?.text, doc.language.clone()))
+    })??;
+    match action {
+        RemoteAction::ApplyPatch { target_path, .. } => {
+            let result = git::apply_patch(text, &target_path, false)?;
+            Ok(ActionOutput::Patch { result })
+        }
+        RemoteAction::RunDocument { stdin, .. } => {
+            let language = language.ok_or("The document has no language to run it as")?;
+            let command = runner::run_command(app, &app.state::<SettingsStore>(), &language)?;
+            let output = runner::run(&command, &language, &text, &stdin.unwrap_or_default(), runner::DEFAULT_TIMEOUT)?;
+            Ok(ActionOutput::Run { output })
+        }
+    }
+}
+
+/// Ask the host natively. The answer arrives through `answer`, as the hotkeys' does.
+#[cfg(desktop)]
+fn ask(app: &AppHandle, request_id: &str, description: &str) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let (handle, id) = (app.clone(), request_id.to_string());
+    app.dialog()
+        .message(format!("{}\n\nDeny unless you expected this.", description))
+        .title("Allow remote action?")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom("Allow".to_string(), "Deny".to_string()))
+        .show(move |allowed| {
+            answer(&handle, Some(&id), allowed).ok();
+        });
+}
+
+#[cfg(mobile)]
+fn ask(_app: &AppHandle, _request_id: &str, _description: &str) {}
+
+/// Settle the waiting request, or the one with `request_id` only. Fails if none is waiting.
+pub fn answer(app: &AppHandle, request_id: Option<&str>, approved: bool) -> Result<(), String> {
+    let actions = app.state::<RemoteActions>();
+    let mut pending = actions.pending.lock().unwrap();
+    if pending.as_ref().map_or(true, |p| request_id.is_some_and(|id| id != p.request_id)) {
+        return Err("No remote action is waiting for approval".to_string());
+    }
+    let pending = pending.take().unwrap();
+    // The waiting thread is gone only if it already timed out
+    pending.answer.send(approved).ok();
+    Ok(())
+}
+
+/// Carry out an approved action, record the decision and tell the participant. `refusal` says why
+/// a request was `Refused`.
+fn settle(
+    app: &AppHandle,
+    participant: &Participant,
+    request_id: String,
+    action: RemoteAction,
+    seq: Option<u64>,
+    decision: Decision,
+    refusal: Option<String>,
+) {
+    if decision != Decision::Refused {
+        let settled = ActionSettled {
+            request_id: request_id.clone(),
+            decision,
+        };
+        if let Err(e) = app.emit("remote-action-settled", settled) {
+            log::warn!("Failed to emit remote-action-settled: {}", e);
+        }
+    }
+    let outcome = match decision {
+        Decision::Approved => perform(app, action.clone(), seq.unwrap_or_default()).map(Some),
+        Decision::Denied => Err("The host denied the request".to_string()),
+        Decision::TimedOut => Err("The host did not answer in time".to_string()),
+        Decision::Refused => Err(refusal.unwrap_or_default()),
+    };
+    record(
+        app,
+        AuditEntry {
+            request_id: request_id.clone(),
+            participant_id: participant.id.clone(),
+            participant_name: participant.name.clone(),
+            action,
+            seq,
+            decision,
+            at: storage::now_secs(),
+            error: match decision {
+                Decision::Approved | Decision::Refused => outcome.as_ref().err().cloned(),
+                _ => None,
+            },
+        },
+    );
+    let (output, error) = match outcome {
+        Ok(output) => (output, None),
+        Err(e) => (None, Some(e)),
+    };
+    let result = Message::ActionResult {
+        request_id,
+        approved: decision == Decision::Approved,
+        output,
+        error,
+    };
+    if let Err(e) = session::send_to(app, &participant.id, &result) {
+        log::warn!("Failed to send the remote action result: {}", e);
+    }
+}
+
+/// Handle an `ActionRequest` from a participant. Returns at once; the challenge runs on its own
+/// thread so the transport isn't held up while the host decides.
+pub fn request(app: &AppHandle, session: &Session, request_id: String, participant_id: String, action: RemoteAction) {
+    let Ok(participant) = session.participant(&participant_id) else {
+        return;
+    };
+    let refusal = if participant.role == Role::Viewer {
+        Some("Viewers cannot ask for remote actions")
+    } else if cfg!(mobile) {
+        Some("Remote actions need a host on a desktop computer")
+    } else {
+        None
+    };
+    if let Some(reason) = refusal {
+        settle(app, &participant, request_id, action, None, Decision::Refused, Some(reason.to_string()));
+        return;
+    }
+    let seq = match app.state::<DocumentStore>().with(document_id(&action), |doc| doc.timeline.seq()) {
+        Ok(seq) => seq,
+        Err(e) => {
+            settle(app, &participant, request_id, action, None, Decision::Refused, Some(e));
+            return;
+        }
+    };
+    let (tx, rx) = mpsc::channel();
+    {
+        let actions = app.state::<RemoteActions>();
+        let mut pending = actions.pending.lock().unwrap();
+        if pending.is_some() {
+            drop(pending);
+            let reason = "The host is answering another request; try again shortly".to_string();
+            settle(app, &participant, request_id, action, Some(seq), Decision::Refused, Some(reason));
+            return;
+        }
+        *pending = Some(Pending {
+            request_id: request_id.clone(),
+            answer: tx,
+        });
+    }
+
+    let app = app.clone();
+    thread::spawn(move || {
+        let description = describe(&app, &participant, &action, seq);
+        let requested = ActionRequested {
+            request_id: request_id.clone(),
+            participant_id: participant.id.clone(),
+            participant_name: participant.name.clone(),
+            action: action.clone(),
+            seq,
+            description: description.clone(),
+            timeout_ms: TIMEOUT.as_millis() as u64,
+        };
+        if let Err(e) = app.emit("remote-action-requested", requested) {
+            log::warn!("Failed to emit remote-action-requested: {}", e);
+        }
+        ask(&app, &request_id, &description);
+        let decision = match rx.recv_timeout(TIMEOUT) {
+            Ok(true) => Decision::Approved,
+            Ok(false) => Decision::Denied,
+            Err(_) => {
+                // A dialog answered from now on finds nothing waiting
+                answer(&app, Some(&request_id), false).ok();
+                Decision::TimedOut
+            }
+        };
+        settle(&app, &participant, request_id, action, Some(seq), decision, None);
+    });
+}
+
+/// Every remote action requested, oldest first.
+#[tauri::command]
+pub fn get_audit_log(app: AppHandle) -> Result<Vec<AuditEntry>, String> {
+    Ok(storage::load_json::<AuditLog>(&app, AUDIT_FILE)?.entries)
+}
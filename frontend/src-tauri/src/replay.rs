@@ -0,0 +1,437 @@
+//! Typing replays: a self-contained animated SVG (or an HTML page wrapping one) that plays back a
+//! stretch of a document's timeline, like an asciinema cast but without a player.
+//!
+//! Every frame is laid out once, stacked vertically in a clipped viewport, and a CSS animation with
+//! stepped keyframes slides the stack one frame at a time. Lines repeat heavily between frames, so
+//! each distinct line is defined once and referenced with `<use>`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::accessibility;
+use crate::documents::DocumentStore;
+use crate::export::{self, Rgb, Run, ThemeName, Viewport};
+use crate::history::ReplayStep;
+use crate::magnifier::Magnifier;
+use crate::syntax::HighlightClass;
+use crate::workers::{Priority, WorkerPool};
+
+/// Longer replays drop intermediate frames rather than growing without bound.
+const MAX_FRAMES: usize = 600;
+/// Changes closer together than this share a frame.
+const MIN_FRAME_MS: f64 = 40.0;
+const LEAD_IN_MS: f64 = 500.0;
+const HOLD_END_MS: f64 = 2000.0;
+/// Monospace advance and line height, relative to the font size.
+const CHAR_WIDTH: f64 = 0.6;
+const LINE_HEIGHT: f64 = 1.4;
+const PADDING: f64 = 12.0;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayFormat {
+    #[default]
+    Svg,
+    Html,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReplayOptions {
+    pub format: ReplayFormat,
+    pub theme: ThemeName,
+    /// First and last timeline positions to play; by default the whole kept history.
+    pub from_seq: Option<u64>,
+    pub to_seq: Option<u64>,
+    /// Playback speed relative to the original typing.
+    pub speed: f64,
+    /// Pauses longer than this are shortened to it, before `speed` applies.
+    pub max_idle_ms: u64,
+    /// Size of the visible area, in characters.
+    pub columns: usize,
+    pub rows: usize,
+    /// In pixels.
+    pub font_size: f64,
+    pub line_numbers: bool,
+    /// Show only the host's magnified region, enlarged, when it is on this document.
+    pub magnify: bool,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            format: ReplayFormat::Svg,
+            theme: ThemeName::Dark,
+            from_seq: None,
+            to_seq: None,
+            speed: 1.0,
+            max_idle_ms: 1000,
+            columns: 80,
+            rows: 24,
+            font_size: 14.0,
+            line_numbers: true,
+            magnify: true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayInfo {
+    pub path: String,
+    pub frames: usize,
+    pub duration_ms: u64,
+    pub size: u64,
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn class_name(class: HighlightClass) -> &'static str {
+    match class {
+        HighlightClass::Keyword => "k",
+        HighlightClass::String => "s",
+        HighlightClass::Number => "n",
+        HighlightClass::Comment => "c",
+        HighlightClass::Type => "t",
+        HighlightClass::Function => "f",
+    }
+}
+
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            // Not allowed in XML at all.
+            c if c.is_control() => out.push(' '),
+            c => out.push(c),
+        }
+    }
+}
+
+/// One animation frame: the visible lines (as indices into the line definitions) and the caret.
+struct Frame {
+    start_ms: f64,
+    top: usize,
+    lines: Vec<Option<usize>>,
+    /// Row and column of the caret, unless a fixed viewport hides it.
+    caret: Option<(usize, usize)>,
+}
+
+/// Lays out frames, sharing identical visible lines between them.
+struct Layout<'a> {
+    options: &'a ReplayOptions,
+    language: Option<String>,
+    tab_width: usize,
+    viewport: Viewport,
+    /// Markup of each distinct visible line, and its index.
+    defs: Vec<String>,
+    seen: HashMap<String, usize>,
+}
+
+impl Layout<'_> {
+    /// The markup of columns `left..left + columns` of `runs`, or `None` if nothing shows.
+    fn line_markup(&self, runs: &[Run], left: usize) -> Option<String> {
+        let mut markup = String::new();
+        let mut cell = 0;
+        for Run { text, class } in runs {
+            let visible: String = text
+                .chars()
+                .enumerate()
+                .filter(|(i, _)| (left..left + self.options.columns).contains(&(cell + i)))
+                .map(|(_, c)| c)
+                .collect();
+            cell += text.chars().count();
+            if visible.is_empty() {
+                continue;
+            }
+            match class {
+                Some(class) => {
+                    write!(markup, "<tspan class=\"{}\">", class_name(*class)).unwrap();
+                    escape(&visible, &mut markup);
+                    markup.push_str("</tspan>");
+                }
+                None => escape(&visible, &mut markup),
+            }
+        }
+        (!markup.trim().is_empty()).then_some(markup)
+    }
+
+    fn frame(&mut self, text: &str, caret: usize, start_ms: f64) -> Frame {
+        let (line, column) = export::caret_position(text, caret, self.tab_width);
+        self.viewport.follow(line, column);
+        let Viewport { rows, top, left, .. } = self.viewport;
+        let styled = export::styled_lines(text, self.language.as_deref(), self.tab_width);
+        let lines = styled
+            .iter()
+            .skip(top)
+            .take(rows)
+            .map(|runs| {
+                let markup = self.line_markup(runs, left)?;
+                let next = self.defs.len();
+                let index = *self.seen.entry(markup.clone()).or_insert(next);
+                if index == next {
+                    self.defs.push(markup);
+                }
+                Some(index)
+            })
+            .collect();
+        Frame {
+            start_ms,
+            top,
+            lines,
+            caret: self.viewport.locate(line, column),
+        }
+    }
+}
+
+/// Play `steps` over `base` on a compressed clock, one frame per interval that shows a change.
+/// Returns the frames and the total duration.
+fn layout_frames(layout: &mut Layout, base: String, steps: &[ReplayStep]) -> (Vec<Frame>, f64) {
+    let options = layout.options;
+    let times = export::playback_times(steps, LEAD_IN_MS, options.max_idle_ms, options.speed);
+    let total = times.last().copied().unwrap_or(LEAD_IN_MS) + HOLD_END_MS;
+    let interval = MIN_FRAME_MS.max(total / MAX_FRAMES as f64);
+
+    let mut text = base;
+    let mut caret = 0;
+    let mut frames = vec![layout.frame(&text, caret, 0.0)];
+    let mut next = 0;
+    while next < steps.len() {
+        // Everything due before the next interval boundary goes into one frame.
+        let start = times[next];
+        let due = (start / interval).floor() * interval + interval;
+        while next < steps.len() && times[next] < due {
+            steps[next].apply(&mut text);
+            caret = steps[next].start + steps[next].insert.len();
+            next += 1;
+        }
+        frames.push(layout.frame(&text, caret, start));
+    }
+    (frames, total)
+}
+
+fn render_svg(
+    title: &str,
+    language: Option<String>,
+    tab_width: usize,
+    base: String,
+    steps: &[ReplayStep],
+    options: &ReplayOptions,
+    viewport: Viewport,
+) -> (String, usize, f64) {
+    let mut text = base.clone();
+    let mut max_lines = text.lines().count();
+    for step in steps {
+        step.apply(&mut text);
+        max_lines = max_lines.max(text.lines().count());
+    }
+    let gutter = if options.line_numbers {
+        max_lines.to_string().len().max(3) + 1
+    } else {
+        0
+    };
+
+    let mut layout = Layout {
+        options,
+        language,
+        tab_width,
+        viewport,
+        defs: Vec::new(),
+        seen: HashMap::new(),
+    };
+    let (frames, total) = layout_frames(&mut layout, base, steps);
+
+    let cell = options.font_size * CHAR_WIDTH;
+    let line_height = options.font_size * LINE_HEIGHT;
+    let width = (2.0 * PADDING + (gutter + options.columns) as f64 * cell).ceil();
+    let height = (2.0 * PADDING + options.rows as f64 * line_height).ceil();
+    // Baseline of a row, from the top of the frame.
+    let baseline = |row: usize| PADDING + row as f64 * line_height + options.font_size * 1.05;
+    let text_x = PADDING + gutter as f64 * cell;
+    let theme = export::theme(options.theme);
+
+    let mut svg = String::new();
+    write!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n<title>"
+    )
+    .unwrap();
+    escape(title, &mut svg);
+    svg.push_str("</title>\n<style>\n");
+    write!(
+        svg,
+        "text{{font-family:ui-monospace,SFMono-Regular,Menlo,Consolas,monospace;font-size:{}px;white-space:pre;fill:{}}}\n\
+         .g{{fill:{}}} .cl{{fill:{}}} .cr{{fill:{}}}\n",
+        options.font_size,
+        hex(theme.foreground),
+        hex(theme.gutter),
+        hex(theme.current_line()),
+        hex(theme.foreground),
+    )
+    .unwrap();
+    for class in [
+        HighlightClass::Keyword,
+        HighlightClass::String,
+        HighlightClass::Number,
+        HighlightClass::Comment,
+        HighlightClass::Type,
+        HighlightClass::Function,
+    ] {
+        writeln!(svg, ".{}{{fill:{}}}", class_name(class), hex(theme.color(Some(class)))).unwrap();
+    }
+    // Each keyframe jumps to its frame and holds until the next one. With reduced motion the
+    // replay plays once and stays on the result, and viewers who ask for it only see the result.
+    let last = frames.len().saturating_sub(1) as f64 * height;
+    let repeat = if accessibility::current().reduce_motion { "1 forwards" } else { "infinite" };
+    writeln!(svg, "#film{{animation:play {}ms step-end {}}}", total.round(), repeat).unwrap();
+    writeln!(
+        svg,
+        "@media (prefers-reduced-motion:reduce){{#film{{animation:none;transform:translate(0,-{}px)}}}}",
+        last
+    )
+    .unwrap();
+    svg.push_str("@keyframes play{");
+    for (i, frame) in frames.iter().enumerate() {
+        write!(
+            svg,
+            "{:.4}%{{transform:translate(0,-{}px)}}",
+            frame.start_ms / total * 100.0,
+            i as f64 * height
+        )
+        .unwrap();
+    }
+    writeln!(svg, "100%{{transform:translate(0,-{}px)}}}}\n</style>", last).unwrap();
+    writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>", hex(theme.background)).unwrap();
+
+    // The nested viewport clips the filmstrip to one frame.
+    writeln!(svg, "<svg width=\"{width}\" height=\"{height}\">\n<defs>").unwrap();
+    for (i, markup) in layout.defs.iter().enumerate() {
+        writeln!(svg, "<text id=\"l{i}\" x=\"{text_x:.1}\" xml:space=\"preserve\">{markup}</text>").unwrap();
+    }
+    svg.push_str("</defs>\n<g id=\"film\">\n");
+    for (i, frame) in frames.iter().enumerate() {
+        writeln!(svg, "<g transform=\"translate(0 {})\">", i as f64 * height).unwrap();
+        if let Some((caret_row, _)) = frame.caret {
+            writeln!(
+                svg,
+                "<rect class=\"cl\" y=\"{:.1}\" width=\"100%\" height=\"{line_height:.1}\"/>",
+                PADDING + caret_row as f64 * line_height
+            )
+            .unwrap();
+        }
+        for (row, line) in frame.lines.iter().enumerate() {
+            let y = baseline(row);
+            if gutter > 0 {
+                writeln!(
+                    svg,
+                    "<text class=\"g\" x=\"{PADDING}\" y=\"{y:.1}\">{:>width$}</text>",
+                    frame.top + row + 1,
+                    width = gutter - 1
+                )
+                .unwrap();
+            }
+            if let Some(index) = line {
+                writeln!(svg, "<use xlink:href=\"#l{index}\" href=\"#l{index}\" y=\"{y:.1}\"/>").unwrap();
+            }
+        }
+        if let Some((caret_row, caret_column)) = frame.caret {
+            writeln!(
+                svg,
+                "<rect class=\"cr\" x=\"{:.1}\" y=\"{:.1}\" width=\"2\" height=\"{line_height:.1}\"/>",
+                text_x + caret_column as f64 * cell,
+                PADDING + caret_row as f64 * line_height
+            )
+            .unwrap();
+        }
+        svg.push_str("</g>\n");
+    }
+    svg.push_str("</g>\n</svg>\n</svg>\n");
+    (svg, frames.len(), total)
+}
+
+fn render_html(title: &str, svg: &str, background: Rgb) -> String {
+    let mut escaped = String::new();
+    escape(title, &mut escaped);
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{escaped}</title>\n<style>\
+         body{{margin:0;min-height:100vh;display:flex;align-items:center;justify-content:center;background:{}}}\
+         svg{{max-width:100%;height:auto}}</style>\n</head>\n<body>\n{svg}</body>\n</html>\n",
+        hex(background)
+    )
+}
+
+/// Export a document's typing as a self-playing animation, written to `path`.
+#[tauri::command]
+pub async fn export_typing_replay(
+    store: State<'_, DocumentStore>,
+    magnifier: State<'_, Magnifier>,
+    pool: State<'_, WorkerPool>,
+    doc_id: String,
+    path: String,
+    options: Option<ReplayOptions>,
+) -> Result<ReplayInfo, String> {
+    let mut options = options.unwrap_or_default();
+    if options.columns == 0 || options.rows == 0 || !(6.0..=48.0).contains(&options.font_size) {
+        return Err("Invalid replay size".to_string());
+    }
+    if options.speed.is_nan() || options.speed <= 0.0 || options.speed > 100.0 {
+        return Err("Speed must be above 0 and at most 100".to_string());
+    }
+    let (title, language, tab_width, replay) = store.with(&doc_id, |doc| {
+        let title = doc
+            .path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map_or_else(|| "Untitled".to_string(), |n| n.to_string_lossy().into_owned());
+        let from = options.from_seq.unwrap_or_else(|| doc.timeline.first_seq());
+        let to = options.to_seq.unwrap_or_else(|| doc.timeline.seq());
+        let replay = doc.timeline.replay(from, to);
+        (title, doc.language.clone(), doc.profile.tab_width as usize, replay)
+    })?;
+    let (base, steps) = replay?;
+    if steps.is_empty() {
+        return Err("There are no changes to replay in that range".to_string());
+    }
+
+    // A magnified region is drawn in a larger font, so the replay keeps about its size.
+    let viewport = match options.magnify.then(|| magnifier.region_for(&doc_id)).flatten() {
+        Some(region) => {
+            options.font_size *= region.zoom(options.rows, options.columns);
+            options.rows = region.rows;
+            options.columns = region.columns;
+            region.viewport()
+        }
+        None => Viewport::new(options.rows, options.columns),
+    };
+
+    pool.run(Priority::Background, "Replay export", move |cancel| {
+        let (svg, frames, total) = render_svg(&title, language, tab_width.max(1), base, &steps, &options, viewport);
+        cancel.check()?;
+        let output = match options.format {
+            ReplayFormat::Svg => svg,
+            ReplayFormat::Html => render_html(&title, &svg, export::theme(options.theme).background),
+        };
+        let path = Path::new(&path);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &output).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("Failed to finalize {}: {}", path.display(), e))?;
+        Ok(ReplayInfo {
+            path: path.to_string_lossy().into_owned(),
+            frames,
+            duration_ms: total as u64,
+            size: output.len() as u64,
+        })
+    })
+    .await
+}
@@ -0,0 +1,482 @@
+//! Review mode: fetch a GitHub pull request or GitLab merge request, present it as a
+//! structured review document, and post review comments back.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, State};
+
+use crate::documents::{self, Document, DocumentStore};
+use crate::settings::SettingsStore;
+use crate::storage::new_id;
+
+const KEYCHAIN_SERVICE: &str = "sharecode-review";
+const USER_AGENT: &str = "ShareCode-Desktop";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Github,
+    Gitlab,
+}
+
+#[derive(Clone)]
+struct Target {
+    provider: Provider,
+    /// `github.com` or the GitLab host.
+    host: String,
+    /// `owner/repo` or the GitLab project path.
+    project: String,
+    number: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewFile {
+    pub path: String,
+    pub old_path: Option<String>,
+    /// `added`, `removed`, `modified` or `renamed`.
+    pub status: String,
+    /// Unified diff hunks; absent for binary or very large files.
+    pub patch: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewComment {
+    pub id: String,
+    /// `None` for conversation comments not attached to a line.
+    pub path: Option<String>,
+    pub line: Option<u64>,
+    pub author: String,
+    pub body: String,
+    pub created_at: String,
+    pub reply_to: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    pub id: String,
+    pub provider: Provider,
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub files: Vec<ReviewFile>,
+    pub comments: Vec<ReviewComment>,
+    /// Document holding the combined diff, ready to be shared like any other document.
+    pub document_id: String,
+    #[serde(skip)]
+    head_sha: String,
+    #[serde(skip)]
+    base_sha: String,
+    #[serde(skip)]
+    start_sha: String,
+    #[serde(skip)]
+    target: Option<Target>,
+}
+
+#[derive(Default)]
+pub struct ReviewStore {
+    reviews: Mutex<HashMap<String, Review>>,
+}
+
+impl ReviewStore {
+    /// Comments on the review presented in `document_id`, if that document is a review.
+    pub fn comments_for(&self, document_id: &str) -> Vec<ReviewComment> {
+        self.reviews
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|r| r.document_id == document_id)
+            .flat_map(|r| r.comments.clone())
+            .collect()
+    }
+}
+
+fn parse_url(url: &str) -> Result<Target, String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let rest = trimmed
+        .strip_prefix("https://")
+        .or_else(|| trimmed.strip_prefix("http://"))
+        .ok_or("Review URL must start with http:// or https://")?;
+    let (host, path) = rest.split_once('/').ok_or("Review URL has no path")?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+
+    if let Some((project, number)) = path.split_once("/-/merge_requests/") {
+        let number = number.split('/').next().unwrap_or_default();
+        return Ok(Target {
+            provider: Provider::Gitlab,
+            host: host.to_string(),
+            project: project.to_string(),
+            number: number.parse().map_err(|_| "Invalid merge request number")?,
+        });
+    }
+    let parts: Vec<&str> = path.split('/').collect();
+    if host == "github.com" && parts.len() >= 4 && parts[2] == "pull" {
+        return Ok(Target {
+            provider: Provider::Github,
+            host: host.to_string(),
+            project: format!("{}/{}", parts[0], parts[1]),
+            number: parts[3].parse().map_err(|_| "Invalid pull request number")?,
+        });
+    }
+    Err("Not a GitHub pull request or GitLab merge request URL".to_string())
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn token_for(host: &str) -> Option<String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, host).ok()?.get_password().ok()
+}
+
+fn api_base(target: &Target) -> String {
+    match target.provider {
+        Provider::Github => format!("https://api.github.com/repos/{}", target.project),
+        Provider::Gitlab => format!("https://{}/api/v4/projects/{}", target.host, percent_encode(&target.project)),
+    }
+}
+
+fn authorize(request: ureq::Request, target: &Target) -> ureq::Request {
+    let request = request.set("User-Agent", USER_AGENT);
+    match (token_for(&target.host), target.provider) {
+        (Some(token), Provider::Github) => request
+            .set("Authorization", &format!("Bearer {}", token))
+            .set("Accept", "application/vnd.github+json"),
+        (Some(token), Provider::Gitlab) => request.set("PRIVATE-TOKEN", &token),
+        (None, _) => request,
+    }
+}
+
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(401 | 403, _) => "Access denied; set a token for this host".to_string(),
+        ureq::Error::Status(404, _) => "Not found (private repositories need a token)".to_string(),
+        ureq::Error::Status(code, response) => {
+            format!("Request failed with {}: {}", code, response.into_string().unwrap_or_default())
+        }
+        other => format!("Request failed: {}", other),
+    }
+}
+
+fn get_json(target: &Target, url: &str) -> Result<Value, String> {
+    let response = authorize(ureq::get(url), target).call().map_err(describe)?;
+    let body = response.into_string().map_err(|e| e.to_string())?;
+    serde_json::from_str(&body).map_err(|e| format!("Invalid response from {}: {}", target.host, e))
+}
+
+/// Fetch every page of a list endpoint (both providers accept `per_page` and `page`).
+fn get_all(target: &Target, url: &str) -> Result<Vec<Value>, String> {
+    let mut items = Vec::new();
+    for page in 1..=50 {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let batch = get_json(target, &format!("{}{}per_page=100&page={}", url, separator, page))?;
+        let batch = batch.as_array().cloned().unwrap_or_default();
+        let done = batch.len() < 100;
+        items.extend(batch);
+        if done {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+fn str_at(value: &Value, pointer: &str) -> String {
+    value.pointer(pointer).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+fn id_at(value: &Value, pointer: &str) -> Option<String> {
+    value.pointer(pointer).and_then(|v| match v {
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    })
+}
+
+fn fetch_github(target: &Target, url: &str) -> Result<Review, String> {
+    let base = api_base(target);
+    let pr = get_json(target, &format!("{}/pulls/{}", base, target.number))?;
+    let files = get_all(target, &format!("{}/pulls/{}/files", base, target.number))?
+        .iter()
+        .map(|f| ReviewFile {
+            path: str_at(f, "/filename"),
+            old_path: f.get("previous_filename").and_then(Value::as_str).map(str::to_string),
+            status: str_at(f, "/status"),
+            patch: f.get("patch").and_then(Value::as_str).map(str::to_string),
+        })
+        .collect();
+    let mut comments: Vec<ReviewComment> = get_all(target, &format!("{}/issues/{}/comments", base, target.number))?
+        .iter()
+        .map(|c| ReviewComment {
+            id: id_at(c, "/id").unwrap_or_default(),
+            path: None,
+            line: None,
+            author: str_at(c, "/user/login"),
+            body: str_at(c, "/body"),
+            created_at: str_at(c, "/created_at"),
+            reply_to: None,
+        })
+        .collect();
+    comments.extend(
+        get_all(target, &format!("{}/pulls/{}/comments", base, target.number))?
+            .iter()
+            .map(|c| ReviewComment {
+                id: id_at(c, "/id").unwrap_or_default(),
+                path: Some(str_at(c, "/path")),
+                line: c.get("line").and_then(Value::as_u64).or_else(|| c.get("original_line").and_then(Value::as_u64)),
+                author: str_at(c, "/user/login"),
+                body: str_at(c, "/body"),
+                created_at: str_at(c, "/created_at"),
+                reply_to: id_at(c, "/in_reply_to_id"),
+            }),
+    );
+    Ok(Review {
+        id: new_id(),
+        provider: Provider::Github,
+        url: url.to_string(),
+        title: str_at(&pr, "/title"),
+        description: str_at(&pr, "/body"),
+        author: str_at(&pr, "/user/login"),
+        files,
+        comments,
+        document_id: String::new(),
+        head_sha: str_at(&pr, "/head/sha"),
+        base_sha: str_at(&pr, "/base/sha"),
+        start_sha: String::new(),
+        target: Some(target.clone()),
+    })
+}
+
+fn fetch_gitlab(target: &Target, url: &str) -> Result<Review, String> {
+    let base = format!("{}/merge_requests/{}", api_base(target), target.number);
+    let mr = get_json(target, &base)?;
+    let changes = get_json(target, &format!("{}/changes", base))?;
+    let files = changes
+        .get("changes")
+        .and_then(Value::as_array)
+        .map(|changes| {
+            changes
+                .iter()
+                .map(|c| {
+                    let flag = |key: &str| c.get(key).and_then(Value::as_bool).unwrap_or(false);
+                    let status = if flag("new_file") {
+                        "added"
+                    } else if flag("deleted_file") {
+                        "removed"
+                    } else if flag("renamed_file") {
+                        "renamed"
+                    } else {
+                        "modified"
+                    };
+                    ReviewFile {
+                        path: str_at(c, "/new_path"),
+                        old_path: Some(str_at(c, "/old_path")).filter(|_| flag("renamed_file")),
+                        status: status.to_string(),
+                        patch: c.get("diff").and_then(Value::as_str).map(str::to_string),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut comments = Vec::new();
+    for discussion in get_all(target, &format!("{}/discussions", base))? {
+        let notes = discussion.get("notes").and_then(Value::as_array).cloned().unwrap_or_default();
+        let first_id = notes.first().and_then(|n| id_at(n, "/id"));
+        for (i, note) in notes.iter().enumerate() {
+            if note.get("system").and_then(Value::as_bool).unwrap_or(false) {
+                continue;
+            }
+            comments.push(ReviewComment {
+                id: id_at(note, "/id").unwrap_or_default(),
+                path: note.pointer("/position/new_path").and_then(Value::as_str).map(str::to_string),
+                line: note
+                    .pointer("/position/new_line")
+                    .and_then(Value::as_u64)
+                    .or_else(|| note.pointer("/position/old_line").and_then(Value::as_u64)),
+                author: str_at(note, "/author/username"),
+                body: str_at(note, "/body"),
+                created_at: str_at(note, "/created_at"),
+                reply_to: if i > 0 { first_id.clone() } else { None },
+            });
+        }
+    }
+    Ok(Review {
+        id: new_id(),
+        provider: Provider::Gitlab,
+        url: url.to_string(),
+        title: str_at(&mr, "/title"),
+        description: str_at(&mr, "/description"),
+        author: str_at(&mr, "/author/username"),
+        files,
+        comments,
+        document_id: String::new(),
+        head_sha: str_at(&mr, "/diff_refs/head_sha"),
+        base_sha: str_at(&mr, "/diff_refs/base_sha"),
+        start_sha: str_at(&mr, "/diff_refs/start_sha"),
+        target: Some(target.clone()),
+    })
+}
+
+fn render_diff(review: &Review) -> String {
+    let mut out = String::new();
+    for file in &review.files {
+        let old = file.old_path.as_deref().unwrap_or(&file.path);
+        out.push_str(&format!("diff --git a/{} b/{}\n--- a/{}\n+++ b/{}\n", old, file.path, old, file.path));
+        match &file.patch {
+            Some(patch) => {
+                out.push_str(patch);
+                if !patch.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+            None => out.push_str("(binary or too large to display)\n"),
+        }
+    }
+    out
+}
+
+/// Fetch a pull/merge request by URL and materialize it as a review plus a shareable diff document.
+#[tauri::command]
+pub async fn open_review(
+    app: AppHandle,
+    reviews: State<'_, ReviewStore>,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    url: String,
+) -> Result<Review, String> {
+    let target = parse_url(&url)?;
+    let fetch_url = url.clone();
+    let mut review = tauri::async_runtime::spawn_blocking(move || match target.provider {
+        Provider::Github => fetch_github(&target, &fetch_url),
+        Provider::Gitlab => fetch_gitlab(&target, &fetch_url),
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let info = store.insert(Document::new(None, render_diff(&review)));
+    documents::apply_language(&app, &store, &settings, &info.id, "diff")?;
+    review.document_id = info.id;
+    reviews.reviews.lock().unwrap().insert(review.id.clone(), review.clone());
+    Ok(review)
+}
+
+/// Post a comment on a review. With `path` and `line` it is attached to that line of the new file.
+#[tauri::command]
+pub async fn post_review_comment(
+    reviews: State<'_, ReviewStore>,
+    review_id: String,
+    body: String,
+    path: Option<String>,
+    line: Option<u64>,
+) -> Result<ReviewComment, String> {
+    let review = reviews
+        .reviews
+        .lock()
+        .unwrap()
+        .get(&review_id)
+        .cloned()
+        .ok_or_else(|| format!("Review {} is not open", review_id))?;
+    let target = review.target.clone().ok_or("Review has no remote target")?;
+    if token_for(&target.host).is_none() {
+        return Err(format!("Set a token for {} before posting comments", target.host));
+    }
+
+    let base = api_base(&target);
+    let (endpoint, payload) = match (target.provider, &path, line) {
+        (Provider::Github, Some(path), Some(line)) => (
+            format!("{}/pulls/{}/comments", base, target.number),
+            json!({ "body": body, "commit_id": review.head_sha, "path": path, "line": line, "side": "RIGHT" }),
+        ),
+        (Provider::Github, _, _) => (format!("{}/issues/{}/comments", base, target.number), json!({ "body": body })),
+        (Provider::Gitlab, Some(path), Some(line)) => (
+            format!("{}/merge_requests/{}/discussions", base, target.number),
+            json!({
+                "body": body,
+                "position": {
+                    "position_type": "text",
+                    "base_sha": review.base_sha,
+                    "start_sha": review.start_sha,
+                    "head_sha": review.head_sha,
+                    "new_path": path,
+                    "new_line": line,
+                },
+            }),
+        ),
+        (Provider::Gitlab, _, _) => (
+            format!("{}/merge_requests/{}/notes", base, target.number),
+            json!({ "body": body }),
+        ),
+    };
+
+    let request_target = target.clone();
+    let created = tauri::async_runtime::spawn_blocking(move || {
+        let response = authorize(ureq::post(&endpoint), &request_target)
+            .set("Content-Type", "application/json")
+            .send_string(&payload.to_string())
+            .map_err(describe)?;
+        let text = response.into_string().map_err(|e| e.to_string())?;
+        serde_json::from_str::<Value>(&text).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    // GitLab discussions wrap the note; GitHub returns the comment itself.
+    let note = created.pointer("/notes/0").cloned().unwrap_or(created);
+    let author = match target.provider {
+        Provider::Github => str_at(&note, "/user/login"),
+        Provider::Gitlab => str_at(&note, "/author/username"),
+    };
+    let comment = ReviewComment {
+        id: id_at(&note, "/id").unwrap_or_default(),
+        path,
+        line,
+        author,
+        body: str_at(&note, "/body"),
+        created_at: str_at(&note, "/created_at"),
+        reply_to: None,
+    };
+    if let Some(review) = reviews.reviews.lock().unwrap().get_mut(&review_id) {
+        review.comments.push(comment.clone());
+    }
+    Ok(comment)
+}
+
+/// Store (or with `None`, remove) the API token for a host in the OS keychain.
+#[tauri::command]
+pub fn set_review_token(host: String, token: Option<String>) -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, &host).map_err(|e| e.to_string())?;
+    match token {
+        Some(token) => entry.set_password(&token).map_err(|e| e.to_string()),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        },
+    }
+}
+
+#[tauri::command]
+pub fn has_review_token(host: String) -> bool {
+    token_for(&host).is_some()
+}
+
+#[tauri::command]
+pub fn close_review(reviews: State<'_, ReviewStore>, store: State<'_, DocumentStore>, review_id: String) -> bool {
+    match reviews.reviews.lock().unwrap().remove(&review_id) {
+        Some(review) => {
+            store.docs.lock().unwrap().remove(&review.document_id);
+            true
+        }
+        None => false,
+    }
+}
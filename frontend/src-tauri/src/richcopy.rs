@@ -0,0 +1,224 @@
+//! Copying code with its highlighting, for pasting into chat, documents and slides.
+//!
+//! The webview can only put plain text on the clipboard reliably, so `copy_rich` writes plain
+//! text, HTML and RTF flavours in one go through the native clipboard, and the app pasted into
+//! picks the richest one it understands. Linux gets plain text and HTML only, since the clipboard
+//! crate used there has no RTF.
+
+use crate::export::{self, Rgb, Run, ThemeName};
+
+pub(crate) const TAB_WIDTH: usize = 4;
+
+#[cfg(target_os = "macos")]
+const FONT: &str = "Menlo";
+#[cfg(target_os = "windows")]
+const FONT: &str = "Consolas";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const FONT: &str = "DejaVu Sans Mono";
+
+fn hex([r, g, b]: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A `<pre>` with inline styles only, since pasted HTML loses any stylesheet.
+pub(crate) fn html(lines: &[Vec<Run>], theme: &export::Theme) -> String {
+    let mut out = format!(
+        "<pre style=\"background:{};color:{};font-family:{},monospace;padding:8px\">",
+        hex(theme.background),
+        hex(theme.foreground),
+        FONT
+    );
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        for run in line {
+            match run.class {
+                Some(_) => out.push_str(&format!(
+                    "<span style=\"color:{}\">{}</span>",
+                    hex(theme.color(run.class)),
+                    escape_html(&run.text)
+                )),
+                None => out.push_str(&escape_html(&run.text)),
+            }
+        }
+    }
+    out.push_str("</pre>");
+    out
+}
+
+#[cfg_attr(not(any(target_os = "windows", target_os = "macos")), allow(dead_code))]
+fn escape_rtf(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            ' '..='~' => out.push(c),
+            // Anything else as signed 16-bit units, with no fallback character.
+            _ => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    out.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+}
+
+#[cfg_attr(not(any(target_os = "windows", target_os = "macos")), allow(dead_code))]
+fn rtf(lines: &[Vec<Run>], theme: &export::Theme) -> String {
+    // Colour 1 is the background, 2 the plain foreground, then one per colour used.
+    let mut colors = vec![theme.background, theme.foreground];
+    let mut index = |color: Rgb| match colors.iter().position(|&c| c == color) {
+        Some(i) => i + 1,
+        None => {
+            colors.push(color);
+            colors.len()
+        }
+    };
+    let mut body = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            body.push_str("\\line\n");
+        }
+        for run in line {
+            body.push_str(&format!("\\cf{} ", index(theme.color(run.class))));
+            escape_rtf(&run.text, &mut body);
+        }
+    }
+    let table: String = colors
+        .iter()
+        .map(|[r, g, b]| format!("\\red{}\\green{}\\blue{};", r, g, b))
+        .collect();
+    // The background is set both ways: Word reads \chcbpat, Cocoa apps read \cb.
+    format!(
+        "{{\\rtf1\\ansi\\deff0{{\\fonttbl{{\\f0\\fmodern {};}}}}{{\\colortbl;{}}}\\f0\\fs20\\chshdng0\\chcbpat1\\cb1\n{}}}",
+        FONT, table, body
+    )
+}
+
+#[cfg(target_os = "windows")]
+mod win32 {
+    use windows::core::w;
+    use windows::Win32::Foundation::{HANDLE, HWND};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+
+    /// `CF_UNICODETEXT`.
+    const UNICODE_TEXT: u32 = 13;
+
+    /// The `HTML Format` flavour: the markup behind a header of byte offsets into it.
+    fn cf_html(fragment: &str) -> String {
+        let prefix = "<html><body>\r\n<!--StartFragment-->";
+        let suffix = "<!--EndFragment-->\r\n</body></html>";
+        let header = |[start_html, end_html, start_fragment, end_fragment]: [usize; 4]| {
+            format!(
+                "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+                start_html, end_html, start_fragment, end_fragment
+            )
+        };
+        // Offsets are zero-padded, so the header is as long whatever they are.
+        let start_html = header([0; 4]).len();
+        let start_fragment = start_html + prefix.len();
+        let end_fragment = start_fragment + fragment.len();
+        let end_html = end_fragment + suffix.len();
+        let header = header([start_html, end_html, start_fragment, end_fragment]);
+        format!("{}{}{}{}", header, prefix, fragment, suffix)
+    }
+
+    unsafe fn put(format: u32, bytes: &[u8]) -> Result<(), String> {
+        let memory = GlobalAlloc(GMEM_MOVEABLE, bytes.len()).map_err(|e| format!("Failed to allocate: {}", e))?;
+        let target = GlobalLock(memory) as *mut u8;
+        if target.is_null() {
+            return Err("Failed to lock clipboard memory".to_string());
+        }
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), target, bytes.len());
+        // Reports an "error" once the last lock is released.
+        let _ = GlobalUnlock(memory);
+        // The clipboard owns the memory from here on.
+        SetClipboardData(format, HANDLE(memory.0))
+            .map(|_| ())
+            .map_err(|e| format!("Failed to set clipboard data: {}", e))
+    }
+
+    pub fn copy(text: &str, html: &str, rtf: &str) -> Result<(), String> {
+        let unicode: Vec<u8> = text.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        unsafe {
+            OpenClipboard(HWND::default()).map_err(|e| format!("Failed to open the clipboard: {}", e))?;
+            let result = (|| {
+                EmptyClipboard().map_err(|e| format!("Failed to clear the clipboard: {}", e))?;
+                put(UNICODE_TEXT, &unicode)?;
+                put(RegisterClipboardFormatW(w!("HTML Format")), format!("{}\0", cf_html(html)).as_bytes())?;
+                put(RegisterClipboardFormatW(w!("Rich Text Format")), format!("{}\0", rtf).as_bytes())
+            })();
+            let _ = CloseClipboard();
+            result
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use cocoa::base::{id, nil, BOOL, YES};
+    use cocoa::foundation::NSString;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    pub fn copy(text: &str, html: &str, rtf: &str) -> Result<(), String> {
+        unsafe {
+            let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+            let _: isize = msg_send![pasteboard, clearContents];
+            for (kind, bytes) in [
+                ("public.utf8-plain-text", text.as_bytes()),
+                ("public.html", html.as_bytes()),
+                ("public.rtf", rtf.as_bytes()),
+            ] {
+                let data: id = msg_send![class!(NSData), dataWithBytes: bytes.as_ptr() length: bytes.len()];
+                let name: id = msg_send![NSString::alloc(nil).init_str(kind), autorelease];
+                let written: BOOL = msg_send![pasteboard, setData: data forType: name];
+                if written != YES {
+                    return Err(format!("Failed to put {} on the clipboard", kind));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copy `text` as plain text plus highlighted HTML and RTF, so pasting keeps the colours.
+#[tauri::command]
+pub fn copy_rich(text: String, language: Option<String>, theme: Option<ThemeName>) -> Result<(), String> {
+    let theme = export::theme(theme.unwrap_or_default());
+    let lines = export::styled_lines(&text, language.as_deref(), TAB_WIDTH);
+    let html = html(&lines, &theme);
+
+    #[cfg(target_os = "windows")]
+    return win32::copy(&text, &html, &rtf(&lines, &theme));
+
+    #[cfg(target_os = "macos")]
+    return macos::copy(&text, &html, &rtf(&lines, &theme));
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_html(html, Some(text)))
+            .map_err(|e| format!("Failed to copy: {}", e))
+    }
+}
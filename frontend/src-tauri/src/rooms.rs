@@ -0,0 +1,267 @@
+//! Rooms: hosting and joining a session by a six-digit code, so the webview deals in rooms and
+//! peers rather than servers, links and transports.
+//!
+//! `create_room` starts the session server for every open document, adds the host as a
+//! participant under their nickname, and answers discovery for the room's code: `join_room`
+//! broadcasts the code on the LAN (UDP port `DISCOVERY_PORT`) and joins whichever host answers
+//! with its session link, through the usual viewer connection. The code only finds the host; the
+//! link it answers with carries the session token that admits the viewer. An address that sends
+//! `MAX_WRONG_CODES` wrong codes gets no more answers, so the code can't be found by trying them
+//! all. Both ends need to be on one broadcast domain; across networks, share the link instead.
+//!
+//! The host sees participants come and go as `room-member-joined` and `room-member-left`. A peer
+//! that joined asks the host for the list with `list_peers`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::documents::DocumentStore;
+use crate::server;
+use crate::session::{self, Participant, Role, Session};
+use crate::viewer::{self, ViewerClient};
+
+const DISCOVERY_PORT: u16 = 47813;
+const QUERY_PREFIX: &[u8] = b"sharecode-room?";
+const ANSWER_PREFIX: &[u8] = b"sharecode-room!";
+/// How long `join_room` looks for the host, asking again every `DISCOVERY_RETRY`.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+const DISCOVERY_RETRY: Duration = Duration::from_millis(500);
+const MAX_WRONG_CODES: u32 = 10;
+
+struct HostedRoom {
+    host_id: String,
+    stopped: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct Rooms {
+    hosted: Mutex<Option<HostedRoom>>,
+    /// The code of the room joined, while watching one.
+    joined: Mutex<Option<String>>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomCode {
+    /// Six digits, shown as `123 456`; separators are ignored when joining.
+    pub code: String,
+    /// The session link, for joining from another network.
+    pub url: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinedRoom {
+    pub code: String,
+    /// This app's id among the room's peers.
+    pub participant_id: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Peer {
+    /// In a joined room, the host tells each peer its own id only.
+    pub id: Option<String>,
+    pub nickname: String,
+    pub role: Role,
+    pub joined_at: u64,
+    pub is_you: bool,
+}
+
+fn peer(participant: Participant, you: &str) -> Peer {
+    Peer {
+        is_you: participant.id == you,
+        id: Some(participant.id),
+        nickname: participant.name,
+        role: participant.role,
+        joined_at: participant.joined_at,
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemberLeft {
+    peer_id: String,
+    nickname: String,
+}
+
+fn digits(code: &str) -> String {
+    code.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+fn hosting(app: &AppHandle) -> bool {
+    app.try_state::<Rooms>().is_some_and(|rooms| rooms.hosted.lock().unwrap().is_some())
+}
+
+/// Called by the session when someone is admitted.
+pub fn member_joined(app: &AppHandle, participant: &Participant) {
+    if !hosting(app) {
+        return;
+    }
+    if let Err(e) = app.emit("room-member-joined", peer(participant.clone(), "")) {
+        log::warn!("Failed to emit room-member-joined: {}", e);
+    }
+}
+
+/// Called by the session when someone leaves or is removed.
+pub fn member_left(app: &AppHandle, participant: &Participant) {
+    if !hosting(app) {
+        return;
+    }
+    let left = MemberLeft {
+        peer_id: participant.id.clone(),
+        nickname: participant.name.clone(),
+    };
+    if let Err(e) = app.emit("room-member-left", left) {
+        log::warn!("Failed to emit room-member-left: {}", e);
+    }
+}
+
+/// Answer discovery queries for `code` with `url` until stopped.
+fn answer_discovery(socket: UdpSocket, code: String, url: String, stopped: Arc<AtomicBool>) {
+    let mut wrong: HashMap<IpAddr, u32> = HashMap::new();
+    let mut buf = [0u8; 64];
+    while !stopped.load(Ordering::Relaxed) {
+        // Times out every second to notice the room closing
+        let Ok((len, from)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        let Some(asked) = buf[..len].strip_prefix(QUERY_PREFIX) else {
+            continue;
+        };
+        let attempts = wrong.entry(from.ip()).or_default();
+        if *attempts >= MAX_WRONG_CODES {
+            continue;
+        }
+        if asked != code.as_bytes() {
+            *attempts += 1;
+            continue;
+        }
+        let answer = [ANSWER_PREFIX, url.as_bytes()].concat();
+        if let Err(e) = socket.send_to(&answer, from) {
+            log::warn!("Failed to answer room discovery from {}: {}", from, e);
+        }
+    }
+}
+
+/// Broadcast `code` and wait for a host to answer with its session link.
+fn discover(code: &str) -> Result<String, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| socket.set_broadcast(true).map(|_| socket))
+        .and_then(|socket| socket.set_read_timeout(Some(DISCOVERY_RETRY)).map(|_| socket))
+        .map_err(|e| format!("Failed to open a discovery socket: {}", e))?;
+    let query = [QUERY_PREFIX, code.as_bytes()].concat();
+    let broadcast = SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT));
+    let deadline = Instant::now() + DISCOVERY_TIMEOUT;
+    let mut buf = [0u8; 512];
+    while Instant::now() < deadline {
+        socket
+            .send_to(&query, broadcast)
+            .map_err(|e| format!("Failed to look for the room: {}", e))?;
+        let Ok((len, _)) = socket.recv_from(&mut buf) else {
+            continue;
+        };
+        if let Some(url) = buf[..len].strip_prefix(ANSWER_PREFIX) {
+            return Ok(String::from_utf8_lossy(url).into_owned());
+        }
+    }
+    Err("No room with that code was found on this network".to_string())
+}
+
+/// Open a room sharing every open document, with the host in it as `nickname`.
+#[tauri::command]
+pub fn create_room(app: AppHandle, rooms: State<'_, Rooms>, nickname: Option<String>) -> Result<RoomCode, String> {
+    if rooms.hosted.lock().unwrap().is_some() {
+        return Err("A room is already open; leave it first".to_string());
+    }
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))
+        .and_then(|socket| socket.set_read_timeout(Some(Duration::from_secs(1))).map(|_| socket))
+        .map_err(|e| format!("Failed to listen for room discovery (is another room open here?): {}", e))?;
+
+    let document_ids: Vec<String> = app.state::<DocumentStore>().docs.lock().unwrap().keys().cloned().collect();
+    let info = server::start_session_server(app.clone(), app.state(), None, document_ids, Some(false))?;
+    let nickname = nickname.filter(|n| !n.trim().is_empty()).unwrap_or_else(|| "Host".to_string());
+    let host = session::admit(&app, &app.state::<Session>(), None, nickname, Role::Host)?;
+
+    let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+    let stopped = Arc::new(AtomicBool::new(false));
+    let (answer_code, answer_url, answer_stopped) = (code.clone(), info.url.clone(), Arc::clone(&stopped));
+    thread::spawn(move || answer_discovery(socket, answer_code, answer_url, answer_stopped));
+    *rooms.hosted.lock().unwrap() = Some(HostedRoom {
+        host_id: host.id,
+        stopped,
+    });
+    Ok(RoomCode {
+        code: format!("{} {}", &code[..3], &code[3..]),
+        url: info.url,
+    })
+}
+
+/// Find the room with `code` on the LAN and join it as `nickname`.
+#[tauri::command]
+pub async fn join_room(app: AppHandle, rooms: State<'_, Rooms>, code: String, nickname: String) -> Result<JoinedRoom, String> {
+    let code = digits(&code);
+    if code.len() != 6 {
+        return Err("Room codes have six digits".to_string());
+    }
+    let asked = code.clone();
+    let link = tauri::async_runtime::spawn_blocking(move || discover(&asked))
+        .await
+        .map_err(|e| e.to_string())??;
+    let joined = viewer::join_remote_session(app.clone(), app.state(), link, nickname).await?;
+    *rooms.joined.lock().unwrap() = Some(code.clone());
+    Ok(JoinedRoom {
+        code: format!("{} {}", &code[..3], &code[3..]),
+        participant_id: joined.participant_id,
+    })
+}
+
+/// Close the room this app hosts, or leave the one it joined. Returns false if in neither.
+#[tauri::command]
+pub fn leave_room(app: AppHandle, rooms: State<'_, Rooms>) -> bool {
+    // Taken first: dismissing the host reports to `member_left`, which checks the room
+    let hosted = rooms.hosted.lock().unwrap().take();
+    if let Some(room) = hosted {
+        room.stopped.store(true, Ordering::Relaxed);
+        session::dismiss(&app, &app.state::<Session>(), &room.host_id).ok();
+        server::stop_session_server(app.clone(), app.state());
+        return true;
+    }
+    if rooms.joined.lock().unwrap().take().is_some() {
+        return viewer::leave_remote_session(app.clone(), app.state());
+    }
+    false
+}
+
+/// Everyone in the room, in order of arrival.
+#[tauri::command]
+pub fn list_peers(
+    rooms: State<'_, Rooms>,
+    session: State<'_, Session>,
+    client: State<'_, ViewerClient>,
+) -> Result<Vec<Peer>, String> {
+    if let Some(room) = rooms.hosted.lock().unwrap().as_ref() {
+        let peers = session.participants().into_iter().map(|p| peer(p, &room.host_id));
+        return Ok(peers.collect());
+    }
+    if rooms.joined.lock().unwrap().is_some() {
+        let (_, you) = client.remote().ok_or("The connection to the room was closed")?;
+        let peers = client.peers()?.into_iter().map(|p| Peer {
+            is_you: p.id.as_deref() == Some(you.as_str()),
+            id: p.id,
+            nickname: p.name,
+            role: p.role,
+            joined_at: p.joined_at,
+        });
+        return Ok(peers.collect());
+    }
+    Err("Not in a room".to_string())
+}
@@ -0,0 +1,294 @@
+//! Run code snippets in a throwaway directory with a time limit and capped output.
+//!
+//! This is not a security boundary: the program runs as the current user. It keeps runs from
+//! touching the user's files by accident, hanging forever, or flooding memory with output.
+//!
+//! The program runs in a process group of its own (a job object on Windows), since run commands
+//! like `go run` or `npx tsx` start the program as a grandchild. A timeout ends the whole group,
+//! and so does anything it leaves holding the output pipes after it exits.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::language;
+use crate::settings::SettingsStore;
+use crate::storage::new_id;
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_OUTPUT: usize = 64 * 1024;
+/// How long output may keep arriving after the program exits or is killed.
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunOutput {
+    /// `None` if the process was killed (timeout or signal).
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    /// Output beyond the cap was discarded.
+    pub truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// File name the run command is pointed at, chosen so interpreters recognise the language.
+fn file_name(language: &str) -> &'static str {
+    match language {
+        "python" => "main.py",
+        "javascript" => "main.js",
+        "typescript" => "main.ts",
+        "java" => "Main.java",
+        "c" => "main.c",
+        "cpp" => "main.cpp",
+        "csharp" => "Program.cs",
+        "go" => "main.go",
+        "rust" => "main.rs",
+        "php" => "main.php",
+        "ruby" => "main.rb",
+        "swift" => "main.swift",
+        "kotlin" => "main.kts",
+        "shell" => "main.sh",
+        _ => "main.txt",
+    }
+}
+
+/// Removes the run directory however the run ends.
+struct RunDir(PathBuf);
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// The run's process and everything it starts.
+struct ProcessGroup {
+    #[cfg(unix)]
+    id: libc::pid_t,
+    #[cfg(windows)]
+    job: windows::Win32::Foundation::HANDLE,
+}
+
+impl ProcessGroup {
+    /// Spawn `command` as the leader of a new group.
+    fn spawn(command: &mut Command) -> std::io::Result<(Child, Self)> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+
+            let child = command.process_group(0).spawn()?;
+            let id = child.id() as libc::pid_t;
+            Ok((child, Self { id }))
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::AsRawHandle;
+            use windows::core::PCWSTR;
+            use windows::Win32::Foundation::HANDLE;
+            use windows::Win32::System::JobObjects::{
+                AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+                SetInformationJobObject, JOBOBJECT_BASIC_LIMIT_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+                JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            };
+
+            let mut child = command.spawn()?;
+            // Closing the job, when the group is dropped, ends whatever is still in it
+            let limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION {
+                BasicLimitInformation: JOBOBJECT_BASIC_LIMIT_INFORMATION {
+                    LimitFlags: JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let joined = unsafe {
+                CreateJobObjectW(None, PCWSTR::null()).and_then(|job| {
+                    let group = Self { job };
+                    SetInformationJobObject(
+                        group.job,
+                        JobObjectExtendedLimitInformation,
+                        &limits as *const _ as *const std::ffi::c_void,
+                        std::mem::size_of_val(&limits) as u32,
+                    )?;
+                    AssignProcessToJobObject(group.job, HANDLE(child.as_raw_handle()))?;
+                    Ok(group)
+                })
+            };
+            let group = match joined {
+                Ok(group) => group,
+                Err(e) => {
+                    child.kill().ok();
+                    child.wait().ok();
+                    return Err(std::io::Error::other(e));
+                }
+            };
+            Ok((child, group))
+        }
+    }
+
+    /// Kill every process in the group.
+    fn kill(&self) {
+        // The group outlives its leader while any member runs, so the id is not reused
+        #[cfg(unix)]
+        unsafe {
+            libc::killpg(self.id, libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        unsafe {
+            windows::Win32::System::JobObjects::TerminateJobObject(self.job, 1).ok();
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        unsafe {
+            windows::Win32::Foundation::CloseHandle(self.job).ok();
+        }
+    }
+}
+
+/// Output read so far, and whether any was discarded past the cap.
+type Captured = Arc<Mutex<(Vec<u8>, bool)>>;
+
+/// Read `reader` on a thread until it closes. The receiver hears when it has.
+fn read_capped(mut reader: impl Read + Send + 'static) -> (Captured, mpsc::Receiver<()>) {
+    let captured = Captured::default();
+    let (done, finished) = mpsc::channel();
+    let output = Arc::clone(&captured);
+    thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        // Keep draining past the cap so the child never blocks on a full pipe.
+        while let Ok(n) = reader.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let (kept, truncated) = &mut *output.lock().unwrap();
+            let room = MAX_OUTPUT.saturating_sub(kept.len());
+            kept.extend_from_slice(&buf[..n.min(room)]);
+            *truncated |= n > room;
+        }
+        done.send(()).ok();
+    });
+    (captured, finished)
+}
+
+/// Write `code` to a fresh directory and run `command` (with `{file}` substituted) there.
+pub fn run(command: &str, language: &str, code: &str, stdin: &str, timeout: Duration) -> Result<RunOutput, String> {
+    let dir = RunDir(std::env::temp_dir().join(format!("sharecode-run-{}", new_id())));
+    fs::create_dir_all(&dir.0).map_err(|e| format!("Failed to create {}: {}", dir.0.display(), e))?;
+    let file = dir.0.join(file_name(language));
+    fs::write(&file, code).map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+
+    let file = file.to_string_lossy();
+    let mut args = command.split_whitespace().map(|part| part.replace("{file}", &file));
+    let program = args.next().ok_or("Run command is empty")?;
+    let started = Instant::now();
+    let (mut child, group) = ProcessGroup::spawn(
+        Command::new(&program)
+            .args(args)
+            .current_dir(&dir.0)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if let Some(mut pipe) = child.stdin.take() {
+        let input = stdin.as_bytes().to_vec();
+        // A program that never reads stdin must not block the runner.
+        thread::spawn(move || pipe.write_all(&input).ok());
+    }
+    let (stdout, stdout_done) = read_capped(child.stdout.take().ok_or("Missing stdout pipe")?);
+    let (stderr, stderr_done) = read_capped(child.stderr.take().ok_or("Missing stderr pipe")?);
+
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break Some(status);
+        }
+        if started.elapsed() >= timeout {
+            group.kill();
+            child.wait().ok();
+            timed_out = true;
+            break None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    // Whatever the program started may still hold the pipes; end it if it does.
+    let deadline = Instant::now() + DRAIN_GRACE;
+    let drained = |done: &mpsc::Receiver<()>| done.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_ok();
+    if !(drained(&stdout_done) && drained(&stderr_done)) {
+        group.kill();
+        // Still open after this means a process outside the group holds them; stop waiting.
+        stdout_done.recv_timeout(DRAIN_GRACE).ok();
+        stderr_done.recv_timeout(DRAIN_GRACE).ok();
+    }
+    let (stdout, out_truncated) = std::mem::take(&mut *stdout.lock().unwrap());
+    let (stderr, err_truncated) = std::mem::take(&mut *stderr.lock().unwrap());
+    Ok(RunOutput {
+        exit_code: status.and_then(|s| s.code()),
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        timed_out,
+        truncated: out_truncated || err_truncated,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// The configured run command for `language`.
+pub fn run_command(app: &AppHandle, settings: &SettingsStore, language: &str) -> Result<String, String> {
+    language::resolve_profile(app, settings, language)?
+        .run_command
+        .ok_or_else(|| format!("No run command configured for {}", language))
+}
+
+/// Run a snippet with the language's configured run command.
+#[tauri::command]
+pub async fn run_code(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    language: String,
+    code: String,
+    stdin: Option<String>,
+    timeout_ms: Option<u64>,
+) -> Result<RunOutput, String> {
+    let command = run_command(&app, &settings, &language)?;
+    let timeout = timeout_ms.map(Duration::from_millis).unwrap_or(DEFAULT_TIMEOUT);
+    tauri::async_runtime::spawn_blocking(move || run(&command, &language, &code, &stdin.unwrap_or_default(), timeout))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_ends_grandchildren() {
+        let started = Instant::now();
+        let output = run("sh {file}", "shell", "sleep 30 & sleep 30", "", Duration::from_millis(300)).unwrap();
+        assert!(output.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn leftovers_holding_the_pipes_are_ended() {
+        let started = Instant::now();
+        let output = run("sh {file}", "shell", "echo hi; sleep 30 &", "", Duration::from_secs(10)).unwrap();
+        assert!(!output.timed_out);
+        assert_eq!(output.exit_code, Some(0));
+        assert_eq!(output.stdout, "hi\n");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
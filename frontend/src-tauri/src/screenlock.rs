@@ -0,0 +1,100 @@
+//! Pause the session while the screen is locked, so stepping away doesn't leave a live feed
+//! running.
+//!
+//! On lock, a hosted session is paused (see `lifecycle`) and `screen-lock-changed` tells the
+//! frontend to blank what it shares. On unlock the session is resumed, but only if the lock paused
+//! it; one the host paused by hand stays paused. Windows reports locks through session
+//! notifications on the main window (see `watch_session` in the crate root), macOS through the
+//! distributed `com.apple.screenIsLocked` notifications. Linux desktops announce locks over D-Bus,
+//! which the app does not listen to, so nothing happens there.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use crate::lifecycle;
+
+/// Set while a session is paused because of the lock.
+static PAUSED_BY_LOCK: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScreenLockChanged {
+    locked: bool,
+    /// Whether the session is paused because of the lock.
+    session_paused: bool,
+}
+
+fn changed(app: &AppHandle, locked: bool) {
+    let event = ScreenLockChanged {
+        locked,
+        session_paused: PAUSED_BY_LOCK.load(Ordering::Relaxed),
+    };
+    if let Err(e) = app.emit("screen-lock-changed", event) {
+        log::warn!("Failed to emit screen-lock-changed: {}", e);
+    }
+}
+
+pub fn locked(app: &AppHandle) {
+    // Fails when nothing is being hosted, or it is paused already.
+    if lifecycle::pause_session(app.clone()).is_ok() {
+        log::info!("Screen locked; session paused");
+        PAUSED_BY_LOCK.store(true, Ordering::Relaxed);
+    }
+    changed(app, true);
+}
+
+pub fn unlocked(app: &AppHandle) {
+    if PAUSED_BY_LOCK.swap(false, Ordering::Relaxed) {
+        match lifecycle::resume_session(app.clone()) {
+            Ok(()) => log::info!("Screen unlocked; session resumed"),
+            // Ended while locked.
+            Err(e) => log::info!("Screen unlocked; not resuming: {}", e),
+        }
+    }
+    changed(app, false);
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use block::ConcreteBlock;
+    use cocoa::base::nil;
+    use cocoa::foundation::NSString;
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+    use tauri::AppHandle;
+
+    type Id = *mut Object;
+
+    /// Observe lock and unlock. Must run on the main thread.
+    pub unsafe fn install(app: AppHandle) {
+        let center: Id = msg_send![class!(NSDistributedNotificationCenter), defaultCenter];
+        let queue: Id = msg_send![class!(NSOperationQueue), mainQueue];
+        for (name, locked) in [("com.apple.screenIsLocked", true), ("com.apple.screenIsUnlocked", false)] {
+            let app = app.clone();
+            let handler = ConcreteBlock::new(move |_notification: Id| {
+                if locked {
+                    super::locked(&app);
+                } else {
+                    super::unlocked(&app);
+                }
+            })
+            .copy();
+            let name = NSString::alloc(nil).init_str(name);
+            let _: Id = msg_send![center, addObserverForName: name object: nil queue: queue usingBlock: &*handler];
+            // Observed for the life of the app, so the handler must stay too.
+            std::mem::forget(handler);
+        }
+    }
+}
+
+/// Start watching for the screen locking. Called once from setup, on the main thread.
+pub fn install(app: &AppHandle) {
+    #[cfg(target_os = "macos")]
+    unsafe {
+        macos::install(app.clone());
+    }
+    #[cfg(not(target_os = "macos"))]
+    let _ = app;
+}
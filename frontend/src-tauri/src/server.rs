@@ -0,0 +1,845 @@
+//! Embedded HTTP server through which viewers on other machines join the session.
+//!
+//! A viewer first posts to `/session/join`, and the backend answers with the transport to use:
+//! a WebSocket, server-sent events, or long-polling for networks whose proxies strip upgrades and
+//! buffer streamed responses. The client reports transports that failed on a previous attempt so
+//! the next join falls back further. All three carry the same frames from the [`SharingHub`],
+//! and messages from viewers come in through `POST /session/send` whichever transport is used.
+//!
+//! Optionally the server also serves a read-only web viewer at the session URL, for participants
+//! without the app. Its highlighting is rendered here (`/session/render`) so the page needs no
+//! grammars of its own. Every endpoint checks the session token, and web viewers join as viewers.
+//!
+//! Each participant is given a secret when it joins, and every request made for it must carry it,
+//! so knowing someone's participant id is not enough to speak for them. Ids are not handed out
+//! either: `/session/peers` lists everyone else without theirs. Tokens and secrets are compared
+//! in constant time.
+//!
+//! Requests are refused with 503 past `MAX_CONNECTIONS` at once, and with 429 past
+//! `MAX_PER_PARTICIPANT` for one participant, so no client can tie up threads without bound.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::UdpSocket;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde_json::json;
+use subtle::ConstantTimeEq;
+use tauri::{AppHandle, Manager, State};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tungstenite::protocol::Role as WsRole;
+use tungstenite::WebSocket;
+
+use crate::documents::DocumentStore;
+use crate::highlight::Highlights;
+use crate::lifecycle::{self, HostEvent};
+use crate::magnifier;
+use crate::minimap::Minimaps;
+use crate::netsim::{self, Direction};
+use crate::protocol::{Message, Negotiated};
+use crate::session::{self, Role, Session};
+use crate::sharing::{SharingHub, ViewerHandle};
+use crate::storage::new_id;
+use crate::syntax::HighlightSpan;
+
+const VIEWER_PAGE: &str = include_str!("../assets/viewer.html");
+
+/// How long a long-poll request waits for the first frame before returning empty.
+const POLL_WAIT: Duration = Duration::from_secs(25);
+/// Frames returned by one long-poll response at most.
+const POLL_BATCH: usize = 64;
+/// A long-polling viewer that has not polled for this long is considered gone.
+const POLL_EXPIRY: Duration = Duration::from_secs(60);
+/// Keep-alive interval for streaming transports, short enough for idle-timeout proxies.
+const HEARTBEAT: Duration = Duration::from_secs(20);
+const MAX_BODY: u64 = 1024 * 1024;
+/// Requests handled at once. Each has a thread, as streams hold their connection open.
+const MAX_CONNECTIONS: usize = 128;
+/// Requests one participant may have in flight: a stream, a send and a few fetches.
+const MAX_PER_PARTICIPANT: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Transport {
+    #[serde(rename = "websocket")]
+    WebSocket,
+    #[serde(rename = "sse")]
+    Sse,
+    #[serde(rename = "long-poll")]
+    LongPoll,
+}
+
+const TRANSPORTS: [Transport; 3] = [Transport::WebSocket, Transport::Sse, Transport::LongPoll];
+
+struct Polling {
+    handle: Arc<Mutex<ViewerHandle>>,
+    last_seen: Instant,
+}
+
+struct Running {
+    server: Arc<Server>,
+    port: u16,
+    token: String,
+    document_ids: Vec<String>,
+    polling: Mutex<HashMap<String, Polling>>,
+    /// Participant id -> the secret its requests must carry.
+    secrets: Mutex<HashMap<String, String>>,
+    /// What each viewer negotiated when it joined.
+    negotiated: Mutex<HashMap<String, Negotiated>>,
+    /// Serve the web viewer page at `/session`.
+    web_viewer: bool,
+    /// Document id -> (timeline seq, rendered payload), shared by every web viewer.
+    rendered: Mutex<HashMap<String, (u64, Arc<[u8]>)>>,
+    /// Requests being handled, against `MAX_CONNECTIONS`.
+    active: AtomicUsize,
+    /// Participant id -> requests being handled for it, against `MAX_PER_PARTICIPANT`.
+    in_flight: Mutex<HashMap<String, usize>>,
+    stopped: AtomicBool,
+}
+
+/// A request counted against `MAX_PER_PARTICIPANT`, until dropped.
+struct Slot<'a> {
+    running: &'a Running,
+    participant_id: String,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.running.in_flight.lock().unwrap();
+        if let Some(count) = in_flight.get_mut(&self.participant_id) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.participant_id);
+            }
+        }
+    }
+}
+
+/// A request counted against `MAX_CONNECTIONS`, until dropped.
+struct Active(Arc<Running>);
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[derive(Default)]
+pub struct SessionServer {
+    running: Mutex<Option<Arc<Running>>>,
+}
+
+impl SessionServer {
+    /// The session URL viewers join by, while the server runs.
+    pub fn url(&self) -> Option<String> {
+        self.running.lock().unwrap().as_ref().map(|r| r.info().url)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub port: u16,
+    /// Address to hand to viewers, including the session token.
+    pub url: String,
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JoinRequest {
+    token: String,
+    name: String,
+    /// Set when renegotiating the transport of a viewer that already joined, with `secret`.
+    participant_id: Option<String>,
+    /// The secret issued with `participant_id`; without the right one, the viewer joins afresh.
+    secret: Option<String>,
+    /// Transports the client implements, in its order of preference.
+    supports: Option<Vec<Transport>>,
+    /// Transports that did not work for this client on an earlier attempt.
+    #[serde(default)]
+    failed: Vec<Transport>,
+    /// Protocol version of the client; clients from before versioning send none.
+    protocol: Option<u32>,
+    /// Capabilities the client implements; without them, those of its protocol version.
+    capabilities: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JoinResponse {
+    participant_id: String,
+    /// Sent as `secret` with every later request.
+    secret: String,
+    transport: Transport,
+    #[serde(flatten)]
+    negotiated: Negotiated,
+}
+
+/// An error answered with an HTTP status and a JSON `{ "error": ... }` body.
+type Reply = Result<Vec<u8>, (u16, String)>;
+
+fn header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv(name))
+        .map(|h| h.value.as_str())
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).unwrap()
+}
+
+/// Path and query parameters of a request URL. Parameters are ids and tokens, which are hex, so
+/// no percent-decoding is needed.
+fn split_url(url: &str) -> (String, HashMap<String, String>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let params = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    (path.to_string(), params)
+}
+
+fn read_json<T: DeserializeOwned>(request: &mut Request) -> Result<T, (u16, String)> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY)
+        .read_to_end(&mut body)
+        .map_err(|e| (400, e.to_string()))?;
+    serde_json::from_slice(&body).map_err(|e| (400, format!("Invalid request: {}", e)))
+}
+
+/// Pick the transport for a joining client: its first supported one that has not failed,
+/// preferring streams over a WebSocket when the request came through a proxy.
+fn negotiate(request: &Request, supports: Option<Vec<Transport>>, failed: &[Transport]) -> Transport {
+    let mut candidates: Vec<Transport> = supports
+        .unwrap_or_else(|| TRANSPORTS.to_vec())
+        .into_iter()
+        .filter(|t| !failed.contains(t))
+        .collect();
+    let proxied = ["Via", "Forwarded", "X-Forwarded-For"]
+        .iter()
+        .any(|name| header(request, *name).is_some());
+    if proxied {
+        candidates.sort_by_key(|t| *t == Transport::WebSocket);
+    }
+    // Long-polling works through anything that passes plain HTTP.
+    candidates.first().copied().unwrap_or(Transport::LongPoll)
+}
+
+/// Compare in constant time, so how long a rejection takes says nothing about how much of a
+/// token or secret was right.
+fn same_secret(given: Option<&String>, expected: Option<&String>) -> bool {
+    match (given, expected) {
+        (Some(given), Some(expected)) => given.as_bytes().ct_eq(expected.as_bytes()).into(),
+        _ => false,
+    }
+}
+
+impl Running {
+    /// The participant a request is made for, after checking the session token and the
+    /// participant's secret.
+    fn authorize(&self, app: &AppHandle, query: &HashMap<String, String>) -> Result<String, (u16, String)> {
+        if !same_secret(query.get("token"), Some(&self.token)) {
+            return Err((403, "Invalid session token".to_string()));
+        }
+        let participant_id = query.get("participant").ok_or((400, "Missing participant".to_string()))?;
+        let secret = query.get("secret").ok_or((403, "Missing participant secret".to_string()))?;
+        if !same_secret(Some(secret), self.secrets.lock().unwrap().get(participant_id)) {
+            return Err((403, "Invalid participant secret".to_string()));
+        }
+        app.state::<Session>()
+            .participant(participant_id)
+            .map_err(|e| (403, e))?;
+        Ok(participant_id.clone())
+    }
+
+    /// Count a request made for a participant, refusing it if they already have too many.
+    fn claim(&self, participant_id: &str) -> Option<Slot<'_>> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(participant_id.to_string()).or_default();
+        if *count >= MAX_PER_PARTICIPANT {
+            return None;
+        }
+        *count += 1;
+        Some(Slot {
+            running: self,
+            participant_id: participant_id.to_string(),
+        })
+    }
+
+    fn info(&self) -> ServerInfo {
+        ServerInfo {
+            port: self.port,
+            url: format!("http://{}:{}/session?token={}", local_address(), self.port, self.token),
+            token: self.token.clone(),
+        }
+    }
+
+    fn join_hub(&self, app: &AppHandle, participant_id: &str) -> Result<ViewerHandle, String> {
+        let store = app.state::<DocumentStore>();
+        let negotiated = self
+            .negotiated
+            .lock()
+            .unwrap()
+            .get(participant_id)
+            .cloned()
+            .unwrap_or_else(Negotiated::legacy);
+        let handle = app
+            .state::<Arc<SharingHub>>()
+            .join(participant_id, &store, &self.document_ids, negotiated)?;
+        magnifier::catch_up(app, participant_id);
+        Ok(handle)
+    }
+
+    /// Re-join a viewer that fell behind, so its next frames are fresh snapshots.
+    fn resync(&self, app: &AppHandle, participant_id: &str, handle: &mut ViewerHandle) -> Result<(), String> {
+        if handle.needs_resync.swap(false, Ordering::Relaxed) {
+            *handle = self.join_hub(app, participant_id)?;
+        }
+        Ok(())
+    }
+
+    /// Drop a viewer that disconnected or stopped polling.
+    fn leave(&self, app: &AppHandle, participant_id: &str) {
+        self.polling.lock().unwrap().remove(participant_id);
+        self.negotiated.lock().unwrap().remove(participant_id);
+        self.secrets.lock().unwrap().remove(participant_id);
+        app.state::<Arc<SharingHub>>().leave(participant_id);
+        session::dismiss(app, &app.state::<Session>(), participant_id).ok();
+    }
+}
+
+fn join(app: &AppHandle, running: &Running, request: &mut Request) -> Reply {
+    let body: JoinRequest = read_json(request)?;
+    if !same_secret(Some(&body.token), Some(&running.token)) {
+        return Err((403, "Invalid session token".to_string()));
+    }
+    // Refused before admitting anyone, with a status old clients show as an error.
+    let negotiated =
+        Negotiated::with(body.protocol.unwrap_or(1), body.capabilities.as_deref()).map_err(|e| (426, e))?;
+    let transport = negotiate(request, body.supports, &body.failed);
+    let session = app.state::<Session>();
+    // Resuming takes the secret issued with the id; anyone else who names it joins afresh.
+    let resuming = body.participant_id.and_then(|id| {
+        let secret = running.secrets.lock().unwrap().get(&id).cloned()?;
+        let viewer = session.participant(&id).is_ok_and(|p| p.role == Role::Viewer);
+        (viewer && same_secret(body.secret.as_ref(), Some(&secret))).then_some((id, secret))
+    });
+    let (participant_id, secret) = match resuming {
+        Some(resumed) => resumed,
+        None => {
+            let id = session::admit(app, &session, None, body.name, Role::Viewer).map_err(|e| (500, e))?.id;
+            let secret = new_secret();
+            running.secrets.lock().unwrap().insert(id.clone(), secret.clone());
+            (id, secret)
+        }
+    };
+    running
+        .negotiated
+        .lock()
+        .unwrap()
+        .insert(participant_id.clone(), negotiated.clone());
+    let response = JoinResponse {
+        participant_id,
+        secret,
+        transport,
+        negotiated,
+    };
+    serde_json::to_vec(&response).map_err(|e| (500, e.to_string()))
+}
+
+fn poll(app: &AppHandle, running: &Running, query: &HashMap<String, String>) -> Reply {
+    let participant_id = running.authorize(app, query)?;
+    let existing = running
+        .polling
+        .lock()
+        .unwrap()
+        .get(&participant_id)
+        .map(|p| Arc::clone(&p.handle));
+    let handle = match existing {
+        Some(handle) => handle,
+        None => {
+            let handle = Arc::new(Mutex::new(running.join_hub(app, &participant_id).map_err(|e| (503, e))?));
+            let entry = Polling {
+                handle: Arc::clone(&handle),
+                last_seen: Instant::now(),
+            };
+            running.polling.lock().unwrap().insert(participant_id.clone(), entry);
+            handle
+        }
+    };
+    // A second poll from the same viewer waits for the first one to finish.
+    let mut handle = handle.lock().unwrap();
+    running.resync(app, &participant_id, &mut handle).map_err(|e| (503, e))?;
+    // Clients saving battery poll on a timer with `wait=0` instead of holding a request open.
+    let wait = query
+        .get("wait")
+        .and_then(|secs| secs.parse().ok())
+        .map_or(POLL_WAIT, |secs| Duration::from_secs(secs).min(POLL_WAIT));
+    let mut frames = Vec::new();
+    if let Ok(frame) = handle.frames.recv_timeout(wait) {
+        frames.push(frame);
+        while frames.len() < POLL_BATCH {
+            match handle.frames.try_recv() {
+                Ok(frame) => frames.push(frame),
+                Err(_) => break,
+            }
+        }
+    }
+    if let Some(entry) = running.polling.lock().unwrap().get_mut(&participant_id) {
+        entry.last_seen = Instant::now();
+    }
+    // Frames are already JSON; splice them into the array rather than re-encoding.
+    let mut body = b"{\"messages\":[".to_vec();
+    for (i, frame) in frames.iter().enumerate() {
+        if i > 0 {
+            body.push(b',');
+        }
+        body.extend_from_slice(frame);
+    }
+    body.extend_from_slice(b"]}");
+    Ok(body)
+}
+
+/// A document's text with highlight spans, ready for a viewer to display without a grammar.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedDocument {
+    pub document_id: String,
+    pub seq: u64,
+    pub language: Option<String>,
+    pub text: String,
+    pub spans: Vec<HighlightSpan>,
+}
+
+/// A shared document's text with highlight spans, cached per timeline position.
+fn render(app: &AppHandle, running: &Running, query: &HashMap<String, String>) -> Reply {
+    running.authorize(app, query)?;
+    let doc_id = query.get("document").ok_or((400, "Missing document".to_string()))?;
+    if !running.document_ids.contains(doc_id) {
+        return Err((403, "Document is not shared".to_string()));
+    }
+    let store = app.state::<DocumentStore>();
+    let seq = store.with(doc_id, |doc| doc.timeline.seq()).map_err(|e| (404, e))?;
+    if let Some((cached, body)) = running.rendered.lock().unwrap().get(doc_id) {
+        if *cached == seq {
+            return Ok(body.to_vec());
+        }
+    }
+    let (seq, language, text, spans) = app
+        .state::<Highlights>()
+        .render(&store, doc_id)
+        .map_err(|e| (404, e))?;
+    let rendered = RenderedDocument {
+        document_id: doc_id.clone(),
+        seq,
+        language,
+        text,
+        spans,
+    };
+    let body: Arc<[u8]> = serde_json::to_vec(&rendered).map_err(|e| (500, e.to_string()))?.into();
+    running
+        .rendered
+        .lock()
+        .unwrap()
+        .insert(doc_id.clone(), (seq, Arc::clone(&body)));
+    Ok(body.to_vec())
+}
+
+/// A shared document's minimap, so viewers need not highlight the whole file to draw one.
+fn minimap(app: &AppHandle, running: &Running, query: &HashMap<String, String>) -> Reply {
+    running.authorize(app, query)?;
+    let doc_id = query.get("document").ok_or((400, "Missing document".to_string()))?;
+    if !running.document_ids.contains(doc_id) {
+        return Err((403, "Document is not shared".to_string()));
+    }
+    let bitmap = app
+        .state::<Minimaps>()
+        .bitmap(&app.state::<DocumentStore>(), doc_id)
+        .map_err(|e| (404, e))?;
+    serde_json::to_vec(&bitmap).map_err(|e| (500, e.to_string()))
+}
+
+/// A participant as the others see them, without the id only they are told.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    /// Set only on the entry of the participant asking.
+    pub id: Option<String>,
+    pub name: String,
+    pub role: Role,
+    pub hand_raised: bool,
+    pub joined_at: u64,
+    pub color: String,
+}
+
+/// Everyone in the session, for peers that joined a room.
+fn peers(app: &AppHandle, running: &Running, query: &HashMap<String, String>) -> Reply {
+    let you = running.authorize(app, query)?;
+    let peers: Vec<PeerInfo> = app
+        .state::<Session>()
+        .participants()
+        .into_iter()
+        .map(|p| PeerInfo {
+            id: (p.id == you).then_some(p.id),
+            name: p.name,
+            role: p.role,
+            hand_raised: p.hand_raised,
+            joined_at: p.joined_at,
+            color: p.color,
+        })
+        .collect();
+    serde_json::to_vec(&peers).map_err(|e| (500, e.to_string()))
+}
+
+fn page(running: &Running, query: &HashMap<String, String>) -> Response<std::io::Cursor<Vec<u8>>> {
+    if !running.web_viewer {
+        return error_response(404, "Not found");
+    }
+    if !same_secret(query.get("token"), Some(&running.token)) {
+        return error_response(403, "Invalid session token");
+    }
+    Response::from_string(VIEWER_PAGE).with_header(content_type("text/html; charset=utf-8"))
+}
+
+/// Accept a message from a viewer. Viewers may only speak for themselves, and only send the
+/// kinds of message a participant originates.
+fn send(app: &AppHandle, running: &Running, request: &mut Request, query: &HashMap<String, String>) -> Reply {
+    let participant_id = running.authorize(app, query)?;
+    let message: Message = read_json(request)?;
+    let sender = match &message {
+        Message::Signal { participant_id, .. }
+        | Message::Vote { participant_id, .. }
+        | Message::Scratchpad { participant_id, .. }
+        | Message::Stroke { participant_id, .. }
+        | Message::ActionRequest { participant_id, .. }
+        | Message::Receipt { participant_id, .. } => participant_id,
+        // A newer viewer using something this host lacks; it carries on without it.
+        Message::Unsupported => return Ok(json!({ "accepted": false }).to_string().into_bytes()),
+        _ => return Err((403, "Viewers cannot send this message".to_string())),
+    };
+    if *sender != participant_id {
+        return Err((403, "Message is not from this participant".to_string()));
+    }
+    let accepted = session::route(app, &app.state::<Session>(), message).map_err(|e| (400, e))?;
+    Ok(json!({ "accepted": accepted }).to_string().into_bytes())
+}
+
+/// Stream frames until the viewer goes away (`true`) or is attached elsewhere (`false`).
+fn stream_websocket(app: &AppHandle, running: &Running, request: Request, participant_id: &str) -> bool {
+    let Some(key) = header(&request, "Sec-WebSocket-Key") else {
+        request.respond(Response::empty(400)).ok();
+        return false;
+    };
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+    let mut handle = match running.join_hub(app, participant_id) {
+        Ok(handle) => handle,
+        Err(e) => {
+            request.respond(error_response(503, &e)).ok();
+            return false;
+        }
+    };
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes(&b"Sec-WebSocket-Accept"[..], accept.as_bytes()).unwrap());
+    let stream = request.upgrade("websocket", response);
+    let mut socket = WebSocket::from_raw_socket(stream, WsRole::Server, None);
+    let gone = loop {
+        if running.stopped.load(Ordering::Relaxed) || running.resync(app, participant_id, &mut handle).is_err() {
+            break true;
+        }
+        let sent = match handle.frames.recv_timeout(HEARTBEAT) {
+            Ok(frame) => {
+                if netsim::transmit(Direction::Upload, frame.len()).is_err() {
+                    break true;
+                }
+                socket.send(tungstenite::Message::Text(String::from_utf8_lossy(&frame).into_owned()))
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => socket.send(tungstenite::Message::Ping(Vec::new())),
+            Err(mpsc::RecvTimeoutError::Disconnected) => break false,
+        };
+        if sent.is_err() {
+            break true;
+        }
+    };
+    socket.close(None).ok();
+    gone
+}
+
+fn stream_events(app: &AppHandle, running: &Running, request: Request, participant_id: &str) -> bool {
+    let mut handle = match running.join_hub(app, participant_id) {
+        Ok(handle) => handle,
+        Err(e) => {
+            request.respond(error_response(503, &e)).ok();
+            return false;
+        }
+    };
+    let mut writer = request.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache, no-transform\r\n\
+                X-Accel-Buffering: no\r\nConnection: close\r\n\r\n";
+    if writer.write_all(head.as_bytes()).and_then(|_| writer.flush()).is_err() {
+        return true;
+    }
+    loop {
+        if running.stopped.load(Ordering::Relaxed) || running.resync(app, participant_id, &mut handle).is_err() {
+            return true;
+        }
+        let written = match handle.frames.recv_timeout(HEARTBEAT) {
+            // Frames are single-line JSON, so each fits in one `data:` field.
+            Ok(frame) => {
+                if netsim::transmit(Direction::Upload, frame.len()).is_err() {
+                    return true;
+                }
+                writer
+                    .write_all(b"data: ")
+                    .and_then(|_| writer.write_all(&frame))
+                    .and_then(|_| writer.write_all(b"\n\n"))
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => writer.write_all(b": keep-alive\n\n"),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return false,
+        };
+        if written.and_then(|_| writer.flush()).is_err() {
+            return true;
+        }
+    }
+}
+
+/// Stream frames over whichever transport the request asks for.
+fn stream(app: &AppHandle, running: &Running, request: Request, query: &HashMap<String, String>) {
+    let participant_id = match running.authorize(app, query) {
+        Ok(id) => id,
+        Err((status, message)) => {
+            request.respond(error_response(status, &message)).ok();
+            return;
+        }
+    };
+    let upgrade = header(&request, "Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let events = header(&request, "Accept").is_some_and(|v| v.contains("text/event-stream"));
+    if !upgrade && !events {
+        request
+            .respond(error_response(400, "Use /session/poll for long-polling"))
+            .ok();
+        return;
+    }
+    // A viewer upgrading from long-polling must not be expired as a stale poller.
+    running.polling.lock().unwrap().remove(&participant_id);
+    let gone = if upgrade {
+        stream_websocket(app, running, request, &participant_id)
+    } else {
+        stream_events(app, running, request, &participant_id)
+    };
+    if gone {
+        running.leave(app, &participant_id);
+    }
+}
+
+fn error_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(json!({ "error": message }).to_string())
+        .with_status_code(status)
+        .with_header(content_type("application/json"))
+}
+
+fn handle(app: &AppHandle, running: &Running, mut request: Request) {
+    // A connection the network simulator drops gets no response at all.
+    if netsim::transmit(Direction::Download, request.body_length().unwrap_or(0)).is_err() {
+        drop(request.into_writer());
+        return;
+    }
+    let (path, query) = split_url(request.url());
+    // Only requests that pass authorization count against a participant, so naming someone
+    // else's id cannot use up their allowance. The endpoints reject the rest themselves.
+    let _slot = match running.authorize(app, &query).map(|id| running.claim(&id)) {
+        Ok(None) => {
+            request
+                .respond(error_response(429, "Too many requests for this participant at once"))
+                .ok();
+            return;
+        }
+        slot => slot,
+    };
+    let method = request.method().clone();
+    let reply = match (&method, path.as_str()) {
+        (Method::Get, "/session") => {
+            request.respond(page(running, &query)).ok();
+            return;
+        }
+        (Method::Get, "/session/stream") => return stream(app, running, request, &query),
+        (Method::Post, "/session/join") => join(app, running, &mut request),
+        (Method::Get, "/session/poll") => poll(app, running, &query),
+        (Method::Get, "/session/render") => render(app, running, &query),
+        (Method::Get, "/session/minimap") => minimap(app, running, &query),
+        (Method::Get, "/session/peers") => peers(app, running, &query),
+        (Method::Post, "/session/send") => send(app, running, &mut request, &query),
+        _ => Err((404, "Not found".to_string())),
+    };
+    let response = match reply {
+        Ok(body) => Response::from_data(body).with_header(content_type("application/json")),
+        Err((status, message)) => error_response(status, &message),
+    };
+    if netsim::transmit(Direction::Upload, response.data_length().unwrap_or(0)).is_err() {
+        drop(request.into_writer());
+        return;
+    }
+    request.respond(response).ok();
+}
+
+/// A participant's secret: two ids' worth of randomness, as it guards more than an id does.
+fn new_secret() -> String {
+    format!("{}{}", new_id(), new_id())
+}
+
+/// Best guess at the address other machines on the LAN reach this host by.
+fn local_address() -> String {
+    // Connecting a UDP socket sends nothing; it only selects the outgoing interface.
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| socket.connect("192.0.2.1:80").map(|_| socket))
+        .and_then(|socket| socket.local_addr())
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_string())
+}
+
+fn expire_polls(app: &AppHandle, running: &Running) {
+    let expired: Vec<String> = running
+        .polling
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, p)| p.last_seen.elapsed() > POLL_EXPIRY)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in expired {
+        running.leave(app, &id);
+    }
+}
+
+/// Start serving the session to viewers on `port` (any free port if omitted), sharing `document_ids`.
+/// With `web_viewer`, browsers opening the session URL get a read-only viewer page.
+#[tauri::command]
+pub fn start_session_server(
+    app: AppHandle,
+    server: State<'_, SessionServer>,
+    port: Option<u16>,
+    document_ids: Vec<String>,
+    web_viewer: Option<bool>,
+) -> Result<ServerInfo, String> {
+    lifecycle::host(&app, HostEvent::Start, || start(&app, &server, port, document_ids, web_viewer))
+}
+
+fn start(
+    app: &AppHandle,
+    server: &SessionServer,
+    port: Option<u16>,
+    document_ids: Vec<String>,
+    web_viewer: Option<bool>,
+) -> Result<ServerInfo, String> {
+    let mut slot = server.running.lock().unwrap();
+    let http = Server::http(("0.0.0.0", port.unwrap_or(0))).map_err(|e| format!("Failed to start server: {}", e))?;
+    let port = http
+        .server_addr()
+        .to_ip()
+        .map(|addr| addr.port())
+        .ok_or("Server is not listening on an IP address")?;
+    let running = Arc::new(Running {
+        server: Arc::new(http),
+        port,
+        token: new_id(),
+        document_ids,
+        polling: Mutex::new(HashMap::new()),
+        secrets: Mutex::new(HashMap::new()),
+        negotiated: Mutex::new(HashMap::new()),
+        web_viewer: web_viewer.unwrap_or(false),
+        rendered: Mutex::new(HashMap::new()),
+        active: AtomicUsize::new(0),
+        in_flight: Mutex::new(HashMap::new()),
+        stopped: AtomicBool::new(false),
+    });
+
+    let accept = Arc::clone(&running);
+    let accept_app = app.clone();
+    thread::spawn(move || {
+        for request in accept.server.incoming_requests() {
+            if accept.active.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                accept.active.fetch_sub(1, Ordering::AcqRel);
+                request.respond(error_response(503, "The host is handling too many connections")).ok();
+                continue;
+            }
+            let active = Active(Arc::clone(&accept));
+            let app = accept_app.clone();
+            // Streams hold their connection open, so every request gets its own thread.
+            thread::spawn(move || handle(&app, &active.0, request));
+        }
+    });
+    let reaper = Arc::clone(&running);
+    let app = app.clone();
+    thread::spawn(move || {
+        while !reaper.stopped.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_secs(5));
+            expire_polls(&app, &reaper);
+        }
+    });
+
+    let info = running.info();
+    *slot = Some(running);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn stop_session_server(app: AppHandle, server: State<'_, SessionServer>) -> bool {
+    lifecycle::host(&app, HostEvent::Stop, || {
+        server.running.lock().unwrap().take().ok_or("The session server is not running".to_string())
+    })
+    .map(|running| stop(&app, &running))
+    .is_ok()
+}
+
+/// Alias of `start_session_server`, for callers that only deal in sessions.
+#[tauri::command]
+pub fn start_session(
+    app: AppHandle,
+    server: State<'_, SessionServer>,
+    port: Option<u16>,
+    document_ids: Vec<String>,
+    web_viewer: Option<bool>,
+) -> Result<ServerInfo, String> {
+    start_session_server(app, server, port, document_ids, web_viewer)
+}
+
+/// Alias of `stop_session_server`.
+#[tauri::command]
+pub fn stop_session(app: AppHandle, server: State<'_, SessionServer>) -> bool {
+    stop_session_server(app, server)
+}
+
+fn stop(app: &AppHandle, running: &Running) {
+    app.state::<Arc<SharingHub>>().set_paused(false);
+    running.stopped.store(true, Ordering::Relaxed);
+    running.server.unblock();
+    let polling: Vec<String> = running.polling.lock().unwrap().keys().cloned().collect();
+    for id in polling {
+        running.leave(app, &id);
+    }
+}
+
+/// The session URL as an SVG QR code, for phones to join by scanning.
+#[tauri::command]
+pub fn get_session_qr(server: State<'_, SessionServer>) -> Result<String, String> {
+    let running = server.running.lock().unwrap();
+    let info = running.as_ref().ok_or("The session server is not running")?.info();
+    let code = QrCode::new(info.url.as_bytes()).map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    Ok(code.render::<svg::Color>().min_dimensions(240, 240).build())
+}
+
+#[tauri::command]
+pub fn get_session_server(server: State<'_, SessionServer>) -> Option<ServerInfo> {
+    server.running.lock().unwrap().as_ref().map(|r| r.info())
+}
@@ -0,0 +1,382 @@
+//! Participants of the current sharing session and routing of protocol messages between them.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::breakout;
+use crate::colors::{self, ColorProfile};
+use crate::cues::{self, AudioCue};
+use crate::events::{self, Channel};
+use crate::pen::StrokeEvent;
+use crate::polls::{self, PollStore, PollTally};
+use crate::protocol::{Capability, Envelope, Message, Signal};
+use crate::receipts;
+use crate::remote_actions;
+use crate::rooms;
+use crate::sharing::{self, SharingHub};
+use crate::speech;
+use crate::storage::{self, new_id};
+use crate::timetrack::{self, SessionTime};
+use crate::webhooks::{self, WebhookEvent};
+
+/// At most this many signals per participant within `SIGNAL_WINDOW`; extra ones are dropped.
+const SIGNAL_LIMIT: usize = 5;
+const SIGNAL_WINDOW: Duration = Duration::from_secs(10);
+
+/// Every outgoing message, in order, for the active transport.
+const SESSION_MESSAGE: Channel = Channel::lossless("session-message");
+/// The whole participant list, so only the newest matters.
+const PARTICIPANTS_CHANGED: Channel = Channel::latest_wins("participants-changed");
+/// Reactions arrive in bursts; the webview gets them as an array.
+const PARTICIPANT_SIGNAL: Channel = Channel::batch("participant-signal");
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Host,
+    Editor,
+    Viewer,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Participant {
+    pub id: String,
+    pub name: String,
+    pub role: Role,
+    pub hand_raised: bool,
+    pub joined_at: u64,
+    /// Cursor colour, from the selected colour-vision palette.
+    pub color: String,
+}
+
+#[derive(Default)]
+pub struct Session {
+    participants: Mutex<HashMap<String, Participant>>,
+    recent_signals: Mutex<HashMap<String, VecDeque<Instant>>>,
+    /// Classroom mode: everyone but the host joins as a read-only viewer.
+    classroom: AtomicBool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SignalEvent {
+    participant_id: String,
+    name: String,
+    signal: Signal,
+    at: u64,
+}
+
+impl Session {
+    pub fn participant(&self, id: &str) -> Result<Participant, String> {
+        self.participants
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown participant {}", id))
+    }
+
+    pub fn participants(&self) -> Vec<Participant> {
+        let mut list: Vec<Participant> = self.participants.lock().unwrap().values().cloned().collect();
+        list.sort_by_key(|p| p.joined_at);
+        list
+    }
+
+    /// Record a signal against the rate limit; `false` means it should be dropped.
+    fn allow_signal(&self, participant_id: &str) -> bool {
+        let now = Instant::now();
+        let mut recent = self.recent_signals.lock().unwrap();
+        let times = recent.entry(participant_id.to_string()).or_default();
+        while times.front().is_some_and(|t| now.duration_since(*t) > SIGNAL_WINDOW) {
+            times.pop_front();
+        }
+        if times.len() >= SIGNAL_LIMIT {
+            return false;
+        }
+        times.push_back(now);
+        true
+    }
+}
+
+/// Something worth keeping after the session ends, stored in `session-history.json`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum SessionRecord {
+    Poll(PollTally),
+    Time(SessionTime),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionHistory {
+    records: Vec<SessionRecord>,
+}
+
+const HISTORY_FILE: &str = "session-history.json";
+
+pub fn record(app: &AppHandle, record: SessionRecord) -> Result<(), String> {
+    let mut history: SessionHistory = storage::load_json(app, HISTORY_FILE)?;
+    history.records.push(record);
+    storage::save_json(app, HISTORY_FILE, &history)
+}
+
+pub fn history(app: &AppHandle) -> Result<Vec<SessionRecord>, String> {
+    Ok(storage::load_json::<SessionHistory>(app, HISTORY_FILE)?.records)
+}
+
+fn capability(message: &Message) -> Result<Capability, String> {
+    message.capability().ok_or_else(|| "Unsupported messages cannot be sent".to_string())
+}
+
+/// Hand a message to the active transport for delivery to the other participants.
+pub fn broadcast(app: &AppHandle, message: &Message) -> Result<(), String> {
+    app.state::<Arc<SharingHub>>().publish(capability(message)?, sharing::encode(message)?);
+    events::emit(app, &SESSION_MESSAGE, Envelope { to: None, message })
+}
+
+/// Deliver a message to a single participant only.
+pub fn send_to(app: &AppHandle, participant_id: &str, message: &Message) -> Result<(), String> {
+    app.state::<Arc<SharingHub>>().send_to(participant_id, capability(message)?, sharing::encode(message)?);
+    let envelope = Envelope {
+        to: Some(participant_id),
+        message,
+    };
+    events::emit(app, &SESSION_MESSAGE, envelope)
+}
+
+fn participants_changed(app: &AppHandle, session: &Session) -> Result<(), String> {
+    events::emit(app, &PARTICIPANTS_CHANGED, session.participants())
+}
+
+/// Apply a signal from `participant_id` and notify the UI. Returns `false` if it was dropped
+/// by the rate limit or changed nothing (lowering a hand that is not raised).
+fn handle_signal(
+    app: &AppHandle,
+    session: &Session,
+    participant_id: &str,
+    signal: Signal,
+    at: u64,
+) -> Result<bool, String> {
+    let participant = session.participant(participant_id)?;
+    let hand = match signal {
+        Signal::RaiseHand => Some(true),
+        Signal::LowerHand => Some(false),
+        _ => None,
+    };
+    if hand == Some(participant.hand_raised) || !session.allow_signal(participant_id) {
+        return Ok(false);
+    }
+    if let Some(raised) = hand {
+        if let Some(p) = session.participants.lock().unwrap().get_mut(participant_id) {
+            p.hand_raised = raised;
+        }
+        participants_changed(app, session)?;
+        if raised {
+            cues::sound(app, AudioCue::HandRaised);
+        }
+    }
+    speech::announce_signal(app, &participant.name, signal);
+    let event = SignalEvent {
+        participant_id: participant_id.to_string(),
+        name: participant.name,
+        signal,
+        at,
+    };
+    events::emit(app, &PARTICIPANT_SIGNAL, event)?;
+    Ok(true)
+}
+
+/// Add a participant, demoting editors to viewers in classroom mode.
+pub fn admit(app: &AppHandle, session: &Session, id: Option<String>, name: String, role: Role) -> Result<Participant, String> {
+    let role = match role {
+        Role::Editor if session.classroom.load(Ordering::Relaxed) => Role::Viewer,
+        role => role,
+    };
+    let id = id.unwrap_or_else(new_id);
+    let mut participants = session.participants.lock().unwrap();
+    let taken: Vec<String> = participants.values().filter(|p| p.id != id).map(|p| p.color.clone()).collect();
+    let participant = Participant {
+        color: colors::assign(app, &id, &taken),
+        id,
+        name,
+        role,
+        hand_raised: false,
+        joined_at: storage::now_secs(),
+    };
+    participants.insert(participant.id.clone(), participant.clone());
+    drop(participants);
+    timetrack::joined(app, &participant);
+    if participant.role != Role::Host {
+        cues::sound(app, AudioCue::ViewerJoined);
+    }
+    rooms::member_joined(app, &participant);
+    let joined = json!({ "participantId": participant.id, "name": participant.name, "role": participant.role });
+    webhooks::notify(app, WebhookEvent::ParticipantJoined, joined);
+    participants_changed(app, session)?;
+    Ok(participant)
+}
+
+/// Give everyone a colour from `profile`'s palette, earliest arrivals choosing first.
+pub fn recolor(app: &AppHandle, session: &Session, profile: ColorProfile) -> Result<(), String> {
+    let mut participants = session.participants.lock().unwrap();
+    let mut order: Vec<&mut Participant> = participants.values_mut().collect();
+    order.sort_by(|a, b| (a.joined_at, &a.id).cmp(&(b.joined_at, &b.id)));
+    let mut taken = Vec::with_capacity(order.len());
+    for participant in order {
+        participant.color = colors::pick(profile, &participant.id, &taken);
+        taken.push(participant.color.clone());
+    }
+    drop(participants);
+    participants_changed(app, session)
+}
+
+pub fn dismiss(app: &AppHandle, session: &Session, id: &str) -> Result<bool, String> {
+    let removed = session.participants.lock().unwrap().remove(id);
+    session.recent_signals.lock().unwrap().remove(id);
+    let Some(participant) = removed else {
+        return Ok(false);
+    };
+    timetrack::left(app, id);
+    rooms::member_left(app, &participant);
+    participants_changed(app, session)?;
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn add_participant(
+    app: AppHandle,
+    session: State<'_, Session>,
+    id: Option<String>,
+    name: String,
+    role: Role,
+) -> Result<Participant, String> {
+    admit(&app, &session, id, name, role)
+}
+
+#[tauri::command]
+pub fn remove_participant(app: AppHandle, session: State<'_, Session>, id: String) -> Result<bool, String> {
+    dismiss(&app, &session, &id)
+}
+
+#[tauri::command]
+pub fn list_participants(session: State<'_, Session>) -> Vec<Participant> {
+    session.participants()
+}
+
+/// Send a signal (raise hand, reaction) on behalf of a local participant.
+#[tauri::command]
+pub fn send_signal(
+    app: AppHandle,
+    session: State<'_, Session>,
+    participant_id: String,
+    signal: Signal,
+) -> Result<bool, String> {
+    let at = storage::now_secs();
+    let delivered = handle_signal(&app, &session, &participant_id, signal, at)?;
+    if delivered {
+        broadcast(&app, &Message::Signal { participant_id, signal, at })?;
+    }
+    Ok(delivered)
+}
+
+/// Route a message received from another participant by the transport.
+#[tauri::command]
+pub fn receive_message(app: AppHandle, session: State<'_, Session>, message: Message) -> Result<bool, String> {
+    route(&app, &session, message)
+}
+
+pub fn route(app: &AppHandle, session: &Session, message: Message) -> Result<bool, String> {
+    if let Some(participant_id) = message.participant_id() {
+        timetrack::activity(app, participant_id);
+    }
+    match message {
+        Message::Signal { participant_id, signal, at } => handle_signal(app, session, &participant_id, signal, at),
+        Message::PollUpdated { poll } => {
+            app.emit("poll-updated", poll).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::Vote {
+            poll_id,
+            participant_id,
+            option,
+        } => {
+            let polls = app.state::<PollStore>();
+            polls::handle_vote(app, &polls, session, &poll_id, &participant_id, option).map(|_| true)
+        }
+        Message::Scratchpad { participant_id, text, .. } => {
+            breakout::handle_scratchpad(app, session, &participant_id, text)
+        }
+        Message::InterviewTask { task } => {
+            app.emit("interview-task-revealed", task).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        snapshot @ Message::Snapshot { .. } => {
+            app.emit("document-snapshot", snapshot).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        patch @ Message::Patch { .. } => {
+            app.emit("document-patch", patch).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::Stroke { participant_id, stroke } => {
+            session.participant(&participant_id)?;
+            app.emit("annotation-stroke", StrokeEvent { participant_id, stroke })
+                .map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::Magnifier { region } => {
+            app.emit("magnifier-changed", region).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::SessionEnded { reason } => {
+            app.emit("session-ended", reason).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::Snippet { snippet } => {
+            app.emit("snippet-received", snippet).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Message::ActionRequest {
+            request_id,
+            participant_id,
+            action,
+        } => {
+            remote_actions::request(app, session, request_id, participant_id, action);
+            Ok(true)
+        }
+        Message::Receipt {
+            participant_id,
+            document_id,
+            seq,
+            viewed,
+        } => receipts::record(app, session, &participant_id, &document_id, seq, viewed),
+        result @ Message::ActionResult { .. } => {
+            app.emit("remote-action-result", result).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        frozen @ Message::DocumentFrozen { .. } => {
+            app.emit("remote-document-frozen", frozen).map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        // From a newer peer; the feature it belongs to is simply not available here.
+        Message::Unsupported => Ok(false),
+    }
+}
+
+#[tauri::command]
+pub fn get_session_history(app: AppHandle) -> Result<Vec<SessionRecord>, String> {
+    history(&app)
+}
+
+/// In classroom mode new participants join read-only, whatever role they ask for.
+#[tauri::command]
+pub fn set_classroom_mode(session: State<'_, Session>, enabled: bool) {
+    session.classroom.store(enabled, Ordering::Relaxed);
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::language::LanguageProfile;
+use crate::storage;
+use crate::ProtectionPolicy;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Desktop-only settings persisted by the backend. Unknown fields are ignored so older builds can read newer files.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// User overrides keyed by language id; shipped defaults apply to anything not listed.
+    pub language_profiles: HashMap<String, LanguageProfile>,
+    /// Enables the debug console (`debug_execute`).
+    pub developer_mode: bool,
+    /// Protects every window while the screen appears to be shared (see `share_detector`).
+    pub share_detection: bool,
+    /// Capture protection for windows as they open.
+    pub default_protection: ProtectionPolicy,
+    /// Keeps saved snippets out of Spotlight and Windows Search (see `desktop_search`).
+    pub skip_snippet_indexing: bool,
+    /// Turns off the trackpad tap when a hotkey lands (see `haptics`).
+    pub skip_haptics: bool,
+    /// Advertises this instance and finds others over mDNS (see `discovery`).
+    pub lan_discovery: bool,
+    /// The name other instances see; the computer's name when unset.
+    pub discovery_name: Option<String>,
+}
+
+/// Settings are re-read on every access so a restored backup takes effect immediately.
+#[derive(Default)]
+pub struct SettingsStore {
+    lock: Mutex<()>,
+}
+
+impl SettingsStore {
+    pub fn get(&self, app: &AppHandle) -> Result<Settings, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, SETTINGS_FILE)
+    }
+
+    pub fn update<R>(&self, app: &AppHandle, f: impl FnOnce(&mut Settings) -> R) -> Result<R, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut settings: Settings = storage::load_json(app, SETTINGS_FILE)?;
+        let result = f(&mut settings);
+        storage::save_json(app, SETTINGS_FILE, &settings)?;
+        Ok(result)
+    }
+}
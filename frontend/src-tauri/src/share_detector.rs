@@ -0,0 +1,306 @@
+//! Noticing that the screen is being shared, and turning capture protection on while it is.
+//!
+//! No OS reports who is capturing the screen (Windows Graphics Capture sessions in particular
+//! cannot be enumerated), so this is a heuristic, polled: processes that only run while capturing
+//! (OBS, Zoom's share host), and on Windows and macOS the windows meeting apps and browsers put up
+//! while sharing ("… is sharing your screen"). macOS only reveals other apps' window titles with the
+//! screen recording permission, so without it only processes count there. Linux is limited to
+//! processes, and as windows cannot be protected there, only the event is sent.
+//!
+//! Detection is off until `set_share_detection` turns it on. When sharing starts every window is
+//! protected and `screen-share-detected` is emitted; when it stops, protection this module turned
+//! on is turned off again and `screen-share-ended` is emitted. Windows the user protected
+//! themselves are left alone.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::settings::SettingsStore;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Processes that run only while something is capturing the screen, lower case without `.exe`.
+const CAPTURE_PROCESSES: [&str; 7] = [
+    "obs",
+    "obs64",
+    "obs32",
+    // Zoom's screen share host, started for the share and gone after it
+    "cpthost",
+    "simplescreenrecorder",
+    "kazam",
+    "gpu-screen-recorder",
+];
+
+/// Lower-case fragments of the titles of windows shown while sharing.
+const SHARING_TITLES: [&str; 6] = [
+    // Chromium's and Edge's sharing bar
+    "is sharing your screen",
+    "is sharing a window",
+    "is sharing this tab",
+    // Zoom's share toolbar
+    "you are screen sharing",
+    "zoom share toolbar",
+    // Teams' sharing control bar
+    "sharing control bar",
+];
+
+#[derive(Default)]
+struct Detector {
+    /// What gave the share away, empty while no one is sharing.
+    sources: Vec<String>,
+    /// Windows protected because of the share, to be unprotected once it ends.
+    protected: Vec<String>,
+}
+
+static DETECTOR: Mutex<Detector> = Mutex::new(Detector {
+    sources: Vec::new(),
+    protected: Vec::new(),
+});
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScreenShareDetected {
+    sources: Vec<String>,
+    /// Labels of the windows protection was turned on for.
+    protected: Vec<String>,
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn process_names() -> Vec<String> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+
+    let mut names = Vec::new();
+    unsafe {
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return names;
+        };
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut next = Process32FirstW(snapshot, &mut entry);
+        while next.is_ok() {
+            let len = entry.szExeFile.iter().position(|&c| c == 0).unwrap_or(entry.szExeFile.len());
+            names.push(String::from_utf16_lossy(&entry.szExeFile[..len]));
+            next = Process32NextW(snapshot, &mut entry);
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    names
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn process_names() -> Vec<String> {
+    let Ok(output) = std::process::Command::new("ps").args(["-Aco", "comm="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout).lines().map(|line| line.trim().to_string()).collect()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub(crate) fn process_names() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("comm")).ok())
+        .map(|comm| comm.trim().to_string())
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+fn window_titles() -> Vec<String> {
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetWindowTextW, IsWindowVisible};
+
+    unsafe extern "system" fn collect(hwnd: HWND, titles: LPARAM) -> BOOL {
+        let titles = &mut *(titles.0 as *mut Vec<String>);
+        if IsWindowVisible(hwnd).as_bool() {
+            let mut buf = [0u16; 256];
+            let len = GetWindowTextW(hwnd, &mut buf);
+            if len > 0 {
+                titles.push(String::from_utf16_lossy(&buf[..len as usize]));
+            }
+        }
+        BOOL(1)
+    }
+
+    let mut titles = Vec::new();
+    unsafe {
+        let _ = EnumWindows(Some(collect), LPARAM(&mut titles as *mut Vec<String> as isize));
+    }
+    titles
+}
+
+#[cfg(target_os = "macos")]
+fn window_titles() -> Vec<String> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{msg_send, sel, sel_impl};
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGWindowListCopyWindowInfo(option: u32, relative_to: u32) -> id;
+    }
+    const ON_SCREEN_ONLY: u32 = 1;
+
+    let mut titles = Vec::new();
+    unsafe {
+        // A CFArray of CFDictionaries, toll-free bridged to their Foundation counterparts
+        let windows = CGWindowListCopyWindowInfo(ON_SCREEN_ONLY, 0);
+        if windows == nil {
+            return titles;
+        }
+        let key = NSString::alloc(nil).init_str("kCGWindowName");
+        let count: usize = msg_send![windows, count];
+        for i in 0..count {
+            let info: id = msg_send![windows, objectAtIndex: i];
+            let name: id = msg_send![info, objectForKey: key];
+            if name != nil {
+                let utf8 = std::ffi::CStr::from_ptr(name.UTF8String());
+                titles.push(utf8.to_string_lossy().into_owned());
+            }
+        }
+        let _: () = msg_send![key, release];
+        let _: () = msg_send![windows, release];
+    }
+    titles
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn window_titles() -> Vec<String> {
+    Vec::new()
+}
+
+/// What currently suggests the screen is being shared.
+fn detect() -> Vec<String> {
+    let mut sources: Vec<String> = process_names()
+        .into_iter()
+        .filter(|name| {
+            let name = name.to_lowercase();
+            let name = name.strip_suffix(".exe").unwrap_or(&name);
+            CAPTURE_PROCESSES.contains(&name)
+        })
+        .collect();
+    sources.extend(window_titles().into_iter().filter(|title| {
+        let title = title.to_lowercase();
+        SHARING_TITLES.iter().any(|fragment| title.contains(fragment))
+    }));
+    sources.sort();
+    sources.dedup();
+    sources
+}
+
+/// Protect every window that isn't already, returning the ones that weren't.
+fn protect_all(app: &AppHandle) -> Vec<String> {
+    let mut protected = Vec::new();
+    for (label, window) in app.webview_windows() {
+        let protection = crate::read_capture_protection(&window);
+        if protection.is_ok_and(|p| !matches!(p, crate::CaptureProtection::Off)) {
+            continue;
+        }
+        match crate::protect_from_capture(&window, true) {
+            Ok(_) => protected.push(label),
+            Err(e) => log::warn!("Failed to protect window {} from capture: {}", label, e),
+        }
+    }
+    crate::tray::refresh(app);
+    protected
+}
+
+fn unprotect(app: &AppHandle, labels: Vec<String>) {
+    for label in labels {
+        let Some(window) = app.get_webview_window(&label) else {
+            continue;
+        };
+        if let Err(e) = crate::protect_from_capture(&window, false) {
+            log::warn!("Failed to lift capture protection from window {}: {}", label, e);
+        }
+    }
+    crate::tray::refresh(app);
+}
+
+fn started(app: &AppHandle, sources: Vec<String>) {
+    log::info!("Screen sharing detected: {}", sources.join(", "));
+    DETECTOR.lock().unwrap().sources = sources;
+    let handle = app.clone();
+    // Window attributes belong to the main thread on macOS.
+    let scheduled = app.run_on_main_thread(move || {
+        let protected = protect_all(&handle);
+        let mut detector = DETECTOR.lock().unwrap();
+        detector.protected.extend(protected.iter().cloned());
+        let detected = ScreenShareDetected {
+            sources: detector.sources.clone(),
+            protected,
+        };
+        drop(detector);
+        if let Err(e) = handle.emit("screen-share-detected", detected) {
+            log::warn!("Failed to emit screen-share-detected: {}", e);
+        }
+    });
+    if let Err(e) = scheduled {
+        log::warn!("Failed to protect windows from the share: {}", e);
+    }
+}
+
+fn ended(app: &AppHandle) {
+    log::info!("Screen sharing ended");
+    DETECTOR.lock().unwrap().sources.clear();
+    let handle = app.clone();
+    let scheduled = app.run_on_main_thread(move || {
+        let labels = std::mem::take(&mut DETECTOR.lock().unwrap().protected);
+        unprotect(&handle, labels);
+        if let Err(e) = handle.emit("screen-share-ended", ()) {
+            log::warn!("Failed to emit screen-share-ended: {}", e);
+        }
+    });
+    if let Err(e) = scheduled {
+        log::warn!("Failed to lift protection after the share: {}", e);
+    }
+}
+
+fn enabled(app: &AppHandle) -> bool {
+    app.state::<SettingsStore>().get(app).is_ok_and(|settings| settings.share_detection)
+}
+
+/// Poll for screen sharing for the life of the app. Called once from setup.
+pub fn spawn_watcher(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        // Turning detection off counts as the share ending, so protection is not left behind.
+        let sources = if enabled(&app) { detect() } else { Vec::new() };
+        let sharing = !DETECTOR.lock().unwrap().sources.is_empty();
+        match (sharing, sources.is_empty()) {
+            (false, false) => started(&app, sources),
+            (true, true) => ended(&app),
+            (true, false) => DETECTOR.lock().unwrap().sources = sources,
+            (false, true) => {}
+        }
+    });
+}
+
+/// What gave away the screen share in progress; empty if none was detected.
+#[tauri::command]
+pub fn get_screen_share() -> Vec<String> {
+    DETECTOR.lock().unwrap().sources.clone()
+}
+
+#[tauri::command]
+pub fn get_share_detection(app: AppHandle, settings: State<'_, SettingsStore>) -> Result<bool, String> {
+    Ok(settings.get(&app)?.share_detection)
+}
+
+/// Turn automatic protection during screen shares on or off.
+#[tauri::command]
+pub fn set_share_detection(app: AppHandle, settings: State<'_, SettingsStore>, enabled: bool) -> Result<(), String> {
+    settings.update(&app, |s| s.share_detection = enabled)
+}
@@ -0,0 +1,658 @@
+//! Host-side fan-out of session messages to connected viewers.
+//!
+//! Transports (the webview relay, and network servers) attach each viewer and drain its queue.
+//! A message is serialized once and handed to a small worker pool that copies the shared frame
+//! into every viewer's bounded queue, so a lecture with 100+ viewers costs one serialization per
+//! message rather than one per viewer. A join gate spreads bursts of joins (everyone clicking the
+//! link at 9:00) over time.
+//!
+//! Late joiners get a compacted snapshot of each document plus the patches recorded since, rather
+//! than the full history. Snapshots are shared by all joiners and recompacted in the background
+//! once the tail behind them grows.
+//!
+//! Everything queued for viewers is metered (see `bandwidth`). Near the host's upload cap, document
+//! changes are held and flushed in batches or as snapshots by `spawn_coalescer`.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::bandwidth::{self, Level, Meter};
+use crate::documents::DocumentStore;
+use crate::events::{self, Channel};
+use crate::history::TailPatch;
+use crate::magnifier;
+use crate::protocol::{Capability, Message, Negotiated};
+
+/// A serialized message shared by every viewer it is sent to.
+pub type Frame = Arc<[u8]>;
+
+/// Frames a viewer may lag behind before it is marked for a resync instead of queueing more.
+const VIEWER_QUEUE: usize = 256;
+/// Viewers handled per fan-out job.
+const FANOUT_CHUNK: usize = 32;
+const MAX_WORKERS: usize = 4;
+/// Joins served at once; the rest wait up to `JOIN_WAIT` for a slot.
+const MAX_CONCURRENT_JOINS: usize = 8;
+const JOIN_WAIT: Duration = Duration::from_secs(15);
+/// A snapshot is recompacted once this many patches have piled up behind it, or once their
+/// text reaches a quarter of the document's size.
+const COMPACT_AFTER_PATCHES: u64 = 100;
+const COMPACT_INTERVAL: Duration = Duration::from_secs(15);
+/// Upload bandwidth assumed by the capacity estimate when none is given.
+const DEFAULT_UPLOAD_KBPS: u64 = 10_000;
+/// How often held document changes are looked at.
+const COALESCE_TICK: Duration = Duration::from_secs(1);
+/// How long document changes are held while coalescing, and while sending snapshots only.
+const COALESCE_INTERVAL: Duration = Duration::from_secs(3);
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(15);
+
+struct Viewer {
+    queue: SyncSender<Frame>,
+    /// What the viewer negotiated; frames of other capabilities are not sent to it.
+    negotiated: Negotiated,
+    /// Set when the queue overflowed; the transport should resend a snapshot.
+    needs_resync: Arc<AtomicBool>,
+}
+
+struct Compacted {
+    /// Timeline position the snapshot was taken at.
+    seq: u64,
+    frame: Frame,
+    text_bytes: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncStats {
+    pub document_id: String,
+    pub snapshot_seq: u64,
+    /// Encoded snapshot size, and the document text it holds.
+    pub snapshot_bytes: usize,
+    pub text_bytes: usize,
+    /// Patches (and their text) a joining viewer replays on top of the snapshot.
+    pub tail_patches: u64,
+    pub tail_bytes: usize,
+}
+
+struct Job {
+    frame: Frame,
+    targets: Vec<(SyncSender<Frame>, Arc<AtomicBool>)>,
+}
+
+/// A document's changes held back while near the upload cap.
+struct Held {
+    patches: Vec<TailPatch>,
+    /// Text bytes in `patches`.
+    bytes: usize,
+    /// The patches were dropped, so only a snapshot will do.
+    snapshot: bool,
+    since: Instant,
+}
+
+#[derive(Default)]
+struct Coalescer {
+    held: HashMap<String, Held>,
+    /// Timeline position of the last snapshot flushed to every viewer, per document; patches up
+    /// to it are stale.
+    flushed: HashMap<String, u64>,
+}
+
+#[derive(Default)]
+struct Stats {
+    frames: u64,
+    bytes: u64,
+    /// Exponential moving average of fan-out time per viewer, in nanoseconds.
+    per_viewer_nanos: f64,
+    started: Option<Instant>,
+}
+
+struct JoinGate {
+    in_flight: Mutex<usize>,
+    freed: Condvar,
+}
+
+struct JoinPermit<'a>(&'a JoinGate);
+
+impl Drop for JoinPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.in_flight.lock().unwrap() -= 1;
+        self.0.freed.notify_one();
+    }
+}
+
+impl JoinGate {
+    fn acquire(&self) -> Result<JoinPermit<'_>, String> {
+        let guard = self.in_flight.lock().unwrap();
+        let (mut in_flight, timeout) = self
+            .freed
+            .wait_timeout_while(guard, JOIN_WAIT, |n| *n >= MAX_CONCURRENT_JOINS)
+            .unwrap();
+        if timeout.timed_out() {
+            return Err("Too many viewers are joining right now; try again shortly".to_string());
+        }
+        *in_flight += 1;
+        Ok(JoinPermit(self))
+    }
+}
+
+pub struct SharingHub {
+    viewers: RwLock<HashMap<String, Viewer>>,
+    workers: Vec<SyncSender<Job>>,
+    next_worker: AtomicU64,
+    /// Document id -> compacted snapshot for late joiners.
+    snapshots: Mutex<HashMap<String, Compacted>>,
+    joins: JoinGate,
+    stats: Arc<Mutex<Stats>>,
+    dropped: Arc<AtomicU64>,
+    meter: Arc<Meter>,
+    coalescer: Mutex<Coalescer>,
+    /// While set, nothing but lifecycle notices is published and viewers cannot join.
+    paused: AtomicBool,
+}
+
+impl Default for SharingHub {
+    fn default() -> Self {
+        let worker_count = thread::available_parallelism().map_or(2, |n| n.get()).clamp(1, MAX_WORKERS);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let meter = Arc::new(Meter::default());
+        let workers = (0..worker_count)
+            .map(|_| {
+                let (tx, rx) = mpsc::sync_channel::<Job>(64);
+                let stats = Arc::clone(&stats);
+                let dropped = Arc::clone(&dropped);
+                let meter = Arc::clone(&meter);
+                thread::spawn(move || {
+                    for job in rx {
+                        let started = Instant::now();
+                        for (queue, needs_resync) in &job.targets {
+                            match queue.try_send(Arc::clone(&job.frame)) {
+                                Ok(()) => meter.add(job.frame.len()),
+                                Err(TrySendError::Disconnected(_)) => {}
+                                Err(TrySendError::Full(_)) => {
+                                    needs_resync.store(true, Ordering::Relaxed);
+                                    dropped.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                        let per_viewer = started.elapsed().as_nanos() as f64 / job.targets.len().max(1) as f64;
+                        let mut stats = stats.lock().unwrap();
+                        stats.per_viewer_nanos = if stats.per_viewer_nanos == 0.0 {
+                            per_viewer
+                        } else {
+                            stats.per_viewer_nanos * 0.9 + per_viewer * 0.1
+                        };
+                    }
+                });
+                tx
+            })
+            .collect();
+        Self {
+            viewers: RwLock::new(HashMap::new()),
+            workers,
+            next_worker: AtomicU64::new(0),
+            snapshots: Mutex::new(HashMap::new()),
+            joins: JoinGate {
+                in_flight: Mutex::new(0),
+                freed: Condvar::new(),
+            },
+            stats,
+            dropped,
+            meter,
+            coalescer: Mutex::new(Coalescer::default()),
+            paused: AtomicBool::new(false),
+        }
+    }
+}
+
+pub fn encode(message: &Message) -> Result<Frame, String> {
+    serde_json::to_vec(message)
+        .map(Frame::from)
+        .map_err(|e| format!("Failed to encode message: {}", e))
+}
+
+fn patch_message(doc_id: &str, patch: TailPatch) -> Message {
+    Message::Patch {
+        document_id: doc_id.to_string(),
+        seq: patch.seq,
+        start: patch.start,
+        delete_count: patch.delete_count,
+        insert: patch.insert,
+    }
+}
+
+/// A viewer's end of the hub: frames to deliver, and whether it fell behind.
+pub struct ViewerHandle {
+    pub frames: Receiver<Frame>,
+    pub needs_resync: Arc<AtomicBool>,
+}
+
+impl SharingHub {
+    pub fn viewer_count(&self) -> usize {
+        self.viewers.read().unwrap().len()
+    }
+
+    /// Viewers whose queue overflowed and that are waiting for a fresh snapshot.
+    pub fn resyncs_pending(&self) -> usize {
+        let viewers = self.viewers.read().unwrap();
+        viewers.values().filter(|v| v.needs_resync.load(Ordering::Relaxed)).count()
+    }
+
+    /// Hold back everything sent to viewers. On resuming, every viewer is marked for a resync, since
+    /// what was held back is gone.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        if !paused {
+            for viewer in self.viewers.read().unwrap().values() {
+                viewer.needs_resync.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn meter(&self) -> &Meter {
+        &self.meter
+    }
+
+    /// Whether frames of `capability` are held back, by a pause or the upload cap. Viewers still
+    /// hear that the session ended.
+    fn holding(&self, capability: Capability) -> bool {
+        let held = self.paused.load(Ordering::Relaxed) || self.meter.level() == Level::Capped;
+        held && capability != Capability::Lifecycle
+    }
+
+    /// Queue `frame` for every attached viewer that negotiated `capability`, on the worker pool.
+    pub fn publish(&self, capability: Capability, frame: Frame) {
+        if self.holding(capability) {
+            return;
+        }
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.frames += 1;
+            stats.bytes += frame.len() as u64;
+            stats.started.get_or_insert_with(Instant::now);
+        }
+        let viewers = self.viewers.read().unwrap();
+        let targets: Vec<_> = viewers
+            .values()
+            .filter(|v| v.negotiated.allows(capability))
+            .map(|v| (v.queue.clone(), Arc::clone(&v.needs_resync)))
+            .collect();
+        drop(viewers);
+        for chunk in targets.chunks(FANOUT_CHUNK) {
+            let worker = self.next_worker.fetch_add(1, Ordering::Relaxed) as usize % self.workers.len();
+            let job = Job {
+                frame: Arc::clone(&frame),
+                targets: chunk.to_vec(),
+            };
+            self.workers[worker].send(job).ok();
+        }
+    }
+
+    /// Send a document's new patches to viewers, if any viewer has been sent the document. Near the
+    /// upload cap, or while earlier changes are still held, they are held for the coalescer.
+    pub fn publish_changes(&self, doc_id: &str, patches: Vec<TailPatch>) {
+        if !self.snapshots.lock().unwrap().contains_key(doc_id) {
+            return;
+        }
+        let mut coalescer = self.coalescer.lock().unwrap();
+        let flushed = coalescer.flushed.get(doc_id).copied().unwrap_or(0);
+        let patches = patches.into_iter().filter(|p| p.seq > flushed);
+        if self.meter.level() == Level::Normal && !coalescer.held.contains_key(doc_id) {
+            self.publish_patches(doc_id, patches);
+            return;
+        }
+        let held = coalescer.held.entry(doc_id.to_string()).or_insert_with(|| Held {
+            patches: Vec::new(),
+            bytes: 0,
+            snapshot: false,
+            since: Instant::now(),
+        });
+        if !held.snapshot {
+            held.patches.extend(patches);
+            held.bytes = held.patches.iter().map(|p| p.insert.len()).sum();
+            // More than a joiner's tail would hold, or past the point of sending patches at all
+            if held.patches.len() > VIEWER_QUEUE / 2 || self.meter.level() >= Level::SnapshotOnly {
+                held.patches = Vec::new();
+                held.snapshot = true;
+            }
+        }
+    }
+
+    fn publish_patches(&self, doc_id: &str, patches: impl IntoIterator<Item = TailPatch>) {
+        for patch in patches {
+            match encode(&patch_message(doc_id, patch)) {
+                Ok(frame) => self.publish(Capability::Documents, frame),
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+    }
+
+    /// Send held document changes that have waited long enough for the current level, as their
+    /// patches or a fresh snapshot, whichever is smaller.
+    fn flush_held(&self, store: &DocumentStore) {
+        let level = self.meter.level();
+        let wait = match level {
+            Level::Normal => Duration::ZERO,
+            Level::Coalescing => COALESCE_INTERVAL,
+            Level::SnapshotOnly => SNAPSHOT_INTERVAL,
+            Level::Capped => return,
+        };
+        let mut coalescer = self.coalescer.lock().unwrap();
+        let due: Vec<String> = coalescer
+            .held
+            .iter()
+            .filter(|(_, held)| held.since.elapsed() >= wait)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for doc_id in due {
+            let held = coalescer.held.remove(&doc_id).unwrap();
+            let Ok(text_bytes) = store.with(&doc_id, |doc| doc.text.len()) else {
+                continue;
+            };
+            if !held.snapshot && level < Level::SnapshotOnly && held.bytes <= text_bytes {
+                self.publish_patches(&doc_id, held.patches);
+                continue;
+            }
+            match self.compact(store, &doc_id) {
+                Ok((seq, frame)) => {
+                    coalescer.flushed.insert(doc_id, seq);
+                    self.publish(Capability::Documents, frame);
+                }
+                Err(e) => log::warn!("Failed to snapshot {} for viewers: {}", doc_id, e),
+            }
+        }
+    }
+
+    /// Build a fresh compacted snapshot of a document from its current text.
+    fn compact(&self, store: &DocumentStore, doc_id: &str) -> Result<(u64, Frame), String> {
+        let (seq, message, text_bytes) = store.with(doc_id, |doc| {
+            let seq = doc.timeline.seq();
+            let message = Message::Snapshot {
+                document_id: doc_id.to_string(),
+                seq,
+                text: doc.text.clone(),
+                language: doc.language.clone(),
+                frozen: doc.frozen,
+            };
+            (seq, message, doc.text.len())
+        })?;
+        let frame = encode(&message)?;
+        let compacted = Compacted {
+            seq,
+            frame: Arc::clone(&frame),
+            text_bytes,
+        };
+        self.snapshots.lock().unwrap().insert(doc_id.to_string(), compacted);
+        Ok((seq, frame))
+    }
+
+    /// Rebuild the snapshot of a document viewers have been sent after a change its patches do not
+    /// carry, such as freezing it, so later joiners see it.
+    pub fn refresh(&self, store: &DocumentStore, doc_id: &str) -> Result<(), String> {
+        if !self.snapshots.lock().unwrap().contains_key(doc_id) {
+            return Ok(());
+        }
+        self.compact(store, doc_id).map(|_| ())
+    }
+
+    /// The document's compacted snapshot, shared by every viewer joining until the next compaction.
+    fn snapshot(&self, store: &DocumentStore, doc_id: &str) -> Result<(u64, Frame), String> {
+        if let Some(compacted) = self.snapshots.lock().unwrap().get(doc_id) {
+            return Ok((compacted.seq, Arc::clone(&compacted.frame)));
+        }
+        self.compact(store, doc_id)
+    }
+
+    /// Snapshot plus the patches recorded since it, as frames to send a joining viewer.
+    fn sync_frames(&self, store: &DocumentStore, doc_id: &str) -> Result<Vec<Frame>, String> {
+        let (mut seq, mut snapshot) = self.snapshot(store, doc_id)?;
+        let mut tail = store.with(doc_id, |doc| doc.timeline.patches_after(seq))?;
+        // Fall back to a fresh snapshot if the tail was pruned or would not fit the viewer queue.
+        let usable = matches!(&tail, Some(patches) if patches.len() <= VIEWER_QUEUE / 2);
+        if !usable {
+            (seq, snapshot) = self.compact(store, doc_id)?;
+            tail = store.with(doc_id, |doc| doc.timeline.patches_after(seq))?;
+        }
+        let mut frames = vec![snapshot];
+        for patch in tail.unwrap_or_default() {
+            frames.push(encode(&patch_message(doc_id, patch))?);
+        }
+        Ok(frames)
+    }
+
+    /// Attach a viewer, queueing a snapshot and tail patches of each document before any live frames.
+    pub fn join(
+        &self,
+        viewer_id: &str,
+        store: &DocumentStore,
+        doc_ids: &[String],
+        negotiated: Negotiated,
+    ) -> Result<ViewerHandle, String> {
+        if self.paused.load(Ordering::Relaxed) {
+            return Err("The host has paused the session; try again shortly".to_string());
+        }
+        if self.meter.level() == Level::Capped {
+            return Err("The host has used up the upload allowance for this session".to_string());
+        }
+        let _permit = self.joins.acquire()?;
+        let (queue, frames) = mpsc::sync_channel(VIEWER_QUEUE);
+        let doc_ids = if negotiated.allows(Capability::Documents) { doc_ids } else { &[] };
+        for doc_id in doc_ids {
+            for frame in self.sync_frames(store, doc_id)? {
+                let bytes = frame.len();
+                queue.try_send(frame).map_err(|_| "Viewer queue is full")?;
+                self.meter.add(bytes);
+            }
+        }
+        let needs_resync = Arc::new(AtomicBool::new(false));
+        let viewer = Viewer {
+            queue,
+            negotiated,
+            needs_resync: Arc::clone(&needs_resync),
+        };
+        self.viewers.write().unwrap().insert(viewer_id.to_string(), viewer);
+        Ok(ViewerHandle { frames, needs_resync })
+    }
+
+    /// Size of each document's snapshot and the tail a joiner would replay on top of it.
+    pub fn sync_stats(&self, store: &DocumentStore) -> Vec<SyncStats> {
+        let snapshots: Vec<(String, u64, usize, usize)> = self
+            .snapshots
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, c)| (id.clone(), c.seq, c.frame.len(), c.text_bytes))
+            .collect();
+        snapshots
+            .into_iter()
+            .filter_map(|(document_id, seq, snapshot_bytes, text_bytes)| {
+                let (latest, tail_bytes) = store
+                    .with(&document_id, |doc| (doc.timeline.seq(), doc.timeline.bytes_after(seq)))
+                    .ok()?;
+                Some(SyncStats {
+                    document_id,
+                    snapshot_seq: seq,
+                    snapshot_bytes,
+                    text_bytes,
+                    tail_patches: latest.saturating_sub(seq),
+                    tail_bytes,
+                })
+            })
+            .collect()
+    }
+
+    /// Recompact snapshots whose tail has grown large, and drop those of closed documents.
+    fn run_compaction(&self, store: &DocumentStore) {
+        let ids: Vec<String> = self.snapshots.lock().unwrap().keys().cloned().collect();
+        let stats = self.sync_stats(store);
+        for id in ids {
+            let Some(stat) = stats.iter().find(|s| s.document_id == id) else {
+                self.forget(&id);
+                continue;
+            };
+            let due = stat.tail_patches >= COMPACT_AFTER_PATCHES || stat.tail_bytes * 4 > stat.text_bytes.max(4096);
+            if due {
+                if let Err(e) = self.compact(store, &id) {
+                    log::warn!("Failed to compact snapshot of {}: {}", id, e);
+                }
+            }
+        }
+    }
+
+    /// Queue `frame` for one viewer only. Returns `false` if it is not attached or did not
+    /// negotiate `capability`.
+    pub fn send_to(&self, viewer_id: &str, capability: Capability, frame: Frame) -> bool {
+        let viewers = self.viewers.read().unwrap();
+        let Some(viewer) = viewers.get(viewer_id).filter(|v| v.negotiated.allows(capability)) else {
+            return false;
+        };
+        if self.holding(capability) {
+            return true;
+        }
+        let bytes = frame.len();
+        match viewer.queue.try_send(frame) {
+            Ok(()) => self.meter.add(bytes),
+            Err(TrySendError::Full(_)) => {
+                viewer.needs_resync.store(true, Ordering::Relaxed);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+        true
+    }
+
+    pub fn leave(&self, viewer_id: &str) -> bool {
+        self.viewers.write().unwrap().remove(viewer_id).is_some()
+    }
+
+    /// Forget cached snapshots and held changes of a closed document.
+    pub fn forget(&self, doc_id: &str) {
+        self.snapshots.lock().unwrap().remove(doc_id);
+        let mut coalescer = self.coalescer.lock().unwrap();
+        coalescer.held.remove(doc_id);
+        coalescer.flushed.remove(doc_id);
+    }
+}
+
+/// Periodically recompact late-joiner snapshots.
+pub fn spawn_compactor(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(COMPACT_INTERVAL);
+        let hub = app.state::<Arc<SharingHub>>();
+        hub.run_compaction(&app.state::<DocumentStore>());
+    });
+}
+
+/// Sample the upload meter and flush held document changes, once a second.
+pub fn spawn_coalescer(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(COALESCE_TICK);
+        let hub = app.state::<Arc<SharingHub>>();
+        if hub.meter.sample().is_some() {
+            bandwidth::level_changed(&app, &hub.meter);
+        }
+        hub.flush_held(&app.state::<DocumentStore>());
+    });
+}
+
+#[tauri::command]
+pub fn get_sync_stats(hub: State<'_, Arc<SharingHub>>, store: State<'_, DocumentStore>) -> Vec<SyncStats> {
+    hub.sync_stats(&store)
+}
+
+/// Frames build on each other, so none may be dropped or merged.
+const VIEWER_FRAME: Channel = Channel::lossless("viewer-frame");
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ViewerFrame {
+    viewer_id: String,
+    /// A JSON-encoded protocol message.
+    frame: String,
+    /// The viewer missed frames and should be sent fresh snapshots.
+    resync: bool,
+}
+
+/// Attach a viewer relayed by the webview; its frames are emitted as `viewer-frame` events
+/// until `detach_viewer`. `protocol` and `capabilities` are what the viewer announced, if it
+/// negotiates; the agreed set is returned.
+#[tauri::command]
+pub fn attach_viewer(
+    app: AppHandle,
+    hub: State<'_, Arc<SharingHub>>,
+    store: State<'_, DocumentStore>,
+    viewer_id: String,
+    document_ids: Vec<String>,
+    protocol: Option<u32>,
+    capabilities: Option<Vec<String>>,
+) -> Result<Negotiated, String> {
+    let negotiated = Negotiated::with(protocol.unwrap_or(1), capabilities.as_deref())?;
+    let handle = hub.join(&viewer_id, &store, &document_ids, negotiated.clone())?;
+    magnifier::catch_up(&app, &viewer_id);
+    thread::spawn(move || {
+        // Ends when the viewer is detached and its queue sender dropped.
+        for frame in handle.frames {
+            let event = ViewerFrame {
+                viewer_id: viewer_id.clone(),
+                frame: String::from_utf8_lossy(&frame).into_owned(),
+                resync: handle.needs_resync.swap(false, Ordering::Relaxed),
+            };
+            if events::emit(&app, &VIEWER_FRAME, event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(negotiated)
+}
+
+#[tauri::command]
+pub fn detach_viewer(hub: State<'_, Arc<SharingHub>>, viewer_id: String) -> bool {
+    hub.leave(&viewer_id)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapacityEstimate {
+    pub viewers: usize,
+    /// Viewers the host can sustain at the current message rate, limited by upload bandwidth
+    /// or fan-out CPU time, whichever runs out first.
+    pub max_viewers: usize,
+    pub limited_by: &'static str,
+    pub frames_per_second: f64,
+    pub average_frame_bytes: u64,
+    pub worker_threads: usize,
+    pub dropped_frames: u64,
+}
+
+/// Estimate how many viewers this host can serve, from observed traffic.
+#[tauri::command]
+pub fn get_room_capacity_estimate(hub: State<'_, Arc<SharingHub>>, upload_kbps: Option<u64>) -> CapacityEstimate {
+    let stats = hub.stats.lock().unwrap();
+    let elapsed = stats.started.map_or(0.0, |s| s.elapsed().as_secs_f64()).max(1.0);
+    // Assume at least typing speed (10 messages/s of ~200 bytes) before there is real traffic.
+    let frames_per_second = (stats.frames as f64 / elapsed).max(10.0);
+    let average_frame_bytes = if stats.frames == 0 { 200 } else { stats.bytes / stats.frames };
+
+    let upload_bytes = upload_kbps.unwrap_or(DEFAULT_UPLOAD_KBPS) as f64 * 1000.0 / 8.0;
+    let by_bandwidth = upload_bytes / (frames_per_second * average_frame_bytes as f64);
+    // Leave half of the workers' time for everything else the app does.
+    let per_viewer = if stats.per_viewer_nanos > 0.0 { stats.per_viewer_nanos } else { 2_000.0 };
+    let by_cpu = hub.workers.len() as f64 * 0.5 * 1e9 / (frames_per_second * per_viewer);
+
+    CapacityEstimate {
+        viewers: hub.viewer_count(),
+        max_viewers: by_bandwidth.min(by_cpu) as usize,
+        limited_by: if by_bandwidth <= by_cpu { "bandwidth" } else { "cpu" },
+        frames_per_second,
+        average_frame_bytes,
+        worker_threads: hub.workers.len(),
+        dropped_frames: hub.dropped.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,87 @@
+//! Orderly shutdown. Quitting used to drop whatever was in flight: viewers kept reconnecting to a
+//! host that was gone, and undo history since the last save was lost.
+//!
+//! [`run`] is called when the app is asked to exit, when it exits without being asked (macOS
+//! logout goes straight there), and on Windows when the user's session ends. It tells the webview,
+//! saves the undo history of every document that matches its file, tells participants the session
+//! is over, closes the session server and any remote session, tells the MQTT broker and mDNS peers
+//! the app is offline, then runs the exit backup. Only the first call does anything.
+//!
+//! Capture protection, taskbar hiding, opacity and click-through are attributes of this process's
+//! windows and go with them, and the app holds no wake locks or do-not-disturb state, so there is
+//! no OS state left over to restore.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::documents::DocumentStore;
+use crate::protocol::Message;
+use crate::server::{self, SessionServer};
+use crate::session::{self, Session};
+use crate::sharing::SharingHub;
+use crate::{backup, discovery, encoding, history, mqtt, viewer};
+
+/// Time viewers get to receive the farewell before their connections are closed.
+const FAREWELL_GRACE: Duration = Duration::from_millis(500);
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Save history for documents that have not changed since they were saved. History of unsaved
+/// edits would not match the file, and would replace history that does.
+fn flush_history(app: &AppHandle) {
+    let store = app.state::<DocumentStore>();
+    let mut docs = store.docs.lock().unwrap();
+    for doc in docs.values_mut() {
+        let Some(path) = doc.path.clone() else {
+            continue;
+        };
+        let on_disk = fs::read(&path).ok().and_then(|raw| encoding::decode(&raw).ok());
+        if !matches!(on_disk, Some(decoded) if decoded.text == doc.text) {
+            continue;
+        }
+        if let Err(e) = history::save(app, &path, &mut doc.history, &doc.text) {
+            log::warn!("Failed to save history on exit: {}", e);
+        }
+    }
+}
+
+/// Tell everyone in the session it is over, then stop serving it.
+fn end_hosting(app: &AppHandle) {
+    let serving = app.state::<SessionServer>().url().is_some();
+    if !serving && app.state::<Session>().participants().is_empty() {
+        return;
+    }
+    let ended = Message::SessionEnded {
+        reason: "The host quit ShareCode".to_string(),
+    };
+    if let Err(e) = session::broadcast(app, &ended) {
+        log::warn!("Failed to notify participants on exit: {}", e);
+    }
+    if app.state::<Arc<SharingHub>>().viewer_count() > 0 {
+        thread::sleep(FAREWELL_GRACE);
+    }
+    if serving {
+        server::stop_session_server(app.clone(), app.state());
+    }
+}
+
+/// Wind everything down before the process exits.
+pub fn run(app: &AppHandle) {
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    log::info!("Shutting down");
+    // The webview may still be there to save its own state.
+    app.emit("app-shutting-down", ()).ok();
+    flush_history(app);
+    end_hosting(app);
+    viewer::leave_remote_session(app.clone(), app.state());
+    mqtt::disconnect(app);
+    discovery::stop(app);
+    backup::on_exit(app);
+}
@@ -0,0 +1,397 @@
+//! Load simulation for pre-release QA, behind the `sim` feature.
+//!
+//! `run_simulation` shares a scratch document from a fresh session server, has virtual
+//! participants join it over HTTP the way real viewers do, and drives host edits and participant
+//! signals at the configured rates. Each participant rebuilds the document from the frames it
+//! receives; at the end every copy is compared with the host's, and the report gives ordering
+//! errors, resyncs, delivery latency and throughput.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Manager};
+
+use crate::documents::{self, Document, DocumentStore};
+use crate::protocol::{Capability, Message, Signal, PROTOCOL_VERSION};
+use crate::server::{self, SessionServer};
+use crate::sharing::SharingHub;
+use crate::storage;
+use crate::syntax::OffsetMap;
+
+/// How long participants get after the last edit to catch up before copies are compared.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_WAIT_SECS: u64 = 1;
+const MAX_PARTICIPANTS: usize = 200;
+const MAX_DURATION: Duration = Duration::from_secs(600);
+/// Inserted by host edits; the non-ASCII one exercises UTF-16 offsets.
+const WORDS: [&str; 8] = ["let ", "fn ", "x", " = ", "42", ";\n", "{}", "é"];
+const SIGNALS: [Signal; 4] = [Signal::ThumbsUp, Signal::Confused, Signal::RaiseHand, Signal::LowerHand];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SimulationOptions {
+    pub participants: usize,
+    pub duration_secs: u64,
+    /// Host edits to the shared document per second, across the whole run.
+    pub edits_per_second: f64,
+    /// Signals (reactions, raised hands) per second, for each participant.
+    pub signals_per_second: f64,
+}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        Self {
+            participants: 10,
+            duration_secs: 30,
+            edits_per_second: 20.0,
+            signals_per_second: 0.5,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    pub participants: usize,
+    pub duration_ms: u64,
+    pub edits: u64,
+    pub frames_received: u64,
+    pub signals_sent: u64,
+    /// Signals the host dropped: over its rate limit, or a hand raised twice.
+    pub signals_rejected: u64,
+    /// Patches that arrived with a `seq` at or before one already applied.
+    pub ordering_errors: u64,
+    /// Gaps in the patch sequence, which the host repairs with a fresh snapshot.
+    pub resyncs: u64,
+    /// Participants whose copy matched the host's at the end.
+    pub converged: usize,
+    pub latency_p50_ms: u64,
+    pub latency_p95_ms: u64,
+    pub latency_max_ms: u64,
+    /// Failures of individual participants, which don't stop the run.
+    pub errors: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JoinReply {
+    participant_id: String,
+    secret: String,
+}
+
+#[derive(Deserialize)]
+struct PollReply {
+    messages: Vec<Message>,
+}
+
+#[derive(Default)]
+struct Counters {
+    frames: AtomicU64,
+    sent: AtomicU64,
+    rejected: AtomicU64,
+    ordering_errors: AtomicU64,
+    resyncs: AtomicU64,
+}
+
+/// A participant's copy of the shared document.
+#[derive(Default)]
+struct Replica {
+    seq: u64,
+    text: Option<String>,
+}
+
+struct Shared {
+    base: String,
+    token: String,
+    document_id: String,
+    counters: Counters,
+    /// When each patch was published, by `seq`.
+    published: Mutex<HashMap<u64, Instant>>,
+    latencies: Mutex<Vec<Duration>>,
+    /// Set once the host stops editing; participants then run until they hold `final_seq`.
+    editing_done: AtomicBool,
+    final_seq: AtomicU64,
+    stopped: AtomicBool,
+}
+
+struct Participant {
+    shared: Arc<Shared>,
+    agent: ureq::Agent,
+    id: String,
+    secret: String,
+    signals_per_second: f64,
+    replica: Replica,
+}
+
+impl Participant {
+    fn join(shared: Arc<Shared>, index: usize, signals_per_second: f64) -> Result<Self, String> {
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(POLL_WAIT_SECS + 10)).build();
+        let body = json!({
+            "token": shared.token,
+            "name": format!("Virtual participant {}", index + 1),
+            "supports": ["long-poll"],
+            "protocol": PROTOCOL_VERSION,
+            "capabilities": Capability::ALL,
+        });
+        let reply: JoinReply = agent
+            .post(&format!("{}/session/join", shared.base))
+            .send_json(body)
+            .map_err(|e| format!("Join failed: {}", e))?
+            .into_json()
+            .map_err(|e| format!("Invalid join reply: {}", e))?;
+        Ok(Self {
+            shared,
+            agent,
+            id: reply.participant_id,
+            secret: reply.secret,
+            signals_per_second,
+            replica: Replica::default(),
+        })
+    }
+
+    fn query(&self) -> String {
+        format!("token={}&participant={}&secret={}", self.shared.token, self.id, self.secret)
+    }
+
+    fn receive(&mut self, message: Message) {
+        self.shared.counters.frames.fetch_add(1, Ordering::Relaxed);
+        match message {
+            Message::Snapshot { document_id, seq, text, .. } if document_id == self.shared.document_id => {
+                self.replica = Replica { seq, text: Some(text) };
+            }
+            Message::Patch {
+                document_id,
+                seq,
+                start,
+                delete_count,
+                insert,
+            } if document_id == self.shared.document_id => {
+                let counters = &self.shared.counters;
+                let replica = &mut self.replica;
+                if seq <= replica.seq {
+                    counters.ordering_errors.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                // A gap: the host notices the dropped frames and follows up with a snapshot.
+                let Some(text) = replica.text.as_mut().filter(|_| seq == replica.seq + 1) else {
+                    counters.resyncs.fetch_add(1, Ordering::Relaxed);
+                    return;
+                };
+                let offsets = OffsetMap::new(text);
+                let (from, to) = (offsets.to_byte(start), offsets.to_byte(start + delete_count));
+                text.replace_range(from..to, &insert);
+                replica.seq = seq;
+                if let Some(published) = self.shared.published.lock().unwrap().get(&seq) {
+                    self.shared.latencies.lock().unwrap().push(published.elapsed());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn poll(&mut self) -> Result<(), String> {
+        let url = format!("{}/session/poll?{}&wait={}", self.shared.base, self.query(), POLL_WAIT_SECS);
+        let reply: PollReply = self
+            .agent
+            .get(&url)
+            .call()
+            .map_err(|e| format!("Poll failed: {}", e))?
+            .into_json()
+            .map_err(|e| format!("Invalid poll reply: {}", e))?;
+        for message in reply.messages {
+            self.receive(message);
+        }
+        Ok(())
+    }
+
+    /// Send a random signal, as a viewer would.
+    fn signal(&self) -> Result<(), String> {
+        let message = Message::Signal {
+            participant_id: self.id.clone(),
+            signal: SIGNALS[rand::thread_rng().gen_range(0..SIGNALS.len())],
+            at: storage::now_secs(),
+        };
+        let reply: serde_json::Value = self
+            .agent
+            .post(&format!("{}/session/send?{}", self.shared.base, self.query()))
+            .send_json(&message)
+            .map_err(|e| format!("Send failed: {}", e))?
+            .into_json()
+            .map_err(|e| format!("Invalid send reply: {}", e))?;
+        let counters = &self.shared.counters;
+        counters.sent.fetch_add(1, Ordering::Relaxed);
+        if reply["accepted"] != json!(true) {
+            counters.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn caught_up(&self) -> bool {
+        self.shared.editing_done.load(Ordering::Relaxed)
+            && self.replica.seq >= self.shared.final_seq.load(Ordering::Relaxed)
+    }
+
+    /// Poll and signal until the host has stopped editing and this copy has every patch.
+    fn run(mut self) -> Result<Replica, String> {
+        let interval = Duration::from_secs_f64(1.0 / self.signals_per_second.max(0.001));
+        let mut next_signal = Instant::now() + interval.mul_f64(rand::thread_rng().gen());
+        while !self.caught_up() && !self.shared.stopped.load(Ordering::Relaxed) {
+            self.poll()?;
+            if self.signals_per_second > 0.0 && Instant::now() >= next_signal {
+                self.signal()?;
+                next_signal += interval;
+            }
+        }
+        Ok(self.replica)
+    }
+}
+
+/// One random insertion or deletion, on character boundaries.
+fn random_edit(text: &str) -> String {
+    let mut rng = rand::thread_rng();
+    let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
+    let at = boundaries[rng.gen_range(0..boundaries.len())];
+    let mut edited = text.to_string();
+    if text.is_empty() || rng.gen_bool(0.6) {
+        edited.insert_str(at, WORDS[rng.gen_range(0..WORDS.len())]);
+    } else {
+        let from = boundaries.iter().position(|&b| b == at).unwrap();
+        let to = boundaries[(from + rng.gen_range(1..=4)).min(boundaries.len() - 1)];
+        edited.replace_range(at..to, "");
+    }
+    edited
+}
+
+/// Edit the shared document at `edits_per_second` until `until`, publishing like the editor does.
+fn edit(app: &AppHandle, shared: &Shared, edits_per_second: f64, until: Instant) -> Result<u64, String> {
+    let store = app.state::<DocumentStore>();
+    let hub = app.state::<Arc<SharingHub>>();
+    let interval = Duration::from_secs_f64(1.0 / edits_per_second.max(0.001));
+    let mut edits = 0;
+    while Instant::now() < until && edits_per_second > 0.0 {
+        let patches = store.with(&shared.document_id, |doc| {
+            let seq = doc.timeline.seq();
+            doc.set_text(random_edit(&doc.text), Some("simulation"))?;
+            Ok::<_, String>(doc.timeline.patches_after(seq).unwrap_or_default())
+        })??;
+        let now = Instant::now();
+        shared.published.lock().unwrap().extend(patches.iter().map(|p| (p.seq, now)));
+        hub.publish_changes(&shared.document_id, patches);
+        edits += 1;
+        thread::sleep(interval);
+    }
+    Ok(edits)
+}
+
+fn percentile(sorted: &[Duration], fraction: f64) -> u64 {
+    let Some(last) = sorted.len().checked_sub(1) else {
+        return 0;
+    };
+    sorted[(last as f64 * fraction).round() as usize].as_millis() as u64
+}
+
+fn simulate(app: &AppHandle, options: SimulationOptions) -> Result<SimulationReport, String> {
+    let store = app.state::<DocumentStore>();
+    let document_id = store.insert(Document::new(None, String::new())).id;
+    let info = match server::start_session_server(app.clone(), app.state(), None, vec![document_id.clone()], None) {
+        Ok(info) => info,
+        Err(e) => {
+            documents::close_document(app.clone(), store, document_id);
+            return Err(e);
+        }
+    };
+    let shared = Arc::new(Shared {
+        base: format!("http://127.0.0.1:{}", info.port),
+        token: info.token,
+        document_id: document_id.clone(),
+        counters: Counters::default(),
+        published: Mutex::new(HashMap::new()),
+        latencies: Mutex::new(Vec::new()),
+        editing_done: AtomicBool::new(false),
+        final_seq: AtomicU64::new(0),
+        stopped: AtomicBool::new(false),
+    });
+
+    let started = Instant::now();
+    let workers: Vec<_> = (0..options.participants)
+        .map(|index| {
+            let shared = Arc::clone(&shared);
+            let rate = options.signals_per_second;
+            thread::spawn(move || Participant::join(shared, index, rate)?.run())
+        })
+        .collect();
+    let edits = edit(app, &shared, options.edits_per_second, started + Duration::from_secs(options.duration_secs));
+    let host_text = store.with(&document_id, |doc| {
+        shared.final_seq.store(doc.timeline.seq(), Ordering::Relaxed);
+        doc.text.clone()
+    });
+    shared.editing_done.store(true, Ordering::Relaxed);
+
+    let settle_by = Instant::now() + SETTLE_TIMEOUT;
+    while Instant::now() < settle_by && workers.iter().any(|w| !w.is_finished()) {
+        thread::sleep(Duration::from_millis(50));
+    }
+    shared.stopped.store(true, Ordering::Relaxed);
+    let replicas: Vec<_> = workers
+        .into_iter()
+        .map(|w| w.join().unwrap_or_else(|_| Err("Participant panicked".to_string())))
+        .collect();
+    let duration = started.elapsed();
+
+    server::stop_session_server(app.clone(), app.state::<SessionServer>());
+    documents::close_document(app.clone(), store, document_id);
+    let (edits, host_text) = (edits?, host_text?);
+
+    let mut errors = Vec::new();
+    let mut converged = 0;
+    for (index, replica) in replicas.into_iter().enumerate() {
+        match replica {
+            Ok(replica) if replica.text.as_deref() == Some(host_text.as_str()) => converged += 1,
+            Ok(replica) => errors.push(format!("Participant {} diverged at seq {}", index + 1, replica.seq)),
+            Err(e) => errors.push(format!("Participant {}: {}", index + 1, e)),
+        }
+    }
+    let mut latencies = std::mem::take(&mut *shared.latencies.lock().unwrap());
+    latencies.sort();
+    let counters = &shared.counters;
+    Ok(SimulationReport {
+        participants: options.participants,
+        duration_ms: duration.as_millis() as u64,
+        edits,
+        frames_received: counters.frames.load(Ordering::Relaxed),
+        signals_sent: counters.sent.load(Ordering::Relaxed),
+        signals_rejected: counters.rejected.load(Ordering::Relaxed),
+        ordering_errors: counters.ordering_errors.load(Ordering::Relaxed),
+        resyncs: counters.resyncs.load(Ordering::Relaxed),
+        converged,
+        latency_p50_ms: percentile(&latencies, 0.5),
+        latency_p95_ms: percentile(&latencies, 0.95),
+        latency_max_ms: latencies.last().map_or(0, |d| d.as_millis() as u64),
+        errors,
+    })
+}
+
+/// Run a load simulation against a session server of its own. Refused while a real session is
+/// being served, since both would use the same session and participant list.
+#[tauri::command]
+pub async fn run_simulation(app: AppHandle, options: Option<SimulationOptions>) -> Result<SimulationReport, String> {
+    let options = options.unwrap_or_default();
+    if options.participants == 0 || options.participants > MAX_PARTICIPANTS {
+        return Err(format!("Simulations take 1 to {} participants", MAX_PARTICIPANTS));
+    }
+    if Duration::from_secs(options.duration_secs) > MAX_DURATION {
+        return Err(format!("Simulations run for at most {} seconds", MAX_DURATION.as_secs()));
+    }
+    if app.state::<SessionServer>().url().is_some() {
+        return Err("Stop the session server before running a simulation".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || simulate(&app, options))
+        .await
+        .map_err(|e| e.to_string())?
+}
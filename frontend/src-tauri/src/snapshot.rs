@@ -0,0 +1,133 @@
+//! Session snapshots: the open documents, unencrypted, in an `artifact` container.
+//!
+//! The same documents always give the same file. Documents are ordered by name and then text, not
+//! by their per-install ids, and nothing about when or where the snapshot was taken is stored.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::artifact::{self, Kind, Metadata};
+use crate::bundle::ImportedDocument;
+use crate::documents::{self, Document, DocumentStore};
+use crate::recents::{self, RecentKind};
+use crate::settings::SettingsStore;
+use crate::webhooks;
+
+/// Refuse to read anything larger; no session of plain-text documents comes close.
+const MAX_SNAPSHOT_BYTES: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SnapshotDocument {
+    /// File name without its directory, so local paths do not travel with the snapshot.
+    pub name: Option<String>,
+    pub language: Option<String>,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    documents: Vec<SnapshotDocument>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub path: String,
+    pub size: u64,
+    pub documents: usize,
+    /// Hex BLAKE3 of the snapshot's contents; equal for equal sessions.
+    pub content_hash: String,
+}
+
+/// Write the open documents, or only `document_ids`, to `path`.
+#[tauri::command]
+pub fn export_session_snapshot(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    path: String,
+    document_ids: Option<Vec<String>>,
+) -> Result<SnapshotInfo, String> {
+    let mut documents: Vec<SnapshotDocument> = store
+        .docs
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(id, _)| document_ids.as_ref().map_or(true, |ids| ids.contains(id)))
+        .map(|(_, doc)| SnapshotDocument {
+            name: doc
+                .path
+                .as_ref()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
+            language: doc.language.clone(),
+            text: doc.text.clone(),
+        })
+        .collect();
+    if documents.is_empty() {
+        return Err("No open documents to snapshot".to_string());
+    }
+    documents.sort_by(|a, b| (&a.name, &a.text).cmp(&(&b.name, &b.text)));
+
+    let count = documents.len();
+    let body = serde_json::to_vec(&Snapshot { documents }).map_err(|e| e.to_string())?;
+    let metadata = Metadata::from([("documents".to_string(), count.to_string())]);
+    let file = artifact::encode(Kind::SessionSnapshot, &metadata, &body)?;
+
+    let target = Path::new(&path);
+    let tmp = target.with_extension("tmp");
+    fs::write(&tmp, &file).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, target).map_err(|e| format!("Failed to finalize {}: {}", path, e))?;
+    webhooks::export_created(&app, "session-snapshot", target);
+
+    Ok(SnapshotInfo {
+        path,
+        size: file.len() as u64,
+        documents: count,
+        content_hash: blake3::hash(&body).to_hex().to_string(),
+    })
+}
+
+/// Verify a snapshot and read its documents.
+pub(crate) fn read(path: &str) -> Result<Vec<SnapshotDocument>, String> {
+    let size = fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path, e))?.len();
+    if size > MAX_SNAPSHOT_BYTES {
+        return Err("Snapshot is too large".to_string());
+    }
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let body = artifact::decode(&bytes, Kind::SessionSnapshot)?;
+    let snapshot: Snapshot = serde_json::from_slice(body).map_err(|e| format!("Snapshot is invalid: {}", e))?;
+    Ok(snapshot.documents)
+}
+
+/// Verify a snapshot and open its documents as new untitled documents.
+#[tauri::command]
+pub fn import_session_snapshot(
+    app: AppHandle,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+) -> Result<Vec<ImportedDocument>, String> {
+    let documents = read(&path)?;
+    recents::note(&app, Path::new(&path), RecentKind::Snapshot);
+    let mut imported = Vec::new();
+    for saved in documents {
+        let info = store.insert(Document::new(None, saved.text));
+        let info = match saved.language {
+            Some(language) => {
+                documents::apply_language(&app, &store, &settings, &info.id, &language)?;
+                store.with(&info.id, |doc| doc.info(&info.id))?
+            }
+            None => info,
+        };
+        imported.push(ImportedDocument {
+            name: saved.name,
+            document: info,
+        });
+    }
+    Ok(imported)
+}
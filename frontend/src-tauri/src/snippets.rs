@@ -0,0 +1,498 @@
+use std::fs;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::protocol::Message;
+use crate::session;
+use crate::storage::{self, new_id};
+
+const DATABASE_FILE: &str = "snippets.db";
+/// Where the library was kept before it moved into `DATABASE_FILE`.
+const LEGACY_FILE: &str = "snippets.json";
+
+/// A saved snippet. `body` uses TextMate/VS Code placeholder syntax (`$1`, `${2:default}`, `$TM_FILENAME`).
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Where the snippet came from, e.g. `vscode:python.json`.
+    #[serde(default)]
+    pub source: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetInput {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// A snippet as it travels to another machine: without its local id, timestamps or source.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortableSnippet {
+    pub title: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    pub body: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl From<Snippet> for PortableSnippet {
+    fn from(snippet: Snippet) -> Self {
+        Self {
+            title: snippet.title,
+            prefix: snippet.prefix,
+            body: snippet.body,
+            language: snippet.language,
+            description: snippet.description,
+            tags: snippet.tags,
+        }
+    }
+}
+
+impl PortableSnippet {
+    /// A new library entry recording how it arrived, e.g. `bluetooth`.
+    pub fn into_input(self, source: &str) -> SnippetInput {
+        SnippetInput {
+            id: None,
+            title: self.title,
+            prefix: self.prefix,
+            body: self.body,
+            language: self.language,
+            description: self.description,
+            tags: self.tags,
+            source: Some(source.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnippetFilters {
+    pub language: Option<String>,
+    /// Snippets must carry every one of these tags.
+    pub tags: Vec<String>,
+    /// At most this many results; `DEFAULT_SEARCH_LIMIT` if unset.
+    pub limit: Option<usize>,
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SnippetField {
+    Title,
+    Prefix,
+    Description,
+    Body,
+}
+
+/// A matched term in one of a snippet's fields, in UTF-16 code units.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetHighlight {
+    pub field: SnippetField,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetMatch {
+    pub snippet: Snippet,
+    /// Higher is better; only comparable within one search.
+    pub score: f64,
+    pub highlights: Vec<SnippetHighlight>,
+}
+
+// Wrapped around matched terms by `highlight()`, then turned into offsets
+const MATCH_START: char = '\u{1}';
+const MATCH_END: char = '\u{2}';
+
+/// Every word of `query` as a prefix, all of them required, quoted so FTS5 syntax in the query
+/// is searched for literally.
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn highlights(field: SnippetField, marked: Option<String>, out: &mut Vec<SnippetHighlight>) {
+    let Some(marked) = marked else {
+        return;
+    };
+    let (mut offset, mut start) = (0, None);
+    for c in marked.chars() {
+        match c {
+            MATCH_START => start = Some(offset),
+            MATCH_END => {
+                if let Some(start) = start.take() {
+                    out.push(SnippetHighlight { field, start, end: offset });
+                }
+            }
+            c => offset += c.len_utf16(),
+        }
+    }
+}
+
+/// Keep the Spotlight and Windows Search sidecars in step. Failing to never fails the change.
+fn sync_search(app: &AppHandle, snippets: &[Snippet]) {
+    #[cfg(desktop)]
+    if let Err(e) = crate::desktop_search::sync(app, snippets) {
+        log::warn!("Failed to update snippet search sidecars: {}", e);
+    }
+    #[cfg(not(desktop))]
+    let _ = (app, snippets);
+}
+
+/// The library lives in a SQLite database, opened on every access so a restored backup is picked
+/// up immediately. Libraries from before the database are imported from `snippets.json` on first
+/// open; the file is then kept as `snippets.json.imported`.
+#[derive(Default)]
+pub struct SnippetLibrary {
+    lock: Mutex<()>,
+}
+
+const COLUMNS: &str = "id, title, prefix, body, language, description, tags, source, created_at, updated_at";
+
+fn read_row(row: &Row) -> rusqlite::Result<Snippet> {
+    let tags: String = row.get(6)?;
+    Ok(Snippet {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        prefix: row.get(2)?,
+        body: row.get(3)?,
+        language: row.get(4)?,
+        description: row.get(5)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        source: row.get(7)?,
+        created_at: row.get::<_, i64>(8)? as u64,
+        updated_at: row.get::<_, i64>(9)? as u64,
+    })
+}
+
+fn write_row(db: &Connection, snippet: &Snippet) -> rusqlite::Result<()> {
+    let tags = serde_json::to_string(&snippet.tags).unwrap_or_else(|_| "[]".to_string());
+    db.execute(
+        // An upsert rather than `INSERT OR REPLACE`, whose implicit delete skips the search triggers
+        &format!(
+            "INSERT INTO snippets ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            ON CONFLICT (id) DO UPDATE SET title = excluded.title, prefix = excluded.prefix,
+                body = excluded.body, language = excluded.language, description = excluded.description,
+                tags = excluded.tags, source = excluded.source, updated_at = excluded.updated_at",
+            COLUMNS
+        ),
+        params![
+            snippet.id,
+            snippet.title,
+            snippet.prefix,
+            snippet.body,
+            snippet.language,
+            snippet.description,
+            tags,
+            snippet.source,
+            snippet.created_at as i64,
+            snippet.updated_at as i64,
+        ],
+    )?;
+    Ok(())
+}
+
+fn db_error(e: rusqlite::Error) -> String {
+    format!("Snippet database error: {}", e)
+}
+
+/// Move a JSON library into the database, once.
+fn import_json(app: &AppHandle, db: &mut Connection) -> Result<(), String> {
+    let path = storage::data_dir(app)?.join(LEGACY_FILE);
+    if !path.exists() {
+        return Ok(());
+    }
+    let snippets: Vec<Snippet> = storage::load_json(app, LEGACY_FILE)?;
+    let tx = db.transaction().map_err(db_error)?;
+    for snippet in &snippets {
+        write_row(&tx, snippet).map_err(db_error)?;
+    }
+    tx.commit().map_err(db_error)?;
+    let imported = path.with_extension("json.imported");
+    fs::rename(&path, &imported).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))?;
+    log::info!("Imported {} snippets into {}", snippets.len(), DATABASE_FILE);
+    Ok(())
+}
+
+fn open(app: &AppHandle) -> Result<Connection, String> {
+    let path = storage::data_dir(app)?.join(DATABASE_FILE);
+    let mut db = Connection::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    db.execute_batch(
+        "CREATE TABLE IF NOT EXISTS snippets (
+            id TEXT PRIMARY KEY,
+            title TEXT NOT NULL,
+            prefix TEXT,
+            body TEXT NOT NULL,
+            language TEXT,
+            description TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            source TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS snippets_language ON snippets (language);",
+    )
+    .map_err(db_error)?;
+    create_search_index(&db).map_err(db_error)?;
+    import_json(app, &mut db)?;
+    Ok(db)
+}
+
+/// The full-text index behind `search`, kept in step with the table by triggers. Libraries from
+/// before it existed are indexed when it is created.
+fn create_search_index(db: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = db.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'snippets_fts')",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(());
+    }
+    db.execute_batch(
+        "BEGIN;
+        CREATE VIRTUAL TABLE snippets_fts USING fts5(
+            id UNINDEXED, title, prefix, description, tags, body,
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER snippets_fts_insert AFTER INSERT ON snippets BEGIN
+            INSERT INTO snippets_fts (id, title, prefix, description, tags, body)
+            VALUES (new.id, new.title, new.prefix, new.description, new.tags, new.body);
+        END;
+        CREATE TRIGGER snippets_fts_delete AFTER DELETE ON snippets BEGIN
+            DELETE FROM snippets_fts WHERE id = old.id;
+        END;
+        CREATE TRIGGER snippets_fts_update AFTER UPDATE ON snippets BEGIN
+            DELETE FROM snippets_fts WHERE id = old.id;
+            INSERT INTO snippets_fts (id, title, prefix, description, tags, body)
+            VALUES (new.id, new.title, new.prefix, new.description, new.tags, new.body);
+        END;
+        INSERT INTO snippets_fts (id, title, prefix, description, tags, body)
+        SELECT id, title, prefix, description, tags, body FROM snippets;
+        COMMIT;",
+    )
+}
+
+fn load_all(db: &Connection) -> Result<Vec<Snippet>, String> {
+    let mut query = db
+        .prepare(&format!("SELECT {} FROM snippets ORDER BY created_at, rowid", COLUMNS))
+        .map_err(db_error)?;
+    let rows = query.query_map([], read_row).map_err(db_error)?;
+    rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+}
+
+impl SnippetLibrary {
+    pub fn all(&self, app: &AppHandle) -> Result<Vec<Snippet>, String> {
+        let _guard = self.lock.lock().unwrap();
+        load_all(&open(app)?)
+    }
+
+    /// Insert or update a batch of snippets in a single transaction.
+    pub fn upsert_many(&self, app: &AppHandle, inputs: Vec<SnippetInput>) -> Result<Vec<Snippet>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut db = open(app)?;
+        let tx = db.transaction().map_err(db_error)?;
+        let now = storage::now_secs();
+        let mut saved = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let existing = match &input.id {
+                Some(id) => tx
+                    .query_row(&format!("SELECT {} FROM snippets WHERE id = ?1", COLUMNS), [id], read_row)
+                    .optional()
+                    .map_err(db_error)?,
+                None => None,
+            };
+            let snippet = match existing {
+                Some(mut snippet) => {
+                    snippet.title = input.title;
+                    snippet.prefix = input.prefix;
+                    snippet.body = input.body;
+                    snippet.language = input.language;
+                    snippet.description = input.description;
+                    snippet.tags = input.tags;
+                    snippet.source = input.source.or(snippet.source.take());
+                    snippet.updated_at = now;
+                    snippet
+                }
+                None => Snippet {
+                    id: input.id.unwrap_or_else(new_id),
+                    title: input.title,
+                    prefix: input.prefix,
+                    body: input.body,
+                    language: input.language,
+                    description: input.description,
+                    tags: input.tags,
+                    source: input.source,
+                    created_at: now,
+                    updated_at: now,
+                },
+            };
+            write_row(&tx, &snippet).map_err(db_error)?;
+            saved.push(snippet);
+        }
+        tx.commit().map_err(db_error)?;
+        sync_search(app, &load_all(&db)?);
+        Ok(saved)
+    }
+
+    /// Snippets matching `query` by title, prefix, description, tags or body, best first, with
+    /// where each matched. An empty query lists the filtered snippets, most recently updated first.
+    pub fn search(&self, app: &AppHandle, query: &str, filters: &SnippetFilters) -> Result<Vec<SnippetMatch>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let db = open(app)?;
+        let limit = i64::try_from(filters.limit.unwrap_or(DEFAULT_SEARCH_LIMIT)).unwrap_or(i64::MAX);
+        let expression = match_expression(query);
+        let columns: Vec<String> = COLUMNS.split(", ").map(|column| format!("s.{}", column)).collect();
+        let mut sql = match expression {
+            // Title matches count most, body matches least
+            Some(_) => format!(
+                "SELECT {}, -bm25(snippets_fts, 0.0, 10.0, 5.0, 3.0, 2.0, 1.0) AS score,
+                    highlight(snippets_fts, 1, char(1), char(2)), highlight(snippets_fts, 2, char(1), char(2)),
+                    highlight(snippets_fts, 3, char(1), char(2)), highlight(snippets_fts, 5, char(1), char(2))
+                FROM snippets_fts JOIN snippets s ON s.id = snippets_fts.id
+                WHERE snippets_fts MATCH ?1",
+                columns.join(", ")
+            ),
+            // `?1` is there so the parameters are numbered the same either way
+            None => format!("SELECT {} FROM snippets s WHERE ?1 IS NULL", columns.join(", ")),
+        };
+        sql.push_str(" AND (?2 IS NULL OR s.language = ?2)");
+        for i in 0..filters.tags.len() {
+            sql.push_str(&format!(" AND EXISTS (SELECT 1 FROM json_each(s.tags) WHERE value = ?{})", i + 4));
+        }
+        sql.push_str(match expression {
+            Some(_) => " ORDER BY score DESC LIMIT ?3",
+            None => " ORDER BY s.updated_at DESC LIMIT ?3",
+        });
+
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&expression, &filters.language, &limit];
+        values.extend(filters.tags.iter().map(|tag| tag as &dyn rusqlite::ToSql));
+        let mut statement = db.prepare(&sql).map_err(db_error)?;
+        let ranked = expression.is_some();
+        let rows = statement
+            .query_map(values.as_slice(), |row| {
+                let snippet = read_row(row)?;
+                if !ranked {
+                    return Ok(SnippetMatch {
+                        snippet,
+                        score: 0.0,
+                        highlights: Vec::new(),
+                    });
+                }
+                let mut found = Vec::new();
+                highlights(SnippetField::Title, row.get(11)?, &mut found);
+                highlights(SnippetField::Prefix, row.get(12)?, &mut found);
+                highlights(SnippetField::Description, row.get(13)?, &mut found);
+                highlights(SnippetField::Body, row.get(14)?, &mut found);
+                Ok(SnippetMatch {
+                    snippet,
+                    score: row.get(10)?,
+                    highlights: found,
+                })
+            })
+            .map_err(db_error)?;
+        rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+    }
+
+    pub fn get(&self, app: &AppHandle, id: &str) -> Result<Snippet, String> {
+        let _guard = self.lock.lock().unwrap();
+        open(app)?
+            .query_row(&format!("SELECT {} FROM snippets WHERE id = ?1", COLUMNS), [id], read_row)
+            .optional()
+            .map_err(db_error)?
+            .ok_or_else(|| format!("Unknown snippet {}", id))
+    }
+
+    pub fn remove(&self, app: &AppHandle, id: &str) -> Result<bool, String> {
+        let _guard = self.lock.lock().unwrap();
+        let db = open(app)?;
+        let removed = db.execute("DELETE FROM snippets WHERE id = ?1", [id]).map_err(db_error)?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        sync_search(app, &load_all(&db)?);
+        Ok(true)
+    }
+}
+
+#[tauri::command]
+pub fn list_snippets(app: AppHandle, library: State<'_, SnippetLibrary>) -> Result<Vec<Snippet>, String> {
+    library.all(&app)
+}
+
+#[tauri::command]
+pub fn get_snippet(app: AppHandle, library: State<'_, SnippetLibrary>, id: String) -> Result<Snippet, String> {
+    library.get(&app, &id)
+}
+
+#[tauri::command]
+pub fn search_snippets(
+    app: AppHandle,
+    library: State<'_, SnippetLibrary>,
+    query: String,
+    filters: Option<SnippetFilters>,
+) -> Result<Vec<SnippetMatch>, String> {
+    library.search(&app, &query, &filters.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn save_snippet(app: AppHandle, library: State<'_, SnippetLibrary>, snippet: SnippetInput) -> Result<Snippet, String> {
+    library
+        .upsert_many(&app, vec![snippet])?
+        .pop()
+        .ok_or_else(|| "Failed to save snippet".to_string())
+}
+
+#[tauri::command]
+pub fn delete_snippet(app: AppHandle, library: State<'_, SnippetLibrary>, id: String) -> Result<bool, String> {
+    library.remove(&app, &id)
+}
+
+/// Send a snippet from the library to everyone in the hosted session, over whichever transport
+/// they joined with. It arrives as `snippet-received`, for the participant to save if they want.
+#[tauri::command]
+pub fn broadcast_snippet(app: AppHandle, library: State<'_, SnippetLibrary>, id: String) -> Result<(), String> {
+    let snippet = PortableSnippet::from(library.get(&app, &id)?);
+    session::broadcast(&app, &Message::Snippet { snippet })
+}
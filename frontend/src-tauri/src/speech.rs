@@ -0,0 +1,303 @@
+//! Reading session events aloud with the operating system's own voices, for presenters whose
+//! sharecode window is hidden or behind the presentation.
+//!
+//! Utterances are spoken one at a time by a worker thread that drives the platform speech tool:
+//! `say` on macOS, System.Speech through PowerShell on Windows and Speech Dispatcher's `spd-say`
+//! elsewhere. Nothing is queued while muted, and anything that waited too long is dropped rather
+//! than read out of context.
+
+#[cfg(desktop)]
+use std::io::Write;
+use std::process::Child;
+#[cfg(desktop)]
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::protocol::Signal;
+use crate::storage;
+
+const CONFIG_FILE: &str = "speech.json";
+/// Further events are dropped while this many are waiting to be spoken.
+const MAX_QUEUED: usize = 5;
+const MAX_WAIT: Duration = Duration::from_secs(30);
+/// Long comments are cut off; the rest is on screen.
+const MAX_SPOKEN_CHARS: usize = 300;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeechEvent {
+    Chat,
+    Annotation,
+    Signal,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpeechConfig {
+    pub enabled: bool,
+    pub chat: bool,
+    pub annotations: bool,
+    /// Raised hands and reactions.
+    pub signals: bool,
+    /// 0 to 1; low by default so it does not carry over a call.
+    pub volume: f32,
+    /// Relative to the voice's normal pace, 0.5 to 2.
+    pub rate: f32,
+    /// Platform voice name; the system default when unset.
+    pub voice: Option<String>,
+    /// Stay quiet while a sharecode window has focus, since the events are on screen.
+    pub only_when_unfocused: bool,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chat: true,
+            annotations: true,
+            signals: false,
+            volume: 0.3,
+            rate: 1.0,
+            voice: None,
+            only_when_unfocused: true,
+        }
+    }
+}
+
+struct Utterance {
+    text: String,
+    queued_at: Instant,
+}
+
+#[derive(Default)]
+pub struct Speech {
+    config: Mutex<SpeechConfig>,
+    muted: AtomicBool,
+    queued: AtomicUsize,
+    worker: Mutex<Option<Sender<Utterance>>>,
+    /// The process speaking right now, so muting can cut it off.
+    speaking: Mutex<Option<Child>>,
+}
+
+impl Speech {
+    pub fn load(app: &AppHandle) -> Self {
+        let config = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+            log::warn!("Using default speech config: {}", e);
+            SpeechConfig::default()
+        });
+        Self {
+            config: Mutex::new(config),
+            ..Default::default()
+        }
+    }
+
+    fn config(&self) -> SpeechConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    fn stop_speaking(&self) {
+        if let Some(mut child) = self.speaking.lock().unwrap().take() {
+            child.kill().ok();
+            child.wait().ok();
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn speak_command(config: &SpeechConfig) -> (Command, String) {
+    let mut command = Command::new("say");
+    command.arg("-r").arg(((175.0 * config.rate).round() as u32).to_string());
+    if let Some(voice) = &config.voice {
+        command.arg("-v").arg(voice);
+    }
+    // `say` has no volume flag, only an embedded command.
+    (command, format!("[[volm {:.2}]] ", config.volume))
+}
+
+#[cfg(target_os = "windows")]
+fn speak_command(config: &SpeechConfig) -> (Command, String) {
+    let mut script = format!(
+        "Add-Type -AssemblyName System.Speech; \
+         $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; \
+         $s.Volume = {}; $s.Rate = {}; ",
+        (config.volume * 100.0).round() as u32,
+        (config.rate.log2() * 5.0).round().clamp(-10.0, 10.0) as i32,
+    );
+    if let Some(voice) = &config.voice {
+        script.push_str(&format!("$s.SelectVoice('{}'); ", voice.replace('\'', "''")));
+    }
+    script.push_str("$s.Speak([Console]::In.ReadToEnd())");
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+    (command, String::new())
+}
+
+#[cfg(all(desktop, not(any(target_os = "macos", target_os = "windows"))))]
+fn speak_command(config: &SpeechConfig) -> (Command, String) {
+    let mut command = Command::new("spd-say");
+    command
+        .arg("--wait")
+        .arg("--pipe-mode")
+        .arg("-i")
+        .arg(((config.volume * 200.0 - 100.0).round() as i32).to_string())
+        .arg("-r")
+        .arg(((config.rate.log2() * 100.0).round().clamp(-100.0, 100.0) as i32).to_string());
+    if let Some(voice) = &config.voice {
+        command.arg("-y").arg(voice);
+    }
+    command.stdout(Stdio::null());
+    (command, String::new())
+}
+
+/// Speak `text` and wait until it is finished or cut off.
+#[cfg(desktop)]
+fn speak(speech: &Speech, text: &str) -> Result<(), String> {
+    let (mut command, prefix) = speak_command(&speech.config());
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Text-to-speech is unavailable: {}", e))?;
+    // Text goes through stdin so it is never interpreted as arguments.
+    let mut stdin = child.stdin.take().ok_or("Text-to-speech is unavailable")?;
+    *speech.speaking.lock().unwrap() = Some(child);
+    stdin.write_all(format!("{}{}", prefix, text).as_bytes()).ok();
+    drop(stdin);
+    // Poll instead of blocking in `wait` so muting can take the child and kill it.
+    loop {
+        let mut speaking = speech.speaking.lock().unwrap();
+        let Some(child) = speaking.as_mut() else {
+            return Ok(());
+        };
+        if child.try_wait().map_err(|e| e.to_string())?.is_some() {
+            speaking.take();
+            return Ok(());
+        }
+        drop(speaking);
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(mobile)]
+fn speak(_speech: &Speech, _text: &str) -> Result<(), String> {
+    Err("Text-to-speech is not supported on this platform".to_string())
+}
+
+fn run_worker(app: AppHandle, rx: Receiver<Utterance>) {
+    let speech = app.state::<Speech>();
+    for utterance in rx {
+        speech.queued.fetch_sub(1, Ordering::Relaxed);
+        if speech.muted.load(Ordering::Relaxed) || utterance.queued_at.elapsed() > MAX_WAIT {
+            continue;
+        }
+        if let Err(e) = speak(&speech, &utterance.text) {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+fn shorten(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(MAX_SPOKEN_CHARS) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    }
+}
+
+/// Queue `text` for reading aloud if events of this kind are enabled and nothing is muted.
+pub fn announce(app: &AppHandle, event: SpeechEvent, text: &str) {
+    let speech = app.state::<Speech>();
+    let config = speech.config();
+    let wanted = match event {
+        SpeechEvent::Chat => config.chat,
+        SpeechEvent::Annotation => config.annotations,
+        SpeechEvent::Signal => config.signals,
+    };
+    if !config.enabled || !wanted || speech.muted.load(Ordering::Relaxed) {
+        return;
+    }
+    if config.only_when_unfocused && app.webview_windows().values().any(|w| w.is_focused().unwrap_or(false)) {
+        return;
+    }
+    if speech.queued.load(Ordering::Relaxed) >= MAX_QUEUED {
+        return;
+    }
+
+    let mut worker = speech.worker.lock().unwrap();
+    let tx = worker.get_or_insert_with(|| {
+        let (tx, rx) = mpsc::channel();
+        let app = app.clone();
+        thread::spawn(move || run_worker(app, rx));
+        tx
+    });
+    let utterance = Utterance {
+        text: shorten(text),
+        queued_at: Instant::now(),
+    };
+    speech.queued.fetch_add(1, Ordering::Relaxed);
+    if tx.send(utterance).is_err() {
+        speech.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Read out a participant's signal. Lowered hands are not worth interrupting for.
+pub fn announce_signal(app: &AppHandle, name: &str, signal: Signal) {
+    let action = match signal {
+        Signal::RaiseHand => "raised their hand",
+        Signal::ThumbsUp => "gave a thumbs up",
+        Signal::GoSlower => "asked you to slow down",
+        Signal::GoFaster => "asked you to speed up",
+        Signal::Confused => "is confused",
+        Signal::LowerHand => return,
+    };
+    announce(app, SpeechEvent::Signal, &format!("{} {}", name, action));
+}
+
+#[tauri::command]
+pub fn get_speech_config(speech: State<'_, Speech>) -> SpeechConfig {
+    speech.config()
+}
+
+#[tauri::command]
+pub fn set_speech_config(app: AppHandle, speech: State<'_, Speech>, config: SpeechConfig) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&config.volume) || !(0.5..=2.0).contains(&config.rate) {
+        return Err("Volume must be between 0 and 1 and rate between 0.5 and 2".to_string());
+    }
+    storage::save_json(&app, CONFIG_FILE, &config)?;
+    if !config.enabled {
+        speech.stop_speaking();
+    }
+    *speech.config.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Silence everything at once, including what is being spoken now. Not persisted.
+#[tauri::command]
+pub fn set_speech_muted(speech: State<'_, Speech>, muted: bool) {
+    speech.muted.store(muted, Ordering::Relaxed);
+    if muted {
+        speech.stop_speaking();
+    }
+}
+
+pub fn is_muted(app: &AppHandle) -> bool {
+    app.state::<Speech>().muted.load(Ordering::Relaxed)
+}
+
+/// Read out an event the frontend received, e.g. a chat message or an annotation comment.
+#[tauri::command]
+pub fn announce_event(app: AppHandle, event: SpeechEvent, author: Option<String>, text: String) {
+    let text = match (event, author) {
+        (SpeechEvent::Chat, Some(author)) => format!("{} says: {}", author, text),
+        (SpeechEvent::Annotation, Some(author)) => format!("{} commented: {}", author, text),
+        (_, _) => text,
+    };
+    announce(&app, event, &text);
+}
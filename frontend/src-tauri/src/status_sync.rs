@@ -0,0 +1,361 @@
+//! Setting the user's Slack and Teams status to "presenting" while a session is hosted, and
+//! putting it back afterwards.
+//!
+//! Slack gets a custom status (text and emoji), with whatever was set before saved and restored.
+//! Teams has no presence a user can set to "Presenting", so it gets Do Not Disturb as a preferred
+//! presence, which also silences notifications during the session; clearing the preference
+//! afterwards hands presence back to Teams. Tokens are pasted in by the user and kept in the OS
+//! keychain: a Slack user token with `users.profile:read` and `users.profile:write`, and a
+//! Microsoft Graph token with `Presence.ReadWrite`.
+//!
+//! Updates run on a background thread so an unreachable service never holds up the session. Each
+//! is retried after `RETRY_DELAYS`, abandoned if the session changes again meanwhile, and reported
+//! as `status-sync-failed` once the retries run out. What was changed is saved to disk before the
+//! change is made, so a crash mid-session is put right at the next startup.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::storage;
+
+const CONFIG_FILE: &str = "status-sync.json";
+const SAVED_FILE: &str = "status-sync-saved.json";
+const KEYCHAIN_SERVICE: &str = "sharecode-status-sync";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_DELAYS: [Duration; 3] = [Duration::from_secs(5), Duration::from_secs(30), Duration::from_secs(120)];
+/// Teams drops the preference after this even if it is never cleared, in case ShareCode can't.
+const TEAMS_EXPIRATION: &str = "PT8H";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusService {
+    Slack,
+    Teams,
+}
+
+impl StatusService {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Slack => "Slack",
+            Self::Teams => "Teams",
+        }
+    }
+
+    fn account(self) -> &'static str {
+        match self {
+            Self::Slack => "slack",
+            Self::Teams => "teams",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StatusSyncConfig {
+    pub services: Vec<StatusService>,
+    pub slack_text: String,
+    pub slack_emoji: String,
+}
+
+impl Default for StatusSyncConfig {
+    fn default() -> Self {
+        StatusSyncConfig {
+            services: Vec::new(),
+            slack_text: "Presenting".to_string(),
+            slack_emoji: ":desktop_computer:".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SlackStatus {
+    text: String,
+    emoji: String,
+    expiration: i64,
+}
+
+/// The services currently showing "presenting", and the Slack status to put back.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Saved {
+    presenting: Vec<StatusService>,
+    slack: Option<SlackStatus>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusSyncFailed {
+    service: StatusService,
+    presenting: bool,
+    error: String,
+}
+
+#[derive(Default)]
+pub struct StatusSync {
+    lock: Mutex<()>,
+    /// Bumped on every session change, so retries for an earlier one stop.
+    generation: AtomicU64,
+}
+
+impl StatusSync {
+    fn config(&self, app: &AppHandle) -> Result<StatusSyncConfig, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, CONFIG_FILE)
+    }
+
+    fn save_config(&self, app: &AppHandle, config: &StatusSyncConfig) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::save_json(app, CONFIG_FILE, config)
+    }
+
+    fn saved(&self, app: &AppHandle) -> Result<Saved, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, SAVED_FILE)
+    }
+
+    fn update_saved(&self, app: &AppHandle, f: impl FnOnce(&mut Saved)) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut saved: Saved = storage::load_json(app, SAVED_FILE)?;
+        f(&mut saved);
+        storage::save_json(app, SAVED_FILE, &saved)
+    }
+}
+
+fn token(service: StatusService) -> Result<String, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, service.account())
+        .and_then(|entry| entry.get_password())
+        .map_err(|_| format!("No {} token has been set", service.name()))
+}
+
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(401 | 403, _) => "The token was rejected; it may have expired".to_string(),
+        ureq::Error::Status(code, response) => {
+            format!("Request failed with {}: {}", code, response.into_string().unwrap_or_default())
+        }
+        other => format!("Request failed: {}", other),
+    }
+}
+
+/// Slack answers errors with 200 and `ok: false`.
+fn slack_call(request: ureq::Request, body: Option<Value>) -> Result<Value, String> {
+    let request = request
+        .timeout(REQUEST_TIMEOUT)
+        .set("Authorization", &format!("Bearer {}", token(StatusService::Slack)?));
+    let response = match body {
+        Some(body) => request.set("Content-Type", "application/json; charset=utf-8").send_json(body),
+        None => request.call(),
+    };
+    let reply: Value = response
+        .map_err(describe)?
+        .into_json()
+        .map_err(|e| format!("Invalid response from Slack: {}", e))?;
+    if reply["ok"] != json!(true) {
+        return Err(format!("Slack refused: {}", reply["error"].as_str().unwrap_or("unknown error")));
+    }
+    Ok(reply)
+}
+
+fn set_slack_status(status: &SlackStatus) -> Result<(), String> {
+    let profile = json!({ "profile": {
+        "status_text": status.text,
+        "status_emoji": status.emoji,
+        "status_expiration": status.expiration,
+    }});
+    slack_call(ureq::post("https://slack.com/api/users.profile.set"), Some(profile)).map(|_| ())
+}
+
+fn teams_call(action: &str, body: Value) -> Result<(), String> {
+    ureq::post(&format!("https://graph.microsoft.com/v1.0/me/presence/{}", action))
+        .timeout(REQUEST_TIMEOUT)
+        .set("Authorization", &format!("Bearer {}", token(StatusService::Teams)?))
+        .send_json(body)
+        .map_err(describe)?;
+    Ok(())
+}
+
+fn start_presenting(app: &AppHandle, service: StatusService) -> Result<(), String> {
+    let sync = app.state::<StatusSync>();
+    let config = sync.config(app)?;
+    match service {
+        StatusService::Slack => {
+            // Only saved the first time, so a retry doesn't save our own status as the user's
+            if sync.saved(app)?.slack.is_none() {
+                let reply = slack_call(ureq::get("https://slack.com/api/users.profile.get"), None)?;
+                let profile = &reply["profile"];
+                let previous = SlackStatus {
+                    text: profile["status_text"].as_str().unwrap_or_default().to_string(),
+                    emoji: profile["status_emoji"].as_str().unwrap_or_default().to_string(),
+                    expiration: profile["status_expiration"].as_i64().unwrap_or(0),
+                };
+                sync.update_saved(app, |saved| saved.slack = Some(previous))?;
+            }
+            sync.update_saved(app, |saved| mark(saved, service, true))?;
+            set_slack_status(&SlackStatus {
+                text: config.slack_text,
+                emoji: config.slack_emoji,
+                expiration: 0,
+            })
+        }
+        StatusService::Teams => {
+            sync.update_saved(app, |saved| mark(saved, service, true))?;
+            teams_call(
+                "setUserPreferredPresence",
+                json!({
+                    "availability": "DoNotDisturb",
+                    "activity": "DoNotDisturb",
+                    "expirationDuration": TEAMS_EXPIRATION,
+                }),
+            )
+        }
+    }
+}
+
+fn stop_presenting(app: &AppHandle, service: StatusService) -> Result<(), String> {
+    let sync = app.state::<StatusSync>();
+    match service {
+        StatusService::Slack => {
+            let previous = sync.saved(app)?.slack.unwrap_or(SlackStatus {
+                text: String::new(),
+                emoji: String::new(),
+                expiration: 0,
+            });
+            set_slack_status(&previous)?;
+            sync.update_saved(app, |saved| {
+                saved.slack = None;
+                mark(saved, service, false);
+            })
+        }
+        StatusService::Teams => {
+            teams_call("clearUserPreferredPresence", json!({}))?;
+            sync.update_saved(app, |saved| mark(saved, service, false))
+        }
+    }
+}
+
+fn mark(saved: &mut Saved, service: StatusService, presenting: bool) {
+    saved.presenting.retain(|s| *s != service);
+    if presenting {
+        saved.presenting.push(service);
+    }
+}
+
+/// Bring every affected service to `presenting` in the background, retrying failures.
+fn sync_services(app: &AppHandle, presenting: bool) {
+    let sync = app.state::<StatusSync>();
+    let generation = sync.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    let services = if presenting {
+        sync.config(app).map(|config| config.services)
+    } else {
+        // Restore whatever was changed, even for a service turned off since
+        sync.saved(app).map(|saved| saved.presenting)
+    };
+    let services = match services {
+        Ok(services) => services,
+        Err(e) => {
+            log::warn!("Failed to read status sync settings: {}", e);
+            return;
+        }
+    };
+
+    for service in services {
+        let app = app.clone();
+        thread::spawn(move || {
+            let current = || app.state::<StatusSync>().generation.load(Ordering::SeqCst) == generation;
+            let mut delays = RETRY_DELAYS.iter();
+            loop {
+                let result = if presenting {
+                    start_presenting(&app, service)
+                } else {
+                    stop_presenting(&app, service)
+                };
+                let Err(error) = result else {
+                    return;
+                };
+                match delays.next() {
+                    Some(delay) => {
+                        log::warn!("Failed to update {} status, retrying: {}", service.name(), error);
+                        thread::sleep(*delay);
+                        if !current() {
+                            return;
+                        }
+                    }
+                    None => {
+                        let failed = StatusSyncFailed {
+                            service,
+                            presenting,
+                            error,
+                        };
+                        if let Err(e) = app.emit("status-sync-failed", failed) {
+                            log::warn!("Failed to emit status-sync-failed: {}", e);
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Called when hosting starts or ends.
+pub fn session_changed(app: &AppHandle, presenting: bool) {
+    sync_services(app, presenting);
+}
+
+/// Put back any status a previous run left as "presenting". Called once from setup.
+pub fn restore(app: &AppHandle) {
+    let left_over = app.state::<StatusSync>().saved(app).is_ok_and(|saved| !saved.presenting.is_empty());
+    if left_over {
+        log::info!("Restoring status left from a previous session");
+        sync_services(app, false);
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusSyncSettings {
+    pub config: StatusSyncConfig,
+    /// Services with a token in the keychain.
+    pub connected: Vec<StatusService>,
+}
+
+#[tauri::command]
+pub fn get_status_sync(app: AppHandle, sync: State<'_, StatusSync>) -> Result<StatusSyncSettings, String> {
+    let connected = [StatusService::Slack, StatusService::Teams]
+        .into_iter()
+        .filter(|service| token(*service).is_ok())
+        .collect();
+    Ok(StatusSyncSettings {
+        config: sync.config(&app)?,
+        connected,
+    })
+}
+
+/// Choose the services to update and the Slack status to show. Takes effect from the next session.
+#[tauri::command]
+pub fn set_status_sync(app: AppHandle, sync: State<'_, StatusSync>, config: StatusSyncConfig) -> Result<(), String> {
+    sync.save_config(&app, &config)
+}
+
+/// Store a service's token in the keychain, or remove it with `None`.
+#[tauri::command]
+pub fn set_status_token(service: StatusService, token: Option<String>) -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, service.account())
+        .map_err(|e| format!("Failed to open the keychain: {}", e))?;
+    let stored = match token.as_deref().map(str::trim) {
+        Some(token) if !token.is_empty() => entry.set_password(token),
+        _ => match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        },
+    };
+    stored.map_err(|e| format!("Failed to store the {} token: {}", service.name(), e))
+}
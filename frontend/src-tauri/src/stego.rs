@@ -0,0 +1,157 @@
+//! Experimental: hide a small snippet in the low bits of a PNG, for channels where only images
+//! can be exchanged.
+//!
+//! The deflated snippet is written one bit per colour channel into the least significant bits of
+//! the red, green and blue samples, skipping fully transparent pixels (some optimizers zero their
+//! colour). This survives lossless copies only; anything that re-encodes the image, as most chat
+//! apps do for photos, destroys the payload, which the integrity check then reports.
+
+use std::path::Path;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+
+use crate::armor;
+use crate::imaging::{self, Image};
+use crate::snippets::{PortableSnippet, SnippetLibrary};
+
+const MAGIC: &[u8; 4] = b"SCS1";
+const CHECKSUM_LEN: usize = 8;
+/// Magic, payload length (u32, big-endian) and checksum.
+const HEADER_LEN: usize = MAGIC.len() + 4 + CHECKSUM_LEN;
+/// Larger payloads make the image conspicuous and defeat the purpose.
+const MAX_HIDDEN_BYTES: usize = 64 * 1024;
+const MIN_GENERATED_SIDE: u32 = 256;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StegoInfo {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes hidden, including the header.
+    pub hidden_bytes: usize,
+    /// Bytes the image could have held.
+    pub capacity: usize,
+}
+
+/// Indices of the samples that carry payload bits.
+fn carriers(image: &Image) -> impl Iterator<Item = usize> + '_ {
+    image
+        .pixels
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(_, px)| px[3] != 0)
+        .flat_map(|(i, _)| (0..3).map(move |c| i * 4 + c))
+}
+
+fn capacity(image: &Image) -> usize {
+    carriers(image).count() / 8
+}
+
+/// A soft, abstract gradient just large enough for `bytes`, used when no cover image is given.
+fn generate_cover(bytes: usize) -> Image {
+    let pixels_needed = (bytes * 8).div_ceil(3) as f64;
+    let side = (pixels_needed.sqrt().ceil() as u32).max(MIN_GENERATED_SIDE);
+    let mut pixels = Vec::with_capacity((side * side * 4) as usize);
+    for y in 0..side {
+        for x in 0..side {
+            let (fx, fy) = (x as f64 / side as f64, y as f64 / side as f64);
+            let wave = ((fx * 6.0).sin() + (fy * 4.0 + fx * 2.0).cos()) * 0.25 + 0.5;
+            pixels.extend_from_slice(&[
+                (120.0 + 100.0 * fx) as u8,
+                (140.0 + 80.0 * wave) as u8,
+                (200.0 - 60.0 * fy) as u8,
+                255,
+            ]);
+        }
+    }
+    Image {
+        width: side,
+        height: side,
+        pixels,
+    }
+}
+
+fn embed(image: &mut Image, data: &[u8]) -> Result<(), String> {
+    if data.len() > capacity(image) {
+        return Err(format!(
+            "Image can hold {} bytes but the snippet needs {}; use a larger cover image",
+            capacity(image),
+            data.len()
+        ));
+    }
+    let indices: Vec<usize> = carriers(image).take(data.len() * 8).collect();
+    let bits = data.iter().flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1));
+    for (index, bit) in indices.into_iter().zip(bits) {
+        image.pixels[index] = (image.pixels[index] & !1) | bit;
+    }
+    Ok(())
+}
+
+fn extract(image: &Image) -> Result<Vec<u8>, String> {
+    let mut samples = carriers(image);
+    let mut read = |count: usize| -> Option<Vec<u8>> {
+        (0..count)
+            .map(|_| (0..8).try_fold(0u8, |byte, _| Some((byte << 1) | (image.pixels[samples.next()?] & 1))))
+            .collect()
+    };
+    let not_found = || "No snippet is hidden in this image".to_string();
+    let header = read(HEADER_LEN).ok_or_else(not_found)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return Err(not_found());
+    }
+    let len = u32::from_be_bytes(header[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap()) as usize;
+    if len > MAX_HIDDEN_BYTES {
+        return Err("Hidden snippet is corrupted (implausible length)".to_string());
+    }
+    let data = read(len).ok_or("Hidden snippet is truncated")?;
+    if Sha256::digest(&data)[..CHECKSUM_LEN] != header[MAGIC.len() + 4..] {
+        return Err("Hidden snippet failed its integrity check (was the image re-encoded?)".to_string());
+    }
+    Ok(data)
+}
+
+/// Hide a snippet in `cover_path` (or a generated image) and write the result to `output_path`.
+#[tauri::command]
+pub fn hide_snippet_in_png(
+    app: AppHandle,
+    library: State<'_, SnippetLibrary>,
+    id: String,
+    output_path: String,
+    cover_path: Option<String>,
+) -> Result<StegoInfo, String> {
+    let compressed = armor::compress(&PortableSnippet::from(library.get(&app, &id)?))?;
+    if compressed.len() > MAX_HIDDEN_BYTES {
+        return Err(format!("Only snippets up to {} KiB compressed can be hidden", MAX_HIDDEN_BYTES / 1024));
+    }
+    let mut data = Vec::with_capacity(HEADER_LEN + compressed.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    data.extend_from_slice(&Sha256::digest(&compressed)[..CHECKSUM_LEN]);
+    data.extend_from_slice(&compressed);
+
+    let mut image = match &cover_path {
+        Some(cover) => imaging::read_png(Path::new(cover))?,
+        None => generate_cover(data.len()),
+    };
+    let room = capacity(&image);
+    embed(&mut image, &data)?;
+    imaging::write_png(Path::new(&output_path), &image)?;
+
+    Ok(StegoInfo {
+        path: output_path,
+        width: image.width,
+        height: image.height,
+        hidden_bytes: data.len(),
+        capacity: room,
+    })
+}
+
+/// Recover a snippet hidden by [`hide_snippet_in_png`]. The caller decides whether to save it.
+#[tauri::command]
+pub fn reveal_snippet_from_png(path: String) -> Result<PortableSnippet, String> {
+    let image = imaging::read_png(Path::new(&path))?;
+    armor::decompress(&extract(&image)?)
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+/// Set once a backup has been swapped in. The stores in memory still hold what they loaded from
+/// the old files and would write it over the restored ones, so nothing touches the data directory
+/// again until the app restarts.
+static SEALED: AtomicBool = AtomicBool::new(false);
+
+/// Refuse all access to the data directory for the rest of this run.
+pub fn seal_data_dir() {
+    SEALED.store(true, Ordering::Relaxed);
+}
+
+/// Directory holding everything the desktop app persists (library, settings, ...).
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    if SEALED.load(Ordering::Relaxed) {
+        return Err("A backup was restored; restart ShareCode to use it".to_string());
+    }
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Directory for machine-local secrets that must never end up inside a backup.
+pub fn config_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to resolve app config directory: {}", e))?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Read a JSON file from the data directory, falling back to `T::default()` when it is missing.
+pub fn load_json<T: DeserializeOwned + Default>(app: &AppHandle, name: &str) -> Result<T, String> {
+    let path = data_dir(app)?.join(name);
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let raw = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_slice(&raw).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Write a JSON file into the data directory via a temp file so a crash never leaves it half-written.
+pub fn save_json<T: Serialize>(app: &AppHandle, name: &str, value: &T) -> Result<(), String> {
+    let path = data_dir(app)?.join(name);
+    let tmp = path.with_extension("json.tmp");
+    let raw = serde_json::to_vec_pretty(value).map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+    fs::write(&tmp, raw).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, &path).map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+/// Seconds since the Unix epoch, used for timestamps across the backend.
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Random 16-hex-digit identifier for snippets, documents and other backend records.
+pub fn new_id() -> String {
+    let mut bytes = [0u8; 8];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
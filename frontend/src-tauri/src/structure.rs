@@ -0,0 +1,144 @@
+//! Fold ranges and structural selection computed from tree-sitter syntax trees.
+//!
+//! Offsets exchanged with the webview are UTF-16 code units, matching the editor's model.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use tree_sitter::Node;
+
+use crate::syntax::{self, OffsetMap, SymbolKind};
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FoldKind {
+    Region,
+    Comment,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FoldRange {
+    /// Zero-based; the start line stays visible when folded.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub kind: FoldKind,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectionRange {
+    pub start: usize,
+    pub end: usize,
+    /// Tree-sitter node kind (`function_item`, `block`), or the symbol kind for `select_enclosing_symbol`.
+    pub kind: String,
+}
+
+fn is_comment(node: Node) -> bool {
+    node.kind().contains("comment")
+}
+
+fn collect_folds(node: Node, folds: &mut BTreeMap<usize, FoldRange>) {
+    let start = node.start_position().row;
+    // A node ending at column 0 ends on the previous line for folding purposes.
+    let end = match node.end_position() {
+        p if p.column == 0 && p.row > start => p.row - 1,
+        p => p.row,
+    };
+    if end > start && node.parent().is_some() {
+        let kind = if is_comment(node) { FoldKind::Comment } else { FoldKind::Region };
+        // Keep the widest fold per start line so nested nodes on one line don't shadow it.
+        let wider = folds.get(&start).map_or(true, |f| f.end_line < end);
+        if wider {
+            folds.insert(start, FoldRange { start_line: start, end_line: end, kind });
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_folds(child, folds);
+    }
+}
+
+/// Fold ranges for every multi-line syntax node and comment, ordered by start line.
+pub fn fold_ranges(language: &str, text: &str) -> Result<Vec<FoldRange>, String> {
+    let tree = syntax::parse(language, text)?;
+    let mut folds = BTreeMap::new();
+    collect_folds(tree.root_node(), &mut folds);
+    Ok(folds.into_values().collect())
+}
+
+/// Successively larger syntax ranges enclosing `start..end`, innermost first.
+pub fn selection_ranges(language: &str, text: &str, start: usize, end: usize) -> Result<Vec<SelectionRange>, String> {
+    let tree = syntax::parse(language, text)?;
+    let offsets = OffsetMap::new(text);
+    let (start_byte, end_byte) = (offsets.to_byte(start), offsets.to_byte(end.max(start)));
+    let Some(mut node) = tree
+        .root_node()
+        .named_descendant_for_byte_range(start_byte, end_byte)
+    else {
+        return Ok(Vec::new());
+    };
+    let mut ranges: Vec<SelectionRange> = Vec::new();
+    loop {
+        let range = SelectionRange {
+            start: offsets.to_utf16(node.start_byte()),
+            end: offsets.to_utf16(node.end_byte()),
+            kind: node.kind().to_string(),
+        };
+        // Skip nodes identical to the current selection or to the previous step.
+        let grows = (range.start, range.end) != (start, end)
+            && ranges.last().map_or(true, |last| (last.start, last.end) != (range.start, range.end));
+        if grows {
+            ranges.push(range);
+        }
+        match node.parent() {
+            Some(parent) => node = parent,
+            None => break,
+        }
+    }
+    Ok(ranges)
+}
+
+/// The innermost definition of one of `kinds` containing `offset`, as a selection.
+pub fn enclosing_symbol(
+    language: &str,
+    text: &str,
+    offset: usize,
+    kinds: &[SymbolKind],
+) -> Result<Option<SelectionRange>, String> {
+    let tree = syntax::parse(language, text)?;
+    let offsets = OffsetMap::new(text);
+    let byte = offsets.to_byte(offset);
+    let symbol = syntax::symbols(language, &tree, text)
+        .into_iter()
+        .filter(|s| kinds.is_empty() || kinds.contains(&s.kind))
+        .filter(|s| s.range.start_byte <= byte && byte <= s.range.end_byte)
+        .min_by_key(|s| s.range.end_byte - s.range.start_byte);
+    Ok(symbol.map(|s| SelectionRange {
+        start: offsets.to_utf16(s.range.start_byte),
+        end: offsets.to_utf16(s.range.end_byte),
+        kind: s.kind.as_str().to_string(),
+    }))
+}
+
+#[tauri::command]
+pub fn get_fold_ranges(language: String, text: String) -> Result<Vec<FoldRange>, String> {
+    fold_ranges(&language, &text)
+}
+
+#[tauri::command]
+pub fn expand_selection(language: String, text: String, start: usize, end: usize) -> Result<Vec<SelectionRange>, String> {
+    selection_ranges(&language, &text, start, end)
+}
+
+/// Select the function (or class, with `kinds`) around `offset` for "share just this function".
+#[tauri::command]
+pub fn select_enclosing_symbol(
+    language: String,
+    text: String,
+    offset: usize,
+    kinds: Option<Vec<SymbolKind>>,
+) -> Result<Option<SelectionRange>, String> {
+    let kinds = kinds.unwrap_or_else(|| vec![SymbolKind::Function, SymbolKind::Method]);
+    enclosing_symbol(&language, &text, offset, &kinds)
+}
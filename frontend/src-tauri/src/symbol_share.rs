@@ -0,0 +1,258 @@
+//! Share a single function or class as a self-contained snippet, optionally kept in sync with its file.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+use tree_sitter::Node;
+
+use crate::documents::{self, Document, DocumentInfo, DocumentStore};
+use crate::events::{self, Channel};
+use crate::indexer::{split_qualified, ProjectIndex};
+use crate::language;
+use crate::settings::SettingsStore;
+use crate::syntax;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Tracked {
+    path: PathBuf,
+    symbol: String,
+    modified: Option<SystemTime>,
+}
+
+#[derive(Default)]
+pub struct SymbolShares {
+    tracked: Mutex<HashMap<String, Tracked>>,
+    watcher_running: AtomicBool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedSymbol {
+    pub document: DocumentInfo,
+    pub tracking: bool,
+}
+
+/// Carries the whole text, so saves in quick succession only need the last one.
+const SHARED_SYMBOL_UPDATED: Channel = Channel::latest_wins("shared-symbol-updated");
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SharedSymbolUpdated {
+    id: String,
+    text: String,
+}
+
+fn is_import(language: &str, kind: &str) -> bool {
+    matches!(
+        (language, kind),
+        ("rust", "use_declaration" | "extern_crate_declaration")
+            | ("python", "import_statement" | "import_from_statement" | "future_import_statement")
+            | ("javascript" | "typescript", "import_statement")
+            | ("go", "import_declaration")
+            | ("java", "import_declaration")
+            | ("c" | "cpp", "preproc_include")
+    )
+}
+
+/// Comments, attributes and decorators that belong to the definition that follows them.
+fn is_leading_trivia(kind: &str) -> bool {
+    kind.contains("comment") || matches!(kind, "attribute_item" | "decorator" | "annotation" | "marker_annotation")
+}
+
+fn words(text: &str) -> HashSet<&str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+const IMPORT_KEYWORDS: &[&str] = &["use", "import", "from", "as", "pub", "crate", "self", "super", "extern", "type", "static", "include"];
+
+/// An import is kept if any name it brings in is mentioned by the extracted code.
+fn import_is_used(language: &str, import: &str, body_words: &HashSet<&str>) -> bool {
+    if language == "c" || language == "cpp" {
+        return true;
+    }
+    words(import)
+        .into_iter()
+        .filter(|w| !IMPORT_KEYWORDS.contains(w))
+        .any(|w| body_words.contains(&w))
+}
+
+fn find_node<'a>(node: Node<'a>, start: usize, end: usize) -> Option<Node<'a>> {
+    let mut node = node.descendant_for_byte_range(start, end)?;
+    while node.byte_range() != (start..end) {
+        node = node.parent()?;
+    }
+    Some(node)
+}
+
+fn line_start(text: &str, byte: usize) -> usize {
+    text[..byte].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+fn dedent(text: &str) -> String {
+    let indent = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    text.lines()
+        .map(|l| if l.len() >= indent { &l[indent..] } else { l.trim_start() })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Build the snippet text for `symbol` (`name` or `Container::name`) in `path`.
+pub fn extract_symbol(path: &Path, symbol: &str) -> Result<(String, &'static str), String> {
+    let language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(language::language_for_extension)
+        .ok_or_else(|| format!("Unsupported file type: {}", path.display()))?;
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let tree = syntax::parse(language, &text)?;
+
+    let (container, name) = split_qualified(symbol);
+    let found = syntax::symbols(language, &tree, &text)
+        .into_iter()
+        .filter(|s| s.name == name)
+        .find(|s| container.is_none() || s.container.as_deref() == container)
+        .ok_or_else(|| format!("Symbol {} not found in {}", symbol, path.display()))?;
+
+    let root = tree.root_node();
+    let node = find_node(root, found.range.start_byte, found.range.end_byte)
+        .ok_or_else(|| format!("Symbol {} not found in {}", symbol, path.display()))?;
+
+    // Walk back over doc comments and attributes that sit directly above the definition.
+    let mut start = node.start_byte();
+    let mut current = node;
+    while let Some(prev) = current.prev_named_sibling() {
+        let gap = current.start_position().row.saturating_sub(prev.end_position().row);
+        if !is_leading_trivia(prev.kind()) || gap > 1 {
+            break;
+        }
+        start = prev.start_byte();
+        current = prev;
+    }
+    let body = dedent(&text[line_start(&text, start)..node.end_byte()]);
+
+    let body_words = words(&body);
+    let mut cursor = root.walk();
+    let imports: Vec<&str> = root
+        .named_children(&mut cursor)
+        .filter(|n| is_import(language, n.kind()))
+        .map(|n| &text[n.byte_range()])
+        .filter(|import| import_is_used(language, import, &body_words))
+        .collect();
+
+    let mut snippet = String::new();
+    if !imports.is_empty() {
+        snippet.push_str(&imports.join("\n"));
+        snippet.push_str("\n\n");
+    }
+    snippet.push_str(&body);
+    snippet.push('\n');
+    Ok((snippet, language))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn spawn_watcher(app: AppHandle, shares: Arc<SymbolShares>) {
+    if shares.watcher_running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut changed = Vec::new();
+        {
+            let mut tracked = shares.tracked.lock().unwrap();
+            if tracked.is_empty() {
+                shares.watcher_running.store(false, Ordering::SeqCst);
+                return;
+            }
+            for (id, entry) in tracked.iter_mut() {
+                let now = modified(&entry.path);
+                if now != entry.modified {
+                    entry.modified = now;
+                    changed.push((id.clone(), entry.path.clone(), entry.symbol.clone()));
+                }
+            }
+        }
+        let store = app.state::<DocumentStore>();
+        for (id, path, symbol) in changed {
+            // A failed extraction (e.g. the file is mid-save or the symbol was renamed) keeps the last good text.
+            let text = match extract_symbol(&path, &symbol) {
+                Ok((text, _)) => text,
+                Err(e) => {
+                    log::warn!("Not updating shared symbol {}: {}", symbol, e);
+                    continue;
+                }
+            };
+            // Frozen documents keep their text; the next change to the file is picked up after unfreezing.
+            let updated = store.with(&id, |doc| doc.text != text && doc.set_text(text.clone(), None).is_ok());
+            match updated {
+                Ok(true) => {
+                    events::emit_keyed(&app, &SHARED_SYMBOL_UPDATED, &id, SharedSymbolUpdated { id: id.clone(), text }).ok();
+                }
+                Ok(false) => {}
+                // The document was closed; stop tracking it.
+                Err(_) => {
+                    shares.tracked.lock().unwrap().remove(&id);
+                }
+            }
+        }
+    });
+}
+
+/// Extract a function or class (with its imports and doc comment) into a new shareable document.
+///
+/// With `track`, the document is refreshed whenever the source file changes and
+/// `shared-symbol-updated` is emitted.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn share_symbol(
+    app: AppHandle,
+    index: State<'_, Arc<ProjectIndex>>,
+    store: State<'_, DocumentStore>,
+    settings: State<'_, SettingsStore>,
+    shares: State<'_, Arc<SymbolShares>>,
+    file: String,
+    symbol: String,
+    track: Option<bool>,
+) -> Result<SharedSymbol, String> {
+    let path = index.resolve(&file);
+    let (text, language) = extract_symbol(&path, &symbol)?;
+    let info = store.insert(Document::new(None, text));
+    documents::apply_language(&app, &store, &settings, &info.id, language)?;
+    let document = store.with(&info.id, |doc| doc.info(&info.id))?;
+
+    let tracking = track.unwrap_or(false);
+    if tracking {
+        shares.tracked.lock().unwrap().insert(
+            info.id.clone(),
+            Tracked {
+                modified: modified(&path),
+                path,
+                symbol,
+            },
+        );
+        spawn_watcher(app.clone(), Arc::clone(&shares));
+    }
+    Ok(SharedSymbol { document, tracking })
+}
+
+#[tauri::command]
+pub fn stop_tracking_symbol(shares: State<'_, Arc<SymbolShares>>, id: String) -> bool {
+    shares.tracked.lock().unwrap().remove(&id).is_some()
+}
@@ -0,0 +1,430 @@
+//! Tree-sitter parsing shared by the indexer and the structural editing commands.
+
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// Grammar for an editor language id, if one is bundled.
+pub fn grammar(language: &str) -> Option<Language> {
+    let grammar = match language {
+        "rust" => tree_sitter_rust::LANGUAGE,
+        "python" => tree_sitter_python::LANGUAGE,
+        "javascript" => tree_sitter_javascript::LANGUAGE,
+        "typescript" => tree_sitter_typescript::LANGUAGE_TSX,
+        "go" => tree_sitter_go::LANGUAGE,
+        "java" => tree_sitter_java::LANGUAGE,
+        "c" => tree_sitter_c::LANGUAGE,
+        "cpp" => tree_sitter_cpp::LANGUAGE,
+        _ => return None,
+    };
+    Some(grammar.into())
+}
+
+pub fn parse(language: &str, text: &str) -> Result<Tree, String> {
+    let grammar = grammar(language).ok_or_else(|| format!("No syntax support for {}", language))?;
+    let mut parser = Parser::new();
+    parser
+        .set_language(&grammar)
+        .map_err(|e| format!("Failed to load {} grammar: {}", language, e))?;
+    parser
+        .parse(text, None)
+        .ok_or_else(|| format!("Failed to parse {} source", language))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Class,
+    Struct,
+    Enum,
+    Interface,
+    Module,
+    Type,
+    Constant,
+    Macro,
+}
+
+impl SymbolKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Method => "method",
+            SymbolKind::Class => "class",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Module => "module",
+            SymbolKind::Type => "type",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Macro => "macro",
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextRange {
+    /// Zero-based line and column (in bytes) of the start and end positions.
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl TextRange {
+    pub fn of(node: Node) -> Self {
+        Self {
+            start_line: node.start_position().row,
+            start_column: node.start_position().column,
+            end_line: node.end_position().row,
+            end_column: node.end_position().column,
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole definition, including its body.
+    pub range: TextRange,
+    /// Just the identifier, for placing the cursor on a jump.
+    pub name_range: TextRange,
+    /// Name of the enclosing class/impl/module, if any.
+    pub container: Option<String>,
+}
+
+fn kind_of(language: &str, node: Node) -> Option<SymbolKind> {
+    use SymbolKind::*;
+    let kind = match (language, node.kind()) {
+        ("rust", "function_item" | "function_signature_item") => Function,
+        ("rust", "struct_item" | "union_item") => Struct,
+        ("rust", "enum_item") => Enum,
+        ("rust", "trait_item") => Interface,
+        ("rust", "mod_item") => Module,
+        ("rust", "type_item") => Type,
+        ("rust", "const_item" | "static_item") => Constant,
+        ("rust", "macro_definition") => Macro,
+        ("python", "function_definition") => Function,
+        ("python", "class_definition") => Class,
+        ("javascript" | "typescript", "function_declaration" | "generator_function_declaration") => Function,
+        ("javascript" | "typescript", "class_declaration" | "abstract_class_declaration") => Class,
+        ("javascript" | "typescript", "method_definition") => Method,
+        ("javascript" | "typescript", "interface_declaration") => Interface,
+        ("javascript" | "typescript", "type_alias_declaration") => Type,
+        ("javascript" | "typescript", "enum_declaration") => Enum,
+        ("javascript" | "typescript", "variable_declarator") => {
+            // `const handler = () => {}` is how most modern JS defines functions.
+            let value = node.child_by_field_name("value")?;
+            match value.kind() {
+                "arrow_function" | "function_expression" | "function" => Function,
+                _ => return None,
+            }
+        }
+        ("go", "function_declaration") => Function,
+        ("go", "method_declaration") => Method,
+        ("go", "type_spec") => Type,
+        ("java", "class_declaration" | "record_declaration") => Class,
+        ("java", "interface_declaration") => Interface,
+        ("java", "enum_declaration") => Enum,
+        ("java", "method_declaration" | "constructor_declaration") => Method,
+        ("c" | "cpp", "function_definition") => Function,
+        ("c" | "cpp", "struct_specifier" | "union_specifier") if node.child_by_field_name("body").is_some() => Struct,
+        ("c" | "cpp", "enum_specifier") if node.child_by_field_name("body").is_some() => Enum,
+        ("cpp", "class_specifier") if node.child_by_field_name("body").is_some() => Class,
+        ("cpp", "namespace_definition") => Module,
+        _ => return None,
+    };
+    // Python functions nested directly in a class body are methods.
+    if kind == Function && language == "python" {
+        let in_class = node
+            .parent()
+            .and_then(|p| p.parent())
+            .is_some_and(|p| p.kind() == "class_definition");
+        if in_class {
+            return Some(Method);
+        }
+    }
+    Some(kind)
+}
+
+fn name_node<'a>(language: &str, node: Node<'a>) -> Option<Node<'a>> {
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(name);
+    }
+    match (language, node.kind()) {
+        ("rust", "impl_item") => node.child_by_field_name("type"),
+        ("c" | "cpp", "function_definition") => {
+            // Follow `declarator` fields through pointers and function declarators to the identifier.
+            let mut current = node.child_by_field_name("declarator")?;
+            while let Some(inner) = current.child_by_field_name("declarator") {
+                current = inner;
+            }
+            Some(current)
+        }
+        _ => None,
+    }
+}
+
+/// Name of the container that scopes nested definitions (impl blocks have no symbol of their own).
+fn container_name(language: &str, node: Node, text: &str) -> Option<String> {
+    let is_container = kind_of(language, node).is_some_and(|k| {
+        matches!(k, SymbolKind::Class | SymbolKind::Struct | SymbolKind::Interface | SymbolKind::Module | SymbolKind::Enum)
+    }) || (language == "rust" && node.kind() == "impl_item");
+    if !is_container {
+        return None;
+    }
+    name_node(language, node).map(|n| text[n.byte_range()].to_string())
+}
+
+/// All definitions in a parsed file, in document order.
+pub fn symbols(language: &str, tree: &Tree, text: &str) -> Vec<Symbol> {
+    let mut out = Vec::new();
+    collect_symbols(language, tree.root_node(), text, None, &mut out);
+    out
+}
+
+fn collect_symbols(language: &str, node: Node, text: &str, container: Option<&str>, out: &mut Vec<Symbol>) {
+    if let Some(kind) = kind_of(language, node) {
+        if let Some(name) = name_node(language, node) {
+            let kind = match (kind, container) {
+                (SymbolKind::Function, Some(_)) if language == "rust" || language == "cpp" => SymbolKind::Method,
+                _ => kind,
+            };
+            // For `const f = () => {}` the whole declaration reads better than just the declarator.
+            let range_node = match node.kind() {
+                "variable_declarator" => node.parent().unwrap_or(node),
+                _ => node,
+            };
+            out.push(Symbol {
+                name: text[name.byte_range()].to_string(),
+                kind,
+                range: TextRange::of(range_node),
+                name_range: TextRange::of(name),
+                container: container.map(str::to_string),
+            });
+        }
+    }
+    let own = container_name(language, node, text);
+    let container = own.as_deref().or(container);
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_symbols(language, child, text, container, out);
+    }
+}
+
+/// Converts between byte offsets (tree-sitter) and UTF-16 offsets (the webview editor).
+pub struct OffsetMap<'a> {
+    text: &'a str,
+}
+
+impl<'a> OffsetMap<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text }
+    }
+
+    pub fn to_utf16(&self, byte: usize) -> usize {
+        let byte = byte.min(self.text.len());
+        self.text[..byte].encode_utf16().count()
+    }
+
+    /// Byte offset of a UTF-16 position, clamped to the text and rounded down to a char boundary.
+    pub fn to_byte(&self, utf16: usize) -> usize {
+        let mut units = 0;
+        for (byte, c) in self.text.char_indices() {
+            if units >= utf16 {
+                return byte;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallSite {
+    /// Innermost enclosing function or method; `None` for top-level code.
+    pub caller: Option<String>,
+    pub caller_container: Option<String>,
+    /// Called name as written, without receiver or path (`foo.bar()` -> `bar`).
+    pub callee: String,
+    pub range: TextRange,
+}
+
+/// The identifier naming what a call expression invokes, per language.
+fn callee_node<'a>(language: &str, node: Node<'a>) -> Option<Node<'a>> {
+    let target = match (language, node.kind()) {
+        ("rust", "call_expression") => node.child_by_field_name("function")?,
+        ("rust", "macro_invocation") => node.child_by_field_name("macro")?,
+        ("python", "call") => node.child_by_field_name("function")?,
+        ("javascript" | "typescript", "call_expression") => node.child_by_field_name("function")?,
+        ("javascript" | "typescript", "new_expression") => node.child_by_field_name("constructor")?,
+        ("go", "call_expression") => node.child_by_field_name("function")?,
+        ("java", "method_invocation") => node.child_by_field_name("name")?,
+        ("java", "object_creation_expression") => node.child_by_field_name("type")?,
+        ("c" | "cpp", "call_expression") => node.child_by_field_name("function")?,
+        _ => return None,
+    };
+    // Drill through receivers and paths to the final name segment.
+    let mut target = target;
+    loop {
+        let next = match target.kind() {
+            "scoped_identifier" | "qualified_identifier" | "generic_type" | "scoped_type_identifier" => {
+                target.child_by_field_name("name")
+            }
+            "field_expression" | "selector_expression" => target.child_by_field_name("field"),
+            "member_expression" => target.child_by_field_name("property"),
+            "attribute" => target.child_by_field_name("attribute"),
+            "generic_function" => target.child_by_field_name("function"),
+            _ => None,
+        };
+        match next {
+            Some(next) => target = next,
+            None => return Some(target),
+        }
+    }
+}
+
+/// Every call in a parsed file, attributed to the function it occurs in.
+pub fn calls(language: &str, tree: &Tree, text: &str) -> Vec<CallSite> {
+    let mut out = Vec::new();
+    collect_calls(language, tree.root_node(), text, None, None, &mut out);
+    out
+}
+
+fn collect_calls(
+    language: &str,
+    node: Node,
+    text: &str,
+    container: Option<&str>,
+    caller: Option<&str>,
+    out: &mut Vec<CallSite>,
+) {
+    if let Some(target) = callee_node(language, node) {
+        let callee = &text[target.byte_range()];
+        // Calls through computed expressions (`fns[i]()`) have no stable name to resolve.
+        if callee.chars().all(|c| c.is_alphanumeric() || c == '_') && !callee.is_empty() {
+            out.push(CallSite {
+                caller: caller.map(str::to_string),
+                caller_container: container.map(str::to_string),
+                callee: callee.to_string(),
+                range: TextRange::of(node),
+            });
+        }
+    }
+    let own_caller = match kind_of(language, node) {
+        Some(SymbolKind::Function | SymbolKind::Method) => name_node(language, node).map(|n| &text[n.byte_range()]),
+        _ => None,
+    };
+    let own_container = container_name(language, node, text);
+    let container = own_container.as_deref().or(container);
+    let caller = own_caller.or(caller);
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_calls(language, child, text, container, caller, out);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightClass {
+    Keyword,
+    String,
+    Number,
+    Comment,
+    Type,
+    Function,
+}
+
+/// A highlighted stretch of text. Offsets are UTF-16 code units, ready for a browser.
+#[derive(Clone, Serialize)]
+pub struct HighlightSpan {
+    pub start: usize,
+    pub end: usize,
+    pub class: HighlightClass,
+}
+
+fn highlight_class(language: &str, node: Node) -> Option<HighlightClass> {
+    let kind = node.kind();
+    if !node.is_named() {
+        // Anonymous word tokens (`fn`, `return`, `if`) are the grammar's keywords.
+        let word = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphabetic() || c == '_');
+        return word.then_some(HighlightClass::Keyword);
+    }
+    if kind.contains("comment") {
+        return Some(HighlightClass::Comment);
+    }
+    if kind.contains("string") || kind.contains("char_literal") || kind == "rune_literal" {
+        return Some(HighlightClass::String);
+    }
+    if kind.contains("integer") || kind.contains("float") || kind.starts_with("number") {
+        return Some(HighlightClass::Number);
+    }
+    if matches!(kind, "type_identifier" | "primitive_type" | "predefined_type" | "sized_type_specifier") {
+        return Some(HighlightClass::Type);
+    }
+    let named = node
+        .parent()
+        .filter(|p| matches!(kind_of(language, *p), Some(SymbolKind::Function | SymbolKind::Method)))
+        .and_then(|p| name_node(language, p))
+        .is_some_and(|n| n.id() == node.id());
+    named.then_some(HighlightClass::Function)
+}
+
+/// Highlight spans for a parsed file, in document order and non-overlapping.
+pub fn highlight(language: &str, tree: &Tree, text: &str) -> Vec<HighlightSpan> {
+    // Spans come out in order, so UTF-16 offsets are counted incrementally rather than from the start.
+    let (mut byte, mut units) = (0, 0);
+    let mut to_utf16 = |to: usize| {
+        units += text[byte..to].encode_utf16().count();
+        byte = to;
+        units
+    };
+    let mut out = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if let Some(class) = highlight_class(language, node) {
+            // A string or comment is one span, whatever escapes or interpolations it contains.
+            let start = to_utf16(node.start_byte());
+            out.push(HighlightSpan {
+                start,
+                end: to_utf16(node.end_byte()),
+                class,
+            });
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
+
+/// Highlighted byte ranges of the nodes overlapping `range`, in document order. Cheaper than
+/// [`highlight`] when only a few lines changed.
+pub fn highlight_bytes(language: &str, tree: &Tree, range: Range<usize>) -> Vec<(Range<usize>, HighlightClass)> {
+    let mut out = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.end_byte() <= range.start || node.start_byte() >= range.end {
+            continue;
+        }
+        if let Some(class) = highlight_class(language, node) {
+            out.push((node.start_byte()..node.end_byte(), class));
+            continue;
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    out
+}
@@ -0,0 +1,562 @@
+//! TextMate/VS Code compatible snippet templates: `$1`, `${1:default}`, `${1|a,b|}`,
+//! `$VAR`, `${VAR:default}` and `${VAR/regex/format/flags}` transforms.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{Datelike, Local, Timelike};
+use rand::RngCore;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::snippets::SnippetLibrary;
+
+enum Node {
+    Text(String),
+    TabStop {
+        index: u32,
+        placeholder: Vec<Node>,
+        choices: Option<Vec<String>>,
+        transform: Option<Transform>,
+    },
+    Variable {
+        name: String,
+        default: Vec<Node>,
+        transform: Option<Transform>,
+    },
+}
+
+struct Transform {
+    regex: Regex,
+    format: Vec<FormatItem>,
+    global: bool,
+}
+
+enum CaseOp {
+    Upcase,
+    Downcase,
+    Capitalize,
+    CamelCase,
+    PascalCase,
+}
+
+enum FormatItem {
+    Text(String),
+    Group(usize),
+    Case(usize, CaseOp),
+    Conditional { group: usize, if_set: String, if_unset: String },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        if !self.peek().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') {
+            return None;
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Read raw text up to an unescaped `end`, keeping escapes other than `\end` intact.
+    fn until(&mut self, end: char) -> Option<String> {
+        let mut out = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == '\\' && self.peek() == Some(end) {
+                out.push(end);
+                self.pos += 1;
+            } else if c == end {
+                return Some(out);
+            } else {
+                out.push(c);
+            }
+        }
+        None
+    }
+
+    fn nodes(&mut self, in_braces: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if in_braces && c == '}' {
+                break;
+            }
+            if c == '\\' {
+                self.pos += 1;
+                match self.peek() {
+                    Some(next @ ('$' | '\\' | '}')) => {
+                        text.push(next);
+                        self.pos += 1;
+                    }
+                    _ => text.push('\\'),
+                }
+                continue;
+            }
+            if c == '$' {
+                let start = self.pos;
+                self.pos += 1;
+                if let Some(node) = self.dollar() {
+                    if !text.is_empty() {
+                        nodes.push(Node::Text(std::mem::take(&mut text)));
+                    }
+                    nodes.push(node);
+                    continue;
+                }
+                self.pos = start + 1;
+                text.push('$');
+                continue;
+            }
+            text.push(c);
+            self.pos += 1;
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        nodes
+    }
+
+    // Called just after a `$`; on failure the caller rewinds and treats `$` as text.
+    fn dollar(&mut self) -> Option<Node> {
+        if let Some(index) = self.number() {
+            return Some(Node::TabStop { index, placeholder: Vec::new(), choices: None, transform: None });
+        }
+        if let Some(name) = self.name() {
+            return Some(Node::Variable { name, default: Vec::new(), transform: None });
+        }
+        if !self.eat('{') {
+            return None;
+        }
+        if let Some(index) = self.number() {
+            let mut placeholder = Vec::new();
+            let mut choices = None;
+            let mut transform = None;
+            if self.eat(':') {
+                placeholder = self.nodes(true);
+            } else if self.eat('|') {
+                let raw = self.until('|')?;
+                choices = Some(raw.split(',').map(|c| c.replace("\\,", ",")).collect());
+            } else if self.eat('/') {
+                transform = Some(self.transform()?);
+            }
+            return self.eat('}').then_some(Node::TabStop { index, placeholder, choices, transform });
+        }
+        let name = self.name()?;
+        let mut default = Vec::new();
+        let mut transform = None;
+        if self.eat(':') {
+            default = self.nodes(true);
+        } else if self.eat('/') {
+            transform = Some(self.transform()?);
+        }
+        self.eat('}').then_some(Node::Variable { name, default, transform })
+    }
+
+    // Parses `regex/format/flags` after the first `/`, stopping before the closing `}`.
+    fn transform(&mut self) -> Option<Transform> {
+        let pattern = self.until('/')?;
+        let format = self.until('/')?;
+        let mut flags = String::new();
+        while let Some(c) = self.peek().filter(|c| *c != '}') {
+            flags.push(c);
+            self.pos += 1;
+        }
+        let prefix = if flags.contains('i') { "(?i)" } else { "" };
+        let regex = Regex::new(&format!("{}{}", prefix, pattern)).ok()?;
+        Some(Transform { regex, format: parse_format(&format), global: flags.contains('g') })
+    }
+}
+
+fn parse_format(format: &str) -> Vec<FormatItem> {
+    let mut parser = Parser { chars: format.chars().collect(), pos: 0 };
+    let mut items = Vec::new();
+    let mut text = String::new();
+    while let Some(c) = parser.peek() {
+        parser.pos += 1;
+        if c == '\\' {
+            match parser.peek() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(next) => text.push(next),
+                None => text.push('\\'),
+            }
+            parser.pos += 1;
+            continue;
+        }
+        if c != '$' {
+            text.push(c);
+            continue;
+        }
+        let start = parser.pos;
+        let item = if let Some(group) = parser.number() {
+            Some(FormatItem::Group(group as usize))
+        } else if parser.eat('{') {
+            parser.number().and_then(|group| {
+                let group = group as usize;
+                if parser.eat('}') {
+                    return Some(FormatItem::Group(group));
+                }
+                if !parser.eat(':') {
+                    return None;
+                }
+                let item = if parser.eat('/') {
+                    let op = match parser.name()?.as_str() {
+                        "upcase" => CaseOp::Upcase,
+                        "downcase" => CaseOp::Downcase,
+                        "capitalize" => CaseOp::Capitalize,
+                        "camelcase" => CaseOp::CamelCase,
+                        "pascalcase" => CaseOp::PascalCase,
+                        _ => return None,
+                    };
+                    FormatItem::Case(group, op)
+                } else if parser.eat('+') {
+                    FormatItem::Conditional { group, if_set: parser.until('}')?, if_unset: String::new() }
+                } else if parser.eat('?') {
+                    let if_set = parser.until(':')?;
+                    FormatItem::Conditional { group, if_set, if_unset: parser.until('}')? }
+                } else {
+                    parser.eat('-');
+                    FormatItem::Conditional { group, if_set: String::new(), if_unset: parser.until('}')? }
+                };
+                // `until` already consumed the closing brace for conditionals.
+                if matches!(item, FormatItem::Case(..)) && !parser.eat('}') {
+                    return None;
+                }
+                Some(item)
+            })
+        } else {
+            None
+        };
+        match item {
+            Some(item) => {
+                if !text.is_empty() {
+                    items.push(FormatItem::Text(std::mem::take(&mut text)));
+                }
+                items.push(item);
+            }
+            None => {
+                parser.pos = start;
+                text.push('$');
+            }
+        }
+    }
+    if !text.is_empty() {
+        items.push(FormatItem::Text(text));
+    }
+    items
+}
+
+fn apply_case(value: &str, op: &CaseOp) -> String {
+    let words = || value.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty());
+    let capitalize = |w: &str| {
+        let mut chars = w.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect::<String>())
+            .unwrap_or_default()
+    };
+    match op {
+        CaseOp::Upcase => value.to_uppercase(),
+        CaseOp::Downcase => value.to_lowercase(),
+        CaseOp::Capitalize => {
+            let mut chars = value.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+        CaseOp::PascalCase => words().map(capitalize).collect(),
+        CaseOp::CamelCase => words()
+            .enumerate()
+            .map(|(i, w)| if i == 0 { w.to_lowercase() } else { capitalize(w) })
+            .collect(),
+    }
+}
+
+impl Transform {
+    fn format_captures(&self, caps: &Captures) -> String {
+        let group = |n: usize| caps.get(n).map(|m| m.as_str()).unwrap_or_default();
+        let mut out = String::new();
+        for item in &self.format {
+            match item {
+                FormatItem::Text(text) => out.push_str(text),
+                FormatItem::Group(n) => out.push_str(group(*n)),
+                FormatItem::Case(n, op) => out.push_str(&apply_case(group(*n), op)),
+                FormatItem::Conditional { group: n, if_set, if_unset } => {
+                    out.push_str(if group(*n).is_empty() { if_unset } else { if_set })
+                }
+            }
+        }
+        out
+    }
+
+    fn apply(&self, input: &str) -> String {
+        if self.global {
+            self.regex
+                .replace_all(input, |caps: &Captures| self.format_captures(caps))
+                .into_owned()
+        } else {
+            self.regex
+                .replace(input, |caps: &Captures| self.format_captures(caps))
+                .into_owned()
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExpandContext {
+    pub file_path: Option<String>,
+    pub language: Option<String>,
+    pub selected_text: Option<String>,
+    pub current_line: Option<String>,
+    pub current_word: Option<String>,
+    /// Zero-based line the snippet is inserted on.
+    pub line_index: Option<u32>,
+    /// When absent the system clipboard is read on demand.
+    pub clipboard: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TabStopRange {
+    pub index: u32,
+    pub start: usize,
+    pub end: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<String>>,
+}
+
+/// Expanded text plus tab-stop ranges. Offsets are UTF-16 code units so the editor can use them directly.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Expansion {
+    pub text: String,
+    pub tab_stops: Vec<TabStopRange>,
+}
+
+fn comment_tokens(language: Option<&str>) -> (&'static str, &'static str, &'static str) {
+    match language.unwrap_or_default() {
+        "python" | "ruby" | "shell" | "bash" | "yaml" | "toml" | "perl" | "r" => ("#", "", ""),
+        "html" | "xml" | "markdown" => ("", "<!--", "-->"),
+        "sql" | "lua" | "haskell" => ("--", "", ""),
+        _ => ("//", "/*", "*/"),
+    }
+}
+
+#[cfg(desktop)]
+fn system_clipboard() -> Option<String> {
+    arboard::Clipboard::new().and_then(|mut c| c.get_text()).ok()
+}
+
+/// Mobile webviews pass the clipboard in the context; there is no system clipboard API here.
+#[cfg(mobile)]
+fn system_clipboard() -> Option<String> {
+    None
+}
+
+struct Expander<'a> {
+    context: &'a ExpandContext,
+    out: String,
+    utf16_len: usize,
+    tab_stops: Vec<TabStopRange>,
+    mirrors: HashMap<u32, String>,
+    clipboard: Option<String>,
+}
+
+impl Expander<'_> {
+    fn push(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.utf16_len += text.encode_utf16().count();
+    }
+
+    fn variable(&mut self, name: &str) -> Option<String> {
+        let ctx = self.context;
+        let path = ctx.file_path.as_deref().map(Path::new);
+        let now = Local::now();
+        let (line_comment, block_start, block_end) = comment_tokens(ctx.language.as_deref());
+        let value = match name {
+            "TM_SELECTED_TEXT" => ctx.selected_text.clone()?,
+            "TM_CURRENT_LINE" => ctx.current_line.clone()?,
+            "TM_CURRENT_WORD" => ctx.current_word.clone()?,
+            "TM_LINE_INDEX" => ctx.line_index?.to_string(),
+            "TM_LINE_NUMBER" => (ctx.line_index? + 1).to_string(),
+            "TM_FILENAME" => path?.file_name()?.to_string_lossy().into_owned(),
+            "TM_FILENAME_BASE" => path?.file_stem()?.to_string_lossy().into_owned(),
+            "TM_DIRECTORY" => path?.parent()?.to_string_lossy().into_owned(),
+            "TM_FILEPATH" => ctx.file_path.clone()?,
+            "CLIPBOARD" => {
+                if self.clipboard.is_none() {
+                    self.clipboard = ctx
+                        .clipboard
+                        .clone()
+                        .or_else(system_clipboard);
+                }
+                self.clipboard.clone()?
+            }
+            "CURRENT_YEAR" => now.year().to_string(),
+            "CURRENT_YEAR_SHORT" => format!("{:02}", now.year() % 100),
+            "CURRENT_MONTH" => format!("{:02}", now.month()),
+            "CURRENT_MONTH_NAME" => now.format("%B").to_string(),
+            "CURRENT_MONTH_NAME_SHORT" => now.format("%b").to_string(),
+            "CURRENT_DATE" => format!("{:02}", now.day()),
+            "CURRENT_DAY_NAME" => now.format("%A").to_string(),
+            "CURRENT_DAY_NAME_SHORT" => now.format("%a").to_string(),
+            "CURRENT_HOUR" => format!("{:02}", now.hour()),
+            "CURRENT_MINUTE" => format!("{:02}", now.minute()),
+            "CURRENT_SECOND" => format!("{:02}", now.second()),
+            "CURRENT_SECONDS_UNIX" => now.timestamp().to_string(),
+            "RANDOM" => format!("{:06}", rand::rngs::OsRng.next_u32() % 1_000_000),
+            "RANDOM_HEX" => format!("{:06x}", rand::rngs::OsRng.next_u32() & 0xff_ffff),
+            "UUID" => {
+                let mut b = [0u8; 16];
+                rand::rngs::OsRng.fill_bytes(&mut b);
+                b[6] = (b[6] & 0x0f) | 0x40;
+                b[8] = (b[8] & 0x3f) | 0x80;
+                let hex: String = b.iter().map(|x| format!("{:02x}", x)).collect();
+                format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+            }
+            "LINE_COMMENT" => line_comment.to_string(),
+            "BLOCK_COMMENT_START" => block_start.to_string(),
+            "BLOCK_COMMENT_END" => block_end.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Expand nodes into a detached buffer (used for transforms, which rewrite the whole value).
+    fn render(&mut self, nodes: &[Node]) -> String {
+        let (out, len, stops) = (std::mem::take(&mut self.out), self.utf16_len, self.tab_stops.len());
+        self.expand(nodes);
+        self.tab_stops.truncate(stops);
+        self.utf16_len = len;
+        std::mem::replace(&mut self.out, out)
+    }
+
+    fn expand(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push(text),
+                Node::TabStop { index, placeholder, choices, transform } => {
+                    let start = self.utf16_len;
+                    match (transform, choices) {
+                        (Some(transform), _) => {
+                            let value = self.mirrors.get(index).cloned().unwrap_or_default();
+                            self.push(&transform.apply(&value));
+                        }
+                        (None, Some(choices)) => {
+                            let first = choices.first().cloned().unwrap_or_default();
+                            self.mirrors.entry(*index).or_insert_with(|| first.clone());
+                            self.push(&first);
+                        }
+                        (None, None) if placeholder.is_empty() => {
+                            // A bare `$1` after `${1:foo}` mirrors the earlier value.
+                            let mirror = self.mirrors.get(index).cloned().unwrap_or_default();
+                            self.push(&mirror);
+                        }
+                        (None, None) => {
+                            let value = self.render(placeholder);
+                            self.mirrors.entry(*index).or_insert_with(|| value.clone());
+                            self.expand(placeholder);
+                        }
+                    }
+                    self.tab_stops.push(TabStopRange {
+                        index: *index,
+                        start,
+                        end: self.utf16_len,
+                        choices: choices.clone(),
+                    });
+                }
+                Node::Variable { name, default, transform } => {
+                    let value = self.variable(name);
+                    match (value, transform) {
+                        (Some(value), Some(transform)) => self.push(&transform.apply(&value)),
+                        (Some(value), None) => self.push(&value),
+                        (None, Some(transform)) => {
+                            let fallback = self.render(default);
+                            self.push(&transform.apply(&fallback));
+                        }
+                        (None, None) if default.is_empty() => self.push(name),
+                        (None, None) => self.expand(default),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Expand a template, evaluating variables against `context`.
+pub fn expand(template: &str, context: &ExpandContext) -> Expansion {
+    let mut parser = Parser { chars: template.chars().collect(), pos: 0 };
+    let nodes = parser.nodes(false);
+    let mut expander = Expander {
+        context,
+        out: String::new(),
+        utf16_len: 0,
+        tab_stops: Vec::new(),
+        mirrors: HashMap::new(),
+        clipboard: None,
+    };
+    expander.expand(&nodes);
+
+    let mut tab_stops = expander.tab_stops;
+    // Visit order: $1, $2, ... and the final cursor $0 last.
+    tab_stops.sort_by_key(|t| (t.index == 0, t.index, t.start));
+    if !tab_stops.iter().any(|t| t.index == 0) {
+        let end = expander.utf16_len;
+        tab_stops.push(TabStopRange { index: 0, start: end, end, choices: None });
+    }
+    Expansion { text: expander.out, tab_stops }
+}
+
+#[tauri::command]
+pub fn expand_template(template: String, context: Option<ExpandContext>) -> Expansion {
+    expand(&template, &context.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn expand_snippet(
+    app: AppHandle,
+    library: State<'_, SnippetLibrary>,
+    id: String,
+    context: Option<ExpandContext>,
+) -> Result<Expansion, String> {
+    let snippet = library
+        .all(&app)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| format!("Snippet {} not found", id))?;
+    let mut context = context.unwrap_or_default();
+    if context.language.is_none() {
+        context.language = snippet.language.clone();
+    }
+    Ok(expand(&snippet.body, &context))
+}
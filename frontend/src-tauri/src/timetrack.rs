@@ -0,0 +1,421 @@
+//! Time spent in hosted sessions, split into active and idle, for the whole session and for each
+//! participant, so pairing time can be billed.
+//!
+//! Time counts as active for `IDLE_AFTER` after each sign of activity: joining, a message routed
+//! from the participant (signals, votes, scratchpad edits, pen strokes), or `note_activity`, which
+//! the frontend calls while the local user types. The session itself is active while anyone is.
+//! Time spent paused is kept apart from both. When the session ends its totals are appended to the
+//! session history as a `time` record, which `get_time_report` and `export_time_report` read back.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::lifecycle::HostState;
+use crate::session::{self, Participant, Role, SessionRecord};
+use crate::storage::{self, new_id};
+use crate::webhooks;
+
+/// Seconds without activity after which time counts as idle.
+const IDLE_AFTER: u64 = 5 * 60;
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeTotals {
+    pub active_secs: u64,
+    pub idle_secs: u64,
+    pub paused_secs: u64,
+}
+
+impl TimeTotals {
+    fn add(&mut self, other: &TimeTotals) {
+        self.active_secs += other.active_secs;
+        self.idle_secs += other.idle_secs;
+        self.paused_secs += other.paused_secs;
+    }
+}
+
+/// Running totals, brought up to date whenever something happens.
+#[derive(Clone, Copy)]
+struct Clock {
+    totals: TimeTotals,
+    /// Up to when `totals` accounts for.
+    mark: u64,
+    last_active: u64,
+}
+
+impl Clock {
+    fn new(now: u64) -> Self {
+        Self {
+            totals: TimeTotals::default(),
+            mark: now,
+            last_active: now,
+        }
+    }
+
+    /// Account for the time since the last mark.
+    fn advance(&mut self, now: u64, paused: bool) {
+        let span = now.saturating_sub(self.mark);
+        if paused {
+            self.totals.paused_secs += span;
+        } else {
+            let active_until = now.min(self.last_active + IDLE_AFTER);
+            let active = active_until.saturating_sub(self.mark).min(span);
+            self.totals.active_secs += active;
+            self.totals.idle_secs += span - active;
+        }
+        self.mark = self.mark.max(now);
+    }
+
+    fn active(&mut self, now: u64, paused: bool) {
+        self.advance(now, paused);
+        self.last_active = now;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantTime {
+    pub id: String,
+    pub name: String,
+    pub role: Role,
+    pub joined_at: u64,
+    pub left_at: u64,
+    #[serde(flatten)]
+    pub totals: TimeTotals,
+}
+
+/// One hosted session's time, stored in the session history when it ends.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTime {
+    pub id: String,
+    /// Seconds since the Unix epoch.
+    pub started_at: u64,
+    pub ended_at: u64,
+    #[serde(flatten)]
+    pub totals: TimeTotals,
+    /// A participant who left and came back has an entry for each visit.
+    pub participants: Vec<ParticipantTime>,
+    /// Still running; `endedAt` is when the report was made.
+    #[serde(default)]
+    pub ongoing: bool,
+}
+
+struct Present {
+    participant: Participant,
+    joined_at: u64,
+    clock: Clock,
+}
+
+struct Tracked {
+    id: String,
+    started_at: u64,
+    paused: bool,
+    clock: Clock,
+    present: HashMap<String, Present>,
+    left: Vec<ParticipantTime>,
+}
+
+impl Tracked {
+    fn advance(&mut self, now: u64) {
+        self.clock.advance(now, self.paused);
+        for present in self.present.values_mut() {
+            present.clock.advance(now, self.paused);
+        }
+    }
+
+    fn leave(&mut self, id: &str, now: u64) {
+        let Some(mut present) = self.present.remove(id) else {
+            return;
+        };
+        present.clock.advance(now, self.paused);
+        self.left.push(ParticipantTime {
+            id: present.participant.id,
+            name: present.participant.name,
+            role: present.participant.role,
+            joined_at: present.joined_at,
+            left_at: now,
+            totals: present.clock.totals,
+        });
+    }
+
+    /// The session's time up to `now`, with everyone still present counted as leaving then.
+    fn time(&self, now: u64, ongoing: bool) -> SessionTime {
+        let mut clock = self.clock;
+        clock.advance(now, self.paused);
+        let mut present: Vec<&Present> = self.present.values().collect();
+        present.sort_by_key(|p| p.joined_at);
+        let mut participants = self.left.clone();
+        participants.extend(present.into_iter().map(|p| {
+            let mut clock = p.clock;
+            clock.advance(now, self.paused);
+            ParticipantTime {
+                id: p.participant.id.clone(),
+                name: p.participant.name.clone(),
+                role: p.participant.role,
+                joined_at: p.joined_at,
+                left_at: now,
+                totals: clock.totals,
+            }
+        }));
+        SessionTime {
+            id: self.id.clone(),
+            started_at: self.started_at,
+            ended_at: now,
+            totals: clock.totals,
+            participants,
+            ongoing,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TimeTracker {
+    current: Mutex<Option<Tracked>>,
+}
+
+fn with_tracked(app: &AppHandle, f: impl FnOnce(&mut Tracked, u64)) {
+    let tracker = app.state::<TimeTracker>();
+    let mut current = tracker.current.lock().unwrap();
+    if let Some(tracked) = current.as_mut() {
+        f(tracked, storage::now_secs());
+    }
+}
+
+/// Follow the host session through `state`. Called by `lifecycle::host` after every transition.
+pub fn host_changed(app: &AppHandle, state: HostState) {
+    let now = storage::now_secs();
+    let tracker = app.state::<TimeTracker>();
+    let mut current = tracker.current.lock().unwrap();
+    match state {
+        HostState::Hosting if current.is_none() => {
+            let participants = app.state::<session::Session>().participants();
+            let present = participants
+                .into_iter()
+                .map(|participant| {
+                    let present = Present {
+                        participant: participant.clone(),
+                        joined_at: now,
+                        clock: Clock::new(now),
+                    };
+                    (participant.id, present)
+                })
+                .collect();
+            *current = Some(Tracked {
+                id: new_id(),
+                started_at: now,
+                paused: false,
+                clock: Clock::new(now),
+                present,
+                left: Vec::new(),
+            });
+        }
+        HostState::Hosting | HostState::Paused => {
+            if let Some(tracked) = current.as_mut() {
+                tracked.advance(now);
+                tracked.paused = state == HostState::Paused;
+            }
+        }
+        HostState::Ended | HostState::Idle => {
+            let Some(tracked) = current.take() else {
+                return;
+            };
+            drop(current);
+            if let Err(e) = session::record(app, SessionRecord::Time(tracked.time(now, false))) {
+                log::warn!("Failed to record the session's time: {}", e);
+            }
+        }
+    }
+}
+
+pub fn joined(app: &AppHandle, participant: &Participant) {
+    with_tracked(app, |tracked, now| {
+        // Rejoining under the same id closes the earlier visit first
+        tracked.leave(&participant.id, now);
+        tracked.clock.active(now, tracked.paused);
+        let present = Present {
+            participant: participant.clone(),
+            joined_at: now,
+            clock: Clock::new(now),
+        };
+        tracked.present.insert(participant.id.clone(), present);
+    });
+}
+
+pub fn left(app: &AppHandle, participant_id: &str) {
+    with_tracked(app, |tracked, now| tracked.leave(participant_id, now));
+}
+
+/// Count the participant, and so the session, as active from now.
+pub fn activity(app: &AppHandle, participant_id: &str) {
+    with_tracked(app, |tracked, now| {
+        // Paused sessions stay paused, whatever participants do meanwhile
+        if tracked.paused {
+            return;
+        }
+        let Some(present) = tracked.present.get_mut(participant_id) else {
+            return;
+        };
+        present.clock.active(now, false);
+        tracked.clock.active(now, false);
+    });
+}
+
+/// Sessions starting from `from` (inclusive) to `to` (exclusive), in seconds since the Unix epoch.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeRange {
+    #[serde(default)]
+    pub from: Option<u64>,
+    #[serde(default)]
+    pub to: Option<u64>,
+}
+
+impl TimeRange {
+    fn contains(&self, secs: u64) -> bool {
+        self.from.map_or(true, |from| secs >= from) && self.to.map_or(true, |to| secs < to)
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParticipantTotals {
+    pub name: String,
+    pub sessions: usize,
+    #[serde(flatten)]
+    pub totals: TimeTotals,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeReport {
+    /// Oldest first, the running session last.
+    pub sessions: Vec<SessionTime>,
+    #[serde(flatten)]
+    pub totals: TimeTotals,
+    /// Per participant across the sessions, by name since ids don't outlive a session.
+    pub participants: Vec<ParticipantTotals>,
+}
+
+fn report(app: &AppHandle, range: TimeRange) -> Result<TimeReport, String> {
+    let mut sessions: Vec<SessionTime> = session::history(app)?
+        .into_iter()
+        .filter_map(|record| match record {
+            SessionRecord::Time(time) => Some(time),
+            _ => None,
+        })
+        .collect();
+    let running = app.state::<TimeTracker>().current.lock().unwrap().as_ref().map(|tracked| {
+        tracked.time(storage::now_secs(), true)
+    });
+    sessions.extend(running);
+    sessions.retain(|time| range.contains(time.started_at));
+    sessions.sort_by_key(|time| time.started_at);
+
+    let mut totals = TimeTotals::default();
+    let mut participants: Vec<ParticipantTotals> = Vec::new();
+    for time in &sessions {
+        totals.add(&time.totals);
+        let mut counted: Vec<&str> = Vec::new();
+        for participant in &time.participants {
+            let index = match participants.iter().position(|p| p.name == participant.name) {
+                Some(index) => index,
+                None => {
+                    participants.push(ParticipantTotals {
+                        name: participant.name.clone(),
+                        sessions: 0,
+                        totals: TimeTotals::default(),
+                    });
+                    participants.len() - 1
+                }
+            };
+            participants[index].totals.add(&participant.totals);
+            if !counted.contains(&participant.name.as_str()) {
+                counted.push(&participant.name);
+                participants[index].sessions += 1;
+            }
+        }
+    }
+    participants.sort_by(|a, b| b.totals.active_secs.cmp(&a.totals.active_secs));
+    Ok(TimeReport {
+        sessions,
+        totals,
+        participants,
+    })
+}
+
+fn local_time(secs: u64) -> String {
+    chrono::DateTime::from_timestamp(secs as i64, 0)
+        .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn hours(secs: u64) -> String {
+    format!("{:.2}", secs as f64 / 3600.0)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One row per session, then one per participant visit, with times in hours.
+fn report_csv(report: &TimeReport) -> String {
+    let mut out = String::from("session,participant,role,start,end,active hours,idle hours,paused hours\n");
+    let mut row = |session: &str, name: &str, role: &str, start: u64, end: u64, totals: &TimeTotals| {
+        let fields = [
+            csv_field(session),
+            csv_field(name),
+            role.to_string(),
+            local_time(start),
+            local_time(end),
+            hours(totals.active_secs),
+            hours(totals.idle_secs),
+            hours(totals.paused_secs),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    };
+    for time in &report.sessions {
+        row(&time.id, "", "", time.started_at, time.ended_at, &time.totals);
+        for participant in &time.participants {
+            let role = match participant.role {
+                Role::Host => "host",
+                Role::Editor => "editor",
+                Role::Viewer => "viewer",
+            };
+            row(&time.id, &participant.name, role, participant.joined_at, participant.left_at, &participant.totals);
+        }
+    }
+    out
+}
+
+/// Time spent in sessions started within `range`, including the one running now.
+#[tauri::command]
+pub fn get_time_report(app: AppHandle, range: Option<TimeRange>) -> Result<TimeReport, String> {
+    report(&app, range.unwrap_or_default())
+}
+
+/// Write the time report for `range` to `path` as CSV, for a spreadsheet or invoicing tool.
+#[tauri::command]
+pub fn export_time_report(app: AppHandle, range: Option<TimeRange>, path: String) -> Result<(), String> {
+    let report = report(&app, range.unwrap_or_default())?;
+    let path = PathBuf::from(path);
+    fs::write(&path, report_csv(&report)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    webhooks::export_created(&app, "time-report", &path);
+    Ok(())
+}
+
+/// The local user is active, e.g. typing. The frontend calls this at most every few seconds.
+#[tauri::command]
+pub fn note_activity(app: AppHandle, participant_id: String) {
+    activity(&app, &participant_id);
+}
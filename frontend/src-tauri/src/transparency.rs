@@ -0,0 +1,273 @@
+//! Transparency reports: a signed record of which privacy features were active during a session.
+//!
+//! Organisations that run interviews over ShareCode can require candidates to share this
+//! report. It is signed with a per-device Ed25519 key. That key is on the candidate's machine, so
+//! the report is self-attested: the signature only shows that whoever holds the key produced it.
+//! An organisation pins the candidate's public key (`get_transparency_public_key`) before the
+//! interview and verifies against that, which stops a report from another key being passed off,
+//! but a determined key holder can still sign a report of their own making.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, Manager, State};
+
+use crate::storage;
+
+const KEY_FILE: &str = "transparency.key";
+const REPORT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PrivacyFeature {
+    /// The window was excluded from screen capture.
+    CaptureHidden,
+    /// The app was hidden from the taskbar or dock.
+    TaskbarHidden,
+    /// The app was hidden from the app switcher and Task View or Mission Control.
+    SwitcherHidden,
+    /// The window was disguised as another application.
+    Disguise,
+    /// Prepared text was typed into a shared document as if by hand.
+    GhostTyping,
+}
+
+const FEATURES: [PrivacyFeature; 5] = [
+    PrivacyFeature::CaptureHidden,
+    PrivacyFeature::TaskbarHidden,
+    PrivacyFeature::SwitcherHidden,
+    PrivacyFeature::Disguise,
+    PrivacyFeature::GhostTyping,
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Toggle {
+    pub feature: PrivacyFeature,
+    pub active: bool,
+    pub at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureUsage {
+    pub feature: PrivacyFeature,
+    /// Active at any point during the session.
+    pub used: bool,
+    pub active_seconds: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransparencyReport {
+    pub version: u32,
+    pub label: Option<String>,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub features: Vec<FeatureUsage>,
+    pub toggles: Vec<Toggle>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedReport {
+    pub report: TransparencyReport,
+    /// Hex-encoded Ed25519 public key and signature over the report's canonical JSON.
+    pub public_key: String,
+    pub signature: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Verification {
+    /// Signed by the pinned key.
+    pub valid: bool,
+    /// The report was signed by this installation's key.
+    pub signed_by_this_device: bool,
+}
+
+struct Recording {
+    label: Option<String>,
+    started_at: u64,
+    initial: HashMap<PrivacyFeature, bool>,
+    toggles: Vec<Toggle>,
+}
+
+/// Current state of every privacy feature, plus the report being recorded, if any.
+#[derive(Default)]
+pub struct TransparencyState {
+    active: Mutex<HashMap<PrivacyFeature, bool>>,
+    recording: Mutex<Option<Recording>>,
+}
+
+/// Note that a privacy feature was switched on or off. Called by the features themselves.
+pub fn note(app: &AppHandle, feature: PrivacyFeature, active: bool) {
+    let state = app.state::<TransparencyState>();
+    let previous = state.active.lock().unwrap().insert(feature, active);
+    if previous == Some(active) {
+        return;
+    }
+    if let Some(recording) = state.recording.lock().unwrap().as_mut() {
+        recording.toggles.push(Toggle {
+            feature,
+            active,
+            at: storage::now_secs(),
+        });
+    }
+}
+
+/// The privacy features currently on.
+pub fn active_features(app: &AppHandle) -> Vec<PrivacyFeature> {
+    FEATURES.into_iter().filter(|&feature| is_active(app, feature)).collect()
+}
+
+/// Whether a privacy feature is currently on, as last noted.
+pub fn is_active(app: &AppHandle, feature: PrivacyFeature) -> bool {
+    app.state::<TransparencyState>().active.lock().unwrap().get(&feature).copied().unwrap_or(false)
+}
+
+fn signing_key(app: &AppHandle) -> Result<SigningKey, String> {
+    let path = storage::config_dir(app)?.join(KEY_FILE);
+    let mut seed = [0u8; 32];
+    if path.exists() {
+        let raw = fs::read(&path).map_err(|e| format!("Failed to read transparency key: {}", e))?;
+        if raw.len() != seed.len() {
+            return Err("Transparency key file is corrupted".to_string());
+        }
+        seed.copy_from_slice(&raw);
+    } else {
+        rand::rngs::OsRng.fill_bytes(&mut seed);
+        fs::write(&path, seed).map_err(|e| format!("Failed to write transparency key: {}", e))?;
+    }
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// JSON with sorted keys, so a report re-read from disk signs to the same bytes.
+fn canonical(report: &Value) -> Vec<u8> {
+    serde_json::to_vec(report).unwrap_or_default()
+}
+
+fn summarize(recording: &Recording, finished_at: u64) -> Vec<FeatureUsage> {
+    FEATURES
+        .iter()
+        .map(|&feature| {
+            let mut active = recording.initial.get(&feature).copied().unwrap_or(false);
+            let mut since = recording.started_at;
+            let mut used = active;
+            let mut active_seconds = 0;
+            for toggle in recording.toggles.iter().filter(|t| t.feature == feature) {
+                if active {
+                    active_seconds += toggle.at.saturating_sub(since);
+                }
+                active = toggle.active;
+                used |= active;
+                since = toggle.at;
+            }
+            if active {
+                active_seconds += finished_at.saturating_sub(since);
+            }
+            FeatureUsage {
+                feature,
+                used,
+                active_seconds,
+            }
+        })
+        .collect()
+}
+
+/// Start recording privacy feature usage; `label` identifies the session in the report.
+#[tauri::command]
+pub fn start_transparency_report(state: State<'_, TransparencyState>, label: Option<String>) {
+    let initial = state.active.lock().unwrap().clone();
+    *state.recording.lock().unwrap() = Some(Recording {
+        label,
+        started_at: storage::now_secs(),
+        initial,
+        toggles: Vec::new(),
+    });
+}
+
+/// Stop recording and return the signed report, also written to `path` when given.
+#[tauri::command]
+pub fn finish_transparency_report(
+    app: AppHandle,
+    state: State<'_, TransparencyState>,
+    path: Option<String>,
+) -> Result<SignedReport, String> {
+    let recording = state
+        .recording
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or("No transparency report is being recorded")?;
+    let finished_at = storage::now_secs();
+    let report = TransparencyReport {
+        version: REPORT_VERSION,
+        label: recording.label.clone(),
+        started_at: recording.started_at,
+        finished_at,
+        features: summarize(&recording, finished_at),
+        toggles: recording.toggles,
+    };
+    let key = signing_key(&app)?;
+    let value = serde_json::to_value(&report).map_err(|e| e.to_string())?;
+    let signed = SignedReport {
+        report,
+        public_key: to_hex(key.verifying_key().as_bytes()),
+        signature: to_hex(&key.sign(&canonical(&value)).to_bytes()),
+    };
+    if let Some(path) = path {
+        let raw = serde_json::to_vec_pretty(&signed).map_err(|e| e.to_string())?;
+        fs::write(&path, raw).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+    Ok(signed)
+}
+
+/// This installation's public key, hex-encoded, for an organisation to pin before an interview.
+#[tauri::command]
+pub fn get_transparency_public_key(app: AppHandle) -> Result<String, String> {
+    Ok(to_hex(signing_key(&app)?.verifying_key().as_bytes()))
+}
+
+/// Check a report's signature against `public_key`, the hex key the organisation pinned in
+/// advance. The key carried in the report is not trusted. `report` is the JSON produced by
+/// `finish_transparency_report`.
+#[tauri::command]
+pub fn verify_transparency_report(app: AppHandle, report: Value, public_key: String) -> Result<Verification, String> {
+    let (Some(signature), Some(body)) = (
+        report.get("signature").and_then(Value::as_str).and_then(from_hex),
+        report.get("report"),
+    ) else {
+        return Err("Not a signed transparency report".to_string());
+    };
+    let public_key: [u8; 32] = from_hex(public_key.trim())
+        .and_then(|key| key.try_into().ok())
+        .ok_or("Invalid public key")?;
+    let key = VerifyingKey::from_bytes(&public_key).map_err(|_| "Invalid public key")?;
+    let signature = Signature::from_slice(&signature).map_err(|_| "Invalid signature")?;
+    let valid = key.verify(&canonical(body), &signature).is_ok();
+    let own = signing_key(&app)?.verifying_key();
+    Ok(Verification {
+        valid,
+        signed_by_this_device: valid && own == key,
+    })
+}
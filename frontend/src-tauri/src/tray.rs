@@ -0,0 +1,198 @@
+//! Tray (menu bar on macOS) icon with the privacy controls.
+//!
+//! Once the app is hidden from the taskbar or dock and its window is hidden, the tray is the only
+//! place left to bring it back, so it lives here rather than in the frontend. Clicking the icon
+//! shows the window; the menu toggles capture and taskbar hiding through the same paths as the
+//! settings screen, so the changes are recorded in transparency reports. It is also the way to
+//! turn click-through back off, since the window itself can't be clicked then.
+
+use serde::Serialize;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow, Wry};
+
+use crate::transparency::{self, PrivacyFeature};
+use crate::{panic_hide, CaptureProtection};
+
+const MAIN_WINDOW: &str = "main";
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrayAction {
+    ShowWindow,
+    HideFromCapture,
+    HideFromTaskbar,
+    /// Let clicks through the window to whatever is beneath it.
+    ClickThrough,
+    /// Hide every window at once; see `panic_hide`.
+    PanicHide,
+}
+
+impl TrayAction {
+    const ALL: [TrayAction; 5] =
+        [Self::ShowWindow, Self::HideFromCapture, Self::HideFromTaskbar, Self::ClickThrough, Self::PanicHide];
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::ShowWindow => "show-window",
+            Self::HideFromCapture => "hide-from-capture",
+            Self::HideFromTaskbar => "hide-from-taskbar",
+            Self::ClickThrough => "click-through",
+            Self::PanicHide => "panic-hide",
+        }
+    }
+}
+
+/// Sent as `tray-triggered` after every tray action.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TrayTriggered {
+    action: TrayAction,
+    /// Why the action could not be applied, if it failed.
+    error: Option<String>,
+}
+
+/// The menu's check items, kept in step with what is applied.
+pub struct Tray {
+    capture: CheckMenuItem<Wry>,
+    taskbar: CheckMenuItem<Wry>,
+    click_through: CheckMenuItem<Wry>,
+}
+
+fn show_window(window: &WebviewWindow) -> Result<(), String> {
+    window.show().map_err(|e| e.to_string())?;
+    window.unminimize().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+fn perform(app: &AppHandle, action: TrayAction) -> Result<(), String> {
+    let window = app.get_webview_window(MAIN_WINDOW).ok_or("The main window is gone")?;
+    match action {
+        // After a panic hide, put back everything it changed, not just the window.
+        TrayAction::ShowWindow if panic_hide::is_hidden(app) => {
+            let errors = panic_hide::restore(app).errors;
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors.join("; "))
+            }
+        }
+        TrayAction::ShowWindow => show_window(&window),
+        TrayAction::HideFromCapture => {
+            let protected = !matches!(crate::read_capture_protection(&window)?, CaptureProtection::Off);
+            crate::protect_from_capture(&window, !protected).map(|_| ())
+        }
+        TrayAction::HideFromTaskbar => {
+            let hidden = transparency::is_active(app, PrivacyFeature::TaskbarHidden);
+            crate::apply_taskbar_visibility(&window, hidden)
+        }
+        TrayAction::ClickThrough => {
+            let enabled = crate::read_click_through(&window)?;
+            crate::apply_click_through(&window, !enabled)
+        }
+        TrayAction::PanicHide => {
+            let errors = panic_hide::hide(app).errors;
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(errors.join("; "))
+            }
+        }
+    }
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    if event.id().as_ref() == "quit" {
+        app.exit(0);
+        return;
+    }
+    let Some(action) = TrayAction::ALL.into_iter().find(|a| event.id().as_ref() == a.id()) else {
+        return;
+    };
+    let error = perform(app, action).err();
+    if let Some(e) = &error {
+        log::warn!("Tray action failed: {}", e);
+    }
+    // Check items flip themselves when clicked, even when applying failed.
+    refresh(app);
+    if let Err(e) = app.emit("tray-triggered", TrayTriggered { action, error }) {
+        log::warn!("Failed to emit tray-triggered: {}", e);
+    }
+}
+
+/// Set the check items from the state actually applied, and pass it on to the MQTT broker. Called
+/// whenever something else changes it.
+pub fn refresh(app: &AppHandle) {
+    crate::mqtt::publish_status(app);
+    let Some(tray) = app.try_state::<Tray>() else {
+        return;
+    };
+    let window = app.get_webview_window(MAIN_WINDOW);
+    let protected = window
+        .as_ref()
+        .and_then(|window| crate::read_capture_protection(window).ok())
+        .is_some_and(|p| !matches!(p, CaptureProtection::Off));
+    let hidden = transparency::is_active(app, PrivacyFeature::TaskbarHidden);
+    let click_through = window.as_ref().and_then(|window| crate::read_click_through(window).ok()) == Some(true);
+    let updated = tray
+        .capture
+        .set_checked(protected)
+        .and_then(|_| tray.taskbar.set_checked(hidden))
+        .and_then(|_| tray.click_through.set_checked(click_through));
+    if let Err(e) = updated {
+        log::warn!("Failed to update the tray menu: {}", e);
+    }
+}
+
+/// Add the tray icon and its menu. Called once from setup.
+pub fn install(app: &AppHandle) -> tauri::Result<()> {
+    let item = |action: TrayAction, label: &str| MenuItem::with_id(app, action.id(), label, true, None::<&str>);
+    let check = |action: TrayAction, label: &str| {
+        CheckMenuItem::with_id(app, action.id(), label, true, false, None::<&str>)
+    };
+    let capture = check(TrayAction::HideFromCapture, "Hide from capture")?;
+    let taskbar = check(TrayAction::HideFromTaskbar, "Hide from taskbar")?;
+    let click_through = check(TrayAction::ClickThrough, "Click-through")?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &item(TrayAction::ShowWindow, "Show ShareCode")?,
+            &PredefinedMenuItem::separator(app)?,
+            &capture,
+            &taskbar,
+            &click_through,
+            &item(TrayAction::PanicHide, "Panic hide")?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id("main")
+        .tooltip("ShareCode")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                if let Err(e) = perform(tray.app_handle(), TrayAction::ShowWindow) {
+                    log::warn!("Failed to show the window: {}", e);
+                }
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    app.manage(Tray {
+        capture,
+        taskbar,
+        click_through,
+    });
+    Ok(())
+}
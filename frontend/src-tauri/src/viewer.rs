@@ -0,0 +1,596 @@
+//! Watching a session hosted by another instance's session server, as the mobile companion does.
+//!
+//! Participants join by scanning the host's QR code (the session URL). The backend keeps the
+//! connection: it streams over server-sent events while the app is in the foreground, and polls
+//! on a timer in the background or in battery saver mode. Documents are kept here, patched as
+//! frames arrive, and emitted with highlight spans as `remote-document` so the webview only has to
+//! display them. Lost connections are retried with backoff; `resume_remote_session` retries at
+//! once, for when the OS wakes the app or the network comes back.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::events::{self, Channel};
+use crate::highlight::Highlighter;
+use crate::lifecycle::{self, RemoteEvent};
+use crate::netsim::{self, Direction};
+use crate::protocol::{Capability, Message, Negotiated, PROTOCOL_VERSION};
+use crate::server::{PeerInfo, RenderedDocument, Transport};
+use crate::syntax::OffsetMap;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longer than the server's heartbeat and long-poll wait, so a live connection never times out.
+const READ_TIMEOUT: Duration = Duration::from_secs(45);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Poll intervals when not streaming. The background one stays under the server's 60 s expiry.
+const BACKGROUND_INTERVAL: Duration = Duration::from_secs(45);
+const SAVER_INTERVAL: Duration = Duration::from_secs(15);
+/// Patches arriving within this window are highlighted together.
+const RENDER_COALESCE: Duration = Duration::from_millis(250);
+/// Each rendering replaces the last one of the same document.
+const REMOTE_DOCUMENT: Channel = Channel::latest_wins("remote-document");
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerMode {
+    /// On screen: stream changes as they happen.
+    #[default]
+    Foreground,
+    /// Off screen: poll rarely so the radio can sleep.
+    Background,
+    /// On screen with battery saver on: poll every few seconds instead of streaming.
+    Saver,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinLink {
+    /// Scheme, host and port of the session server.
+    pub base: String,
+    pub token: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteSession {
+    pub base: String,
+    pub participant_id: String,
+    pub transport: Transport,
+    /// What the host agreed to; features outside it are unavailable in this session.
+    #[serde(flatten)]
+    pub negotiated: Negotiated,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RemoteStatus {
+    /// `live`, `reconnecting` or `closed`.
+    state: &'static str,
+    transport: Option<Transport>,
+    retry_in_ms: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JoinReply {
+    participant_id: String,
+    /// Absent from hosts that predate participant secrets.
+    secret: Option<String>,
+    transport: Transport,
+    /// Absent from hosts that predate versioning.
+    protocol: Option<u32>,
+    capabilities: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct PollReply {
+    messages: Vec<Message>,
+}
+
+struct RemoteDocument {
+    seq: u64,
+    language: Option<String>,
+    text: String,
+    /// Created on the first render and kept up to date by patches.
+    highlighter: Option<Highlighter>,
+}
+
+struct Connection {
+    link: JoinLink,
+    name: String,
+    agent: ureq::Agent,
+    participant_id: Mutex<String>,
+    secret: Mutex<Option<String>>,
+    negotiated: Mutex<Negotiated>,
+    docs: Mutex<HashMap<String, RemoteDocument>>,
+    /// Documents changed since they were last emitted.
+    dirty: Mutex<HashSet<String>>,
+    dirty_changed: Condvar,
+    /// Set to cut the current wait short.
+    woken: Mutex<bool>,
+    wake: Condvar,
+    stopped: AtomicBool,
+    /// What this connection reports its progress to the lifecycle under.
+    generation: u64,
+}
+
+#[derive(Default)]
+pub struct ViewerClient {
+    connection: Mutex<Option<Arc<Connection>>>,
+    power: Mutex<PowerMode>,
+}
+
+impl ViewerClient {
+    fn power(&self) -> PowerMode {
+        *self.power.lock().unwrap()
+    }
+
+    /// The host this app is viewing, and the participant id it joined as.
+    pub fn remote(&self) -> Option<(String, String)> {
+        let connection = self.connection.lock().unwrap();
+        let connection = connection.as_ref().filter(|c| !c.stopped.load(Ordering::Relaxed))?;
+        Some((connection.link.base.clone(), connection.participant_id.lock().unwrap().clone()))
+    }
+
+    /// Everyone in the session being watched, as the host lists them.
+    pub fn peers(&self) -> Result<Vec<PeerInfo>, String> {
+        let connection = self.connection.lock().unwrap().clone().ok_or("Not watching a session")?;
+        connection
+            .agent
+            .get(&format!("{}/session/peers?{}", connection.link.base, connection.query()))
+            .call()
+            .map_err(describe)?
+            .into_json()
+            .map_err(|e| format!("Invalid reply from the host: {}", e))
+    }
+
+    /// The link of the session being watched, if any.
+    pub fn link(&self) -> Option<String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .as_ref()
+            .map(|c| format!("{}/session?token={}", c.link.base, c.link.token))
+    }
+}
+
+/// Split a session URL (as shown by the host or scanned from its QR code) into server and token.
+pub fn parse_link(link: &str) -> Result<JoinLink, String> {
+    let link = link.trim();
+    let invalid = || format!("Not a session link: {}", link);
+    let (scheme, rest) = link.split_once("://").ok_or_else(invalid)?;
+    if scheme != "http" && scheme != "https" {
+        return Err(invalid());
+    }
+    let (host, path) = rest.split_once('/').ok_or_else(invalid)?;
+    let (path, query) = path.split_once('?').ok_or_else(invalid)?;
+    if host.is_empty() || path.trim_end_matches('/') != "session" {
+        return Err(invalid());
+    }
+    let token = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+        .filter(|token| !token.is_empty() && token.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(invalid)?;
+    Ok(JoinLink {
+        base: format!("{}://{}", scheme, host),
+        token: token.to_string(),
+    })
+}
+
+fn describe(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(403, _) => "The host rejected this viewer".to_string(),
+        ureq::Error::Status(code, response) => {
+            format!("Request failed with {}: {}", code, response.into_string().unwrap_or_default())
+        }
+        other => format!("Could not reach the host: {}", other),
+    }
+}
+
+impl Connection {
+    fn query(&self) -> String {
+        let mut query = format!("token={}&participant={}", self.link.token, self.participant_id.lock().unwrap());
+        if let Some(secret) = self.secret.lock().unwrap().as_ref() {
+            query.push_str(&format!("&secret={}", secret));
+        }
+        query
+    }
+
+    /// Join (or rejoin as the same participant) and let the host pick a transport.
+    fn join(&self, failed: &[Transport]) -> Result<Transport, String> {
+        let participant_id = self.participant_id.lock().unwrap().clone();
+        let secret = self.secret.lock().unwrap().clone();
+        let body = json!({
+            "token": self.link.token,
+            "name": self.name,
+            "participantId": (!participant_id.is_empty()).then_some(participant_id),
+            "secret": secret,
+            "supports": [Transport::Sse, Transport::LongPoll],
+            "failed": failed,
+            "protocol": PROTOCOL_VERSION,
+            "capabilities": Capability::ALL,
+        });
+        let reply: JoinReply = self
+            .agent
+            .post(&format!("{}/session/join", self.link.base))
+            .send_json(body)
+            .map_err(describe)?
+            .into_json()
+            .map_err(|e| format!("Invalid reply from the host: {}", e))?;
+        *self.participant_id.lock().unwrap() = reply.participant_id;
+        *self.secret.lock().unwrap() = reply.secret;
+        let negotiated = Negotiated::with(reply.protocol.unwrap_or(1), reply.capabilities.as_deref())?;
+        *self.negotiated.lock().unwrap() = negotiated;
+        Ok(reply.transport)
+    }
+
+    /// Tell the host which version of a document this app holds, and whether it is on screen.
+    fn acknowledge(&self, document_id: &str, seq: u64, viewed: bool) {
+        if !self.negotiated.lock().unwrap().allows(Capability::Receipts) {
+            return;
+        }
+        let receipt = Message::Receipt {
+            participant_id: self.participant_id.lock().unwrap().clone(),
+            document_id: document_id.to_string(),
+            seq,
+            viewed,
+        };
+        let sent = self
+            .agent
+            .post(&format!("{}/session/send?{}", self.link.base, self.query()))
+            .send_json(&receipt);
+        // The next render sends a newer one anyway
+        if let Err(e) = sent {
+            log::debug!("Failed to send a read receipt: {}", describe(e));
+        }
+    }
+
+    fn receive(&self, app: &AppHandle, message: Message) {
+        match message {
+            Message::Snapshot {
+                document_id,
+                seq,
+                text,
+                language,
+                frozen,
+            } => {
+                let doc = RemoteDocument {
+                    seq,
+                    language,
+                    text,
+                    highlighter: None,
+                };
+                self.docs.lock().unwrap().insert(document_id.clone(), doc);
+                if frozen {
+                    let notice = Message::DocumentFrozen {
+                        document_id: document_id.clone(),
+                        frozen,
+                    };
+                    app.emit("remote-message", notice).ok();
+                }
+                self.mark_dirty(document_id);
+            }
+            Message::Patch {
+                document_id,
+                seq,
+                start,
+                delete_count,
+                insert,
+            } => {
+                let mut docs = self.docs.lock().unwrap();
+                // A gap means frames were dropped; the host follows up with a fresh snapshot.
+                let Some(doc) = docs.get_mut(&document_id).filter(|doc| doc.seq + 1 == seq) else {
+                    return;
+                };
+                let offsets = OffsetMap::new(&doc.text);
+                let from = offsets.to_byte(start);
+                let to = offsets.to_byte(start + delete_count);
+                if let Some(highlighter) = doc.highlighter.as_mut() {
+                    highlighter.edit(from, to - from, &insert);
+                }
+                doc.text.replace_range(from..to, &insert);
+                doc.seq = seq;
+                drop(docs);
+                self.mark_dirty(document_id);
+            }
+            Message::Unsupported => {}
+            Message::Snippet { snippet } => {
+                app.emit("snippet-received", snippet).ok();
+            }
+            ended @ Message::SessionEnded { .. } => {
+                // Nothing to reconnect to.
+                lifecycle::remote(app, self.generation, RemoteEvent::Leave);
+                app.emit("remote-message", ended).ok();
+                stop(self);
+            }
+            other => {
+                app.emit("remote-message", other).ok();
+            }
+        }
+    }
+
+    fn mark_dirty(&self, document_id: String) {
+        self.dirty.lock().unwrap().insert(document_id);
+        self.dirty_changed.notify_one();
+    }
+
+    /// Stream frames over server-sent events until the connection drops or the app leaves the
+    /// foreground. `delivered` reports whether the stream worked at all.
+    fn stream(&self, app: &AppHandle, delivered: &mut bool) -> Result<(), String> {
+        let response = self
+            .agent
+            .get(&format!("{}/session/stream?{}", self.link.base, self.query()))
+            .set("Accept", "text/event-stream")
+            .call()
+            .map_err(describe)?;
+        let reader = BufReader::new(response.into_reader());
+        for line in reader.lines() {
+            let line = line.map_err(|e| format!("Connection lost: {}", e))?;
+            netsim::transmit(Direction::Download, line.len())?;
+            *delivered = true;
+            if let Some(data) = line.strip_prefix("data: ") {
+                if let Ok(message) = serde_json::from_str(data) {
+                    self.receive(app, message);
+                }
+            }
+            // Heartbeats arrive every 20 s, so mode changes are noticed within that.
+            if self.stopped.load(Ordering::Relaxed) || app.state::<ViewerClient>().power() != PowerMode::Foreground {
+                return Ok(());
+            }
+        }
+        Err("The host closed the stream".to_string())
+    }
+
+    fn poll(&self, app: &AppHandle, wait: bool) -> Result<(), String> {
+        let url = format!(
+            "{}/session/poll?{}{}",
+            self.link.base,
+            self.query(),
+            if wait { "" } else { "&wait=0" }
+        );
+        let response = self.agent.get(&url).call().map_err(describe)?;
+        let length = response.header("Content-Length").and_then(|l| l.parse().ok()).unwrap_or(0);
+        netsim::transmit(Direction::Download, length)?;
+        let reply: PollReply = response
+            .into_json()
+            .map_err(|e| format!("Invalid reply from the host: {}", e))?;
+        for message in reply.messages {
+            self.receive(app, message);
+        }
+        Ok(())
+    }
+
+    /// Sleep for `delay` unless woken first.
+    fn wait(&self, delay: Duration) {
+        let woken = self.woken.lock().unwrap();
+        let (mut woken, _) = self.wake.wait_timeout_while(woken, delay, |woken| !*woken).unwrap();
+        *woken = false;
+    }
+
+    fn wake(&self) {
+        *self.woken.lock().unwrap() = true;
+        self.wake.notify_all();
+    }
+}
+
+fn status(app: &AppHandle, state: &'static str, transport: Option<Transport>, retry: Option<Duration>, error: Option<String>) {
+    let status = RemoteStatus {
+        state,
+        transport,
+        retry_in_ms: retry.map(|d| d.as_millis() as u64),
+        error,
+    };
+    app.emit("remote-session-status", status).ok();
+}
+
+/// Keep the connection alive, choosing how to sync from the power mode.
+fn sync(app: AppHandle, connection: Arc<Connection>, mut transport: Option<Transport>) {
+    let mut failed = Vec::new();
+    let mut backoff = MIN_BACKOFF;
+    status(&app, "live", transport, None, None);
+    while !connection.stopped.load(Ordering::Relaxed) {
+        let mode = app.state::<ViewerClient>().power();
+        let result = (|| -> Result<Duration, String> {
+            let current = match transport {
+                Some(current) => current,
+                None => {
+                    let joined = connection.join(&failed)?;
+                    transport = Some(joined);
+                    lifecycle::remote(&app, connection.generation, RemoteEvent::Joined);
+                    status(&app, "live", transport, None, None);
+                    joined
+                }
+            };
+            match (mode, current) {
+                (PowerMode::Foreground, Transport::Sse) => {
+                    let mut delivered = false;
+                    connection.stream(&app, &mut delivered).inspect_err(|_| {
+                        if !delivered {
+                            failed.push(Transport::Sse);
+                        }
+                    })?;
+                    Ok(Duration::ZERO)
+                }
+                (PowerMode::Foreground, _) => connection.poll(&app, true).map(|_| Duration::ZERO),
+                (PowerMode::Background, _) => connection.poll(&app, false).map(|_| BACKGROUND_INTERVAL),
+                (PowerMode::Saver, _) => connection.poll(&app, false).map(|_| SAVER_INTERVAL),
+            }
+        })();
+        if connection.stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        match result {
+            Ok(delay) => {
+                backoff = MIN_BACKOFF;
+                connection.wait(delay);
+            }
+            Err(e) => {
+                // Rejoin on the next attempt; the host may have expired this viewer.
+                transport = None;
+                lifecycle::remote(&app, connection.generation, RemoteEvent::Lost);
+                status(&app, "reconnecting", None, Some(backoff), Some(e));
+                connection.wait(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+    status(&app, "closed", None, None, None);
+}
+
+/// Emit changed documents with highlighting, coalescing bursts of patches.
+fn render(app: AppHandle, connection: Arc<Connection>) {
+    loop {
+        {
+            let dirty = connection.dirty.lock().unwrap();
+            let _dirty = connection
+                .dirty_changed
+                .wait_while(dirty, |dirty| dirty.is_empty() && !connection.stopped.load(Ordering::Relaxed))
+                .unwrap();
+        }
+        if connection.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+        thread::sleep(RENDER_COALESCE);
+        let ids: Vec<String> = connection.dirty.lock().unwrap().drain().collect();
+        for id in ids {
+            let mut docs = connection.docs.lock().unwrap();
+            let Some(doc) = docs.get_mut(&id) else {
+                continue;
+            };
+            if doc.highlighter.is_none() {
+                doc.highlighter = doc.language.as_deref().and_then(|language| Highlighter::new(language, &doc.text));
+            }
+            let spans = doc.highlighter.as_mut().map(|h| h.spans(&doc.text)).unwrap_or_default();
+            let rendered = RenderedDocument {
+                document_id: id,
+                seq: doc.seq,
+                language: doc.language.clone(),
+                text: doc.text.clone(),
+                spans,
+            };
+            drop(docs);
+            events::emit_keyed(&app, &REMOTE_DOCUMENT, &rendered.document_id, &rendered).ok();
+            let viewed = app.state::<ViewerClient>().power() != PowerMode::Background;
+            connection.acknowledge(&rendered.document_id, rendered.seq, viewed);
+        }
+    }
+}
+
+#[tauri::command]
+pub fn parse_join_link(link: String) -> Result<JoinLink, String> {
+    parse_link(&link)
+}
+
+/// Join a session from its link as a read-only viewer and keep it in sync in the background.
+#[tauri::command]
+pub async fn join_remote_session(
+    app: AppHandle,
+    client: State<'_, ViewerClient>,
+    link: String,
+    name: String,
+) -> Result<RemoteSession, String> {
+    let link = parse_link(&link)?;
+    // Joining replaces the session being watched, even if the new one cannot be reached.
+    if let Some(previous) = client.connection.lock().unwrap().take() {
+        stop(&previous);
+    }
+    let generation = lifecycle::join(&app);
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build();
+    let connection = Arc::new(Connection {
+        link,
+        name,
+        agent,
+        participant_id: Mutex::new(String::new()),
+        secret: Mutex::new(None),
+        negotiated: Mutex::new(Negotiated::legacy()),
+        docs: Mutex::new(HashMap::new()),
+        dirty: Mutex::new(HashSet::new()),
+        dirty_changed: Condvar::new(),
+        woken: Mutex::new(false),
+        wake: Condvar::new(),
+        stopped: AtomicBool::new(false),
+        generation,
+    });
+    let joining = Arc::clone(&connection);
+    let joined = tauri::async_runtime::spawn_blocking(move || joining.join(&[]))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result);
+    let transport = match joined {
+        Ok(transport) => transport,
+        Err(e) => {
+            lifecycle::remote(&app, generation, RemoteEvent::Leave);
+            return Err(e);
+        }
+    };
+    lifecycle::remote(&app, generation, RemoteEvent::Joined);
+
+    if let Some(previous) = client.connection.lock().unwrap().replace(Arc::clone(&connection)) {
+        stop(&previous);
+    }
+    let sync_app = app.clone();
+    let sync_connection = Arc::clone(&connection);
+    thread::spawn(move || sync(sync_app, sync_connection, Some(transport)));
+    let render_connection = Arc::clone(&connection);
+    thread::spawn(move || render(app, render_connection));
+
+    let participant_id = connection.participant_id.lock().unwrap().clone();
+    let negotiated = connection.negotiated.lock().unwrap().clone();
+    Ok(RemoteSession {
+        base: connection.link.base.clone(),
+        participant_id,
+        transport,
+        negotiated,
+    })
+}
+
+fn stop(connection: &Connection) {
+    connection.stopped.store(true, Ordering::Relaxed);
+    connection.wake();
+    connection.dirty_changed.notify_all();
+}
+
+#[tauri::command]
+pub fn leave_remote_session(app: AppHandle, client: State<'_, ViewerClient>) -> bool {
+    lifecycle::leave(&app);
+    let Some(connection) = client.connection.lock().unwrap().take() else {
+        return false;
+    };
+    stop(&connection);
+    true
+}
+
+/// Tell the viewer whether the app is on screen and whether battery saver is on.
+#[tauri::command]
+pub fn set_viewer_power_mode(client: State<'_, ViewerClient>, mode: PowerMode) {
+    *client.power.lock().unwrap() = mode;
+    if let Some(connection) = client.connection.lock().unwrap().as_ref() {
+        connection.wake();
+    }
+}
+
+/// Reconnect now instead of waiting out the backoff, e.g. when a push notification or the
+/// network coming back wakes the app.
+#[tauri::command]
+pub fn resume_remote_session(client: State<'_, ViewerClient>) -> bool {
+    match client.connection.lock().unwrap().as_ref() {
+        Some(connection) => {
+            connection.wake();
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,306 @@
+//! Opt-in voice control: hold a push-to-talk hotkey, say a command phrase, and the matching
+//! action is sent to the frontend.
+//!
+//! Recognition runs entirely on this machine with a Vosk model the user downloads and points the
+//! app at; the recognizer is restricted to the configured phrases, which keeps small models
+//! accurate. The microphone is only open while the hotkey is held, and no audio is stored.
+//! Built only with the `voice` feature, since it links against libvosk.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, Sample, SizedSample};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use vosk::{Model, Recognizer};
+
+use crate::storage;
+
+const CONFIG_FILE: &str = "voice.json";
+/// Holding the hotkey longer than this ends the utterance anyway.
+const MAX_UTTERANCE: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoiceCommand {
+    pub phrase: String,
+    /// Identifier handed to the frontend, e.g. `stealth-on`.
+    pub action: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VoiceConfig {
+    pub enabled: bool,
+    /// Directory of an unpacked Vosk model.
+    pub model_path: Option<String>,
+    pub hotkey: String,
+    pub commands: Vec<VoiceCommand>,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        let command = |phrase: &str, action: &str| VoiceCommand {
+            phrase: phrase.to_string(),
+            action: action.to_string(),
+        };
+        Self {
+            enabled: false,
+            model_path: None,
+            hotkey: "CommandOrControl+Shift+Space".to_string(),
+            commands: vec![
+                command("stealth on", "stealth-on"),
+                command("stealth off", "stealth-off"),
+                command("next bookmark", "next-bookmark"),
+                command("previous bookmark", "previous-bookmark"),
+                command("start session", "start-session"),
+                command("stop session", "stop-session"),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecognizedCommand {
+    pub phrase: String,
+    pub action: String,
+}
+
+#[derive(Default)]
+pub struct VoiceControl {
+    config: Mutex<VoiceConfig>,
+    /// The loaded model and the path it came from.
+    model: Mutex<Option<(String, Arc<Model>)>>,
+    hotkey: Mutex<Option<Shortcut>>,
+    /// Present while listening; setting it ends the utterance.
+    stop: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl VoiceControl {
+    fn config(&self) -> VoiceConfig {
+        self.config.lock().unwrap().clone()
+    }
+}
+
+fn normalize(phrase: &str) -> String {
+    phrase.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+fn load_model(state: &VoiceControl, path: &str) -> Result<Arc<Model>, String> {
+    if let Some((loaded, model)) = state.model.lock().unwrap().as_ref() {
+        if loaded == path {
+            return Ok(model.clone());
+        }
+    }
+    let model = Arc::new(Model::new(path).ok_or_else(|| format!("Failed to load a Vosk model from {}", path))?);
+    *state.model.lock().unwrap() = Some((path.to_string(), model.clone()));
+    Ok(model)
+}
+
+fn register_hotkey(app: &AppHandle, hotkey: &str) -> Result<(), String> {
+    let shortcut: Shortcut = hotkey.parse().map_err(|e| format!("Invalid hotkey {}: {}", hotkey, e))?;
+    unregister_hotkey(app);
+    app.global_shortcut()
+        .on_shortcut(shortcut, |app, _, event| match event.state() {
+            ShortcutState::Pressed => start_listening(app),
+            ShortcutState::Released => stop_listening(app),
+        })
+        .map_err(|e| format!("Failed to register {}: {}", hotkey, e))?;
+    *app.state::<VoiceControl>().hotkey.lock().unwrap() = Some(shortcut);
+    Ok(())
+}
+
+fn unregister_hotkey(app: &AppHandle) {
+    if let Some(shortcut) = app.state::<VoiceControl>().hotkey.lock().unwrap().take() {
+        if let Err(e) = app.global_shortcut().unregister(shortcut) {
+            log::warn!("Failed to unregister voice hotkey: {}", e);
+        }
+    }
+}
+
+/// Load the model and bind the hotkey for `config`, or release both when it is disabled.
+fn apply(app: &AppHandle, config: &VoiceConfig) -> Result<(), String> {
+    if !config.enabled {
+        unregister_hotkey(app);
+        stop_listening(app);
+        *app.state::<VoiceControl>().model.lock().unwrap() = None;
+        return Ok(());
+    }
+    let path = config.model_path.as_deref().ok_or("Choose a speech model before enabling voice control")?;
+    load_model(&app.state::<VoiceControl>(), path)?;
+    register_hotkey(app, &config.hotkey)
+}
+
+/// Manage the voice state and restore a saved configuration. Called once from setup.
+pub fn install(app: &AppHandle) {
+    let config: VoiceConfig = storage::load_json(app, CONFIG_FILE).unwrap_or_else(|e| {
+        log::warn!("Using default voice config: {}", e);
+        VoiceConfig::default()
+    });
+    app.manage(VoiceControl {
+        config: Mutex::new(config.clone()),
+        ..Default::default()
+    });
+    if config.enabled {
+        // Models take a few seconds to load; don't hold up startup.
+        let app = app.clone();
+        thread::spawn(move || {
+            if let Err(e) = apply(&app, &config) {
+                log::warn!("Voice control unavailable: {}", e);
+            }
+        });
+    }
+}
+
+fn build_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, tx: Sender<Vec<i16>>) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    i16: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _: &cpal::InputCallbackInfo| {
+                // The first channel is enough for speech.
+                let samples = data.chunks(channels).map(|frame| frame[0].to_sample::<i16>()).collect();
+                tx.send(samples).ok();
+            },
+            |e| log::warn!("Microphone error: {}", e),
+            None,
+        )
+        .map_err(|e| format!("Failed to open the microphone: {}", e))
+}
+
+/// Record until `stop` is set and return what the recognizer heard.
+fn listen(model: &Model, phrases: &[String], stop: &AtomicBool) -> Result<String, String> {
+    let device = cpal::default_host().default_input_device().ok_or("No microphone found")?;
+    let supported = device
+        .default_input_config()
+        .map_err(|e| format!("Failed to query the microphone: {}", e))?;
+    let sample_rate = supported.sample_rate().0;
+    let config = supported.config();
+    let (tx, rx) = mpsc::channel();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::I16 => build_stream::<i16>(&device, &config, tx),
+        cpal::SampleFormat::U16 => build_stream::<u16>(&device, &config, tx),
+        cpal::SampleFormat::F32 => build_stream::<f32>(&device, &config, tx),
+        format => Err(format!("Unsupported microphone sample format {}", format)),
+    }?;
+
+    // "[unk]" absorbs speech outside the grammar instead of forcing it onto a command.
+    let mut grammar: Vec<&str> = phrases.iter().map(String::as_str).collect();
+    grammar.push("[unk]");
+    let mut recognizer =
+        Recognizer::new_with_grammar(model, sample_rate as f32, &grammar).ok_or("Failed to start the recognizer")?;
+    stream.play().map_err(|e| format!("Failed to start the microphone: {}", e))?;
+
+    let deadline = Instant::now() + MAX_UTTERANCE;
+    while !stop.load(Ordering::Relaxed) && Instant::now() < deadline {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(samples) => {
+                recognizer.accept_waveform(&samples).ok();
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    drop(stream);
+    for samples in rx.try_iter() {
+        recognizer.accept_waveform(&samples).ok();
+    }
+    Ok(recognizer
+        .final_result()
+        .single()
+        .map(|result| result.text.to_string())
+        .unwrap_or_default())
+}
+
+fn start_listening(app: &AppHandle) {
+    let state = app.state::<VoiceControl>();
+    let mut current = state.stop.lock().unwrap();
+    if current.is_some() {
+        return;
+    }
+    let config = state.config();
+    let Some(model) = state.model.lock().unwrap().as_ref().map(|(_, model)| model.clone()) else {
+        app.emit("voice-error", "Voice control is not enabled").ok();
+        return;
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    *current = Some(stop.clone());
+    drop(current);
+
+    let app = app.clone();
+    // cpal streams are not Send, so the whole utterance lives on one thread.
+    thread::spawn(move || {
+        app.emit("voice-listening", true).ok();
+        let phrases: Vec<String> = config.commands.iter().map(|c| normalize(&c.phrase)).collect();
+        let heard = listen(&model, &phrases, &stop);
+        app.state::<VoiceControl>().stop.lock().unwrap().take();
+        app.emit("voice-listening", false).ok();
+        let heard = match heard {
+            Ok(heard) => normalize(&heard),
+            Err(e) => {
+                app.emit("voice-error", e).ok();
+                return;
+            }
+        };
+        let emitted = match config.commands.iter().find(|c| normalize(&c.phrase) == heard) {
+            Some(command) => app.emit(
+                "voice-command",
+                RecognizedCommand {
+                    phrase: command.phrase.clone(),
+                    action: command.action.clone(),
+                },
+            ),
+            None => app.emit("voice-unrecognized", heard),
+        };
+        emitted.ok();
+    });
+}
+
+fn stop_listening(app: &AppHandle) {
+    if let Some(stop) = app.state::<VoiceControl>().stop.lock().unwrap().as_ref() {
+        stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[tauri::command]
+pub fn get_voice_config(state: State<'_, VoiceControl>) -> VoiceConfig {
+    state.config()
+}
+
+/// Save the voice settings and apply them; enabling loads the model and binds the hotkey.
+#[tauri::command]
+pub async fn set_voice_config(app: AppHandle, config: VoiceConfig) -> Result<(), String> {
+    if config.commands.iter().any(|c| normalize(&c.phrase).is_empty() || c.action.is_empty()) {
+        return Err("Every voice command needs a phrase and an action".to_string());
+    }
+    let handle = app.clone();
+    let applied = config.clone();
+    tauri::async_runtime::spawn_blocking(move || apply(&handle, &applied))
+        .await
+        .map_err(|e| e.to_string())??;
+    storage::save_json(&app, CONFIG_FILE, &config)?;
+    *app.state::<VoiceControl>().config.lock().unwrap() = config;
+    Ok(())
+}
+
+/// Start listening as if the hotkey were pressed, for an on-screen push-to-talk button.
+#[tauri::command]
+pub fn start_voice_listening(app: AppHandle) {
+    start_listening(&app);
+}
+
+#[tauri::command]
+pub fn stop_voice_listening(app: AppHandle) {
+    stop_listening(&app);
+}
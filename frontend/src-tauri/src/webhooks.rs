@@ -0,0 +1,370 @@
+//! Outgoing webhooks: signed JSON events POSTed to endpoints the user configures, so team
+//! dashboards can follow sessions without polling.
+//!
+//! Events are queued in a persistent outbox (`webhook-outbox.json`) and delivered by a background
+//! worker, so a slow or unreachable endpoint never holds up the session, and nothing is lost to a
+//! restart. A failed delivery is retried with exponential backoff, from `FIRST_RETRY` up to
+//! `MAX_RETRY`, and dropped after `MAX_ATTEMPTS` with `webhook-failed` emitted.
+//!
+//! Each endpoint has its own secret, kept in the OS keychain and shown once when it is added.
+//! Requests carry `X-ShareCode-Signature: t=<unix seconds>,v1=<hex>`, the HMAC-SHA256 of
+//! `<t>.<body>` under that secret; receivers should check it and reject stale timestamps.
+
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::storage::{self, new_id};
+
+const ENDPOINTS_FILE: &str = "webhooks.json";
+const OUTBOX_FILE: &str = "webhook-outbox.json";
+const KEYCHAIN_SERVICE: &str = "sharecode-webhooks";
+const USER_AGENT: &str = "ShareCode-Webhooks";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const FIRST_RETRY: u64 = 10;
+const MAX_RETRY: u64 = 60 * 60;
+const MAX_ATTEMPTS: u32 = 10;
+/// The worker looks for due retries at least this often.
+const WORKER_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    SessionStarted,
+    SessionEnded,
+    ParticipantJoined,
+    ExportCreated,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub url: String,
+    /// Events to send; empty means all of them.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    pub enabled: bool,
+    pub created_at: u64,
+}
+
+impl WebhookEndpoint {
+    fn wants(&self, event: WebhookEvent) -> bool {
+        self.enabled && (self.events.is_empty() || self.events.contains(&event))
+    }
+}
+
+/// An endpoint as just added, with its secret. The secret is not shown again.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewWebhook {
+    pub endpoint: WebhookEndpoint,
+    pub secret: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: String,
+    pub endpoint_id: String,
+    pub event: WebhookEvent,
+    /// The exact body sent, so every retry signs and sends the same bytes.
+    pub body: String,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookFailed {
+    endpoint_id: String,
+    delivery_id: String,
+    event: WebhookEvent,
+    error: String,
+}
+
+/// Endpoints and the outbox are re-read from disk on every access, like the other stores.
+#[derive(Default)]
+pub struct Webhooks {
+    lock: Mutex<()>,
+    queued: Condvar,
+}
+
+impl Webhooks {
+    fn endpoints(&self, app: &AppHandle) -> Result<Vec<WebhookEndpoint>, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, ENDPOINTS_FILE)
+    }
+
+    fn update_endpoints<R>(
+        &self,
+        app: &AppHandle,
+        f: impl FnOnce(&mut Vec<WebhookEndpoint>) -> Result<R, String>,
+    ) -> Result<R, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut endpoints: Vec<WebhookEndpoint> = storage::load_json(app, ENDPOINTS_FILE)?;
+        let result = f(&mut endpoints)?;
+        storage::save_json(app, ENDPOINTS_FILE, &endpoints)?;
+        Ok(result)
+    }
+
+    fn outbox(&self, app: &AppHandle) -> Result<Vec<Delivery>, String> {
+        let _guard = self.lock.lock().unwrap();
+        storage::load_json(app, OUTBOX_FILE)
+    }
+
+    fn update_outbox<R>(&self, app: &AppHandle, f: impl FnOnce(&mut Vec<Delivery>) -> R) -> Result<R, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut outbox: Vec<Delivery> = storage::load_json(app, OUTBOX_FILE)?;
+        let result = f(&mut outbox);
+        storage::save_json(app, OUTBOX_FILE, &outbox)?;
+        Ok(result)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn secret_for(endpoint_id: &str) -> Result<String, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, endpoint_id)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| format!("Failed to read the webhook secret: {}", e))
+}
+
+fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    to_hex(&mac.finalize().into_bytes())
+}
+
+/// Plain HTTP only to this machine, where nothing on the network can read the events.
+fn check_url(url: &str) -> Result<(), String> {
+    let local = ["http://localhost", "http://127.0.0.1", "http://[::1]"];
+    let local = local
+        .iter()
+        .any(|prefix| url.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with([':', '/'])));
+    if url.starts_with("https://") || local {
+        Ok(())
+    } else {
+        Err("Webhook URLs must use https, except on localhost".to_string())
+    }
+}
+
+/// Queue `event` for every endpoint that wants it. Failing to queue never fails what triggered it.
+pub fn notify(app: &AppHandle, event: WebhookEvent, data: Value) {
+    let webhooks = app.state::<Webhooks>();
+    let endpoints = match webhooks.endpoints(app) {
+        Ok(endpoints) => endpoints,
+        Err(e) => {
+            log::warn!("Failed to read webhook endpoints: {}", e);
+            return;
+        }
+    };
+    let now = storage::now_secs();
+    let deliveries: Vec<Delivery> = endpoints
+        .iter()
+        .filter(|endpoint| endpoint.wants(event))
+        .map(|endpoint| {
+            let id = new_id();
+            let body = json!({ "id": id, "event": event, "createdAt": now, "data": data }).to_string();
+            Delivery {
+                id,
+                endpoint_id: endpoint.id.clone(),
+                event,
+                body,
+                attempts: 0,
+                next_attempt_at: now,
+                last_error: None,
+            }
+        })
+        .collect();
+    if deliveries.is_empty() {
+        return;
+    }
+    match webhooks.update_outbox(app, |outbox| outbox.extend(deliveries)) {
+        Ok(()) => webhooks.queued.notify_one(),
+        Err(e) => log::warn!("Failed to queue a webhook event: {}", e),
+    }
+}
+
+/// Queue `export-created` for a file just written. Only the file's name is sent, not where it is.
+pub fn export_created(app: &AppHandle, kind: &str, path: &Path) {
+    let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+    notify(app, WebhookEvent::ExportCreated, json!({ "kind": kind, "fileName": file_name }));
+}
+
+fn post(endpoint: &WebhookEndpoint, delivery: &Delivery) -> Result<(), String> {
+    let timestamp = storage::now_secs();
+    let signature = sign(&secret_for(&endpoint.id)?, timestamp, &delivery.body);
+    let event = serde_json::to_value(delivery.event).map_err(|e| e.to_string())?;
+    ureq::post(&endpoint.url)
+        .timeout(REQUEST_TIMEOUT)
+        .set("User-Agent", USER_AGENT)
+        .set("Content-Type", "application/json")
+        .set("X-ShareCode-Event", event.as_str().unwrap_or_default())
+        .set("X-ShareCode-Delivery", &delivery.id)
+        .set("X-ShareCode-Signature", &format!("t={},v1={}", timestamp, signature))
+        .send_string(&delivery.body)
+        .map(|_| ())
+        .map_err(|e| match e {
+            ureq::Error::Status(code, _) => format!("The endpoint answered {}", code),
+            e => e.to_string(),
+        })
+}
+
+fn backoff(attempts: u32) -> u64 {
+    FIRST_RETRY.saturating_mul(1 << attempts.min(16)).min(MAX_RETRY)
+}
+
+/// Try every delivery that is due, once.
+fn deliver_due(app: &AppHandle) -> Result<(), String> {
+    let webhooks = app.state::<Webhooks>();
+    let now = storage::now_secs();
+    let mut due = webhooks.outbox(app)?;
+    due.retain(|d| d.next_attempt_at <= now);
+    if due.is_empty() {
+        return Ok(());
+    }
+    let endpoints = webhooks.endpoints(app)?;
+
+    for delivery in due {
+        // Removed or disabled since the event was queued
+        let Some(endpoint) = endpoints.iter().find(|e| e.id == delivery.endpoint_id && e.enabled) else {
+            webhooks.update_outbox(app, |outbox| outbox.retain(|d| d.id != delivery.id))?;
+            continue;
+        };
+        let result = post(endpoint, &delivery);
+        let failed = webhooks.update_outbox(app, |outbox| {
+            let index = outbox.iter().position(|d| d.id == delivery.id)?;
+            let error = match result {
+                Ok(()) => {
+                    outbox.remove(index);
+                    return None;
+                }
+                Err(error) => error,
+            };
+            let entry = &mut outbox[index];
+            entry.attempts += 1;
+            if entry.attempts < MAX_ATTEMPTS {
+                entry.next_attempt_at = storage::now_secs() + backoff(entry.attempts - 1);
+                entry.last_error = Some(error);
+                return None;
+            }
+            outbox.remove(index);
+            Some(WebhookFailed {
+                endpoint_id: delivery.endpoint_id.clone(),
+                delivery_id: delivery.id.clone(),
+                event: delivery.event,
+                error,
+            })
+        })?;
+        if let Some(failed) = failed {
+            log::warn!("Gave up on webhook {} to {}: {}", failed.delivery_id, endpoint.url, failed.error);
+            if let Err(e) = app.emit("webhook-failed", failed) {
+                log::warn!("Failed to emit webhook-failed: {}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deliver queued events for the life of the app. Called once from setup.
+pub fn spawn_worker(app: AppHandle) {
+    thread::spawn(move || loop {
+        if let Err(e) = deliver_due(&app) {
+            log::warn!("Failed to deliver webhooks: {}", e);
+        }
+        let webhooks = app.state::<Webhooks>();
+        let guard = webhooks.lock.lock().unwrap();
+        drop(webhooks.queued.wait_timeout(guard, WORKER_INTERVAL).unwrap());
+    });
+}
+
+#[tauri::command]
+pub fn list_webhooks(app: AppHandle, webhooks: State<'_, Webhooks>) -> Result<Vec<WebhookEndpoint>, String> {
+    webhooks.endpoints(&app)
+}
+
+/// Add an endpoint for `events` (all of them if empty). Keep the returned secret to verify
+/// signatures with; it can't be read back later.
+#[tauri::command]
+pub fn add_webhook(
+    app: AppHandle,
+    webhooks: State<'_, Webhooks>,
+    url: String,
+    events: Vec<WebhookEvent>,
+) -> Result<NewWebhook, String> {
+    check_url(&url)?;
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let secret = to_hex(&bytes);
+    let endpoint = WebhookEndpoint {
+        id: new_id(),
+        url,
+        events,
+        enabled: true,
+        created_at: storage::now_secs(),
+    };
+    keyring::Entry::new(KEYCHAIN_SERVICE, &endpoint.id)
+        .and_then(|entry| entry.set_password(&secret))
+        .map_err(|e| format!("Failed to store the webhook secret: {}", e))?;
+    webhooks.update_endpoints(&app, |endpoints| {
+        endpoints.push(endpoint.clone());
+        Ok(())
+    })?;
+    Ok(NewWebhook { endpoint, secret })
+}
+
+/// Remove an endpoint, its secret and anything still queued for it.
+#[tauri::command]
+pub fn remove_webhook(app: AppHandle, webhooks: State<'_, Webhooks>, id: String) -> Result<bool, String> {
+    let removed = webhooks.update_endpoints(&app, |endpoints| {
+        let before = endpoints.len();
+        endpoints.retain(|e| e.id != id);
+        Ok(endpoints.len() != before)
+    })?;
+    webhooks.update_outbox(&app, |outbox| outbox.retain(|d| d.endpoint_id != id))?;
+    if let Ok(entry) = keyring::Entry::new(KEYCHAIN_SERVICE, &id) {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => log::warn!("Failed to remove a webhook secret: {}", e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Pause or resume sending to an endpoint. Events queued while it is disabled are dropped.
+#[tauri::command]
+pub fn set_webhook_enabled(
+    app: AppHandle,
+    webhooks: State<'_, Webhooks>,
+    id: String,
+    enabled: bool,
+) -> Result<WebhookEndpoint, String> {
+    webhooks.update_endpoints(&app, |endpoints| {
+        let endpoint = endpoints
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| format!("Unknown webhook {}", id))?;
+        endpoint.enabled = enabled;
+        Ok(endpoint.clone())
+    })
+}
+
+/// Events waiting to be delivered or retried.
+#[tauri::command]
+pub fn get_webhook_outbox(app: AppHandle, webhooks: State<'_, Webhooks>) -> Result<Vec<Delivery>, String> {
+    webhooks.outbox(&app)
+}
@@ -0,0 +1,266 @@
+//! A shared worker pool for CPU-heavy commands: highlighting benchmarks, blame, OCR and export
+//! rendering.
+//!
+//! Tasks wait in one queue per priority and are picked most urgent first, oldest first.
+//! Background tasks (exports) never take the last worker, so a long export cannot hold up
+//! interactive work, and none of it runs on the sharing fan-out's own threads. Queued tasks
+//! can be cancelled outright; running ones are asked to stop and check between steps.
+
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::storage::new_id;
+
+const MAX_WORKERS: usize = 8;
+const CANCELLED: &str = "Cancelled";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Someone is waiting on the result: blame, highlighting.
+    Interactive,
+    Normal,
+    /// Exports and benchmarks.
+    Background,
+}
+
+const PRIORITIES: [Priority; 3] = [Priority::Interactive, Priority::Normal, Priority::Background];
+
+/// Set when a task is cancelled; long tasks should `check` it between steps.
+#[derive(Clone, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// `Err` once the task has been cancelled, for `?` between steps.
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        Ok(())
+    }
+}
+
+struct Task {
+    id: String,
+    label: String,
+    priority: Priority,
+    queued_at: Instant,
+    cancel: Cancel,
+    job: Box<dyn FnOnce() + Send>,
+}
+
+struct Running {
+    label: String,
+    priority: Priority,
+    started_at: Instant,
+    cancel: Cancel,
+}
+
+#[derive(Default)]
+struct Queue {
+    waiting: [VecDeque<Task>; 3],
+    running: HashMap<String, Running>,
+}
+
+impl Queue {
+    /// The next task to run, leaving one worker free of background tasks.
+    fn next(&mut self, workers: usize) -> Option<Task> {
+        let background = self
+            .running
+            .values()
+            .filter(|r| r.priority == Priority::Background)
+            .count();
+        let background_limit = workers.saturating_sub(1).max(1);
+        PRIORITIES
+            .iter()
+            .filter(|&&p| p != Priority::Background || background < background_limit)
+            .find_map(|&p| self.waiting[p as usize].pop_front())
+    }
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    workers: usize,
+    completed: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+}
+
+impl Default for WorkerPool {
+    fn default() -> Self {
+        let workers = thread::available_parallelism().map_or(2, |n| n.get()).clamp(2, MAX_WORKERS);
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            workers,
+            completed: AtomicU64::new(0),
+            cancelled: AtomicU64::new(0),
+        });
+        for _ in 0..workers {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || work(&shared));
+        }
+        Self { shared }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let task = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(task) = queue.next(shared.workers) {
+                    let running = Running {
+                        label: task.label.clone(),
+                        priority: task.priority,
+                        started_at: Instant::now(),
+                        cancel: task.cancel.clone(),
+                    };
+                    queue.running.insert(task.id.clone(), running);
+                    break task;
+                }
+                queue = shared.ready.wait(queue).unwrap();
+            }
+        };
+        (task.job)();
+        let counter = if task.cancel.is_cancelled() {
+            &shared.cancelled
+        } else {
+            &shared.completed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        shared.queue.lock().unwrap().running.remove(&task.id);
+        // A background slot may have opened up.
+        shared.ready.notify_all();
+    }
+}
+
+impl WorkerPool {
+    /// Run `job` on the pool and wait for its result. `label` is shown in the worker stats.
+    pub async fn run<T: Send + 'static>(
+        &self,
+        priority: Priority,
+        label: &str,
+        job: impl FnOnce(&Cancel) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let cancel = Cancel::default();
+        let token = cancel.clone();
+        let failed = format!("{} failed unexpectedly", label);
+        let job = Box::new(move || {
+            let result = match token.check() {
+                Ok(()) => panic::catch_unwind(AssertUnwindSafe(|| job(&token))).unwrap_or(Err(failed)),
+                Err(e) => Err(e),
+            };
+            tx.send(result).ok();
+        });
+        let task = Task {
+            id: new_id(),
+            label: label.to_string(),
+            priority,
+            queued_at: Instant::now(),
+            cancel,
+            job,
+        };
+        self.shared.queue.lock().unwrap().waiting[priority as usize].push_back(task);
+        self.shared.ready.notify_one();
+        // A task dropped from the queue drops its sender too.
+        rx.await.map_err(|_| CANCELLED.to_string())?
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskInfo {
+    pub id: String,
+    pub label: String,
+    pub priority: Priority,
+    pub running: bool,
+    /// How long the task has been waiting, or running once it started.
+    pub elapsed_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueDepths {
+    pub interactive: usize,
+    pub normal: usize,
+    pub background: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStats {
+    pub workers: usize,
+    pub queued: QueueDepths,
+    pub tasks: Vec<TaskInfo>,
+    pub completed: u64,
+    pub cancelled: u64,
+}
+
+#[tauri::command]
+pub fn get_worker_stats(pool: State<'_, WorkerPool>) -> WorkerStats {
+    let queue = pool.shared.queue.lock().unwrap();
+    let mut tasks: Vec<TaskInfo> = queue
+        .running
+        .iter()
+        .map(|(id, r)| TaskInfo {
+            id: id.clone(),
+            label: r.label.clone(),
+            priority: r.priority,
+            running: true,
+            elapsed_ms: r.started_at.elapsed().as_millis() as u64,
+        })
+        .collect();
+    tasks.extend(queue.waiting.iter().flatten().map(|t| TaskInfo {
+        id: t.id.clone(),
+        label: t.label.clone(),
+        priority: t.priority,
+        running: false,
+        elapsed_ms: t.queued_at.elapsed().as_millis() as u64,
+    }));
+    WorkerStats {
+        workers: pool.shared.workers,
+        queued: QueueDepths {
+            interactive: queue.waiting[Priority::Interactive as usize].len(),
+            normal: queue.waiting[Priority::Normal as usize].len(),
+            background: queue.waiting[Priority::Background as usize].len(),
+        },
+        tasks,
+        completed: pool.shared.completed.load(Ordering::Relaxed),
+        cancelled: pool.shared.cancelled.load(Ordering::Relaxed),
+    }
+}
+
+/// Cancel a task from the worker stats. Returns `false` if it already finished.
+#[tauri::command]
+pub fn cancel_task(pool: State<'_, WorkerPool>, id: String) -> bool {
+    let mut queue = pool.shared.queue.lock().unwrap();
+    if let Some(running) = queue.running.get(&id) {
+        running.cancel.0.store(true, Ordering::Relaxed);
+        return true;
+    }
+    for waiting in &mut queue.waiting {
+        if let Some(at) = waiting.iter().position(|t| t.id == id) {
+            waiting.remove(at);
+            pool.shared.cancelled.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+    }
+    false
+}
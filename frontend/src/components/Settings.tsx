@@ -50,7 +50,10 @@ export function Settings() {
         // Apply privacy settings if in Tauri
         if (isTauri && supportsCaptureProtection) {
             try {
-                await setScreenCaptureProtection(hideFromCapture)
+                const protection = await setScreenCaptureProtection(hideFromCapture)
+                if (protection === 'monitor') {
+                    console.warn('This Windows version can only black out the window in captures, not hide it')
+                }
                 await setTaskbarVisibility(!hideFromTaskbar)
             } catch (error) {
                 console.error('Failed to apply privacy settings:', error)
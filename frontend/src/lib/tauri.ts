@@ -1,19 +1,55 @@
 import { invoke } from '@tauri-apps/api/core'
 
+/**
+ * How the window is kept out of captures: `exclude-from-capture` leaves it out entirely,
+ * `monitor` (Windows before 10 2004) captures it as a black rectangle.
+ */
+export type CaptureProtection = 'off' | 'exclude-from-capture' | 'monitor' | 'sharing-none'
+
 /**
  * Enable or disable screen capture protection
  * When enabled, the window will be hidden from screen recording software
  * @param enabled - true to hide from screen capture, false to allow capture
+ * @returns the mechanism that was applied
  */
-export async function setScreenCaptureProtection(enabled: boolean): Promise<void> {
+export async function setScreenCaptureProtection(enabled: boolean): Promise<CaptureProtection> {
     try {
-        await invoke('set_screen_capture_protection', { enabled })
+        return await invoke<CaptureProtection>('set_screen_capture_protection', { enabled })
     } catch (error) {
         console.error('Failed to set screen capture protection:', error)
         throw error
     }
 }
 
+/**
+ * The protection the OS is actually applying to the window. It can be dropped without the app
+ * asking (display changes, GPU driver resets), so this may differ from the last setting.
+ */
+export async function getScreenCaptureProtection(): Promise<CaptureProtection> {
+    return invoke<CaptureProtection>('get_screen_capture_protection')
+}
+
+/** Capture protection for windows as they open: none, always, or as the main window has it (the default). */
+export type ProtectionPolicy = 'off' | 'always' | 'follow-main'
+
+export async function getDefaultProtectionPolicy(): Promise<ProtectionPolicy> {
+    return invoke<ProtectionPolicy>('get_default_protection_policy')
+}
+
+/** Set the protection new windows get. Windows already open keep theirs. */
+export async function setDefaultProtectionPolicy(policy: ProtectionPolicy): Promise<void> {
+    return invoke<void>('set_default_protection_policy', { policy })
+}
+
+/**
+ * Payload of `capture-protection-lost`: the OS dropped the window's protection (display change,
+ * session unlock) and putting it back failed, so the window may be visible in captures.
+ */
+export interface CaptureProtectionLost {
+    window: string
+    error: string
+}
+
 /**
  * Show or hide the application from taskbar (Windows) or dock (macOS)
  * @param visible - true to show in taskbar/dock, false to hide
@@ -27,6 +63,133 @@ export async function setTaskbarVisibility(visible: boolean): Promise<void> {
     }
 }
 
+/**
+ * Show or hide the app in Alt+Tab/Cmd-Tab and Task View/Mission Control. Hiding also hides it
+ * from the taskbar or dock, which the OS offers no way to keep. Not supported on Linux.
+ */
+export async function setSwitcherVisibility(visible: boolean): Promise<void> {
+    return invoke<void>('set_switcher_visibility', { visible })
+}
+
+/** Result of `panicHide`/`panicRestore`, also sent as the `panic-hide-changed` event. */
+export interface PanicOutcome {
+    hidden: boolean
+    /** Steps that could not be applied, e.g. capture protection where the OS has none. */
+    errors: string[]
+}
+
+/**
+ * Hide every window, protect them from capture, leave the taskbar or dock and mute speech, in one
+ * call. Hold back in-app notifications until `panic-hide-changed` reports `hidden: false`.
+ */
+export async function panicHide(): Promise<PanicOutcome> {
+    return invoke<PanicOutcome>('panic_hide')
+}
+
+/** Undo `panicHide`, leaving alone whatever was already hidden, protected or muted before it. */
+export async function panicRestore(): Promise<PanicOutcome> {
+    return invoke<PanicOutcome>('panic_restore')
+}
+
+export type TrayAction = 'show-window' | 'hide-from-capture' | 'hide-from-taskbar' | 'click-through' | 'panic-hide'
+
+/**
+ * Payload of `tray-triggered`, sent after each action chosen from the tray icon's menu.
+ * `error` is set if it could not be applied.
+ */
+export interface TrayTriggered {
+    action: TrayAction
+    error: string | null
+}
+
+export type HotkeyAction =
+    | 'toggle-capture-protection'
+    | 'toggle-taskbar'
+    | 'hide-window'
+    | 'opacity-up'
+    | 'opacity-down'
+    | 'toggle-click-through'
+    | 'panic-hide'
+    | 'approve-remote-action'
+    | 'deny-remote-action'
+
+export interface HotkeyBinding {
+    action: HotkeyAction
+    /** Accelerator such as `CommandOrControl+Alt+H`. */
+    shortcut: string
+}
+
+export interface HotkeyConfig {
+    enabled: boolean
+    bindings: HotkeyBinding[]
+    /** How much one opacity step changes the window's opacity, 0 to 1. */
+    opacityStep: number
+}
+
+/**
+ * Payload of `hotkey-triggered`, sent after each press of a system-wide hotkey with the state it
+ * left the main window in. `error` is set if the action could not be applied.
+ */
+export interface HotkeyTriggered {
+    action: HotkeyAction
+    captureProtection: CaptureProtection
+    taskbarVisible: boolean
+    windowVisible: boolean
+    opacity: number
+    clickThrough: boolean
+    error: string | null
+}
+
+export async function getHotkeyConfig(): Promise<HotkeyConfig> {
+    return invoke<HotkeyConfig>('get_hotkey_config')
+}
+
+/** Save the hotkeys and bind them system-wide; nothing is saved if a shortcut can't be bound. */
+export async function setHotkeyConfig(config: HotkeyConfig): Promise<void> {
+    return invoke<void>('set_hotkey_config', { config })
+}
+
+/**
+ * Let mouse clicks pass through the window to whatever is beneath it, to use it as an overlay.
+ * The window can't be clicked while this is on; the tray menu and hotkeys can turn it off.
+ */
+export async function setClickThrough(enabled: boolean): Promise<void> {
+    return invoke<void>('set_click_through', { enabled })
+}
+
+export async function getClickThrough(): Promise<boolean> {
+    return invoke<boolean>('get_click_through')
+}
+
+/** Show the window under another application's title, or its own with null. Recorded in transparency reports. */
+export async function setWindowDisguise(title: string | null): Promise<void> {
+    return invoke<void>('set_window_disguise', { title })
+}
+
+/** Fade the window, from 0.2 to 1 (opaque). Not supported on Linux. */
+export async function setWindowOpacity(alpha: number): Promise<void> {
+    return invoke<void>('set_window_opacity', { alpha })
+}
+
+export async function getWindowOpacity(): Promise<number> {
+    return invoke<number>('get_window_opacity')
+}
+
+export interface OverlayMode {
+    alwaysOnTop: boolean
+    /** On every virtual desktop or Space; always false on Windows. */
+    allWorkspaces: boolean
+}
+
+/** Keep the window above all others and on every workspace. */
+export async function setOverlayMode(enabled: boolean): Promise<OverlayMode> {
+    return invoke<OverlayMode>('set_overlay_mode', { enabled })
+}
+
+export async function getOverlayMode(): Promise<OverlayMode> {
+    return invoke<OverlayMode>('get_overlay_mode')
+}
+
 /**
  * Check if we're running in Tauri environment
  */
@@ -40,6 +203,2402 @@ export function isTauriApp(): boolean {
 export function isScreenCaptureProtectionSupported(): boolean {
     if (!isTauriApp()) return false
 
-    // Only Windows and macOS are supported
+    // Only Windows and macOS are supported; on Linux the command explains why it cannot hide the window
     return navigator.platform.includes('Win') || navigator.platform.includes('Mac')
 }
+
+export interface BackupConfig {
+    enabled: boolean
+    intervalMinutes: number
+    onExit: boolean
+    keepLast: number
+    maxAgeDays: number | null
+}
+
+export interface BackupInfo {
+    path: string
+    createdAt: number
+    size: number
+}
+
+export async function getBackupConfig(): Promise<BackupConfig> {
+    return invoke<BackupConfig>('get_backup_config')
+}
+
+export async function setBackupConfig(config: BackupConfig): Promise<void> {
+    await invoke('set_backup_config', { config })
+}
+
+export async function createBackup(): Promise<BackupInfo> {
+    return invoke<BackupInfo>('create_backup')
+}
+
+export async function listBackups(): Promise<BackupInfo[]> {
+    return invoke<BackupInfo[]>('list_backups')
+}
+
+/**
+ * Restore an encrypted backup archive. The archive is fully validated before
+ * any file is overwritten, and the current state is backed up first. The app
+ * restarts once the restored files are in place.
+ */
+export async function restoreBackup(path: string): Promise<void> {
+    await invoke('restore_backup', { path })
+}
+
+export interface Snippet {
+    id: string
+    title: string
+    prefix: string | null
+    body: string
+    language: string | null
+    description: string | null
+    tags: string[]
+    source: string | null
+    createdAt: number
+    updatedAt: number
+}
+
+export type SnippetInput = Partial<Pick<Snippet, 'id' | 'prefix' | 'language' | 'description' | 'tags' | 'source'>> &
+    Pick<Snippet, 'title' | 'body'>
+
+export async function listSnippets(): Promise<Snippet[]> {
+    return invoke<Snippet[]>('list_snippets')
+}
+
+export async function getSnippet(id: string): Promise<Snippet> {
+    return invoke<Snippet>('get_snippet', { id })
+}
+
+export interface SnippetFilters {
+    language?: string
+    /** Snippets must carry every one of these tags. */
+    tags?: string[]
+    limit?: number
+}
+
+export interface SnippetHighlight {
+    field: 'title' | 'prefix' | 'description' | 'body'
+    /** UTF-16 offsets into the field. */
+    start: number
+    end: number
+}
+
+export interface SnippetMatch {
+    snippet: Snippet
+    score: number
+    highlights: SnippetHighlight[]
+}
+
+/** Full-text search of the library, every word as a prefix, best match first. */
+export async function searchSnippets(query: string, filters?: SnippetFilters): Promise<SnippetMatch[]> {
+    return invoke<SnippetMatch[]>('search_snippets', { query, filters })
+}
+
+export async function saveSnippet(snippet: SnippetInput): Promise<Snippet> {
+    return invoke<Snippet>('save_snippet', { snippet })
+}
+
+export async function deleteSnippet(id: string): Promise<boolean> {
+    return invoke<boolean>('delete_snippet', { id })
+}
+
+/** Send a snippet to everyone in the hosted session; it arrives as `snippet-received`. */
+export async function broadcastSnippet(id: string): Promise<void> {
+    return invoke<void>('broadcast_snippet', { id })
+}
+
+export type ReleaseItem = { kind: 'snippet'; snippetId: string } | { kind: 'message'; text: string }
+
+/** Sent as `release-sent`; `release-failed` carries `{ release, error }`. */
+export interface Release {
+    id: string
+    item: ReleaseItem
+    /** Seconds since the Unix epoch. */
+    at: number | null
+    trigger: string | null
+    createdAt: number
+}
+
+/**
+ * Queue a snippet or chat message to go out at `at`, when `trigger` is pulled, or whichever comes
+ * first. Due messages arrive as `release-message` (`{ id, text }`) for the chat to send.
+ */
+export async function scheduleRelease(item: ReleaseItem, at?: number, trigger?: string): Promise<Release> {
+    return invoke<Release>('schedule_release', { item, at, trigger })
+}
+
+/** Everything queued, soonest first. */
+export async function listReleases(): Promise<Release[]> {
+    return invoke<Release[]>('list_releases')
+}
+
+export async function cancelRelease(id: string): Promise<boolean> {
+    return invoke<boolean>('cancel_release', { id })
+}
+
+/** Release everything queued on `trigger` now. Needs a hosted session. */
+export async function pullReleaseTrigger(trigger: string): Promise<Release[]> {
+    return invoke<Release[]>('pull_release_trigger', { trigger })
+}
+
+export type SnippetImportFormat = 'vscode' | 'sublime' | 'textexpander' | 'textexpander-csv'
+
+export interface ImportReport {
+    imported: number
+    skipped: string[]
+}
+
+/**
+ * Import snippets from a VS Code, Sublime or TextExpander file into the library.
+ * The format is detected from the file extension unless given explicitly.
+ */
+export async function importSnippets(path: string, format?: SnippetImportFormat): Promise<ImportReport> {
+    return invoke<ImportReport>('import_snippets', { path, format })
+}
+
+export interface ExpandContext {
+    filePath?: string
+    language?: string
+    selectedText?: string
+    currentLine?: string
+    currentWord?: string
+    lineIndex?: number
+    clipboard?: string
+}
+
+export interface TabStopRange {
+    index: number
+    start: number
+    end: number
+    choices?: string[]
+}
+
+/** Expanded snippet text; tab-stop offsets are UTF-16 code units, in visit order. */
+export interface Expansion {
+    text: string
+    tabStops: TabStopRange[]
+}
+
+export async function expandTemplate(template: string, context?: ExpandContext): Promise<Expansion> {
+    return invoke<Expansion>('expand_template', { template, context })
+}
+
+export async function expandSnippet(id: string, context?: ExpandContext): Promise<Expansion> {
+    return invoke<Expansion>('expand_snippet', { id, context })
+}
+
+export interface LanguageProfile {
+    tabWidth: number
+    insertSpaces: boolean
+    theme: string | null
+    formatter: string | null
+    runCommand: string | null
+}
+
+export interface DocumentInfo {
+    id: string
+    path: string | null
+    language: string | null
+    profile: LanguageProfile
+    length: number
+    /** Frozen documents reject edits until unfrozen. */
+    frozen: boolean
+}
+
+export async function getLanguageProfile(language: string): Promise<LanguageProfile> {
+    return invoke<LanguageProfile>('get_language_profile', { language })
+}
+
+/**
+ * Override the profile for a language. Pass null to revert to the shipped default.
+ */
+export async function setLanguageProfile(language: string, profile: LanguageProfile | null): Promise<LanguageProfile> {
+    return invoke<LanguageProfile>('set_language_profile', { language, profile })
+}
+
+export interface LanguageGuess {
+    /** `null` when the text doesn't look like any language in particular. */
+    language: string | null
+    extension: string | null
+    /** 0 to 1. */
+    confidence: number
+    /** Other likely languages, most likely first. */
+    alternatives: string[]
+}
+
+/** Guess the language of pasted code from its shebang, typical constructs and whether it parses. */
+export async function detectLanguage(source: string): Promise<LanguageGuess> {
+    return invoke<LanguageGuess>('detect_language', { source })
+}
+
+/** Sent as `clipboard-code` while the clipboard is watched. */
+export interface ClipboardCode {
+    text: string
+    guess: LanguageGuess
+}
+
+/** Watch the system clipboard for copied code (desktop only). Returns false if already watching. */
+export async function startClipboardWatch(): Promise<boolean> {
+    return invoke<boolean>('start_clipboard_watch')
+}
+
+export async function stopClipboardWatch(): Promise<boolean> {
+    return invoke<boolean>('stop_clipboard_watch')
+}
+
+export async function openDocument(path: string): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('open_document', { path })
+}
+
+export async function createDocument(text: string, language?: string): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('create_document', { text, language })
+}
+
+export async function getDocumentText(id: string): Promise<string> {
+    return invoke<string>('get_document_text', { id })
+}
+
+/** Replace a document's text; `author` attributes the change in the undo history. */
+export async function updateDocumentText(id: string, text: string, author?: string): Promise<void> {
+    await invoke('update_document_text', { id, text, author })
+}
+
+export async function closeDocument(id: string): Promise<boolean> {
+    return invoke<boolean>('close_document', { id })
+}
+
+/**
+ * Change a document's language. The backend applies the language profile and
+ * emits `document-language-changed` with the effective settings.
+ */
+export async function setDocumentLanguage(id: string, language: string): Promise<LanguageProfile> {
+    return invoke<LanguageProfile>('set_document_language', { id, language })
+}
+
+export type TextEncoding = 'utf8' | 'utf16le' | 'utf16be' | 'latin1'
+export type LineEnding = 'lf' | 'crlf' | 'cr' | 'mixed'
+
+export interface DocumentFormat {
+    encoding: TextEncoding
+    bom: boolean
+    eol: LineEnding
+    lineEndings: { lf: number; crlf: number; cr: number }
+}
+
+export async function getDocumentFormat(id: string): Promise<DocumentFormat> {
+    return invoke<DocumentFormat>('get_document_format', { id })
+}
+
+export async function convertDocument(
+    id: string,
+    options: { eol?: LineEnding; encoding?: TextEncoding; bom?: boolean }
+): Promise<DocumentFormat> {
+    return invoke<DocumentFormat>('convert_document', { id, ...options })
+}
+
+/**
+ * Save a document with its original encoding and line endings.
+ * @param path - optional new location; defaults to the path it was opened from
+ */
+export async function saveDocument(id: string, path?: string): Promise<void> {
+    await invoke('save_document', { id, path })
+}
+
+export interface LargeFileInfo {
+    id: string
+    path: string
+    size: number
+    indexedLines: number
+    complete: boolean
+}
+
+export interface LineWindow {
+    startLine: number
+    lines: string[]
+    atEnd: boolean
+}
+
+/**
+ * Open a file read-only through a memory map. Lines are indexed on demand;
+ * call indexLargeFile to count them in the background (emits `large-file-indexed`).
+ */
+export async function openLargeFile(path: string): Promise<LargeFileInfo> {
+    return invoke<LargeFileInfo>('open_large_file', { path })
+}
+
+export async function indexLargeFile(id: string): Promise<void> {
+    await invoke('index_large_file', { id })
+}
+
+export async function getLargeFileInfo(id: string): Promise<LargeFileInfo> {
+    return invoke<LargeFileInfo>('get_large_file_info', { id })
+}
+
+export async function readLargeFileLines(id: string, startLine: number, count: number): Promise<LineWindow> {
+    return invoke<LineWindow>('read_large_file_lines', { id, startLine, count })
+}
+
+export async function readLargeFileRange(id: string, offset: number, length: number): Promise<string> {
+    return invoke<string>('read_large_file_range', { id, offset, length })
+}
+
+/** Copy a line range of a large file into a regular, shareable document. */
+export async function extractLargeFileRegion(id: string, startLine: number, endLine: number): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('extract_large_file_region', { id, startLine, endLine })
+}
+
+export async function closeLargeFile(id: string): Promise<boolean> {
+    return invoke<boolean>('close_large_file', { id })
+}
+
+export type SymbolKind =
+    | 'function' | 'method' | 'class' | 'struct' | 'enum'
+    | 'interface' | 'module' | 'type' | 'constant' | 'macro'
+
+/** Zero-based positions; columns are byte offsets within the line. */
+export interface TextRange {
+    startLine: number
+    startColumn: number
+    endLine: number
+    endColumn: number
+    startByte: number
+    endByte: number
+}
+
+export interface CodeSymbol {
+    name: string
+    kind: SymbolKind
+    range: TextRange
+    nameRange: TextRange
+    container: string | null
+}
+
+export interface SymbolLocation {
+    file: string
+    symbol: CodeSymbol
+}
+
+/**
+ * Import a project directory and index its symbols in the background.
+ * Progress is reported through `index-progress` events. Resolves with the number of files queued.
+ */
+export async function importProject(root: string): Promise<number> {
+    return invoke<number>('import_project', { root })
+}
+
+export async function listSymbols(file: string): Promise<CodeSymbol[]> {
+    return invoke<CodeSymbol[]>('list_symbols', { file })
+}
+
+export async function gotoDefinition(symbol: string, fromFile?: string): Promise<SymbolLocation[]> {
+    return invoke<SymbolLocation[]>('goto_definition', { symbol, fromFile })
+}
+
+export interface FoldRange {
+    startLine: number
+    endLine: number
+    kind: 'region' | 'comment'
+}
+
+/** A selectable range; offsets are UTF-16 code units into the text that was sent. */
+export interface SelectionRange {
+    start: number
+    end: number
+    kind: string
+}
+
+export async function getFoldRanges(language: string, text: string): Promise<FoldRange[]> {
+    return invoke<FoldRange[]>('get_fold_ranges', { language, text })
+}
+
+/** Syntax ranges enclosing the selection, innermost first, for expand-selection steps. */
+export async function expandSelection(language: string, text: string, start: number, end: number): Promise<SelectionRange[]> {
+    return invoke<SelectionRange[]>('expand_selection', { language, text, start, end })
+}
+
+/** The function (or other symbol kinds) around an offset, for "share just this function". */
+export async function selectEnclosingSymbol(
+    language: string,
+    text: string,
+    offset: number,
+    kinds?: SymbolKind[]
+): Promise<SelectionRange | null> {
+    return invoke<SelectionRange | null>('select_enclosing_symbol', { language, text, offset, kinds })
+}
+
+export interface SharedSymbol {
+    document: DocumentInfo
+    tracking: boolean
+}
+
+/**
+ * Extract a function or class, with its imports and doc comment, into a new document.
+ * With track=true the document follows edits to the source file (`shared-symbol-updated`).
+ * @param symbol - `name` or `Container::name`
+ */
+export async function shareSymbol(file: string, symbol: string, track = false): Promise<SharedSymbol> {
+    return invoke<SharedSymbol>('share_symbol', { file, symbol, track })
+}
+
+export async function stopTrackingSymbol(id: string): Promise<boolean> {
+    return invoke<boolean>('stop_tracking_symbol', { id })
+}
+
+export interface CallEdge {
+    name: string
+    container: string | null
+    file: string
+    callRange: TextRange
+    definitions: SymbolLocation[]
+}
+
+/** Who calls this? Requires an imported project (see importProject). */
+export async function getCallers(symbol: string): Promise<CallEdge[]> {
+    return invoke<CallEdge[]>('get_callers', { symbol })
+}
+
+/** What does this function call? `symbol` may be `name` or `Container::name`. */
+export async function getCallees(symbol: string): Promise<CallEdge[]> {
+    return invoke<CallEdge[]>('get_callees', { symbol })
+}
+
+export interface BlameLine {
+    line: number
+    commit: string
+    author: string
+    authorEmail: string
+    timestamp: number
+    ageSeconds: number
+    summary: string
+    uncommitted: boolean
+}
+
+/**
+ * Last-change info per line from git blame (cached until the file or HEAD changes).
+ * @param range - one-based inclusive line range; omit for the whole file
+ */
+export async function blame(path: string, range?: { start: number; end: number }): Promise<BlameLine[]> {
+    return invoke<BlameLine[]>('blame', { path, range })
+}
+
+export interface ReviewFile {
+    path: string
+    oldPath: string | null
+    status: 'added' | 'removed' | 'modified' | 'renamed' | string
+    patch: string | null
+}
+
+export interface ReviewComment {
+    id: string
+    path: string | null
+    line: number | null
+    author: string
+    body: string
+    createdAt: string
+    replyTo: string | null
+}
+
+export interface Review {
+    id: string
+    provider: 'github' | 'gitlab'
+    url: string
+    title: string
+    description: string
+    author: string
+    files: ReviewFile[]
+    comments: ReviewComment[]
+    /** Document holding the combined diff. */
+    documentId: string
+}
+
+/** Fetch a GitHub pull request or GitLab merge request by URL into a review. */
+export async function openReview(url: string): Promise<Review> {
+    return invoke<Review>('open_review', { url })
+}
+
+/** Post a comment; pass `path` and `line` (new-file line) to comment on a diff line. */
+export async function postReviewComment(
+    reviewId: string,
+    body: string,
+    path?: string,
+    line?: number,
+): Promise<ReviewComment> {
+    return invoke<ReviewComment>('post_review_comment', { reviewId, body, path, line })
+}
+
+/** Store the API token for a host (e.g. `github.com`) in the OS keychain; `null` removes it. */
+export async function setReviewToken(host: string, token: string | null): Promise<void> {
+    return invoke<void>('set_review_token', { host, token })
+}
+
+export async function hasReviewToken(host: string): Promise<boolean> {
+    return invoke<boolean>('has_review_token', { host })
+}
+
+export async function closeReview(reviewId: string): Promise<boolean> {
+    return invoke<boolean>('close_review', { reviewId })
+}
+
+export interface PatchFileStat {
+    path: string
+    /** null for binary files */
+    added: number | null
+    removed: number | null
+}
+
+export interface PatchResult {
+    files: PatchFileStat[]
+    conflicts: string[]
+    applied: boolean
+}
+
+/**
+ * Apply a diff shared as a document to the local work tree.
+ * Conflicting patches are reported and left unapplied; `dryRun` only previews.
+ */
+export async function applySharedPatch(docId: string, targetPath: string, dryRun?: boolean): Promise<PatchResult> {
+    return invoke<PatchResult>('apply_shared_patch', { docId, targetPath, dryRun })
+}
+
+export interface HistoryState {
+    text: string
+    canUndo: boolean
+    canRedo: boolean
+    /** Author of the step that was undone or redone. */
+    author: string | null
+}
+
+export interface HistoryEntry {
+    id: number
+    parent: number | null
+    author: string | null
+    startedAt: number
+    updatedAt: number
+    current: boolean
+}
+
+export async function undoDocument(id: string): Promise<HistoryState> {
+    return invoke<HistoryState>('undo_document', { id })
+}
+
+/** Redo the last undone step, or follow `branch` (a child id from getDocumentHistory). */
+export async function redoDocument(id: string, branch?: number): Promise<HistoryState> {
+    return invoke<HistoryState>('redo_document', { id, branch })
+}
+
+/** The document's undo tree, for rendering a history/branch view. */
+export async function getDocumentHistory(id: string): Promise<HistoryEntry[]> {
+    return invoke<HistoryEntry[]>('get_document_history', { id })
+}
+
+/**
+ * Make a document read-only for everyone until unfrozen; emits `document-frozen` and tells
+ * participants with a `documentFrozen` message.
+ */
+export async function freezeDocument(id: string): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('freeze_document', { id })
+}
+
+export async function unfreezeDocument(id: string): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('unfreeze_document', { id })
+}
+
+export interface TimelineEntry {
+    seq: number
+    /** Milliseconds since the Unix epoch. */
+    timestamp: number
+    author: string | null
+}
+
+export interface Revision {
+    seq: number
+    timestamp: number | null
+    author: string | null
+    text: string
+}
+
+/** Every recorded change to a document, oldest first, for driving a history scrubber. */
+export async function getDocumentTimeline(id: string): Promise<TimelineEntry[]> {
+    return invoke<TimelineEntry[]>('get_document_timeline', { id })
+}
+
+/** The document as it was right after change `seq` (0 is the text it was opened with). */
+export async function getDocumentAt(id: string, seq: number): Promise<Revision> {
+    return invoke<Revision>('get_document_at', { id, seq })
+}
+
+export type ParticipantRole = 'host' | 'editor' | 'viewer'
+
+export interface Participant {
+    id: string
+    name: string
+    role: ParticipantRole
+    handRaised: boolean
+    joinedAt: number
+    /** Cursor colour from the selected colour-vision palette. */
+    color: string
+}
+
+export type Signal = 'raise-hand' | 'lower-hand' | 'thumbs-up' | 'go-slower' | 'go-faster' | 'confused'
+
+/** Protocol message; outgoing ones are emitted in a `session-message` envelope for the transport to deliver. */
+export type SessionMessage =
+    | { type: 'signal'; participantId: string; signal: Signal; at: number }
+    | { type: 'pollUpdated'; poll: PollTally }
+    | { type: 'vote'; pollId: string; participantId: string; option: number }
+    | { type: 'scratchpad'; participantId: string; text: string; language: string | null }
+    | { type: 'interviewTask'; task: RevealedTask }
+    | { type: 'snapshot'; documentId: string; seq: number; text: string; language: string | null; frozen?: boolean }
+    | { type: 'patch'; documentId: string; seq: number; start: number; deleteCount: number; insert: string }
+    | { type: 'magnifier'; region: FocusRegion | null }
+    | { type: 'sessionEnded'; reason: string }
+    | { type: 'snippet'; snippet: PortableSnippet }
+    | { type: 'documentFrozen'; documentId: string; frozen: boolean }
+
+/** Payload of `session-message`: deliver `message` to `to`, or to everyone when null. */
+export interface SessionEnvelope {
+    to: string | null
+    message: SessionMessage
+}
+
+export async function addParticipant(name: string, role: ParticipantRole, id?: string): Promise<Participant> {
+    return invoke<Participant>('add_participant', { id, name, role })
+}
+
+export async function removeParticipant(id: string): Promise<boolean> {
+    return invoke<boolean>('remove_participant', { id })
+}
+
+export async function listParticipants(): Promise<Participant[]> {
+    return invoke<Participant[]>('list_participants')
+}
+
+/**
+ * Raise/lower a hand or send a reaction. Returns false when the signal was rate-limited
+ * or changed nothing; listeners receive it otherwise, in the array payload of the next
+ * `participant-signal` event ({ participantId, name, signal, at }[]).
+ */
+export async function sendSignal(participantId: string, signal: Signal): Promise<boolean> {
+    return invoke<boolean>('send_signal', { participantId, signal })
+}
+
+/** Hand a message received from the network to the backend for routing. */
+export async function receiveMessage(message: SessionMessage): Promise<boolean> {
+    return invoke<boolean>('receive_message', { message })
+}
+
+export interface PollTally {
+    id: string
+    question: string
+    options: string[]
+    counts: number[]
+    total: number
+    anonymous: boolean
+    createdAt: number
+    closedAt: number | null
+    /** Participant name -> option index; null for anonymous polls and on participants' side. */
+    voters: Record<string, number> | null
+}
+
+export type SessionRecord = ({ kind: 'poll' } & PollTally) | ({ kind: 'time' } & SessionTime)
+
+/** Open a poll; live tallies are emitted as `poll-updated`. */
+export async function createPoll(question: string, options: string[], anonymous?: boolean): Promise<PollTally> {
+    return invoke<PollTally>('create_poll', { question, options, anonymous })
+}
+
+/** Vote once per poll; returns null when the vote was forwarded to the host. */
+export async function votePoll(pollId: string, participantId: string, option: number): Promise<PollTally | null> {
+    return invoke<PollTally | null>('vote_poll', { pollId, participantId, option })
+}
+
+/** Close a poll and store its result in the session history. */
+export async function closePoll(pollId: string): Promise<PollTally> {
+    return invoke<PollTally>('close_poll', { pollId })
+}
+
+export async function listPolls(): Promise<PollTally[]> {
+    return invoke<PollTally[]>('list_polls')
+}
+
+export async function getSessionHistory(): Promise<SessionRecord[]> {
+    return invoke<SessionRecord[]>('get_session_history')
+}
+
+export interface ParticipantView {
+    participantId: string
+    name: string
+    receivedSeq: number | null
+    viewedSeq: number | null
+    /** Has seen the current version. */
+    caughtUp: boolean
+}
+
+/** Payload of `view-status-changed`. */
+export interface ViewStatus {
+    documentId: string
+    seq: number
+    participants: ParticipantView[]
+    allCaughtUp: boolean
+}
+
+/** Which version of a shared document each participant has received and seen. */
+export async function getViewStatus(docId: string): Promise<ViewStatus> {
+    return invoke<ViewStatus>('get_view_status', { docId })
+}
+
+export type RemoteAction =
+    | { kind: 'apply-patch'; documentId: string; targetPath: string }
+    | { kind: 'run-document'; documentId: string; stdin?: string }
+
+export interface AuditEntry {
+    requestId: string
+    participantId: string
+    participantName: string
+    action: RemoteAction
+    /** Timeline position of the document the host was asked about; the action ran on it. */
+    seq: number | null
+    decision: 'approved' | 'denied' | 'timed-out' | 'refused'
+    at: number
+    error: string | null
+}
+
+/**
+ * Remote actions participants asked for and what the host decided, oldest first. Requests are
+ * answered natively or by hotkey; the webview only sees `remote-action-requested` and `-settled`.
+ */
+export async function getAuditLog(): Promise<AuditEntry[]> {
+    return invoke<AuditEntry[]>('get_audit_log')
+}
+
+export interface Scratchpad {
+    participantId: string
+    participantName: string
+    document: DocumentInfo
+}
+
+/** Give a participant a private scratchpad held by the host; edits arrive as `scratchpad-updated`. */
+export async function openScratchpad(participantId: string, text?: string, language?: string): Promise<Scratchpad> {
+    return invoke<Scratchpad>('open_scratchpad', { participantId, text, language })
+}
+
+export async function listScratchpads(): Promise<Scratchpad[]> {
+    return invoke<Scratchpad[]>('list_scratchpads')
+}
+
+export async function viewScratchpad(participantId: string): Promise<string> {
+    return invoke<string>('view_scratchpad', { participantId })
+}
+
+/**
+ * Copy a scratchpad into the main document.
+ * @param offset - UTF-16 insert position, used with mode `insert`
+ */
+export async function pullScratchpad(
+    participantId: string,
+    targetId: string,
+    mode: 'append' | 'replace' | 'insert',
+    offset?: number,
+): Promise<DocumentInfo> {
+    return invoke<DocumentInfo>('pull_scratchpad', { participantId, targetId, mode, offset })
+}
+
+export async function closeScratchpad(participantId: string): Promise<boolean> {
+    return invoke<boolean>('close_scratchpad', { participantId })
+}
+
+export interface RunOutput {
+    /** null if the process was killed (timeout or signal) */
+    exitCode: number | null
+    stdout: string
+    stderr: string
+    timedOut: boolean
+    truncated: boolean
+    durationMs: number
+}
+
+/** Run a snippet in a temporary directory with the language's run command. */
+export async function runCode(language: string, code: string, stdin?: string, timeoutMs?: number): Promise<RunOutput> {
+    return invoke<RunOutput>('run_code', { language, code, stdin, timeoutMs })
+}
+
+export interface TestCase {
+    name: string
+    stdin: string
+    expectedStdout: string
+    points: number
+    hidden: boolean
+}
+
+export interface RevealedTask {
+    index: number
+    id: string
+    title: string
+    prompt: string
+    starterCode: string
+    language: string
+    /** Visible tests only. */
+    examples: TestCase[]
+}
+
+export interface TaskReveal {
+    task: RevealedTask
+    document: DocumentInfo
+}
+
+export interface TestResult {
+    name: string
+    hidden: boolean
+    passed: boolean
+    points: number
+    /** null for hidden tests */
+    output: RunOutput | null
+}
+
+export interface TestRun {
+    taskId: string
+    passed: number
+    total: number
+    points: number
+    maxPoints: number
+    results: TestResult[]
+}
+
+export interface RubricScore {
+    criterion: string
+    points: number
+    maxPoints: number
+    note: string | null
+}
+
+export interface TaskReport {
+    id: string
+    title: string
+    revealed: boolean
+    testPoints: number
+    testMaxPoints: number
+    testsPassed: number
+    testsTotal: number
+    rubric: RubricScore[]
+    finalCode: string | null
+}
+
+export interface InterviewReport {
+    title: string
+    candidate: string | null
+    startedAt: number
+    finishedAt: number
+    tasks: TaskReport[]
+    points: number
+    maxPoints: number
+}
+
+export interface InterviewOverview {
+    title: string
+    taskCount: number
+    revealed: number
+}
+
+/** Load an interview pack (tasks, starter code, hidden tests, rubric) from a JSON file. */
+export async function loadInterviewPack(path: string, candidate?: string): Promise<InterviewOverview> {
+    return invoke<InterviewOverview>('load_interview_pack', { path, candidate })
+}
+
+/** Reveal the next task to participants and open its starter code as a document. */
+export async function revealNextTask(): Promise<TaskReveal> {
+    return invoke<TaskReveal>('reveal_next_task')
+}
+
+export async function runInterviewTests(taskId: string): Promise<TestRun> {
+    return invoke<TestRun>('run_interview_tests', { taskId })
+}
+
+export async function scoreRubric(taskId: string, criterion: string, points: number, note?: string): Promise<void> {
+    return invoke<void>('score_rubric', { taskId, criterion, points, note })
+}
+
+export async function finishInterview(): Promise<InterviewReport> {
+    return invoke<InterviewReport>('finish_interview')
+}
+
+export async function exportInterviewReport(path: string, format?: 'json' | 'markdown'): Promise<void> {
+    return invoke<void>('export_interview_report', { path, format })
+}
+
+export type PrivacyFeature = 'capture-hidden' | 'taskbar-hidden' | 'switcher-hidden' | 'disguise' | 'ghost-typing'
+
+export interface FeatureUsage {
+    feature: PrivacyFeature
+    used: boolean
+    activeSeconds: number
+}
+
+export interface TransparencyReport {
+    version: number
+    label: string | null
+    startedAt: number
+    finishedAt: number
+    features: FeatureUsage[]
+    toggles: { feature: PrivacyFeature; active: boolean; at: number }[]
+}
+
+export interface SignedTransparencyReport {
+    report: TransparencyReport
+    publicKey: string
+    signature: string
+}
+
+/** Start recording which privacy features are used, for a signed transparency report. */
+export async function startTransparencyReport(label?: string): Promise<void> {
+    return invoke<void>('start_transparency_report', { label })
+}
+
+/** Stop recording and sign the report; also written to `path` when given. */
+export async function finishTransparencyReport(path?: string): Promise<SignedTransparencyReport> {
+    return invoke<SignedTransparencyReport>('finish_transparency_report', { path })
+}
+
+/** This installation's signing key, for an organisation to pin before an interview. */
+export async function getTransparencyPublicKey(): Promise<string> {
+    return invoke<string>('get_transparency_public_key')
+}
+
+/** Check a report against `publicKey`, the key pinned in advance; the one in the report is ignored. */
+export async function verifyTransparencyReport(
+    report: SignedTransparencyReport,
+    publicKey: string,
+): Promise<{ valid: boolean; signedByThisDevice: boolean }> {
+    return invoke('verify_transparency_report', { report, publicKey })
+}
+
+/**
+ * Type `text` into a document a character at a time, at `offset` (the end if omitted). Each
+ * character arrives as a `document-patch` event. Recorded in transparency reports.
+ */
+export async function startGhostTyping(docId: string, text: string, offset?: number, charsPerSecond?: number): Promise<void> {
+    return invoke<void>('start_ghost_typing', { docId, text, offset, charsPerSecond })
+}
+
+export async function stopGhostTyping(): Promise<boolean> {
+    return invoke<boolean>('stop_ghost_typing')
+}
+
+/** In classroom mode everyone except the host joins as a read-only viewer. */
+export async function setClassroomMode(enabled: boolean): Promise<void> {
+    return invoke<void>('set_classroom_mode', { enabled })
+}
+
+/** Groups of session messages a peer may support; peers only exchange what both have. */
+export type ProtocolCapability =
+    | 'documents'
+    | 'signals'
+    | 'polls'
+    | 'scratchpad'
+    | 'interview'
+    | 'strokes'
+    | 'magnifier'
+    | 'lifecycle'
+    | 'snippets'
+    | 'frozen'
+
+export interface NegotiatedProtocol {
+    protocol: number
+    capabilities: ProtocolCapability[]
+}
+
+/**
+ * Attach a viewer relayed by the webview. It first receives a snapshot of each document, then
+ * live messages, as `viewer-frame` events ({ viewerId, frame, resync }). Pass the version and
+ * capabilities the viewer announced, if any; viewers that announce none get protocol 1.
+ */
+export async function attachViewer(
+    viewerId: string,
+    documentIds: string[],
+    protocol?: number,
+    capabilities?: string[],
+): Promise<NegotiatedProtocol> {
+    return invoke<NegotiatedProtocol>('attach_viewer', {
+        viewerId,
+        documentIds,
+        protocol: protocol ?? null,
+        capabilities: capabilities ?? null,
+    })
+}
+
+export async function detachViewer(viewerId: string): Promise<boolean> {
+    return invoke<boolean>('detach_viewer', { viewerId })
+}
+
+export interface CapacityEstimate {
+    viewers: number
+    maxViewers: number
+    limitedBy: 'bandwidth' | 'cpu'
+    framesPerSecond: number
+    averageFrameBytes: number
+    workerThreads: number
+    droppedFrames: number
+}
+
+/** How many viewers this host can sustain, from observed traffic and upload bandwidth. */
+export async function getRoomCapacityEstimate(uploadKbps?: number): Promise<CapacityEstimate> {
+    return invoke<CapacityEstimate>('get_room_capacity_estimate', { uploadKbps })
+}
+
+/** How much the sharing pipeline holds back as the upload cap nears. */
+export type BandwidthLevel = 'normal' | 'coalescing' | 'snapshot-only' | 'capped'
+
+/** Also sent as `bandwidth-level-changed`. */
+export interface BandwidthUsage {
+    /** Bytes queued for viewers since the session started. */
+    sentBytes: number
+    capBytes: number | null
+    remainingBytes: number | null
+    bytesPerSecond: number
+    level: BandwidthLevel
+}
+
+export async function getBandwidthUsage(): Promise<BandwidthUsage> {
+    return invoke<BandwidthUsage>('get_bandwidth_usage')
+}
+
+/** Cap what a session may upload, in bytes; null lifts the cap. */
+export async function setUploadCap(capBytes: number | null): Promise<void> {
+    return invoke<void>('set_upload_cap', { capBytes })
+}
+
+export interface SyncStats {
+    documentId: string
+    snapshotSeq: number
+    snapshotBytes: number
+    textBytes: number
+    tailPatches: number
+    tailBytes: number
+}
+
+/** Size of each shared document's late-joiner snapshot and the patches replayed on top of it. */
+export async function getSyncStats(): Promise<SyncStats[]> {
+    return invoke<SyncStats[]>('get_sync_stats')
+}
+
+export type ViewerTransport = 'websocket' | 'sse' | 'long-poll'
+
+export interface ServerInfo {
+    port: number
+    /** Address to hand to viewers, including the session token. */
+    url: string
+    token: string
+}
+
+/**
+ * Serve the session to viewers on other machines. Viewers are offered a WebSocket, server-sent
+ * events or long-polling depending on what gets through their network. With `webViewer`, the
+ * session URL also opens a read-only viewer in any browser.
+ */
+export async function startSessionServer(
+    documentIds: string[],
+    options: { port?: number; webViewer?: boolean } = {},
+): Promise<ServerInfo> {
+    return invoke<ServerInfo>('start_session_server', { documentIds, ...options })
+}
+
+export async function stopSessionServer(): Promise<boolean> {
+    return invoke<boolean>('stop_session_server')
+}
+
+/** Same as `startSessionServer`. */
+export async function startSession(
+    documentIds: string[],
+    options: { port?: number; webViewer?: boolean } = {},
+): Promise<ServerInfo> {
+    return invoke<ServerInfo>('start_session', { documentIds, ...options })
+}
+
+/** Same as `stopSessionServer`. */
+export async function stopSession(): Promise<boolean> {
+    return invoke<boolean>('stop_session')
+}
+
+export async function getSessionServer(): Promise<ServerInfo | null> {
+    return invoke<ServerInfo | null>('get_session_server')
+}
+
+/** The session URL as an SVG QR code, for phones to join by scanning. */
+export async function getSessionQr(): Promise<string> {
+    return invoke<string>('get_session_qr')
+}
+
+export type HostState = 'idle' | 'hosting' | 'paused' | 'ended'
+export type RemoteState = 'idle' | 'joining' | 'connected' | 'reconnecting' | 'left'
+
+/** Also sent as the `session-state-changed` event. */
+export interface SessionState {
+    host: HostState
+    remote: RemoteState
+}
+
+export async function getSessionState(): Promise<SessionState> {
+    return invoke<SessionState>('get_session_state')
+}
+
+/** Stop sending viewers anything and turn away joins, without ending the session. */
+export async function pauseSession(): Promise<void> {
+    return invoke<void>('pause_session')
+}
+
+/** Viewers are resent each document on resuming. */
+export async function resumeSession(): Promise<void> {
+    return invoke<void>('resume_session')
+}
+
+/** Payload of `screen-lock-changed`; blank the shared view while `locked`. */
+export interface ScreenLockChanged {
+    locked: boolean
+    /** Whether the session is paused because of the lock. */
+    sessionPaused: boolean
+}
+
+export interface HighlightSpan {
+    /** UTF-16 offsets into `text`. */
+    start: number
+    end: number
+    class: 'keyword' | 'string' | 'number' | 'comment' | 'type' | 'function'
+}
+
+export interface StyledSpan extends HighlightSpan {
+    /** `#rrggbb` in the requested theme. */
+    color: string
+}
+
+/** Highlight code natively, with the grammars and themes exports use. Unknown languages get no spans. */
+export async function highlightCode(source: string, language: string, theme?: 'light' | 'dark'): Promise<StyledSpan[]> {
+    return invoke<StyledSpan[]>('highlight_code', { source, language, theme })
+}
+
+/** `source` as a `<pre>` with inline colours. Desktop only. */
+export async function highlightCodeHtml(source: string, language: string, theme?: 'light' | 'dark'): Promise<string> {
+    return invoke<string>('highlight_code_html', { source, language, theme })
+}
+
+/** Payload of `remote-document`: a watched document ready to display. */
+export interface RenderedDocument {
+    documentId: string
+    seq: number
+    language: string | null
+    text: string
+    spans: HighlightSpan[]
+}
+
+/** Payload of `remote-session-status`. */
+export interface RemoteSessionStatus {
+    state: 'live' | 'reconnecting' | 'closed'
+    transport: ViewerTransport | null
+    retryInMs: number | null
+    error: string | null
+}
+
+export interface JoinLink {
+    base: string
+    token: string
+}
+
+export interface RemoteSession extends NegotiatedProtocol {
+    base: string
+    participantId: string
+    transport: ViewerTransport
+}
+
+export type ViewerPowerMode = 'foreground' | 'background' | 'saver'
+
+/** Validate a scanned or pasted session link. */
+export async function parseJoinLink(link: string): Promise<JoinLink> {
+    return invoke<JoinLink>('parse_join_link', { link })
+}
+
+/** Watch a session hosted elsewhere; documents arrive as `remote-document` events. */
+export async function joinRemoteSession(link: string, name: string): Promise<RemoteSession> {
+    return invoke<RemoteSession>('join_remote_session', { link, name })
+}
+
+export async function leaveRemoteSession(): Promise<boolean> {
+    return invoke<boolean>('leave_remote_session')
+}
+
+/** Report app visibility and battery saver so the viewer can sync less often. */
+export async function setViewerPowerMode(mode: ViewerPowerMode): Promise<void> {
+    return invoke<void>('set_viewer_power_mode', { mode })
+}
+
+/** Reconnect now, e.g. after a push notification or when the network returns. */
+export async function resumeRemoteSession(): Promise<boolean> {
+    return invoke<boolean>('resume_remote_session')
+}
+
+/** Payload of `handoff-received`: where to pick up a session continued from another device. */
+export interface HandoffPayload {
+    version: number
+    link: string
+    documentId: string | null
+    seq: number | null
+    /** UTF-16 offset of the first visible character. */
+    scrollOffset: number | null
+    issuedAt: number
+}
+
+/** Offer the current session to the user's other Apple devices via Handoff. */
+export async function advertiseHandoff(documentId?: string, seq?: number, scrollOffset?: number): Promise<HandoffPayload> {
+    return invoke<HandoffPayload>('advertise_handoff', { documentId, seq, scrollOffset })
+}
+
+export async function stopHandoff(): Promise<boolean> {
+    return invoke<boolean>('stop_handoff')
+}
+
+/** Validate a Handoff payload that arrived through another channel. */
+export async function acceptHandoff(payload: string): Promise<HandoffPayload> {
+    return invoke<HandoffPayload>('accept_handoff', { payload })
+}
+
+export interface BlePeer {
+    id: string
+    name: string | null
+    rssi: number | null
+}
+
+/** Payload of `ble-pairing-code`: show `code` and ask the user whether the other device shows the same. */
+export interface BlePairingCode {
+    transferId: string
+    code: string
+    direction: 'send' | 'receive'
+}
+
+/** Payload of `ble-progress`. */
+export interface BleProgress {
+    transferId: string
+    direction: 'send' | 'receive'
+    bytes: number
+    total: number
+}
+
+/** Find nearby instances ready to receive a snippet over Bluetooth. */
+export async function scanBlePeers(seconds?: number): Promise<BlePeer[]> {
+    return invoke<BlePeer[]>('scan_ble_peers', { seconds })
+}
+
+/** Send a snippet to a nearby peer; resolves once the peer has verified it. */
+export async function sendSnippetBle(peerId: string, snippetId: string): Promise<void> {
+    return invoke<void>('send_snippet_ble', { peerId, snippetId })
+}
+
+/** Hand a write received by the platform's GATT service to the backend; returns bytes to notify back. */
+export async function receiveBleFrame(frame: number[]): Promise<number[] | null> {
+    return invoke<number[] | null>('receive_ble_frame', { frame })
+}
+
+/** Confirm the pairing code matches; when receiving, returns bytes to notify to the sender. */
+export async function confirmBlePairing(transferId: string, accept: boolean): Promise<number[] | null> {
+    return invoke<number[] | null>('confirm_ble_pairing', { transferId, accept })
+}
+
+export interface BundleInfo {
+    path: string
+    createdAt: number
+    size: number
+    documents: number
+    annotations: number
+}
+
+export interface BundleAnnotation {
+    documentId: string
+    path: string | null
+    line: number | null
+    author: string
+    body: string
+    createdAt: string
+}
+
+export interface ImportedBundle {
+    createdAt: number
+    /** `name` is the file name on the exporting machine, to suggest when saving. */
+    documents: { name: string | null; document: DocumentInfo }[]
+    annotations: BundleAnnotation[]
+}
+
+/** Write the open documents and their annotations to an encrypted bundle, e.g. on a USB stick. */
+export async function exportOfflineBundle(path: string, passphrase: string): Promise<BundleInfo> {
+    return invoke<BundleInfo>('export_offline_bundle', { path, passphrase })
+}
+
+/** Decrypt and verify a bundle, opening its documents as new untitled documents. */
+export async function importOfflineBundle(path: string, passphrase: string): Promise<ImportedBundle> {
+    return invoke<ImportedBundle>('import_offline_bundle', { path, passphrase })
+}
+
+/** A snippet as it travels between machines, without its local id, timestamps or source. */
+export type PortableSnippet = Pick<Snippet, 'title' | 'prefix' | 'body' | 'language' | 'description' | 'tags'>
+
+/** Wrap a snippet in ASCII armor for pasting into mail or chat. */
+export async function armorSnippet(id: string): Promise<string> {
+    return invoke<string>('armor_snippet', { id })
+}
+
+/** Decode armor anywhere in pasted text; `null` when there is none. */
+export async function dearmorSnippet(text: string): Promise<PortableSnippet | null> {
+    return invoke<PortableSnippet | null>('dearmor_snippet', { text })
+}
+
+export interface StegoInfo {
+    path: string
+    width: number
+    height: number
+    hiddenBytes: number
+    capacity: number
+}
+
+/** Experimental: hide a snippet in a PNG (a generated image unless `coverPath` is given). */
+export async function hideSnippetInPng(id: string, outputPath: string, coverPath?: string): Promise<StegoInfo> {
+    return invoke<StegoInfo>('hide_snippet_in_png', { id, outputPath, coverPath })
+}
+
+/** Recover a snippet hidden by `hideSnippetInPng`; fails if the image was re-encoded. */
+export async function revealSnippetFromPng(path: string): Promise<PortableSnippet> {
+    return invoke<PortableSnippet>('reveal_snippet_from_png', { path })
+}
+
+export interface PrintOptions {
+    paper?: 'a4' | 'letter'
+    landscape?: boolean
+    fontSize?: number
+    lineNumbers?: boolean
+    /** File name, date and page numbers on each page. */
+    header?: boolean
+    wrap?: boolean
+    color?: boolean
+    /** Save the PDF here instead of printing it. */
+    outputPath?: string
+    printer?: string
+}
+
+export interface PrintJob {
+    pages: number
+    path: string | null
+    printed: boolean
+}
+
+/** Print a document as paginated, highlighted pages, or save them as a PDF. */
+export async function printDocument(docId: string, options?: PrintOptions): Promise<PrintJob> {
+    return invoke<PrintJob>('print_document', { docId, options })
+}
+
+export interface PreparedScreenshot {
+    /** Greyscale PNG `data:` URL for the recognizer. */
+    dataUrl: string
+    width: number
+    height: number
+    /** Text region within the pasted image. */
+    crop: { x: number; y: number; width: number; height: number }
+    skewDegrees: number
+    inverted: boolean
+    scale: number
+}
+
+/** Crop, deskew and normalize a pasted screenshot of code before text recognition. */
+export async function prepareCodeScreenshot(image: string): Promise<PreparedScreenshot> {
+    return invoke<PreparedScreenshot>('prepare_code_screenshot', { image })
+}
+
+export interface RecordingOptions {
+    format?: 'gif' | 'webm'
+    theme?: 'light' | 'dark'
+    columns?: number
+    rows?: number
+    fps?: number
+    /** Playback speed relative to the original typing. */
+    speed?: number
+    /** Pauses longer than this are shortened to it. */
+    maxIdleMs?: number
+    lineNumbers?: boolean
+    scale?: number
+    /** Show only the host's magnified region, enlarged; on by default. */
+    magnify?: boolean
+    /** Rasterize on the GPU in builds with GPU support (default true); falls back to the CPU. */
+    gpu?: boolean
+}
+
+export interface RecordingInfo {
+    path: string
+    frames: number
+    durationMs: number
+    width: number
+    height: number
+    /** Which rasterizer drew the frames. */
+    renderer: 'gpu' | 'cpu'
+}
+
+/** Start recording a document's view; returns the timeline position the clip starts from. */
+export async function startRecording(docId: string, options?: RecordingOptions): Promise<number> {
+    return invoke<number>('start_recording', { docId, options })
+}
+
+/** Stop recording and render the clip to `path`. WebM needs ffmpeg on the PATH. */
+export async function stopRecording(docId: string, path: string): Promise<RecordingInfo> {
+    return invoke<RecordingInfo>('stop_recording', { docId, path })
+}
+
+export async function cancelRecording(docId: string): Promise<boolean> {
+    return invoke<boolean>('cancel_recording', { docId })
+}
+
+export interface ReplayOptions {
+    format?: 'svg' | 'html'
+    theme?: 'light' | 'dark'
+    /** Timeline range to play; defaults to the whole kept history. */
+    fromSeq?: number
+    toSeq?: number
+    speed?: number
+    maxIdleMs?: number
+    columns?: number
+    rows?: number
+    fontSize?: number
+    lineNumbers?: boolean
+    /** Show only the host's magnified region, enlarged; on by default. */
+    magnify?: boolean
+}
+
+export interface ReplayInfo {
+    path: string
+    frames: number
+    durationMs: number
+    size: number
+}
+
+/** Export the document's typing as a self-playing SVG or HTML file; no player or script needed. */
+export async function exportTypingReplay(docId: string, path: string, options?: ReplayOptions): Promise<ReplayInfo> {
+    return invoke<ReplayInfo>('export_typing_replay', { docId, path, options })
+}
+
+export interface VoiceCommand {
+    phrase: string
+    /** Identifier delivered in the `voice-command` event, e.g. `stealth-on`. */
+    action: string
+}
+
+export interface VoiceConfig {
+    enabled: boolean
+    /** Directory of an unpacked Vosk model. */
+    modelPath: string | null
+    hotkey: string
+    commands: VoiceCommand[]
+}
+
+/**
+ * Voice control is only present in builds made with the `voice` feature. While the hotkey is held
+ * the backend emits `voice-listening`, then `voice-command` ({ phrase, action }),
+ * `voice-unrecognized` (the text heard) or `voice-error`.
+ */
+export async function getVoiceConfig(): Promise<VoiceConfig> {
+    return invoke<VoiceConfig>('get_voice_config')
+}
+
+/** Save voice settings; enabling loads the model and binds the push-to-talk hotkey. */
+export async function setVoiceConfig(config: VoiceConfig): Promise<void> {
+    return invoke<void>('set_voice_config', { config })
+}
+
+export async function startVoiceListening(): Promise<void> {
+    return invoke<void>('start_voice_listening')
+}
+
+export async function stopVoiceListening(): Promise<void> {
+    return invoke<void>('stop_voice_listening')
+}
+
+export interface SpeechConfig {
+    enabled: boolean
+    chat: boolean
+    annotations: boolean
+    /** Raised hands and reactions. */
+    signals: boolean
+    /** 0 to 1. */
+    volume: number
+    /** Relative to the voice's normal pace, 0.5 to 2. */
+    rate: number
+    voice: string | null
+    /** Stay quiet while a sharecode window has focus. */
+    onlyWhenUnfocused: boolean
+}
+
+export async function getSpeechConfig(): Promise<SpeechConfig> {
+    return invoke<SpeechConfig>('get_speech_config')
+}
+
+export async function setSpeechConfig(config: SpeechConfig): Promise<void> {
+    return invoke<void>('set_speech_config', { config })
+}
+
+/** Silence all readouts immediately, including the one in progress. */
+export async function setSpeechMuted(muted: boolean): Promise<void> {
+    return invoke<void>('set_speech_muted', { muted })
+}
+
+/** Read out a chat message or annotation comment received by the frontend, if enabled. */
+export async function announceEvent(
+    event: 'chat' | 'annotation' | 'signal',
+    text: string,
+    author?: string,
+): Promise<void> {
+    return invoke<void>('announce_event', { event, author, text })
+}
+
+export interface PinchEvent {
+    phase: 'began' | 'changed' | 'ended' | 'cancelled'
+    /** Change since the previous event (0.1 is 10% larger). */
+    magnification: number
+    /** Cumulative scale since the gesture began. */
+    scale: number
+}
+
+/**
+ * macOS only. While enabled (the default) trackpad gestures arrive as `trackpad-pinch`
+ * ({@link PinchEvent}) and `trackpad-swipe` (`'previous' | 'next'`) events instead of reaching the page.
+ */
+export async function setTrackpadGestures(enabled: boolean): Promise<void> {
+    return invoke<void>('set_trackpad_gestures', { enabled })
+}
+
+export interface PenSample {
+    /** CSS pixels from the top-left of the window's content. */
+    x: number
+    y: number
+    /** 0 to 1. */
+    pressure: number
+    /** Degrees, as in `PointerEvent`. */
+    tiltX?: number
+    tiltY?: number
+}
+
+export interface PenCanvas {
+    documentId: string
+    participantId: string
+    /** The text area, in CSS pixels from the top-left of the window's content. */
+    left: number
+    top: number
+    width: number
+    height: number
+    columnWidth: number
+    lineHeight: number
+    /** Zero-based, fractional line and column at the top-left corner after scrolling. */
+    firstLine: number
+    firstColumn: number
+    color: string
+    /** Width at full pressure, in CSS pixels. */
+    penWidth: number
+}
+
+/** Cubic Bézier piece of a stroke; points are `[column, line]` and widths are in lines. */
+export interface StrokeSegment {
+    from: [number, number]
+    c1: [number, number]
+    c2: [number, number]
+    to: [number, number]
+    startWidth: number
+    endWidth: number
+}
+
+export interface Stroke {
+    id: string
+    documentId: string
+    color: string
+    segments: StrokeSegment[]
+}
+
+/**
+ * Arm pen annotation over a document (call again after scrolling), or disarm with `null`.
+ * Resolves to `true` when pen input is read natively; otherwise forward pen pointer events
+ * with {@link addPenSamples}. Finished strokes arrive as `annotation-stroke` events.
+ */
+export async function setPenCanvas(canvas: PenCanvas | null): Promise<boolean> {
+    return invoke<boolean>('set_pen_canvas', { canvas })
+}
+
+/** Add samples to the stroke in progress; `end` completes it and sends it to the session. */
+export async function addPenSamples(samples: PenSample[], end: boolean): Promise<Stroke | null> {
+    return invoke<Stroke | null>('add_pen_samples', { samples, end })
+}
+
+export type ColorProfile = 'standard' | 'deuteranopia' | 'protanopia'
+
+export interface ColorPalette {
+    profile: ColorProfile
+    colors: string[]
+}
+
+export interface ParticipantColor {
+    color: string
+    /** Translucent, for selections. */
+    colorLight: string
+}
+
+/** Stable colour for a user id, from the selected palette. */
+export async function getParticipantColor(key: string): Promise<ParticipantColor> {
+    return invoke<ParticipantColor>('get_participant_color', { key })
+}
+
+export async function getColorPalette(): Promise<ColorPalette> {
+    return invoke<ColorPalette>('get_color_palette')
+}
+
+/** Switch to a palette that stays distinguishable under `profile`; participants are recoloured. */
+export async function setColorPalette(profile: ColorProfile): Promise<ColorPalette> {
+    return invoke<ColorPalette>('set_color_palette', { profile })
+}
+
+export interface SystemPreferences {
+    highContrast: boolean
+    reduceMotion: boolean
+}
+
+/** The OS accessibility settings exports follow; changes arrive as `system-preferences-changed`. */
+export async function getSystemPreferences(): Promise<SystemPreferences> {
+    return invoke<SystemPreferences>('get_system_preferences')
+}
+
+/** A rectangle of a document in lines and display columns, from zero. */
+export interface FocusRegion {
+    documentId: string
+    top: number
+    left: number
+    rows: number
+    columns: number
+}
+
+export async function getMagnifier(): Promise<FocusRegion | null> {
+    return invoke<FocusRegion | null>('get_magnifier')
+}
+
+/** Move the magnifier viewers zoom to, or put it away with null. Viewers hear `magnifier-changed`. */
+export async function setMagnifier(region: FocusRegion | null): Promise<void> {
+    return invoke<void>('set_magnifier', { region })
+}
+
+/** A document's minimap at four bits per pixel; viewers fetch the same from `/session/minimap`. */
+export interface MinimapBitmap {
+    documentId: string
+    seq: number
+    width: number
+    height: number
+    /** Document lines folded into each bitmap row. */
+    linesPerRow: number
+    /**
+     * Base64 of `height` rows of `width` pixels, high nibble first, rows padded to a whole byte.
+     * 0 is background, 1 plain text, then keyword, string, number, comment, type and function.
+     */
+    data: string
+}
+
+export async function getMinimap(docId: string): Promise<MinimapBitmap> {
+    return invoke<MinimapBitmap>('get_minimap', { docId })
+}
+
+export interface HighlightStats {
+    fullParses: number
+    incrementalParses: number
+    linesHighlighted: number
+    /** Lines whose cached spans were used as they were. */
+    linesReused: number
+    updates: number
+    averageUpdateMicros: number
+    cachedDocuments: number
+}
+
+export interface HighlightBenchmark {
+    lines: number
+    edits: number
+    /** Average time per edit to parse and highlight the whole document. */
+    fullMicros: number
+    incrementalMicros: number
+    /** Both ways produced the same spans after every edit. */
+    consistent: boolean
+}
+
+export async function getHighlightStats(): Promise<HighlightStats> {
+    return invoke<HighlightStats>('get_highlight_stats')
+}
+
+/**
+ * Time full against incremental highlighting while typing into a copy of an open document.
+ * Only in builds made with the `bench` feature.
+ */
+export async function runHighlightBenchmark(docId: string, edits: number): Promise<HighlightBenchmark> {
+    return invoke<HighlightBenchmark>('run_highlight_benchmark', { docId, edits })
+}
+
+export type TaskPriority = 'interactive' | 'normal' | 'background'
+
+export interface WorkerTask {
+    id: string
+    /** What the task is doing, e.g. "Recording export". */
+    label: string
+    priority: TaskPriority
+    running: boolean
+    /** Time spent waiting, or running once started. */
+    elapsedMs: number
+}
+
+export interface WorkerStats {
+    workers: number
+    /** Tasks waiting for a worker, by priority. */
+    queued: Record<TaskPriority, number>
+    tasks: WorkerTask[]
+    completed: number
+    cancelled: number
+}
+
+export async function getWorkerStats(): Promise<WorkerStats> {
+    return invoke<WorkerStats>('get_worker_stats')
+}
+
+/** Cancel a queued or running task; resolves `false` if it already finished. */
+export async function cancelTask(id: string): Promise<boolean> {
+    return invoke<boolean>('cancel_task', { id })
+}
+
+/** A binary IPC response: a JSON header and the raw bytes that follow it. */
+export interface BinaryFrame<H> {
+    header: H
+    body: Uint8Array
+}
+
+/** Split a binary frame: `SCB1`, a little-endian u32 header length, the JSON header, the body. */
+export function decodeFrame<H>(buffer: ArrayBuffer): BinaryFrame<H> {
+    const view = new DataView(buffer)
+    const magic = new TextDecoder().decode(new Uint8Array(buffer, 0, 4))
+    if (magic !== 'SCB1') {
+        throw new Error('Not a binary frame')
+    }
+    const headerLength = view.getUint32(4, true)
+    const header = JSON.parse(new TextDecoder().decode(new Uint8Array(buffer, 8, headerLength))) as H
+    return { header, body: new Uint8Array(buffer, 8 + headerLength) }
+}
+
+export type MinimapHeader = Omit<MinimapBitmap, 'data'>
+
+/** The minimap with its packed pixels as bytes rather than base64 (same layout as `data`). */
+export async function getMinimapBinary(docId: string): Promise<BinaryFrame<MinimapHeader>> {
+    return decodeFrame(await invoke<ArrayBuffer>('get_minimap_binary', { docId }))
+}
+
+export type PreparedImage = Omit<PreparedScreenshot, 'dataUrl'>
+
+/** Like `prepareCodeScreenshot`, sending the PNG bytes and receiving the prepared PNG as bytes. */
+export async function prepareCodeScreenshotBinary(png: Uint8Array): Promise<BinaryFrame<PreparedImage>> {
+    return decodeFrame(await invoke<ArrayBuffer>('prepare_code_screenshot_binary', png))
+}
+
+/** Exact bytes of a large file range; `header` is the range actually read. */
+export async function readLargeFileRangeBinary(
+    id: string,
+    offset: number,
+    length: number
+): Promise<BinaryFrame<{ offset: number; length: number }>> {
+    return decodeFrame(await invoke<ArrayBuffer>('read_large_file_range_binary', { id, offset, length }))
+}
+
+export interface IpcBenchmark {
+    bytes: number
+    /** Best time to receive the payload as base64 JSON and decode it to bytes. */
+    jsonMs: number
+    /** Best time to receive it as a binary frame. */
+    binaryMs: number
+}
+
+/**
+ * Time moving `megabytes` (at most 16) from the backend as base64 JSON against a binary frame.
+ * Only in builds made with the `bench` feature.
+ */
+export async function benchmarkBinaryIpc(megabytes = 8, rounds = 3): Promise<IpcBenchmark> {
+    const bytes = Math.round(Math.min(megabytes, 16) * 1024 * 1024)
+    let jsonMs = Infinity
+    let binaryMs = Infinity
+    for (let i = 0; i < rounds; i++) {
+        let started = performance.now()
+        const encoded = await invoke<string>('ipc_benchmark_payload', { bytes, binary: false })
+        Uint8Array.from(atob(encoded), (c) => c.charCodeAt(0))
+        jsonMs = Math.min(jsonMs, performance.now() - started)
+
+        started = performance.now()
+        decodeFrame(await invoke<ArrayBuffer>('ipc_benchmark_payload', { bytes, binary: true }))
+        binaryMs = Math.min(binaryMs, performance.now() - started)
+    }
+    return { bytes, jsonMs, binaryMs }
+}
+
+/** Frame hand-off between drawing and encoding threads, per pipeline (e.g. `recording`). */
+export interface FrameStats {
+    pipeline: string
+    /** Channels currently open. */
+    active: number
+    frames: number
+    /** Frames replaced before the encoder got to them. */
+    dropped: number
+    /** Times drawing had to wait for the encoder. */
+    stalls: number
+}
+
+export async function getFrameStats(): Promise<FrameStats[]> {
+    return invoke<FrameStats[]>('get_frame_stats')
+}
+
+export interface SnapshotInfo {
+    path: string
+    size: number
+    documents: number
+    /** BLAKE3 of the snapshot's contents; equal for equal sessions. */
+    contentHash: string
+}
+
+/** Write the open documents (or only `documentIds`) to an unencrypted, reproducible snapshot. */
+export async function exportSessionSnapshot(path: string, documentIds?: string[]): Promise<SnapshotInfo> {
+    return invoke<SnapshotInfo>('export_session_snapshot', { path, documentIds: documentIds ?? null })
+}
+
+/** Verify a snapshot, opening its documents as new untitled documents. */
+export async function importSessionSnapshot(path: string): Promise<ImportedBundle['documents']> {
+    return invoke<ImportedBundle['documents']>('import_session_snapshot', { path })
+}
+
+/** A file opened recently, newest first; also sent as the list in `recent-files-changed`. */
+export interface RecentFile {
+    path: string
+    kind: 'document' | 'snapshot' | 'bundle'
+    /** Seconds since the Unix epoch. */
+    openedAt: number
+}
+
+export async function getRecentFiles(): Promise<RecentFile[]> {
+    return invoke<RecentFile[]>('get_recent_files')
+}
+
+/** Take one file off the app's list; the Dock and jump list keep it until cleared. */
+export async function removeRecentFile(path: string): Promise<RecentFile[]> {
+    return invoke<RecentFile[]>('remove_recent_file', { path })
+}
+
+/** Forget every recent file, in the app and in the Dock or jump list. */
+export async function clearRecentFiles(): Promise<void> {
+    return invoke<void>('clear_recent_files')
+}
+
+export interface ArtifactReport {
+    /** `sessionSnapshot`, `recording`, `offlineBundle`, `backup`, `gifRecording`, `webmRecording`,
+     *  `replay`, `pdf`, or `unknown`. */
+    kind: string
+    /** Container version as `major.minor`, or the format version of a legacy file. */
+    version: string | null
+    size: number
+    /** BLAKE3 of the contents, for snapshots and bundles. */
+    contentHash: string | null
+    metadata: Record<string, string>
+    /** Whether everything checkable without a key or passphrase checked out. */
+    valid: boolean
+    problems: string[]
+}
+
+/** Identify a file ShareCode wrote and check its integrity without opening it. */
+export async function validateFile(path: string): Promise<ArtifactReport> {
+    return invoke<ArtifactReport>('validate_file', { path })
+}
+
+export interface SimulationOptions {
+    participants?: number
+    durationSecs?: number
+    /** Host edits to the shared document per second. */
+    editsPerSecond?: number
+    /** Signals per second from each participant. */
+    signalsPerSecond?: number
+}
+
+export interface SimulationReport {
+    participants: number
+    durationMs: number
+    edits: number
+    framesReceived: number
+    signalsSent: number
+    /** Dropped by the host's rate limit, or a hand raised twice. */
+    signalsRejected: number
+    /** Patches that arrived at or before a `seq` already applied; should be 0. */
+    orderingErrors: number
+    /** Gaps in the patch sequence repaired with a fresh snapshot. */
+    resyncs: number
+    /** Participants whose copy matched the host's document at the end. */
+    converged: number
+    latencyP50Ms: number
+    latencyP95Ms: number
+    latencyMaxMs: number
+    errors: string[]
+}
+
+/**
+ * Load a local session with virtual participants and check they all end up with the host's text.
+ * Only in builds made with the `sim` feature, and refused while the session server is running.
+ */
+export async function runSimulation(options?: SimulationOptions): Promise<SimulationReport> {
+    return invoke<SimulationReport>('run_simulation', { options })
+}
+
+/** Simulated network conditions on the session transports, for QA. */
+export interface NetworkImpairment {
+    latencyMs?: number
+    /** The delay varies by up to this much either side of `latencyMs`. */
+    jitterMs?: number
+    /** Frames lost and retransmitted, which stalls them like TCP does. */
+    lossPercent?: number
+    /** Throughput in each direction, shared by every connection; 0 for no cap. */
+    bandwidthKbps?: number
+    /** Frames that break their connection instead of arriving. */
+    disconnectPercent?: number
+}
+
+export async function getNetworkImpairment(): Promise<NetworkImpairment | null> {
+    return invoke<NetworkImpairment | null>('get_network_impairment')
+}
+
+/** Impair the session server and viewer transports, or pass `null` to restore the real network. */
+export async function setNetworkImpairment(impairment: NetworkImpairment | null): Promise<void> {
+    return invoke<void>('set_network_impairment', { impairment })
+}
+
+export async function getDeveloperMode(): Promise<boolean> {
+    return invoke<boolean>('get_developer_mode')
+}
+
+/** Developer mode enables the debug console. */
+export async function setDeveloperMode(enabled: boolean): Promise<void> {
+    return invoke<void>('set_developer_mode', { enabled })
+}
+
+/**
+ * Run a debug console command (`help`, `windows`, `session`, `queues`, `caches` or `all`) and get
+ * its JSON dump of internal state, for bug reports. Fails unless developer mode is on.
+ */
+export async function debugExecute(command: string): Promise<unknown> {
+    return invoke<unknown>('debug_execute', { command })
+}
+
+/** Copy code as plain text plus highlighted HTML and RTF (no RTF on Linux), so pasting keeps the colours. */
+export async function copyRich(text: string, language?: string, theme?: 'light' | 'dark'): Promise<void> {
+    return invoke<void>('copy_rich', { text, language, theme })
+}
+
+/** Sent as `snippet-drag-finished` once a drag started by `startSnippetDrag` ends. */
+export interface SnippetDragFinished {
+    snippetId: string
+    dropped: boolean
+}
+
+/** Drag a snippet out of the app as a file. Call from `mousedown` while the button is held. */
+export async function startSnippetDrag(id: string): Promise<void> {
+    return invoke<void>('start_snippet_drag', { id })
+}
+
+/** Sent as `screen-share-detected` when a screen share starts while share detection is on. */
+export interface ScreenShareDetected {
+    /** Processes and window titles that gave the share away. */
+    sources: string[]
+    /** Labels of the windows protection was turned on for; lifted again on `screen-share-ended`. */
+    protected: string[]
+}
+
+/** What gave away the screen share in progress; empty if none was detected. */
+export async function getScreenShare(): Promise<string[]> {
+    return invoke<string[]>('get_screen_share')
+}
+
+export async function getShareDetection(): Promise<boolean> {
+    return invoke<boolean>('get_share_detection')
+}
+
+/** Protect every window automatically while the screen appears to be shared. Off by default. */
+export async function setShareDetection(enabled: boolean): Promise<void> {
+    return invoke<void>('set_share_detection', { enabled })
+}
+
+/** Show a readable preview of a ShareCode file (snapshot, bundle, backup…) in Quick Look. macOS only. */
+export async function previewFile(path: string): Promise<void> {
+    return invoke<void>('preview_file', { path })
+}
+
+/** A `.sharecode` file opened from the file manager, shown read-only in a window of its own. */
+export interface OpenedFile {
+    path: string
+    kind: 'sessionSnapshot' | 'offlineBundle'
+    /** An offline bundle waiting for `unlockOpenedFile`. */
+    locked: boolean
+    documents: ImportedBundle['documents']
+    annotations: BundleAnnotation[]
+}
+
+/** Sent to the main window as `file-open-failed` when a file opened from outside can't be loaded. */
+export interface FileOpenFailed {
+    path: string
+    error: string
+}
+
+/** The file this window was opened for, or null in the main window. */
+export async function getOpenedFile(): Promise<OpenedFile | null> {
+    return invoke<OpenedFile | null>('get_opened_file')
+}
+
+/** Decrypt the offline bundle this window was opened for. Its documents stay frozen. */
+export async function unlockOpenedFile(passphrase: string): Promise<OpenedFile> {
+    return invoke<OpenedFile>('unlock_opened_file', { passphrase })
+}
+
+/** Whether saved snippets can be found from Spotlight and Windows Search. On by default. */
+export async function getSnippetIndexing(): Promise<boolean> {
+    return invoke<boolean>('get_snippet_indexing')
+}
+
+/** Turn search indexing of snippet titles and tags on or off. Off removes the indexed files. */
+export async function setSnippetIndexing(enabled: boolean): Promise<void> {
+    return invoke<void>('set_snippet_indexing', { enabled })
+}
+
+export interface TimeTotals {
+    activeSecs: number
+    idleSecs: number
+    pausedSecs: number
+}
+
+export interface ParticipantTime extends TimeTotals {
+    id: string
+    name: string
+    role: ParticipantRole
+    joinedAt: number
+    leftAt: number
+}
+
+/** A hosted session's time. Times are seconds since the Unix epoch. */
+export interface SessionTime extends TimeTotals {
+    id: string
+    startedAt: number
+    endedAt: number
+    /** One entry per visit; someone who rejoined appears more than once. */
+    participants: ParticipantTime[]
+    /** The session still running, with `endedAt` the time of the report. */
+    ongoing: boolean
+}
+
+export interface TimeReport extends TimeTotals {
+    sessions: SessionTime[]
+    /** Totals per participant name, most active first. */
+    participants: ({ name: string; sessions: number } & TimeTotals)[]
+}
+
+/** Sessions started from `from` up to (not including) `to`, in seconds since the Unix epoch. */
+export interface TimeRange {
+    from?: number
+    to?: number
+}
+
+/** Active, idle and paused time in hosted sessions, including the one running now. */
+export async function getTimeReport(range?: TimeRange): Promise<TimeReport> {
+    return invoke<TimeReport>('get_time_report', { range })
+}
+
+/** Write the time report to `path` as CSV, one row per session and per participant visit. */
+export async function exportTimeReport(path: string, range?: TimeRange): Promise<void> {
+    return invoke<void>('export_time_report', { range, path })
+}
+
+/** Count the local participant as active; call on input, at most every few seconds. */
+export async function noteActivity(participantId: string): Promise<void> {
+    return invoke<void>('note_activity', { participantId })
+}
+
+/** A session description to send to the other side, and the id of the connection it belongs to. */
+export interface PeerOffer {
+    peerId: string
+    sdp: string
+}
+
+/** Sent as `p2p-state-changed`; `open` means keys are being exchanged. */
+export interface PeerStateChanged {
+    peerId: string
+    state: 'new' | 'connecting' | 'connected' | 'disconnected' | 'failed' | 'closed' | 'open'
+}
+
+/** Sent as `p2p-payload` for each payload the peer sends. */
+export interface PeerPayload {
+    peerId: string
+    payload: string
+}
+
+/** Start a peer-to-peer connection; send `sdp` to the other side, who calls `acceptOffer`. */
+export async function createOffer(): Promise<PeerOffer> {
+    return invoke<PeerOffer>('create_offer')
+}
+
+/** Answer the other side's offer; send the returned `sdp` back for `acceptAnswer`. */
+export async function acceptOffer(sdp: string): Promise<PeerOffer> {
+    return invoke<PeerOffer>('accept_offer', { sdp })
+}
+
+export async function acceptAnswer(peerId: string, sdp: string): Promise<void> {
+    return invoke<void>('accept_answer', { peerId, sdp })
+}
+
+/** Encrypt and send up to 48 KiB of text to a connected peer whose short auth string is verified. */
+export async function sendPayload(peerId: string, payload: string): Promise<void> {
+    return invoke<void>('send_payload', { peerId, payload })
+}
+
+export async function closePeer(peerId: string): Promise<boolean> {
+    return invoke<boolean>('close_peer', { peerId })
+}
+
+export type WebhookEvent = 'session-started' | 'session-ended' | 'participant-joined' | 'export-created'
+
+export interface WebhookEndpoint {
+    id: string
+    url: string
+    /** Events sent to the endpoint; empty means all. */
+    events: WebhookEvent[]
+    enabled: boolean
+    createdAt: number
+}
+
+/** A queued event, waiting for its first attempt or a retry. */
+export interface WebhookDelivery {
+    id: string
+    endpointId: string
+    event: WebhookEvent
+    body: string
+    attempts: number
+    nextAttemptAt: number
+    lastError: string | null
+}
+
+/** Sent as `webhook-failed` when a delivery is given up on after its last retry. */
+export interface WebhookFailed {
+    endpointId: string
+    deliveryId: string
+    event: WebhookEvent
+    error: string
+}
+
+export async function listWebhooks(): Promise<WebhookEndpoint[]> {
+    return invoke<WebhookEndpoint[]>('list_webhooks')
+}
+
+/**
+ * Add an endpoint (https, or http on localhost). The returned secret signs every request as
+ * `X-ShareCode-Signature: t=<seconds>,v1=<HMAC-SHA256 of "t.body">`; it is only shown now.
+ */
+export async function addWebhook(url: string, events: WebhookEvent[] = []): Promise<{ endpoint: WebhookEndpoint; secret: string }> {
+    return invoke<{ endpoint: WebhookEndpoint; secret: string }>('add_webhook', { url, events })
+}
+
+export async function removeWebhook(id: string): Promise<boolean> {
+    return invoke<boolean>('remove_webhook', { id })
+}
+
+export async function setWebhookEnabled(id: string, enabled: boolean): Promise<WebhookEndpoint> {
+    return invoke<WebhookEndpoint>('set_webhook_enabled', { id, enabled })
+}
+
+export async function getWebhookOutbox(): Promise<WebhookDelivery[]> {
+    return invoke<WebhookDelivery[]>('get_webhook_outbox')
+}
+
+/** Sent as `p2p-key-agreed`; both users should see the same `shortAuthString`. */
+export interface KeyAgreed {
+    peerId: string
+    shortAuthString: string
+}
+
+export interface KeyStatus {
+    peerId: string
+    publicKey: string
+    shortAuthString: string | null
+    verified: boolean
+}
+
+/** Replace the session key for a peer; the short auth string changes and must be verified again. */
+export async function generateSessionKey(peerId: string): Promise<KeyStatus> {
+    return invoke<KeyStatus>('generate_session_key', { peerId })
+}
+
+/** Our public session key for a peer, never the secret half. */
+export async function exportSessionKey(peerId: string): Promise<KeyStatus> {
+    return invoke<KeyStatus>('export_session_key', { peerId })
+}
+
+/** Compare the code the other side reads out; payloads can be sent once it matches. */
+export async function verifyShortAuthString(peerId: string, code: string): Promise<boolean> {
+    return invoke<boolean>('verify_short_auth_string', { peerId, code })
+}
+
+export interface MqttConfig {
+    enabled: boolean
+    host: string
+    port: number
+    tls: boolean
+    username: string | null
+    /** Retained JSON status (`online`, `stealth`, `session`, `updatedAt`) is published here. */
+    topic: string
+}
+
+export interface MqttSettings {
+    config: MqttConfig
+    hasPassword: boolean
+    connected: boolean
+}
+
+export async function getMqttSettings(): Promise<MqttSettings> {
+    return invoke<MqttSettings>('get_mqtt_settings')
+}
+
+/** Save and reconnect. Omit `password` to keep the stored one; pass '' to remove it. */
+export async function setMqttSettings(config: MqttConfig, password?: string): Promise<void> {
+    return invoke<void>('set_mqtt_settings', { config, password })
+}
+
+export type StatusService = 'slack' | 'teams'
+
+export interface StatusSyncConfig {
+    services: StatusService[]
+    slackText: string
+    slackEmoji: string
+}
+
+export interface StatusSyncSettings {
+    config: StatusSyncConfig
+    /** Services with a token stored. */
+    connected: StatusService[]
+}
+
+/** Sent as `status-sync-failed` when a status update is given up on. */
+export interface StatusSyncFailed {
+    service: StatusService
+    presenting: boolean
+    error: string
+}
+
+export async function getStatusSync(): Promise<StatusSyncSettings> {
+    return invoke<StatusSyncSettings>('get_status_sync')
+}
+
+export async function setStatusSync(config: StatusSyncConfig): Promise<void> {
+    return invoke<void>('set_status_sync', { config })
+}
+
+/** Store a Slack user token or Microsoft Graph token in the keychain; `null` removes it. */
+export async function setStatusToken(service: StatusService, token: string | null): Promise<void> {
+    return invoke<void>('set_status_token', { service, token })
+}
+
+export interface RoomCode {
+    /** Six digits, shown as `123 456`. */
+    code: string
+    /** The session link, for peers on another network. */
+    url: string
+}
+
+export interface JoinedRoom {
+    code: string
+    participantId: string
+}
+
+export interface Peer {
+    /** In a joined room, the host tells each peer its own id only. */
+    id: string | null
+    nickname: string
+    role: ParticipantRole
+    joinedAt: number
+    isYou: boolean
+}
+
+/** Sent as `room-member-left` to the host; `room-member-joined` carries a `Peer`. */
+export interface RoomMemberLeft {
+    peerId: string
+    nickname: string
+}
+
+/** Host a room sharing every open document. */
+export async function createRoom(nickname?: string): Promise<RoomCode> {
+    return invoke<RoomCode>('create_room', { nickname })
+}
+
+/** Find a room by its code on the local network and join it. */
+export async function joinRoom(code: string, nickname: string): Promise<JoinedRoom> {
+    return invoke<JoinedRoom>('join_room', { code, nickname })
+}
+
+export async function leaveRoom(): Promise<boolean> {
+    return invoke<boolean>('leave_room')
+}
+
+export async function listPeers(): Promise<Peer[]> {
+    return invoke<Peer[]>('list_peers')
+}
+
+export type AudioCue = 'viewer-joined' | 'hand-raised' | 'connection-lost'
+
+export interface AudioCueConfig {
+    enabled: boolean
+    /** 0 to 1 per cue; unlisted cues play at 0.4, and 0 turns a cue off. */
+    volumes: Partial<Record<AudioCue, number>>
+    /** Skip cues while a meeting app is using the microphone. */
+    muteDuringMeetings: boolean
+}
+
+export async function getAudioCueConfig(): Promise<AudioCueConfig> {
+    return invoke<AudioCueConfig>('get_audio_cue_config')
+}
+
+export async function setAudioCueConfig(config: AudioCueConfig): Promise<void> {
+    return invoke<void>('set_audio_cue_config', { config })
+}
+
+export async function setAudioCuesMuted(muted: boolean): Promise<void> {
+    return invoke<void>('set_audio_cues_muted', { muted })
+}
+
+/** Play a cue at `volume` (its configured one if omitted), even during a meeting. */
+export async function previewAudioCue(cue: AudioCue, volume?: number): Promise<void> {
+    return invoke<void>('preview_audio_cue', { cue, volume })
+}
+
+/** Sent as `peer-discovered` when an instance appears or changes; `peer-lost` carries `{ id }`. */
+export interface DiscoveredPeer {
+    id: string
+    name: string
+    address: string
+    /** The session server's port, while `hosting`. */
+    port: number
+    hosting: boolean
+}
+
+/** ShareCode instances seen on the local network. */
+export async function discoverPeers(): Promise<DiscoveredPeer[]> {
+    return invoke<DiscoveredPeer[]>('discover_peers')
+}
+
+export async function getLanDiscovery(): Promise<boolean> {
+    return invoke<boolean>('get_lan_discovery')
+}
+
+/** Advertise this instance on the network and look for others; `name` is what others see. */
+export async function setLanDiscovery(enabled: boolean, name?: string): Promise<void> {
+    return invoke<void>('set_lan_discovery', { enabled, name })
+}
+
+/** Whether hotkeys tap the trackpad (macOS Force Touch trackpads only). */
+export async function getHapticFeedback(): Promise<boolean> {
+    return invoke<boolean>('get_haptic_feedback')
+}
+
+export async function setHapticFeedback(enabled: boolean): Promise<void> {
+    return invoke<void>('set_haptic_feedback', { enabled })
+}